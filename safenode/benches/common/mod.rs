@@ -0,0 +1,54 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+// Each bench is a crate of its own, compiling this module along with it, and uses only
+// some of the helpers below.
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use eyre::{eyre, Result};
+use libp2p::PeerId;
+use safenode::{
+    network::Network,
+    protocol::messages::{Cmd, CmdResponse, Query, QueryResponse, Replica, Request, Response},
+    storage::chunks::Chunk,
+};
+use std::time::Duration;
+
+// How long the nodes of a bench are given to listen and to connect to each other.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn random_bytes(size: usize) -> Bytes {
+    let mut bytes = vec![0; size];
+    getrandom::getrandom(&mut bytes).expect("random bytes");
+    Bytes::from(bytes)
+}
+
+pub fn random_chunk(size: usize) -> Chunk {
+    Chunk::new(random_bytes(size))
+}
+
+// Puts the chunk to the peer as a replica, which needs no payment.
+pub async fn put(network: &mut Network, peer: PeerId, chunk: &Chunk) -> Result<()> {
+    let cmd = Cmd::Replicate(Replica::Chunk(chunk.value().clone()));
+    let request = Request::Cmd(network.sign_cmd(cmd)?);
+    match network.send_request(request, peer).await?.response {
+        Response::Cmd(CmdResponse::Ack { .. }) => Ok(()),
+        Response::Cmd(CmdResponse::Replicate(Err(err))) => Err(eyre!(err)),
+        other => Err(eyre!("Unexpected response to a put: {other:?}")),
+    }
+}
+
+pub async fn get(network: &mut Network, peer: PeerId, chunk: &Chunk) -> Result<Chunk> {
+    let request = Request::Query(Query::GetChunk(*chunk.address()));
+    match network.send_request(request, peer).await?.response {
+        Response::Query(QueryResponse::GetChunk(Ok(chunk))) => Ok(chunk),
+        Response::Query(QueryResponse::GetChunk(Err(err))) => Err(eyre!(err)),
+        other => Err(eyre!("Unexpected response to a get: {other:?}")),
+    }
+}
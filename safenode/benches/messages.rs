@@ -0,0 +1,96 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The serialisation of the msgs exchanged between peers, as done by the codec.
+
+mod common;
+
+use common::random_chunk;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use libp2p::identity::Keypair;
+use safenode::{
+    network::{decode_request, decode_response, MsgHeader},
+    protocol::{
+        messages::{Cmd, Query, QueryResponse, Request, Response, SignedCmd, SignedResponse},
+        types::payment::PaymentProof,
+    },
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use xor_name::XorName;
+
+// The sizes of the chunks carried, up to the largest self-encryption produces.
+const CHUNK_SIZES: [usize; 3] = [1024, 64 * 1024, 1024 * 1024];
+
+fn store_chunk_request(chunk: &Chunk, keypair: &Keypair) -> Request {
+    let cmd = Cmd::StoreChunk {
+        address: *chunk.address(),
+        value: chunk.value().clone(),
+        payment: PaymentProof::default(),
+    };
+    Request::Cmd(SignedCmd::new(cmd, keypair).expect("cmd to be signed"))
+}
+
+fn requests(c: &mut Criterion) {
+    let keypair = Keypair::generate_ed25519();
+    let mut group = c.benchmark_group("store_chunk_request");
+    for size in CHUNK_SIZES {
+        let request = (
+            MsgHeader::default(),
+            store_chunk_request(&random_chunk(size), &keypair),
+        );
+        let bytes = rmp_serde::to_vec(&request).expect("request to serialise");
+        group.throughput(Throughput::Bytes(size as u64));
+        let _ = group.bench_with_input(BenchmarkId::new("serialise", size), &request, |b, req| {
+            b.iter(|| rmp_serde::to_vec(req))
+        });
+        let _ =
+            group.bench_with_input(BenchmarkId::new("deserialise", size), &bytes, |b, bytes| {
+                b.iter(|| decode_request(bytes))
+            });
+    }
+    group.finish();
+
+    let request = (
+        MsgHeader::default(),
+        Request::Query(Query::GetChunk(ChunkAddress(XorName::from_content(
+            b"chunk",
+        )))),
+    );
+    let bytes = rmp_serde::to_vec(&request).expect("request to serialise");
+    let _ = c.bench_function("get_chunk_query/serialise", |b| {
+        b.iter(|| rmp_serde::to_vec(&request))
+    });
+    let _ = c.bench_function("get_chunk_query/deserialise", |b| {
+        b.iter(|| decode_request(&bytes))
+    });
+}
+
+fn responses(c: &mut Criterion) {
+    let keypair = Keypair::generate_ed25519();
+    let mut group = c.benchmark_group("get_chunk_response");
+    for size in CHUNK_SIZES {
+        let response = Response::Query(QueryResponse::GetChunk(Ok(random_chunk(size))));
+        let signed = SignedResponse::new(response.clone(), &keypair).expect("response to sign");
+        let bytes = rmp_serde::to_vec(&signed).expect("response to serialise");
+        group.throughput(Throughput::Bytes(size as u64));
+        let _ = group.bench_with_input(BenchmarkId::new("sign", size), &response, |b, resp| {
+            b.iter(|| SignedResponse::new(resp.clone(), &keypair))
+        });
+        let _ = group.bench_with_input(BenchmarkId::new("verify", size), &signed, |b, signed| {
+            b.iter(|| signed.verify())
+        });
+        let _ =
+            group.bench_with_input(BenchmarkId::new("deserialise", size), &bytes, |b, bytes| {
+                b.iter(|| decode_response(bytes))
+            });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, requests, responses);
+criterion_main!(benches);
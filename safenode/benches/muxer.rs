@@ -0,0 +1,128 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The get of a max-size chunk between two nodes running in process, over TCP connections on
+//! the loopback interface multiplexed with yamux, at various receive windows, to tune the
+//! defaults of the `MuxerConfig`.
+//!
+//! The nodes join a private network, the connections of which are the yamux ones.
+
+mod common;
+
+use async_std::task::{block_on, sleep, spawn};
+use common::{get, put, random_chunk, CONNECT_TIMEOUT};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use eyre::{eyre, Result};
+use futures::StreamExt;
+use libp2p::{pnet::PreSharedKey, Multiaddr, PeerId};
+use safenode::{
+    network::{MuxerConfig, Network, NetworkEvent, NetworkSwarmLoop, SwarmConfig},
+    node::Node,
+    storage::{CompressionConfig, DataStorage},
+};
+use std::time::{Duration, Instant};
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+const RECEIVE_WINDOWS: [u32; 4] = [256 * 1024, 1024 * 1024, 2 * 1024 * 1024, 4 * 1024 * 1024];
+
+// Starts a node with the given muxer settings, storing its records in the given dir,
+// returning its `Network` and the address it listens on.
+async fn start_node(dir: &assert_fs::TempDir, muxer: MuxerConfig) -> Result<(Network, Multiaddr)> {
+    let (mut network, mut events, swarm_loop) = NetworkSwarmLoop::with_config(SwarmConfig {
+        network_key: Some(PreSharedKey::new([7; 32])),
+        muxer,
+        ..Default::default()
+    })?;
+    let _handle = spawn(swarm_loop.run());
+    network
+        .start_listening("/ip4/127.0.0.1/tcp/0".parse()?)
+        .await?;
+
+    let storage = DataStorage::new(dir.path(), u64::MAX, CompressionConfig::default(), 0);
+    // The other node is the only peer there is.
+    let node = Node::new(network.clone(), storage).with_min_peers_to_serve(1);
+    let api = network.clone();
+    let _handle = spawn(async move {
+        while let Some(event) = events.next().await {
+            if let NetworkEvent::RequestReceived {
+                peer, req, channel, ..
+            } = event
+            {
+                let node = node.clone();
+                let mut api = api.clone();
+                let _handle = spawn(async move {
+                    let resp = node.handle_request(peer, req).await;
+                    let _ = api.send_response(resp, channel).await;
+                });
+            }
+        }
+    });
+
+    let started = Instant::now();
+    loop {
+        if let Some(addr) = network.listen_addrs().await?.into_iter().next() {
+            return Ok((network, addr));
+        }
+        if started.elapsed() > CONNECT_TIMEOUT {
+            return Err(eyre!("The node is not listening"));
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+// Starts two nodes connected to each other, returning the `Network` of the first along with
+// the peer id of the second.
+async fn start_nodes(
+    dirs: &[assert_fs::TempDir; 2],
+    muxer: MuxerConfig,
+) -> Result<(Network, PeerId, Network)> {
+    let (mut sender, _) = start_node(&dirs[0], muxer).await?;
+    let (mut receiver, receiver_addr) = start_node(&dirs[1], muxer).await?;
+    let receiver_id = receiver.peer_id();
+    sender.dial(receiver_id, receiver_addr).await?;
+
+    let started = Instant::now();
+    while sender.routing_table_size().await? == 0 || receiver.routing_table_size().await? == 0 {
+        if started.elapsed() > CONNECT_TIMEOUT {
+            return Err(eyre!(
+                "The nodes did not add each other to their routing table"
+            ));
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    Ok((sender, receiver_id, receiver))
+}
+
+fn muxer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("muxer");
+    group.sample_size(20);
+    group.throughput(Throughput::Bytes(CHUNK_SIZE as u64));
+    for receive_window in RECEIVE_WINDOWS {
+        let dirs = [
+            assert_fs::TempDir::new().expect("temp dir"),
+            assert_fs::TempDir::new().expect("temp dir"),
+        ];
+        let muxer = MuxerConfig {
+            receive_window,
+            max_buffer_size: 2 * receive_window as usize,
+        };
+        // The receiving node is kept running for the bench of the window.
+        let (mut network, peer, _receiver) =
+            block_on(start_nodes(&dirs, muxer)).expect("nodes to start");
+
+        let chunk = random_chunk(CHUNK_SIZE);
+        block_on(put(&mut network, peer, &chunk)).expect("chunk to be put");
+        let _ = group.bench_function(BenchmarkId::new("get", receive_window), |b| {
+            b.iter(|| block_on(get(&mut network, peer, &chunk)).expect("chunk to be got"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, muxer);
+criterion_main!(benches);
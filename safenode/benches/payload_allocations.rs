@@ -0,0 +1,101 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Counts the bytes allocated on the path a chunk takes through a node, to check its payload
+//! is not copied more than needed: reading it off the wire, fanning it out to the close group
+//! and reading it back from disk.
+
+use bytes::Bytes;
+use libp2p::identity::Keypair;
+use safenode::{
+    network::{decode_request, MsgHeader},
+    protocol::{
+        messages::{Cmd, Request, SignedCmd},
+        types::payment::PaymentProof,
+    },
+    storage::{
+        chunks::{Chunk, ChunkAddress},
+        CompressionConfig, DataStorage,
+    },
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use xor_name::XorName;
+
+const PAYLOAD_SIZE: usize = 1024 * 1024;
+const FAN_OUT: usize = 5;
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Runs `f`, returning its output along with the bytes allocated while running it.
+fn allocated_by<T>(f: impl FnOnce() -> T) -> (T, u64) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let output = f();
+    (output, ALLOCATED.load(Ordering::Relaxed) - before)
+}
+
+fn report(step: &str, allocated: u64) {
+    println!(
+        "{step:<40} {allocated:>10} bytes allocated, {:.2}x the payload",
+        allocated as f64 / PAYLOAD_SIZE as f64
+    );
+}
+
+fn main() -> eyre::Result<()> {
+    let mut value = vec![0; PAYLOAD_SIZE];
+    getrandom::getrandom(&mut value)?;
+    let value = Bytes::from(value);
+    let address = ChunkAddress(XorName::from_content(&value));
+    let cmd = Cmd::StoreChunk {
+        address,
+        value: value.clone(),
+        payment: PaymentProof::default(),
+    };
+    let request = Request::Cmd(SignedCmd::new(cmd, &Keypair::generate_ed25519())?);
+
+    let wire = rmp_serde::to_vec(&(MsgHeader::default(), &request))?;
+    let (decoded, allocated) = allocated_by(|| decode_request(&wire));
+    let _ = decoded?;
+    report("decoding a request off the wire", allocated);
+
+    let (requests, allocated) =
+        allocated_by(|| (0..FAN_OUT).map(|_| request.clone()).collect::<Vec<_>>());
+    drop(requests);
+    report(
+        &format!("fanning a request out to {FAN_OUT} peers"),
+        allocated,
+    );
+
+    let root_dir = assert_fs::TempDir::new()?;
+    // No cache, for every read to go to disk.
+    let storage = DataStorage::new(root_dir.path(), u64::MAX, CompressionConfig::default(), 0);
+    async_std::task::block_on(storage.store(&Chunk::new(value)))?;
+    let (chunk, allocated) = allocated_by(|| async_std::task::block_on(storage.query(&address)));
+    let _ = chunk?;
+    report("reading a chunk back from disk", allocated);
+
+    Ok(())
+}
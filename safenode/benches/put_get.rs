@@ -0,0 +1,124 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The put and get of a chunk end to end, between two nodes running in process and talking
+//! over QUIC on the loopback interface.
+//!
+//! Puts are made as replicas handed from one node to the other, which need no payment.
+
+mod common;
+
+use async_std::task::{block_on, sleep, spawn};
+use common::{get, put, random_chunk, CONNECT_TIMEOUT};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use eyre::{eyre, Result};
+use futures::StreamExt;
+use libp2p::{Multiaddr, PeerId};
+use safenode::{
+    network::{Network, NetworkEvent, NetworkSwarmLoop},
+    node::Node,
+    storage::{CompressionConfig, DataStorage},
+};
+use std::time::{Duration, Instant};
+
+const CHUNK_SIZES: [usize; 2] = [64 * 1024, 1024 * 1024];
+
+// Starts a node storing its records in the given dir, returning its `Network` and the
+// address it listens on.
+async fn start_node(dir: &assert_fs::TempDir) -> Result<(Network, Multiaddr)> {
+    let (mut network, mut events, swarm_loop) = NetworkSwarmLoop::new()?;
+    let _handle = spawn(swarm_loop.run());
+    network
+        .start_listening("/ip4/127.0.0.1/udp/0/quic-v1".parse()?)
+        .await?;
+
+    let storage = DataStorage::new(dir.path(), u64::MAX, CompressionConfig::default(), 0);
+    // The other node is the only peer there is.
+    let node = Node::new(network.clone(), storage).with_min_peers_to_serve(1);
+    let api = network.clone();
+    let _handle = spawn(async move {
+        while let Some(event) = events.next().await {
+            if let NetworkEvent::RequestReceived {
+                peer, req, channel, ..
+            } = event
+            {
+                let node = node.clone();
+                let mut api = api.clone();
+                let _handle = spawn(async move {
+                    let resp = node.handle_request(peer, req).await;
+                    let _ = api.send_response(resp, channel).await;
+                });
+            }
+        }
+    });
+
+    let started = Instant::now();
+    loop {
+        if let Some(addr) = network.listen_addrs().await?.into_iter().next() {
+            return Ok((network, addr));
+        }
+        if started.elapsed() > CONNECT_TIMEOUT {
+            return Err(eyre!("The node is not listening"));
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+// Starts two nodes, returning the `Network` of the first along with the peer id of the
+// second, once they are in the routing table of each other.
+async fn start_nodes(dirs: &[assert_fs::TempDir; 2]) -> Result<(Network, PeerId, Network)> {
+    let (mut sender, _) = start_node(&dirs[0]).await?;
+    let (mut receiver, receiver_addr) = start_node(&dirs[1]).await?;
+    let receiver_id = receiver.peer_id();
+    sender.dial(receiver_id, receiver_addr).await?;
+
+    let started = Instant::now();
+    while sender.routing_table_size().await? == 0 || receiver.routing_table_size().await? == 0 {
+        if started.elapsed() > CONNECT_TIMEOUT {
+            return Err(eyre!(
+                "The nodes did not add each other to their routing table"
+            ));
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    Ok((sender, receiver_id, receiver))
+}
+
+fn put_get(c: &mut Criterion) {
+    let dirs = [
+        assert_fs::TempDir::new().expect("temp dir"),
+        assert_fs::TempDir::new().expect("temp dir"),
+    ];
+    // The receiving node is kept running for the whole bench.
+    let (network, peer, _receiver) = block_on(start_nodes(&dirs)).expect("nodes to start");
+
+    let mut group = c.benchmark_group("put_get");
+    group.sample_size(20);
+    for size in CHUNK_SIZES {
+        group.throughput(Throughput::Bytes(size as u64));
+        let _ = group.bench_function(BenchmarkId::new("put", size), |b| {
+            let mut network = network.clone();
+            b.iter_batched(
+                || random_chunk(size),
+                |chunk| block_on(put(&mut network, peer, &chunk)).expect("chunk to be put"),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+
+        let chunk = random_chunk(size);
+        let mut network = network.clone();
+        block_on(put(&mut network, peer, &chunk)).expect("chunk to be put");
+        let _ = group.bench_function(BenchmarkId::new("get", size), |b| {
+            b.iter(|| block_on(get(&mut network, peer, &chunk)).expect("chunk to be got"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, put_get);
+criterion_main!(benches);
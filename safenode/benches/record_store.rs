@@ -0,0 +1,68 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The reads and writes of the record store on the local disk.
+
+mod common;
+
+use async_std::task::block_on;
+use common::random_chunk;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use safenode::storage::{Compression, CompressionConfig, DataStorage};
+
+const CHUNK_SIZES: [usize; 2] = [64 * 1024, 1024 * 1024];
+const CACHE_CAPACITY: u64 = 64 * 1024 * 1024;
+
+fn storage(dir: &assert_fs::TempDir, algorithm: Compression, cache_capacity: u64) -> DataStorage {
+    let compression = CompressionConfig {
+        algorithm,
+        ..Default::default()
+    };
+    DataStorage::new(dir.path(), u64::MAX, compression, cache_capacity)
+}
+
+fn writes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("record_store_write");
+    for size in CHUNK_SIZES {
+        group.throughput(Throughput::Bytes(size as u64));
+        for algorithm in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            let dir = assert_fs::TempDir::new().expect("temp dir");
+            let storage = storage(&dir, algorithm, 0);
+            let id = BenchmarkId::new(format!("{algorithm:?}"), size);
+            let _ = group.bench_function(id, |b| {
+                // A new chunk each time, for the write not to be of a record already held.
+                b.iter_batched(
+                    || random_chunk(size),
+                    |chunk| block_on(storage.store(&chunk)),
+                    criterion::BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+fn reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("record_store_read");
+    for size in CHUNK_SIZES {
+        group.throughput(Throughput::Bytes(size as u64));
+        for (name, cache_capacity) in [("disk", 0), ("cache", CACHE_CAPACITY)] {
+            let dir = assert_fs::TempDir::new().expect("temp dir");
+            let storage = storage(&dir, Compression::None, cache_capacity);
+            let chunk = random_chunk(size);
+            block_on(storage.store(&chunk)).expect("chunk to be stored");
+            let _ = group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter(|| block_on(storage.query(chunk.address())))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, writes, reads);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The self-encryption of files into chunks, as done by clients before uploading them.
+
+mod common;
+
+use bytes::Bytes;
+use common::random_bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::io::Cursor;
+use safenode::client::{EncryptedFile, StreamingEncryptor};
+
+const FILE_SIZES: [usize; 3] = [64 * 1024, 1024 * 1024, 10 * 1024 * 1024];
+
+// Encrypts the file a window at a time, as uploaded by the CLI.
+fn encrypt_streaming(bytes: &Bytes) {
+    async_std::task::block_on(async {
+        let mut encryptor = StreamingEncryptor::new(Cursor::new(bytes.as_ref()));
+        while encryptor
+            .next_chunks()
+            .await
+            .expect("window to encrypt")
+            .is_some()
+        {}
+        let _ = encryptor.finish().expect("data map");
+    });
+}
+
+fn encrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("self_encryption");
+    group.sample_size(10);
+    for size in FILE_SIZES {
+        let bytes = random_bytes(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        let _ = group.bench_with_input(BenchmarkId::new("encrypt", size), &bytes, |b, bytes| {
+            b.iter(|| EncryptedFile::new(bytes.clone()).expect("bytes to encrypt"))
+        });
+        let _ = group.bench_with_input(
+            BenchmarkId::new("encrypt_streaming", size),
+            &bytes,
+            |b, bytes| b.iter(|| encrypt_streaming(bytes)),
+        );
+        let (data_map, chunks) = self_encryption::encrypt(bytes).expect("bytes to encrypt");
+        let _ = group.bench_with_input(BenchmarkId::new("decrypt", size), &chunks, |b, chunks| {
+            b.iter(|| self_encryption::decrypt_full_set(&data_map, chunks))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encrypt);
+criterion_main!(benches);
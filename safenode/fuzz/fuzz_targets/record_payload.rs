@@ -0,0 +1,49 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Feeds arbitrary bytes to the decoding of the records nodes store, as carried in requests
+//! and read back from disk, and to their validation.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libp2p::identity::Keypair;
+use safenode::{
+    protocol::types::register::{Policy, PolicyOp, Register, RegisterOp},
+    storage::chunks::Chunk,
+};
+use sn_dbc::SignedSpend;
+
+fuzz_target!(|data: &[u8]| {
+    // A chunk is addressed by the hash of its content, whatever the content.
+    let _ = Chunk::new(data.to_vec().into()).address();
+
+    if let Ok(op) = rmp_serde::from_slice::<RegisterOp>(data) {
+        // Any key is allowed to write, for the op to get past the check of the policy.
+        let owner = Keypair::generate_ed25519();
+        let policy = Policy::new(&owner.public()).sign(op.address, &owner);
+        if let (Ok(signer), Ok(policy)) = (op.signer(), policy) {
+            let policy = policy.policy.with_writers([&signer]).sign(op.address, &owner);
+            if let Ok(mut register) = policy.and_then(|policy| Register::new(op.address, policy)) {
+                let _ = register.apply_op(op);
+                let _ = register.read();
+            }
+        }
+    }
+    if let Ok(op) = rmp_serde::from_slice::<PolicyOp>(data) {
+        let _ = Register::new(op.address, op.clone());
+    }
+    if let Ok(register) = rmp_serde::from_slice::<Register>(data) {
+        let _ = register.read();
+        let _ = register.size();
+        let _ = register.verified();
+    }
+    if let Ok(signed_spend) = rmp_serde::from_slice::<SignedSpend>(data) {
+        let _ = signed_spend.verify(signed_spend.spent_tx_hash());
+    }
+});
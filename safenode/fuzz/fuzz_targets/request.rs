@@ -0,0 +1,22 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Feeds arbitrary bytes to the decoding of the requests nodes read off the wire, and to
+//! the parts of handling a request that do not need a running node.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use safenode::network::decode_request;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((header, request)) = decode_request(data) {
+        let _ = request.dst();
+        let _ = format!("{header:?} {request:?}");
+    }
+});
@@ -0,0 +1,202 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Dispenses the tokens of a testnet, from a wallet credited with its genesis Dbc, for developers
+//! to pay for the storage of their data with.
+//!
+//! `GET /<address>` sends tokens to the hex encoded wallet address, as printed by
+//! `safe wallet address`, and responds with the transfer to be received with
+//! `safe wallet receive`. Each address is dispensed to at most once per interval.
+
+use async_std::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    task::spawn,
+};
+use clap::Parser;
+use eyre::{eyre, Result};
+use futures::StreamExt;
+use libp2p::Multiaddr;
+use safenode::{
+    client::{Client, Wallet, CONNECT_TIMEOUT},
+    http::{read_head, respond},
+};
+use sn_dbc::{PublicAddress, Token};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+/// The wallet dir used by default, relative to the home dir.
+const DEFAULT_WALLET_DIR: &str = ".safe/faucet/wallet";
+
+#[derive(Parser, Debug)]
+#[clap(name = "faucet", version, about = "Dispense the tokens of a testnet")]
+struct Opt {
+    /// The address to serve requests for tokens on.
+    #[clap(long, default_value = "127.0.0.1:8000")]
+    listen: SocketAddr,
+
+    /// The address of a peer to bootstrap from, ending with its `/p2p/<peer id>`.
+    /// Can be given multiple times. Peers on the local network are found without it.
+    #[clap(long = "peer")]
+    bootstrap_peers: Vec<Multiaddr>,
+
+    /// The wallet to dispense tokens from, instead of `~/.safe/faucet/wallet`.
+    #[clap(long)]
+    wallet_dir: Option<PathBuf>,
+
+    /// Spend a genesis Dbc of this amount into the wallet, unless it already holds tokens.
+    #[clap(long)]
+    mint_genesis: Option<String>,
+
+    /// The amount of tokens dispensed per request.
+    #[clap(long, default_value = "10")]
+    amount: String,
+
+    /// The min number of seconds between two requests for the same address.
+    #[clap(long, default_value_t = 3600)]
+    interval_secs: u64,
+}
+
+// The state shared by the handlers of the requests.
+struct Faucet {
+    wallet: Mutex<Wallet>,
+    amount: Token,
+    interval: Duration,
+    // When each address, hex encoded, was last dispensed to.
+    dispensed: Mutex<HashMap<String, Instant>>,
+}
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    let opt = Opt::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let amount = parse_token(&opt.amount)?;
+    let client = Client::connect(&opt.bootstrap_peers, CONNECT_TIMEOUT).await?;
+    let wallet_dir = match opt.wallet_dir {
+        Some(dir) => dir,
+        None => dirs_next::home_dir()
+            .map(|home| home.join(DEFAULT_WALLET_DIR))
+            .ok_or_else(|| eyre!("Could not find the home dir"))?,
+    };
+    let mut wallet = Wallet::load_from(client, &wallet_dir).await?;
+    if let Some(genesis_amount) = &opt.mint_genesis {
+        wallet.mint_genesis(parse_token(genesis_amount)?).await?;
+    }
+    info!(
+        "Faucet holding {} tokens, dispensing {amount} per request",
+        wallet.balance()?
+    );
+
+    let faucet = Arc::new(Faucet {
+        wallet: Mutex::new(wallet),
+        amount,
+        interval: Duration::from_secs(opt.interval_secs),
+        dispensed: Mutex::new(HashMap::new()),
+    });
+
+    let listener = TcpListener::bind(opt.listen).await?;
+    info!("Faucet listening on {}", opt.listen);
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        match stream {
+            Ok(stream) => {
+                let faucet = Arc::clone(&faucet);
+                let _handle = spawn(async move {
+                    if let Err(err) = handle_connection(stream, &faucet).await {
+                        warn!("Failed to handle faucet request: {err}");
+                    }
+                });
+            }
+            Err(err) => warn!("Failed to accept faucet connection: {err}"),
+        }
+    }
+    Ok(())
+}
+
+async fn handle_connection(stream: TcpStream, faucet: &Faucet) -> Result<()> {
+    let head = read_head(&mut BufReader::new(&stream)).await?;
+    let hex_address = head.path().trim_start_matches('/').to_lowercase();
+    let (status, body) = match decode::<PublicAddress>(&hex_address) {
+        Ok(address) => faucet.dispense(hex_address, address).await,
+        Err(err) => ("400 Bad Request", format!("Invalid address: {err}")),
+    };
+    let headers = [("Content-Type", "text/plain")];
+    Ok(respond(&mut &stream, status, &headers, body.as_bytes()).await?)
+}
+
+impl Faucet {
+    // Sends tokens to the address unless it was dispensed to within the interval, returning
+    // the status and body of the response.
+    async fn dispense(
+        &self,
+        hex_address: String,
+        address: PublicAddress,
+    ) -> (&'static str, String) {
+        {
+            let mut dispensed = self.dispensed.lock().await;
+            // The addresses free to be dispensed to again are forgotten, for the map not to
+            // grow with every address ever seen.
+            dispensed.retain(|_, last| last.elapsed() < self.interval);
+            if let Some(last) = dispensed.get(&hex_address) {
+                let wait = self.interval.saturating_sub(last.elapsed());
+                if !wait.is_zero() {
+                    return (
+                        "429 Too Many Requests",
+                        format!("Retry in {}s", wait.as_secs()),
+                    );
+                }
+            }
+            // Recorded ahead of the transfer, for concurrent requests not to get through.
+            let _ = dispensed.insert(hex_address.clone(), Instant::now());
+        }
+
+        match self.transfer_to(address).await {
+            Ok(transfer) => {
+                info!("Dispensed {} to {address:?}", self.amount);
+                ("200 OK", transfer)
+            }
+            Err(err) => {
+                warn!("Failed to dispense to {address:?}: {err}");
+                let _ = self.dispensed.lock().await.remove(&hex_address);
+                ("500 Internal Server Error", err.to_string())
+            }
+        }
+    }
+
+    // Sends the amount to the address, returning the encoded transfer.
+    async fn transfer_to(&self, address: PublicAddress) -> Result<String> {
+        let dbc = self.wallet.lock().await.send(self.amount, address).await?;
+        encode(&dbc)
+    }
+}
+
+fn parse_token(amount: &str) -> Result<Token> {
+    Token::from_str(amount).map_err(|err| eyre!("Invalid amount {amount}: {err}"))
+}
+
+// Addresses and transfers are hex encoded as with the `safe` CLI.
+fn encode(value: &impl serde::Serialize) -> Result<String> {
+    Ok(hex::encode(rmp_serde::to_vec(value)?))
+}
+
+fn decode<T: serde::de::DeserializeOwned>(hex_value: &str) -> Result<T> {
+    let bytes = hex::decode(hex_value).map_err(|err| eyre!("Invalid hex: {err}"))?;
+    Ok(rmp_serde::from_slice(&bytes)?)
+}
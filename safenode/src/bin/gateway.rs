@@ -0,0 +1,344 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Serves the public content of the network over HTTP, for ordinary browsers to read it.
+//!
+//! `GET /safe/<address>` fetches the file uploaded at the hex encoded address, as printed by
+//! `safe files upload`, and streams it back as its chunks are decrypted. The content type is
+//! told from the extension of a file name following the address, as in
+//! `/safe/<address>/photo.jpg`, or else sniffed from the first bytes of the file.
+//!
+//! Anyone can upload content, so all of it is served in a sandbox of its own: pages are given
+//! a unique origin and run no scripts, nor can they reach the cookies or storage of the
+//! gateway, and browsers are told not to sniff the content type for themselves.
+
+use async_std::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    task::spawn,
+};
+use bytes::Bytes;
+use clap::Parser;
+use eyre::{eyre, Result};
+use futures::{AsyncWriteExt, StreamExt};
+use libp2p::Multiaddr;
+use safenode::{
+    client::{Client, Error as ClientError, CONNECT_TIMEOUT},
+    http::{self, read_head, write_head},
+    storage::chunks::ChunkAddress,
+};
+use std::net::SocketAddr;
+use tracing::{info, trace, warn};
+use tracing_subscriber::EnvFilter;
+use xor_name::XorName;
+
+/// The path the content of the network is served under.
+const SAFE_PATH: &str = "/safe/";
+/// The content type of the files whose type could not be told.
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+/// The number of bytes at the start of a file checked for being text.
+const TEXT_SAMPLE_LEN: usize = 512;
+/// The headers sent with every response, keeping the content from running as the gateway.
+const SECURITY_HEADERS: [(&str, &str); 2] = [
+    ("Content-Security-Policy", "sandbox"),
+    ("X-Content-Type-Options", "nosniff"),
+];
+
+#[derive(Parser, Debug)]
+#[clap(
+    name = "gateway",
+    version,
+    about = "Serve the public content of the SAFE Network over HTTP"
+)]
+struct Opt {
+    /// The address to serve the content on.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// The address of a peer to bootstrap from, ending with its `/p2p/<peer id>`.
+    /// Can be given multiple times. Peers on the local network are found without it.
+    #[clap(long = "peer")]
+    bootstrap_peers: Vec<Multiaddr>,
+
+    /// The max number of chunks fetched ahead of the ones sent to the browser.
+    #[clap(long, default_value_t = 4)]
+    concurrency: usize,
+}
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    let opt = Opt::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let client = Client::connect(&opt.bootstrap_peers, CONNECT_TIMEOUT).await?;
+    let listener = TcpListener::bind(opt.listen).await?;
+    info!("Gateway listening on {}", opt.listen);
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        match stream {
+            Ok(stream) => {
+                let client = client.clone();
+                let concurrency = opt.concurrency;
+                let _handle = spawn(async move {
+                    if let Err(err) = handle_connection(stream, &client, concurrency).await {
+                        warn!("Failed to handle gateway request: {err}");
+                    }
+                });
+            }
+            Err(err) => warn!("Failed to accept gateway connection: {err}"),
+        }
+    }
+    Ok(())
+}
+
+async fn handle_connection(stream: TcpStream, client: &Client, concurrency: usize) -> Result<()> {
+    let head = read_head(&mut BufReader::new(&stream)).await?;
+    let method = head.method.as_str();
+    let path = head.target.as_str();
+    trace!("Gateway request received: {method} {path}");
+
+    if method != "GET" && method != "HEAD" {
+        return respond(&stream, "405 Method Not Allowed", "Only GET is supported").await;
+    }
+    let (address, file_name) = match parse_path(path) {
+        Some(parsed) => parsed,
+        None => return respond(&stream, "404 Not Found", "Expected /safe/<address>").await,
+    };
+
+    let mut content = Box::pin(client.read_bytes_stream_with(address, concurrency));
+    // The first bytes are awaited before the headers are sent, for a missing file to be
+    // answered with a status telling so, and for its content type to be sniffed.
+    let first = match content.next().await {
+        Some(Ok(bytes)) => bytes,
+        None => Bytes::new(),
+        Some(Err(err)) => {
+            warn!("Failed to read {address:?}: {err}");
+            let status = match err {
+                ClientError::NotFound(_) | ClientError::NoPeersFound(_) => "404 Not Found",
+                _ => "502 Bad Gateway",
+            };
+            return respond(&stream, status, &err.to_string()).await;
+        }
+    };
+    let content_type = file_name
+        .and_then(content_type_of_name)
+        .unwrap_or_else(|| sniff_content_type(&first));
+
+    let mut headers = vec![
+        ("Content-Type", content_type),
+        ("Transfer-Encoding", "chunked"),
+        ("Connection", "close"),
+    ];
+    headers.extend(SECURITY_HEADERS);
+    write_head(&mut &stream, "200 OK", &headers).await?;
+    if method == "HEAD" {
+        return Ok((&stream).flush().await?);
+    }
+
+    write_chunk(&stream, &first).await?;
+    while let Some(bytes) = content.next().await {
+        match bytes {
+            Ok(bytes) => write_chunk(&stream, &bytes).await?,
+            // The status is sent already, closing the connection without the last chunk
+            // tells the browser the content is incomplete.
+            Err(err) => return Err(eyre!("Failed to read {address:?} past its start: {err}")),
+        }
+    }
+    (&stream).write_all(b"0\r\n\r\n").await?;
+    Ok((&stream).flush().await?)
+}
+
+// Writes the bytes as a chunk of a response of chunked transfer encoding.
+async fn write_chunk(stream: &TcpStream, bytes: &[u8]) -> Result<()> {
+    if bytes.is_empty() {
+        // An empty chunk would end the response.
+        return Ok(());
+    }
+    let mut writer = stream;
+    writer
+        .write_all(format!("{:x}\r\n", bytes.len()).as_bytes())
+        .await?;
+    writer.write_all(bytes).await?;
+    writer.write_all(b"\r\n").await?;
+    Ok(())
+}
+
+async fn respond(stream: &TcpStream, status: &str, body: &str) -> Result<()> {
+    let mut headers = vec![("Content-Type", "text/plain")];
+    headers.extend(SECURITY_HEADERS);
+    let mut writer = stream;
+    Ok(http::respond(&mut writer, status, &headers, body.as_bytes()).await?)
+}
+
+// Parses `/safe/<address>[/<file name>]`, ignoring any query string.
+fn parse_path(path: &str) -> Option<(ChunkAddress, Option<&str>)> {
+    let path = path.split('?').next().unwrap_or_default();
+    let rest = path.strip_prefix(SAFE_PATH)?;
+    let mut segments = rest.splitn(2, '/');
+    let bytes = hex::decode(segments.next()?).ok()?;
+    let name: [u8; 32] = bytes.try_into().ok()?;
+    let file_name = segments.next().filter(|name| !name.is_empty());
+    Some((ChunkAddress(XorName(name)), file_name))
+}
+
+// The content type of the usual file types browsers display, from the extension of the name.
+fn content_type_of_name(file_name: &str) -> Option<&'static str> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    let content_type = match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+// The content type of the file, as told by the magic numbers of the usual binary types,
+// text being served as plain text for it never to be run as a page.
+fn sniff_content_type(start: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"\x1a\x45\xdf\xa3", "video/webm"),
+        (b"\x00asm", "application/wasm"),
+        (b"wOFF", "font/woff"),
+        (b"wOF2", "font/woff2"),
+    ];
+    if let Some((_, content_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| start.starts_with(signature))
+    {
+        return content_type;
+    }
+    if start.len() >= 12 && &start[0..4] == b"RIFF" && &start[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+    if start.len() >= 8 && &start[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+    let sample = &start[..start.len().min(TEXT_SAMPLE_LEN)];
+    let is_text = match std::str::from_utf8(sample) {
+        Ok(text) => !text.is_empty(),
+        // The sample may cut the last char short, of up to 4 bytes.
+        Err(err) => {
+            sample.len() == TEXT_SAMPLE_LEN
+                && err.error_len().is_none()
+                && err.valid_up_to() + 3 >= sample.len()
+        }
+    };
+    if is_text {
+        return "text/plain; charset=utf-8";
+    }
+    DEFAULT_CONTENT_TYPE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_parsed_into_an_address_and_a_file_name() {
+        let name = XorName([7; 32]);
+        let hex_name = hex::encode(name);
+
+        let path = format!("/safe/{hex_name}");
+        assert_eq!(parse_path(&path), Some((ChunkAddress(name), None)));
+        let path = format!("/safe/{hex_name}/");
+        assert_eq!(parse_path(&path), Some((ChunkAddress(name), None)));
+        let path = format!("/safe/{hex_name}/index.html?v=1");
+        assert_eq!(
+            parse_path(&path),
+            Some((ChunkAddress(name), Some("index.html")))
+        );
+
+        assert_eq!(parse_path(&format!("/other/{hex_name}")), None);
+        assert_eq!(parse_path(&format!("/safe/{}", &hex_name[2..])), None);
+        assert_eq!(parse_path("/safe/not-hex"), None);
+    }
+
+    #[test]
+    fn content_types_are_told_by_the_extension_regardless_of_case() {
+        assert_eq!(
+            content_type_of_name("index.HTML"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(
+            content_type_of_name("photo.tar.gz"),
+            Some("application/gzip")
+        );
+        assert_eq!(content_type_of_name("archive.unknown"), None);
+        assert_eq!(content_type_of_name("README"), None);
+    }
+
+    #[test]
+    fn content_types_are_sniffed_from_the_start_of_the_content() {
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n...."), "image/png");
+        assert_eq!(sniff_content_type(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_content_type(b"\0\0\0\x20ftypisom"), "video/mp4");
+        assert_eq!(
+            sniff_content_type(b"<html><script>"),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            sniff_content_type(b"\xff\xfe\x00binary"),
+            DEFAULT_CONTENT_TYPE
+        );
+        assert_eq!(sniff_content_type(b""), DEFAULT_CONTENT_TYPE);
+    }
+
+    #[test]
+    fn text_cut_short_by_the_sample_is_still_text() {
+        // A 3 bytes char straddling the end of the sample.
+        let mut text = "a".repeat(TEXT_SAMPLE_LEN - 1);
+        text.push_str("\u{20ac} and more");
+        assert_eq!(
+            sniff_content_type(text.as_bytes()),
+            "text/plain; charset=utf-8"
+        );
+
+        // Content ending with a char cut short is not text.
+        let truncated = &text.as_bytes()[..TEXT_SAMPLE_LEN - 10];
+        let mut content = truncated.to_vec();
+        content.extend_from_slice(&"\u{20ac}".as_bytes()[..2]);
+        assert_eq!(sniff_content_type(&content), DEFAULT_CONTENT_TYPE);
+
+        // Invalid bytes before the end of the sample make it binary.
+        let mut content = "a".repeat(TEXT_SAMPLE_LEN - 2).into_bytes();
+        content.extend_from_slice(b"\xff\xfe and more");
+        assert_eq!(sniff_content_type(&content), DEFAULT_CONTENT_TYPE);
+    }
+}
@@ -11,58 +11,224 @@ use async_std::task::spawn;
 use bytes::Bytes;
 use clap::Parser;
 use eyre::{eyre, Result};
-use futures::{channel::oneshot, prelude::*, StreamExt};
-use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use futures::{
+    channel::{mpsc, oneshot},
+    prelude::*,
+    StreamExt,
+};
+use libp2p::{identity::PublicKey, multiaddr::Protocol, Multiaddr, PeerId};
 use safenode::{
-    log::init_node_logging,
-    network::{Network, NetworkEvent, NetworkSwarmLoop, Request, Response},
+    log::{init_node_logging, LogFormat, LogReopener},
+    network::{fetch_contacts, Network, NetworkEvent, NetworkSwarmLoop},
+    node::{NetworkPreset, Node, NodeConfig, NodeCtrl, NodeRootDir},
+    protocol::messages::{Query, QueryResponse, Request, Response},
     storage::{
         chunks::{Chunk, ChunkAddress},
-        DataStorage,
+        Compression, DataStorage,
     },
 };
-use std::{fs, path::PathBuf};
+use std::{
+    collections::HashSet,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::Command,
+};
 use std::{thread, time};
-use tracing::{info, warn};
+use tracing::{debug, info, info_span, warn, Instrument, Span};
 use walkdir::WalkDir;
 use xor_name::XorName;
 
+// The files the node leaves in its root dir.
+const PEER_ID_FILENAME: &str = "peer_id";
+const PID_FILENAME: &str = "safenode.pid";
+const NETWORK_CONTACTS_FILENAME: &str = "network_contacts";
+// How long the node must have been without any connection for it to bootstrap again once
+// reconnected, its routing table being likely stale by then.
+const REBOOTSTRAP_AFTER_DISCONNECTION: time::Duration = time::Duration::from_secs(60);
+
 #[async_std::main]
 async fn main() -> Result<()> {
     let opt = Opt::parse();
-    let _log_appender_guard = init_node_logging(&opt.log_dir)?;
-
-    let (mut network_api, mut network_events, network_event_loop) = NetworkSwarmLoop::new()?;
+    let mut config = node_config(&opt)?;
+    if opt.dump_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+    // All the state of the node is kept in a temporary dir, removed on exit, unless a root
+    // dir is configured, in which case the logs also go there unless configured otherwise.
     let temp_dir = TempDir::new()?;
-    let storage = DataStorage::new(&temp_dir);
+    let root_dir = NodeRootDir::new(
+        config
+            .root_dir
+            .clone()
+            .unwrap_or_else(|| temp_dir.to_path_buf()),
+    );
+    if config.root_dir.is_some() && config.log.dir.is_none() {
+        config.log.dir = Some(root_dir.log_dir());
+    }
+    let (_log_appender_guard, log_reopener) =
+        init_node_logging(&config.log.dir, config.log.format, &config.log.rotation)?;
+    root_dir.init()?;
+
+    let mut swarm_config = config.network.swarm_config()?;
+    swarm_config.keypair = Some(root_dir.load_or_create_keypair()?);
+    let (mut network_api, mut network_events, network_event_loop) =
+        NetworkSwarmLoop::with_config(swarm_config)?;
+    let storage = DataStorage::new(
+        &root_dir.record_store_dir(),
+        config.max_capacity,
+        config.compression_config(),
+        config.cache_capacity,
+    );
+    if config.root_dir.is_some() {
+        write_node_files(root_dir.path(), &network_api.peer_id())?;
+    }
+
+    // The fields of the node span are attached to all the logs of the node.
+    let node_span = info_span!("node", peer_id = %network_api.peer_id());
 
     // Spawn the network task for it to run in the background.
-    spawn(network_event_loop.run());
+    spawn(network_event_loop.run().instrument(node_span.clone()));
+
+    for addr in config.listen_addrs.iter().cloned() {
+        network_api.start_listening(addr).await?;
+    }
+    for addr in config.external_addrs.iter().cloned() {
+        network_api.add_external_addr(addr).await?;
+    }
+    // The contact of the first node is only written once it listens, for the nodes
+    // bootstrapping from it not to dial it too early.
+    if let Some(addr) = opt.first {
+        let contact = quic_multiaddr(addr).with(Protocol::P2p(network_api.peer_id().into()));
+        info!("Starting as the first node of the network, reached at {contact}");
+        fs::write(
+            root_dir.path().join(NETWORK_CONTACTS_FILENAME),
+            contact.to_string(),
+        )?;
+    }
+
+    network_api
+        .set_record_validator(Node::record_validator())
+        .await?;
+
+    let api_clone = network_api.clone();
+    let mut node = Node::new(network_api.clone(), storage.clone())
+        .with_query_cache_ttl(config.query_cache_ttl());
+    if config.relay_cache_capacity > 0 {
+        node = node.with_relay_cache(config.relay_cache_capacity, config.relay_cache_ttl());
+    }
+    if let Some(policy) = config.royalty_policy()? {
+        node = node.with_royalties(policy);
+    }
+    if let Some(cost) = config.store_cost()? {
+        info!(
+            "Asking {} nanos for the storage of a record, paid at {:?}",
+            cost.cost_nanos, cost.address
+        );
+        node = node.with_store_cost(cost);
+    }
+    node = node.with_min_peers_to_serve(config.min_peers_to_serve);
+
+    // The counts of the puts are kept in the root dir, to carry on from them after restarts.
+    node.load_stats(root_dir.path()).await?;
+    spawn(
+        node.clone()
+            .persist_stats(root_dir.path().to_path_buf())
+            .instrument(node_span.clone()),
+    );
 
-    let mut api_clone = network_api.clone();
-    let storage_clone = storage.clone();
+    let (ctrl_tx, mut ctrl_rx) = mpsc::channel(5);
+    start_rpc(
+        opt.rpc,
+        &config.log.dir,
+        log_reopener,
+        node.clone(),
+        ctrl_tx,
+    );
+    start_health(opt.health, opt.health_min_peers, node.clone()).await?;
+    // Records corrupted on disk are looked for in the background.
+    spawn(node.clone().scrub_records().instrument(node_span.clone()));
+    // The peers of the close groups of the chunks held are challenged to prove they hold them too.
+    spawn(
+        node.clone()
+            .audit_neighbours()
+            .instrument(node_span.clone()),
+    );
+    // Replicas of registers edited concurrently on different nodes are brought back in sync.
+    spawn(node.clone().sync_registers().instrument(node_span.clone()));
+
+    // Kept to hand off the records when the node is stopped.
+    let stopping_node = node.clone();
     let (peer_dicovered_send, peer_dicovered_rx) = oneshot::channel();
     let mut peer_dicovered_send = Some(peer_dicovered_send);
-    spawn(async move {
+    let mut connected_peers = HashSet::new();
+    let mut disconnected_since = None;
+    let handle_events = async move {
         loop {
             let event = match network_events.next().await {
                 Some(event) => event,
                 None => continue,
             };
             match event {
-                NetworkEvent::RequestReceived { req, channel } => {
-                    // Reply with the content of the file on incoming requests.
-                    if let Request::GetChunk(xor_name) = req {
-                        let addr = ChunkAddress(xor_name);
-                        let chunk = storage_clone.query(&addr).await.unwrap();
-                        if let Err(err) = api_clone
-                            .send_response(Response::Chunk(chunk), channel)
-                            .await
-                        {
-                            warn!("Error while sending response: {err:?}");
+                NetworkEvent::RequestReceived {
+                    peer,
+                    request_id,
+                    header,
+                    req,
+                    channel,
+                } => {
+                    let record_key = req.dst().map(|name| format!("{name:x}"));
+                    let request_span = info_span!(
+                        "request",
+                        msg_id = %request_id,
+                        record_key = %record_key.unwrap_or_default(),
+                    );
+                    // Traces the handling of the request as part of the sender's trace.
+                    header.set_parent_of(&request_span);
+                    // Handling a request may involve querying other peers, so it's done in its
+                    // own task to keep the network events flowing in the meantime.
+                    let node = node.clone();
+                    let mut api_clone = api_clone.clone();
+                    spawn(
+                        async move {
+                            let resp = node.handle_request(peer, req).await;
+                            if let Err(err) = api_clone.send_response(resp, channel).await {
+                                warn!("Error while sending response: {err:?}");
+                            }
+                        }
+                        .instrument(request_span),
+                    );
+                }
+                NetworkEvent::EventReceived { peer, event } => {
+                    info!("Received event from {peer:?}: {event:?}");
+                }
+                NetworkEvent::IncompatiblePeer {
+                    peer_id,
+                    protocol_version,
+                } => {
+                    warn!("Peer {peer_id:?} runs incompatible protocol version {protocol_version}");
+                }
+                NetworkEvent::PeerConnected { peer_id, endpoint } => {
+                    debug!("Connected to {peer_id:?} at {endpoint:?}");
+                    let _ = connected_peers.insert(peer_id);
+                    // Peers joining our close group are told of the records they should hold.
+                    spawn(node.clone().replicate_to(peer_id).in_current_span());
+                    if let Some(since) = disconnected_since.take() {
+                        let disconnected_for = time::Instant::now().duration_since(since);
+                        if disconnected_for >= REBOOTSTRAP_AFTER_DISCONNECTION {
+                            info!("Reconnected after {disconnected_for:?}, bootstrapping again");
+                            spawn(bootstrap(api_clone.clone()).in_current_span());
                         }
                     }
                 }
+                NetworkEvent::PeerDisconnected { peer_id, endpoint } => {
+                    debug!("Disconnected from {peer_id:?} at {endpoint:?}");
+                    let _ = connected_peers.remove(&peer_id);
+                    if connected_peers.is_empty() {
+                        disconnected_since = Some(time::Instant::now());
+                    }
+                }
                 NetworkEvent::PeerDiscovered => {
                     if let Some(sender) = peer_dicovered_send.take() {
                         if let Err(err) = sender.send(()) {
@@ -72,7 +238,24 @@ async fn main() -> Result<()> {
                 }
             }
         }
-    });
+    };
+    if let Some((source, public_key)) = config.network.contacts()? {
+        let refresh = config.network.contacts_refresh();
+        spawn(
+            dial_contacts(network_api.clone(), source, public_key, refresh)
+                .instrument(node_span.clone()),
+        );
+    }
+    if config.network.upnp {
+        map_ports(network_api.clone(), &node_span);
+    }
+    spawn(handle_events.instrument(node_span.clone()));
+
+    for addr in config.bootstrap_peers.iter() {
+        if let Err(err) = bootstrap_node(&mut network_api, addr.clone()).await {
+            warn!("Failed to bootstrap from {addr}: {err}");
+        }
+    }
 
     // wait until we discover atleast one peer
     peer_dicovered_rx.await?;
@@ -82,6 +265,8 @@ async fn main() -> Result<()> {
     // there is no way to confirm it since `RoutingUpdate` is private/no debug impl.
     // Hence sleep for sometime before querying the network
     thread::sleep(time::Duration::from_millis(100));
+    // Fills the routing table in rather than waiting for it to be filled as peers are met.
+    spawn(bootstrap(network_api.clone()).instrument(node_span));
 
     if let Some(files_path) = opt.upload_chunks {
         for entry in WalkDir::new(files_path).into_iter().flatten() {
@@ -117,7 +302,10 @@ async fn main() -> Result<()> {
             let mut network_api = network_api.clone();
             async move {
                 network_api
-                    .send_request(Request::GetChunk(xor_name), peer)
+                    .send_request(
+                        Request::Query(Query::GetChunk(ChunkAddress(xor_name))),
+                        peer,
+                    )
                     .await
             }
             .boxed()
@@ -127,40 +315,444 @@ async fn main() -> Result<()> {
             .await
             .map_err(|_| eyre!("None of the providers returned file."))?
             .0;
-        if let Response::Chunk(chunk) = resp {
+        resp.verify()?;
+        if let Response::Query(QueryResponse::GetChunk(Ok(chunk))) = resp.response {
             info!("got chunk {:x}", chunk.name());
         }
     }
 
-    // Keep the node running
+    // Keep the node running until told otherwise
     loop {
-        thread::sleep(time::Duration::from_millis(100));
+        match ctrl_rx.next().await {
+            Some(NodeCtrl::Stop { delay }) => {
+                info!("Node stopping in {delay:?}");
+                let started = time::Instant::now();
+                // The records held are pushed to their close group first, for them not to be
+                // under-replicated once we are gone.
+                let budget = config.handoff_budget();
+                if !budget.is_zero() {
+                    let _ = stopping_node.hand_off_records(budget).await;
+                }
+                async_std::task::sleep(delay.saturating_sub(started.elapsed())).await;
+                return Ok(());
+            }
+            Some(NodeCtrl::Restart { delay }) => {
+                info!("Node restarting in {delay:?}");
+                async_std::task::sleep(delay).await;
+                start_new_node_process()?;
+                return Ok(());
+            }
+            // Nothing controls the node, it keeps running.
+            None => future::pending::<()>().await,
+        }
+    }
+}
+
+// Starts the RPC service if an address to serve it on was given.
+#[cfg(feature = "rpc")]
+fn start_rpc(
+    addr: Option<SocketAddr>,
+    log_dir: &Option<PathBuf>,
+    log_reopener: LogReopener,
+    node: Node,
+    ctrl_tx: mpsc::Sender<NodeCtrl>,
+) {
+    if let Some(addr) = addr {
+        let log_dir = log_dir
+            .as_ref()
+            .map_or_else(|| "stdout".to_string(), |dir| dir.display().to_string());
+        safenode::node::start_rpc_service(addr, log_dir, log_reopener, node, ctrl_tx);
     }
 }
 
+#[cfg(not(feature = "rpc"))]
+fn start_rpc(
+    addr: Option<SocketAddr>,
+    _log_dir: &Option<PathBuf>,
+    _log_reopener: LogReopener,
+    _node: Node,
+    _ctrl_tx: mpsc::Sender<NodeCtrl>,
+) {
+    if addr.is_some() {
+        warn!("safenode was built without the `rpc` feature, not starting the RPC service");
+    }
+}
+
+// Starts the health service if an address to serve it on was given.
+#[cfg(feature = "health")]
+async fn start_health(
+    addr: Option<SocketAddr>,
+    min_peers: Option<usize>,
+    node: Node,
+) -> Result<()> {
+    if let Some(addr) = addr {
+        let min_peers = min_peers.unwrap_or(safenode::node::DEFAULT_READY_MIN_PEERS);
+        safenode::node::start_health_service(addr, node, min_peers).await?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "health"))]
+async fn start_health(
+    addr: Option<SocketAddr>,
+    _min_peers: Option<usize>,
+    _node: Node,
+) -> Result<()> {
+    if addr.is_some() {
+        warn!("safenode was built without the `health` feature, not starting the health service");
+    }
+    Ok(())
+}
+
+// Leaves the peer id and pid of the node in its root dir, for local tooling such as the
+// testnet launcher to dial it, and to stop it.
+fn write_node_files(root_dir: &Path, peer_id: &PeerId) -> Result<()> {
+    fs::create_dir_all(root_dir)?;
+    fs::write(root_dir.join(PEER_ID_FILENAME), peer_id.to_string())?;
+    fs::write(root_dir.join(PID_FILENAME), std::process::id().to_string())?;
+    Ok(())
+}
+
+// The multiaddr of a QUIC listener on the socket address.
+fn quic_multiaddr(addr: SocketAddr) -> Multiaddr {
+    Multiaddr::from(addr.ip())
+        .with(Protocol::Udp(addr.port()))
+        .with(Protocol::QuicV1)
+}
+
+// Reads the addresses of the peers to bootstrap from, one per line.
+fn read_network_contacts(path: &Path) -> Result<Vec<Multiaddr>> {
+    fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse()
+                .map_err(|err| eyre!("Invalid network contact {line} in {path:?}: {err}"))
+        })
+        .collect()
+}
+
+// Spawns a copy of the current node process, with the same args.
+fn start_new_node_process() -> Result<()> {
+    let current_exe = std::env::current_exe()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    info!("Starting a new node process: {current_exe:?} {args:?}");
+    let _child = Command::new(current_exe).args(args).spawn()?;
+    Ok(())
+}
+
 #[derive(Parser, Debug)]
 #[clap(name = "safenode cli")]
 struct Opt {
+    /// The config file to read, instead of `~/.safe/node/config.toml`.
+    /// The settings given as flags take precedence over the ones in the file.
+    #[clap(long)]
+    config: Option<PathBuf>,
+
+    /// Print the config resulting from the config file and flags, then exit.
+    #[clap(long)]
+    dump_config: bool,
+
+    /// The dir the node keeps its keypair, records, wallet and logs in, created if missing.
+    /// Records left right in the dir by earlier versions are moved to its record store.
+    #[clap(long)]
+    root_dir: Option<PathBuf>,
+
+    /// An address to listen on, IPv4 or IPv6, can be given multiple times.
+    #[clap(long = "listen-addr")]
+    listen_addrs: Vec<Multiaddr>,
+
+    /// An address the node is reached at from outside, e.g. `/ip4/<public ip>/udp/12000/quic-v1`,
+    /// advertised to peers. Can be given multiple times.
+    #[clap(long = "external-addr")]
+    external_addrs: Vec<Multiaddr>,
+
+    /// The address of a peer to bootstrap from, ending with its `/p2p/<peer id>`.
+    /// Can be given multiple times.
+    #[clap(long = "peer")]
+    bootstrap_peers: Vec<Multiaddr>,
+
+    /// Start as the first node of a network, reached by the other nodes at this address.
+    /// Its contact is left in the `network_contacts` file of the root dir, for them to
+    /// bootstrap from with `--network-contacts-file`.
+    #[clap(long)]
+    first: Option<SocketAddr>,
+
+    /// A socket address to listen on for QUIC connections, e.g. `0.0.0.0:12000`, instead of
+    /// the addresses given with `--listen-addr`.
+    #[clap(long)]
+    local_addr: Option<SocketAddr>,
+
+    /// A file of the addresses of peers to bootstrap from, one per line, such as the one left
+    /// by a node started with `--first`.
+    #[clap(long)]
+    network_contacts_file: Option<PathBuf>,
+
+    /// The max number of bytes of records to store.
+    #[clap(long)]
+    max_capacity: Option<u64>,
+
+    /// The algorithm to compress the stored records with, `none`, `zstd` or `lz4`.
+    #[clap(long)]
+    compression: Option<Compression>,
+
+    /// The hex encoded 32 bytes key of the private network to join, e.g. generated with
+    /// `openssl rand -hex 32`. Only nodes started with the same key can connect to each other.
+    #[clap(long)]
+    network_key: Option<String>,
+
+    /// The network-contacts file to bootstrap from, a local path or an `https://` URL.
+    #[clap(long)]
+    contacts: Option<String>,
+
+    /// The hex encoded public key the network contacts are signed with.
+    #[clap(long)]
+    contacts_public_key: Option<String>,
+
+    /// Map the ports listened on through UPnP, for the node to be reached from outside of
+    /// the local network.
+    #[clap(long)]
+    upnp: bool,
+
+    /// Do not map the ports listened on through UPnP, even if the config file says to.
+    #[clap(long, conflicts_with = "upnp")]
+    no_upnp: bool,
+
+    /// Relay the connections of peers behind NATs, within the limits of the config file.
+    #[clap(long)]
+    relay_server: bool,
+
+    /// Do not relay the connections of peers, even if the config file says to.
+    #[clap(long, conflicts_with = "relay_server")]
+    no_relay_server: bool,
+
     #[clap(long)]
     log_dir: Option<PathBuf>,
 
+    /// The format to write logs in, `default` or `json`.
+    #[clap(long)]
+    log_format: Option<LogFormat>,
+
     #[clap(long)]
     upload_chunks: Option<PathBuf>,
 
     #[clap(long)]
     get_chunk: Option<String>,
+
+    /// The address to serve the RPC service on, for operator tooling to control the node.
+    #[clap(long)]
+    rpc: Option<SocketAddr>,
+
+    /// The address to serve the `/healthz` and `/readyz` HTTP endpoints on.
+    #[clap(long)]
+    health: Option<SocketAddr>,
+
+    /// The min number of peers in the routing table for the node to be reported as ready.
+    #[clap(long)]
+    health_min_peers: Option<usize>,
+
+    /// The min number of peers in the routing table for the node to take data.
+    #[clap(long)]
+    min_peers_to_serve: Option<usize>,
+
+    /// The timeouts and limits of the network suited to where the node runs, `lan` for the
+    /// testnets of a local network or `wan` for the internet.
+    #[clap(long)]
+    network_preset: Option<NetworkPreset>,
+
+    /// The hex encoded address the node is paid at for the storage of records, as printed by
+    /// `safe wallet address`.
+    #[clap(long)]
+    wallet_address: Option<String>,
 }
 
-// Todo: Implement node bootstrapping to connect to peers from outside the local network
-#[allow(dead_code)]
-async fn bootstrap_node(network_api: &mut Network, addr: Multiaddr) -> Result<()> {
-    let peer_id = match addr.iter().last() {
-        Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash).expect("Valid hash."),
+// Reads the config file, and overrides its settings with the ones given as flags.
+fn node_config(opt: &Opt) -> Result<NodeConfig> {
+    let path = match &opt.config {
+        Some(path) => path.clone(),
+        None => NodeConfig::default_path()?,
+    };
+    let mut config = NodeConfig::load(&path)?;
+
+    if opt.root_dir.is_some() {
+        config.root_dir = opt.root_dir.clone();
+    }
+    if !opt.listen_addrs.is_empty() {
+        config.listen_addrs = opt.listen_addrs.clone();
+    }
+    if !opt.external_addrs.is_empty() {
+        config.external_addrs = opt.external_addrs.clone();
+    }
+    if let Some(addr) = opt.local_addr {
+        config.listen_addrs = vec![quic_multiaddr(addr)];
+    }
+    if !opt.bootstrap_peers.is_empty() {
+        config.bootstrap_peers = opt.bootstrap_peers.clone();
+    }
+    if let Some(path) = &opt.network_contacts_file {
+        config.bootstrap_peers.extend(read_network_contacts(path)?);
+    }
+    if let Some(max_capacity) = opt.max_capacity {
+        config.max_capacity = max_capacity;
+    }
+    if let Some(compression) = opt.compression {
+        config.compression = compression;
+    }
+    if opt.network_key.is_some() {
+        config.network.network_key = opt.network_key.clone();
+    }
+    if opt.contacts.is_some() {
+        config.network.contacts = opt.contacts.clone();
+    }
+    if opt.contacts_public_key.is_some() {
+        config.network.contacts_public_key = opt.contacts_public_key.clone();
+    }
+    if opt.upnp || opt.no_upnp {
+        config.network.upnp = opt.upnp;
+    }
+    if opt.relay_server || opt.no_relay_server {
+        config.network.relay_server = opt.relay_server;
+    }
+    if opt.log_dir.is_some() {
+        config.log.dir = opt.log_dir.clone();
+    }
+    if let Some(format) = opt.log_format {
+        config.log.format = format;
+    }
+    if let Some(min_peers) = opt.min_peers_to_serve {
+        config.min_peers_to_serve = min_peers;
+    }
+    if let Some(preset) = opt.network_preset {
+        config.network.apply_preset(preset);
+    }
+    if let Some(address) = &opt.wallet_address {
+        config.wallet_address = Some(address.clone());
+    }
+
+    Ok(config)
+}
+
+// Maps the ports listened on by the gateway of the local network, in the background.
+#[cfg(feature = "upnp")]
+fn map_ports(network_api: Network, node_span: &Span) {
+    spawn(network_api.map_ports().instrument(node_span.clone()));
+}
+
+#[cfg(not(feature = "upnp"))]
+fn map_ports(_network_api: Network, _node_span: &Span) {
+    warn!("safenode was built without the `upnp` feature, not mapping ports through UPnP");
+}
+
+// Dials a random subset of the network contacts, fetching them again periodically for
+// their new peers to be dialed.
+async fn dial_contacts(
+    mut network_api: Network,
+    source: String,
+    public_key: PublicKey,
+    refresh: time::Duration,
+) {
+    loop {
+        match fetch_contacts(&source, &public_key).await {
+            Ok(contacts) => {
+                info!("Fetched {} network contacts from {source}", contacts.len());
+                if let Err(err) = network_api.dial_contacts(contacts).await {
+                    warn!("Failed to dial the network contacts: {err}");
+                }
+            }
+            Err(err) => warn!("Failed to fetch the network contacts: {err}"),
+        }
+        async_std::task::sleep(refresh).await;
+    }
+}
+
+// Bootstraps off the routing table, the failures being logged only.
+async fn bootstrap(mut network_api: Network) {
+    match network_api.bootstrap().await {
+        Ok(()) => info!("Bootstrapped"),
+        Err(err) => warn!("Failed to bootstrap: {err}"),
+    }
+}
+
+// Connects to a peer outside of the local network.
+async fn bootstrap_node(network_api: &mut Network, mut addr: Multiaddr) -> Result<()> {
+    // The peer id is appended back to the address when dialing.
+    let peer_id = match addr.pop() {
+        Some(Protocol::P2p(hash)) => {
+            PeerId::from_multihash(hash).map_err(|_| eyre!("Invalid peer ID in {addr}."))?
+        }
         _ => return Err(eyre!("Expect peer multiaddr to contain peer ID.")),
     };
-    network_api
-        .dial(peer_id, addr)
-        .await
-        .expect("Dial to succeed");
+    network_api.dial(peer_id, addr).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parses the args given after the name of the binary, into the config they result in.
+    fn config_of(args: &[&str]) -> Result<NodeConfig> {
+        let opt = Opt::try_parse_from(std::iter::once("safenode").chain(args.iter().copied()))?;
+        node_config(&opt)
+    }
+
+    #[test]
+    fn flags_take_precedence_over_the_config_file() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.join("config.toml");
+        fs::write(
+            &path,
+            "max_capacity = 1024\n\n[network]\nupnp = true\nrelay_server = true\n",
+        )?;
+        let path = path.to_string_lossy();
+
+        let config = config_of(&["--config", &path])?;
+        assert_eq!(config.max_capacity, 1024);
+        assert!(config.network.upnp);
+        assert!(config.network.relay_server);
+
+        let config = config_of(&[
+            "--config",
+            &path,
+            "--max-capacity",
+            "2048",
+            "--no-upnp",
+            "--no-relay-server",
+        ])?;
+        assert_eq!(config.max_capacity, 2048);
+        assert!(!config.network.upnp);
+        assert!(!config.network.relay_server);
+        Ok(())
+    }
+
+    #[test]
+    fn flags_and_their_negation_conflict() {
+        assert!(Opt::try_parse_from(["safenode", "--upnp", "--no-upnp"]).is_err());
+        assert!(Opt::try_parse_from(["safenode", "--relay-server", "--no-relay-server"]).is_err());
+    }
+
+    #[test]
+    fn dumped_configs_are_read_back_as_they_were() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.join("config.toml");
+        fs::write(&path, "[network]\nupnp = true\n")?;
+        let config = config_of(&[
+            "--config",
+            &path.to_string_lossy(),
+            "--relay-server",
+            "--max-capacity",
+            "2048",
+            "--network-preset",
+            "lan",
+        ])?;
+
+        let dumped_path = dir.join("dumped.toml");
+        fs::write(&dumped_path, config.to_toml()?)?;
+        assert_eq!(
+            config_of(&["--config", &dumped_path.to_string_lossy()])?,
+            config
+        );
+        Ok(())
+    }
+}
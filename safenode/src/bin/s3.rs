@@ -0,0 +1,484 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Serves a minimal subset of the S3 API, for existing S3 tooling to store objects on the
+//! network through it.
+//!
+//! Requests address buckets by path, as in `PUT /<bucket>/<key>`. Supported are:
+//! - `PUT /<bucket>`, creating the bucket (CreateBucket),
+//! - `PUT /<bucket>/<key>`, storing the body as the object (PutObject),
+//! - `GET` or `HEAD /<bucket>/<key>`, reading the object (GetObject, HeadObject),
+//! - `GET /<bucket>?prefix=<prefix>`, listing the objects (ListObjects and ListObjectsV2).
+//!
+//! Buckets are owned by the key of the frontend, and paid for, along with the objects, from
+//! its wallet. Requests must carry the token written to the token file on start, as the access
+//! key id the S3 client is configured with, any secret key being accepted, or as an
+//! `Authorization: Bearer <token>` header. The signatures of the S3 clients are not verified,
+//! so the frontend is meant to listen on the loopback interface only.
+
+use async_std::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    task::spawn,
+};
+use bytes::Bytes;
+use clap::Parser;
+use eyre::{eyre, Result};
+use futures::{AsyncWriteExt, StreamExt};
+use libp2p::Multiaddr;
+use safenode::{
+    client::{AccessToken, Bucket, Client, Error as ClientError, Wallet, CONNECT_TIMEOUT},
+    http::{read_body, read_head, write_head},
+};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use tokio::sync::Mutex;
+use tracing::{info, trace, warn};
+use tracing_subscriber::EnvFilter;
+
+/// The wallet dir used by default, relative to the home dir.
+const DEFAULT_WALLET_DIR: &str = ".safe/s3/wallet";
+/// The token file used by default, relative to the home dir.
+const DEFAULT_TOKEN_FILE: &str = ".safe/s3/token";
+/// The max number of keys listed, as S3 does by default. Listings are never truncated, the
+/// value is only echoed back for clients expecting it.
+const MAX_KEYS: usize = 1000;
+
+#[derive(Parser, Debug)]
+#[clap(
+    name = "s3",
+    version,
+    about = "Serve a subset of the S3 API on top of the SAFE Network"
+)]
+struct Opt {
+    /// The address to serve the API on.
+    #[clap(long, default_value = "127.0.0.1:9000")]
+    listen: SocketAddr,
+
+    /// The address of a peer to bootstrap from, ending with its `/p2p/<peer id>`.
+    /// Can be given multiple times. Peers on the local network are found without it.
+    #[clap(long = "peer")]
+    bootstrap_peers: Vec<Multiaddr>,
+
+    /// The wallet to pay for the buckets and objects from, instead of `~/.safe/s3/wallet`.
+    #[clap(long)]
+    wallet_dir: Option<PathBuf>,
+
+    /// The file to write the token authenticating requests to, instead of `~/.safe/s3/token`.
+    #[clap(long)]
+    token_file: Option<PathBuf>,
+
+    /// The max size of the objects put, in bytes, as they are held in memory while uploaded.
+    #[clap(long, default_value_t = 100 * 1024 * 1024)]
+    max_object_size: usize,
+}
+
+// The state shared by the handlers of the requests.
+struct Frontend {
+    client: Client,
+    // Puts are paid for, and so made one at a time.
+    wallet: Mutex<Wallet>,
+    token: AccessToken,
+    max_object_size: usize,
+}
+
+// The parts of a request the frontend makes use of, once authorized.
+struct Request {
+    method: String,
+    bucket: String,
+    key: Option<String>,
+    prefix: String,
+    body: Bytes,
+}
+
+// A response, with the value of its `ETag` header, if any.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    etag: Option<String>,
+    // The length of the content, which the body is empty of in answer to a `HEAD`.
+    content_length: usize,
+    body: Bytes,
+}
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    let opt = Opt::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let client = Client::connect(&opt.bootstrap_peers, CONNECT_TIMEOUT).await?;
+    let wallet_dir = match opt.wallet_dir {
+        Some(dir) => dir,
+        None => dirs_next::home_dir()
+            .map(|home| home.join(DEFAULT_WALLET_DIR))
+            .ok_or_else(|| eyre!("Could not find the home dir"))?,
+    };
+    let wallet = Wallet::load_from(client.clone(), &wallet_dir).await?;
+    info!(
+        "S3 frontend paying from a wallet holding {}",
+        wallet.balance()?
+    );
+
+    let token = AccessToken::random();
+    let token_file = match opt.token_file {
+        Some(file) => file,
+        None => dirs_next::home_dir()
+            .map(|home| home.join(DEFAULT_TOKEN_FILE))
+            .ok_or_else(|| eyre!("Could not find the home dir"))?,
+    };
+    token.write_to(&token_file)?;
+
+    let frontend = Arc::new(Frontend {
+        client,
+        wallet: Mutex::new(wallet),
+        token,
+        max_object_size: opt.max_object_size,
+    });
+
+    let listener = TcpListener::bind(opt.listen).await?;
+    info!("S3 frontend listening on {}", opt.listen);
+    eprintln!(
+        "Serving S3 on {}, with the access key id written to {token_file:?}",
+        opt.listen
+    );
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        match stream {
+            Ok(stream) => {
+                let frontend = Arc::clone(&frontend);
+                let _handle = spawn(async move {
+                    if let Err(err) = handle_connection(stream, &frontend).await {
+                        warn!("Failed to handle S3 request: {err}");
+                    }
+                });
+            }
+            Err(err) => warn!("Failed to accept S3 connection: {err}"),
+        }
+    }
+    Ok(())
+}
+
+async fn handle_connection(stream: TcpStream, frontend: &Frontend) -> Result<()> {
+    let response = match read_request(&stream, &frontend.token, frontend.max_object_size).await {
+        Ok(request) => {
+            trace!(
+                "S3 request received: {} {}/{:?}",
+                request.method,
+                request.bucket,
+                request.key
+            );
+            frontend.handle(request).await
+        }
+        Err(response) => response,
+    };
+    write_response(&stream, response).await
+}
+
+impl Frontend {
+    async fn handle(&self, request: Request) -> Response {
+        let is_head = request.method == "HEAD";
+        let result = match (request.method.as_str(), &request.key) {
+            ("PUT", None) => self.create_bucket(&request.bucket).await,
+            ("PUT", Some(key)) => self.put_object(&request.bucket, key, request.body).await,
+            ("GET" | "HEAD", Some(key)) => self.get_object(&request.bucket, key, is_head).await,
+            ("GET", None) => self.list_objects(&request.bucket, &request.prefix).await,
+            _ => {
+                return error_response(
+                    "405 Method Not Allowed",
+                    "MethodNotAllowed",
+                    "The method is not allowed against this resource",
+                )
+            }
+        };
+        match result {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("Failed S3 request on bucket {:?}: {err}", request.bucket);
+                match err {
+                    ClientError::PathNotFound(_) => error_response(
+                        "404 Not Found",
+                        "NoSuchKey",
+                        "The specified key does not exist",
+                    ),
+                    ClientError::NotFound(_) => error_response(
+                        "404 Not Found",
+                        "NoSuchBucket",
+                        "The specified bucket does not exist",
+                    ),
+                    ClientError::PaymentRequired(_) | ClientError::TransferFailed(_) => {
+                        error_response("402 Payment Required", "PaymentRequired", &err.to_string())
+                    }
+                    _ => error_response(
+                        "500 Internal Server Error",
+                        "InternalError",
+                        &err.to_string(),
+                    ),
+                }
+            }
+        }
+    }
+
+    async fn create_bucket(&self, name: &str) -> Result<Response, ClientError> {
+        let mut wallet = self.wallet.lock().await;
+        let _ = Bucket::create(&mut wallet, name).await?;
+        Ok(empty_response(None))
+    }
+
+    async fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Bytes,
+    ) -> Result<Response, ClientError> {
+        let mut wallet = self.wallet.lock().await;
+        let mut bucket = Bucket::open(self.client.clone(), bucket).await?;
+        let address = bucket.put_object(&mut wallet, key, body).await?;
+        Ok(empty_response(Some(hex::encode(address.name()))))
+    }
+
+    async fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        is_head: bool,
+    ) -> Result<Response, ClientError> {
+        let bucket = Bucket::open(self.client.clone(), bucket).await?;
+        let (address, size) = bucket.head_object(key).await?;
+        let body = if is_head {
+            Bytes::new()
+        } else {
+            bucket.get_object(key).await?
+        };
+        Ok(Response {
+            status: "200 OK",
+            content_type: "application/octet-stream",
+            etag: Some(hex::encode(address.name())),
+            content_length: size as usize,
+            body,
+        })
+    }
+
+    async fn list_objects(&self, bucket: &str, prefix: &str) -> Result<Response, ClientError> {
+        let bucket = Bucket::open(self.client.clone(), bucket).await?;
+        let objects = bucket.list_objects(prefix).await?;
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{MAX_KEYS}</MaxKeys><IsTruncated>false</IsTruncated>",
+            escape_xml(bucket.name()),
+            escape_xml(prefix),
+            objects.len(),
+        );
+        for (key, size) in objects {
+            xml.push_str(&format!(
+                "<Contents><Key>{}</Key><Size>{size}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                escape_xml(&key)
+            ));
+        }
+        xml.push_str("</ListBucketResult>");
+        Ok(Response {
+            status: "200 OK",
+            content_type: "application/xml",
+            etag: None,
+            content_length: xml.len(),
+            body: Bytes::from(xml),
+        })
+    }
+}
+
+// Reads the request line, the headers and the body of the request, or returns the error
+// response to answer it with. The body is only read once the request is authorized with the
+// token, for unauthorized clients not to have us read what they send.
+async fn read_request(
+    stream: &TcpStream,
+    token: &AccessToken,
+    max_body_size: usize,
+) -> Result<Request, Response> {
+    let incomplete =
+        |err: std::io::Error| error_response("400 Bad Request", "IncompleteBody", &err.to_string());
+    let mut reader = BufReader::new(stream);
+    let head = read_head(&mut reader).await.map_err(incomplete)?;
+    if head
+        .header("transfer-encoding")
+        .is_some_and(|value| !value.eq_ignore_ascii_case("identity"))
+    {
+        return Err(not_implemented("Transfer-Encoding"));
+    }
+    if head
+        .header("x-amz-content-sha256")
+        .is_some_and(|value| value.starts_with("STREAMING-"))
+    {
+        return Err(not_implemented("aws-chunked payloads"));
+    }
+    let content_length = head.content_length().map_err(|_| {
+        error_response(
+            "400 Bad Request",
+            "InvalidArgument",
+            "Invalid Content-Length",
+        )
+    })?;
+    if content_length > max_body_size {
+        return Err(error_response(
+            "400 Bad Request",
+            "EntityTooLarge",
+            "Your proposed upload exceeds the maximum allowed object size",
+        ));
+    }
+    let (path, query) = (head.path(), head.query());
+    let path = path.trim_start_matches('/');
+    let (bucket, key) = match path.split_once('/') {
+        Some((bucket, key)) if !key.is_empty() => (bucket, Some(percent_decode(key))),
+        Some((bucket, _)) => (bucket, None),
+        None => (path, None),
+    };
+    if bucket.is_empty() {
+        return Err(not_implemented("ListBuckets"));
+    }
+    let param = |name: &str| {
+        query
+            .split('&')
+            .find_map(|param| param.strip_prefix(name))
+            .map(percent_decode)
+    };
+    let prefix = param("prefix=").unwrap_or_default();
+    // Presigned URLs carry the access key id in the query.
+    let access_key = head
+        .header("authorization")
+        .and_then(access_key_of)
+        .or_else(|| {
+            param("X-Amz-Credential=").and_then(|credential| access_key_of_credential(&credential))
+        })
+        .or_else(|| param("AWSAccessKeyId="));
+    if !access_key.is_some_and(|key| token.matches(&key)) {
+        return Err(error_response(
+            "403 Forbidden",
+            "InvalidAccessKeyId",
+            "The access key id you provided is not the token of the frontend",
+        ));
+    }
+
+    let body = read_body(&mut reader, content_length)
+        .await
+        .map_err(incomplete)?;
+    Ok(Request {
+        method: head.method.clone(),
+        bucket: percent_decode(bucket),
+        key,
+        prefix,
+        body: Bytes::from(body),
+    })
+}
+
+// Returns the access key id of the `Authorization` header of a request, as signed with
+// version 4 or 2 of the AWS signatures, or the token of a bearer.
+fn access_key_of(authorization: &str) -> Option<String> {
+    if let Some(token) = authorization.strip_prefix("Bearer ") {
+        return Some(token.trim().to_string());
+    }
+    if let Some(signed) = authorization.strip_prefix("AWS4-HMAC-SHA256 ") {
+        return signed
+            .split(',')
+            .find_map(|part| part.trim().strip_prefix("Credential="))
+            .and_then(access_key_of_credential);
+    }
+    authorization
+        .strip_prefix("AWS ")
+        .and_then(|signed| signed.split_once(':'))
+        .map(|(access_key, _)| access_key.trim().to_string())
+}
+
+// Returns the access key id of an AWS v4 credential, `<access key id>/<date>/<region>/...`.
+fn access_key_of_credential(credential: &str) -> Option<String> {
+    credential
+        .split_once('/')
+        .map(|(access_key, _)| access_key.to_string())
+}
+
+async fn write_response(stream: &TcpStream, response: Response) -> Result<()> {
+    // The `Content-Length` is the one of the content, which the body is empty of for a `HEAD`.
+    let etag = response.etag.map(|etag| format!("\"{etag}\""));
+    let content_length = response.content_length.to_string();
+    let mut headers = vec![
+        ("Content-Type", response.content_type),
+        ("Content-Length", content_length.as_str()),
+    ];
+    if let Some(etag) = &etag {
+        headers.push(("ETag", etag.as_str()));
+    }
+    headers.push(("Connection", "close"));
+    let mut writer = stream;
+    write_head(&mut writer, response.status, &headers).await?;
+    writer.write_all(&response.body).await?;
+    Ok(writer.flush().await?)
+}
+
+fn empty_response(etag: Option<String>) -> Response {
+    Response {
+        status: "200 OK",
+        content_type: "application/xml",
+        etag,
+        content_length: 0,
+        body: Bytes::new(),
+    }
+}
+
+fn not_implemented(what: &str) -> Response {
+    error_response(
+        "501 Not Implemented",
+        "NotImplemented",
+        &format!("{what} not supported"),
+    )
+}
+
+// The error document of S3, telling clients the code of the error.
+fn error_response(status: &'static str, code: &str, message: &str) -> Response {
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{code}</Code><Message>{}</Message></Error>",
+        escape_xml(message)
+    );
+    Response {
+        status,
+        content_type: "application/xml",
+        etag: None,
+        content_length: xml.len(),
+        body: Bytes::from(xml),
+    }
+}
+
+// Decodes the `%XX` escapes of a path segment or query value, and `+` as a space.
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
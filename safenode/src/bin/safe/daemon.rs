@@ -0,0 +1,393 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Serves the client operations over JSON-RPC 2.0, for tooling in other languages on the same
+//! machine to upload and download files and use the wallet.
+//!
+//! Requests are `POST`ed over HTTP, or sent as text messages over a WebSocket opened with a
+//! `GET` upgrade request, and carry the token written to the token file on start, either as
+//! an `Authorization: Bearer <token>` header or, for browsers, a `?token=<token>` query.
+
+use super::{
+    files::{parse_address, pay_for_chunks},
+    wallet::{decode, encode},
+    Opt,
+};
+use async_std::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    task::spawn,
+};
+use async_tungstenite::{
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message},
+    WebSocketStream,
+};
+use clap::Args;
+use eyre::{eyre, Result};
+use futures::{SinkExt, StreamExt};
+use safenode::{
+    client::{AccessToken, Client, EncryptedFile, Wallet},
+    http::{self, read_body, read_head, write_head},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sn_dbc::{Dbc, PublicAddress, Token};
+use std::{collections::BTreeSet, net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc};
+use tokio::sync::Mutex;
+use tracing::{info, trace, warn};
+
+/// The token file used by default, relative to the home dir.
+const DEFAULT_TOKEN_FILE: &str = ".safe/client/daemon_token";
+/// The max size of a request body, as requests carry paths and transfers, not content.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+// The error codes defined by JSON-RPC 2.0.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// The code of the failures of the operations themselves, in the range left to servers.
+const OPERATION_FAILED: i64 = -32000;
+
+#[derive(Args, Debug)]
+pub(super) struct DaemonArgs {
+    /// The address to serve the API on.
+    #[clap(long, default_value = "127.0.0.1:12500")]
+    listen: SocketAddr,
+
+    /// The file to write the token authenticating requests to, instead of
+    /// `~/.safe/client/daemon_token`.
+    #[clap(long)]
+    token_file: Option<PathBuf>,
+
+    /// The amount paid for the storage of each chunk uploaded, in nanos, when the nodes
+    /// storing it do not ask for a cost of their own.
+    #[clap(long, default_value_t = 1)]
+    chunk_payment_nanos: u64,
+}
+
+// The state shared by the handlers of the requests.
+struct Daemon {
+    client: Client,
+    wallet: Mutex<Wallet>,
+    token: AccessToken,
+    chunk_payment: Token,
+}
+
+// An error answered to a request, with its JSON-RPC code.
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<eyre::Report> for RpcError {
+    fn from(err: eyre::Report) -> Self {
+        Self::new(OPERATION_FAILED, err)
+    }
+}
+
+impl From<safenode::client::Error> for RpcError {
+    fn from(err: safenode::client::Error) -> Self {
+        Self::new(OPERATION_FAILED, err)
+    }
+}
+
+#[derive(Deserialize)]
+struct UploadParams {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct DownloadParams {
+    address: String,
+    dest: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct SendParams {
+    amount: String,
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct ReceiveParams {
+    transfer: String,
+}
+
+pub(super) async fn run(opt: &Opt, args: &DaemonArgs) -> Result<()> {
+    let client = opt.connect().await?;
+    let wallet = opt.load_wallet(client.clone()).await?;
+
+    let token = AccessToken::random();
+    let token_file = match &args.token_file {
+        Some(file) => file.clone(),
+        None => dirs_next::home_dir()
+            .map(|home| home.join(DEFAULT_TOKEN_FILE))
+            .ok_or_else(|| eyre!("Could not find the home dir"))?,
+    };
+    token.write_to(&token_file)?;
+
+    let daemon = Arc::new(Daemon {
+        client,
+        wallet: Mutex::new(wallet),
+        token,
+        chunk_payment: Token::from_nano(args.chunk_payment_nanos),
+    });
+    let listener = TcpListener::bind(args.listen).await?;
+    info!("Daemon listening on {}", args.listen);
+    eprintln!(
+        "Serving JSON-RPC on {}, with the token written to {token_file:?}",
+        args.listen
+    );
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        match stream {
+            Ok(stream) => {
+                let daemon = Arc::clone(&daemon);
+                let _handle = spawn(async move {
+                    if let Err(err) = daemon.handle_connection(stream).await {
+                        warn!("Failed to handle daemon request: {err}");
+                    }
+                });
+            }
+            Err(err) => warn!("Failed to accept daemon connection: {err}"),
+        }
+    }
+    Ok(())
+}
+
+impl Daemon {
+    // Answers the single HTTP request of the connection, or the messages of the WebSocket
+    // it is upgraded to.
+    async fn handle_connection(&self, stream: TcpStream) -> Result<()> {
+        let mut reader = BufReader::new(&stream);
+        let head = read_head(&mut reader).await?;
+        let method = head.method.as_str();
+        // The path alone, the query possibly carrying the token.
+        trace!("Daemon request received: {method} {}", head.path());
+
+        let bearer = head
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let query_token = head.query_param("token");
+        let authorised = bearer
+            .or(query_token)
+            .is_some_and(|token| self.token.matches(token));
+        if !authorised {
+            return respond(&stream, "401 Unauthorized", "text/plain", "Invalid token").await;
+        }
+
+        match (method, head.header("sec-websocket-key")) {
+            ("GET", Some(key)) => self.serve_websocket(stream, key).await,
+            ("POST", _) => {
+                let content_length = head.content_length()?;
+                if content_length > MAX_BODY_SIZE {
+                    return respond(&stream, "413 Payload Too Large", "text/plain", "").await;
+                }
+                let body = read_body(&mut reader, content_length).await?;
+                let response = self.handle_request(&body).await;
+                respond(&stream, "200 OK", "application/json", &response.to_string()).await
+            }
+            _ => {
+                respond(
+                    &stream,
+                    "405 Method Not Allowed",
+                    "text/plain",
+                    "POST a JSON-RPC request, or GET a WebSocket upgrade",
+                )
+                .await
+            }
+        }
+    }
+
+    async fn serve_websocket(&self, stream: TcpStream, key: &str) -> Result<()> {
+        let accept_key = derive_accept_key(key.as_bytes());
+        let headers = [
+            ("Upgrade", "websocket"),
+            ("Connection", "Upgrade"),
+            ("Sec-WebSocket-Accept", accept_key.as_str()),
+        ];
+        write_head(&mut &stream, "101 Switching Protocols", &headers).await?;
+        let mut websocket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+        while let Some(message) = websocket.next().await {
+            let response = match message? {
+                Message::Text(text) => self.handle_request(text.as_bytes()).await,
+                Message::Binary(bytes) => self.handle_request(&bytes).await,
+                Message::Close(_) => break,
+                // Pings are answered by the stream itself.
+                _ => continue,
+            };
+            websocket.send(Message::Text(response.to_string())).await?;
+        }
+        Ok(())
+    }
+
+    // Answers the JSON-RPC request with its result or error.
+    async fn handle_request(&self, body: &[u8]) -> Value {
+        let request: Value = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(err) => return error_response(Value::Null, RpcError::new(PARSE_ERROR, err)),
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None => {
+                return error_response(id, RpcError::new(INVALID_REQUEST, "Missing method"));
+            }
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        match self.call(method, params).await {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => {
+                warn!("Failed daemon call of {method}: {}", err.message);
+                error_response(id, err)
+            }
+        }
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "files.upload" => self.upload(parse_params(params)?).await,
+            "files.download" => self.download(parse_params(params)?).await,
+            "wallet.address" => {
+                let address = encode(&self.wallet.lock().await.address())?;
+                Ok(json!({ "address": address }))
+            }
+            "wallet.balance" => {
+                let balance = self.wallet.lock().await.balance()?;
+                Ok(json!({ "balance": balance.to_string() }))
+            }
+            "wallet.send" => self.send(parse_params(params)?).await,
+            "wallet.receive" => self.receive(parse_params(params)?).await,
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {method}"),
+            )),
+        }
+    }
+
+    async fn upload(&self, params: UploadParams) -> Result<Value, RpcError> {
+        let file = EncryptedFile::from_path(&params.path).await?;
+        let address = file.address;
+        // Collected first, for the future of the upload to be `Send`, which it is not with
+        // the closure mapping the chunks held across the check.
+        let addresses: Vec<_> = file.chunks.iter().map(|chunk| *chunk.address()).collect();
+        let stored: BTreeSet<_> = self
+            .client
+            .stored_chunks(addresses)
+            .await
+            .into_iter()
+            .collect();
+        let to_upload: Vec<_> = file
+            .chunks
+            .into_iter()
+            .filter(|chunk| !stored.contains(chunk.address()))
+            .collect();
+
+        let mut payments = {
+            let mut wallet = self.wallet.lock().await;
+            pay_for_chunks(
+                &self.client,
+                &mut wallet,
+                &to_upload,
+                self.chunk_payment,
+                None,
+                || {},
+            )
+            .await?
+        };
+        let chunks = to_upload.into_iter().map(|chunk| {
+            let payment = payments.remove(chunk.name()).unwrap_or_default();
+            (chunk, payment)
+        });
+        let report = self.client.upload_chunks(chunks).await;
+        if let Some((failed, err)) = report.failed.first() {
+            return Err(RpcError::new(
+                OPERATION_FAILED,
+                format!(
+                    "Failed to upload {} chunks, e.g. {}: {err}",
+                    report.failed.len(),
+                    hex::encode(failed.name())
+                ),
+            ));
+        }
+        Ok(json!({
+            "path": params.path,
+            "address": hex::encode(address.name()),
+            "chunks_stored": report.stored.len(),
+            "chunks_skipped": stored.len() + report.skipped.len(),
+        }))
+    }
+
+    async fn download(&self, params: DownloadParams) -> Result<Value, RpcError> {
+        let address =
+            parse_address(&params.address).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+        let bytes = self.client.read_file(address).await?;
+        async_std::fs::write(&params.dest, &bytes)
+            .await
+            .map_err(|err| RpcError::new(OPERATION_FAILED, err))?;
+        Ok(json!({
+            "address": params.address,
+            "dest": params.dest,
+            "bytes": bytes.len(),
+        }))
+    }
+
+    async fn send(&self, params: SendParams) -> Result<Value, RpcError> {
+        let amount = Token::from_str(&params.amount).map_err(|err| {
+            RpcError::new(
+                INVALID_PARAMS,
+                format!("Invalid amount {}: {err}", params.amount),
+            )
+        })?;
+        let recipient: PublicAddress =
+            decode(&params.address).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+        let mut wallet = self.wallet.lock().await;
+        let dbc = wallet.send(amount, recipient).await?;
+        Ok(json!({
+            "amount": amount.to_string(),
+            "transfer": encode(&dbc)?,
+            "balance": wallet.balance()?.to_string(),
+        }))
+    }
+
+    async fn receive(&self, params: ReceiveParams) -> Result<Value, RpcError> {
+        let dbc: Dbc =
+            decode(&params.transfer).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+        let mut wallet = self.wallet.lock().await;
+        wallet.receive(vec![dbc]).await?;
+        Ok(json!({ "balance": wallet.balance()?.to_string() }))
+    }
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err))
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": err.code, "message": err.message },
+    })
+}
+
+async fn respond(stream: &TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    let mut writer = stream;
+    let headers = [("Content-Type", content_type)];
+    Ok(http::respond(&mut writer, status, &headers, body.as_bytes()).await?)
+}
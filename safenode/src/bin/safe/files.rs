@@ -0,0 +1,268 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{wallet::decode, Opt};
+use async_std::{fs::File, io::WriteExt};
+use clap::{Args, Subcommand};
+use eyre::{eyre, Result};
+use futures::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use safenode::{
+    client::{Client, StreamingEncryptor, Wallet},
+    protocol::types::payment::{PaymentProof, RoyaltyPolicy},
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use serde_json::json;
+use sn_dbc::Token;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+use xor_name::XorName;
+
+#[derive(Subcommand, Debug)]
+pub(super) enum FilesCmd {
+    /// Self-encrypt the file, pay for its chunks and upload them, printing the address
+    /// the file is downloaded from.
+    Upload(UploadArgs),
+    /// Download the file uploaded at the given address.
+    Download(DownloadArgs),
+}
+
+#[derive(Args, Debug)]
+pub(super) struct UploadArgs {
+    /// The file to upload.
+    path: PathBuf,
+
+    /// The max number of chunks uploaded at a time.
+    #[clap(long, default_value_t = 32)]
+    concurrency: usize,
+
+    /// The amount paid for the storage of each chunk, in nanos, when the nodes storing it
+    /// do not ask for a cost of their own.
+    #[clap(long, default_value_t = 1)]
+    chunk_payment_nanos: u64,
+
+    /// The hex encoded address the network royalties are paid to, for networks requiring them.
+    #[clap(long)]
+    royalties_address: Option<String>,
+
+    /// The share of the payment for each chunk paid as royalties, in basis points.
+    #[clap(long, default_value_t = 0)]
+    royalty_basis_points: u64,
+}
+
+#[derive(Args, Debug)]
+pub(super) struct DownloadArgs {
+    /// The hex encoded address of the file, as printed by `files upload`.
+    address: String,
+
+    /// The path to write the file to.
+    dest: PathBuf,
+
+    /// The max number of chunks fetched ahead of the ones written to the file.
+    #[clap(long, default_value_t = 4)]
+    concurrency: usize,
+}
+
+pub(super) async fn run(opt: &Opt, cmd: &FilesCmd) -> Result<()> {
+    let client = opt.connect().await?;
+    match cmd {
+        FilesCmd::Upload(args) => upload(opt, client, args).await,
+        FilesCmd::Download(args) => download(opt, client, args).await,
+    }
+}
+
+async fn upload(opt: &Opt, client: Client, args: &UploadArgs) -> Result<()> {
+    let royalties = match &args.royalties_address {
+        Some(hex_address) => Some(RoyaltyPolicy {
+            address: decode(hex_address)?,
+            basis_points: args.royalty_basis_points,
+        }),
+        None => None,
+    };
+    let mut uploader = Uploader {
+        wallet: opt.load_wallet(client.clone()).await?,
+        client,
+        args,
+        royalties,
+        bar: progress_bar(0, "Uploading", opt.json),
+        chunks_stored: 0,
+        chunks_skipped: 0,
+    };
+
+    // The file is encrypted, paid for and uploaded a window at a time, its data map last,
+    // so that it is never held in memory as a whole.
+    let mut encryptor = StreamingEncryptor::from_path(&args.path).await?;
+    while let Some(chunks) = encryptor.next_chunks().await? {
+        uploader.upload(chunks).await?;
+    }
+    let data_map_chunk = encryptor.finish()?;
+    let address = *data_map_chunk.address();
+    uploader.upload(vec![data_map_chunk]).await?;
+    uploader.bar.finish_and_clear();
+
+    let address = to_hex(address.name());
+    if opt.json {
+        let output = json!({
+            "path": args.path,
+            "address": address,
+            "chunks_stored": uploader.chunks_stored,
+            "chunks_skipped": uploader.chunks_skipped,
+        });
+        println!("{output}");
+    } else {
+        println!("Uploaded {:?} to {address}", args.path);
+    }
+    Ok(())
+}
+
+// Pays for and uploads the chunks of a file, a window of them at a time.
+struct Uploader<'a> {
+    client: Client,
+    wallet: Wallet,
+    args: &'a UploadArgs,
+    royalties: Option<RoyaltyPolicy>,
+    bar: ProgressBar,
+    chunks_stored: usize,
+    chunks_skipped: usize,
+}
+
+impl Uploader<'_> {
+    async fn upload(&mut self, chunks: Vec<Chunk>) -> Result<()> {
+        // Chunks already held by the network, e.g. shared with files uploaded before, are not paid for.
+        let stored: BTreeSet<_> = self
+            .client
+            .stored_chunks(chunks.iter().map(|chunk| *chunk.address()))
+            .await
+            .into_iter()
+            .collect();
+        self.chunks_skipped += stored.len();
+        let to_upload: Vec<_> = chunks
+            .into_iter()
+            .filter(|chunk| !stored.contains(chunk.address()))
+            .collect();
+
+        self.bar.inc_length(to_upload.len() as u64);
+        self.bar.set_message("Paying");
+        let mut payments = pay_for_chunks(
+            &self.client,
+            &mut self.wallet,
+            &to_upload,
+            Token::from_nano(self.args.chunk_payment_nanos),
+            self.royalties.as_ref(),
+            || {},
+        )
+        .await?;
+
+        self.bar.set_message("Uploading");
+        let chunks = to_upload.into_iter().map(|chunk| {
+            let payment = payments.remove(chunk.name()).unwrap_or_default();
+            (chunk, payment)
+        });
+        let bar = &self.bar;
+        let report = self
+            .client
+            .upload_chunks_with(chunks, self.args.concurrency, |_| bar.inc(1))
+            .await;
+        self.chunks_stored += report.stored.len();
+        self.chunks_skipped += report.skipped.len();
+
+        match report.failed.first() {
+            Some((failed, err)) => Err(eyre!(
+                "Failed to upload {} chunks, e.g. {}: {err}",
+                report.failed.len(),
+                to_hex(failed.name())
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+async fn download(opt: &Opt, client: Client, args: &DownloadArgs) -> Result<()> {
+    let address = parse_address(&args.address)?;
+    let mut dest = File::create(&args.dest).await?;
+
+    let downloading = if opt.json {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new_spinner().with_message("Downloading")
+    };
+    if let Ok(style) = ProgressStyle::with_template("{spinner} {msg} {bytes} ({bytes_per_sec})") {
+        downloading.set_style(style);
+    }
+
+    let mut bytes_written = 0;
+    let mut content = Box::pin(client.read_bytes_stream_with(address, args.concurrency));
+    while let Some(bytes) = content.next().await {
+        let bytes = bytes?;
+        dest.write_all(&bytes).await?;
+        bytes_written += bytes.len();
+        downloading.inc(bytes.len() as u64);
+    }
+    dest.flush().await?;
+    downloading.finish_and_clear();
+
+    if opt.json {
+        let output = json!({
+            "address": args.address,
+            "dest": args.dest,
+            "bytes": bytes_written,
+        });
+        println!("{output}");
+    } else {
+        println!("Downloaded {} to {:?}", args.address, args.dest);
+    }
+    Ok(())
+}
+
+// Pays for the storage of each chunk the costs asked by its close group, or `fallback` when
+// none is asked, returning the proofs of payment by chunk name.
+pub(super) async fn pay_for_chunks(
+    client: &Client,
+    wallet: &mut Wallet,
+    chunks: &[Chunk],
+    fallback: Token,
+    royalties: Option<&RoyaltyPolicy>,
+    mut on_paid: impl FnMut(),
+) -> Result<BTreeMap<XorName, PaymentProof>> {
+    let mut payments = BTreeMap::new();
+    for chunk in chunks {
+        let costs = client.get_store_costs(*chunk.name()).await?;
+        let payment = wallet
+            .pay_store_costs(chunk.name(), &costs, fallback, royalties)
+            .await?;
+        let _ = payments.insert(*chunk.name(), payment);
+        on_paid();
+    }
+    Ok(payments)
+}
+
+// A bar counting the chunks processed, hidden when the output is JSON.
+fn progress_bar(len: usize, msg: &'static str, json: bool) -> ProgressBar {
+    if json {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len as u64).with_message(msg);
+    if let Ok(style) = ProgressStyle::with_template("{msg} [{bar:40}] {pos}/{len} chunks") {
+        bar.set_style(style);
+    }
+    bar
+}
+
+pub(super) fn parse_address(hex_address: &str) -> Result<ChunkAddress> {
+    let bytes = hex::decode(hex_address).map_err(|err| eyre!("Invalid address: {err}"))?;
+    let name: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| eyre!("Invalid address: expected 32 bytes"))?;
+    Ok(ChunkAddress(XorName(name)))
+}
+
+fn to_hex(name: &XorName) -> String {
+    hex::encode(name.0)
+}
@@ -0,0 +1,110 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+mod daemon;
+mod files;
+#[cfg(all(unix, feature = "fuse"))]
+mod mount;
+mod wallet;
+
+use self::{daemon::DaemonArgs, files::FilesCmd, wallet::WalletCmd};
+use clap::{Parser, Subcommand};
+use eyre::{eyre, Result};
+use libp2p::Multiaddr;
+use safenode::{
+    client::{Client, Wallet, CONNECT_TIMEOUT},
+    network::NetworkSwarmLoop,
+};
+use std::path::PathBuf;
+use tracing_subscriber::EnvFilter;
+
+/// The wallet dir used by default, relative to the home dir.
+const DEFAULT_WALLET_DIR: &str = ".safe/client/wallet";
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    let opt = Opt::parse();
+    // Logs go to stderr, leaving stdout to the output of the command.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    match opt.cmd {
+        Cmd::Files(ref cmd) => files::run(&opt, cmd).await,
+        Cmd::Wallet(ref cmd) => wallet::run(&opt, cmd).await,
+        Cmd::Daemon(ref args) => daemon::run(&opt, args).await,
+        #[cfg(all(unix, feature = "fuse"))]
+        Cmd::Mount(ref args) => mount::run(&opt, args).await,
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(
+    name = "safe",
+    version,
+    about = "Store and get data on the SAFE Network"
+)]
+struct Opt {
+    /// The address of a peer to bootstrap from, ending with its `/p2p/<peer id>`.
+    /// Can be given multiple times. Peers on the local network are found without it.
+    #[clap(long = "peer", global = true)]
+    bootstrap_peers: Vec<Multiaddr>,
+
+    /// The wallet to use, instead of `~/.safe/client/wallet`.
+    #[clap(long, global = true)]
+    wallet_dir: Option<PathBuf>,
+
+    /// Print the result as JSON on stdout, for scripts to consume.
+    #[clap(long, global = true)]
+    json: bool,
+
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Upload and download files.
+    #[clap(subcommand)]
+    Files(FilesCmd),
+    /// Manage the local wallet and transfer tokens.
+    #[clap(subcommand)]
+    Wallet(WalletCmd),
+    /// Serve the operations of the client over JSON-RPC, for other local tooling to use.
+    Daemon(DaemonArgs),
+    /// Mount the folder at the given address on a local dir, uploading the changes made to
+    /// it once unmounted.
+    #[cfg(all(unix, feature = "fuse"))]
+    Mount(mount::MountArgs),
+}
+
+impl Opt {
+    // Connects a client to the network through the given peers, waiting for one of them, or
+    // a peer found on the local network, to be in its routing table.
+    async fn connect(&self) -> Result<Client> {
+        Ok(Client::connect(&self.bootstrap_peers, CONNECT_TIMEOUT).await?)
+    }
+
+    // A client for the commands which only read the local wallet, without connecting to the network.
+    fn offline_client(&self) -> Result<Client> {
+        let (network, _events, _swarm_loop) = NetworkSwarmLoop::new_client()?;
+        Ok(Client::new(network))
+    }
+
+    // Loads the wallet, creating it if there is none yet.
+    async fn load_wallet(&self, client: Client) -> Result<Wallet> {
+        let wallet_dir = match &self.wallet_dir {
+            Some(dir) => dir.clone(),
+            None => dirs_next::home_dir()
+                .map(|home| home.join(DEFAULT_WALLET_DIR))
+                .ok_or_else(|| eyre!("Could not find the home dir"))?,
+        };
+        Ok(Wallet::load_from(client, &wallet_dir).await?)
+    }
+}
@@ -0,0 +1,814 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Exposes a folder of the network as a FUSE filesystem.
+//!
+//! Folders are immutable, so the changes made through the mount are kept in memory and only
+//! uploaded once it is unmounted, with `fusermount -u <dir>` (or `umount <dir>` on macOS),
+//! the address of the new root folder being printed then.
+
+use super::{
+    files::{parse_address, pay_for_chunks},
+    Opt,
+};
+use async_std::task::{block_on, spawn_blocking};
+use bytes::Bytes;
+use clap::Args;
+use eyre::{eyre, Result};
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
+    ReplyEmpty, ReplyEntry, ReplyWrite, Request, TimeOrNow,
+};
+use libc::{EEXIST, EINVAL, EIO, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY};
+use safenode::{
+    client::{Client, EncryptedFile, Error as ClientError, Folder, FolderEntry, VersionedDataMap},
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use sn_dbc::Token;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::OsStr,
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, info, warn};
+
+/// The inode of the root folder, as FUSE expects it.
+const ROOT_INO: u64 = 1;
+/// How long the kernel may cache the attributes and entries it is given.
+const TTL: Duration = Duration::from_secs(1);
+/// The block size reported for the files.
+const BLOCK_SIZE: u32 = 512;
+/// The data maps cache dir used by default, relative to the home dir.
+const DEFAULT_CACHE_DIR: &str = ".safe/client/data_maps";
+
+#[derive(Args, Debug)]
+pub(super) struct MountArgs {
+    /// The hex encoded address of the folder to mount.
+    address: String,
+
+    /// The dir to mount the folder on.
+    dir: PathBuf,
+
+    /// Mount the folder read-only, leaving nothing to upload on unmount.
+    #[clap(long)]
+    read_only: bool,
+
+    /// The amount paid for the storage of each chunk written, in nanos, when the nodes
+    /// storing it do not ask for a cost of their own.
+    #[clap(long, default_value_t = 1)]
+    chunk_payment_nanos: u64,
+
+    /// The max number of chunks uploaded at a time on unmount.
+    #[clap(long, default_value_t = 32)]
+    concurrency: usize,
+}
+
+pub(super) async fn run(opt: &Opt, args: &MountArgs) -> Result<()> {
+    let root = parse_address(&args.address)?;
+    let client = opt.connect().await?;
+    let cache_dir = dirs_next::home_dir()
+        .map(|home| home.join(DEFAULT_CACHE_DIR))
+        .ok_or_else(|| eyre!("Could not find the home dir"))?;
+    std::fs::create_dir_all(&cache_dir)?;
+    // The files are owned by the owner of the dir they are mounted on.
+    let dir_metadata = std::fs::metadata(&args.dir)?;
+
+    let tree = Arc::new(Mutex::new(Tree::new(root)));
+    let fs = MountFs {
+        client: client.clone(),
+        tree: Arc::clone(&tree),
+        data_maps: DataMapCache {
+            dir: cache_dir,
+            maps: HashMap::new(),
+        },
+        read_only: args.read_only,
+        uid: dir_metadata.uid(),
+        gid: dir_metadata.gid(),
+    };
+    let mut options = vec![MountOption::FSName("safe".to_string())];
+    if args.read_only {
+        options.push(MountOption::RO);
+    }
+    let dir = args.dir.clone();
+    info!("Mounting {} on {dir:?}", args.address);
+    // The filesystem calls back into the client from the thread of the mount, blocking on it.
+    spawn_blocking(move || fuser::mount2(fs, &dir, &options)).await?;
+
+    let mut chunks = vec![];
+    let address = {
+        let tree = tree
+            .lock()
+            .map_err(|_| eyre!("The mounted tree was poisoned"))?;
+        if !tree.is_modified() {
+            println!("Unmounted {}, nothing changed", args.address);
+            return Ok(());
+        }
+        let mut skipped = vec![];
+        let address = tree.encode_dir(ROOT_INO, "", &mut chunks, &mut skipped)?;
+        for (path, err) in skipped {
+            println!("Could not encrypt {path}, it is left out of the folder: {err}");
+        }
+        address
+    };
+    upload(opt, &client, chunks, args).await?;
+    println!(
+        "Unmounted {}, the folder is now at {}",
+        args.address,
+        hex::encode(address.name())
+    );
+    Ok(())
+}
+
+// Pays for the chunks not yet held by the network, and uploads them.
+async fn upload(opt: &Opt, client: &Client, chunks: Vec<Chunk>, args: &MountArgs) -> Result<()> {
+    let stored: BTreeSet<_> = client
+        .stored_chunks(chunks.iter().map(|chunk| *chunk.address()))
+        .await
+        .into_iter()
+        .collect();
+    let to_upload: Vec<_> = chunks
+        .into_iter()
+        .filter(|chunk| !stored.contains(chunk.address()))
+        .collect();
+    info!(
+        "Uploading the {} chunks written to the mount",
+        to_upload.len()
+    );
+
+    let mut wallet = opt.load_wallet(client.clone()).await?;
+    let mut payments = pay_for_chunks(
+        client,
+        &mut wallet,
+        &to_upload,
+        Token::from_nano(args.chunk_payment_nanos),
+        None,
+        || {},
+    )
+    .await?;
+    let chunks = to_upload.into_iter().map(|chunk| {
+        let payment = payments.remove(chunk.name()).unwrap_or_default();
+        (chunk, payment)
+    });
+    let report = client
+        .upload_chunks_with(chunks, args.concurrency, |_| {})
+        .await;
+    match report.failed.first() {
+        Some((failed, err)) => Err(eyre!(
+            "Failed to upload {} chunks, e.g. {}: {err}",
+            report.failed.len(),
+            hex::encode(failed.name())
+        )),
+        None => Ok(()),
+    }
+}
+
+// The files and dirs of the mount, by inode.
+struct Tree {
+    nodes: HashMap<u64, Node>,
+    next_ino: u64,
+}
+
+struct Node {
+    parent: u64,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Dir {
+        // The address of the folder as stored on the network, none for dirs made in the mount.
+        address: Option<ChunkAddress>,
+        // The inodes of the entries by name, none until the folder is fetched.
+        children: Option<BTreeMap<String, u64>>,
+        // Whether the dir, or any dir under it, was changed since it was fetched.
+        modified: bool,
+    },
+    File(FileContent),
+}
+
+enum FileContent {
+    Stored { address: ChunkAddress, size: u64 },
+    // The content of a file written to in the mount, held until it is unmounted.
+    Written(Vec<u8>),
+}
+
+impl Tree {
+    fn new(root: ChunkAddress) -> Self {
+        let root = Node {
+            parent: ROOT_INO,
+            kind: NodeKind::Dir {
+                address: Some(root),
+                children: None,
+                modified: false,
+            },
+        };
+        Self {
+            nodes: HashMap::from([(ROOT_INO, root)]),
+            next_ino: ROOT_INO + 1,
+        }
+    }
+
+    fn is_modified(&self) -> bool {
+        matches!(
+            self.nodes.get(&ROOT_INO),
+            Some(Node {
+                kind: NodeKind::Dir { modified: true, .. },
+                ..
+            })
+        )
+    }
+
+    fn insert(&mut self, parent: u64, kind: NodeKind) -> u64 {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        let _ = self.nodes.insert(ino, Node { parent, kind });
+        ino
+    }
+
+    // The inodes of the entries of the dir, if it was fetched already.
+    fn children(&self, ino: u64) -> Result<Option<&BTreeMap<String, u64>>, i32> {
+        match self.nodes.get(&ino).map(|node| &node.kind) {
+            Some(NodeKind::Dir { children, .. }) => Ok(children.as_ref()),
+            Some(NodeKind::File(_)) => Err(ENOTDIR),
+            None => Err(ENOENT),
+        }
+    }
+
+    fn children_mut(&mut self, ino: u64) -> Result<&mut BTreeMap<String, u64>, i32> {
+        match self.nodes.get_mut(&ino).map(|node| &mut node.kind) {
+            Some(NodeKind::Dir {
+                children: Some(children),
+                ..
+            }) => Ok(children),
+            Some(NodeKind::Dir { children: None, .. }) => Err(EIO),
+            Some(NodeKind::File(_)) => Err(ENOTDIR),
+            None => Err(ENOENT),
+        }
+    }
+
+    // Flags the dir and the dirs above it as changed, for them to be uploaded on unmount.
+    fn mark_modified(&mut self, mut ino: u64) {
+        loop {
+            let parent = match self.nodes.get_mut(&ino) {
+                Some(Node {
+                    parent,
+                    kind: NodeKind::Dir { modified, .. },
+                }) => {
+                    *modified = true;
+                    *parent
+                }
+                _ => return,
+            };
+            if ino == ROOT_INO {
+                return;
+            }
+            ino = parent;
+        }
+    }
+
+    // Encrypts the files written and builds the folders changed under the dir, appending
+    // their chunks to `chunks`. Returns the address of the folder of the dir.
+    //
+    // A file written that fails to be encrypted is left out of its folder and added to
+    // `skipped` along with the error, for the rest of the tree not to be lost with it.
+    fn encode_dir(
+        &self,
+        ino: u64,
+        path: &str,
+        chunks: &mut Vec<Chunk>,
+        skipped: &mut Vec<(String, ClientError)>,
+    ) -> Result<ChunkAddress> {
+        let (address, children) = match self.nodes.get(&ino).map(|node| &node.kind) {
+            Some(NodeKind::Dir {
+                address: Some(address),
+                modified: false,
+                ..
+            }) => return Ok(*address),
+            Some(NodeKind::Dir {
+                address, children, ..
+            }) => (address, children),
+            _ => return Err(eyre!("Inode {ino} is not a dir")),
+        };
+        let children = match children {
+            Some(children) => children,
+            // A dir is only changed once it has been fetched.
+            None => return (*address).ok_or_else(|| eyre!("Dir {ino} was never fetched")),
+        };
+
+        let mut folder = Folder::default();
+        for (name, child) in children {
+            let child_path = format!("{path}/{name}");
+            let entry = match self.nodes.get(child).map(|node| &node.kind) {
+                Some(NodeKind::Dir { .. }) => {
+                    FolderEntry::Folder(self.encode_dir(*child, &child_path, chunks, skipped)?)
+                }
+                Some(NodeKind::File(FileContent::Stored { address, size })) => FolderEntry::File {
+                    address: *address,
+                    size: *size,
+                },
+                Some(NodeKind::File(FileContent::Written(bytes))) => {
+                    match EncryptedFile::new(Bytes::from(bytes.clone())) {
+                        Ok(file) => {
+                            chunks.extend(file.chunks);
+                            FolderEntry::File {
+                                address: file.address,
+                                size: bytes.len() as u64,
+                            }
+                        }
+                        Err(err) => {
+                            warn!("Could not encrypt {child_path}: {err}");
+                            skipped.push((child_path, err));
+                            continue;
+                        }
+                    }
+                }
+                None => continue,
+            };
+            let _ = folder.insert(name.clone(), entry);
+        }
+        let chunk = folder.to_chunk()?;
+        let address = *chunk.address();
+        chunks.push(chunk);
+        Ok(address)
+    }
+}
+
+// The data maps of the files read, kept on disk for the files to be read again without
+// fetching them. Data maps are immutable, as are the files they describe.
+struct DataMapCache {
+    dir: PathBuf,
+    maps: HashMap<ChunkAddress, VersionedDataMap>,
+}
+
+impl DataMapCache {
+    fn get(&mut self, client: &Client, address: ChunkAddress) -> Result<&VersionedDataMap, i32> {
+        if !self.maps.contains_key(&address) {
+            let data_map = self.load(client, address)?;
+            let _ = self.maps.insert(address, data_map);
+        }
+        self.maps.get(&address).ok_or(EIO)
+    }
+
+    fn load(&self, client: &Client, address: ChunkAddress) -> Result<VersionedDataMap, i32> {
+        let path = self.dir.join(hex::encode(address.name()));
+        if let Ok(bytes) = std::fs::read(&path) {
+            match VersionedDataMap::from_chunk(&Chunk::new(Bytes::from(bytes))) {
+                Ok(data_map) => return Ok(data_map),
+                Err(err) => warn!("Ignoring the cached data map at {path:?}: {err}"),
+            }
+        }
+        let data_map = block_on(client.get_file_data_map(address)).map_err(errno)?;
+        match data_map.to_chunk() {
+            Ok(chunk) => {
+                if let Err(err) = std::fs::write(&path, chunk.value()) {
+                    warn!("Failed to cache the data map of {address:?}: {err}");
+                }
+            }
+            Err(err) => warn!("Failed to cache the data map of {address:?}: {err}"),
+        }
+        Ok(data_map)
+    }
+}
+
+struct MountFs {
+    client: Client,
+    tree: Arc<Mutex<Tree>>,
+    data_maps: DataMapCache,
+    read_only: bool,
+    uid: u32,
+    gid: u32,
+}
+
+impl MountFs {
+    // Runs the operation on the tree, fetching the entries of the dir first if it has not
+    // been yet.
+    fn with_dir<T>(
+        &self,
+        ino: u64,
+        op: impl FnOnce(&mut Tree) -> Result<T, i32>,
+    ) -> Result<T, i32> {
+        let mut tree = self.tree.lock().map_err(|_| EIO)?;
+        if tree.children(ino)?.is_none() {
+            let address = match tree.nodes.get(&ino).map(|node| &node.kind) {
+                Some(NodeKind::Dir {
+                    address: Some(address),
+                    ..
+                }) => *address,
+                _ => return Err(EIO),
+            };
+            debug!("Fetching folder {address:?} of inode {ino}");
+            let folder = block_on(self.client.get_folder(address)).map_err(errno)?;
+            let mut children = BTreeMap::new();
+            for (name, entry) in folder.entries() {
+                let kind = match entry {
+                    FolderEntry::Folder(address) => NodeKind::Dir {
+                        address: Some(*address),
+                        children: None,
+                        modified: false,
+                    },
+                    FolderEntry::File { address, size } => NodeKind::File(FileContent::Stored {
+                        address: *address,
+                        size: *size,
+                    }),
+                };
+                let _ = children.insert(name.clone(), tree.insert(ino, kind));
+            }
+            if let Some(Node {
+                kind: NodeKind::Dir { children: dir, .. },
+                ..
+            }) = tree.nodes.get_mut(&ino)
+            {
+                *dir = Some(children);
+            }
+        }
+        op(&mut tree)
+    }
+
+    // The inode of the entry of the dir with the given name.
+    fn lookup_ino(&self, parent: u64, name: &OsStr) -> Result<u64, i32> {
+        let name = name.to_str().ok_or(EINVAL)?;
+        self.with_dir(parent, |tree| {
+            tree.children_mut(parent)?.get(name).copied().ok_or(ENOENT)
+        })
+    }
+
+    fn attr(&self, ino: u64) -> Result<FileAttr, i32> {
+        let tree = self.tree.lock().map_err(|_| EIO)?;
+        let node = tree.nodes.get(&ino).ok_or(ENOENT)?;
+        let (kind, size, perm) = match &node.kind {
+            NodeKind::Dir { .. } => (FileType::Directory, 0, 0o755),
+            NodeKind::File(FileContent::Stored { size, .. }) => {
+                (FileType::RegularFile, *size, 0o644)
+            }
+            NodeKind::File(FileContent::Written(bytes)) => {
+                (FileType::RegularFile, bytes.len() as u64, 0o644)
+            }
+        };
+        let perm = if self.read_only { perm & 0o555 } else { perm };
+        // The network keeps no times, the files are as old as the epoch.
+        Ok(FileAttr {
+            ino,
+            size,
+            blocks: (size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64,
+            atime: SystemTime::UNIX_EPOCH,
+            mtime: SystemTime::UNIX_EPOCH,
+            ctime: SystemTime::UNIX_EPOCH,
+            crtime: SystemTime::UNIX_EPOCH,
+            kind,
+            perm,
+            nlink: 1,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        })
+    }
+
+    // The content of the file, fetched in full, for it to be written to.
+    fn written_content(&self, ino: u64) -> Result<(), i32> {
+        let stored = {
+            let tree = self.tree.lock().map_err(|_| EIO)?;
+            match tree.nodes.get(&ino).map(|node| &node.kind) {
+                Some(NodeKind::File(FileContent::Stored { address, .. })) => *address,
+                Some(NodeKind::File(FileContent::Written(_))) => return Ok(()),
+                Some(NodeKind::Dir { .. }) => return Err(EISDIR),
+                None => return Err(ENOENT),
+            }
+        };
+        let bytes = block_on(self.client.read_file(stored)).map_err(errno)?;
+        let mut tree = self.tree.lock().map_err(|_| EIO)?;
+        let parent = match tree.nodes.get_mut(&ino) {
+            Some(node) => {
+                node.kind = NodeKind::File(FileContent::Written(bytes.to_vec()));
+                node.parent
+            }
+            None => return Err(ENOENT),
+        };
+        tree.mark_modified(parent);
+        Ok(())
+    }
+
+    // Applies the change to the content of the file, fetching it first if it was not written yet.
+    fn write_to(&self, ino: u64, change: impl FnOnce(&mut Vec<u8>)) -> Result<(), i32> {
+        if self.read_only {
+            return Err(libc::EROFS);
+        }
+        self.written_content(ino)?;
+        let mut tree = self.tree.lock().map_err(|_| EIO)?;
+        match tree.nodes.get_mut(&ino).map(|node| &mut node.kind) {
+            Some(NodeKind::File(FileContent::Written(bytes))) => {
+                change(bytes);
+                Ok(())
+            }
+            _ => Err(EIO),
+        }
+    }
+
+    // Adds an entry to the dir, failing if one of the same name exists.
+    fn add_entry(&self, parent: u64, name: &OsStr, kind: NodeKind) -> Result<u64, i32> {
+        if self.read_only {
+            return Err(libc::EROFS);
+        }
+        let name = name.to_str().ok_or(EINVAL)?.to_string();
+        self.with_dir(parent, |tree| {
+            if tree.children_mut(parent)?.contains_key(&name) {
+                return Err(EEXIST);
+            }
+            let ino = tree.insert(parent, kind);
+            let _ = tree.children_mut(parent)?.insert(name, ino);
+            tree.mark_modified(parent);
+            Ok(ino)
+        })
+    }
+
+    // Removes the entry of the dir, the dirs removed having to be empty.
+    fn remove_entry(&self, parent: u64, name: &OsStr, is_dir: bool) -> Result<(), i32> {
+        if self.read_only {
+            return Err(libc::EROFS);
+        }
+        let ino = self.lookup_ino(parent, name)?;
+        if is_dir {
+            let is_empty = self.with_dir(ino, |tree| Ok(tree.children_mut(ino)?.is_empty()))?;
+            if !is_empty {
+                return Err(ENOTEMPTY);
+            }
+        }
+        let name = name.to_str().ok_or(EINVAL)?;
+        let mut tree = self.tree.lock().map_err(|_| EIO)?;
+        match (tree.nodes.get(&ino).map(|node| &node.kind), is_dir) {
+            (Some(NodeKind::Dir { .. }), false) => return Err(EISDIR),
+            (Some(NodeKind::File(_)), true) => return Err(ENOTDIR),
+            _ => {}
+        }
+        let _ = tree.children_mut(parent)?.remove(name);
+        let _ = tree.nodes.remove(&ino);
+        tree.mark_modified(parent);
+        Ok(())
+    }
+}
+
+impl Filesystem for MountFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.lookup_ino(parent, name).and_then(|ino| self.attr(ino)) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    // Only the size can be set, to truncate or extend the file.
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        let result = match size {
+            Some(size) => self.write_to(ino, |bytes| bytes.resize(size as usize, 0)),
+            None => Ok(()),
+        };
+        match result.and_then(|_| self.attr(ino)) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let entries = self.with_dir(ino, |tree| {
+            let parent = tree.nodes.get(&ino).map(|node| node.parent).ok_or(ENOENT)?;
+            let mut entries = vec![
+                (ino, FileType::Directory, ".".to_string()),
+                (parent, FileType::Directory, "..".to_string()),
+            ];
+            for (name, child) in tree.children(ino)?.ok_or(EIO)? {
+                let kind = match tree.nodes.get(child).map(|node| &node.kind) {
+                    Some(NodeKind::Dir { .. }) => FileType::Directory,
+                    _ => FileType::RegularFile,
+                };
+                entries.push((*child, kind, name.clone()));
+            }
+            Ok(entries)
+        });
+        match entries {
+            Ok(entries) => {
+                for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize)
+                {
+                    // The offset given is the one of the next entry.
+                    if reply.add(ino, (i + 1) as i64, kind, name) {
+                        break;
+                    }
+                }
+                reply.ok()
+            }
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let offset = offset.max(0) as u64;
+        let stored = {
+            let tree = match self.tree.lock() {
+                Ok(tree) => tree,
+                Err(_) => return reply.error(EIO),
+            };
+            match tree.nodes.get(&ino).map(|node| &node.kind) {
+                Some(NodeKind::File(FileContent::Stored { address, .. })) => *address,
+                Some(NodeKind::File(FileContent::Written(bytes))) => {
+                    let start = (offset as usize).min(bytes.len());
+                    let end = start.saturating_add(size as usize).min(bytes.len());
+                    return reply.data(&bytes[start..end]);
+                }
+                Some(NodeKind::Dir { .. }) => return reply.error(EISDIR),
+                None => return reply.error(ENOENT),
+            }
+        };
+        let client = self.client.clone();
+        let bytes = self.data_maps.get(&client, stored).and_then(|data_map| {
+            block_on(client.read_file_range(data_map, offset, size as u64)).map_err(errno)
+        });
+        match bytes {
+            Ok(bytes) => reply.data(&bytes),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let offset = offset.max(0) as usize;
+        let result = self.write_to(ino, |bytes| {
+            let end = offset + data.len();
+            if bytes.len() < end {
+                bytes.resize(end, 0);
+            }
+            bytes[offset..end].copy_from_slice(data);
+        });
+        match result {
+            Ok(()) => reply.written(data.len() as u32),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let file = NodeKind::File(FileContent::Written(vec![]));
+        match self
+            .add_entry(parent, name, file)
+            .and_then(|ino| self.attr(ino))
+        {
+            Ok(attr) => reply.created(&TTL, &attr, 0, 0, flags as u32),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let dir = NodeKind::Dir {
+            address: None,
+            children: Some(BTreeMap::new()),
+            modified: true,
+        };
+        match self
+            .add_entry(parent, name, dir)
+            .and_then(|ino| self.attr(ino))
+        {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove_entry(parent, name, false) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove_entry(parent, name, true) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        let result = (|| {
+            if self.read_only {
+                return Err(libc::EROFS);
+            }
+            let ino = self.lookup_ino(parent, name)?;
+            let new_name = newname.to_str().ok_or(EINVAL)?.to_string();
+            // Both dirs are fetched before the tree is changed.
+            self.with_dir(newparent, |_| Ok(()))?;
+            let mut tree = self.tree.lock().map_err(|_| EIO)?;
+            if let Some(replaced) = tree.children_mut(newparent)?.get(&new_name).copied() {
+                if matches!(
+                    tree.nodes.get(&replaced).map(|node| &node.kind),
+                    Some(NodeKind::Dir { .. })
+                ) {
+                    return Err(EEXIST);
+                }
+                let _ = tree.nodes.remove(&replaced);
+            }
+            let _ = tree
+                .children_mut(parent)?
+                .remove(name.to_str().ok_or(EINVAL)?);
+            let _ = tree.children_mut(newparent)?.insert(new_name, ino);
+            if let Some(node) = tree.nodes.get_mut(&ino) {
+                node.parent = newparent;
+            }
+            tree.mark_modified(parent);
+            tree.mark_modified(newparent);
+            Ok(())
+        })();
+        match result {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+}
+
+// The errno telling the kernel why a request to the network failed.
+fn errno(err: ClientError) -> i32 {
+    warn!("Failed to serve the mount: {err}");
+    match err {
+        ClientError::NotFound(_) | ClientError::PathNotFound(_) => ENOENT,
+        _ => EIO,
+    }
+}
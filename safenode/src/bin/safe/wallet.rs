@@ -0,0 +1,125 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Opt;
+use clap::{Args, Subcommand};
+use eyre::{eyre, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use sn_dbc::{Dbc, PublicAddress, Token};
+use std::str::FromStr;
+
+#[derive(Subcommand, Debug)]
+pub(super) enum WalletCmd {
+    /// Print the address other wallets send tokens to for this wallet to receive them.
+    Address,
+    /// Print the amount of tokens held by the wallet.
+    Balance,
+    /// Send tokens to the given address, printing the transfer the recipient receives them with.
+    Send(SendArgs),
+    /// Add the tokens of a transfer sent to this wallet to the ones it holds.
+    Receive(ReceiveArgs),
+}
+
+#[derive(Args, Debug)]
+pub(super) struct SendArgs {
+    /// The amount of tokens to send, e.g. `1.5`.
+    amount: String,
+
+    /// The hex encoded address of the recipient, as printed by `wallet address`.
+    address: String,
+}
+
+#[derive(Args, Debug)]
+pub(super) struct ReceiveArgs {
+    /// The hex encoded transfer, as printed by `wallet send`.
+    transfer: String,
+}
+
+pub(super) async fn run(opt: &Opt, cmd: &WalletCmd) -> Result<()> {
+    match cmd {
+        WalletCmd::Address => address(opt).await,
+        WalletCmd::Balance => balance(opt).await,
+        WalletCmd::Send(args) => send(opt, args).await,
+        WalletCmd::Receive(args) => receive(opt, args).await,
+    }
+}
+
+async fn address(opt: &Opt) -> Result<()> {
+    let wallet = opt.load_wallet(opt.offline_client()?).await?;
+    let address = encode(&wallet.address())?;
+    if opt.json {
+        println!("{}", json!({ "address": address }));
+    } else {
+        println!("{address}");
+    }
+    Ok(())
+}
+
+async fn balance(opt: &Opt) -> Result<()> {
+    let wallet = opt.load_wallet(opt.offline_client()?).await?;
+    print_balance(opt, wallet.balance()?);
+    Ok(())
+}
+
+async fn send(opt: &Opt, args: &SendArgs) -> Result<()> {
+    let amount = Token::from_str(&args.amount)
+        .map_err(|err| eyre!("Invalid amount {}: {err}", args.amount))?;
+    let recipient: PublicAddress = decode(&args.address)?;
+
+    let mut wallet = opt.load_wallet(opt.connect().await?).await?;
+    let dbc = wallet.send(amount, recipient).await?;
+    let transfer = encode(&dbc)?;
+    if opt.json {
+        let output = json!({
+            "amount": amount.to_string(),
+            "transfer": transfer,
+            "balance": wallet.balance()?.to_string(),
+        });
+        println!("{output}");
+    } else {
+        eprintln!("Sent {amount} tokens, to be received with the transfer below:");
+        println!("{transfer}");
+    }
+    Ok(())
+}
+
+async fn receive(opt: &Opt, args: &ReceiveArgs) -> Result<()> {
+    let dbc: Dbc = decode(&args.transfer)?;
+
+    // The Dbc is checked not to be spent already, which requires the network.
+    let mut wallet = opt.load_wallet(opt.connect().await?).await?;
+    let before = wallet.balance()?;
+    wallet.receive(vec![dbc]).await?;
+    let after = wallet.balance()?;
+    if after == before {
+        return Err(eyre!(
+            "The transfer was not received, as it is either sent to another address or already spent"
+        ));
+    }
+    print_balance(opt, after);
+    Ok(())
+}
+
+fn print_balance(opt: &Opt, balance: Token) {
+    if opt.json {
+        println!("{}", json!({ "balance": balance.to_string() }));
+    } else {
+        println!("{balance}");
+    }
+}
+
+// Addresses and transfers are handed around as hex encoded strings, to be copied and pasted.
+pub(super) fn encode<T: Serialize>(value: &T) -> Result<String> {
+    Ok(hex::encode(rmp_serde::to_vec(value)?))
+}
+
+pub(super) fn decode<T: DeserializeOwned>(hex_value: &str) -> Result<T> {
+    let bytes = hex::decode(hex_value.trim()).map_err(|err| eyre!("Invalid hex: {err}"))?;
+    Ok(rmp_serde::from_slice(&bytes)?)
+}
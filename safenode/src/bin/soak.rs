@@ -0,0 +1,405 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Drives a testnet with a mixed workload of puts, gets, register edits and transfers for
+//! hours, for the qualification of releases.
+//!
+//! The latency percentiles and the error rate of each kind of operation are logged, and
+//! written to a CSV file, for each reporting interval and for the whole run.
+
+use async_std::task::{sleep, spawn};
+use bytes::Bytes;
+use clap::Parser;
+use eyre::{eyre, Result};
+use libp2p::Multiaddr;
+use rand::Rng;
+use safenode::{
+    client::{Client, ClientRegister, Wallet, CONNECT_TIMEOUT},
+    protocol::types::{payment::PaymentProof, register::RegisterAddress},
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use sn_dbc::Token;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+use xor_name::XorName;
+
+/// The tag of the registers edited during the run.
+const SOAK_REGISTER_TAG: u64 = 0x50a4;
+/// The amount paid for the storage of each chunk and register when no node asks for a cost,
+/// and sent with each transfer.
+const PAYMENT_NANOS: u64 = 1;
+
+#[derive(Parser, Debug)]
+#[clap(
+    name = "soak",
+    version,
+    about = "Drive a testnet with a mixed workload"
+)]
+struct Opt {
+    /// The address of a peer to bootstrap from, ending with its `/p2p/<peer id>`.
+    /// Can be given multiple times. Peers on the local network are found without it.
+    #[clap(long = "peer")]
+    bootstrap_peers: Vec<Multiaddr>,
+
+    /// The wallet paying for the storage and making the transfers, which must hold enough
+    /// for the whole run.
+    #[clap(long)]
+    wallet_dir: PathBuf,
+
+    /// How long to run for, in seconds.
+    #[clap(long, default_value_t = 4 * 3600)]
+    duration_secs: u64,
+
+    /// The number of operations run at once.
+    #[clap(long, default_value_t = 4)]
+    concurrency: usize,
+
+    /// The size of the chunks put, in bytes.
+    #[clap(long, default_value_t = 64 * 1024)]
+    chunk_size: usize,
+
+    /// The relative weight of puts in the workload.
+    #[clap(long, default_value_t = 4)]
+    put_weight: u32,
+
+    /// The relative weight of gets in the workload.
+    #[clap(long, default_value_t = 4)]
+    get_weight: u32,
+
+    /// The relative weight of register edits in the workload.
+    #[clap(long, default_value_t = 1)]
+    register_weight: u32,
+
+    /// The relative weight of transfers in the workload.
+    #[clap(long, default_value_t = 1)]
+    transfer_weight: u32,
+
+    /// The number of seconds between two reports.
+    #[clap(long, default_value_t = 60)]
+    report_interval_secs: u64,
+
+    /// The CSV file the reports are written to.
+    #[clap(long, default_value = "soak.csv")]
+    csv: PathBuf,
+}
+
+// The kinds of operations of the workload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Op {
+    Put,
+    Get,
+    RegisterEdit,
+    Transfer,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Op::Put => "put",
+            Op::Get => "get",
+            Op::RegisterEdit => "register_edit",
+            Op::Transfer => "transfer",
+        };
+        f.write_str(name)
+    }
+}
+
+// The outcomes of the operations of a kind.
+#[derive(Default)]
+struct OpStats {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl OpStats {
+    fn record(&mut self, latency: Duration, ok: bool) {
+        self.latencies.push(latency);
+        if !ok {
+            self.errors += 1;
+        }
+    }
+
+    // The latency below which the given percentage of the operations completed.
+    fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+        if sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        let index = (sorted.len() * percent / 100).min(sorted.len() - 1);
+        sorted[index]
+    }
+
+    // The CSV columns of the stats, from the count of operations on.
+    fn csv_columns(&self) -> String {
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let count = sorted.len();
+        let error_rate = if count == 0 {
+            0.0
+        } else {
+            self.errors as f64 / count as f64
+        };
+        let ms = |latency: Duration| latency.as_millis();
+        format!(
+            "{count},{},{error_rate:.4},{},{},{},{}",
+            self.errors,
+            ms(Self::percentile(&sorted, 50)),
+            ms(Self::percentile(&sorted, 90)),
+            ms(Self::percentile(&sorted, 99)),
+            ms(sorted.last().copied().unwrap_or_default()),
+        )
+    }
+}
+
+// Records the outcomes of the operations, for the current interval and the whole run.
+struct Recorder {
+    started: Instant,
+    interval: BTreeMap<Op, OpStats>,
+    total: BTreeMap<Op, OpStats>,
+    csv: BufWriter<File>,
+}
+
+impl Recorder {
+    fn new(csv: File) -> Result<Self> {
+        let mut csv = BufWriter::new(csv);
+        writeln!(
+            csv,
+            "elapsed_secs,window,op,count,errors,error_rate,p50_ms,p90_ms,p99_ms,max_ms"
+        )?;
+        Ok(Self {
+            started: Instant::now(),
+            interval: BTreeMap::new(),
+            total: BTreeMap::new(),
+            csv,
+        })
+    }
+
+    fn record(&mut self, op: Op, latency: Duration, ok: bool) {
+        self.interval.entry(op).or_default().record(latency, ok);
+        self.total.entry(op).or_default().record(latency, ok);
+    }
+
+    // Writes the stats of the interval, and starts the next one.
+    fn report_interval(&mut self) -> Result<()> {
+        let interval = std::mem::take(&mut self.interval);
+        self.report("interval", &interval)
+    }
+
+    fn report_total(&mut self) -> Result<()> {
+        let total = std::mem::take(&mut self.total);
+        self.report("total", &total)
+    }
+
+    fn report(&mut self, window: &str, stats: &BTreeMap<Op, OpStats>) -> Result<()> {
+        let elapsed = self.started.elapsed().as_secs();
+        for (op, stats) in stats {
+            let columns = stats.csv_columns();
+            info!("{window} {op}: count,errors,error_rate,p50_ms,p90_ms,p99_ms,max_ms = {columns}");
+            writeln!(self.csv, "{elapsed},{window},{op},{columns}")?;
+        }
+        self.csv.flush()?;
+        Ok(())
+    }
+}
+
+// The state shared by the workers.
+struct Soak {
+    client: Client,
+    wallet: Mutex<Wallet>,
+    // The chunks put so far, for the gets to read back.
+    stored: Mutex<Vec<ChunkAddress>>,
+    weights: Vec<(Op, u32)>,
+    chunk_size: usize,
+    recorder: Mutex<Recorder>,
+}
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    let opt = Opt::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let weights: Vec<(Op, u32)> = [
+        (Op::Put, opt.put_weight),
+        (Op::Get, opt.get_weight),
+        (Op::RegisterEdit, opt.register_weight),
+        (Op::Transfer, opt.transfer_weight),
+    ]
+    .into_iter()
+    .filter(|(_, weight)| *weight > 0)
+    .collect();
+    if weights.is_empty() {
+        return Err(eyre!(
+            "The workload needs at least one operation of non-zero weight"
+        ));
+    }
+    if opt.concurrency == 0 {
+        return Err(eyre!("The concurrency must be at least 1"));
+    }
+
+    let client = Client::connect(&opt.bootstrap_peers, CONNECT_TIMEOUT).await?;
+    let wallet = Wallet::load_from(client.clone(), &opt.wallet_dir).await?;
+    info!(
+        "Soaking for {}s with {weights:?}, paying from a wallet holding {} tokens",
+        opt.duration_secs,
+        wallet.balance()?
+    );
+
+    let soak = Arc::new(Soak {
+        client,
+        wallet: Mutex::new(wallet),
+        stored: Mutex::new(vec![]),
+        weights,
+        chunk_size: opt.chunk_size,
+        recorder: Mutex::new(Recorder::new(File::create(&opt.csv)?)?),
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(opt.duration_secs);
+    let workers: Vec<_> = (0..opt.concurrency)
+        .map(|_| spawn(run_worker(Arc::clone(&soak), deadline)))
+        .collect();
+
+    let report_interval = Duration::from_secs(opt.report_interval_secs.max(1));
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        sleep(report_interval.min(remaining)).await;
+        soak.recorder.lock().await.report_interval()?;
+    }
+
+    for worker in workers {
+        worker.await;
+    }
+    let mut recorder = soak.recorder.lock().await;
+    recorder.report_interval()?;
+    recorder.report_total()?;
+    info!("Soak over, reports written to {:?}", opt.csv);
+    Ok(())
+}
+
+// Runs operations drawn from the workload, one at a time, until the deadline.
+async fn run_worker(soak: Arc<Soak>, deadline: Instant) {
+    // The register the worker edits, created by its first edit.
+    let mut register = None;
+    while Instant::now() < deadline {
+        let op = soak.pick_op().await;
+        let started = Instant::now();
+        let result = match op {
+            Op::Put => soak.put().await,
+            Op::Get => soak.get().await,
+            Op::RegisterEdit => soak.edit_register(&mut register).await,
+            Op::Transfer => soak.transfer().await,
+        };
+        let latency = started.elapsed();
+        if let Err(err) = &result {
+            warn!("Failed {op} after {latency:?}: {err}");
+        }
+        soak.recorder
+            .lock()
+            .await
+            .record(op, latency, result.is_ok());
+    }
+}
+
+impl Soak {
+    // Draws the next operation as per the weights. Gets are only drawn once there are
+    // chunks to read back.
+    async fn pick_op(&self) -> Op {
+        let total: u32 = self.weights.iter().map(|(_, weight)| weight).sum();
+        let mut draw = rand::thread_rng().gen_range(0..total);
+        let mut op = self.weights[0].0;
+        for (candidate, weight) in &self.weights {
+            if draw < *weight {
+                op = *candidate;
+                break;
+            }
+            draw -= weight;
+        }
+        if op == Op::Get && self.stored.lock().await.is_empty() {
+            return Op::Put;
+        }
+        op
+    }
+
+    async fn put(&self) -> Result<()> {
+        let mut value = vec![0; self.chunk_size];
+        rand::thread_rng().fill(&mut value[..]);
+        let chunk = Chunk::new(Bytes::from(value));
+        let payment = self.pay_for(*chunk.name()).await?;
+        let address = *chunk.address();
+        self.client.store_chunk(chunk, payment).await?;
+        self.stored.lock().await.push(address);
+        Ok(())
+    }
+
+    async fn get(&self) -> Result<()> {
+        let address = {
+            let stored = self.stored.lock().await;
+            let index = rand::thread_rng().gen_range(0..stored.len());
+            stored[index]
+        };
+        let chunk = self.client.get_chunk(address).await?;
+        if *chunk.address() != address {
+            return Err(eyre!("Chunk {address:?} was retrieved with other content"));
+        }
+        Ok(())
+    }
+
+    async fn edit_register(&self, register: &mut Option<ClientRegister>) -> Result<()> {
+        match register {
+            Some(register) => {
+                let entry = format!("soak entry written at {:?}", Instant::now());
+                let _ = register.write(entry.as_bytes()).await?;
+            }
+            None => {
+                let name = XorName(rand::random());
+                let address = RegisterAddress::new(name, SOAK_REGISTER_TAG);
+                let payment = self.pay_for(address.id()).await?;
+                *register = Some(
+                    ClientRegister::create(self.client.clone(), name, SOAK_REGISTER_TAG, payment)
+                        .await?,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Sends tokens from the wallet to itself, and receives them back.
+    async fn transfer(&self) -> Result<()> {
+        let mut wallet = self.wallet.lock().await;
+        let address = wallet.address();
+        let dbc = wallet
+            .send(Token::from_nano(PAYMENT_NANOS), address)
+            .await?;
+        wallet.receive(vec![dbc]).await?;
+        Ok(())
+    }
+
+    async fn pay_for(&self, name: XorName) -> Result<PaymentProof> {
+        let costs = self.client.get_store_costs(name).await?;
+        let payment = self
+            .wallet
+            .lock()
+            .await
+            .pay_store_costs(&name, &costs, Token::from_nano(PAYMENT_NANOS), None)
+            .await?;
+        Ok(payment)
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Result;
+use rand::RngCore;
+use std::path::Path;
+
+/// A random token authenticating the requests made to a frontend of the client serving
+/// other programs on the same machine, such as the `safe daemon` or the S3 frontend.
+///
+/// The token is written to a file only the user running the frontend can read, for the
+/// programs of that user alone to make requests.
+#[derive(Clone)]
+pub struct AccessToken(String);
+
+impl AccessToken {
+    /// Generates a new random token.
+    pub fn random() -> Self {
+        let mut token = [0; 32];
+        rand::thread_rng().fill_bytes(&mut token);
+        Self(hex::encode(token))
+    }
+
+    /// Writes the token to the file, readable by the user running the frontend only.
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        let _ = options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            let _ = options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        // The mode only applies to a file created, a file of a previous run keeping its own.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::io::Write::write_all(&mut file, self.0.as_bytes())?;
+        Ok(())
+    }
+
+    /// Whether the token given is this one, compared in time independent of where they differ.
+    pub fn matches(&self, token: &str) -> bool {
+        token.len() == self.0.len()
+            && token
+                .bytes()
+                .zip(self.0.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, Error};
+use crate::protocol::error::Error as ProtocolError;
+use futures::{stream, StreamExt};
+use sn_dbc::{DbcId, SignedSpend};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::{debug, info};
+
+/// The number of spends fetched concurrently while walking the spend DAG.
+const AUDIT_CONCURRENCY: usize = 16;
+
+/// The outcome of walking the spend DAG down from a Dbc.
+#[derive(Debug, Default)]
+pub struct SpendAudit {
+    /// The spends found, and verified to be consistent with the spend of their parent.
+    pub spends: BTreeMap<DbcId, SignedSpend>,
+    /// The Dbcs created by the spends found, and not spent yet.
+    pub unspent: BTreeSet<DbcId>,
+    /// The Dbcs whose close group holds conflicting spends of them.
+    pub double_spends: BTreeMap<DbcId, Vec<SignedSpend>>,
+    /// The spends failing verification, with the reason why. Their children are not walked.
+    pub invalid: BTreeMap<DbcId, String>,
+    /// The Dbcs whose spend could not be fetched, with the error encountered.
+    pub unreachable: BTreeMap<DbcId, String>,
+}
+
+impl SpendAudit {
+    /// Whether no double spend nor invalid spend was found, and every spend could be fetched.
+    pub fn is_sound(&self) -> bool {
+        self.double_spends.is_empty() && self.invalid.is_empty() && self.unreachable.is_empty()
+    }
+}
+
+// The spend of a Dbc, as held by its close group.
+enum Fetched {
+    Spent(SignedSpend),
+    Unspent,
+    DoubleSpent(Vec<SignedSpend>),
+    Failed(Error),
+}
+
+impl Client {
+    /// Walk the spend DAG from the spend of the given Dbc, e.g. the genesis Dbc, down to
+    /// the Dbcs not spent yet, fetching each spend from the close group of its Dbc.
+    ///
+    /// Every spend is checked to be signed, and to spend a Dbc created by the transaction
+    /// of its parent spend. Forks, i.e. conflicting spends of the same Dbc, are reported
+    /// as double spends.
+    pub async fn audit_spends(&self, from: DbcId) -> SpendAudit {
+        let mut audit = SpendAudit::default();
+        // The Dbcs to fetch the spend of, along with the spend of their parent.
+        let mut to_fetch: Vec<(DbcId, Option<SignedSpend>)> = vec![(from, None)];
+        let mut seen = BTreeSet::from([from]);
+
+        while !to_fetch.is_empty() {
+            let fetched: Vec<_> =
+                stream::iter(to_fetch.drain(..))
+                    .map(|(dbc_id, parent)| async move {
+                        (dbc_id, parent, self.fetch_spend(&dbc_id).await)
+                    })
+                    .buffer_unordered(AUDIT_CONCURRENCY)
+                    .collect()
+                    .await;
+
+            for (dbc_id, parent, fetched) in fetched {
+                let signed_spend = match fetched {
+                    Fetched::Spent(signed_spend) => signed_spend,
+                    Fetched::Unspent => {
+                        let _ = audit.unspent.insert(dbc_id);
+                        continue;
+                    }
+                    Fetched::DoubleSpent(spends) => {
+                        let _ = audit.double_spends.insert(dbc_id, spends);
+                        continue;
+                    }
+                    Fetched::Failed(err) => {
+                        let _ = audit.unreachable.insert(dbc_id, err.to_string());
+                        continue;
+                    }
+                };
+                if let Err(reason) = verify_spend(&signed_spend, parent.as_ref()) {
+                    let _ = audit.invalid.insert(dbc_id, reason);
+                    continue;
+                }
+
+                for output in &signed_spend.spend.spent_tx.outputs {
+                    if seen.insert(*output.dbc_id()) {
+                        to_fetch.push((*output.dbc_id(), Some(signed_spend.clone())));
+                    }
+                }
+                let _ = audit.spends.insert(dbc_id, signed_spend);
+            }
+            debug!(
+                "Audited {} spends, {} to fetch next",
+                audit.spends.len(),
+                to_fetch.len()
+            );
+        }
+
+        info!(
+            "Audited {} spends from {from:?}: {} unspent, {} double spent, {} invalid, {} unreachable",
+            audit.spends.len(),
+            audit.unspent.len(),
+            audit.double_spends.len(),
+            audit.invalid.len(),
+            audit.unreachable.len()
+        );
+        audit
+    }
+
+    async fn fetch_spend(&self, dbc_id: &DbcId) -> Fetched {
+        match self.get_spend(dbc_id).await {
+            Ok(signed_spend) => Fetched::Spent(signed_spend),
+            Err(Error::NotFound(_)) => Fetched::Unspent,
+            Err(Error::Protocol(ProtocolError::DoubleSpendAttempt { new, existing })) => {
+                Fetched::DoubleSpent(vec![*existing, *new])
+            }
+            Err(err) => Fetched::Failed(err),
+        }
+    }
+}
+
+// Checks the spend is signed by the owner of the Dbc, and that the Dbc was created by the
+// transaction of the parent spend.
+fn verify_spend(signed_spend: &SignedSpend, parent: Option<&SignedSpend>) -> Result<(), String> {
+    signed_spend
+        .verify(signed_spend.spent_tx_hash())
+        .map_err(|err| format!("invalid signature: {err}"))?;
+
+    if let Some(parent) = parent {
+        let parent_tx = &parent.spend.spent_tx;
+        if signed_spend.spend.dbc_creation_tx_hash != parent_tx.hash() {
+            return Err(format!(
+                "spends a Dbc not created by the transaction of the spend of {:?}",
+                parent.dbc_id()
+            ));
+        }
+        if !parent_tx
+            .outputs
+            .iter()
+            .any(|output| output.dbc_id() == signed_spend.dbc_id())
+        {
+            return Err(format!(
+                "spends a Dbc not among the outputs of the spend of {:?}",
+                parent.dbc_id()
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, ClientRegister, EncryptedFile, Error, Folder, FolderEntry, Result, Wallet};
+use crate::{
+    protocol::types::{payment::PaymentProof, register::RegisterAddress},
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use bytes::Bytes;
+use sn_dbc::Token;
+use std::collections::BTreeMap;
+use tracing::{debug, info};
+use xor_name::XorName;
+
+/// The tag of the registers pointing buckets at their current folder.
+pub const BUCKET_TAG: u64 = 0x5333;
+
+// The amount paid for the storage of each chunk of an object, in nanos, when the nodes
+// storing it do not ask for a cost of their own.
+const OBJECT_CHUNK_PAYMENT_NANOS: u64 = 1;
+
+/// A named container of objects, as of the S3 API, owned by the key of the client.
+///
+/// The objects are the files of a [`Folder`], listed by key. The folder is replaced with
+/// every object put, the register of the bucket pointing at its current address.
+#[derive(Clone)]
+pub struct Bucket {
+    name: String,
+    register: ClientRegister,
+}
+
+impl Bucket {
+    /// Creates an empty bucket of the given name, paying for its register from the wallet.
+    pub async fn create(wallet: &mut Wallet, name: &str) -> Result<Self> {
+        let client = wallet.client().clone();
+        let register_name = register_name(&client.network.keypair().public(), name);
+        let address = RegisterAddress::new(register_name, BUCKET_TAG);
+        let payment = pay_for(wallet, &address.id()).await?;
+        let register = ClientRegister::create(client, register_name, BUCKET_TAG, payment).await?;
+        info!("Created bucket {name:?} at {address:?}");
+        Ok(Self {
+            name: name.to_string(),
+            register,
+        })
+    }
+
+    /// Opens the bucket of the given name, as last written to on the network.
+    pub async fn open(client: Client, name: &str) -> Result<Self> {
+        let register_name = register_name(&client.network.keypair().public(), name);
+        let register = ClientRegister::retrieve(client, register_name, BUCKET_TAG).await?;
+        Ok(Self {
+            name: name.to_string(),
+            register,
+        })
+    }
+
+    /// Returns the name of the bucket.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Pulls the latest state of the bucket from the network.
+    pub async fn sync(&mut self) -> Result<()> {
+        self.register.sync().await
+    }
+
+    /// Returns the objects of the bucket, by key, as last synced with the network.
+    ///
+    /// The folders written concurrently, by other clients sharing the key, are merged.
+    pub async fn folder(&self) -> Result<Folder> {
+        let mut merged = Folder::default();
+        for (_, entry) in self.register.read() {
+            let address = folder_address(&entry)?;
+            let folder = self.register.client().get_folder(address).await?;
+            for (key, entry) in folder.entries() {
+                let _ = merged.insert(key.clone(), entry.clone());
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Stores the bytes as the object at `key`, paying for its chunks and those of the new
+    /// folder of the bucket from the wallet. Returns the address of the object.
+    ///
+    /// Objects too small to be self-encrypted, empty ones included, are held in their data map.
+    pub async fn put_object(
+        &mut self,
+        wallet: &mut Wallet,
+        key: &str,
+        bytes: Bytes,
+    ) -> Result<ChunkAddress> {
+        let size = bytes.len() as u64;
+        let file = EncryptedFile::new(bytes)?;
+        let address = file.address;
+
+        self.sync().await?;
+        let mut folder = self.folder().await?;
+        let _ = folder.insert(key.to_string(), FolderEntry::File { address, size });
+        let folder_chunk = folder.to_chunk()?;
+        let folder_address = *folder_chunk.address();
+
+        let mut chunks = file.chunks;
+        chunks.push(folder_chunk);
+        let payments = pay_for_chunks(wallet, &chunks).await?;
+        self.register
+            .client()
+            .upload_paid_chunks(chunks, &payments)
+            .await?;
+        let _ = self.register.write(&folder_address.name().0).await?;
+        debug!(
+            "Put object {key:?} of {size} bytes in bucket {:?}",
+            self.name
+        );
+        Ok(address)
+    }
+
+    /// Returns the address and size of the object at `key`, as last synced with the network.
+    pub async fn head_object(&self, key: &str) -> Result<(ChunkAddress, u64)> {
+        match self.folder().await?.get(key) {
+            Some(FolderEntry::File { address, size }) => Ok((*address, *size)),
+            _ => Err(Error::PathNotFound(key.to_string())),
+        }
+    }
+
+    /// Reads the object at `key`, as last synced with the network.
+    pub async fn get_object(&self, key: &str) -> Result<Bytes> {
+        let (address, _) = self.head_object(key).await?;
+        self.register.client().read_file(address).await
+    }
+
+    /// Lists the keys of the objects starting with `prefix`, in order, with their size.
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<(String, u64)>> {
+        let folder = self.folder().await?;
+        Ok(folder
+            .entries()
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .filter_map(|(key, entry)| match entry {
+                FolderEntry::File { size, .. } => Some((key.clone(), *size)),
+                FolderEntry::Folder(_) => None,
+            })
+            .collect())
+    }
+}
+
+// The name of the register of the bucket, buckets of the same name owned by different keys
+// being distinct.
+fn register_name(owner: &libp2p::identity::PublicKey, bucket: &str) -> XorName {
+    XorName::from_content_parts(&[b"bucket", &owner.encode_protobuf(), bucket.as_bytes()])
+}
+
+fn folder_address(entry: &[u8]) -> Result<ChunkAddress> {
+    let name: [u8; 32] = entry
+        .try_into()
+        .map_err(|_| Error::Serialisation("bucket entry is not a folder address".to_string()))?;
+    Ok(ChunkAddress(XorName(name)))
+}
+
+// Pays for the storage of the data at `name` the costs asked by its close group.
+async fn pay_for(wallet: &mut Wallet, name: &XorName) -> Result<PaymentProof> {
+    let costs = wallet.client().get_store_costs(*name).await?;
+    wallet
+        .pay_store_costs(
+            name,
+            &costs,
+            Token::from_nano(OBJECT_CHUNK_PAYMENT_NANOS),
+            None,
+        )
+        .await
+}
+
+async fn pay_for_chunks(
+    wallet: &mut Wallet,
+    chunks: &[Chunk],
+) -> Result<BTreeMap<XorName, PaymentProof>> {
+    let mut payments = BTreeMap::new();
+    for chunk in chunks {
+        let payment = pay_for(wallet, chunk.name()).await?;
+        let _ = payments.insert(*chunk.name(), payment);
+    }
+    Ok(payments)
+}
@@ -0,0 +1,220 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{cmd_ack_quorum, verify_response, Client, Error, Result};
+use crate::{
+    network::Misbehaviour,
+    protocol::{
+        messages::{Cmd, CmdResponse, Query, QueryResponse, Request, Response},
+        types::payment::PaymentProof,
+    },
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use async_std::future::timeout;
+use futures::future::join_all;
+use libp2p::PeerId;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// How long to wait for a holder to respond in a verified read, before falling back to other peers.
+const VERIFIED_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl Client {
+    /// Store the chunk with the close group of its address.
+    ///
+    /// The `payment` spends must commit to the address of the chunk, see [`PaymentProof::reason_for`].
+    pub async fn store_chunk(&self, chunk: Chunk, payment: PaymentProof) -> Result<()> {
+        let address = *chunk.address();
+        debug!("Storing chunk {address:?}");
+        let cmd = Cmd::StoreChunk {
+            address,
+            value: chunk.value().clone(),
+            payment,
+        };
+        let responses = self.send_cmd_to_closest(*address.name(), &cmd).await?;
+        cmd_ack_quorum(*address.name(), &cmd, responses, |resp| match resp {
+            Response::Cmd(CmdResponse::StoreChunk(result)) => Ok(result),
+            other => Err(other),
+        })
+    }
+
+    /// Get the chunk at the given address from its close group.
+    ///
+    /// When none of the close group returns the chunk, the search is widened to more peers,
+    /// as configured with [`SearchConfig`], before declaring the chunk missing.
+    ///
+    /// [`SearchConfig`]: crate::network::SearchConfig
+    pub async fn get_chunk(&self, address: ChunkAddress) -> Result<Chunk> {
+        let request = Request::Query(Query::GetChunk(address));
+        let found = |resp: &Response| match resp {
+            Response::Query(QueryResponse::GetChunk(Ok(chunk))) => chunk.address() == &address,
+            _ => false,
+        };
+        let responses = self.search_closest(*address.name(), request, found).await?;
+
+        let mut last_err = None;
+        for resp in responses {
+            match resp {
+                Ok(Response::Query(QueryResponse::GetChunk(Ok(chunk)))) => {
+                    if chunk.address() == &address {
+                        return Ok(chunk);
+                    }
+                    warn!(
+                        "Chunk received for {address:?} hashes to {:?}",
+                        chunk.address()
+                    );
+                }
+                Ok(Response::Query(QueryResponse::GetChunk(Err(err)))) => {
+                    last_err = Some(Error::from(err))
+                }
+                Ok(other) => last_err = Some(Error::UnexpectedResponse(other)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or(Error::NoPeersFound(*address.name())))
+    }
+
+    /// Get the chunks at the given addresses from their close groups, looking up the close
+    /// groups of all of them at once, returning the outcome for each address in order.
+    ///
+    /// The search is only widened, as with [`Client::get_chunk`], for the chunks none of
+    /// their close group returned.
+    pub async fn get_chunks(&self, addresses: Vec<ChunkAddress>) -> Result<Vec<Result<Chunk>>> {
+        let reqs = addresses
+            .iter()
+            .map(|address| (*address.name(), Request::Query(Query::GetChunk(*address))))
+            .collect();
+        let responses = self.network.send_to_closest_batch(reqs).await?;
+
+        let reads = addresses
+            .into_iter()
+            .zip(responses)
+            .map(|(address, responses)| async move {
+                let found = responses.into_iter().find_map(|(peer, resp)| {
+                    match verify_response(peer, resp) {
+                        Ok(Response::Query(QueryResponse::GetChunk(Ok(chunk))))
+                            if chunk.address() == &address =>
+                        {
+                            Some(chunk)
+                        }
+                        _ => None,
+                    }
+                });
+                match found {
+                    Some(chunk) => Ok(chunk),
+                    None => {
+                        debug!(
+                            "Chunk {address:?} not returned by its close group, searching further"
+                        );
+                        self.get_chunk(address).await
+                    }
+                }
+            });
+        Ok(join_all(reads).await)
+    }
+
+    /// Check whether the chunk at the given address is held by a majority of its close group,
+    /// in which case it needs not be paid for nor uploaded again.
+    pub async fn chunk_exists(&self, address: ChunkAddress) -> Result<bool> {
+        let request = Request::Query(Query::GetChunkExists(address));
+        let responses = self.send_to_closest(*address.name(), request).await?;
+        let quorum = responses.len() / 2 + 1;
+        let holders = responses
+            .into_iter()
+            .filter(|resp| {
+                matches!(
+                    resp,
+                    Ok(Response::Query(QueryResponse::GetChunkExists(true)))
+                )
+            })
+            .count();
+        Ok(holders >= quorum)
+    }
+
+    /// Get the chunk at the given address, once `quorum` of its holders have returned
+    /// a copy matching the address.
+    ///
+    /// Holders are queried closest first. In place of each one that fails, times out, or returns
+    /// a copy not matching the address, the next closest peer is queried, until running out of peers.
+    /// Holders returning a copy not matching the address are reported as misbehaving, for the
+    /// network to stop relying on them.
+    pub async fn get_chunk_verified(&self, address: ChunkAddress, quorum: usize) -> Result<Chunk> {
+        let name = *address.name();
+        let mut peers = self
+            .network
+            .clone()
+            .get_closest_peers(name)
+            .await?
+            .into_iter()
+            .map(|(peer, _)| peer);
+        let request = Request::Query(Query::GetChunk(address));
+
+        let mut verified = None;
+        let mut copies = 0;
+        while copies < quorum {
+            let batch: Vec<_> = peers.by_ref().take(quorum - copies).collect();
+            if batch.is_empty() {
+                break;
+            }
+
+            let reads = batch.into_iter().map(|peer| {
+                let request = request.clone();
+                async move {
+                    let result =
+                        timeout(VERIFIED_READ_TIMEOUT, self.send_to_peer(peer, request)).await;
+                    (peer, result)
+                }
+            });
+            for (peer, result) in join_all(reads).await {
+                match result {
+                    Ok(Ok(Response::Query(QueryResponse::GetChunk(Ok(chunk))))) => {
+                        if chunk.address() == &address {
+                            copies += 1;
+                            verified = Some(chunk);
+                        } else {
+                            warn!(
+                                "Chunk received from {peer:?} for {address:?} hashes to {:?}",
+                                chunk.address()
+                            );
+                            self.report_divergent_holder(peer).await;
+                        }
+                    }
+                    Ok(Ok(Response::Query(QueryResponse::GetChunk(Err(err))))) => {
+                        warn!("Failed to read {address:?} from {peer:?}: {err}")
+                    }
+                    Ok(Ok(other)) => {
+                        warn!("Unexpected response from {peer:?} reading {address:?}: {other:?}")
+                    }
+                    Ok(Err(err)) => warn!("Failed to read {address:?} from {peer:?}: {err}"),
+                    Err(_) => warn!("Timed out reading {address:?} from {peer:?}"),
+                }
+            }
+        }
+
+        match verified {
+            Some(chunk) if copies >= quorum => Ok(chunk),
+            _ => Err(Error::ReadQuorumNotReached {
+                name,
+                copies,
+                quorum,
+            }),
+        }
+    }
+
+    // Penalises the holder for returning data not matching what was asked for.
+    async fn report_divergent_holder(&self, peer: PeerId) {
+        let mut network = self.network.clone();
+        if let Err(err) = network
+            .report_misbehaviour(peer, Misbehaviour::InvalidData)
+            .await
+        {
+            warn!("Failed to report {peer:?} for returning invalid data: {err}");
+        }
+    }
+}
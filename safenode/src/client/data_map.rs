@@ -0,0 +1,278 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Error, Result};
+use crate::storage::chunks::Chunk;
+use bytes::Bytes;
+use self_encryption::{ChunkInfo, DataMap};
+use serde::{Deserialize, Serialize};
+use xor_name::XorName;
+
+/// The version of the data map format written by this implementation.
+///
+/// Versions only add fields, which older readers ignore. A change older readers cannot
+/// ignore also raises [`VersionedDataMap::readable_from`].
+pub const DATA_MAP_VERSION: u32 = 3;
+
+/// The data map of a self-encrypted file, stored in a chunk of its own, from which any
+/// client implementation can fetch and decrypt the chunks of the file.
+///
+/// It is serialised with MessagePack, as a map keyed by field name, so that fields added
+/// by later versions are skipped by the readers of earlier ones.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionedDataMap {
+    /// The version of the format the data map was written with.
+    pub version: u32,
+    /// The oldest version of the format a reader must implement to read the data map.
+    pub readable_from: u32,
+    /// The size of the file, in bytes.
+    pub file_size: u64,
+    /// The chunks of the file, in the order of their content in the file.
+    pub chunks: Vec<ChunkEntry>,
+    /// The content of a file too small to be self-encrypted, held in the data map itself
+    /// rather than in chunks of its own.
+    ///
+    /// Added in version 3, data maps holding content are only readable from it, as earlier
+    /// readers would read the file as empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Bytes>,
+}
+
+/// A chunk of a self-encrypted file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkEntry {
+    /// The window of the file the chunk was self-encrypted with.
+    ///
+    /// Files streamed through the [`StreamingEncryptor`] are self-encrypted one window at a
+    /// time, each independently of the others. Files encrypted at once are a single window.
+    /// Added in version 2, entries written before it are all of window 0.
+    ///
+    /// [`StreamingEncryptor`]: super::StreamingEncryptor
+    #[serde(default)]
+    pub window: u64,
+    /// The position of the chunk in its window.
+    pub index: u64,
+    /// The name of the encrypted chunk, as stored on the network.
+    pub name: XorName,
+    /// The hash of the content of the chunk before encryption, from which the keys to
+    /// decrypt it and its neighbours are derived.
+    pub src_hash: XorName,
+    /// The size of the content of the chunk before encryption, in bytes.
+    pub src_size: u64,
+}
+
+impl VersionedDataMap {
+    /// Describes the data map returned by self-encryption, in the current version of the format.
+    pub fn new(data_map: &DataMap) -> Self {
+        Self::from_windows(std::slice::from_ref(data_map))
+    }
+
+    /// Describes the data maps returned by the self-encryption of each window of a file,
+    /// in the order of the windows in the file.
+    ///
+    /// Files of more than one window are only readable from version 2 of the format, as
+    /// earlier readers would decrypt their chunks as those of a single window.
+    pub fn from_windows(windows: &[DataMap]) -> Self {
+        let chunks: Vec<_> = windows
+            .iter()
+            .enumerate()
+            .flat_map(|(window, data_map)| {
+                data_map.infos().into_iter().map(move |info| ChunkEntry {
+                    window: window as u64,
+                    index: info.index as u64,
+                    name: info.dst_hash,
+                    src_hash: info.src_hash,
+                    src_size: info.src_size as u64,
+                })
+            })
+            .collect();
+        Self {
+            version: DATA_MAP_VERSION,
+            readable_from: if windows.len() > 1 { 2 } else { 1 },
+            file_size: chunks.iter().map(|chunk| chunk.src_size).sum(),
+            chunks,
+            content: None,
+        }
+    }
+
+    /// Describes a file too small to be self-encrypted, its content being held in the data
+    /// map itself.
+    pub fn inline(content: Bytes) -> Self {
+        Self {
+            version: DATA_MAP_VERSION,
+            readable_from: 3,
+            file_size: content.len() as u64,
+            chunks: vec![],
+            content: Some(content),
+        }
+    }
+
+    /// Returns the data map to decrypt the chunks of each window of the file with, in the
+    /// order of the windows in the file.
+    pub fn to_data_maps(&self) -> Vec<DataMap> {
+        let mut windows: Vec<Vec<ChunkInfo>> = vec![];
+        for chunk in &self.chunks {
+            let info = ChunkInfo {
+                index: chunk.index as usize,
+                dst_hash: chunk.name,
+                src_hash: chunk.src_hash,
+                src_size: chunk.src_size as usize,
+            };
+            let in_last_window = windows.len() as u64 == chunk.window + 1;
+            match windows.last_mut() {
+                Some(infos) if in_last_window => infos.push(info),
+                _ => windows.push(vec![info]),
+            }
+        }
+        windows.into_iter().map(DataMap::new).collect()
+    }
+
+    /// Serialises the data map into the chunk it is stored in.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let bytes =
+            rmp_serde::to_vec_named(self).map_err(|err| Error::Serialisation(err.to_string()))?;
+        Ok(Chunk::new(Bytes::from(bytes)))
+    }
+
+    /// Reads the data map from the chunk it is stored in.
+    ///
+    /// Data maps written before the format was versioned, as a bare self-encryption
+    /// data map, are read as well.
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        match rmp_serde::from_slice::<Self>(chunk.value()) {
+            Ok(data_map) if data_map.readable_from > DATA_MAP_VERSION => {
+                Err(Error::UnsupportedDataMap {
+                    version: data_map.version,
+                    readable_from: data_map.readable_from,
+                })
+            }
+            Ok(data_map) => Ok(data_map),
+            Err(err) => match rmp_serde::from_slice::<DataMap>(chunk.value()) {
+                Ok(legacy) => Ok(Self::new(&legacy)),
+                Err(_) => Err(Error::Serialisation(err.to_string())),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    // A data map as written by a later version of the format, with a field unknown to this one.
+    #[derive(Serialize)]
+    struct LaterDataMap {
+        version: u32,
+        readable_from: u32,
+        file_size: u64,
+        chunks: Vec<ChunkEntry>,
+        compression: String,
+    }
+
+    fn entries() -> Vec<ChunkEntry> {
+        (0..3)
+            .map(|index| ChunkEntry {
+                window: 0,
+                index,
+                name: XorName::from_content(&[index as u8]),
+                src_hash: XorName::from_content(&[index as u8, 1]),
+                src_size: 1024,
+            })
+            .collect()
+    }
+
+    fn later_chunk(readable_from: u32) -> Result<Chunk> {
+        let later = LaterDataMap {
+            version: DATA_MAP_VERSION + 1,
+            readable_from,
+            file_size: 3072,
+            chunks: entries(),
+            compression: "zstd".to_string(),
+        };
+        Ok(Chunk::new(Bytes::from(rmp_serde::to_vec_named(&later)?)))
+    }
+
+    #[test]
+    fn data_map_round_trips_through_its_chunk() -> Result<()> {
+        let data_map = VersionedDataMap {
+            version: DATA_MAP_VERSION,
+            readable_from: 1,
+            file_size: 3072,
+            chunks: entries(),
+            content: None,
+        };
+        let chunk = data_map.to_chunk()?;
+        assert_eq!(VersionedDataMap::from_chunk(&chunk)?, data_map);
+        Ok(())
+    }
+
+    #[test]
+    fn fields_of_later_versions_are_ignored() -> Result<()> {
+        let data_map = VersionedDataMap::from_chunk(&later_chunk(1)?)?;
+        assert_eq!(data_map.version, DATA_MAP_VERSION + 1);
+        assert_eq!(data_map.file_size, 3072);
+        assert_eq!(data_map.chunks, entries());
+        Ok(())
+    }
+
+    #[test]
+    fn versions_not_readable_by_this_one_are_rejected() -> Result<()> {
+        let result = VersionedDataMap::from_chunk(&later_chunk(DATA_MAP_VERSION + 1)?);
+        assert!(matches!(result, Err(Error::UnsupportedDataMap { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn unversioned_data_maps_are_read() -> Result<()> {
+        let data_map = VersionedDataMap {
+            version: DATA_MAP_VERSION,
+            readable_from: 1,
+            file_size: 3072,
+            chunks: entries(),
+            content: None,
+        };
+        let legacy = rmp_serde::to_vec(&data_map.to_data_maps()[0])?;
+        let read = VersionedDataMap::from_chunk(&Chunk::new(Bytes::from(legacy)))?;
+        assert_eq!(read, data_map);
+        Ok(())
+    }
+
+    #[test]
+    fn inline_content_round_trips_through_its_chunk() -> Result<()> {
+        let data_map = VersionedDataMap::inline(Bytes::from_static(b"hi"));
+        assert_eq!(data_map.readable_from, 3);
+        assert_eq!(data_map.file_size, 2);
+
+        let read = VersionedDataMap::from_chunk(&data_map.to_chunk()?)?;
+        assert_eq!(read, data_map);
+        Ok(())
+    }
+
+    #[test]
+    fn windows_are_decrypted_apart() -> Result<()> {
+        let window = VersionedDataMap {
+            version: DATA_MAP_VERSION,
+            readable_from: 1,
+            file_size: 3072,
+            chunks: entries(),
+            content: None,
+        }
+        .to_data_maps()
+        .remove(0);
+        let data_map = VersionedDataMap::from_windows(&[window.clone(), window.clone()]);
+        assert_eq!(data_map.readable_from, 2);
+        assert_eq!(data_map.file_size, 6144);
+        assert_eq!(data_map.chunks[3].window, 1);
+        assert_eq!(data_map.chunks[3].index, 0);
+
+        let read = VersionedDataMap::from_chunk(&data_map.to_chunk()?)?;
+        assert_eq!(read.to_data_maps(), vec![window.clone(), window]);
+        Ok(())
+    }
+}
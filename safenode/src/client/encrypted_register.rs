@@ -0,0 +1,281 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, ClientRegister, Error, Result};
+use crate::protocol::types::{
+    payment::PaymentProof,
+    register::{EntryHash, RegisterAddress},
+};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use sn_dbc::MainKey;
+use std::{collections::BTreeSet, fmt};
+use tracing::debug;
+use xor_name::XorName;
+
+// Separates the keys derived for registers from other uses of the same secret.
+const KEY_DERIVATION_DOMAIN: &[u8] = b"safe-register-entry-key";
+
+/// The symmetric key the entries of a register are encrypted with, held by its owner.
+///
+/// Each key belongs to a generation of the register, rotating the key moving the register
+/// to the next generation, at an address of its own.
+#[derive(Clone)]
+pub struct RegisterKey {
+    key: [u8; 32],
+    generation: u32,
+}
+
+impl fmt::Debug for RegisterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegisterKey")
+            .field("generation", &self.generation)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RegisterKey {
+    /// Derives the key of the first generation from the given secret.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self::derive(secret, 0)
+    }
+
+    /// Derives the key of the first generation from the main key of the owner.
+    pub fn from_main_key(main_key: &MainKey) -> Self {
+        Self::from_secret(&main_key.to_bytes())
+    }
+
+    /// Derives the key of the next generation from the given secret, which may be the
+    /// one this key was derived from, or a new one if this one was compromised.
+    pub fn next_generation(&self, secret: &[u8]) -> Self {
+        Self::derive(secret, self.generation + 1)
+    }
+
+    /// Returns the generation of the register the key belongs to.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    fn derive(secret: &[u8], generation: u32) -> Self {
+        let key = XorName::from_content_parts(&[
+            KEY_DERIVATION_DOMAIN,
+            secret,
+            &generation.to_be_bytes(),
+        ]);
+        Self {
+            key: key.0,
+            generation,
+        }
+    }
+
+    /// Encrypts the entry, with a random nonce stored along with it.
+    pub fn encrypt(&self, entry: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, entry)
+            .map_err(|_| Error::RegisterEncryption)?;
+        let encrypted = EncryptedEntry {
+            generation: self.generation,
+            nonce: nonce.to_vec(),
+            ciphertext,
+        };
+        rmp_serde::to_vec(&encrypted).map_err(|err| Error::Serialisation(err.to_string()))
+    }
+
+    /// Decrypts an entry encrypted with this key.
+    pub fn decrypt(&self, entry: &[u8]) -> Result<Vec<u8>> {
+        let encrypted: EncryptedEntry =
+            rmp_serde::from_slice(entry).map_err(|err| Error::Serialisation(err.to_string()))?;
+        if encrypted.generation != self.generation || encrypted.nonce.len() != 24 {
+            return Err(Error::RegisterDecryption);
+        }
+        self.cipher()
+            .decrypt(
+                XNonce::from_slice(&encrypted.nonce),
+                encrypted.ciphertext.as_ref(),
+            )
+            .map_err(|_| Error::RegisterDecryption)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.key.into())
+    }
+}
+
+// An entry as stored in the register, opaque to the nodes holding it.
+#[derive(Serialize, Deserialize)]
+struct EncryptedEntry {
+    generation: u32,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// A [`ClientRegister`] whose entries are encrypted by the client, so that the nodes
+/// holding it only ever see ciphertext.
+#[derive(Clone)]
+pub struct EncryptedRegister {
+    register: ClientRegister,
+    name: XorName,
+    tag: u64,
+    key: RegisterKey,
+}
+
+impl EncryptedRegister {
+    /// Returns the address of the given generation of the register with the given name and tag.
+    ///
+    /// The first generation is at the name and tag themselves, later ones at a name
+    /// derived from them, for rotating the key not to require the name to be known anew.
+    pub fn address_of(name: XorName, tag: u64, generation: u32) -> RegisterAddress {
+        if generation == 0 {
+            return RegisterAddress::new(name, tag);
+        }
+        let generation_name = XorName::from_content_parts(&[&name.0, &generation.to_be_bytes()]);
+        RegisterAddress::new(generation_name, tag)
+    }
+
+    /// Create the register of the generation of the key on the network, empty.
+    ///
+    /// The `payment` spends must commit to the address of that generation, see
+    /// [`EncryptedRegister::address_of`].
+    pub async fn create(
+        client: Client,
+        name: XorName,
+        tag: u64,
+        key: RegisterKey,
+        payment: PaymentProof,
+    ) -> Result<Self> {
+        let address = Self::address_of(name, tag, key.generation());
+        let register =
+            ClientRegister::create(client, *address.name(), address.tag(), payment).await?;
+        Ok(Self {
+            register,
+            name,
+            tag,
+            key,
+        })
+    }
+
+    /// Retrieve the register of the generation of the key from the network.
+    pub async fn retrieve(
+        client: Client,
+        name: XorName,
+        tag: u64,
+        key: RegisterKey,
+    ) -> Result<Self> {
+        let address = Self::address_of(name, tag, key.generation());
+        let register = ClientRegister::retrieve(client, *address.name(), address.tag()).await?;
+        Ok(Self {
+            register,
+            name,
+            tag,
+            key,
+        })
+    }
+
+    /// Returns the address of the current generation of the register.
+    pub fn address(&self) -> &RegisterAddress {
+        self.register.address()
+    }
+
+    /// Returns the current entries of the register, decrypted.
+    ///
+    /// Fails if any entry is not encrypted with the key of this generation.
+    pub fn read(&self) -> Result<BTreeSet<(EntryHash, Vec<u8>)>> {
+        self.register
+            .read()
+            .into_iter()
+            .map(|(hash, entry)| Ok((hash, self.key.decrypt(&entry)?)))
+            .collect()
+    }
+
+    /// Encrypt and write an entry superseding all the current entries.
+    pub async fn write(&mut self, entry: &[u8]) -> Result<EntryHash> {
+        let encrypted = self.key.encrypt(entry)?;
+        self.register.write(&encrypted).await
+    }
+
+    /// Pull the latest state of the register from the network.
+    pub async fn sync(&mut self) -> Result<()> {
+        self.register.sync().await
+    }
+
+    /// Rotate the key of the register, creating its next generation, to which the
+    /// current entries are written re-encrypted with the new key.
+    ///
+    /// Concurrent entries are written one after another, the last superseding the others.
+    /// The `payment` spends must commit to the address of the new generation, see
+    /// [`EncryptedRegister::address_of`].
+    pub async fn rotate_key(self, new_key: RegisterKey, payment: PaymentProof) -> Result<Self> {
+        if new_key.generation() <= self.key.generation() {
+            return Err(Error::StaleRegisterKey {
+                current: self.key.generation(),
+                new: new_key.generation(),
+            });
+        }
+        let entries = self.read()?;
+
+        let client = self.register.client().clone();
+        let mut next = Self::create(client, self.name, self.tag, new_key, payment).await?;
+        for (_, entry) in entries {
+            let _ = next.write(&entry).await?;
+        }
+        debug!(
+            "Rotated the key of register {:?} to generation {}",
+            self.name,
+            next.key.generation()
+        );
+        Ok(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    #[test]
+    fn entries_decrypt_with_their_key_only() -> Result<()> {
+        let key = RegisterKey::from_secret(b"secret");
+        let encrypted = key.encrypt(b"entry")?;
+        assert_eq!(key.decrypt(&encrypted)?, b"entry");
+
+        let other = RegisterKey::from_secret(b"other secret");
+        assert!(matches!(
+            other.decrypt(&encrypted),
+            Err(Error::RegisterDecryption)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn next_generation_from_same_secret_uses_another_key() -> Result<()> {
+        let key = RegisterKey::from_secret(b"secret");
+        let next = key.next_generation(b"secret");
+        assert_eq!(next.generation(), 1);
+        assert_ne!(key.key, next.key);
+        assert!(next.decrypt(&key.encrypt(b"entry")?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn generations_are_at_distinct_addresses() {
+        let name = XorName::from_content(b"register");
+        assert_eq!(
+            EncryptedRegister::address_of(name, 1, 0),
+            RegisterAddress::new(name, 1)
+        );
+        assert_ne!(
+            EncryptedRegister::address_of(name, 1, 1),
+            EncryptedRegister::address_of(name, 1, 2)
+        );
+    }
+}
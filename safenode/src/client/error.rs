@@ -0,0 +1,142 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    network::Error as NetworkError,
+    protocol::{
+        error::{Error as ProtocolError, ErrorKind},
+        messages::Response,
+    },
+};
+use libp2p::{Multiaddr, PeerId};
+use sn_dbc::Token;
+use std::time::Duration;
+use thiserror::Error;
+use xor_name::XorName;
+
+/// Specialisation of `std::Result` for the client mod.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Client errors.
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum Error {
+    #[error("Network error: {0}")]
+    Network(#[from] NetworkError),
+
+    #[error("Not found: {0}")]
+    NotFound(ProtocolError),
+
+    #[error("Out of range: {0}")]
+    OutOfRange(ProtocolError),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(ProtocolError),
+
+    #[error("Payment required: {0}")]
+    PaymentRequired(ProtocolError),
+
+    #[error("Rate limited: {0}")]
+    RateLimited(ProtocolError),
+
+    #[error("Node not ready: {0}")]
+    NotReady(ProtocolError),
+
+    #[error("Internal node error: {0}")]
+    Internal(ProtocolError),
+
+    #[error("Protocol error: {0}")]
+    Protocol(ProtocolError),
+
+    #[error("No peers found close to {0:?}")]
+    NoPeersFound(XorName),
+
+    #[error("Dbc error: {0}")]
+    Dbc(#[from] sn_dbc::Error),
+
+    #[error("Not enough balance, {available:?} available, {required:?} required")]
+    NotEnoughBalance { available: Token, required: Token },
+
+    #[error("Transfer failed: {0}")]
+    TransferFailed(String),
+
+    #[error("Dbc {0:?} is not valid: {1}")]
+    InvalidDbc(sn_dbc::DbcId, String),
+
+    #[error("No entry found at path {0}")]
+    PathNotFound(String),
+
+    #[error("Self-encryption error: {0}")]
+    SelfEncryption(#[from] self_encryption::Error),
+
+    #[error("Serialisation error: {0}")]
+    Serialisation(String),
+
+    #[error(
+        "Data map of version {version} is only readable from version {readable_from} of the format"
+    )]
+    UnsupportedDataMap { version: u32, readable_from: u32 },
+
+    #[error("Failed to encrypt register entry")]
+    RegisterEncryption,
+
+    #[error("The file is still being encrypted, {0} bytes of it were read so far")]
+    EncryptionUnfinished(u64),
+
+    #[error("Register entry could not be decrypted with the key of this generation")]
+    RegisterDecryption,
+
+    #[error(
+        "Register key of generation {new} does not supersede the current generation {current}"
+    )]
+    StaleRegisterKey { current: u32, new: u32 },
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Only {copies} of the {quorum} required copies of {name:?} could be verified")]
+    ReadQuorumNotReached {
+        name: XorName,
+        copies: usize,
+        quorum: usize,
+    },
+
+    #[error("Timed out storing {0:?}")]
+    StoreTimeout(XorName),
+
+    #[error("Cmd {0:?} was not acknowledged by enough peers")]
+    CmdNotAcknowledged(XorName),
+
+    #[error("Unexpected response: {0:?}")]
+    UnexpectedResponse(Response),
+
+    #[error("Invalid listing from {peer:?}: {reason}")]
+    InvalidListing { peer: PeerId, reason: String },
+
+    #[error("Peer address {0} does not end with a peer id")]
+    InvalidPeerAddress(Multiaddr),
+
+    #[error("Could not connect to any peer of the network within {0:?}")]
+    ConnectTimeout(Duration),
+}
+
+// Maps the errors returned by nodes to the client error of their kind.
+impl From<ProtocolError> for Error {
+    fn from(err: ProtocolError) -> Self {
+        match err.kind() {
+            ErrorKind::NotFound => Self::NotFound(err),
+            ErrorKind::OutOfRange => Self::OutOfRange(err),
+            ErrorKind::QuotaExceeded => Self::QuotaExceeded(err),
+            ErrorKind::PaymentRequired => Self::PaymentRequired(err),
+            ErrorKind::RateLimited => Self::RateLimited(err),
+            ErrorKind::NotReady => Self::NotReady(err),
+            ErrorKind::Internal => Self::Internal(err),
+            ErrorKind::InvalidRequest => Self::Protocol(err),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{cmd_ack_quorum, Client, Result};
+use crate::protocol::messages::{Cmd, CmdResponse, Response, Topic};
+use tracing::debug;
+
+impl Client {
+    /// Subscribe to the events of the topic with the peers publishing them, i.e. the close
+    /// group of the data it is about.
+    ///
+    /// The events are pushed to us as [`NetworkEvent::EventReceived`], once by each of the peers.
+    ///
+    /// [`NetworkEvent::EventReceived`]: crate::network::NetworkEvent::EventReceived
+    pub async fn subscribe(&self, topic: Topic) -> Result<()> {
+        debug!("Subscribing to {topic:?}");
+        let cmd = Cmd::Subscribe(topic);
+        let responses = self.send_cmd_to_closest(topic.name(), &cmd).await?;
+        cmd_ack_quorum(topic.name(), &cmd, responses, |resp| match resp {
+            Response::Cmd(CmdResponse::Subscribe(result)) => Ok(result),
+            other => Err(other),
+        })
+    }
+
+    /// Stop being pushed the events of the topic.
+    pub async fn unsubscribe(&self, topic: Topic) -> Result<()> {
+        debug!("Unsubscribing from {topic:?}");
+        let cmd = Cmd::Unsubscribe(topic);
+        let responses = self.send_cmd_to_closest(topic.name(), &cmd).await?;
+        cmd_ack_quorum(topic.name(), &cmd, responses, |resp| match resp {
+            Response::Cmd(CmdResponse::Unsubscribe(result)) => Ok(result),
+            other => Err(other),
+        })
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, Result, VersionedDataMap};
+use crate::{
+    protocol::types::payment::PaymentProof,
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use bytes::Bytes;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use self_encryption::{ChunkInfo, DataMap, EncryptedChunk, MIN_ENCRYPTABLE_BYTES};
+use std::{collections::BTreeMap, sync::Arc};
+use tracing::{debug, info};
+use xor_name::XorName;
+
+/// The number of chunks fetched ahead of the consumer of a file stream.
+const PREFETCH_CHUNKS: usize = 4;
+
+/// The number of chunks fetched at a time when reading a whole file.
+pub const READ_CONCURRENCY: usize = 8;
+
+/// The chunks a file was self-encrypted into, ready to be paid for and uploaded.
+#[derive(Clone, Debug)]
+pub struct EncryptedFile {
+    /// The address of the chunk holding the data map, from which the file is read back.
+    pub address: ChunkAddress,
+    /// The encrypted chunks of the file, followed by the data map chunk.
+    pub chunks: Vec<Chunk>,
+}
+
+impl EncryptedFile {
+    /// Self-encrypts the bytes into chunks, and stores the data map needed to decrypt
+    /// them in a chunk of its own.
+    ///
+    /// Files of less than [`MIN_ENCRYPTABLE_BYTES`], empty ones included, are too small to
+    /// be self-encrypted, their content being held in the data map chunk instead.
+    pub fn new(bytes: Bytes) -> Result<Self> {
+        if bytes.len() < MIN_ENCRYPTABLE_BYTES {
+            let data_map_chunk = VersionedDataMap::inline(bytes).to_chunk()?;
+            return Ok(Self {
+                address: *data_map_chunk.address(),
+                chunks: vec![data_map_chunk],
+            });
+        }
+
+        let (data_map, encrypted_chunks) = self_encryption::encrypt(bytes)?;
+        let data_map_chunk = VersionedDataMap::new(&data_map).to_chunk()?;
+
+        let address = *data_map_chunk.address();
+        let mut chunks: Vec<Chunk> = encrypted_chunks
+            .into_iter()
+            .map(|encrypted| Chunk::new(encrypted.content))
+            .collect();
+        chunks.push(data_map_chunk);
+
+        Ok(Self { address, chunks })
+    }
+
+    /// Reads and self-encrypts the file at the given path.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn from_path(path: &std::path::Path) -> Result<Self> {
+        let bytes = async_std::fs::read(path).await?;
+        Self::new(Bytes::from(bytes))
+    }
+
+    /// Returns the names of the chunks to be paid for.
+    pub fn names(&self) -> impl Iterator<Item = &XorName> {
+        self.chunks.iter().map(|chunk| chunk.name())
+    }
+}
+
+impl Client {
+    /// Upload all the chunks of the file to their respective close groups, returning
+    /// the address the file can be read back from with [`Client::read_file`].
+    ///
+    /// `payments` holds the proof of payment for the storage of each chunk, by chunk name.
+    pub async fn upload_file(
+        &self,
+        file: EncryptedFile,
+        payments: &BTreeMap<XorName, PaymentProof>,
+    ) -> Result<ChunkAddress> {
+        info!(
+            "Uploading file {:?} of {} chunks",
+            file.address,
+            file.chunks.len()
+        );
+        self.upload_paid_chunks(file.chunks, payments).await?;
+        Ok(file.address)
+    }
+
+    /// Read back the file uploaded at the given address, fetching and decrypting its chunks.
+    ///
+    /// Up to [`READ_CONCURRENCY`] chunks are fetched at a time.
+    pub async fn read_file(&self, address: ChunkAddress) -> Result<Bytes> {
+        self.read_file_with(address, READ_CONCURRENCY).await
+    }
+
+    /// Read back the file as with [`Client::read_file`], fetching up to `concurrency` chunks
+    /// at a time.
+    ///
+    /// The data map is fetched first, then the chunks, a new fetch starting as soon as one
+    /// completes rather than a batch at a time, each chunk being decrypted as it arrives.
+    pub async fn read_file_with(&self, address: ChunkAddress, concurrency: usize) -> Result<Bytes> {
+        let data_map = self.get_file_data_map(address).await?;
+        if let Some(content) = data_map.content {
+            return Ok(content);
+        }
+        let file_chunks = file_chunks(&data_map);
+        debug!("Reading file {address:?} of {} chunks", file_chunks.len());
+
+        let decrypted: Vec<Bytes> = stream::iter(file_chunks)
+            .map(|(window, info)| self.read_chunk_of(window, info))
+            .buffered(concurrency.max(1))
+            .try_collect()
+            .await?;
+
+        Ok(Bytes::from(decrypted.concat()))
+    }
+
+    /// Get the data map of the file uploaded at the given address, for its size to be told
+    /// and ranges of it to be read with [`Client::read_file_range`].
+    pub async fn get_file_data_map(&self, address: ChunkAddress) -> Result<VersionedDataMap> {
+        let data_map_chunk = self.get_chunk(address).await?;
+        VersionedDataMap::from_chunk(&data_map_chunk)
+    }
+
+    /// Read `len` bytes of the file described by the data map, from `offset` on, fetching
+    /// only the chunks holding them. Less bytes are returned past the end of the file.
+    pub async fn read_file_range(
+        &self,
+        data_map: &VersionedDataMap,
+        offset: u64,
+        len: u64,
+    ) -> Result<Bytes> {
+        let end = offset.saturating_add(len).min(data_map.file_size);
+        if offset >= end {
+            return Ok(Bytes::new());
+        }
+        if let Some(content) = &data_map.content {
+            return Ok(content.slice(offset as usize..end as usize));
+        }
+
+        // The chunks overlapping the range, with the offset each starts at.
+        let mut chunk_start = 0;
+        let mut overlapping = vec![];
+        for (window, info) in file_chunks(data_map) {
+            let chunk_end = chunk_start + info.src_size as u64;
+            if chunk_end > offset && chunk_start < end {
+                overlapping.push((chunk_start, window, info));
+            }
+            chunk_start = chunk_end;
+        }
+        let addresses = overlapping
+            .iter()
+            .map(|(_, _, info)| ChunkAddress(info.dst_hash))
+            .collect();
+        let chunks = self.get_chunks(addresses).await?;
+
+        let mut range = Vec::with_capacity((end - offset) as usize);
+        for ((chunk_start, window, info), chunk) in overlapping.iter().zip(chunks) {
+            let decrypted = decrypt_chunk(window, info, &chunk?)?;
+            let from = offset.saturating_sub(*chunk_start) as usize;
+            let to = (end - chunk_start).min(decrypted.len() as u64) as usize;
+            range.extend_from_slice(&decrypted[from..to]);
+        }
+        Ok(Bytes::from(range))
+    }
+
+    // Uploads the chunks with the proof of payment for each of them, failing if any could not be stored.
+    pub(super) async fn upload_paid_chunks(
+        &self,
+        chunks: Vec<Chunk>,
+        payments: &BTreeMap<XorName, PaymentProof>,
+    ) -> Result<()> {
+        let chunks = chunks.into_iter().map(|chunk| {
+            let payment = payments.get(chunk.name()).cloned().unwrap_or_default();
+            (chunk, payment)
+        });
+        let report = self.upload_chunks(chunks).await;
+        match report.failed.into_iter().next() {
+            Some((_, err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Stream the content of the file uploaded at the given address, decrypting its chunks
+    /// in order as they are fetched, so that the whole file need not be held in memory.
+    ///
+    /// A few chunks are fetched ahead of the consumer of the stream.
+    pub fn read_bytes_stream(
+        &self,
+        address: ChunkAddress,
+    ) -> impl Stream<Item = Result<Bytes>> + '_ {
+        self.read_bytes_stream_with(address, PREFETCH_CHUNKS)
+    }
+
+    /// Stream the content of the file as with [`Client::read_bytes_stream`], fetching up to
+    /// `prefetch` chunks ahead of the consumer of the stream.
+    pub fn read_bytes_stream_with(
+        &self,
+        address: ChunkAddress,
+        prefetch: usize,
+    ) -> impl Stream<Item = Result<Bytes>> + '_ {
+        let prefetch = prefetch.max(1);
+        stream::once(self.get_file_data_map(address))
+            .map_ok(move |data_map| match data_map.content {
+                Some(content) => stream::once(async move { Ok(content) }).left_stream(),
+                None => stream::iter(file_chunks(&data_map))
+                    .map(move |(window, info)| self.read_chunk_of(window, info))
+                    .buffered(prefetch)
+                    .right_stream(),
+            })
+            .try_flatten()
+    }
+
+    // Fetches and decrypts a single chunk of a file.
+    async fn read_chunk_of(&self, window: Arc<DataMap>, info: ChunkInfo) -> Result<Bytes> {
+        let chunk = self.get_chunk(ChunkAddress(info.dst_hash)).await?;
+        decrypt_chunk(&window, &info, &chunk)
+    }
+}
+
+// A chunk of a file, along with the data map of the window it was self-encrypted with.
+type FileChunk = (Arc<DataMap>, ChunkInfo);
+
+// Returns the chunks of the file, in the order of their content in the file.
+fn file_chunks(data_map: &VersionedDataMap) -> Vec<FileChunk> {
+    data_map
+        .to_data_maps()
+        .into_iter()
+        .flat_map(|window| {
+            let window = Arc::new(window);
+            window
+                .infos()
+                .into_iter()
+                .map(move |info| (Arc::clone(&window), info))
+        })
+        .collect()
+}
+
+// Decrypts a single chunk of a file, as described by its info in the data map of its window.
+fn decrypt_chunk(data_map: &DataMap, info: &ChunkInfo, chunk: &Chunk) -> Result<Bytes> {
+    let encrypted_chunk = EncryptedChunk {
+        index: info.index,
+        content: chunk.value().clone(),
+    };
+    Ok(self_encryption::decrypt_range(
+        data_map,
+        &[encrypted_chunk],
+        0,
+        info.src_size,
+    )?)
+}
@@ -0,0 +1,236 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, Error, Result};
+use crate::{
+    protocol::types::payment::PaymentProof,
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::info;
+use xor_name::XorName;
+
+// Browsers have no filesystem to build folders from.
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    super::EncryptedFile,
+    async_std::fs::{read, read_dir},
+    bytes::Bytes,
+    futures::{future::BoxFuture, FutureExt, StreamExt},
+    std::path::{Path, PathBuf},
+    tracing::debug,
+};
+
+/// An entry of a [`Folder`].
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub enum FolderEntry {
+    /// A file, to be read with [`Client::read_file`].
+    File {
+        /// The address the file is read back from.
+        address: ChunkAddress,
+        /// The size of the file, in bytes.
+        size: u64,
+    },
+    /// A sub folder, to be read with [`Client::get_folder`].
+    Folder(ChunkAddress),
+}
+
+/// A directory, listing the files and sub folders in it by name.
+///
+/// Folders are stored as chunks, so that the whole of a directory tree can be found
+/// from the address of its root folder.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub struct Folder {
+    entries: BTreeMap<String, FolderEntry>,
+}
+
+impl Folder {
+    /// Returns the entries of the folder, by name.
+    pub fn entries(&self) -> &BTreeMap<String, FolderEntry> {
+        &self.entries
+    }
+
+    /// Returns the entry with the given name.
+    pub fn get(&self, name: &str) -> Option<&FolderEntry> {
+        self.entries.get(name)
+    }
+
+    /// Adds the entry under the given name, returning the entry it replaces, if any.
+    pub fn insert(&mut self, name: String, entry: FolderEntry) -> Option<FolderEntry> {
+        self.entries.insert(name, entry)
+    }
+
+    /// Removes the entry with the given name, returning it, if any.
+    pub fn remove(&mut self, name: &str) -> Option<FolderEntry> {
+        self.entries.remove(name)
+    }
+
+    /// Serialises the folder into the chunk it is stored in, its address being the one
+    /// the folder is read back from with [`Client::get_folder`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let bytes = rmp_serde::to_vec(self).map_err(|err| Error::Serialisation(err.to_string()))?;
+        Ok(Chunk::new(Bytes::from(bytes)))
+    }
+}
+
+/// The chunks of a directory tree, i.e. of its folders and of the files in them,
+/// ready to be paid for and uploaded.
+#[derive(Clone, Debug)]
+pub struct EncryptedFolder {
+    /// The address of the root folder.
+    pub address: ChunkAddress,
+    /// All the chunks of the tree.
+    pub chunks: Vec<Chunk>,
+}
+
+impl EncryptedFolder {
+    /// Self-encrypts the files in the directory at the given path and its subdirectories,
+    /// and builds the folders listing them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn from_path(path: &Path) -> Result<Self> {
+        let mut chunks = vec![];
+        let address = encrypt_dir(path.to_path_buf(), &mut chunks).await?;
+        Ok(Self { address, chunks })
+    }
+
+    /// Returns the names of the chunks to be paid for.
+    pub fn names(&self) -> impl Iterator<Item = &XorName> {
+        self.chunks.iter().map(|chunk| chunk.name())
+    }
+}
+
+// Encrypts the files of the dir and builds its folder, recursing into subdirectories.
+// The chunks are appended to `chunks`, the address of the folder is returned.
+#[cfg(not(target_arch = "wasm32"))]
+fn encrypt_dir(path: PathBuf, chunks: &mut Vec<Chunk>) -> BoxFuture<'_, Result<ChunkAddress>> {
+    async move {
+        let mut folder = Folder::default();
+        let mut dir_entries = read_dir(&path).await?;
+        while let Some(dir_entry) = dir_entries.next().await {
+            let dir_entry = dir_entry?;
+            let name = dir_entry.file_name().to_string_lossy().into_owned();
+            let file_type = dir_entry.file_type().await?;
+            let entry = if file_type.is_dir() {
+                FolderEntry::Folder(encrypt_dir(dir_entry.path().into(), chunks).await?)
+            } else if file_type.is_file() {
+                let bytes = read(dir_entry.path()).await?;
+                let size = bytes.len() as u64;
+                let file = EncryptedFile::new(Bytes::from(bytes))?;
+                chunks.extend(file.chunks);
+                FolderEntry::File {
+                    address: file.address,
+                    size,
+                }
+            } else {
+                debug!("Skipping {:?}, neither a file nor a dir", dir_entry.path());
+                continue;
+            };
+            let _ = folder.entries.insert(name, entry);
+        }
+
+        let chunk = folder.to_chunk()?;
+        let address = *chunk.address();
+        chunks.push(chunk);
+        Ok(address)
+    }
+    .boxed()
+}
+
+impl Client {
+    /// Upload all the chunks of the directory tree, returning the address of its root folder.
+    ///
+    /// `payments` holds the proof of payment for the storage of each chunk, by chunk name.
+    pub async fn upload_folder(
+        &self,
+        folder: EncryptedFolder,
+        payments: &BTreeMap<XorName, PaymentProof>,
+    ) -> Result<ChunkAddress> {
+        info!(
+            "Uploading folder {:?} of {} chunks",
+            folder.address,
+            folder.chunks.len()
+        );
+        self.upload_paid_chunks(folder.chunks, payments).await?;
+        Ok(folder.address)
+    }
+
+    /// Get the folder at the given address.
+    pub async fn get_folder(&self, address: ChunkAddress) -> Result<Folder> {
+        let chunk = self.get_chunk(address).await?;
+        rmp_serde::from_slice(chunk.value()).map_err(|err| Error::Serialisation(err.to_string()))
+    }
+
+    /// Resolve a `path/to/file` style path, relative to the folder at the given address,
+    /// to the entry it points to.
+    pub async fn resolve_path(&self, root: ChunkAddress, path: &str) -> Result<FolderEntry> {
+        let mut entry = FolderEntry::Folder(root);
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let folder = match entry {
+                FolderEntry::Folder(address) => self.get_folder(address).await?,
+                FolderEntry::File { .. } => return Err(Error::PathNotFound(path.to_string())),
+            };
+            entry = folder
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::PathNotFound(path.to_string()))?;
+        }
+        Ok(entry)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::client::VersionedDataMap;
+    use eyre::Result;
+
+    #[async_std::test]
+    async fn empty_and_tiny_files_are_encrypted_into_the_tree() -> Result<()> {
+        let dir = std::env::temp_dir().join(hex::encode(XorName::random(&mut rand::thread_rng())));
+        std::fs::create_dir_all(dir.join("sub"))?;
+        std::fs::write(dir.join("empty"), b"")?;
+        std::fs::write(dir.join("sub").join("tiny"), b"hi")?;
+
+        let result = EncryptedFolder::from_path(&dir).await;
+        std::fs::remove_dir_all(&dir)?;
+        let encrypted = result?;
+
+        let get = |address: &ChunkAddress| {
+            encrypted
+                .chunks
+                .iter()
+                .find(|chunk| chunk.address() == address)
+                .expect("chunk of the tree")
+        };
+        let root: Folder = rmp_serde::from_slice(get(&encrypted.address).value())?;
+        let empty = match root.get("empty") {
+            Some(FolderEntry::File { address, size: 0 }) => address,
+            other => panic!("unexpected entry {other:?}"),
+        };
+        assert_eq!(
+            VersionedDataMap::from_chunk(get(empty))?.content,
+            Some(Bytes::new())
+        );
+
+        let sub: Folder = match root.get("sub") {
+            Some(FolderEntry::Folder(address)) => rmp_serde::from_slice(get(address).value())?,
+            other => panic!("unexpected entry {other:?}"),
+        };
+        let tiny = match sub.get("tiny") {
+            Some(FolderEntry::File { address, size: 2 }) => address,
+            other => panic!("unexpected entry {other:?}"),
+        };
+        assert_eq!(
+            VersionedDataMap::from_chunk(get(tiny))?.content,
+            Some(Bytes::from_static(b"hi"))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, Error, Result};
+use crate::{
+    protocol::types::payment::PaymentProof,
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use async_std::fs::{create_dir_all, read, rename, write};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use xor_name::XorName;
+
+const MANIFEST_FILENAME: &str = "manifest";
+const CHUNKS_DIR_NAME: &str = "chunks";
+
+/// The number of chunks uploaded between two saves of the progress of an upload.
+const CHUNKS_PER_SAVE: usize = 64;
+
+/// The progress of an upload, persisted in a dir along with the chunks being uploaded,
+/// so that an interrupted upload can be resumed without sending the stored chunks again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UploadManifest {
+    #[serde(skip)]
+    dir: PathBuf,
+    /// The address the upload is read back from, once complete.
+    pub address: ChunkAddress,
+    /// The chunks of the upload, and whether each has been confirmed stored.
+    chunks: BTreeMap<ChunkAddress, bool>,
+}
+
+impl UploadManifest {
+    /// Persists the chunks to upload in the given dir, along with a manifest of the upload.
+    pub async fn create(dir: &Path, address: ChunkAddress, chunks: Vec<Chunk>) -> Result<Self> {
+        create_dir_all(dir.join(CHUNKS_DIR_NAME)).await?;
+        let mut manifest = Self {
+            dir: dir.to_path_buf(),
+            address,
+            chunks: BTreeMap::new(),
+        };
+        for chunk in chunks {
+            write(manifest.chunk_path(chunk.address()), chunk.value()).await?;
+            let _ = manifest.chunks.insert(*chunk.address(), false);
+        }
+        manifest.save().await?;
+        Ok(manifest)
+    }
+
+    /// Loads the manifest of the upload persisted in the given dir.
+    pub async fn load(dir: &Path) -> Result<Self> {
+        let bytes = read(dir.join(MANIFEST_FILENAME)).await?;
+        let mut manifest: Self =
+            rmp_serde::from_slice(&bytes).map_err(|err| Error::Serialisation(err.to_string()))?;
+        manifest.dir = dir.to_path_buf();
+        Ok(manifest)
+    }
+
+    /// Returns the addresses of the chunks yet to be confirmed stored.
+    pub fn pending(&self) -> impl Iterator<Item = &ChunkAddress> {
+        self.chunks
+            .iter()
+            .filter(|(_, stored)| !**stored)
+            .map(|(address, _)| address)
+    }
+
+    /// Returns true once all the chunks have been confirmed stored.
+    pub fn is_complete(&self) -> bool {
+        self.pending().next().is_none()
+    }
+
+    fn chunk_path(&self, address: &ChunkAddress) -> PathBuf {
+        self.dir
+            .join(CHUNKS_DIR_NAME)
+            .join(hex::encode(address.name()))
+    }
+
+    // Writes to a temporary file first, for an interrupted save not to leave the manifest
+    // half written, and the upload unable to be resumed.
+    async fn save(&self) -> Result<()> {
+        let bytes = rmp_serde::to_vec(self).map_err(|err| Error::Serialisation(err.to_string()))?;
+        let path = self.dir.join(MANIFEST_FILENAME);
+        let tmp_path = path.with_extension("tmp");
+        write(&tmp_path, bytes).await?;
+        rename(&tmp_path, path).await?;
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Upload the chunks of the manifest not yet confirmed stored, saving the progress made
+    /// as it goes, and returning the address of the upload once all chunks are stored.
+    ///
+    /// `payments` holds the proof of payment for the storage of each chunk, by chunk name.
+    pub async fn resume_upload(
+        &self,
+        manifest: &mut UploadManifest,
+        payments: &BTreeMap<XorName, PaymentProof>,
+    ) -> Result<ChunkAddress> {
+        let pending: Vec<ChunkAddress> = manifest.pending().copied().collect();
+        info!(
+            "Resuming upload of {:?}, {} of {} chunks pending",
+            manifest.address,
+            pending.len(),
+            manifest.chunks.len()
+        );
+
+        let mut first_err = None;
+        for batch in pending.chunks(CHUNKS_PER_SAVE) {
+            let mut chunks = vec![];
+            for address in batch {
+                let value = read(manifest.chunk_path(address)).await?;
+                let payment = payments.get(address.name()).cloned().unwrap_or_default();
+                chunks.push((Chunk::new(Bytes::from(value)), payment));
+            }
+
+            let report = self.upload_chunks(chunks).await;
+            for address in report.stored.into_iter().chain(report.skipped) {
+                let _ = manifest.chunks.insert(address, true);
+            }
+            manifest.save().await?;
+            if first_err.is_none() {
+                first_err = report.failed.into_iter().next().map(|(_, err)| err);
+            }
+        }
+
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(manifest.address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn a_saved_manifest_is_loaded_back() -> Result<()> {
+        let dir = std::env::temp_dir().join(hex::encode(XorName::random(&mut rand::thread_rng())));
+        let chunk = Chunk::new(Bytes::from("manifest test chunk"));
+        let manifest = UploadManifest::create(&dir, *chunk.address(), vec![chunk.clone()]).await?;
+
+        let loaded = UploadManifest::load(&dir).await?;
+
+        assert_eq!(loaded.address, manifest.address);
+        assert_eq!(loaded.pending().collect::<Vec<_>>(), vec![chunk.address()]);
+        assert!(!dir.join(MANIFEST_FILENAME).with_extension("tmp").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod access_token;
+mod audit;
+#[cfg(all(feature = "s3", not(target_arch = "wasm32")))]
+mod buckets;
+mod chunks;
+mod data_map;
+mod encrypted_register;
+mod error;
+mod events;
+mod files;
+mod folders;
+#[cfg(not(target_arch = "wasm32"))]
+mod manifest;
+mod pages;
+mod register;
+mod spends;
+mod store_costs;
+mod streaming;
+mod transfers;
+mod upload;
+#[cfg(not(target_arch = "wasm32"))]
+mod wallet;
+
+pub use self::{
+    audit::SpendAudit,
+    data_map::{ChunkEntry, VersionedDataMap, DATA_MAP_VERSION},
+    encrypted_register::{EncryptedRegister, RegisterKey},
+    error::{Error, Result},
+    files::{EncryptedFile, READ_CONCURRENCY},
+    folders::{EncryptedFolder, Folder, FolderEntry},
+    register::ClientRegister,
+    streaming::{StreamingEncryptor, WINDOW_SIZE},
+    transfers::{create_genesis_dbc, create_transfer, OfflineTransfer},
+    upload::UploadReport,
+};
+
+// The wallet, upload manifests and access tokens are written to disk, which browsers do not provide.
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::{access_token::AccessToken, manifest::UploadManifest, wallet::Wallet};
+
+#[cfg(all(feature = "s3", not(target_arch = "wasm32")))]
+pub use self::buckets::{Bucket, BUCKET_TAG};
+
+use crate::{
+    network::{Error as NetworkError, Network},
+    protocol::{
+        error::Result as ProtocolResult,
+        messages::{Cmd, CmdResponse, Request, Response, SignedResponse},
+    },
+};
+use libp2p::PeerId;
+use std::time::Duration;
+use tracing::{debug, trace, warn};
+use xor_name::XorName;
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    crate::network::NetworkSwarmLoop,
+    async_std::task::{sleep, spawn},
+    futures::StreamExt,
+    libp2p::{multiaddr::Protocol, Multiaddr},
+    std::time::Instant,
+};
+
+/// How long to wait by default for a client to connect to the network, see [`Client::connect`].
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client API implementation to store and get data.
+#[derive(Clone)]
+pub struct Client {
+    network: Network,
+}
+
+impl Client {
+    /// Instantiate a new `Client` on top of the given `Network`
+    pub fn new(network: Network) -> Self {
+        Self { network }
+    }
+
+    /// Connects a client to the network through the given peers, whose addresses end with
+    /// their `/p2p/<peer id>`, waiting up to `timeout` for one of them, or a peer found on
+    /// the local network, to be in its routing table.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn connect(peers: &[Multiaddr], timeout: Duration) -> Result<Self> {
+        let (mut network, mut events, swarm_loop) = NetworkSwarmLoop::new_client()?;
+        let _handle = spawn(swarm_loop.run());
+        // The client has no use for the events, they are only drained for the swarm to keep running.
+        let _handle = spawn(async move { while events.next().await.is_some() {} });
+
+        for addr in peers {
+            let mut addr = addr.clone();
+            let peer_id = match addr.pop() {
+                Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash)
+                    .map_err(|_| Error::InvalidPeerAddress(addr.clone()))?,
+                _ => return Err(Error::InvalidPeerAddress(addr)),
+            };
+            network.dial(peer_id, addr).await?;
+        }
+
+        let started = Instant::now();
+        while network.routing_table_size().await? == 0 {
+            if started.elapsed() > timeout {
+                return Err(Error::ConnectTimeout(timeout));
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        Ok(Self::new(network))
+    }
+
+    /// Send a `Request` to the peers closest to the given `XorName`, returning the
+    /// `Response` of each of them, or the error encountered while sending to them.
+    ///
+    /// Responses whose signature does not verify are returned as errors.
+    pub(super) async fn send_to_closest(
+        &self,
+        xor_name: XorName,
+        request: Request,
+    ) -> Result<Vec<Result<Response>>> {
+        let responses = self.network.send_to_closest(xor_name, request).await?;
+        if responses.is_empty() {
+            return Err(Error::NoPeersFound(xor_name));
+        }
+        Ok(responses
+            .into_iter()
+            .map(|(peer, resp)| verify_response(peer, resp))
+            .collect())
+    }
+
+    /// Send a `Request` to the peers closest to the given `XorName`, as with
+    /// [`Client::send_to_closest`], widening the search to more peers until one of them
+    /// returns a `Response` accepted by `found`.
+    pub(super) async fn search_closest(
+        &self,
+        xor_name: XorName,
+        request: Request,
+        found: impl Fn(&Response) -> bool,
+    ) -> Result<Vec<Result<Response>>> {
+        let responses = self
+            .network
+            .search_closest(xor_name, request, |signed| {
+                found(&signed.response) && signed.verify().is_ok()
+            })
+            .await?;
+        if responses.is_empty() {
+            return Err(Error::NoPeersFound(xor_name));
+        }
+        Ok(responses
+            .into_iter()
+            .map(|(peer, resp)| verify_response(peer, resp))
+            .collect())
+    }
+
+    /// Sign the `Cmd` and send it to the peers closest to the given `XorName`,
+    /// see [`Client::send_to_closest`].
+    pub(super) async fn send_cmd_to_closest(
+        &self,
+        xor_name: XorName,
+        cmd: &Cmd,
+    ) -> Result<Vec<Result<Response>>> {
+        let signed_cmd = self.network.sign_cmd(cmd.clone())?;
+        self.send_to_closest(xor_name, Request::Cmd(signed_cmd))
+            .await
+    }
+
+    /// Send a `Request` to the given peer, returning its `Response` once its signature is verified.
+    pub(super) async fn send_to_peer(&self, peer: PeerId, request: Request) -> Result<Response> {
+        let resp = self.network.clone().send_request(request, peer).await;
+        verify_response(peer, resp)
+    }
+}
+
+// Checks that the response is signed by the peer it came from, handing back the response
+// if it is.
+fn verify_response(peer: PeerId, resp: Result<SignedResponse, NetworkError>) -> Result<Response> {
+    let signed = resp?;
+    let signer = signed.verify().map_err(|err| {
+        warn!("Discarding response from {peer:?}: {err}");
+        err
+    })?;
+    if signer != peer {
+        warn!("Discarding response from {peer:?} signed by {signer:?}");
+        return Err(Error::Network(NetworkError::InvalidResponse {
+            peer,
+            reason: format!("signed by {signer:?}"),
+        }));
+    }
+    trace!("Verified response signed by {signer:?}");
+    Ok(signed.response)
+}
+
+// Succeeds once a majority of the peers the cmd was sent to acknowledge having applied it.
+// `cmd_error` extracts the error from the expected response type, handing back any other response.
+pub(super) fn cmd_ack_quorum(
+    xor_name: XorName,
+    cmd: &Cmd,
+    responses: Vec<Result<Response>>,
+    cmd_error: impl Fn(Response) -> std::result::Result<ProtocolResult<()>, Response>,
+) -> Result<()> {
+    let cmd_hash = cmd.hash();
+    let quorum = responses.len() / 2 + 1;
+    let mut acks = 0;
+    let mut last_err = None;
+    for resp in responses {
+        match resp {
+            Ok(Response::Cmd(CmdResponse::Ack { cmd_hash: acked })) if acked == cmd_hash => {
+                acks += 1
+            }
+            Ok(resp) => match cmd_error(resp) {
+                Ok(Err(err)) => last_err = Some(Error::from(err)),
+                // Only an ack confirms the data was durably stored.
+                Ok(Ok(())) => last_err = Some(Error::CmdNotAcknowledged(cmd_hash)),
+                Err(unexpected) => last_err = Some(Error::UnexpectedResponse(unexpected)),
+            },
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    if acks >= quorum {
+        debug!("Cmd {cmd_hash:?} acknowledged by {acks} peers");
+        return Ok(());
+    }
+    Err(last_err.unwrap_or(Error::NoPeersFound(xor_name)))
+}
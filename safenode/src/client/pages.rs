@@ -0,0 +1,82 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, Error, Result};
+use crate::{
+    protocol::{
+        messages::{Query, QueryResponse, Request, Response},
+        types::page::Page,
+    },
+    storage::chunks::ChunkAddress,
+};
+use libp2p::PeerId;
+use tracing::trace;
+
+/// The max number of items listed from a single peer.
+const MAX_LISTED_ITEMS: usize = 1_000_000;
+
+impl Client {
+    /// List the addresses of all the chunks held by the given peer, fetching
+    /// every page of the listing.
+    pub async fn list_chunks(&self, peer: PeerId) -> Result<Vec<ChunkAddress>> {
+        self.fetch_all_pages(
+            peer,
+            |cursor| Request::Query(Query::ListChunks { cursor }),
+            |resp| match resp {
+                Response::Query(QueryResponse::ListChunks(page)) => Ok(page),
+                other => Err(other),
+            },
+        )
+        .await
+    }
+
+    // Requests the pages of a listing from the peer one after the other, until the last one.
+    // `page` extracts the page from the expected response type, handing back any other response.
+    //
+    // Each page must hold items in ascending order past the cursor, and end with the cursor of
+    // the next, for a peer not to have the client loop forever, nor list more than
+    // `MAX_LISTED_ITEMS`.
+    async fn fetch_all_pages<T: Ord + Clone>(
+        &self,
+        peer: PeerId,
+        request: impl Fn(Option<T>) -> Request,
+        page: impl Fn(Response) -> std::result::Result<Page<T>, Response>,
+    ) -> Result<Vec<T>> {
+        let invalid = |reason: &str| Error::InvalidListing {
+            peer,
+            reason: reason.to_string(),
+        };
+        let mut items = Vec::new();
+        let mut cursor: Option<T> = None;
+        loop {
+            let resp = self.send_to_peer(peer, request(cursor.clone())).await?;
+            let mut page = page(resp).map_err(Error::UnexpectedResponse)?;
+            trace!("Got a page of {} items from {peer:?}", page.items.len());
+            let mut previous = cursor.as_ref();
+            for item in &page.items {
+                if previous.is_some_and(|previous| item <= previous) {
+                    return Err(invalid("items not in ascending order past the cursor"));
+                }
+                previous = Some(item);
+            }
+            if items.len() + page.items.len() > MAX_LISTED_ITEMS {
+                return Err(invalid("more items than can be listed"));
+            }
+            let next = match page.next {
+                Some(next) if page.items.last() == Some(&next) => next,
+                Some(_) => return Err(invalid("the cursor is not the last item of its page")),
+                None => {
+                    items.append(&mut page.items);
+                    return Ok(items);
+                }
+            };
+            items.append(&mut page.items);
+            cursor = Some(next);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{cmd_ack_quorum, Client, Error, Result};
+use crate::protocol::{
+    error::Error as ProtocolError,
+    messages::{Cmd, CmdResponse, Query, QueryResponse, Request, Response},
+    types::{
+        payment::PaymentProof,
+        register::{Entry, EntryHash, Policy, Register, RegisterAddress},
+    },
+};
+use libp2p::identity::PublicKey;
+use std::collections::BTreeSet;
+use tracing::{debug, warn};
+use xor_name::XorName;
+
+/// A `Register` as seen by the client, kept in sync with its replicas on the network.
+#[derive(Clone)]
+pub struct ClientRegister {
+    client: Client,
+    register: Register,
+}
+
+impl ClientRegister {
+    /// Create a new, empty `Register` on the network, owned by us and written to by us only.
+    ///
+    /// The `payment` spends must commit to the register's location on the network, see
+    /// [`PaymentProof::reason_for`] and [`RegisterAddress::id`].
+    pub async fn create(
+        client: Client,
+        name: XorName,
+        tag: u64,
+        payment: PaymentProof,
+    ) -> Result<Self> {
+        Self::create_with_writers(client, name, tag, &[], payment).await
+    }
+
+    /// Create a new, empty `Register` on the network, owned by us, which the given keys
+    /// are allowed to write to as well, see [`ClientRegister::create`].
+    pub async fn create_with_writers(
+        client: Client,
+        name: XorName,
+        tag: u64,
+        writers: &[PublicKey],
+        payment: PaymentProof,
+    ) -> Result<Self> {
+        let address = RegisterAddress::new(name, tag);
+        let policy = Policy::new(&client.network.keypair().public())
+            .with_writers(writers)
+            .sign(address, client.network.keypair())?;
+        debug!("Creating register {address:?}");
+        let cmd = Cmd::RegisterCreate {
+            address,
+            policy: policy.clone(),
+            payment,
+        };
+        let responses = client.send_cmd_to_closest(address.id(), &cmd).await?;
+        cmd_ack_quorum(address.id(), &cmd, responses, |resp| match resp {
+            Response::Cmd(CmdResponse::RegisterCreate(result)) => Ok(result),
+            other => Err(other),
+        })?;
+
+        Ok(Self {
+            client,
+            register: Register::new(address, policy)?,
+        })
+    }
+
+    /// Retrieve a `Register` from the network, merging the replicas held by the
+    /// peers responsible for it.
+    pub async fn retrieve(client: Client, name: XorName, tag: u64) -> Result<Self> {
+        let address = RegisterAddress::new(name, tag);
+        let register = get_register(&client, address).await?;
+        Ok(Self { client, register })
+    }
+
+    /// Returns the client the register is synced through.
+    pub(super) fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Returns the address of the register.
+    pub fn address(&self) -> &RegisterAddress {
+        self.register.address()
+    }
+
+    /// Returns the current entries of the register, as last synced with the network.
+    /// More than one entry is returned when there have been concurrent writes.
+    pub fn read(&self) -> BTreeSet<(EntryHash, Entry)> {
+        self.register.read()
+    }
+
+    /// Write an entry superseding all the current entries, and send the resulting op to
+    /// the peers responsible for the register.
+    pub async fn write(&mut self, entry: &[u8]) -> Result<EntryHash> {
+        let (hash, op) = self
+            .register
+            .write(entry.to_vec(), self.client.network.keypair())?;
+        let cmd = Cmd::RegisterEdit(op);
+        let responses = self
+            .client
+            .send_cmd_to_closest(self.address().id(), &cmd)
+            .await?;
+        cmd_ack_quorum(self.address().id(), &cmd, responses, |resp| match resp {
+            Response::Cmd(CmdResponse::RegisterEdit(result)) => Ok(result),
+            other => Err(other),
+        })?;
+        Ok(hash)
+    }
+
+    /// Returns the policy setting who is allowed to write, as last synced with the network.
+    pub fn policy(&self) -> &Policy {
+        self.register.policy()
+    }
+
+    /// Replace the keys allowed to write besides the owner, which only the owner can do.
+    pub async fn set_writers(&mut self, writers: &[PublicKey]) -> Result<()> {
+        let op =
+            self.register
+                .policy()
+                .next(*self.address(), writers, self.client.network.keypair())?;
+        self.register.apply_policy_op(op.clone())?;
+        let cmd = Cmd::RegisterSetPolicy(op);
+        let responses = self
+            .client
+            .send_cmd_to_closest(self.address().id(), &cmd)
+            .await?;
+        cmd_ack_quorum(self.address().id(), &cmd, responses, |resp| match resp {
+            Response::Cmd(CmdResponse::RegisterSetPolicy(result)) => Ok(result),
+            other => Err(other),
+        })?;
+        Ok(())
+    }
+
+    /// Pull the latest state of the register from the network, merging it into ours.
+    pub async fn sync(&mut self) -> Result<()> {
+        let remote = get_register(&self.client, *self.address()).await?;
+        self.register.merge(remote)?;
+        Ok(())
+    }
+
+    /// Have the peers responsible for the register sync their replicas with each other, then
+    /// pull the latest state of the register and return its current entries.
+    ///
+    /// Unlike [`ClientRegister::read`] after a [`ClientRegister::sync`], the entries include
+    /// every write acknowledged by any of the peers, even if their replicas had diverged.
+    pub async fn read_consistent(&mut self) -> Result<BTreeSet<(EntryHash, Entry)>> {
+        let cmd = Cmd::SyncRegister(*self.address());
+        let responses = self
+            .client
+            .send_cmd_to_closest(self.address().id(), &cmd)
+            .await?;
+        cmd_ack_quorum(self.address().id(), &cmd, responses, |resp| match resp {
+            Response::Cmd(CmdResponse::SyncRegister(result)) => Ok(result),
+            other => Err(other),
+        })?;
+        self.sync().await?;
+        Ok(self.read())
+    }
+}
+
+async fn get_register(client: &Client, address: RegisterAddress) -> Result<Register> {
+    let responses = client
+        .send_to_closest(address.id(), Request::Query(Query::GetRegister(address)))
+        .await?;
+
+    let mut merged: Option<Register> = None;
+    let mut last_err = None;
+    for resp in responses {
+        match resp {
+            Ok(Response::Query(QueryResponse::GetRegister(Ok(replica)))) => {
+                // A replica failing verification is left out, rather than failing the read.
+                let result = if replica.address() != &address {
+                    Err(ProtocolError::RegisterAddressMismatch {
+                        dst: address,
+                        op: *replica.address(),
+                    })
+                } else {
+                    match &mut merged {
+                        Some(register) => register.merge(replica),
+                        None => match replica.verified() {
+                            Ok(replica) => {
+                                merged = Some(replica);
+                                Ok(())
+                            }
+                            Err(err) => Err(err),
+                        },
+                    }
+                };
+                if let Err(err) = result {
+                    warn!("Invalid replica of register {address:?}: {err}");
+                    last_err = Some(Error::from(err));
+                }
+            }
+            Ok(Response::Query(QueryResponse::GetRegister(Err(err)))) => {
+                last_err = Some(Error::from(err))
+            }
+            Ok(other) => last_err = Some(Error::UnexpectedResponse(other)),
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    merged.ok_or_else(|| last_err.unwrap_or(Error::NoPeersFound(address.id())))
+}
@@ -0,0 +1,75 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{cmd_ack_quorum, Client, Error, Result};
+use crate::protocol::{
+    error::Error as ProtocolError,
+    messages::{Cmd, CmdResponse, Query, QueryResponse, Request, Response},
+    types::spend::DbcAddress,
+};
+use sn_dbc::{DbcId, DbcTransaction, SignedSpend};
+use tracing::debug;
+
+impl Client {
+    /// Send the spend of a Dbc to the close group of its address, to be recorded in the spentbook,
+    /// along with the transaction the Dbc was created by, for its parents to be checked.
+    pub async fn spend_dbc(
+        &self,
+        signed_spend: SignedSpend,
+        parent_tx: DbcTransaction,
+    ) -> Result<()> {
+        let addr = DbcAddress::from_dbc_id(signed_spend.dbc_id());
+        debug!("Sending spend of {addr:?}");
+        let cmd = Cmd::SpendDbc {
+            signed_spend: Box::new(signed_spend),
+            parent_tx: Box::new(parent_tx),
+        };
+        let responses = self.send_cmd_to_closest(*addr.name(), &cmd).await?;
+        cmd_ack_quorum(*addr.name(), &cmd, responses, |resp| match resp {
+            Response::Cmd(CmdResponse::Spend(result)) => Ok(result),
+            other => Err(other),
+        })
+    }
+
+    /// Get the spend of the given Dbc from the close group of its address.
+    ///
+    /// Fails if any of the peers reports a double spend attempt, or holds a spend
+    /// differing from the one held by the others.
+    pub async fn get_spend(&self, dbc_id: &DbcId) -> Result<SignedSpend> {
+        let addr = DbcAddress::from_dbc_id(dbc_id);
+        let request = Request::Query(Query::GetSpend(addr));
+        let responses = self.send_to_closest(*addr.name(), request).await?;
+
+        let mut found: Option<SignedSpend> = None;
+        let mut last_err = None;
+        for resp in responses {
+            match resp {
+                Ok(Response::Query(QueryResponse::GetSpend(Ok(spend)))) => match &found {
+                    Some(existing) if existing != &spend => {
+                        return Err(Error::from(ProtocolError::DoubleSpendAttempt {
+                            new: Box::new(spend),
+                            existing: Box::new(existing.clone()),
+                        }))
+                    }
+                    Some(_) => {}
+                    None => found = Some(spend),
+                },
+                Ok(Response::Query(QueryResponse::GetSpend(Err(
+                    err @ ProtocolError::DoubleSpendAttempt { .. },
+                )))) => return Err(Error::from(err)),
+                Ok(Response::Query(QueryResponse::GetSpend(Err(err)))) => {
+                    last_err = Some(Error::from(err))
+                }
+                Ok(other) => last_err = Some(Error::UnexpectedResponse(other)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        found.ok_or_else(|| last_err.unwrap_or(Error::NoPeersFound(*addr.name())))
+    }
+}
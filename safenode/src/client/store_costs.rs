@@ -0,0 +1,43 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, Result};
+use crate::protocol::{
+    messages::{Query, QueryResponse, Request, Response},
+    types::payment::StoreCost,
+};
+use tracing::{debug, warn};
+use xor_name::XorName;
+
+impl Client {
+    /// Get the costs the close group of `name` asks for the storage of the data at it, and
+    /// the addresses they are to be paid at.
+    ///
+    /// Peers not asking to be paid at an address of their own are left out, an empty list
+    /// meaning any payment is taken.
+    pub async fn get_store_costs(&self, name: XorName) -> Result<Vec<StoreCost>> {
+        let request = Request::Query(Query::GetStoreCost(name));
+        let responses = self.send_to_closest(name, request).await?;
+
+        let mut costs = vec![];
+        for resp in responses {
+            match resp {
+                Ok(Response::Query(QueryResponse::GetStoreCost(Some(cost)))) => {
+                    if !costs.contains(&cost) {
+                        costs.push(cost);
+                    }
+                }
+                Ok(Response::Query(QueryResponse::GetStoreCost(None))) => {}
+                Ok(other) => warn!("Unexpected response to the store cost of {name:?}: {other:?}"),
+                Err(err) => warn!("Could not get the store cost of {name:?}: {err}"),
+            }
+        }
+        debug!("Got {} store costs for {name:?}", costs.len());
+        Ok(costs)
+    }
+}
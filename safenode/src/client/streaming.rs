@@ -0,0 +1,214 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Error, Result, VersionedDataMap};
+use crate::storage::chunks::Chunk;
+use bytes::Bytes;
+use futures::io::{AsyncRead, AsyncReadExt};
+use self_encryption::{DataMap, MAX_CHUNK_SIZE, MIN_ENCRYPTABLE_BYTES};
+
+/// The number of bytes of a file self-encrypted at a time by the [`StreamingEncryptor`].
+///
+/// Files no larger than it are encrypted into the same chunks as by [`EncryptedFile::new`].
+///
+/// [`EncryptedFile::new`]: super::EncryptedFile::new
+pub const WINDOW_SIZE: usize = 4 * MAX_CHUNK_SIZE;
+
+/// Self-encrypts a file read from a source a window of [`WINDOW_SIZE`] bytes at a time,
+/// so that the chunks of each window can be paid for and uploaded before the next is read.
+///
+/// Only the window being encrypted and its chunks are held in memory, whatever the size of
+/// the file. Each window is self-encrypted independently of the others, the data map
+/// describing them all being finalized once the source is exhausted.
+pub struct StreamingEncryptor<R> {
+    source: R,
+    // The bytes read from the source and not yet encrypted.
+    buffer: Vec<u8>,
+    // Whether the end of the source was reached.
+    exhausted: bool,
+    bytes_read: u64,
+    // The data maps of the windows encrypted so far.
+    windows: Vec<DataMap>,
+    // The content of a file too small to be self-encrypted, held in its data map.
+    inline: Option<Bytes>,
+}
+
+impl<R: AsyncRead + Unpin> StreamingEncryptor<R> {
+    /// Encrypts the file read from the given source.
+    pub fn new(source: R) -> Self {
+        Self {
+            source,
+            buffer: vec![],
+            exhausted: false,
+            bytes_read: 0,
+            windows: vec![],
+            inline: None,
+        }
+    }
+
+    /// Reads and self-encrypts the next window of the file, returning its chunks, or `None`
+    /// once the whole file was encrypted.
+    pub async fn next_chunks(&mut self) -> Result<Option<Vec<Chunk>>> {
+        // A few bytes are read past the window, for the last one not to be too small to encrypt.
+        let target = WINDOW_SIZE + MIN_ENCRYPTABLE_BYTES;
+        while !self.exhausted && self.buffer.len() < target {
+            let filled = self.buffer.len();
+            self.buffer.resize(target, 0);
+            let read = self.source.read(&mut self.buffer[filled..]).await?;
+            self.buffer.truncate(filled + read);
+            self.bytes_read += read as u64;
+            self.exhausted = read == 0;
+        }
+
+        let window = if !self.exhausted {
+            let rest = self.buffer.split_off(WINDOW_SIZE);
+            std::mem::replace(&mut self.buffer, rest)
+        } else if !self.windows.is_empty() || self.inline.is_some() {
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+            std::mem::take(&mut self.buffer)
+        } else if self.buffer.len() < MIN_ENCRYPTABLE_BYTES {
+            // A file too small for self-encryption, held in the data map.
+            self.inline = Some(Bytes::from(std::mem::take(&mut self.buffer)));
+            return Ok(Some(vec![]));
+        } else {
+            std::mem::take(&mut self.buffer)
+        };
+
+        let (data_map, encrypted_chunks) = self_encryption::encrypt(Bytes::from(window))?;
+        self.windows.push(data_map);
+        Ok(Some(
+            encrypted_chunks
+                .into_iter()
+                .map(|encrypted| Chunk::new(encrypted.content))
+                .collect(),
+        ))
+    }
+
+    /// Returns the chunk holding the data map of the file, to be uploaded last, its address
+    /// being the one the file is read back from.
+    ///
+    /// Fails unless [`StreamingEncryptor::next_chunks`] returned `None`, all the windows of
+    /// the file having been encrypted.
+    pub fn finish(self) -> Result<Chunk> {
+        if !self.exhausted || !self.buffer.is_empty() {
+            return Err(Error::EncryptionUnfinished(self.bytes_read));
+        }
+        match self.inline {
+            Some(content) => VersionedDataMap::inline(content).to_chunk(),
+            None if self.windows.is_empty() => Err(Error::EncryptionUnfinished(self.bytes_read)),
+            None => VersionedDataMap::from_windows(&self.windows).to_chunk(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StreamingEncryptor<async_std::fs::File> {
+    /// Encrypts the file at the given path.
+    pub async fn from_path(path: &std::path::Path) -> Result<Self> {
+        Ok(Self::new(async_std::fs::File::open(path).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::EncryptedFile;
+    use eyre::Result;
+    use futures::io::Cursor;
+
+    fn random_bytes(size: usize) -> Vec<u8> {
+        let mut bytes = vec![0; size];
+        getrandom::getrandom(&mut bytes).expect("random bytes");
+        bytes
+    }
+
+    async fn encrypt_all(bytes: Vec<u8>) -> Result<(Vec<Chunk>, Chunk)> {
+        let mut encryptor = StreamingEncryptor::new(Cursor::new(bytes));
+        let mut chunks = vec![];
+        while let Some(window) = encryptor.next_chunks().await? {
+            chunks.extend(window);
+        }
+        Ok((chunks, encryptor.finish()?))
+    }
+
+    #[async_std::test]
+    async fn files_of_a_single_window_are_encrypted_as_at_once() -> Result<()> {
+        let bytes = random_bytes(WINDOW_SIZE);
+        let file = EncryptedFile::new(Bytes::from(bytes.clone()))?;
+
+        let (mut chunks, data_map_chunk) = encrypt_all(bytes).await?;
+        chunks.push(data_map_chunk);
+        let names = |chunks: &[Chunk]| {
+            let mut names: Vec<_> = chunks.iter().map(|chunk| *chunk.name()).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&chunks), names(&file.chunks));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn windows_are_decrypted_back_into_the_file() -> Result<()> {
+        // The last window is merely a byte, too small to be encrypted on its own.
+        let bytes = random_bytes(2 * WINDOW_SIZE + 1);
+        let (chunks, data_map_chunk) = encrypt_all(bytes.clone()).await?;
+
+        let data_map = VersionedDataMap::from_chunk(&data_map_chunk)?;
+        assert_eq!(data_map.file_size, bytes.len() as u64);
+        assert_eq!(data_map.readable_from, 2);
+
+        let mut decrypted = vec![];
+        for window in data_map.to_data_maps() {
+            let encrypted: Vec<_> = window
+                .infos()
+                .into_iter()
+                .map(|info| {
+                    let chunk = chunks
+                        .iter()
+                        .find(|chunk| *chunk.name() == info.dst_hash)
+                        .expect("chunk of the window");
+                    self_encryption::EncryptedChunk {
+                        index: info.index,
+                        content: chunk.value().clone(),
+                    }
+                })
+                .collect();
+            decrypted.extend(self_encryption::decrypt_full_set(&window, &encrypted)?);
+        }
+        assert_eq!(decrypted, bytes);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn empty_files_are_held_in_the_data_map() -> Result<()> {
+        let (chunks, data_map_chunk) = encrypt_all(vec![]).await?;
+        assert!(chunks.is_empty());
+        assert_eq!(
+            *data_map_chunk.address(),
+            EncryptedFile::new(Bytes::new())?.address
+        );
+
+        let data_map = VersionedDataMap::from_chunk(&data_map_chunk)?;
+        assert_eq!(data_map.file_size, 0);
+        assert_eq!(data_map.content, Some(Bytes::new()));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn the_data_map_is_only_finalized_once_the_file_was_read() -> Result<()> {
+        let mut encryptor = StreamingEncryptor::new(Cursor::new(random_bytes(2 * WINDOW_SIZE)));
+        let _ = encryptor.next_chunks().await?;
+        assert!(matches!(
+            encryptor.finish(),
+            Err(Error::EncryptionUnfinished(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, Error, Result};
+use crate::protocol::types::{
+    payment::RevealedOutput,
+    spend::{genesis_main_key, GENESIS_DERIVATION_INDEX},
+};
+use sn_dbc::{
+    rng, Dbc, DbcId, DbcIdSource, DbcTransaction, DerivedKey, Hash, InputHistory, MainKey,
+    PublicAddress, RevealedAmount, RevealedInput, SignedSpend, Token, TransactionBuilder,
+};
+use tracing::debug;
+
+/// A reissue of Dbcs built and signed offline, its spends yet to be recorded in the spentbook
+/// for the Dbcs it creates to be valid.
+#[derive(Clone, Debug)]
+pub struct OfflineTransfer {
+    /// The ids of the Dbcs spent.
+    pub inputs: Vec<DbcId>,
+    /// The Dbcs created for the recipients, in the order the recipients were given.
+    pub created: Vec<Dbc>,
+    /// The outputs for the recipients with their amount revealed, in the same order, for
+    /// the payments made by the transfer to be checked by nodes.
+    pub revealed_outputs: Vec<RevealedOutput>,
+    /// The Dbc holding the change, if the inputs exceed the amount sent.
+    pub change: Option<Dbc>,
+    /// The spends of the inputs, to be submitted to the spentbook, each along with the
+    /// transaction its Dbc was created by.
+    pub spends: Vec<(SignedSpend, DbcTransaction)>,
+}
+
+/// Builds a transfer of the amounts to their recipients out of the given Dbcs, along with
+/// the keys they are spent with, the change going to `change_to`.
+///
+/// Dbcs are taken in order until their total covers the amounts. The spends carry the given
+/// `reason`, e.g. the one a proof of payment commits to. Nothing is sent to the network.
+pub fn create_transfer(
+    available: Vec<(Dbc, DerivedKey)>,
+    recipients: Vec<(Token, PublicAddress)>,
+    change_to: PublicAddress,
+    reason: Hash,
+) -> Result<OfflineTransfer> {
+    let mut rng = rng::thread_rng();
+    let required: u64 = recipients.iter().map(|(amount, _)| amount.as_nano()).sum();
+
+    let mut inputs = vec![];
+    let mut total = 0;
+    for (dbc, derived_key) in available {
+        if total >= required {
+            break;
+        }
+        total += dbc.revealed_amount(&derived_key)?.value();
+        inputs.push((dbc, derived_key));
+    }
+    if total < required {
+        return Err(Error::NotEnoughBalance {
+            available: Token::from_nano(total),
+            required: Token::from_nano(required),
+        });
+    }
+    let input_ids = inputs.iter().map(|(dbc, _)| dbc.id()).collect();
+
+    let mut tx_builder = TransactionBuilder::default().add_input_dbcs(&inputs)?;
+    let mut created_srcs = vec![];
+    for (amount, recipient) in recipients {
+        let dbc_id_src = recipient.random_dbc_id_src(&mut rng);
+        created_srcs.push(dbc_id_src);
+        tx_builder = tx_builder.add_output(amount, dbc_id_src);
+    }
+    let mut change_id = None;
+    if total > required {
+        let dbc_id_src = change_to.random_dbc_id_src(&mut rng);
+        change_id = Some(dbc_id_src.dbc_id());
+        tx_builder = tx_builder.add_output(Token::from_nano(total - required), dbc_id_src);
+    }
+
+    let dbc_builder = tx_builder.build(reason, &mut rng)?;
+    let spends = dbc_builder
+        .signed_spends()
+        .into_iter()
+        .map(|signed_spend| {
+            let (dbc, _) = inputs
+                .iter()
+                .find(|(dbc, _)| &dbc.id() == signed_spend.dbc_id())
+                .ok_or_else(|| Error::TransferFailed("a spend of no input".to_string()))?;
+            Ok((signed_spend.clone(), dbc.src_tx.clone()))
+        })
+        .collect::<Result<_>>()?;
+    let mut outputs = dbc_builder.build_without_verifying()?;
+
+    let mut take = |id: DbcId| {
+        let index = outputs.iter().position(|(dbc, _)| dbc.id() == id)?;
+        Some(outputs.swap_remove(index))
+    };
+    let mut created = vec![];
+    let mut revealed_outputs = vec![];
+    for dbc_id_src in created_srcs {
+        let (dbc, revealed_amount) = take(dbc_id_src.dbc_id()).ok_or_else(|| {
+            Error::TransferFailed("no Dbc was created for a recipient".to_string())
+        })?;
+        created.push(dbc);
+        revealed_outputs.push(RevealedOutput {
+            dbc_id_src,
+            revealed_amount,
+        });
+    }
+    let change = change_id.and_then(take).map(|(dbc, _)| dbc);
+
+    Ok(OfflineTransfer {
+        inputs: input_ids,
+        created,
+        revealed_outputs,
+        change,
+        spends,
+    })
+}
+
+/// Creates the genesis Dbc, of the given amount out of nothing, owned by the well-known
+/// [`genesis_main_key`], from which the tokens of a testnet are then transferred.
+///
+/// Nodes only take the first spend of the genesis Dbc recorded, so it is minted once per
+/// network, by its faucet.
+pub fn create_genesis_dbc(amount: Token) -> Result<Dbc> {
+    let mut rng = rng::thread_rng();
+    let main_key = genesis_main_key();
+    let dbc_id_src = DbcIdSource {
+        public_address: main_key.public_address(),
+        derivation_index: GENESIS_DERIVATION_INDEX,
+    };
+    let derived_key = main_key.derive_key(&dbc_id_src.derivation_index);
+    let revealed_amount = RevealedAmount::from_amount(amount.as_nano(), &mut rng);
+    // The genesis transaction spends an input of the same key, created by no transaction.
+    let genesis_input = InputHistory {
+        input: RevealedInput::new(derived_key, revealed_amount),
+        input_src_tx: DbcTransaction {
+            inputs: vec![],
+            outputs: vec![],
+        },
+    };
+    let dbc_builder = TransactionBuilder::default()
+        .add_input(genesis_input)
+        .add_output(amount, dbc_id_src)
+        .build(Hash::hash(b"GENESIS"), &mut rng)?;
+    dbc_builder
+        .build_without_verifying()?
+        .into_iter()
+        .next()
+        .map(|(dbc, _)| dbc)
+        .ok_or_else(|| Error::TransferFailed("no genesis Dbc was created".to_string()))
+}
+
+impl Client {
+    /// Submits the spends of the transfer to the spentbook, confirming each of them is
+    /// recorded before returning.
+    pub async fn submit_transfer(&self, transfer: &OfflineTransfer) -> Result<()> {
+        for (signed_spend, parent_tx) in &transfer.spends {
+            self.spend_dbc(signed_spend.clone(), parent_tx.clone())
+                .await?;
+        }
+        for dbc_id in &transfer.inputs {
+            let _ = self.get_spend(dbc_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Verifies a Dbc received from another party: it must be owned by the main key, and
+    /// the spends of its parents it carries must be the ones recorded in the spentbook.
+    pub async fn verify_received_dbc(&self, dbc: &Dbc, main_key: &MainKey) -> Result<()> {
+        let invalid = |details: String| Error::InvalidDbc(dbc.id(), details);
+        dbc.verify(main_key)
+            .map_err(|err| invalid(err.to_string()))?;
+
+        if dbc.signed_spends.is_empty() {
+            return Err(invalid("it carries no spends of its parents".to_string()));
+        }
+        let src_tx_hash = dbc.src_tx.hash();
+        for signed_spend in &dbc.signed_spends {
+            if signed_spend.spent_tx_hash() != src_tx_hash {
+                return Err(invalid(format!(
+                    "the spend of {:?} is not for the transaction it was created by",
+                    signed_spend.dbc_id()
+                )));
+            }
+            let recorded = self.get_spend(signed_spend.dbc_id()).await?;
+            if &recorded != signed_spend {
+                return Err(invalid(format!(
+                    "the spend of {:?} differs from the one in the spentbook",
+                    signed_spend.dbc_id()
+                )));
+            }
+        }
+        debug!("Verified the spends of the parents of Dbc {:?}", dbc.id());
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Client, Error, Result};
+use crate::{
+    protocol::types::payment::PaymentProof,
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use async_std::future::timeout;
+use futures::{
+    stream::{self, FuturesUnordered},
+    StreamExt,
+};
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// The number of chunks uploaded concurrently at the start of a batch upload.
+const INITIAL_CONCURRENCY: usize = 4;
+/// The max number of chunks uploaded concurrently.
+const MAX_CONCURRENCY: usize = 32;
+/// How long to wait for a chunk to be stored, before counting its upload as failed.
+const CHUNK_UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The outcome of the upload of each chunk of a batch.
+#[derive(Debug, Default)]
+pub struct UploadReport {
+    /// The chunks stored with their close group.
+    pub stored: Vec<ChunkAddress>,
+    /// The chunks not uploaded, as their close group already held them.
+    pub skipped: Vec<ChunkAddress>,
+    /// The chunks that failed to be stored, with the reason why.
+    pub failed: Vec<(ChunkAddress, Error)>,
+}
+
+// The outcome of the upload of a single chunk.
+enum ChunkUpload {
+    Stored,
+    AlreadyStored,
+}
+
+impl Client {
+    /// Of the given chunks, returns the ones already held by the network, which need not
+    /// be paid for nor uploaded.
+    ///
+    /// Chunks whose existence could not be checked are assumed not to be held.
+    pub async fn stored_chunks(
+        &self,
+        addresses: impl IntoIterator<Item = ChunkAddress>,
+    ) -> Vec<ChunkAddress> {
+        stream::iter(addresses)
+            .map(|address| async move {
+                match self.chunk_exists(address).await {
+                    Ok(exists) => exists.then_some(address),
+                    Err(err) => {
+                        debug!("Could not check whether {address:?} is stored: {err}");
+                        None
+                    }
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENCY)
+            .filter_map(|address| async move { address })
+            .collect()
+            .await
+    }
+
+    /// Upload the chunks, along with the proof of payment for their storage, several at a time.
+    ///
+    /// Chunks already held by their close group are skipped rather than uploaded again.
+    /// Chunks are pulled from the iterator as uploads complete. The number of concurrent uploads
+    /// grows by one with every chunk stored, and is halved with every failure or timeout, so
+    /// as to back off when the network struggles to keep up.
+    pub async fn upload_chunks(
+        &self,
+        chunks: impl IntoIterator<Item = (Chunk, PaymentProof)>,
+    ) -> UploadReport {
+        self.upload_chunks_with(chunks, MAX_CONCURRENCY, |_| {})
+            .await
+    }
+
+    /// Upload the chunks as with [`Client::upload_chunks`], with at most `max_concurrency`
+    /// of them at a time, calling `on_done` with the address of each chunk once its upload
+    /// has completed, whether successfully or not.
+    pub async fn upload_chunks_with(
+        &self,
+        chunks: impl IntoIterator<Item = (Chunk, PaymentProof)>,
+        max_concurrency: usize,
+        mut on_done: impl FnMut(&ChunkAddress),
+    ) -> UploadReport {
+        let max_concurrency = max_concurrency.max(1);
+        let mut chunks = chunks.into_iter();
+        let mut concurrency = INITIAL_CONCURRENCY.min(max_concurrency);
+        let mut uploads = FuturesUnordered::new();
+        let mut report = UploadReport::default();
+
+        loop {
+            while uploads.len() < concurrency {
+                match chunks.next() {
+                    Some((chunk, payment)) => uploads.push(self.upload_chunk(chunk, payment)),
+                    None => break,
+                }
+            }
+
+            let (address, result) = match uploads.next().await {
+                Some(upload) => upload,
+                None => break,
+            };
+            on_done(&address);
+            match result {
+                Ok(ChunkUpload::Stored) => {
+                    report.stored.push(address);
+                    concurrency = (concurrency + 1).min(max_concurrency);
+                }
+                Ok(ChunkUpload::AlreadyStored) => report.skipped.push(address),
+                Err(err) => {
+                    concurrency = (concurrency / 2).max(1);
+                    debug!("Failed to store {address:?}, backing off to {concurrency} concurrent uploads: {err}");
+                    report.failed.push((address, err));
+                }
+            }
+        }
+
+        info!(
+            "Uploaded {} chunks, {} already stored, {} failed",
+            report.stored.len(),
+            report.skipped.len(),
+            report.failed.len()
+        );
+        report
+    }
+
+    async fn upload_chunk(
+        &self,
+        chunk: Chunk,
+        payment: PaymentProof,
+    ) -> (ChunkAddress, Result<ChunkUpload>) {
+        let address = *chunk.address();
+        let upload = async {
+            match self.chunk_exists(address).await {
+                Ok(true) => {
+                    debug!("Chunk {address:?} already stored, skipping its upload");
+                    return Ok(ChunkUpload::AlreadyStored);
+                }
+                Ok(false) => {}
+                // The chunk is uploaded, the store cmd failing in turn if the network is unreachable.
+                Err(err) => debug!("Could not check whether {address:?} is stored: {err}"),
+            }
+            self.store_chunk(chunk, payment)
+                .await
+                .map(|()| ChunkUpload::Stored)
+        };
+        let result = match timeout(CHUNK_UPLOAD_TIMEOUT, upload).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::StoreTimeout(*address.name())),
+        };
+        (address, result)
+    }
+}
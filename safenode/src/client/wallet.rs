@@ -0,0 +1,307 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    create_genesis_dbc, create_transfer, Client, Error, OfflineTransfer, RegisterKey, Result,
+};
+use crate::{
+    fs::write_secret,
+    protocol::types::{
+        payment::{PaymentProof, RoyaltyPolicy, StoreCost},
+        spend::genesis_main_key,
+    },
+};
+use async_std::fs::{create_dir_all, read, rename, File};
+use futures::AsyncWriteExt;
+use sn_dbc::{blsttc::SecretKey, Dbc, DbcId, Hash, MainKey, PublicAddress, Token};
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info};
+use xor_name::XorName;
+
+const MAIN_KEY_FILENAME: &str = "main_key";
+const DBCS_FILENAME: &str = "dbcs";
+
+/// A wallet holding the Dbcs owned by its main key, stored on the local disk.
+pub struct Wallet {
+    client: Client,
+    main_key: MainKey,
+    wallet_dir: PathBuf,
+    dbcs: BTreeMap<DbcId, Dbc>,
+}
+
+impl Wallet {
+    /// Loads the wallet stored at the given dir, creating a new one with a random main key
+    /// if there is none.
+    pub async fn load_from(client: Client, wallet_dir: &Path) -> Result<Self> {
+        create_dir_all(wallet_dir).await?;
+
+        let main_key_path = wallet_dir.join(MAIN_KEY_FILENAME);
+        let main_key = match read(&main_key_path).await {
+            Ok(bytes) => main_key_from_bytes(&bytes)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                info!("Creating a new wallet at {wallet_dir:?}");
+                let main_key = MainKey::random();
+                write_secret(&main_key_path, &main_key.to_bytes())?;
+                main_key
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let dbcs = match read(wallet_dir.join(DBCS_FILENAME)).await {
+            Ok(bytes) => rmp_serde::from_slice(&bytes)
+                .map_err(|err| Error::Serialisation(err.to_string()))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            client,
+            main_key,
+            wallet_dir: wallet_dir.to_path_buf(),
+            dbcs,
+        })
+    }
+
+    /// Returns the address Dbcs are to be sent to for this wallet to receive them.
+    pub fn address(&self) -> PublicAddress {
+        self.main_key.public_address()
+    }
+
+    // Returns the client the wallet pays through.
+    #[cfg(feature = "s3")]
+    pub(super) fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Returns the key of the first generation of the registers encrypted by this wallet's owner.
+    pub fn register_key(&self) -> RegisterKey {
+        RegisterKey::from_main_key(&self.main_key)
+    }
+
+    /// Returns the sum of the amounts of the Dbcs held.
+    pub fn balance(&self) -> Result<Token> {
+        let nanos = self
+            .dbcs
+            .values()
+            .map(|dbc| self.amount_of(dbc).map(|token| token.as_nano()))
+            .sum::<Result<u64>>()?;
+        Ok(Token::from_nano(nanos))
+    }
+
+    /// Adds the Dbcs sent to this wallet to the ones it holds, ignoring those sent
+    /// to other addresses or already spent.
+    ///
+    /// Fails without crediting any Dbc if one of them does not verify against the spentbook,
+    /// see [`Client::verify_received_dbc`].
+    pub async fn receive(&mut self, dbcs: Vec<Dbc>) -> Result<()> {
+        let mut received = vec![];
+        for dbc in dbcs {
+            if dbc.public_address() != &self.address() {
+                debug!("Ignoring Dbc {:?} sent to another address", dbc.id());
+                continue;
+            }
+            self.client
+                .verify_received_dbc(&dbc, &self.main_key)
+                .await?;
+            if self.client.get_spend(&dbc.id()).await.is_ok() {
+                debug!("Ignoring Dbc {:?} already spent", dbc.id());
+                continue;
+            }
+            received.push(dbc);
+        }
+        for dbc in received {
+            let _ = self.dbcs.insert(dbc.id(), dbc);
+        }
+        self.store().await
+    }
+
+    /// Credits the wallet with the tokens of a genesis Dbc of the given amount, unless it
+    /// already holds Dbcs, see [`create_genesis_dbc`]. Meant for the faucet of a testnet alone.
+    ///
+    /// The genesis Dbc is spent to this wallet, which fails if it was already spent.
+    pub async fn mint_genesis(&mut self, amount: Token) -> Result<()> {
+        if !self.dbcs.is_empty() {
+            debug!("Not minting a genesis Dbc into a wallet already holding Dbcs");
+            return Ok(());
+        }
+        let genesis = create_genesis_dbc(amount)?;
+        let genesis_id = genesis.id();
+        let derived_key = genesis.derived_key(&genesis_main_key())?;
+        let transfer = create_transfer(
+            vec![(genesis, derived_key)],
+            vec![(amount, self.address())],
+            self.address(),
+            Hash::default(),
+        )?;
+        self.client.submit_transfer(&transfer).await?;
+        info!("Minted genesis Dbc {genesis_id:?} of {amount}");
+        for dbc in transfer.created {
+            let _ = self.dbcs.insert(dbc.id(), dbc);
+        }
+        self.store().await
+    }
+
+    /// Sends the amount to the recipient, returning the Dbc to be handed over to them.
+    ///
+    /// The spends of the Dbcs used are recorded in the spentbook and confirmed before returning,
+    /// any change being kept in the wallet.
+    pub async fn send(&mut self, amount: Token, recipient: PublicAddress) -> Result<Dbc> {
+        let transfer = self
+            .transfer(vec![(amount, recipient)], Hash::default())
+            .await?;
+        transfer.created.into_iter().next().ok_or_else(|| {
+            Error::TransferFailed("no Dbc was created for the recipient".to_string())
+        })
+    }
+
+    /// Pays the amount for the storage of the data at `name`, returning the proof of payment
+    /// to store the data with.
+    ///
+    /// The spends of the payment commit to `name`, see [`PaymentProof::reason_for`]. The amount
+    /// is sent back to this wallet, as it is the recorded spends that make the proof.
+    pub async fn pay_for_storage(&mut self, name: &XorName, amount: Token) -> Result<PaymentProof> {
+        self.pay_for_storage_with_royalties(name, amount, None)
+            .await
+    }
+
+    /// Pays for the storage of the data at `name` as with [`Wallet::pay_for_storage`], along
+    /// with the royalties due for the amount as per the policy, if any.
+    pub async fn pay_for_storage_with_royalties(
+        &mut self,
+        name: &XorName,
+        amount: Token,
+        royalties: Option<&RoyaltyPolicy>,
+    ) -> Result<PaymentProof> {
+        let payments = vec![(amount, self.address())];
+        self.pay_recipients(name, payments, royalties).await
+    }
+
+    /// Pays for the storage of the data at `name` the costs asked by the nodes storing it, at
+    /// their addresses, along with the royalties due for the total as per the policy, if any.
+    ///
+    /// When no node asks for a cost, `fallback` is paid back to this wallet instead, as with
+    /// [`Wallet::pay_for_storage`].
+    pub async fn pay_store_costs(
+        &mut self,
+        name: &XorName,
+        costs: &[StoreCost],
+        fallback: Token,
+        royalties: Option<&RoyaltyPolicy>,
+    ) -> Result<PaymentProof> {
+        let payments = if costs.is_empty() {
+            vec![(fallback, self.address())]
+        } else {
+            costs
+                .iter()
+                .map(|cost| (Token::from_nano(cost.cost_nanos), cost.address))
+                .collect()
+        };
+        self.pay_recipients(name, payments, royalties).await
+    }
+
+    // Pays the storage of the data at `name` to the recipients, with the royalties due for
+    // their total. The Dbcs created for this wallet are kept in it.
+    async fn pay_recipients(
+        &mut self,
+        name: &XorName,
+        payments: Vec<(Token, PublicAddress)>,
+        royalties: Option<&RoyaltyPolicy>,
+    ) -> Result<PaymentProof> {
+        let payments_count = payments.len();
+        let total = payments
+            .iter()
+            .map(|(amount, _)| amount.as_nano())
+            .fold(0, u64::saturating_add);
+        let mut recipients = payments;
+        if let Some(policy) = royalties {
+            let due = Token::from_nano(policy.due_for(total));
+            recipients.push((due, policy.address));
+        }
+
+        let address = self.address();
+        let owned: Vec<_> = recipients
+            .iter()
+            .map(|(_, recipient)| recipient == &address)
+            .collect();
+        let reason = PaymentProof::reason_for(name);
+        let transfer = self.transfer(recipients, reason).await?;
+        for (dbc, owned) in transfer.created.into_iter().zip(owned) {
+            if owned {
+                let _ = self.dbcs.insert(dbc.id(), dbc);
+            }
+        }
+        self.store().await?;
+
+        let mut revealed = transfer.revealed_outputs;
+        let royalties = revealed.split_off(payments_count);
+        let spends = transfer
+            .spends
+            .into_iter()
+            .map(|(signed_spend, _)| signed_spend)
+            .collect();
+        Ok(PaymentProof::new(spends).with_outputs(revealed, royalties))
+    }
+
+    // Spends Dbcs for the amounts to be sent to the recipients, the spends carrying the given
+    // reason. Returns the transfer once its spends are recorded in the spentbook.
+    async fn transfer(
+        &mut self,
+        recipients: Vec<(Token, PublicAddress)>,
+        reason: Hash,
+    ) -> Result<OfflineTransfer> {
+        let available = self
+            .dbcs
+            .values()
+            .map(|dbc| Ok((dbc.clone(), dbc.derived_key(&self.main_key)?)))
+            .collect::<Result<_>>()?;
+        let mut transfer = create_transfer(available, recipients, self.address(), reason)?;
+
+        // The spends are confirmed with the spentbook before considering the Dbcs spent.
+        self.client.submit_transfer(&transfer).await?;
+        for dbc_id in &transfer.inputs {
+            let _ = self.dbcs.remove(dbc_id);
+        }
+        if let Some(change) = transfer.change.take() {
+            let _ = self.dbcs.insert(change.id(), change);
+        }
+        self.store().await?;
+        Ok(transfer)
+    }
+
+    fn amount_of(&self, dbc: &Dbc) -> Result<Token> {
+        let derived_key = dbc.derived_key(&self.main_key)?;
+        Ok(Token::from_nano(dbc.revealed_amount(&derived_key)?.value()))
+    }
+
+    // Writes to a temporary file first, for an interrupted store not to leave the Dbcs of
+    // the wallet half written, and lost with the spends already recorded.
+    async fn store(&self) -> Result<()> {
+        let bytes =
+            rmp_serde::to_vec(&self.dbcs).map_err(|err| Error::Serialisation(err.to_string()))?;
+        let path = self.wallet_dir.join(DBCS_FILENAME);
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path).await?;
+        file.write_all(&bytes).await?;
+        file.sync_data().await?;
+        rename(&tmp_path, path).await?;
+        Ok(())
+    }
+}
+
+// The main key is stored as the bytes of its secret key.
+fn main_key_from_bytes(bytes: &[u8]) -> Result<MainKey> {
+    let bytes = bytes
+        .try_into()
+        .map_err(|_| Error::Serialisation(format!("main key of {} bytes", bytes.len())))?;
+    let secret_key = SecretKey::from_bytes(bytes).map_err(sn_dbc::Error::from)?;
+    Ok(MainKey::new(secret_key))
+}
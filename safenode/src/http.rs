@@ -0,0 +1,219 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The minimal HTTP/1.1 shared by the health endpoints and the servers of the binaries, which
+//! answer a single request per connection, then close it.
+//!
+//! The request line and headers are read within bounds, for a client never ending them not to
+//! grow them without limit.
+
+use futures::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::io;
+
+/// The max length in bytes of the request line, and of each header line.
+pub const MAX_LINE_LEN: u64 = 8 * 1024;
+/// The max number of headers read.
+pub const MAX_HEADERS: usize = 100;
+
+/// The request line and headers of a request.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestHead {
+    /// The method, e.g. `GET`.
+    pub method: String,
+    /// The target, i.e. the path along with any query string.
+    pub target: String,
+    // The headers, with their names in lowercase.
+    headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    /// The path of the target, without its query string.
+    pub fn path(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map_or(self.target.as_str(), |(path, _)| path)
+    }
+
+    /// The query string of the target, empty if there is none.
+    pub fn query(&self) -> &str {
+        self.target.split_once('?').map_or("", |(_, query)| query)
+    }
+
+    /// The raw value of the first query parameter of the name, if any.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query().split('&').find_map(|param| {
+            param
+                .split_once('=')
+                .filter(|(param_name, _)| *param_name == name)
+                .map(|(_, value)| value)
+        })
+    }
+
+    /// The value of the first header of the name, which is matched regardless of case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers
+            .iter()
+            .find(|(header_name, _)| *header_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The length of the body as given by the `Content-Length` header, zero without one.
+    pub fn content_length(&self) -> io::Result<usize> {
+        self.header("content-length").map_or(Ok(0), |value| {
+            value
+                .parse()
+                .map_err(|_| invalid_data(format!("invalid Content-Length {value:?}")))
+        })
+    }
+}
+
+/// Reads the request line and headers of a request, up to the empty line ending them.
+pub async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<RequestHead> {
+    let request_line = read_line(reader).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = vec![];
+    loop {
+        let line = read_line(reader).await?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(invalid_data(format!("more than {MAX_HEADERS} headers")));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    Ok(RequestHead {
+        method,
+        target,
+        headers,
+    })
+}
+
+/// Reads a body of the given length, which is to be checked against the max the server takes
+/// beforehand.
+pub async fn read_body<R: AsyncRead + Unpin>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+/// Writes the status line and headers of a response, for its body to be written after.
+pub async fn write_head<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: &str,
+    headers: &[(&str, &str)],
+) -> io::Result<()> {
+    let mut head = format!("HTTP/1.1 {status}\r\n");
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    writer.write_all(head.as_bytes()).await
+}
+
+/// Writes a whole response, telling the client the connection is closed after it.
+pub async fn respond<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> io::Result<()> {
+    let content_length = body.len().to_string();
+    let mut all_headers = headers.to_vec();
+    all_headers.push(("Content-Length", content_length.as_str()));
+    all_headers.push(("Connection", "close"));
+    write_head(writer, status, &all_headers).await?;
+    writer.write_all(body).await?;
+    writer.flush().await
+}
+
+// Reads a line, without its line ending, refusing one longer than `MAX_LINE_LEN`.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    let _ = reader.take(MAX_LINE_LEN).read_line(&mut line).await?;
+    if !line.ends_with('\n') && line.len() as u64 >= MAX_LINE_LEN {
+        return Err(invalid_data(format!(
+            "line longer than {MAX_LINE_LEN} bytes"
+        )));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::Cursor;
+
+    #[async_std::test]
+    async fn heads_are_read_up_to_the_body() -> io::Result<()> {
+        let mut reader = Cursor::new(
+            b"POST /path?token=abc&x=1 HTTP/1.1\r\nContent-Length: 4\r\nX-Key:  value \r\n\r\nbody"
+                .to_vec(),
+        );
+        let head = read_head(&mut reader).await?;
+        assert_eq!(head.method, "POST");
+        assert_eq!(head.path(), "/path");
+        assert_eq!(head.query_param("token"), Some("abc"));
+        assert_eq!(head.query_param("y"), None);
+        assert_eq!(head.header("x-key"), Some("value"));
+        assert_eq!(head.header("Content-Length"), Some("4"));
+        let len = head.content_length()?;
+        assert_eq!(read_body(&mut reader, len).await?, b"body");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn lines_longer_than_the_max_are_refused() {
+        let mut request = b"GET /".to_vec();
+        request.extend(vec![b'a'; MAX_LINE_LEN as usize]);
+        let mut reader = Cursor::new(request);
+        assert!(read_head(&mut reader).await.is_err());
+
+        let mut request = b"GET / HTTP/1.1\r\nX-Key: ".to_vec();
+        request.extend(vec![b'a'; MAX_LINE_LEN as usize]);
+        let mut reader = Cursor::new(request);
+        assert!(read_head(&mut reader).await.is_err());
+    }
+
+    #[async_std::test]
+    async fn heads_with_too_many_headers_are_refused() {
+        let mut request = b"GET / HTTP/1.1\r\n".to_vec();
+        for i in 0..=MAX_HEADERS {
+            request.extend(format!("X-Header-{i}: value\r\n").as_bytes());
+        }
+        request.extend(b"\r\n");
+        assert!(read_head(&mut Cursor::new(request)).await.is_err());
+    }
+
+    #[async_std::test]
+    async fn responses_carry_their_length_and_close_the_connection() -> io::Result<()> {
+        let mut written = vec![];
+        respond(
+            &mut written,
+            "200 OK",
+            &[("Content-Type", "text/plain")],
+            b"ok",
+        )
+        .await?;
+        assert_eq!(
+            written,
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok"
+        );
+        Ok(())
+    }
+}
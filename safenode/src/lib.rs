@@ -38,10 +38,29 @@
     clippy::unwrap_used,
     clippy::unused_async
 )]
+// The messages are passed around by value, with the responses not matching a request handed
+// back in errors.
+#![allow(clippy::large_enum_variant, clippy::result_large_err)]
 
+/// Client
+pub mod client;
+// Helpers for the files kept by the client and node
+mod fs;
+/// HTTP
+#[cfg(feature = "node")]
+pub mod http;
 /// Log
+#[cfg(feature = "node")]
 pub mod log;
 /// Network
 pub mod network;
+/// Node
+#[cfg(feature = "node")]
+pub mod node;
+/// Protocol
+pub mod protocol;
 /// Storage
 pub mod storage;
+/// Test utils
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
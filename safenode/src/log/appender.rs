@@ -6,6 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::LogRotation;
 use file_rotate::{
     compression::Compression,
     suffix::{AppendTimestamp, FileLimit},
@@ -16,34 +17,30 @@ use std::{
     io,
     io::Write,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
 
 pub(super) fn file_rotater(
     dir: &PathBuf,
-    max_size: usize,
-    max_lines: usize,
-    files_to_retain: usize,
-    uncompressed_files: usize,
+    rotation: &LogRotation,
+    reopen_requested: Arc<AtomicBool>,
 ) -> (NonBlocking, WorkerGuard) {
-    let mut content_limit = ContentLimit::BytesSurpassed(max_size);
-    if max_lines > 0 {
-        content_limit = ContentLimit::Lines(max_lines);
-    }
-
     // FileRotate crate changed `0 means for all` to `0 means only original`
-    // Here set the retained value to be same as uncompressed in case of 0.
-    let logs_retained = if files_to_retain == 0 {
-        uncompressed_files
-    } else {
-        files_to_retain
-    };
+    // Here keep at least one rotated file, in case of 0.
+    let logs_retained = rotation.retained_files.max(1);
     let file_appender = FileRotateAppender::make_rotate_appender(
         dir,
         "safenode.log",
         AppendTimestamp::default(FileLimit::MaxFiles(logs_retained)),
-        content_limit,
-        Compression::OnRotate(uncompressed_files),
+        ContentLimit::BytesSurpassed(rotation.max_file_size),
+        Compression::OnRotate(logs_retained),
+        rotation.max_file_age(),
+        reopen_requested,
     );
 
     // configure how tracing non-blocking works: https://tracing.rs/tracing_appender/non_blocking/struct.nonblockingbuilder#method.default
@@ -61,12 +58,17 @@ pub(super) fn file_rotater(
 ///  - most recent logfile name re-used to support following (e.g. 'tail -f=logfile')
 ///  - numbered rotation (logfile.1, logfile.2 etc)
 ///  - limit logfile by size, lines or time
+///  - limit logfile by age
 ///  - limit maximum number of logfiles
 ///  - optional compression of rotated logfiles
+///  - start a new logfile on request
 //
-// The above functionality is provided using crate file_rotation
+// Most of the above functionality is provided using crate file_rotation
 pub(super) struct FileRotateAppender {
     writer: FileRotate<AppendTimestamp>,
+    opened_at: Instant,
+    max_age: Option<Duration>,
+    reopen_requested: Arc<AtomicBool>,
 }
 
 impl FileRotateAppender {
@@ -77,6 +79,8 @@ impl FileRotateAppender {
         file_limit: AppendTimestamp,
         max_log_size: ContentLimit,
         compression: Compression,
+        max_age: Option<Duration>,
+        reopen_requested: Arc<AtomicBool>,
     ) -> Self {
         let log_directory = directory.as_ref();
         let log_filename_prefix = file_name_prefix.as_ref();
@@ -90,12 +94,31 @@ impl FileRotateAppender {
             None,
         );
 
-        Self { writer }
+        Self {
+            writer,
+            opened_at: Instant::now(),
+            max_age,
+            reopen_requested,
+        }
+    }
+
+    // Whether the current logfile is due to be replaced by a new one, being either too old
+    // or requested to be reopened.
+    fn is_rotation_due(&self) -> bool {
+        let too_old = self
+            .max_age
+            .is_some_and(|max_age| self.opened_at.elapsed() >= max_age);
+        self.reopen_requested.swap(false, Ordering::Relaxed) || too_old
     }
 }
 
 impl Write for FileRotateAppender {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.is_rotation_due() {
+            self.writer.flush()?;
+            self.writer.rotate()?;
+            self.opened_at = Instant::now();
+        }
         self.writer.write(buf)
     }
 
@@ -8,7 +8,16 @@
 
 mod appender;
 
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_core::{Event, Subscriber};
 use tracing_subscriber::{
@@ -25,6 +34,74 @@ use tracing_subscriber::{
     EnvFilter, Layer, Registry,
 };
 
+/// The format log lines are written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines, with the spans of the event.
+    #[default]
+    Default,
+    /// JSON lines, with the fields of the event and of its spans, e.g. `peer_id`, `msg_id`
+    /// and `record_key`, for ingestion by log aggregators.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "Unknown log format {other:?}, expected `default` or `json`"
+            )),
+        }
+    }
+}
+
+/// When log files are replaced by new ones, and how many of the old ones are kept.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogRotation {
+    /// The size in bytes past which a log file is replaced.
+    pub max_file_size: usize,
+    /// The age in seconds past which a log file is replaced, regardless of its size.
+    pub max_file_age_secs: Option<u64>,
+    /// The number of replaced log files kept, the oldest ones being removed.
+    pub retained_files: usize,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self {
+            max_file_size: 10 * 1024 * 1024,
+            max_file_age_secs: Some(24 * 60 * 60),
+            retained_files: 100,
+        }
+    }
+}
+
+impl LogRotation {
+    /// The age past which a log file is replaced, if any.
+    pub fn max_file_age(&self) -> Option<Duration> {
+        self.max_file_age_secs.map(Duration::from_secs)
+    }
+}
+
+/// Handle to have the log files flushed and reopened, e.g. after they were moved away by
+/// an external tool. The current file is rotated out and a new one is started on the next
+/// log line.
+#[derive(Clone, Debug, Default)]
+pub struct LogReopener(Arc<AtomicBool>);
+
+impl LogReopener {
+    /// Requests the log files to be reopened. Does nothing when logging to stdout.
+    pub fn reopen(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
 #[derive(Default, Debug)]
 /// Tracing log formatter setup for easier span viewing
 pub struct LogFormatter;
@@ -43,7 +120,7 @@ where
         // Write level and target
         let level = *event.metadata().level();
         let module = event.metadata().module_path().unwrap_or("<unknown module>");
-        let time = SystemTime::default();
+        let time = SystemTime;
 
         write!(writer, "[")?;
         time.format_time(&mut writer)?;
@@ -63,67 +140,124 @@ where
 pub struct TracingLayers {
     layers: Vec<Box<dyn Layer<Registry> + Send + Sync>>,
     guard: Option<WorkerGuard>,
+    reopener: LogReopener,
 }
 
 impl TracingLayers {
-    fn fmt_layer(&mut self, optional_log_dir: &Option<PathBuf>) {
-        // Filter by log level either from `RUST_LOG` or default to crate only.
-        let target_filter: Box<dyn Filter<Registry> + Send + Sync> =
-            if let Ok(f) = EnvFilter::try_from_default_env() {
-                Box::new(f)
-            } else {
-                Box::new(Targets::new().with_target(current_crate_str(), tracing::Level::TRACE))
-            };
+    fn fmt_layer(
+        &mut self,
+        optional_log_dir: &Option<PathBuf>,
+        format: LogFormat,
+        rotation: &LogRotation,
+    ) {
+        let target_filter = target_filter();
         let fmt_layer = tracing_fmt::layer().with_ansi(false);
 
         if let Some(log_dir) = optional_log_dir {
             println!("Starting logging to directory: {log_dir:?}");
 
-            let logs_retained = 0;
-            let logs_max_bytes = 10485760;
-            let logs_max_lines = 5000;
-            let logs_uncompressed = 100;
-
-            let (non_blocking, worker_guard) = appender::file_rotater(
-                log_dir,
-                logs_max_bytes,
-                logs_max_lines,
-                logs_retained,
-                logs_uncompressed,
-            );
+            let (non_blocking, worker_guard) =
+                appender::file_rotater(log_dir, rotation, self.reopener.0.clone());
             self.guard = Some(worker_guard);
 
             let fmt_layer = fmt_layer.with_writer(non_blocking);
 
-            let layer = fmt_layer
-                .event_format(LogFormatter::default())
-                .with_filter(target_filter)
-                .boxed();
+            let layer = match format {
+                LogFormat::Default => fmt_layer
+                    .event_format(LogFormatter)
+                    .with_filter(target_filter)
+                    .boxed(),
+                LogFormat::Json => fmt_layer
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(false)
+                    .with_span_list(true)
+                    .with_filter(target_filter)
+                    .boxed(),
+            };
             self.layers.push(layer);
         } else {
             println!("Starting logging to stdout");
 
-            let layer = fmt_layer
-                .with_target(false)
-                .event_format(LogFormatter::default())
-                .with_filter(target_filter)
-                .boxed();
+            let layer = match format {
+                LogFormat::Default => fmt_layer
+                    .with_target(false)
+                    .event_format(LogFormatter)
+                    .with_filter(target_filter)
+                    .boxed(),
+                LogFormat::Json => fmt_layer
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(false)
+                    .with_span_list(true)
+                    .with_filter(target_filter)
+                    .boxed(),
+            };
             self.layers.push(layer);
         };
     }
+
+    // Exports the spans to the OpenTelemetry collector at `OTEL_EXPORTER_OTLP_ENDPOINT`,
+    // or the default local endpoint.
+    #[cfg(feature = "otlp")]
+    fn otlp_layer(&mut self) -> Result<(), opentelemetry::trace::TraceError> {
+        use opentelemetry::{
+            global, runtime,
+            sdk::{propagation::TraceContextPropagator, trace, Resource},
+            KeyValue,
+        };
+        use opentelemetry_otlp::WithExportConfig;
+
+        // The context of the spans is propagated to other peers in the W3C Trace Context format.
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let tracer =
+            opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(opentelemetry_otlp::new_exporter().http().with_env())
+                .with_trace_config(trace::config().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", current_crate_str()),
+                ])))
+                .install_batch(runtime::AsyncStd)?;
+
+        let layer = tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(target_filter())
+            .boxed();
+        self.layers.push(layer);
+        Ok(())
+    }
+}
+
+// Filter by log level either from `RUST_LOG` or default to crate only.
+fn target_filter() -> Box<dyn Filter<Registry> + Send + Sync> {
+    if let Ok(f) = EnvFilter::try_from_default_env() {
+        Box::new(f)
+    } else {
+        Box::new(Targets::new().with_target(current_crate_str(), tracing::Level::TRACE))
+    }
 }
 
-/// Inits node logging, returning the global node guard if required.
+/// Inits node logging, returning the global node guard if required, and the handle to
+/// reopen the log files with.
 /// This guard should be held for the life of the program.
 ///
 /// Logging should be instantiated only once.
-pub fn init_node_logging(log_dir: &Option<PathBuf>) -> Result<Option<WorkerGuard>, std::io::Error> {
+pub fn init_node_logging(
+    log_dir: &Option<PathBuf>,
+    format: LogFormat,
+    rotation: &LogRotation,
+) -> Result<(Option<WorkerGuard>, LogReopener), std::io::Error> {
     let mut layers = TracingLayers::default();
-    layers.fmt_layer(log_dir);
+    layers.fmt_layer(log_dir, format, rotation);
+    #[cfg(feature = "otlp")]
+    layers
+        .otlp_layer()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
 
     tracing_subscriber::registry().with(layers.layers).init();
 
-    Ok(layers.guard)
+    Ok((layers.guard, layers.reopener))
 }
 
 /// Get current root module name (e.g. "sn_node")
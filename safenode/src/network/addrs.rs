@@ -0,0 +1,107 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use std::{cmp::Reverse, collections::HashMap};
+
+/// The IP version an address is reached over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum AddrFamily {
+    /// IPv4 addresses.
+    Ip4,
+    /// IPv6 addresses.
+    Ip6,
+    /// DNS names and the like, which can resolve to either.
+    Other,
+}
+
+impl AddrFamily {
+    pub(crate) fn of(addr: &Multiaddr) -> Self {
+        match addr.iter().next() {
+            Some(Protocol::Ip4(_)) => Self::Ip4,
+            Some(Protocol::Ip6(_)) => Self::Ip6,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// The number of successful dials per address family, for the families that worked before
+/// to be tried first when dialing a peer known at addresses of several families.
+#[derive(Debug, Default)]
+pub(crate) struct DialPreference {
+    successes: HashMap<AddrFamily, usize>,
+}
+
+impl DialPreference {
+    /// Records that a peer was successfully dialed at the given address.
+    pub(crate) fn record_success(&mut self, addr: &Multiaddr) {
+        *self.successes.entry(AddrFamily::of(addr)).or_default() += 1;
+    }
+
+    /// Sorts the addresses with the families most successfully dialed first, keeping the
+    /// order of the addresses of the same family.
+    pub(crate) fn rank(&self, addrs: &mut [Multiaddr]) {
+        addrs.sort_by_key(|addr| {
+            Reverse(
+                self.successes
+                    .get(&AddrFamily::of(addr))
+                    .copied()
+                    .unwrap_or_default(),
+            )
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs(addrs: &[&str]) -> Vec<Multiaddr> {
+        addrs
+            .iter()
+            .map(|addr| addr.parse().expect("Failed to parse the address"))
+            .collect()
+    }
+
+    #[test]
+    fn the_order_is_kept_without_successful_dials() {
+        let mut ranked = addrs(&["/ip6/::1/udp/1/quic-v1", "/ip4/127.0.0.1/udp/1/quic-v1"]);
+        DialPreference::default().rank(&mut ranked);
+        assert_eq!(
+            ranked,
+            addrs(&["/ip6/::1/udp/1/quic-v1", "/ip4/127.0.0.1/udp/1/quic-v1"])
+        );
+    }
+
+    #[test]
+    fn the_most_successful_family_is_ranked_first() {
+        let mut preference = DialPreference::default();
+        preference.record_success(&addrs(&["/ip4/10.0.0.1/udp/1/quic-v1"])[0]);
+        preference.record_success(&addrs(&["/ip6/::2/udp/1/quic-v1"])[0]);
+        preference.record_success(&addrs(&["/ip6/::3/tcp/1/ws"])[0]);
+
+        let mut ranked = addrs(&[
+            "/ip4/127.0.0.1/udp/1/quic-v1",
+            "/dns4/example.com/tcp/1/ws",
+            "/ip6/::1/udp/1/quic-v1",
+            "/ip4/127.0.0.1/tcp/1/ws",
+            "/ip6/::1/tcp/1/ws",
+        ]);
+        preference.rank(&mut ranked);
+        assert_eq!(
+            ranked,
+            addrs(&[
+                "/ip6/::1/udp/1/quic-v1",
+                "/ip6/::1/tcp/1/ws",
+                "/ip4/127.0.0.1/udp/1/quic-v1",
+                "/ip4/127.0.0.1/tcp/1/ws",
+                "/dns4/example.com/tcp/1/ws",
+            ])
+        );
+    }
+}
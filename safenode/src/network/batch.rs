@@ -0,0 +1,197 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{query::KadQuery, swarm::SwarmOps, SwarmDriver};
+use futures::channel::oneshot;
+use libp2p::PeerId;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::Hash,
+};
+use tracing::debug;
+use xor_name::XorName;
+
+/// The peers holding the data at each key of a batch, closest first.
+pub(crate) type DataHolders = BTreeMap<XorName, Vec<PeerId>>;
+
+// The lookups of the keys of a batch, answered at once when the last of them ends.
+#[derive(Debug)]
+struct DataBatch {
+    remaining: usize,
+    holders: DataHolders,
+    sender: oneshot::Sender<DataHolders>,
+}
+
+/// The batches of lookups being run, with the batch and key each running lookup is for.
+#[derive(Debug)]
+pub(crate) struct DataBatches<Id> {
+    next_id: u64,
+    batches: HashMap<u64, DataBatch>,
+    lookups: HashMap<Id, (u64, XorName)>,
+}
+
+impl<Id> Default for DataBatches<Id> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            batches: HashMap::new(),
+            lookups: HashMap::new(),
+        }
+    }
+}
+
+impl<Id: Eq + Hash> DataBatches<Id> {
+    // Tracks a new batch of `remaining` lookups, returning its id.
+    fn insert(&mut self, remaining: usize, sender: oneshot::Sender<DataHolders>) -> u64 {
+        let batch_id = self.next_id;
+        self.next_id += 1;
+        let _ = self.batches.insert(
+            batch_id,
+            DataBatch {
+                remaining,
+                holders: DataHolders::new(),
+                sender,
+            },
+        );
+        batch_id
+    }
+
+    /// Tracks the lookup of the key as part of the batch.
+    pub(crate) fn lookup_started(&mut self, query_id: Id, batch_id: u64, xor_name: XorName) {
+        let _ = self.lookups.insert(query_id, (batch_id, xor_name));
+    }
+
+    // Records the holders found by the lookup, answering its batch if it was the last one.
+    // Returns whether the lookup was part of a batch.
+    fn lookup_ended(&mut self, query_id: &Id, holders: Vec<PeerId>) -> bool {
+        let (batch_id, xor_name) = match self.lookups.remove(query_id) {
+            Some(lookup) => lookup,
+            None => return false,
+        };
+        let batch = match self.batches.get_mut(&batch_id) {
+            Some(batch) => batch,
+            None => return true,
+        };
+        let _ = batch.holders.insert(xor_name, holders);
+        batch.remaining = batch.remaining.saturating_sub(1);
+        if batch.remaining == 0 {
+            if let Some(batch) = self.batches.remove(&batch_id) {
+                debug!("Lookups of a batch of {} keys ended", batch.holders.len());
+                let _ = batch.sender.send(batch.holders);
+            }
+        }
+        true
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Looks up the holders of each of the keys, all at once, answering with the holders of
+    /// every key once the last lookup ends.
+    pub(crate) fn get_data_batch(
+        &mut self,
+        keys: Vec<XorName>,
+        sender: oneshot::Sender<DataHolders>,
+    ) {
+        let keys: BTreeSet<XorName> = keys.into_iter().collect();
+        if keys.is_empty() {
+            let _ = sender.send(DataHolders::new());
+            return;
+        }
+        let batch_id = self.data_batches.insert(keys.len(), sender);
+        for xor_name in keys {
+            self.query_queue
+                .push(KadQuery::GetBatchedClosestPeers { batch_id, xor_name });
+        }
+        self.query_next();
+    }
+
+    /// Records the peers found by a lookup, if it was part of a batch, returning whether
+    /// it was. Dysfunctional peers are left out, as for single lookups.
+    pub(crate) fn batched_lookup_ended(
+        &mut self,
+        query_id: &S::QueryId,
+        peers: Vec<PeerId>,
+    ) -> bool {
+        if !self.data_batches.lookups.contains_key(query_id) {
+            return false;
+        }
+        let holders = self.functional(peers);
+        self.data_batches.lookup_ended(query_id, holders)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::{command::SwarmCmd, swarm::MockSwarm, SwarmConfig, SwarmDriver};
+    use eyre::Result;
+    use futures::channel::oneshot;
+    use libp2p::{identity::Keypair, PeerId};
+    use xor_name::XorName;
+
+    fn driver() -> SwarmDriver<MockSwarm> {
+        let (_network, _events, driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        driver
+    }
+
+    #[test]
+    fn batch_is_answered_once_every_lookup_has_ended() -> Result<()> {
+        let mut driver = driver();
+        let names = [XorName([1; 32]), XorName([2; 32])];
+        let (sender, mut receiver) = oneshot::channel();
+
+        // Keys asked for twice are looked up once.
+        driver.handle_command(SwarmCmd::GetDataBatch {
+            keys: vec![names[0], names[1], names[0]],
+            sender,
+        })?;
+        assert_eq!(driver.swarm.queries, names.to_vec());
+
+        let holders = vec![PeerId::random(), PeerId::random()];
+        assert!(driver.batched_lookup_ended(&1, holders.clone()));
+        assert!(receiver.try_recv()?.is_none());
+        assert!(driver.batched_lookup_ended(&2, vec![]));
+
+        let found = receiver.try_recv()?.expect("batch to be answered");
+        assert_eq!(found.get(&names[0]), Some(&holders));
+        assert_eq!(found.get(&names[1]), Some(&vec![]));
+        Ok(())
+    }
+
+    #[test]
+    fn lookups_not_part_of_a_batch_are_left_alone() -> Result<()> {
+        let mut driver = driver();
+        let (sender, _receiver) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetClosestPeers {
+            xor_name: XorName([1; 32]),
+            sender,
+        })?;
+
+        assert!(!driver.batched_lookup_ended(&1, vec![PeerId::random()]));
+        assert!(driver.pending_get_closest_peers.contains_key(&1));
+        Ok(())
+    }
+
+    #[test]
+    fn empty_batch_is_answered_straight_away() -> Result<()> {
+        let mut driver = driver();
+        let (sender, mut receiver) = oneshot::channel();
+
+        driver.handle_command(SwarmCmd::GetDataBatch {
+            keys: vec![],
+            sender,
+        })?;
+
+        assert!(driver.swarm.queries.is_empty());
+        assert_eq!(receiver.try_recv()?, Some(Default::default()));
+        Ok(())
+    }
+}
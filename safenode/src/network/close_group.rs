@@ -0,0 +1,156 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{swarm::SwarmOps, SwarmDriver, CLOSE_GROUP_SIZE};
+use libp2p::{
+    kad::kbucket::{Distance, Key},
+    PeerId,
+};
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+/// Returns the close group of `xor_name` among the given peers: the [`CLOSE_GROUP_SIZE`] peers
+/// closest to it, sorted by their distance to it.
+///
+/// The distance is the one Kademlia queries are run with, so that the close group is made of
+/// the peers a `GetClosestPeers` query for `xor_name` finds. The local peer, when given, is a
+/// candidate as well, being part of the close group when it is among the closest.
+pub(crate) fn close_group(
+    xor_name: &XorName,
+    peers: impl IntoIterator<Item = PeerId>,
+    local_peer: Option<PeerId>,
+) -> Vec<PeerId> {
+    closest_peers(
+        xor_name,
+        peers.into_iter().chain(local_peer),
+        CLOSE_GROUP_SIZE,
+    )
+}
+
+/// Returns the `count` peers closest to `xor_name` among the given peers, sorted by their
+/// distance to it, as for the [`close_group`].
+pub(crate) fn closest_peers(
+    xor_name: &XorName,
+    peers: impl IntoIterator<Item = PeerId>,
+    count: usize,
+) -> Vec<PeerId> {
+    let target = Key::new(xor_name.0.to_vec());
+    let candidates: BTreeSet<PeerId> = peers.into_iter().collect();
+    let mut by_distance: Vec<_> = candidates
+        .into_iter()
+        .map(|peer| (target.distance(&Key::from(peer)), peer))
+        .collect();
+    // Distinct peers are at distinct distances, the order does not depend on the input order.
+    by_distance.sort();
+    by_distance
+        .into_iter()
+        .take(count)
+        .map(|(_, peer)| peer)
+        .collect()
+}
+
+/// Whether the record `key` lies within the given distance of the local peer, the range of
+/// the records it is responsible for. Every key is within range while the range is unknown.
+pub(crate) fn is_within_range(key: &[u8], local_peer: PeerId, range: Option<Distance>) -> bool {
+    match range {
+        Some(range) => Key::new(key.to_vec()).distance(&Key::from(local_peer)) <= range,
+        None => true,
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Whether the record `key` is within the range of the records we are responsible for.
+    pub(crate) fn is_responsible_for(&mut self, key: &[u8]) -> bool {
+        let range = self.swarm.responsibility_range();
+        is_within_range(key, self.keypair.public().to_peer_id(), range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::multihash::Multihash;
+    use proptest::prelude::*;
+
+    // The multihash code of SHA2-256, which peer ids of keys too long to be inlined are made of.
+    const SHA2_256: u64 = 0x12;
+
+    fn peer_id() -> impl Strategy<Value = PeerId> {
+        any::<[u8; 32]>().prop_map(|digest| {
+            let multihash = Multihash::wrap(SHA2_256, &digest).expect("a digest fits a multihash");
+            PeerId::from_multihash(multihash).expect("a SHA2-256 multihash is a peer id")
+        })
+    }
+
+    fn xor_name() -> impl Strategy<Value = XorName> {
+        any::<[u8; 32]>().prop_map(XorName)
+    }
+
+    fn distance(xor_name: &XorName, peer: PeerId) -> Distance {
+        Key::new(xor_name.0.to_vec()).distance(&Key::from(peer))
+    }
+
+    proptest! {
+        #[test]
+        fn close_group_is_sorted_by_distance(
+            xor_name in xor_name(),
+            peers in prop::collection::vec(peer_id(), 0..40),
+        ) {
+            let group = close_group(&xor_name, peers, None);
+            for pair in group.windows(2) {
+                prop_assert!(distance(&xor_name, pair[0]) < distance(&xor_name, pair[1]));
+            }
+        }
+
+        #[test]
+        fn close_group_holds_the_closest_distinct_peers(
+            xor_name in xor_name(),
+            peers in prop::collection::vec(peer_id(), 0..40),
+        ) {
+            let distinct: BTreeSet<PeerId> = peers.iter().copied().collect();
+            let group = close_group(&xor_name, peers, None);
+
+            prop_assert_eq!(group.len(), distinct.len().min(CLOSE_GROUP_SIZE));
+            if let Some(farthest) = group.last() {
+                let max_distance = distance(&xor_name, *farthest);
+                for peer in distinct.iter().filter(|peer| !group.contains(peer)) {
+                    prop_assert!(distance(&xor_name, *peer) > max_distance);
+                }
+            }
+        }
+
+        #[test]
+        fn close_group_is_stable_under_permutation_of_the_peers(
+            xor_name in xor_name(),
+            (peers, shuffled) in prop::collection::vec(peer_id(), 0..40)
+                .prop_flat_map(|peers| (Just(peers.clone()), Just(peers).prop_shuffle())),
+        ) {
+            prop_assert_eq!(
+                close_group(&xor_name, peers, None),
+                close_group(&xor_name, shuffled, None)
+            );
+        }
+
+        #[test]
+        fn close_group_includes_the_local_peer_when_among_the_closest(
+            xor_name in xor_name(),
+            peers in prop::collection::vec(peer_id(), 0..40),
+            local_peer in peer_id(),
+        ) {
+            let closer = peers
+                .iter()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .filter(|peer| distance(&xor_name, **peer) < distance(&xor_name, local_peer))
+                .count();
+            let group = close_group(&xor_name, peers, Some(local_peer));
+
+            prop_assert_eq!(group.contains(&local_peer), closer < CLOSE_GROUP_SIZE);
+        }
+    }
+}
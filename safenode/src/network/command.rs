@@ -7,17 +7,29 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
+    batch::DataHolders,
     error::Error,
-    msg::{Request, Response},
-    NetworkSwarmLoop,
+    msg::MsgHeader,
+    query::KadQuery,
+    record::PendingRequest,
+    reputation::{Misbehaviour, PeerReputation},
+    swarm::SwarmOps,
+    validator::RecordValidator,
+    SwarmDriver,
+};
+use crate::{
+    network::error::Result,
+    protocol::messages::{Query, Replica, Request, SignedResponse},
 };
-use crate::network::error::Result;
 use futures::channel::oneshot;
-use libp2p::{multiaddr::Protocol, request_response::ResponseChannel, Multiaddr, PeerId};
+use libp2p::{request_response::ResponseChannel, Multiaddr, PeerId};
 use std::collections::{hash_map, HashSet};
-use tracing::warn;
+use tracing::{instrument, warn};
 use xor_name::XorName;
 
+#[cfg(feature = "test-utils")]
+use super::tap::MsgDirection;
+
 /// Commands to send to the Swarm
 #[derive(Debug)]
 pub(crate) enum SwarmCmd {
@@ -30,97 +42,451 @@ pub(crate) enum SwarmCmd {
         peer_addr: Multiaddr,
         sender: oneshot::Sender<Result<()>>,
     },
+    DialContacts {
+        contacts: Vec<Multiaddr>,
+    },
     StoreData {
         xor_name: XorName,
         sender: oneshot::Sender<Result<()>>,
     },
+    Bootstrap {
+        sender: oneshot::Sender<Result<()>>,
+    },
     GetDataProviders {
         xor_name: XorName,
         sender: oneshot::Sender<HashSet<PeerId>>,
     },
+    GetClosestPeers {
+        xor_name: XorName,
+        sender: oneshot::Sender<Vec<(PeerId, Vec<Multiaddr>)>>,
+    },
+    GetDataBatch {
+        keys: Vec<XorName>,
+        sender: oneshot::Sender<DataHolders>,
+    },
     SendRequest {
+        header: MsgHeader,
         req: Request,
         peer: PeerId,
-        sender: oneshot::Sender<Result<Response>>,
+        sender: oneshot::Sender<Result<SignedResponse>>,
+    },
+    GetRecordFromPeer {
+        peer: PeerId,
+        key: XorName,
+        sender: oneshot::Sender<Result<Replica>>,
     },
     SendResponse {
-        resp: Response,
-        channel: ResponseChannel<Response>,
+        resp: SignedResponse,
+        channel: ResponseChannel<SignedResponse>,
+    },
+    GetConnectedPeers {
+        sender: oneshot::Sender<Vec<PeerId>>,
+    },
+    GetListenAddrs {
+        sender: oneshot::Sender<Vec<Multiaddr>>,
+    },
+    GetRoutingTableSize {
+        sender: oneshot::Sender<usize>,
+    },
+    GetCloseGroup {
+        sender: oneshot::Sender<Vec<PeerId>>,
+    },
+    IsResponsibleFor {
+        xor_name: XorName,
+        sender: oneshot::Sender<bool>,
+    },
+    IsInRoutingTable {
+        peer_id: PeerId,
+        sender: oneshot::Sender<bool>,
+    },
+    ReportMisbehaviour {
+        peer_id: PeerId,
+        misbehaviour: Misbehaviour,
+    },
+    GetPeerReputations {
+        sender: oneshot::Sender<Vec<PeerReputation>>,
+    },
+    SetRecordValidator {
+        validator: RecordValidator,
+    },
+    AddExternalAddr {
+        addr: Multiaddr,
     },
 }
 
-impl NetworkSwarmLoop {
+impl<S: SwarmOps> SwarmDriver<S> {
+    #[instrument(name = "swarm_cmd", skip_all)]
     pub(crate) fn handle_command(&mut self, command: SwarmCmd) -> Result<(), Error> {
         match command {
             SwarmCmd::StartListening { addr, sender } => {
-                let _ = match self.swarm.listen_on(addr) {
-                    Ok(_) => sender.send(Ok(())),
-                    Err(e) => sender.send(Err(e.into())),
-                };
+                let _ = sender.send(self.swarm.listen_on(addr));
             }
             SwarmCmd::Dial {
                 peer_id,
                 peer_addr,
                 sender,
             } => {
-                if let hash_map::Entry::Vacant(e) = self.pending_dial.entry(peer_id) {
-                    let _routing_update = self
-                        .swarm
-                        .behaviour_mut()
-                        .kademlia
-                        .add_address(&peer_id, peer_addr.clone());
-                    match self
-                        .swarm
-                        .dial(peer_addr.with(Protocol::P2p(peer_id.into())))
-                    {
-                        Ok(()) => {
-                            let _ = e.insert(sender);
-                        }
-                        Err(e) => {
-                            let _ = sender.send(Err(e.into()));
-                        }
-                    }
+                if self.reputations.is_blocked(&peer_id) {
+                    let _ = sender.send(Err(Error::PeerBlocked(peer_id)));
+                } else if let hash_map::Entry::Vacant(e) = self.pending_dial.entry(peer_id) {
+                    let _ = e.insert(sender);
+                    self.swarm.add_to_routing_table(peer_id, peer_addr.clone());
+                    self.dial_queue.push(peer_id, peer_addr);
+                    self.dial_next();
                 } else {
                     warn!("Already dialing peer.");
                 }
             }
+            SwarmCmd::DialContacts { contacts } => self.dial_contacts(contacts),
             // todo: the `provider` api should not be used for chunks/dbcs.
             // 1. get the closest nodes to the data
             // 2. store data in them directly, not via provider
+            SwarmCmd::Bootstrap { sender } => match self.swarm.bootstrap() {
+                Ok(query_id) => {
+                    let _ = self.pending_bootstrap.insert(query_id, sender);
+                }
+                Err(err) => {
+                    warn!("Could not bootstrap: {err}");
+                    let _ = sender.send(Err(err));
+                }
+            },
             SwarmCmd::StoreData { xor_name, sender } => {
-                let query_id = self
-                    .swarm
-                    .behaviour_mut()
-                    .kademlia
-                    .start_providing(xor_name.0.to_vec().into())?;
-                let _ = self.pending_start_providing.insert(query_id, sender);
+                self.query_queue
+                    .push(KadQuery::StartProviding { xor_name, sender });
+                self.query_next();
             }
             SwarmCmd::GetDataProviders { xor_name, sender } => {
-                let query_id = self
-                    .swarm
-                    .behaviour_mut()
-                    .kademlia
-                    .get_providers(xor_name.0.to_vec().into());
-                let _ = self.pending_get_providers.insert(query_id, sender);
+                if self.providers_subscribers.subscribe(xor_name, sender) {
+                    self.query_queue.push(KadQuery::GetProviders { xor_name });
+                    self.query_next();
+                }
             }
-            SwarmCmd::SendRequest { req, peer, sender } => {
-                let request_id = self
-                    .swarm
-                    .behaviour_mut()
-                    .request_response
-                    .send_request(&peer, req);
-                let _ = self.pending_requests.insert(request_id, sender);
+            SwarmCmd::GetClosestPeers { xor_name, sender } => {
+                if self.closest_peers_subscribers.subscribe(xor_name, sender) {
+                    self.query_queue
+                        .push(KadQuery::GetClosestPeers { xor_name });
+                    self.query_next();
+                }
             }
+            SwarmCmd::GetDataBatch { keys, sender } => self.get_data_batch(keys, sender),
+            SwarmCmd::SendRequest {
+                header,
+                req,
+                peer,
+                sender,
+            } => self.send_request(peer, header, req, PendingRequest::Response(sender)),
+            SwarmCmd::GetRecordFromPeer { peer, key, sender } => self.send_request(
+                peer,
+                MsgHeader::default(),
+                Request::Query(Query::GetRecord(key)),
+                PendingRequest::Record { key, sender },
+            ),
             SwarmCmd::SendResponse { resp, channel } => {
-                self.swarm
-                    .behaviour_mut()
-                    .request_response
-                    .send_response(channel, resp)
-                    .map_err(|_| {
-                        Error::Other("Connection to peer to be still open.".to_string())
-                    })?;
+                #[cfg(feature = "malice")]
+                let resp = match &self.malice {
+                    Some(malice) => malice.tamper_response(resp, &self.keypair),
+                    None => resp,
+                };
+                #[cfg(feature = "test-utils")]
+                self.tap.response(MsgDirection::Outbound, None, &resp);
+                self.swarm.send_response(channel, resp)?;
+            }
+            SwarmCmd::GetConnectedPeers { sender } => {
+                let _ = sender.send(self.swarm.connected_peers());
+            }
+            SwarmCmd::GetListenAddrs { sender } => {
+                let _ = sender.send(self.swarm.listen_addrs());
+            }
+            SwarmCmd::GetRoutingTableSize { sender } => {
+                let _ = sender.send(self.swarm.routing_table_size());
+            }
+            SwarmCmd::GetCloseGroup { sender } => {
+                let _ = sender.send(self.swarm.close_group());
+            }
+            SwarmCmd::IsResponsibleFor { xor_name, sender } => {
+                let _ = sender.send(self.is_responsible_for(&xor_name.0));
+            }
+            SwarmCmd::IsInRoutingTable { peer_id, sender } => {
+                let _ = sender.send(self.swarm.is_in_routing_table(&peer_id));
+            }
+            SwarmCmd::ReportMisbehaviour {
+                peer_id,
+                misbehaviour,
+            } => self.penalise(peer_id, misbehaviour),
+            SwarmCmd::GetPeerReputations { sender } => {
+                let _ = sender.send(self.reputations.reputations());
+            }
+            SwarmCmd::SetRecordValidator { validator } => {
+                self.record_validator = Some(validator);
             }
+            SwarmCmd::AddExternalAddr { addr } => self.swarm.add_external_addr(addr),
         }
         Ok(())
     }
+
+    fn send_request(
+        &mut self,
+        peer: PeerId,
+        header: MsgHeader,
+        req: Request,
+        pending: PendingRequest,
+    ) {
+        self.idle_connections.touch(peer);
+        #[cfg(feature = "test-utils")]
+        self.tap
+            .request(MsgDirection::Outbound, peer, &header, &req);
+        #[cfg(feature = "malice")]
+        if self
+            .malice
+            .as_ref()
+            .is_some_and(|malice| malice.duplicates_request())
+        {
+            // Only the response to the first is waited for.
+            let _ = self.swarm.send_request(peer, header.clone(), req.clone());
+        }
+        let request_id = self.swarm.send_request(peer, header, req);
+        self.dysfunction.request_sent(request_id);
+        let _ = self.pending_requests.insert(request_id, pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::{swarm::MockSwarm, SwarmConfig, DEFAULT_MAX_CONCURRENT_DIALS},
+        protocol::messages::Query,
+        storage::chunks::ChunkAddress,
+    };
+    use eyre::{eyre, Result};
+    use libp2p::identity::Keypair;
+
+    fn driver(swarm: MockSwarm) -> SwarmDriver<MockSwarm> {
+        driver_with_max_dials(swarm, DEFAULT_MAX_CONCURRENT_DIALS)
+    }
+
+    fn driver_with_max_dials(swarm: MockSwarm, max_dials: usize) -> SwarmDriver<MockSwarm> {
+        let config = SwarmConfig {
+            max_concurrent_dials: max_dials,
+            ..Default::default()
+        };
+        let (_network, _events, driver) =
+            SwarmDriver::new(swarm, Keypair::generate_ed25519(), &config);
+        driver
+    }
+
+    fn peer_addr() -> Result<Multiaddr> {
+        Ok("/ip4/127.0.0.1/udp/12000/quic-v1".parse()?)
+    }
+
+    #[test]
+    fn dial_is_pending_until_the_connection_is_established() -> Result<()> {
+        let mut driver = driver(MockSwarm::default());
+        let peer_id = PeerId::random();
+        let (sender, mut receiver) = oneshot::channel();
+
+        driver.handle_command(SwarmCmd::Dial {
+            peer_id,
+            peer_addr: peer_addr()?,
+            sender,
+        })?;
+
+        assert_eq!(driver.swarm.routing_table, vec![(peer_id, peer_addr()?)]);
+        assert_eq!(driver.swarm.dialed, vec![(peer_id, peer_addr()?)]);
+        assert!(driver.pending_dial.contains_key(&peer_id));
+        assert!(receiver.try_recv()?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn dials_beyond_the_max_are_queued_until_a_dial_ends() -> Result<()> {
+        let mut driver = driver_with_max_dials(MockSwarm::default(), 1);
+        let peers = [PeerId::random(), PeerId::random()];
+        for peer_id in peers {
+            let (sender, _receiver) = oneshot::channel();
+            driver.handle_command(SwarmCmd::Dial {
+                peer_id,
+                peer_addr: peer_addr()?,
+                sender,
+            })?;
+        }
+        assert_eq!(driver.swarm.dialed, vec![(peers[0], peer_addr()?)]);
+
+        driver.dial_failed(peers[0], Error::Other("unreachable".to_string()));
+        driver.dial_next();
+
+        assert_eq!(
+            driver.swarm.dialed,
+            vec![(peers[0], peer_addr()?), (peers[1], peer_addr()?)]
+        );
+        assert!(!driver.pending_dial.contains_key(&peers[0]));
+        Ok(())
+    }
+
+    #[test]
+    fn dial_of_a_peer_already_being_dialed_is_not_repeated() -> Result<()> {
+        let mut driver = driver(MockSwarm::default());
+        let peer_id = PeerId::random();
+        for _ in 0..2 {
+            let (sender, _receiver) = oneshot::channel();
+            driver.handle_command(SwarmCmd::Dial {
+                peer_id,
+                peer_addr: peer_addr()?,
+                sender,
+            })?;
+        }
+
+        assert_eq!(driver.swarm.dialed.len(), 1);
+        assert_eq!(driver.pending_dial.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn failed_dial_is_reported_and_not_left_pending() -> Result<()> {
+        let mut driver = driver(MockSwarm {
+            fail_dials: true,
+            ..Default::default()
+        });
+        let peer_id = PeerId::random();
+        let (sender, mut receiver) = oneshot::channel();
+
+        driver.handle_command(SwarmCmd::Dial {
+            peer_id,
+            peer_addr: peer_addr()?,
+            sender,
+        })?;
+
+        assert!(driver.pending_dial.is_empty());
+        match receiver.try_recv()? {
+            Some(Err(_)) => Ok(()),
+            other => Err(eyre!("Expected the dial to fail, got {other:?}")),
+        }
+    }
+
+    #[test]
+    fn misbehaving_peers_are_demoted_blocked_and_not_dialed() -> Result<()> {
+        let mut driver = driver(MockSwarm::default());
+        let peer_id = PeerId::random();
+        for _ in 0..2 {
+            driver.handle_command(SwarmCmd::ReportMisbehaviour {
+                peer_id,
+                misbehaviour: Misbehaviour::InvalidData,
+            })?;
+        }
+        assert_eq!(driver.swarm.removed_from_routing_table, vec![peer_id]);
+        assert_eq!(driver.swarm.disconnected, vec![peer_id]);
+
+        let (sender, mut reputations) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetPeerReputations { sender })?;
+        match reputations.try_recv()?.as_deref() {
+            Some([reputation]) if reputation.peer_id == peer_id && reputation.blocked => {}
+            other => return Err(eyre!("Expected the peer to be blocked, got {other:?}")),
+        }
+
+        let (sender, mut receiver) = oneshot::channel();
+        driver.handle_command(SwarmCmd::Dial {
+            peer_id,
+            peer_addr: peer_addr()?,
+            sender,
+        })?;
+        assert!(driver.swarm.dialed.is_empty());
+        match receiver.try_recv()? {
+            Some(Err(Error::PeerBlocked(blocked))) if blocked == peer_id => Ok(()),
+            other => Err(eyre!("Expected the dial to be refused, got {other:?}")),
+        }
+    }
+
+    #[test]
+    fn queries_are_pending_under_the_id_they_were_started_with() -> Result<()> {
+        let mut driver = driver(MockSwarm::default());
+        let xor_name = XorName::from_content(b"data");
+
+        let (sender, _receiver) = oneshot::channel();
+        driver.handle_command(SwarmCmd::StoreData { xor_name, sender })?;
+        let (sender, _receiver) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetDataProviders { xor_name, sender })?;
+        let (sender, _receiver) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetClosestPeers { xor_name, sender })?;
+
+        assert_eq!(driver.swarm.providing, vec![xor_name]);
+        assert_eq!(driver.swarm.queries, vec![xor_name, xor_name]);
+        assert!(driver.pending_start_providing.contains_key(&1));
+        assert!(driver.pending_get_providers.contains_key(&2));
+        assert!(driver.pending_get_closest_peers.contains_key(&3));
+        Ok(())
+    }
+
+    #[test]
+    fn bootstrap_is_pending_until_done_or_fails_without_peers() -> Result<()> {
+        let mut driver = driver(MockSwarm::default());
+        let (sender, mut receiver) = oneshot::channel();
+        driver.handle_command(SwarmCmd::Bootstrap { sender })?;
+        assert!(matches!(
+            receiver.try_recv()?,
+            Some(Err(Error::NoKnownPeers(_)))
+        ));
+
+        let addr = "/ip4/127.0.0.1/tcp/1200".parse()?;
+        driver.swarm.routing_table.push((PeerId::random(), addr));
+        let (sender, mut receiver) = oneshot::channel();
+        driver.handle_command(SwarmCmd::Bootstrap { sender })?;
+        assert_eq!(driver.swarm.bootstraps, 1);
+        assert!(driver.pending_bootstrap.contains_key(&1));
+        assert!(receiver.try_recv()?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn request_is_sent_to_the_peer_and_pending_its_response() -> Result<()> {
+        let mut driver = driver(MockSwarm::default());
+        let peer = PeerId::random();
+        let req = Request::Query(Query::GetChunk(ChunkAddress(XorName::from_content(
+            b"chunk",
+        ))));
+        let (sender, mut receiver) = oneshot::channel();
+
+        driver.handle_command(SwarmCmd::SendRequest {
+            header: MsgHeader::default(),
+            req: req.clone(),
+            peer,
+            sender,
+        })?;
+
+        assert_eq!(driver.swarm.requests, vec![(peer, req)]);
+        assert!(driver.pending_requests.contains_key(&1));
+        assert!(receiver.try_recv()?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn state_queries_are_answered_straight_away() -> Result<()> {
+        let peer = PeerId::random();
+        let mut driver = driver(MockSwarm {
+            connected_peers: vec![peer],
+            routing_table_size: 7,
+            close_group: vec![peer],
+            ..Default::default()
+        });
+
+        let (sender, mut listening) = oneshot::channel();
+        driver.handle_command(SwarmCmd::StartListening {
+            addr: peer_addr()?,
+            sender,
+        })?;
+        let (sender, mut peers) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetConnectedPeers { sender })?;
+        let (sender, mut addrs) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetListenAddrs { sender })?;
+        let (sender, mut size) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetRoutingTableSize { sender })?;
+        let (sender, mut close_group) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetCloseGroup { sender })?;
+
+        assert!(matches!(listening.try_recv()?, Some(Ok(()))));
+        assert_eq!(peers.try_recv()?, Some(vec![peer]));
+        assert_eq!(addrs.try_recv()?, Some(vec![peer_addr()?]));
+        assert_eq!(size.try_recv()?, Some(7));
+        assert_eq!(close_group.try_recv()?, Some(vec![peer]));
+        Ok(())
+    }
 }
@@ -0,0 +1,206 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Network;
+use libp2p::swarm::DialError;
+use serde::Serialize;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// The kinds of dial failures, for a flaky network to be told apart from misconfigured peers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DialFailures {
+    /// The peer could not be reached at any of its addresses, e.g. refused or timed out.
+    pub transport: u64,
+    /// A peer other than the one dialed answered at the address.
+    pub wrong_peer: u64,
+    /// The peer was dialed without any address known for it.
+    pub no_addresses: u64,
+    /// The dial was aborted, or not made for the peer being connected already.
+    pub aborted: u64,
+    /// The dial failed otherwise, e.g. on the connection limits.
+    pub other: u64,
+}
+
+/// The connections of the node since startup, and at present.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ConnectionStats {
+    /// The dials started, by us or by the protocols.
+    pub dials: u64,
+    /// The dials which failed, by kind of failure.
+    pub dial_failures: DialFailures,
+    /// The connections peers failed to establish with us.
+    pub incoming_failures: u64,
+    /// The connections established, both ways.
+    pub connections_established: u64,
+    /// The connections closed.
+    pub connections_closed: u64,
+    /// The connections currently open.
+    pub active_connections: u64,
+    /// The peers currently connected to, over one connection or more.
+    pub connected_peers: u64,
+    /// The total time the connections took to be established, in milliseconds, to be
+    /// divided by the `connections_established` for the mean.
+    pub establishment_ms_total: u64,
+    /// The longest time a connection took to be established, in milliseconds.
+    pub establishment_ms_max: u64,
+}
+
+// The counters behind the `ConnectionStats`, shared by the `Network` and the driver.
+#[derive(Debug, Default)]
+pub(super) struct ConnectionCounters {
+    dials: AtomicU64,
+    transport_failures: AtomicU64,
+    wrong_peer_failures: AtomicU64,
+    no_addresses_failures: AtomicU64,
+    aborted_failures: AtomicU64,
+    other_failures: AtomicU64,
+    incoming_failures: AtomicU64,
+    connections_established: AtomicU64,
+    connections_closed: AtomicU64,
+    active_connections: AtomicU64,
+    connected_peers: AtomicU64,
+    establishment_ms_total: AtomicU64,
+    establishment_ms_max: AtomicU64,
+}
+
+impl ConnectionCounters {
+    pub(super) fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            dials: self.dials.load(Ordering::Relaxed),
+            dial_failures: DialFailures {
+                transport: self.transport_failures.load(Ordering::Relaxed),
+                wrong_peer: self.wrong_peer_failures.load(Ordering::Relaxed),
+                no_addresses: self.no_addresses_failures.load(Ordering::Relaxed),
+                aborted: self.aborted_failures.load(Ordering::Relaxed),
+                other: self.other_failures.load(Ordering::Relaxed),
+            },
+            incoming_failures: self.incoming_failures.load(Ordering::Relaxed),
+            connections_established: self.connections_established.load(Ordering::Relaxed),
+            connections_closed: self.connections_closed.load(Ordering::Relaxed),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            connected_peers: self.connected_peers.load(Ordering::Relaxed),
+            establishment_ms_total: self.establishment_ms_total.load(Ordering::Relaxed),
+            establishment_ms_max: self.establishment_ms_max.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(super) fn dialing(&self) {
+        increment(&self.dials);
+    }
+
+    pub(super) fn dial_failed(&self, error: &DialError) {
+        let counter = match error {
+            DialError::Transport(_) => &self.transport_failures,
+            DialError::WrongPeerId { .. } => &self.wrong_peer_failures,
+            DialError::NoAddresses => &self.no_addresses_failures,
+            DialError::Aborted | DialError::DialPeerConditionFalse(_) => &self.aborted_failures,
+            _ => &self.other_failures,
+        };
+        increment(counter);
+    }
+
+    pub(super) fn incoming_failed(&self) {
+        increment(&self.incoming_failures);
+    }
+
+    // Counts the connection, the first one to the peer connecting us to it.
+    pub(super) fn established(&self, first_to_peer: bool, established_in: Duration) {
+        increment(&self.connections_established);
+        increment(&self.active_connections);
+        if first_to_peer {
+            increment(&self.connected_peers);
+        }
+        let millis = u64::try_from(established_in.as_millis()).unwrap_or(u64::MAX);
+        let _ = self
+            .establishment_ms_total
+            .fetch_add(millis, Ordering::Relaxed);
+        let _ = self
+            .establishment_ms_max
+            .fetch_max(millis, Ordering::Relaxed);
+    }
+
+    // Counts the connection out, the last one to the peer disconnecting us from it.
+    pub(super) fn closed(&self, last_to_peer: bool) {
+        increment(&self.connections_closed);
+        decrement(&self.active_connections);
+        if last_to_peer {
+            decrement(&self.connected_peers);
+        }
+    }
+}
+
+fn increment(counter: &AtomicU64) {
+    let _ = counter.fetch_add(1, Ordering::Relaxed);
+}
+
+// Decrements the gauge, without wrapping below zero on connections open before it.
+fn decrement(counter: &AtomicU64) {
+    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+        Some(count.saturating_sub(1))
+    });
+}
+
+impl Network {
+    /// The dials and connections of the node, for a flaky network to be diagnosed.
+    pub fn connection_stats(&self) -> ConnectionStats {
+        self.connections.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dial_failures_are_counted_by_kind() {
+        let counters = ConnectionCounters::default();
+        counters.dial_failed(&DialError::NoAddresses);
+        counters.dial_failed(&DialError::Aborted);
+        counters.dial_failed(&DialError::Transport(vec![]));
+        counters.dial_failed(&DialError::Transport(vec![]));
+
+        let failures = counters.stats().dial_failures;
+        assert_eq!(
+            failures,
+            DialFailures {
+                transport: 2,
+                no_addresses: 1,
+                aborted: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn connections_are_counted_per_peer_and_timed() {
+        let counters = ConnectionCounters::default();
+        counters.established(true, Duration::from_millis(30));
+        counters.established(false, Duration::from_millis(50));
+        counters.closed(false);
+
+        let stats = counters.stats();
+        assert_eq!(stats.connections_established, 2);
+        assert_eq!(stats.connections_closed, 1);
+        assert_eq!(stats.active_connections, 1);
+        assert_eq!(stats.connected_peers, 1);
+        assert_eq!(stats.establishment_ms_total, 80);
+        assert_eq!(stats.establishment_ms_max, 50);
+    }
+
+    #[test]
+    fn gauges_do_not_wrap_below_zero() {
+        let counters = ConnectionCounters::default();
+        counters.closed(true);
+        let stats = counters.stats();
+        assert_eq!(stats.active_connections, 0);
+        assert_eq!(stats.connected_peers, 0);
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{swarm::SwarmOps, SwarmDriver};
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    multiaddr::Protocol,
+    Multiaddr, PeerId,
+};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::debug;
+
+/// The max number of peers dialed out of the contacts, picked at random so that the nodes
+/// spread their bootstrapping over the contacts.
+pub const CONTACTS_DIAL_COUNT: usize = 8;
+/// How often the contacts are fetched again by default, for their new peers to be dialed.
+pub const DEFAULT_CONTACTS_REFRESH: Duration = Duration::from_secs(60 * 60);
+
+/// Errors fetching or verifying the network contacts.
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum ContactsError {
+    #[error("Could not read the contacts from {location}: {err}")]
+    Fetch { location: String, err: String },
+    #[error("Invalid contacts: {0}")]
+    Parse(String),
+    #[error("The contacts are not signed by the network key")]
+    InvalidSignature,
+    #[error("Invalid network public key: {0}")]
+    InvalidPublicKey(String),
+    #[error("Could not sign the contacts: {0}")]
+    Signing(String),
+}
+
+/// The peers to bootstrap from, signed by the key the network was set up with, as published
+/// in a network-contacts file.
+///
+/// The file is the JSON encoding of the contacts, e.g. `{"peers": ["/ip4/1.2.3.4/udp/12000/quic-v1/p2p/<peer id>"], "signature": "<hex>"}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkContacts {
+    /// The addresses of the peers, ending with their `/p2p/<peer id>`.
+    pub peers: Vec<Multiaddr>,
+    /// The hex encoded signature of the peers by the network key.
+    signature: String,
+}
+
+impl NetworkContacts {
+    /// Signs the peers with the network key, for the contacts to be published.
+    pub fn sign(peers: Vec<Multiaddr>, network_key: &Keypair) -> Result<Self, ContactsError> {
+        let signature = network_key
+            .sign(&signed_bytes(&peers))
+            .map_err(|err| ContactsError::Signing(err.to_string()))?;
+        Ok(Self {
+            peers,
+            signature: hex::encode(signature),
+        })
+    }
+
+    /// Parses the JSON encoded contacts, returning their peers once the signature verifies
+    /// against the public network key.
+    pub fn verified_peers(
+        json: &[u8],
+        public_key: &PublicKey,
+    ) -> Result<Vec<Multiaddr>, ContactsError> {
+        let contacts: Self =
+            serde_json::from_slice(json).map_err(|err| ContactsError::Parse(err.to_string()))?;
+        let signature =
+            hex::decode(&contacts.signature).map_err(|_| ContactsError::InvalidSignature)?;
+        if public_key.verify(&signed_bytes(&contacts.peers), &signature) {
+            Ok(contacts.peers)
+        } else {
+            Err(ContactsError::InvalidSignature)
+        }
+    }
+}
+
+/// Decodes the hex encoded protobuf encoding of the public network key.
+pub fn decode_public_key(hex_key: &str) -> Result<PublicKey, ContactsError> {
+    let bytes = hex::decode(hex_key.trim())
+        .map_err(|err| ContactsError::InvalidPublicKey(err.to_string()))?;
+    PublicKey::try_decode_protobuf(&bytes)
+        .map_err(|err| ContactsError::InvalidPublicKey(err.to_string()))
+}
+
+/// Reads the contacts from the file at the given path, or from the given `https://` URL,
+/// returning their peers once the signature verifies against the public network key.
+#[cfg(feature = "node")]
+pub async fn fetch_contacts(
+    source: &str,
+    public_key: &PublicKey,
+) -> Result<Vec<Multiaddr>, ContactsError> {
+    let fetch_error = |err: String| ContactsError::Fetch {
+        location: source.to_string(),
+        err,
+    };
+    let json = if source.starts_with("https://") || source.starts_with("http://") {
+        surf::get(source)
+            .recv_bytes()
+            .await
+            .map_err(|err| fetch_error(err.to_string()))?
+    } else {
+        async_std::fs::read(source)
+            .await
+            .map_err(|err| fetch_error(err.to_string()))?
+    };
+    NetworkContacts::verified_peers(&json, public_key)
+}
+
+// The bytes signed, the addresses of the peers one per line.
+fn signed_bytes(peers: &[Multiaddr]) -> Vec<u8> {
+    let lines: Vec<String> = peers.iter().map(Multiaddr::to_string).collect();
+    lines.join("\n").into_bytes()
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Dials a random subset of the contacts, leaving out the peers already connected to,
+    /// the blocked ones and ourselves.
+    pub(crate) fn dial_contacts(&mut self, contacts: Vec<Multiaddr>) {
+        let our_id = self.keypair.public().to_peer_id();
+        let connected = self.swarm.connected_peers();
+        let mut peers: Vec<(PeerId, Multiaddr)> = contacts
+            .into_iter()
+            .filter_map(split_peer_id)
+            .filter(|(peer_id, _)| {
+                *peer_id != our_id
+                    && !connected.contains(peer_id)
+                    && !self.reputations.is_blocked(peer_id)
+            })
+            .collect();
+        peers.shuffle(&mut rand::thread_rng());
+
+        for (peer_id, addr) in peers.into_iter().take(CONTACTS_DIAL_COUNT) {
+            debug!("Dialing contact {peer_id:?} at {addr:?}");
+            self.swarm.add_to_routing_table(peer_id, addr.clone());
+            self.dial_queue.push(peer_id, addr);
+        }
+        self.dial_next();
+    }
+}
+
+// Splits the `/p2p/<peer id>` off the end of the address.
+fn split_peer_id(mut addr: Multiaddr) -> Option<(PeerId, Multiaddr)> {
+    match addr.pop() {
+        Some(Protocol::P2p(hash)) => match PeerId::from_multihash(hash) {
+            Ok(peer_id) => Some((peer_id, addr)),
+            Err(_) => {
+                debug!("Skipping contact {addr:?} with an invalid peer id");
+                None
+            }
+        },
+        _ => {
+            debug!("Skipping contact {addr:?} without a peer id");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{swarm::MockSwarm, SwarmConfig};
+    use eyre::Result;
+
+    fn contacts(count: usize) -> Result<Vec<Multiaddr>> {
+        (0..count)
+            .map(|port| {
+                let peer_id = PeerId::random();
+                Ok(format!("/ip4/10.0.0.1/tcp/{port}/p2p/{peer_id}").parse()?)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn contacts_signed_by_the_network_key_verify() -> Result<()> {
+        let network_key = Keypair::generate_ed25519();
+        let peers = contacts(3)?;
+        let json = serde_json::to_vec(&NetworkContacts::sign(peers.clone(), &network_key)?)?;
+
+        assert_eq!(
+            NetworkContacts::verified_peers(&json, &network_key.public())?,
+            peers
+        );
+        let other_key = Keypair::generate_ed25519().public();
+        assert!(matches!(
+            NetworkContacts::verified_peers(&json, &other_key),
+            Err(ContactsError::InvalidSignature)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn tampered_contacts_do_not_verify() -> Result<()> {
+        let network_key = Keypair::generate_ed25519();
+        let mut signed = NetworkContacts::sign(contacts(3)?, &network_key)?;
+        signed.peers.extend(contacts(1)?);
+        let json = serde_json::to_vec(&signed)?;
+
+        assert!(matches!(
+            NetworkContacts::verified_peers(&json, &network_key.public()),
+            Err(ContactsError::InvalidSignature)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn public_key_round_trips_through_hex() -> Result<()> {
+        let public_key = Keypair::generate_ed25519().public();
+        let hex_key = hex::encode(public_key.encode_protobuf());
+        assert_eq!(decode_public_key(&hex_key)?, public_key);
+        assert!(decode_public_key("not hex").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn a_random_subset_of_the_contacts_is_dialed() -> Result<()> {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let contacts = contacts(3 * CONTACTS_DIAL_COUNT)?;
+        let without_peer_id: Multiaddr = "/ip4/10.0.0.2/tcp/1".parse()?;
+        driver.dial_contacts(
+            std::iter::once(without_peer_id)
+                .chain(contacts.clone())
+                .collect(),
+        );
+
+        assert_eq!(driver.swarm.routing_table.len(), CONTACTS_DIAL_COUNT);
+        for (peer_id, addr) in &driver.swarm.dialed {
+            assert!(contacts.contains(&addr.clone().with(Protocol::P2p((*peer_id).into()))));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{error::Error, swarm::SwarmOps, SwarmDriver};
+use libp2p::{Multiaddr, PeerId};
+use std::collections::{HashMap, VecDeque};
+use tracing::{debug, warn};
+
+/// The max number of peers dialed at once by default.
+pub const DEFAULT_MAX_CONCURRENT_DIALS: usize = 16;
+
+/// The peers to dial, dialed a bounded number at a time so that bootstrapping off, or
+/// discovering, many peers does not open as many dials at once.
+///
+/// A peer is dialed at one address at a time, the most recently seen one first. When the
+/// dial fails, the peer is queued again, behind the other queued peers, to be dialed at its
+/// next address.
+#[derive(Debug)]
+pub(crate) struct DialQueue {
+    max_concurrent: usize,
+    in_flight: HashMap<PeerId, Multiaddr>,
+    queued: VecDeque<PeerId>,
+    // The addresses not tried yet, with when they were last seen, as a count of the pushes.
+    untried: HashMap<PeerId, Vec<(Multiaddr, u64)>>,
+    pushes: u64,
+}
+
+impl DialQueue {
+    pub(crate) fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent: max_concurrent.max(1),
+            in_flight: HashMap::new(),
+            queued: VecDeque::new(),
+            untried: HashMap::new(),
+            pushes: 0,
+        }
+    }
+
+    /// Queues the peer to be dialed at the address, just seen.
+    pub(crate) fn push(&mut self, peer_id: PeerId, addr: Multiaddr) {
+        self.pushes += 1;
+        let now = self.pushes;
+        let untried = self.untried.entry(peer_id).or_default();
+        match untried.iter_mut().find(|(known, _)| *known == addr) {
+            Some((_, seen_at)) => *seen_at = now,
+            None => untried.push((addr, now)),
+        }
+        if !self.in_flight.contains_key(&peer_id) && !self.queued.contains(&peer_id) {
+            self.queued.push_back(peer_id);
+        }
+    }
+
+    /// Takes the next peer to dial, with the address to dial it at, unless the max number
+    /// of dials are in flight already.
+    pub(crate) fn next_dial(&mut self) -> Option<(PeerId, Multiaddr)> {
+        while self.in_flight.len() < self.max_concurrent {
+            let peer_id = self.queued.pop_front()?;
+            if let Some(addr) = self.take_most_recent(&peer_id) {
+                let _ = self.in_flight.insert(peer_id, addr.clone());
+                return Some((peer_id, addr));
+            }
+        }
+        None
+    }
+
+    /// Ends the dial of the peer as connected, forgetting its other addresses.
+    pub(crate) fn connected(&mut self, peer_id: &PeerId) {
+        self.remove(peer_id);
+    }
+
+    /// Stops dialing the peer.
+    pub(crate) fn remove(&mut self, peer_id: &PeerId) {
+        let _ = self.in_flight.remove(peer_id);
+        let _ = self.untried.remove(peer_id);
+        self.queued.retain(|queued| queued != peer_id);
+    }
+
+    /// Ends the dial of the peer as failed, queueing it again if it has addresses left to
+    /// try. Returns whether it does.
+    pub(crate) fn failed(&mut self, peer_id: &PeerId) -> bool {
+        let _ = self.in_flight.remove(peer_id);
+        let has_untried = self
+            .untried
+            .get(peer_id)
+            .is_some_and(|untried| !untried.is_empty());
+        if !has_untried {
+            let _ = self.untried.remove(peer_id);
+        } else if !self.queued.contains(peer_id) {
+            self.queued.push_back(*peer_id);
+        }
+        has_untried
+    }
+
+    fn take_most_recent(&mut self, peer_id: &PeerId) -> Option<Multiaddr> {
+        let untried = self.untried.get_mut(peer_id)?;
+        let most_recent = untried
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, seen_at))| *seen_at)
+            .map(|(index, _)| index)?;
+        Some(untried.swap_remove(most_recent).0)
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Starts the dials the queue allows for.
+    pub(crate) fn dial_next(&mut self) {
+        while let Some((peer_id, addr)) = self.dial_queue.next_dial() {
+            if self.reputations.is_blocked(&peer_id) {
+                self.dial_queue.remove(&peer_id);
+                if let Some(sender) = self.pending_dial.remove(&peer_id) {
+                    let _ = sender.send(Err(Error::PeerBlocked(peer_id)));
+                }
+                continue;
+            }
+            debug!("Dialing {peer_id:?} at {addr:?}");
+            if let Err(err) = self.swarm.dial(peer_id, addr) {
+                self.dial_failed(peer_id, err);
+            }
+        }
+    }
+
+    /// Ends the dial of the peer as failed, reporting the error to the dial cmd once all the
+    /// addresses of the peer have been tried.
+    pub(crate) fn dial_failed(&mut self, peer_id: PeerId, err: Error) {
+        warn!("Failed to dial {peer_id:?}: {err}");
+        if !self.dial_queue.failed(&peer_id) {
+            self.unreachable(peer_id);
+            if let Some(sender) = self.pending_dial.remove(&peer_id) {
+                let _ = sender.send(Err(err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    fn addr(port: u16) -> Result<Multiaddr> {
+        Ok(format!("/ip4/127.0.0.1/udp/{port}/quic-v1").parse()?)
+    }
+
+    #[test]
+    fn no_more_than_the_max_dials_are_in_flight() -> Result<()> {
+        let mut queue = DialQueue::new(2);
+        let peers: Vec<_> = (0..3).map(|_| PeerId::random()).collect();
+        for peer_id in &peers {
+            queue.push(*peer_id, addr(1)?);
+        }
+
+        assert_eq!(queue.next_dial(), Some((peers[0], addr(1)?)));
+        assert_eq!(queue.next_dial(), Some((peers[1], addr(1)?)));
+        assert_eq!(queue.next_dial(), None);
+
+        queue.connected(&peers[0]);
+        assert_eq!(queue.next_dial(), Some((peers[2], addr(1)?)));
+        Ok(())
+    }
+
+    #[test]
+    fn failed_dials_are_retried_at_the_next_most_recently_seen_address() -> Result<()> {
+        let mut queue = DialQueue::new(1);
+        let peer_id = PeerId::random();
+        let other_peer = PeerId::random();
+        queue.push(peer_id, addr(1)?);
+        queue.push(peer_id, addr(2)?);
+        queue.push(other_peer, addr(3)?);
+        // Seeing an address again makes it the most recent one.
+        queue.push(peer_id, addr(1)?);
+
+        assert_eq!(queue.next_dial(), Some((peer_id, addr(1)?)));
+        assert!(queue.failed(&peer_id));
+        // The peer is retried behind the other queued peers.
+        assert_eq!(queue.next_dial(), Some((other_peer, addr(3)?)));
+        queue.connected(&other_peer);
+        assert_eq!(queue.next_dial(), Some((peer_id, addr(2)?)));
+        assert!(!queue.failed(&peer_id));
+        assert_eq!(queue.next_dial(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{swarm::SwarmOps, SwarmDriver};
+use libp2p::PeerId;
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// The window the failures of a peer are counted over.
+pub const DYSFUNCTION_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// How long a peer can take to respond before its response counts as slow.
+pub const SLOW_RESPONSE: Duration = Duration::from_secs(5);
+/// The weight of the failures within the window from which a peer is suspect.
+pub const SUSPECT_THRESHOLD: u32 = 5;
+/// The weight of the failures within the window from which a peer is dysfunctional, and is
+/// demoted from the routing table and left out of the peers data is sent to.
+pub const DYSFUNCTIONAL_THRESHOLD: u32 = 15;
+
+/// A failure of a peer to do its part, counted against it for the [`DYSFUNCTION_WINDOW`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The peer did not respond to a request.
+    MissedResponse,
+    /// The peer responded, but took longer than [`SLOW_RESPONSE`].
+    SlowResponse,
+    /// The peer sent a record, or a response, that failed validation.
+    InvalidRecord,
+    /// The peer could not be dialed at any of its addresses.
+    Unreachable,
+    /// The peer could not prove it holds a chunk it is responsible for.
+    FailedAudit,
+}
+
+impl Failure {
+    fn weight(self) -> u32 {
+        match self {
+            Self::SlowResponse => 1,
+            Self::MissedResponse | Self::Unreachable => 3,
+            Self::InvalidRecord | Self::FailedAudit => 5,
+        }
+    }
+}
+
+/// How well a peer does its part, going by its failures within the [`DYSFUNCTION_WINDOW`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Health {
+    /// The peer fails seldom, if ever.
+    Healthy,
+    /// The peer failed more than what is usual.
+    Suspect,
+    /// The peer fails too often to be relied on.
+    Dysfunctional,
+}
+
+/// The failures of the peers over a sliding window, and the requests sent to them, for their
+/// responses to be timed.
+#[derive(Debug)]
+pub(crate) struct Dysfunction<Id> {
+    failures: HashMap<PeerId, VecDeque<(Instant, Failure)>>,
+    sent_at: HashMap<Id, Instant>,
+}
+
+impl<Id> Default for Dysfunction<Id> {
+    fn default() -> Self {
+        Self {
+            failures: HashMap::new(),
+            sent_at: HashMap::new(),
+        }
+    }
+}
+
+impl<Id: Eq + Hash> Dysfunction<Id> {
+    /// Starts timing the response to the request.
+    pub(crate) fn request_sent(&mut self, request_id: Id) {
+        let _ = self.sent_at.insert(request_id, Instant::now());
+    }
+
+    /// Ends the request, returning how long it took.
+    pub(crate) fn request_ended(&mut self, request_id: &Id) -> Option<Duration> {
+        self.sent_at
+            .remove(request_id)
+            .map(|sent_at| sent_at.elapsed())
+    }
+
+    /// Counts the failure against the peer, returning its health.
+    pub(crate) fn record(&mut self, peer_id: PeerId, failure: Failure) -> Health {
+        self.record_at(peer_id, failure, Instant::now())
+    }
+
+    /// The health of the peer.
+    pub(crate) fn health(&mut self, peer_id: &PeerId) -> Health {
+        self.health_at(peer_id, Instant::now())
+    }
+
+    fn record_at(&mut self, peer_id: PeerId, failure: Failure, now: Instant) -> Health {
+        debug!("{peer_id:?} failed with {failure:?}");
+        self.failures
+            .entry(peer_id)
+            .or_default()
+            .push_back((now, failure));
+        self.health_at(&peer_id, now)
+    }
+
+    fn health_at(&mut self, peer_id: &PeerId, now: Instant) -> Health {
+        let failures = match self.failures.get_mut(peer_id) {
+            Some(failures) => failures,
+            None => return Health::Healthy,
+        };
+        while let Some((failed_at, _)) = failures.front() {
+            if now.saturating_duration_since(*failed_at) <= DYSFUNCTION_WINDOW {
+                break;
+            }
+            let _ = failures.pop_front();
+        }
+        let weight: u32 = failures.iter().map(|(_, failure)| failure.weight()).sum();
+        if failures.is_empty() {
+            let _ = self.failures.remove(peer_id);
+        }
+        if weight >= DYSFUNCTIONAL_THRESHOLD {
+            Health::Dysfunctional
+        } else if weight >= SUSPECT_THRESHOLD {
+            Health::Suspect
+        } else {
+            Health::Healthy
+        }
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Counts the failure against the peer, demoting it from the routing table once it turns
+    /// dysfunctional.
+    pub(crate) fn record_failure(&mut self, peer_id: PeerId, failure: Failure) {
+        let before = self.dysfunction.health(&peer_id);
+        let after = self.dysfunction.record(peer_id, failure);
+        if after == before {
+            return;
+        }
+        warn!("{peer_id:?} is now {after:?}, after {failure:?}");
+        if after == Health::Dysfunctional {
+            self.swarm.remove_from_routing_table(&peer_id);
+        }
+    }
+
+    /// Whether the peer fails too often to be added to the routing table, or sent data.
+    pub(crate) fn is_dysfunctional(&mut self, peer_id: &PeerId) -> bool {
+        self.dysfunction.health(peer_id) == Health::Dysfunctional
+    }
+
+    /// Leaves the dysfunctional peers out.
+    pub(crate) fn functional(&mut self, peers: Vec<PeerId>) -> Vec<PeerId> {
+        peers
+            .into_iter()
+            .filter(|peer_id| !self.is_dysfunctional(peer_id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{swarm::MockSwarm, Misbehaviour, SwarmConfig};
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn peers_recover_once_their_failures_leave_the_window() {
+        let mut dysfunction = Dysfunction::<u64>::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..4 {
+            assert_eq!(
+                dysfunction.record_at(peer_id, Failure::SlowResponse, now),
+                Health::Healthy
+            );
+        }
+        assert_eq!(
+            dysfunction.record_at(peer_id, Failure::SlowResponse, now),
+            Health::Suspect
+        );
+        assert_eq!(
+            dysfunction.record_at(peer_id, Failure::InvalidRecord, now),
+            Health::Suspect
+        );
+        assert_eq!(
+            dysfunction.record_at(peer_id, Failure::InvalidRecord, now),
+            Health::Dysfunctional
+        );
+
+        let later = now + DYSFUNCTION_WINDOW + Duration::from_secs(1);
+        assert_eq!(dysfunction.health_at(&peer_id, later), Health::Healthy);
+        assert!(dysfunction.failures.is_empty());
+    }
+
+    #[test]
+    fn dysfunctional_peers_are_demoted_and_left_out() {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let failing = PeerId::random();
+        let other = PeerId::random();
+
+        for _ in 0..5 {
+            driver.record_failure(failing, Failure::MissedResponse);
+        }
+
+        // Demoted once, when turning dysfunctional.
+        assert_eq!(driver.swarm.removed_from_routing_table, vec![failing]);
+        assert_eq!(driver.functional(vec![failing, other]), vec![other]);
+    }
+
+    #[test]
+    fn peers_failing_audits_turn_dysfunctional() {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let failing = PeerId::random();
+
+        for _ in 0..2 {
+            driver.penalise(failing, Misbehaviour::FailedAudit);
+        }
+        assert!(!driver.is_dysfunctional(&failing));
+        driver.penalise(failing, Misbehaviour::FailedAudit);
+        assert!(driver.is_dysfunctional(&failing));
+    }
+}
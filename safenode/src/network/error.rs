@@ -7,9 +7,10 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use futures::channel::{mpsc, oneshot};
-use libp2p::{kad, request_response::OutboundFailure, swarm::DialError, TransportError};
+use libp2p::{kad, request_response::OutboundFailure, swarm::DialError, PeerId, TransportError};
 use std::io;
 use thiserror::Error;
+use xor_name::XorName;
 
 /// The type returned by the `sn_routing` message handling methods.
 pub(super) type Result<T, E = Error> = std::result::Result<T, E>;
@@ -25,14 +26,35 @@ pub enum Error {
     Io(#[from] io::Error),
 
     #[error("Transport Error")]
-    TransportError(#[from] TransportError<std::io::Error>),
+    TransportError(#[from] TransportError<io::Error>),
 
     #[error("Dial Error")]
     DialError(#[from] DialError),
 
+    #[error("Peer {0:?} is blocked for misbehaving")]
+    PeerBlocked(PeerId),
+
+    #[error("Peer {0:?} did not respond in time")]
+    RequestTimeout(PeerId),
+
+    #[error("Peer {peer:?} does not hold record {key:?}")]
+    RecordNotHeld { peer: PeerId, key: XorName },
+
+    #[error("Peer {peer:?} returned a record other than {key:?}")]
+    InvalidRecord { peer: PeerId, key: XorName },
+
+    #[error("Response from {peer:?} is not signed by it: {reason}")]
+    InvalidResponse { peer: PeerId, reason: String },
+
     #[error("Outbound Error")]
     OutboundError(#[from] OutboundFailure),
 
+    #[error("No known peers to bootstrap from")]
+    NoKnownPeers(#[from] kad::NoKnownPeers),
+
+    #[error("Kademlia bootstrap timed out")]
+    BootstrapTimeout,
+
     #[error("Kademlia Store error: {0}")]
     KademliaStoreError(#[from] kad::store::Error),
 
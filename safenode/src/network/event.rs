@@ -8,36 +8,59 @@
 
 use super::{
     error::{Error, Result},
-    msg::MsgCodec,
-    NetworkSwarmLoop, Request, Response,
+    identify_protocol_version, is_client_agent,
+    msg::{MsgCodec, MsgHeader},
+    reputation::Misbehaviour,
+    SwarmDriver,
 };
-use futures::{channel::oneshot, SinkExt};
+use crate::protocol::messages::{Event, Request, SignedResponse};
+use futures::channel::oneshot;
 use libp2p::{
-    kad::{store::MemoryStore, GetProvidersOk, Kademlia, KademliaEvent, QueryResult},
-    mdns,
+    core::ConnectedPoint,
+    identify,
+    kad::{
+        store::MemoryStore, GetClosestPeersError, GetClosestPeersOk, GetProvidersOk,
+        InboundRequest, Kademlia, KademliaEvent, QueryResult,
+    },
     multiaddr::Protocol,
-    request_response::{self, ResponseChannel},
-    swarm::{NetworkBehaviour, SwarmEvent},
+    relay,
+    request_response::{self, RequestId, ResponseChannel},
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent},
+    PeerId, Swarm,
 };
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+
+#[cfg(feature = "node")]
+use libp2p::mdns;
+
+// Local discovery is only done by nodes, clients connect to the peers they are given.
+#[cfg(feature = "node")]
+pub(super) type Mdns = mdns::async_io::Behaviour;
+#[cfg(not(feature = "node"))]
+pub(super) type Mdns = libp2p::swarm::dummy::Behaviour;
 
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "NodeEvent")]
 pub(super) struct NodeBehaviour {
     pub(super) request_response: request_response::Behaviour<MsgCodec>,
     pub(super) kademlia: Kademlia<MemoryStore>,
-    pub(super) mdns: mdns::async_io::Behaviour,
+    pub(super) mdns: Toggle<Mdns>,
+    pub(super) identify: identify::Behaviour,
+    pub(super) relay: Toggle<relay::Behaviour>,
 }
 
 #[derive(Debug)]
 pub(super) enum NodeEvent {
-    RequestResponse(request_response::Event<Request, Response>),
+    RequestResponse(request_response::Event<(MsgHeader, Request), SignedResponse>),
     Kademlia(KademliaEvent),
+    #[cfg(feature = "node")]
     Mdns(Box<mdns::Event>),
+    Identify(Box<identify::Event>),
+    Relay(Box<relay::Event>),
 }
 
-impl From<request_response::Event<Request, Response>> for NodeEvent {
-    fn from(event: request_response::Event<Request, Response>) -> Self {
+impl From<request_response::Event<(MsgHeader, Request), SignedResponse>> for NodeEvent {
+    fn from(event: request_response::Event<(MsgHeader, Request), SignedResponse>) -> Self {
         NodeEvent::RequestResponse(event)
     }
 }
@@ -48,95 +71,250 @@ impl From<KademliaEvent> for NodeEvent {
     }
 }
 
+#[cfg(feature = "node")]
 impl From<mdns::Event> for NodeEvent {
     fn from(event: mdns::Event) -> Self {
         NodeEvent::Mdns(Box::new(event))
     }
 }
 
+#[cfg(not(feature = "node"))]
+impl From<void::Void> for NodeEvent {
+    fn from(event: void::Void) -> Self {
+        void::unreachable(event)
+    }
+}
+
+impl From<identify::Event> for NodeEvent {
+    fn from(event: identify::Event) -> Self {
+        NodeEvent::Identify(Box::new(event))
+    }
+}
+
+impl From<relay::Event> for NodeEvent {
+    fn from(event: relay::Event) -> Self {
+        NodeEvent::Relay(Box::new(event))
+    }
+}
+
 #[derive(Debug)]
 /// Events forwarded by the underlying Network; to be used by the upper layers
 pub enum NetworkEvent {
     /// Incoming `Request` from a peer
     RequestReceived {
+        /// The peer the request came from
+        peer: PeerId,
+        /// The id of the request, unique to the connection it came through
+        request_id: RequestId,
+        /// The metadata sent along with the request
+        header: MsgHeader,
         /// Request
         req: Request,
         /// The channel to send the `Response` through
-        channel: ResponseChannel<Response>,
+        channel: ResponseChannel<SignedResponse>,
+    },
+    /// An `Event` pushed by a peer we subscribed to a topic of
+    EventReceived {
+        /// The peer that pushed the event
+        peer: PeerId,
+        /// The event
+        event: Event,
     },
     /// Emmited when we discover a peer.
     /// might/might not be successfully added to the DHT; `RoutingUpdate` is private/no debug impl
     PeerDiscovered,
+    /// Emitted when we get connected to a peer, over its first connection.
+    PeerConnected {
+        /// The peer
+        peer_id: PeerId,
+        /// Whether we dialed the peer or it dialed us, and at which address
+        endpoint: ConnectedPoint,
+    },
+    /// Emitted when the last connection to a peer is closed.
+    PeerDisconnected {
+        /// The peer
+        peer_id: PeerId,
+        /// Whether we dialed the peer or it dialed us, and at which address, for the last
+        /// connection
+        endpoint: ConnectedPoint,
+    },
+    /// Emitted when a peer runs a protocol version incompatible with ours. It is
+    /// disconnected and not added to the DHT.
+    IncompatiblePeer {
+        /// The peer
+        peer_id: PeerId,
+        /// The protocol version the peer advertised
+        protocol_version: String,
+    },
 }
 
-impl NetworkSwarmLoop {
+impl SwarmDriver<Swarm<NodeBehaviour>> {
     // Handle `SwarmEvents`
-    pub(super) async fn handle_event<EventError: std::error::Error>(
+    pub(super) fn handle_event<EventError: std::error::Error>(
         &mut self,
         event: SwarmEvent<NodeEvent, EventError>,
     ) -> Result<()> {
         match event {
             // handle RequestResponse events
             SwarmEvent::Behaviour(NodeEvent::RequestResponse(event)) => {
-                if let Err(e) = self.handle_msg(event).await {
+                if let Err(e) = self.handle_msg(event) {
                     warn!("RequestResponseError: {e:?}");
                 }
             }
             // handle Kademlia events
-            SwarmEvent::Behaviour(NodeEvent::Kademlia(event)) => match event {
-                KademliaEvent::OutboundQueryProgressed {
-                    id,
-                    result: QueryResult::StartProviding(_),
-                    ..
-                } => {
-                    let sender: oneshot::Sender<Result<()>> = self
-                        .pending_start_providing
-                        .remove(&id)
-                        .ok_or(Error::Other(
-                            "Completed query to be previously pending.".to_string(),
-                        ))?;
-                    let _ = sender.send(Ok(()));
+            SwarmEvent::Behaviour(NodeEvent::Kademlia(event)) => {
+                // The queries end with their last step, making room for the queued ones.
+                if let KademliaEvent::OutboundQueryProgressed { id, step, .. } = &event {
+                    if step.last {
+                        self.query_ended(*id);
+                    }
                 }
-                KademliaEvent::OutboundQueryProgressed {
-                    id,
-                    result:
-                        QueryResult::GetProviders(Ok(GetProvidersOk::FoundProviders {
-                            providers, ..
-                        })),
-                    ..
-                } => {
-                    if let Some(sender) = self.pending_get_providers.remove(&id) {
-                        sender
-                            .send(providers)
-                            .map_err(|_| Error::Other("Receiver not to be dropped".to_string()))?;
+                match event {
+                    KademliaEvent::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::Bootstrap(result),
+                        step,
+                        ..
+                    } => {
+                        // Each of the buckets refreshed is a step of the bootstrap.
+                        if let Err(err) = &result {
+                            warn!("Bootstrap step {} failed with {err:?}", step.count);
+                        }
+                        if step.last {
+                            if let Some(sender) = self.pending_bootstrap.remove(&id) {
+                                let _ = sender
+                                    .send(result.map(|_| ()).map_err(|_| Error::BootstrapTimeout));
+                            }
+                        }
+                    }
+                    KademliaEvent::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::StartProviding(_),
+                        ..
+                    } => {
+                        let sender: oneshot::Sender<Result<()>> = self
+                            .pending_start_providing
+                            .remove(&id)
+                            .ok_or(Error::Other(
+                                "Completed query to be previously pending.".to_string(),
+                            ))?;
+                        let _ = sender.send(Ok(()));
+                    }
+                    KademliaEvent::OutboundQueryProgressed {
+                        id,
+                        result:
+                            QueryResult::GetProviders(Ok(GetProvidersOk::FoundProviders {
+                                providers,
+                                ..
+                            })),
+                        ..
+                    } => {
+                        if let Some(xor_name) = self.pending_get_providers.remove(&id) {
+                            self.providers_subscribers.notify(&xor_name, providers);
 
-                        // Finish the query. We are only interested in the first result.
-                        self.swarm
-                            .behaviour_mut()
-                            .kademlia
-                            .query_mut(&id)
-                            .ok_or(Error::Other("Query should exist".to_string()))?
-                            .finish();
+                            // Finish the query. We are only interested in the first result.
+                            self.swarm
+                                .behaviour_mut()
+                                .kademlia
+                                .query_mut(&id)
+                                .ok_or(Error::Other("Query should exist".to_string()))?
+                                .finish();
+                        }
                     }
+                    KademliaEvent::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetClosestPeers(Ok(GetClosestPeersOk { peers, .. })),
+                        step,
+                        ..
+                    } if step.last => {
+                        if let Some(xor_name) = self.pending_get_closest_peers.remove(&id) {
+                            // Data is not sent to the peers failing too often.
+                            let peers = self.functional(peers);
+                            let peers = self.with_addrs(peers);
+                            self.closest_peers_subscribers.notify(&xor_name, peers);
+                        } else {
+                            let _ = self.batched_lookup_ended(&id, peers);
+                        }
+                    }
+                    KademliaEvent::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetClosestPeers(Err(err)),
+                        ..
+                    } => {
+                        warn!("GetClosestPeers query {id:?} failed with {err:?}");
+                        // Fall back to whatever peers the query did find before timing out.
+                        let GetClosestPeersError::Timeout { peers, .. } = err;
+                        if let Some(xor_name) = self.pending_get_closest_peers.remove(&id) {
+                            let peers = self.functional(peers);
+                            let peers = self.with_addrs(peers);
+                            self.closest_peers_subscribers.notify(&xor_name, peers);
+                        } else {
+                            let _ = self.batched_lookup_ended(&id, peers);
+                        }
+                    }
+                    KademliaEvent::InboundRequest {
+                        request:
+                            InboundRequest::PutRecord {
+                                source,
+                                record: Some(record),
+                                ..
+                            },
+                    } => self.put_inbound_record(source, record),
+                    _ => {}
                 }
-                _ => {}
-            },
+            }
+            #[cfg(feature = "node")]
             SwarmEvent::Behaviour(NodeEvent::Mdns(mdns_event)) => match *mdns_event {
                 mdns::Event::Discovered(list) => {
+                    // The peers are only added to the DHT once identified, see below.
                     for (peer_id, multiaddr) in list {
                         info!("Node discovered: {multiaddr:?}");
-                        let _routing_update = self
-                            .swarm
-                            .behaviour_mut()
-                            .kademlia
-                            .add_address(&peer_id, multiaddr);
+                        self.dial_queue.push(peer_id, multiaddr);
                     }
-                    self.event_sender.send(NetworkEvent::PeerDiscovered).await?;
+                    self.dial_next();
                 }
                 mdns::Event::Expired(_) => {
                     info!("mdns peer expired");
                 }
             },
+            SwarmEvent::Behaviour(NodeEvent::Identify(identify_event)) => {
+                if let identify::Event::Received { peer_id, info } = *identify_event {
+                    if info.protocol_version != identify_protocol_version() {
+                        warn!(
+                            "Rejecting peer {peer_id:?} running an incompatible version: {} ({})",
+                            info.protocol_version, info.agent_version
+                        );
+                        let _ = self.swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+                        let _ = self.swarm.disconnect_peer_id(peer_id);
+                        self.penalise(peer_id, Misbehaviour::ProtocolError);
+                        self.send_event(NetworkEvent::IncompatiblePeer {
+                            peer_id,
+                            protocol_version: info.protocol_version,
+                        })?;
+                    } else if is_client_agent(&info.agent_version) {
+                        debug!("Not adding client {peer_id:?} to the routing table");
+                    } else if self.reputations.is_blocked(&peer_id) {
+                        info!("Not adding blocked peer {peer_id:?} to the routing table");
+                    } else if self.is_dysfunctional(&peer_id) {
+                        info!("Not adding dysfunctional peer {peer_id:?} to the routing table");
+                    } else {
+                        // Kademlia dials the addresses in the order they were added.
+                        let mut listen_addrs = info.listen_addrs;
+                        self.dial_preference.rank(&mut listen_addrs);
+                        for multiaddr in listen_addrs {
+                            let _routing_update = self
+                                .swarm
+                                .behaviour_mut()
+                                .kademlia
+                                .add_address(&peer_id, multiaddr);
+                        }
+                        self.send_event(NetworkEvent::PeerDiscovered)?;
+                    }
+                }
+            }
+            SwarmEvent::Behaviour(NodeEvent::Relay(relay_event)) => {
+                self.relay.on_event(&relay_event);
+            }
             SwarmEvent::NewListenAddr { address, .. } => {
                 let local_peer_id = *self.swarm.local_peer_id();
                 info!(
@@ -144,27 +322,69 @@ impl NetworkSwarmLoop {
                     address.with(Protocol::P2p(local_peer_id.into()))
                 );
             }
+            SwarmEvent::ExpiredListenAddr { address, .. } => {
+                info!("Local node is no longer listening on {address:?}");
+            }
+            SwarmEvent::ListenerClosed {
+                addresses, reason, ..
+            } => {
+                warn!("Listener on {addresses:?} closed: {reason:?}");
+            }
+            SwarmEvent::ListenerError { error, .. } => {
+                warn!("Listener error: {error}");
+            }
             SwarmEvent::IncomingConnection { .. } => {}
             SwarmEvent::ConnectionEstablished {
-                peer_id, endpoint, ..
+                peer_id,
+                endpoint,
+                num_established,
+                established_in,
+                ..
             } => {
-                if endpoint.is_dialer() {
-                    info!("Connected with {peer_id:?}");
+                self.connections
+                    .established(num_established.get() == 1, established_in);
+                // Connected either way, there is no need to dial the peer anymore.
+                self.dial_queue.connected(&peer_id);
+                self.idle_connections.touch(peer_id);
+                self.liveness.succeeded(peer_id);
+                if self.reputations.is_blocked(&peer_id) {
+                    info!("Disconnecting from blocked peer {peer_id:?}");
+                    let _ = self.swarm.disconnect_peer_id(peer_id);
+                } else if let ConnectedPoint::Dialer { address, .. } = &endpoint {
+                    info!("Connected with {peer_id:?} at {address:?}");
+                    self.dial_preference.record_success(address);
                     if let Some(sender) = self.pending_dial.remove(&peer_id) {
                         let _ = sender.send(Ok(()));
                     }
                 }
+                self.dial_next();
+                if num_established.get() == 1 {
+                    self.send_event(NetworkEvent::PeerConnected { peer_id, endpoint })?;
+                }
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                endpoint,
+                num_established,
+                ..
+            } => {
+                self.connections.closed(num_established == 0);
+                if num_established == 0 {
+                    self.send_event(NetworkEvent::PeerDisconnected { peer_id, endpoint })?;
+                }
             }
-            SwarmEvent::ConnectionClosed { .. } => {}
             SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+                self.connections.dial_failed(&error);
                 if let Some(peer_id) = peer_id {
-                    if let Some(sender) = self.pending_dial.remove(&peer_id) {
-                        let _ = sender.send(Err(error.into()));
-                    }
+                    self.dial_failed(peer_id, error.into());
+                    self.dial_next();
                 }
             }
-            SwarmEvent::IncomingConnectionError { .. } => {}
-            SwarmEvent::Dialing(peer_id) => info!("Dialing {peer_id}"),
+            SwarmEvent::IncomingConnectionError { .. } => self.connections.incoming_failed(),
+            SwarmEvent::Dialing(peer_id) => {
+                info!("Dialing {peer_id}");
+                self.connections.dialing();
+            }
             e => panic!("{e:?}"),
         }
         Ok(())
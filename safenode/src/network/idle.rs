@@ -0,0 +1,153 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{swarm::SwarmOps, SwarmDriver};
+use libp2p::PeerId;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+/// How long a connection is left unused before it is pruned, unless configured otherwise.
+pub const DEFAULT_IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long the protocols keep an unused connection open, unless configured otherwise.
+/// This is how long the connections to our close group, which are not pruned, stay open
+/// without being used.
+pub const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(60 * 60);
+/// How often idle connections are pruned.
+pub(crate) const PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When each peer was last exchanged msgs with, for the connections that have been left
+/// unused for too long to be pruned.
+#[derive(Debug)]
+pub(crate) struct IdleConnections {
+    timeout: Duration,
+    last_used: HashMap<PeerId, Instant>,
+}
+
+impl IdleConnections {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            last_used: HashMap::new(),
+        }
+    }
+
+    /// Records that the connection to the peer is in use.
+    pub(crate) fn touch(&mut self, peer_id: PeerId) {
+        let _ = self.last_used.insert(peer_id, Instant::now());
+    }
+
+    /// Takes the connected peers whose connection has been unused for longer than the
+    /// timeout, other than the ones to keep.
+    pub(crate) fn take_idle(
+        &mut self,
+        connected: &[PeerId],
+        keep: &HashSet<PeerId>,
+        now: Instant,
+    ) -> Vec<PeerId> {
+        // Peers we are no longer connected to do not need tracking anymore.
+        let connected_set: HashSet<_> = connected.iter().collect();
+        self.last_used
+            .retain(|peer_id, _| connected_set.contains(peer_id));
+
+        let idle: Vec<_> = connected
+            .iter()
+            .filter(|peer_id| !keep.contains(peer_id))
+            .filter(|peer_id| match self.last_used.get(peer_id) {
+                Some(last_used) => now.saturating_duration_since(*last_used) > self.timeout,
+                // Connected before the pruning started tracking it.
+                None => false,
+            })
+            .copied()
+            .collect();
+        for peer_id in &idle {
+            let _ = self.last_used.remove(peer_id);
+        }
+        idle
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Closes the connections left unused for longer than the idle timeout, apart from the
+    /// ones to our close group.
+    pub(crate) fn prune_idle_connections(&mut self) {
+        let connected = self.swarm.connected_peers();
+        let close_group: HashSet<_> = self.swarm.close_group().into_iter().collect();
+        let idle = self
+            .idle_connections
+            .take_idle(&connected, &close_group, Instant::now());
+        // Peers connected without having been used yet start being tracked from now.
+        for peer_id in &connected {
+            if !idle.contains(peer_id) && !self.idle_connections.last_used.contains_key(peer_id) {
+                self.idle_connections.touch(*peer_id);
+            }
+        }
+        for peer_id in idle {
+            debug!("Closing the idle connection to {peer_id:?}");
+            self.swarm.disconnect(peer_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{swarm::MockSwarm, SwarmConfig, SwarmDriver};
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn only_connections_unused_past_the_timeout_are_idle() {
+        let mut idle = IdleConnections::new(Duration::from_secs(60));
+        let [first, second, untracked] = [PeerId::random(), PeerId::random(), PeerId::random()];
+        let now = Instant::now();
+        idle.touch(first);
+        idle.touch(second);
+
+        let connected = [first, second, untracked];
+        assert!(idle
+            .take_idle(&connected, &HashSet::new(), now + Duration::from_secs(30))
+            .is_empty());
+        let later = now + Duration::from_secs(120);
+        assert_eq!(
+            idle.take_idle(&connected, &HashSet::new(), later),
+            vec![first, second]
+        );
+        // The pruned peers are no longer tracked.
+        assert!(idle
+            .take_idle(&connected, &HashSet::new(), later)
+            .is_empty());
+    }
+
+    #[test]
+    fn the_close_group_is_kept_connected() {
+        let close_peer = PeerId::random();
+        let other_peer = PeerId::random();
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm {
+                connected_peers: vec![close_peer, other_peer],
+                close_group: vec![close_peer],
+                ..Default::default()
+            },
+            Keypair::generate_ed25519(),
+            &SwarmConfig {
+                idle_connection_timeout: Duration::ZERO,
+                ..Default::default()
+            },
+        );
+
+        driver.prune_idle_connections();
+        // The connections are only tracked from the first pruning on.
+        assert!(driver.swarm.disconnected.is_empty());
+        std::thread::sleep(Duration::from_millis(10));
+        driver.prune_idle_connections();
+
+        assert_eq!(driver.swarm.disconnected, vec![other_peer]);
+    }
+}
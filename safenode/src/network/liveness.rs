@@ -0,0 +1,139 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{dysfunction::Failure, swarm::SwarmOps, SwarmDriver};
+use libp2p::PeerId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// The number of failed dials in a row after which a peer is deemed gone, and removed from
+/// the routing table, unless it was connected to within the [`STALE_PEER_AGE`].
+pub const STALE_PEER_FAILURES: u32 = 3;
+/// How long since a peer was last connected to before its failed dials can get it removed from
+/// the routing table, for the peers only briefly unreachable to be kept.
+pub const STALE_PEER_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// When the peers were last connected to, and how many times in a row dialing them failed
+/// since, for the peers that vanished not to linger in the routing table.
+#[derive(Debug, Default)]
+pub(crate) struct Liveness {
+    peers: HashMap<PeerId, PeerLiveness>,
+}
+
+#[derive(Debug, Default)]
+struct PeerLiveness {
+    last_success: Option<Instant>,
+    failures: u32,
+}
+
+impl Liveness {
+    /// Records that the peer was connected to.
+    pub(crate) fn succeeded(&mut self, peer_id: PeerId) {
+        self.succeeded_at(peer_id, Instant::now())
+    }
+
+    /// Records that dialing the peer failed, returning whether the peer is stale by now.
+    pub(crate) fn failed(&mut self, peer_id: PeerId) -> bool {
+        self.failed_at(peer_id, Instant::now())
+    }
+
+    /// Forgets about the peer, e.g. once removed from the routing table.
+    pub(crate) fn forget(&mut self, peer_id: &PeerId) {
+        let _ = self.peers.remove(peer_id);
+    }
+
+    fn succeeded_at(&mut self, peer_id: PeerId, now: Instant) {
+        let peer = self.peers.entry(peer_id).or_default();
+        peer.last_success = Some(now);
+        peer.failures = 0;
+    }
+
+    fn failed_at(&mut self, peer_id: PeerId, now: Instant) -> bool {
+        let peer = self.peers.entry(peer_id).or_default();
+        peer.failures += 1;
+        debug!(
+            "Dialing {peer_id:?} failed {} times in a row",
+            peer.failures
+        );
+        let recently_connected = peer
+            .last_success
+            .is_some_and(|at| now.saturating_duration_since(at) < STALE_PEER_AGE);
+        peer.failures >= STALE_PEER_FAILURES && !recently_connected
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Counts the failed dial against the peer, which is left out of the peers data is sent to
+    /// as its failures add up, and removed from the routing table once stale.
+    pub(crate) fn unreachable(&mut self, peer_id: PeerId) {
+        self.record_failure(peer_id, Failure::Unreachable);
+        if self.liveness.failed(peer_id) {
+            warn!("Removing stale {peer_id:?} from the routing table");
+            self.swarm.remove_from_routing_table(&peer_id);
+            self.liveness.forget(&peer_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{swarm::MockSwarm, SwarmConfig};
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn peers_are_stale_after_failing_in_a_row() {
+        let mut liveness = Liveness::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        assert!(!liveness.failed_at(peer_id, now));
+        assert!(!liveness.failed_at(peer_id, now));
+        // A success starts the count afresh.
+        liveness.succeeded_at(peer_id, now);
+        let later = now + STALE_PEER_AGE;
+        assert!(!liveness.failed_at(peer_id, later));
+        assert!(!liveness.failed_at(peer_id, later));
+        assert!(liveness.failed_at(peer_id, later));
+    }
+
+    #[test]
+    fn recently_connected_peers_are_not_stale() {
+        let mut liveness = Liveness::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        liveness.succeeded_at(peer_id, now);
+        for _ in 0..STALE_PEER_FAILURES {
+            assert!(!liveness.failed_at(peer_id, now + Duration::from_secs(1)));
+        }
+        assert!(liveness.failed_at(peer_id, now + STALE_PEER_AGE));
+    }
+
+    #[test]
+    fn stale_peers_are_removed_from_the_routing_table() {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let peer_id = PeerId::random();
+
+        for _ in 0..STALE_PEER_FAILURES - 1 {
+            driver.unreachable(peer_id);
+        }
+        assert!(driver.swarm.removed_from_routing_table.is_empty());
+
+        driver.unreachable(peer_id);
+        assert_eq!(driver.swarm.removed_from_routing_table, vec![peer_id]);
+        assert!(driver.liveness.peers.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    protocol::messages::{QueryResponse, Response, SignedResponse},
+    storage::chunks::Chunk,
+    test_utils::test_rng,
+};
+use bytes::Bytes;
+use libp2p::identity::Keypair;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+/// The faults a node injects into its traffic, for the resilience of the network to
+/// adversarial peers to be tested: of quorum reads, retries and reputation scoring.
+///
+/// The rates are percentages of the msgs sent. The faults are drawn from a generator seeded
+/// with `seed`, for a test sending the same msgs in the same order to see the same faults.
+#[derive(Clone, Debug)]
+pub struct MaliceConfig {
+    /// The percentage of the msgs sent which are dropped, failing as if the stream was reset.
+    pub drop_rate: usize,
+    /// The percentage of the msgs sent which are held for `delay` before being sent.
+    pub delay_rate: usize,
+    /// How long the delayed msgs are held for.
+    pub delay: Duration,
+    /// The percentage of the requests sent twice, the response to the second being discarded.
+    pub duplicate_rate: usize,
+    /// The percentage of the msgs sent which have a byte of their encoding flipped.
+    pub corrupt_rate: usize,
+    /// The percentage of the chunks returned by gets swapped for chunks of other content,
+    /// signed as if they were genuine.
+    pub wrong_record_rate: usize,
+    /// The seed of the generator the faults are drawn from, derived from `SAFE_TEST_SEED` by
+    /// default, see [`test_rng`].
+    pub seed: u64,
+}
+
+impl Default for MaliceConfig {
+    fn default() -> Self {
+        Self {
+            drop_rate: 0,
+            delay_rate: 0,
+            delay: Duration::ZERO,
+            duplicate_rate: 0,
+            corrupt_rate: 0,
+            wrong_record_rate: 0,
+            seed: default_seed(),
+        }
+    }
+}
+
+// The seed of the faults, derived from the test seed for a failing test to be re-run with
+// the faults it failed with.
+fn default_seed() -> u64 {
+    match test_rng("malice") {
+        Ok(mut rng) => rng.gen(),
+        Err(err) => {
+            warn!("Seeding the faults at random: {err}");
+            rand::random()
+        }
+    }
+}
+
+// The fate of a msg about to be written to the wire.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum WireFault {
+    None,
+    Drop,
+    Delay(Duration),
+    // The index of the byte to flip, modulo the length of the encoding.
+    Corrupt(usize),
+}
+
+// Draws the faults to inject, as configured.
+pub(super) struct Malice {
+    config: MaliceConfig,
+    rng: Mutex<StdRng>,
+}
+
+impl Malice {
+    pub(super) fn new(config: MaliceConfig) -> Self {
+        warn!("Injecting faults into the traffic of the node: {config:?}");
+        let rng = Mutex::new(StdRng::seed_from_u64(config.seed));
+        Self { config, rng }
+    }
+
+    fn rng(&self) -> MutexGuard<'_, StdRng> {
+        match self.rng.lock() {
+            Ok(rng) => rng,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    // Whether a fault of the given rate strikes the msg at hand.
+    fn strikes(&self, rate: usize) -> bool {
+        rate > 0 && self.rng().gen_range(0..100) < rate
+    }
+
+    // The fault to inject into a msg about to be written. At most one fault strikes a msg,
+    // the most disruptive first.
+    pub(super) fn wire_fault(&self) -> WireFault {
+        if self.strikes(self.config.drop_rate) {
+            WireFault::Drop
+        } else if self.strikes(self.config.corrupt_rate) {
+            WireFault::Corrupt(self.rng().gen())
+        } else if self.strikes(self.config.delay_rate) {
+            WireFault::Delay(self.config.delay)
+        } else {
+            WireFault::None
+        }
+    }
+
+    // Whether to send the request about to be sent twice.
+    pub(super) fn duplicates_request(&self) -> bool {
+        self.strikes(self.config.duplicate_rate)
+    }
+
+    // Swaps the chunk returned by a get for one of other content, re-signing the response
+    // for it to pass as genuine. Other responses are left as they are.
+    pub(super) fn tamper_response(
+        &self,
+        resp: SignedResponse,
+        keypair: &Keypair,
+    ) -> SignedResponse {
+        let chunk = match &resp.response {
+            Response::Query(QueryResponse::GetChunk(Ok(chunk))) => chunk,
+            _ => return resp,
+        };
+        if !self.strikes(self.config.wrong_record_rate) {
+            return resp;
+        }
+        let mut value = chunk.value().to_vec();
+        value.push(0);
+        debug!("Returning a wrong chunk in place of {:?}", chunk.address());
+        let wrong = Response::Query(QueryResponse::GetChunk(Ok(Chunk::new(Bytes::from(value)))));
+        match SignedResponse::new(wrong, keypair) {
+            Ok(wrong) => wrong,
+            Err(err) => {
+                warn!("Failed to sign a wrong chunk: {err}");
+                resp
+            }
+        }
+    }
+}
+
+// Flips the byte at the given index, modulo the length of the msg.
+pub(super) fn corrupt(bytes: &mut [u8], index: usize) {
+    if bytes.is_empty() {
+        return;
+    }
+    let len = bytes.len();
+    bytes[index % len] ^= 0xff;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::{command::SwarmCmd, swarm::MockSwarm, MsgHeader, SwarmConfig, SwarmDriver},
+        protocol::messages::{Query, Request},
+        storage::chunks::ChunkAddress,
+    };
+    use eyre::Result;
+    use futures::channel::oneshot;
+    use libp2p::PeerId;
+    use xor_name::XorName;
+
+    fn faults(config: MaliceConfig) -> Vec<WireFault> {
+        let malice = Malice::new(config);
+        (0..100).map(|_| malice.wire_fault()).collect()
+    }
+
+    #[test]
+    fn no_faults_are_injected_at_zero_rates() {
+        let faults = faults(MaliceConfig::default());
+        assert!(faults.iter().all(|fault| *fault == WireFault::None));
+    }
+
+    #[test]
+    fn all_msgs_are_struck_at_full_rate() {
+        let faults = faults(MaliceConfig {
+            drop_rate: 100,
+            ..Default::default()
+        });
+        assert!(faults.iter().all(|fault| *fault == WireFault::Drop));
+    }
+
+    #[test]
+    fn faults_are_the_same_for_the_same_seed() {
+        let config = MaliceConfig {
+            drop_rate: 30,
+            delay_rate: 30,
+            corrupt_rate: 30,
+            seed: 7,
+            ..Default::default()
+        };
+        assert_eq!(faults(config.clone()), faults(config));
+    }
+
+    #[test]
+    fn returned_chunks_are_swapped_for_signed_wrong_ones() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let malice = Malice::new(MaliceConfig {
+            wrong_record_rate: 100,
+            ..Default::default()
+        });
+        let chunk = Chunk::new(Bytes::from_static(b"genuine"));
+        let resp = SignedResponse::new(
+            Response::Query(QueryResponse::GetChunk(Ok(chunk.clone()))),
+            &keypair,
+        )?;
+
+        let tampered = malice.tamper_response(resp, &keypair);
+
+        assert_eq!(tampered.verify()?, keypair.public().to_peer_id());
+        match tampered.response {
+            Response::Query(QueryResponse::GetChunk(Ok(wrong))) => {
+                assert_ne!(wrong.address(), chunk.address());
+            }
+            other => panic!("Unexpected response: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn requests_are_duplicated() -> Result<()> {
+        let config = SwarmConfig {
+            malice: Some(MaliceConfig {
+                duplicate_rate: 100,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (_network, _events, mut driver) =
+            SwarmDriver::new(MockSwarm::default(), Keypair::generate_ed25519(), &config);
+        let peer = PeerId::random();
+        let req = Request::Query(Query::GetChunk(ChunkAddress(XorName::from_content(
+            b"chunk",
+        ))));
+        let (sender, _receiver) = oneshot::channel();
+
+        driver.handle_command(SwarmCmd::SendRequest {
+            header: MsgHeader::default(),
+            req: req.clone(),
+            peer,
+            sender,
+        })?;
+
+        assert_eq!(
+            driver.swarm.requests,
+            vec![(peer, req.clone()), (peer, req)]
+        );
+        assert_eq!(driver.pending_requests.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn corruption_flips_a_byte() {
+        let mut bytes = vec![0u8; 4];
+        corrupt(&mut bytes, 6);
+        assert_eq!(bytes, vec![0, 0, 0xff, 0]);
+    }
+}
@@ -6,55 +6,284 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod addrs;
+mod batch;
+mod close_group;
 mod command;
+mod connections;
+mod contacts;
+mod dial;
+mod dysfunction;
 mod error;
 mod event;
+mod idle;
+mod liveness;
+#[cfg(feature = "malice")]
+mod malice;
 mod msg;
+mod muxer;
+mod priority;
+mod query;
+mod queue;
+mod record;
+mod relay;
+mod reputation;
+mod search;
+mod swarm;
+#[cfg(feature = "test-utils")]
+mod tap;
+#[cfg(feature = "upnp")]
+mod upnp;
+mod validator;
 
 pub use self::{
+    connections::{ConnectionStats, DialFailures},
+    contacts::{
+        decode_public_key, ContactsError, NetworkContacts, CONTACTS_DIAL_COUNT,
+        DEFAULT_CONTACTS_REFRESH,
+    },
+    dial::DEFAULT_MAX_CONCURRENT_DIALS,
+    dysfunction::{
+        Failure, Health, DYSFUNCTIONAL_THRESHOLD, DYSFUNCTION_WINDOW, SLOW_RESPONSE,
+        SUSPECT_THRESHOLD,
+    },
+    error::Error,
     event::NetworkEvent,
-    msg::{Request, Response},
+    idle::{DEFAULT_IDLE_CONNECTION_TIMEOUT, DEFAULT_KEEP_ALIVE},
+    liveness::{STALE_PEER_AGE, STALE_PEER_FAILURES},
+    msg::{decode_request, decode_response, MsgHeader},
+    muxer::{MuxerConfig, DEFAULT_MUXER_MAX_BUFFER_SIZE, DEFAULT_MUXER_RECEIVE_WINDOW},
+    priority::CmdPriority,
+    query::DEFAULT_MAX_CONCURRENT_QUERIES,
+    queue::{QueueStats, DEFAULT_CMD_QUEUE_SIZE, DEFAULT_EVENT_QUEUE_SIZE},
+    relay::{
+        RelayConfig, RelayStats, DEFAULT_RELAY_MAX_CIRCUITS, DEFAULT_RELAY_MAX_CIRCUIT_BYTES,
+        DEFAULT_RELAY_MAX_CIRCUIT_DURATION, DEFAULT_RELAY_MAX_RESERVATIONS,
+    },
+    reputation::{
+        Misbehaviour, PeerReputation, BLOCK_DURATION, BLOCK_THRESHOLD, PENALTY_HALF_LIFE,
+    },
+    search::{SearchConfig, DEFAULT_SEARCH_ATTEMPTS, DEFAULT_SEARCH_TIMEOUT},
+    validator::RecordValidator,
 };
 
+#[cfg(feature = "node")]
+pub use self::contacts::fetch_contacts;
+#[cfg(feature = "malice")]
+pub use self::malice::MaliceConfig;
+#[cfg(feature = "test-utils")]
+pub use self::tap::{MsgDirection, TappedMsg, TappedPayload, TAP_CAPACITY};
+#[cfg(feature = "upnp")]
+pub use self::upnp::{UpnpError, UPNP_LEASE};
+
+pub(crate) use self::close_group::close_group;
+#[cfg(feature = "malice")]
+use self::malice::Malice;
+#[cfg(feature = "test-utils")]
+use self::tap::MsgTap;
 use self::{
+    addrs::DialPreference,
+    batch::DataBatches,
     command::SwarmCmd,
+    connections::ConnectionCounters,
+    dial::DialQueue,
+    dysfunction::Dysfunction,
     error::Result,
-    event::NodeBehaviour,
+    event::{Mdns, NodeBehaviour},
+    idle::{IdleConnections, PRUNE_INTERVAL},
+    liveness::Liveness,
     msg::{MsgCodec, MsgProtocol},
+    priority::{cmd_queues, CmdSenders, PriorityCmds},
+    query::{QueryQueue, Subscribers},
+    queue::{event_channel_size, QueueCounters},
+    record::PendingRequest,
+    relay::RelayCounters,
+    reputation::Reputations,
+    swarm::SwarmOps,
+};
+use crate::protocol::{
+    error::Result as ProtocolResult,
+    messages::{Cmd, Request, Response, SignedCmd, SignedResponse},
 };
 use futures::{
     channel::{mpsc, oneshot},
     prelude::*,
 };
 use libp2p::{
-    core::muxing::StreamMuxerBox,
-    identity,
-    kad::{record::store::MemoryStore, Kademlia, KademliaConfig, QueryId},
-    mdns,
-    request_response::{self, ProtocolSupport, RequestId, ResponseChannel},
+    core::{muxing::StreamMuxerBox, transport::Boxed, upgrade},
+    identify, identity,
+    kad::{
+        record::store::{MemoryStore, MemoryStoreConfig},
+        Kademlia, KademliaBucketInserts, KademliaConfig, KademliaStoreInserts,
+    },
+    noise,
+    pnet::PreSharedKey,
+    request_response::{self, ProtocolSupport, ResponseChannel},
     swarm::{Swarm, SwarmBuilder},
     Multiaddr, PeerId, Transport,
 };
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     iter,
+    num::NonZeroUsize,
+    sync::Arc,
     time::Duration,
 };
-use tracing::warn;
+use tracing::{info, trace, warn};
 use xor_name::XorName;
 
+/// The number of peers closest to a piece of data that are responsible for holding it.
+pub const CLOSE_GROUP_SIZE: usize = 8;
+
+/// How long Kademlia queries run for before timing out, unless configured otherwise.
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// The number of peers Kademlia queries are sent to at once by default, which is also the
+/// number of disjoint paths lookups take.
+pub const DEFAULT_QUERY_PARALLELISM: usize = 3;
+/// How long a peer is given to respond to a request by default, before the request fails.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// The max number of streams open at once over a connection by default, each request to a
+/// peer taking one for the time it is in flight.
+pub const DEFAULT_MAX_CONCURRENT_STREAMS: usize = 256;
+
+/// The settings of the network layer.
+#[derive(Clone, Debug)]
+pub struct SwarmConfig {
+    /// How long Kademlia queries run for before timing out.
+    pub query_timeout: Duration,
+    /// The number of peers Kademlia queries are sent to at once.
+    pub query_parallelism: usize,
+    /// Whether lookups take as many disjoint paths as their parallelism, the results of the
+    /// paths being merged, for a lookup to succeed as long as one of its paths is free of
+    /// adversarial peers.
+    pub disjoint_query_paths: bool,
+    /// The key of the private network to join, if any, see [`NetworkSwarmLoop::with_config`].
+    pub network_key: Option<PreSharedKey>,
+    /// The max number of peers dialed at once, the further ones being queued.
+    pub max_concurrent_dials: usize,
+    /// The max number of Kademlia queries run at once, the further ones being queued.
+    pub max_concurrent_queries: usize,
+    /// How long a connection is left unused before it is closed. The connections to our
+    /// close group are not closed for being idle, other than by the `keep_alive`.
+    pub idle_connection_timeout: Duration,
+    /// How long the protocols keep an unused connection open.
+    pub keep_alive: Duration,
+    /// How long a peer is given to respond to a request, before the request fails.
+    pub request_timeout: Duration,
+    /// The max number of streams open at once over a connection, beyond which the requests
+    /// over it wait for the ones in flight to end.
+    pub max_concurrent_streams: usize,
+    /// The flow control of the streams over the connections other than QUIC ones.
+    pub muxer: MuxerConfig,
+    /// How the search for a record is widened when its close group does not return it.
+    pub search: SearchConfig,
+    /// The number of cmds queued for the driver of the swarm at each priority, beyond which
+    /// the upper layers wait for it to catch up, and its reports and dials of contacts are shed.
+    pub cmd_queue_size: usize,
+    /// The number of events queued for the upper layers, beyond which the requests and
+    /// events of peers are shed.
+    pub event_queue_size: usize,
+    /// The keypair the peer id of the node derives from, a random one being created if not set.
+    pub keypair: Option<identity::Keypair>,
+    /// Whether to run as a client, which neither listens for connections nor stores records,
+    /// and which nodes do not add to their routing table, see [`NetworkSwarmLoop::new_client`].
+    pub client_mode: bool,
+    /// The limits of the relaying of the connections of peers behind NATs, for nodes
+    /// volunteering as relays. Nodes do not relay by default.
+    pub relay: Option<RelayConfig>,
+    /// Whether to connect over in-process memory channels rather than sockets, for a network
+    /// of many nodes to run within a single test process, see `test_utils::TestNetwork`.
+    /// Local discovery is off on memory transports.
+    pub memory_transport: bool,
+    /// The faults to inject into the traffic of the node, for adversarial tests.
+    #[cfg(feature = "malice")]
+    pub malice: Option<MaliceConfig>,
+}
+
+impl Default for SwarmConfig {
+    fn default() -> Self {
+        Self {
+            query_timeout: DEFAULT_QUERY_TIMEOUT,
+            query_parallelism: DEFAULT_QUERY_PARALLELISM,
+            disjoint_query_paths: true,
+            network_key: None,
+            max_concurrent_dials: DEFAULT_MAX_CONCURRENT_DIALS,
+            max_concurrent_queries: DEFAULT_MAX_CONCURRENT_QUERIES,
+            idle_connection_timeout: DEFAULT_IDLE_CONNECTION_TIMEOUT,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_concurrent_streams: DEFAULT_MAX_CONCURRENT_STREAMS,
+            muxer: MuxerConfig::default(),
+            search: SearchConfig::default(),
+            cmd_queue_size: DEFAULT_CMD_QUEUE_SIZE,
+            event_queue_size: DEFAULT_EVENT_QUEUE_SIZE,
+            keypair: None,
+            client_mode: false,
+            relay: None,
+            memory_transport: false,
+            #[cfg(feature = "malice")]
+            malice: None,
+        }
+    }
+}
+
+/// The version of the protocol spoken between nodes. To be bumped on every change that
+/// older nodes cannot handle, peers running a different version are not added to the routing table.
+pub const PROTOCOL_VERSION: &str = "17";
+
+// The protocol version advertised through Identify.
+pub(super) fn identify_protocol_version() -> String {
+    format!("safenode/{PROTOCOL_VERSION}")
+}
+
+// The suffix of the agent version advertised by clients, for nodes to tell them apart.
+const CLIENT_AGENT_SUFFIX: &str = "/client";
+
+// Whether the agent version advertised through Identify is the one of a client.
+pub(super) fn is_client_agent(agent_version: &str) -> bool {
+    agent_version.ends_with(CLIENT_AGENT_SUFFIX)
+}
+
 /// The main event loop recieves `SwarmEvents` from the network, `SwarmCmd` from the upper layers and
 /// emmits back `NetworkEvent` to the upper layers.
 /// Also keeps track of the pending queries/requests and their channels. Once we recieve an event
 /// that is the outcome of a previously executed cmd, send a response to them via the stored channel.
 pub struct NetworkSwarmLoop {
-    swarm: Swarm<NodeBehaviour>,
-    cmd_receiver: mpsc::Receiver<SwarmCmd>,
+    driver: SwarmDriver<Swarm<NodeBehaviour>>,
+}
+
+// Carries out the `SwarmCmd`s on the swarm, and keeps track of the pending ones. Generic over
+// the swarm for the handling of the cmds to be tested against a test double.
+pub(crate) struct SwarmDriver<S: SwarmOps> {
+    swarm: S,
+    cmds: PriorityCmds,
     event_sender: mpsc::Sender<NetworkEvent>,
+    event_queue_size: usize,
+    queue: Arc<QueueCounters>,
+    relay: Arc<RelayCounters>,
+    connections: Arc<ConnectionCounters>,
+    #[cfg(feature = "test-utils")]
+    tap: MsgTap,
+    #[cfg(feature = "malice")]
+    malice: Option<Malice>,
     pending_dial: HashMap<PeerId, oneshot::Sender<Result<()>>>,
-    pending_start_providing: HashMap<QueryId, oneshot::Sender<Result<()>>>,
-    pending_get_providers: HashMap<QueryId, oneshot::Sender<HashSet<PeerId>>>,
-    pending_requests: HashMap<RequestId, oneshot::Sender<Result<Response>>>,
+    pending_start_providing: HashMap<S::QueryId, oneshot::Sender<Result<()>>>,
+    pending_bootstrap: HashMap<S::QueryId, oneshot::Sender<Result<()>>>,
+    pending_get_providers: HashMap<S::QueryId, XorName>,
+    pending_get_closest_peers: HashMap<S::QueryId, XorName>,
+    providers_subscribers: Subscribers<HashSet<PeerId>>,
+    closest_peers_subscribers: Subscribers<Vec<(PeerId, Vec<Multiaddr>)>>,
+    data_batches: DataBatches<S::QueryId>,
+    pending_requests: HashMap<S::RequestId, PendingRequest>,
+    dial_preference: DialPreference,
+    dial_queue: DialQueue,
+    query_queue: QueryQueue<S::QueryId>,
+    reputations: Reputations,
+    dysfunction: Dysfunction<S::RequestId>,
+    liveness: Liveness,
+    idle_connections: IdleConnections,
+    record_validator: Option<RecordValidator>,
+    keypair: identity::Keypair,
 }
 
 impl NetworkSwarmLoop {
@@ -66,75 +295,214 @@ impl NetworkSwarmLoop {
     ///
     /// - The `NetworkSwarmLoop` that drives the network.
     pub fn new() -> Result<(Network, impl Stream<Item = NetworkEvent>, NetworkSwarmLoop)> {
-        // Create a random key for ourselves.
-        let keypair = identity::Keypair::generate_ed25519();
+        Self::with_query_timeout(DEFAULT_QUERY_TIMEOUT)
+    }
+
+    /// Creates the network components of a client, as with `new`.
+    ///
+    /// Clients are light: they dial nodes without listening for connections, and do not store
+    /// records. They tell nodes they are clients through Identify, for the nodes not to add
+    /// them to their routing tables, which only hold the nodes serving the DHT.
+    pub fn new_client() -> Result<(Network, impl Stream<Item = NetworkEvent>, NetworkSwarmLoop)> {
+        Self::with_config(SwarmConfig {
+            client_mode: true,
+            ..Default::default()
+        })
+    }
+
+    /// Creates the network components, as with `new`, with Kademlia queries timing out
+    /// after the given duration.
+    pub fn with_query_timeout(
+        query_timeout: Duration,
+    ) -> Result<(Network, impl Stream<Item = NetworkEvent>, NetworkSwarmLoop)> {
+        Self::with_config(SwarmConfig {
+            query_timeout,
+            ..Default::default()
+        })
+    }
+
+    /// Creates the network components, as with `new`, with the given settings.
+    ///
+    /// With a network key, the private network of the key is joined.
+    /// Peers of a private network only accept connections from peers holding the same key.
+    /// They connect over TCP only, as QUIC and WebSocket connections cannot be protected by
+    /// the key, which leaves browser clients out of private networks.
+    pub fn with_config(
+        config: SwarmConfig,
+    ) -> Result<(Network, impl Stream<Item = NetworkEvent>, NetworkSwarmLoop)> {
+        let SwarmConfig {
+            query_timeout,
+            query_parallelism,
+            disjoint_query_paths,
+            network_key,
+            keep_alive,
+            request_timeout,
+            max_concurrent_streams,
+            muxer,
+            keypair,
+            client_mode,
+            relay,
+            memory_transport,
+            ..
+        } = config.clone();
+        // Create a random key for ourselves, unless we were given one to keep our peer id.
+        let keypair = keypair.unwrap_or_else(identity::Keypair::generate_ed25519);
         let local_peer_id = PeerId::from(keypair.public());
 
-        // QUIC configuration
-        let quic_config = libp2p_quic::Config::new(&keypair);
-        let transport = libp2p_quic::async_std::Transport::new(quic_config);
-        let transport = transport
-            .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
-            .boxed();
+        #[cfg(not(target_arch = "wasm32"))]
+        let is_private = network_key.is_some();
+        let transport = if memory_transport {
+            build_memory_transport(&keypair, max_concurrent_streams, muxer)?
+        } else {
+            match network_key {
+                Some(network_key) => {
+                    trace!(
+                        "Joining the private network of key {}",
+                        network_key.fingerprint()
+                    );
+                    build_private_transport(&keypair, network_key, max_concurrent_streams, muxer)?
+                }
+                None => build_transport(&keypair, max_concurrent_streams, muxer)?,
+            }
+        };
         // Create a Kademlia instance and connect to the network address.
         // Create a swarm to manage peers and events.
         let swarm = {
             // Create a Kademlia behaviour.
             let mut cfg = KademliaConfig::default();
-            let _ = cfg.set_query_timeout(Duration::from_secs(5 * 60));
-            let kademlia =
-                Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), cfg);
-            let mdns = mdns::async_io::Behaviour::new(mdns::Config::default(), local_peer_id)?;
+            let _ = cfg.set_query_timeout(query_timeout);
+            // A parallelism of 0 leaves the one of Kademlia.
+            if let Some(parallelism) = NonZeroUsize::new(query_parallelism) {
+                let _ = cfg.set_parallelism(parallelism);
+            }
+            let _ = cfg.disjoint_query_paths(disjoint_query_paths);
+            let _ = cfg.set_connection_idle_timeout(keep_alive);
+            // Peers are only added to the routing table once identified as running a
+            // compatible version.
+            let _ = cfg.set_kbucket_inserts(KademliaBucketInserts::Manual);
+            // Records pushed by peers are handed over to be validated before being stored.
+            let _ = cfg.set_record_filtering(KademliaStoreInserts::FilterBoth);
+            // Clients hold no records, the ones pushed to them being dropped.
+            let store = if client_mode {
+                MemoryStore::with_config(
+                    local_peer_id,
+                    MemoryStoreConfig {
+                        max_records: 0,
+                        max_provided_keys: 0,
+                        ..Default::default()
+                    },
+                )
+            } else {
+                MemoryStore::new(local_peer_id)
+            };
+            let kademlia = Kademlia::with_config(local_peer_id, store, cfg);
+            #[cfg(feature = "node")]
+            let mdns = if memory_transport {
+                None
+            } else {
+                Some(Mdns::new(libp2p::mdns::Config::default(), local_peer_id)?)
+            };
+            #[cfg(not(feature = "node"))]
+            let mdns = Some(Mdns::default());
+            let identify = identify::Behaviour::new(
+                identify::Config::new(identify_protocol_version(), keypair.public())
+                    .with_agent_version(format!(
+                        "safenode/{PROTOCOL_VERSION}/{}{}",
+                        env!("CARGO_PKG_VERSION"),
+                        if client_mode { CLIENT_AGENT_SUFFIX } else { "" }
+                    )),
+            );
+            if let Some(relay) = &relay {
+                info!("Relaying the connections of peers, within {relay:?}");
+            }
+            let relay = relay.map(|relay| {
+                libp2p::relay::Behaviour::new(local_peer_id, relay.to_behaviour_config())
+            });
+            #[cfg(feature = "malice")]
+            let codec = MsgCodec::with_malice(config.malice.clone().map(Malice::new).map(Arc::new));
+            #[cfg(not(feature = "malice"))]
+            let codec = MsgCodec::default();
+            let mut request_response_config = request_response::Config::default();
+            let _ = request_response_config.set_connection_keep_alive(keep_alive);
+            let _ = request_response_config.set_request_timeout(request_timeout);
             let behaviour = NodeBehaviour {
                 request_response: request_response::Behaviour::new(
-                    MsgCodec(),
-                    iter::once((MsgProtocol(), ProtocolSupport::Full)),
-                    Default::default(),
+                    codec,
+                    iter::once((MsgProtocol::new(), ProtocolSupport::Full)),
+                    request_response_config,
                 ),
                 kademlia,
-                mdns,
+                mdns: mdns.into(),
+                identify,
+                relay: relay.into(),
             };
 
+            #[cfg(not(target_arch = "wasm32"))]
             let mut swarm =
                 SwarmBuilder::with_async_std_executor(transport, behaviour, local_peer_id).build();
+            #[cfg(target_arch = "wasm32")]
+            let swarm =
+                SwarmBuilder::with_wasm_executor(transport, behaviour, local_peer_id).build();
 
-            // Listen on all interfaces and whatever port the OS assigns.
-            let addr = "/ip4/0.0.0.0/udp/0/quic-v1"
-                .parse()
-                .expect("Failed to parse the address");
-            let _listener_id = swarm
-                .listen_on(addr)
-                .expect("Failed to listen on the provided address");
+            // Listen on all interfaces of both IP versions and whatever port the OS assigns, over
+            // QUIC for nodes and over WebSocket for browser clients. Browsers cannot listen, only
+            // dial. Private networks are reached over TCP only.
+            // The addresses listened on are advertised to peers through Identify. Clients only
+            // dial.
+            #[cfg(not(target_arch = "wasm32"))]
+            let listen_addrs: &[&str] = if client_mode {
+                &[]
+            } else if memory_transport {
+                &["/memory/0"]
+            } else if is_private {
+                &["/ip4/0.0.0.0/tcp/0", "/ip6/::/tcp/0"]
+            } else {
+                &[
+                    "/ip4/0.0.0.0/udp/0/quic-v1",
+                    "/ip4/0.0.0.0/tcp/0/ws",
+                    "/ip6/::/udp/0/quic-v1",
+                    "/ip6/::/tcp/0/ws",
+                ]
+            };
+            #[cfg(not(target_arch = "wasm32"))]
+            for addr in listen_addrs {
+                let addr: Multiaddr = addr.parse().expect("Failed to parse the address");
+                // Hosts without IPv6 still run on IPv4 alone.
+                if let Err(err) = swarm.listen_on(addr.clone()) {
+                    warn!("Failed to listen on {addr}: {err}");
+                }
+            }
 
             swarm
         };
 
-        let (swarm_cmd_sender, swarm_cmd_receiver) = mpsc::channel(0);
-        let (event_sender, event_receiver) = mpsc::channel(0);
-        let event_loop = Self {
-            swarm,
-            cmd_receiver: swarm_cmd_receiver,
-            event_sender,
-            pending_dial: Default::default(),
-            pending_start_providing: Default::default(),
-            pending_get_providers: Default::default(),
-            pending_requests: Default::default(),
-        };
-
-        Ok((Network { swarm_cmd_sender }, event_receiver, event_loop))
+        let (network, events, driver) = SwarmDriver::new(swarm, keypair, &config);
+        Ok((network, events, Self { driver }))
     }
 
     /// Drive the network
-    pub async fn run(mut self) {
+    pub async fn run(self) {
+        self.driver.run().await
+    }
+}
+
+impl SwarmDriver<Swarm<NodeBehaviour>> {
+    async fn run(mut self) {
+        let mut prune_tick = Box::pin(async_std::task::sleep(PRUNE_INTERVAL).fuse());
         loop {
             futures::select! {
+                () = prune_tick => {
+                    self.prune_idle_connections();
+                    prune_tick = Box::pin(async_std::task::sleep(PRUNE_INTERVAL).fuse());
+                },
                 event = self.swarm.next() => {
-                    if let Err(err) = self.handle_event(event.expect("Swarm stream to be infinite!")).await {
+                    if let Err(err) = self.handle_event(event.expect("Swarm stream to be infinite!")) {
                         warn!("Error while handling event: {err}");
                     }
                 }  ,
-                command = self.cmd_receiver.next() => match command {
+                command = self.cmds.next() => match command {
                     Some(cmd) => {
+                        self.queue.cmd_received();
                         if let Err(err) = self.handle_command(cmd) {
                             warn!("Error while handling cmd: {err}");
                         }
@@ -147,18 +515,212 @@ impl NetworkSwarmLoop {
     }
 }
 
+impl<S: SwarmOps> SwarmDriver<S> {
+    // Wires the driver of the swarm to the `Network` API and the events receiver.
+    fn new(
+        swarm: S,
+        keypair: identity::Keypair,
+        config: &SwarmConfig,
+    ) -> (
+        Network,
+        impl Stream<Item = NetworkEvent> + Unpin,
+        SwarmDriver<S>,
+    ) {
+        let (cmd_senders, cmds) = cmd_queues(config.cmd_queue_size);
+        let (event_sender, event_receiver) =
+            mpsc::channel(event_channel_size(config.event_queue_size));
+        let queue = Arc::new(QueueCounters::default());
+        let relay = Arc::new(RelayCounters::default());
+        let connections = Arc::new(ConnectionCounters::default());
+        #[cfg(feature = "test-utils")]
+        let tap = MsgTap::new();
+        let events = {
+            let queue = queue.clone();
+            event_receiver.inspect(move |_| queue.event_received())
+        };
+        let event_loop = Self {
+            swarm,
+            cmds,
+            event_sender,
+            event_queue_size: config.event_queue_size,
+            queue: queue.clone(),
+            relay: relay.clone(),
+            connections: connections.clone(),
+            #[cfg(feature = "test-utils")]
+            tap: tap.clone(),
+            #[cfg(feature = "malice")]
+            malice: config.malice.clone().map(Malice::new),
+            pending_dial: Default::default(),
+            pending_start_providing: Default::default(),
+            pending_bootstrap: Default::default(),
+            pending_get_providers: Default::default(),
+            pending_get_closest_peers: Default::default(),
+            providers_subscribers: Default::default(),
+            closest_peers_subscribers: Default::default(),
+            data_batches: Default::default(),
+            pending_requests: Default::default(),
+            dial_preference: Default::default(),
+            dial_queue: DialQueue::new(config.max_concurrent_dials),
+            query_queue: QueryQueue::new(config.max_concurrent_queries),
+            idle_connections: IdleConnections::new(config.idle_connection_timeout),
+            reputations: Default::default(),
+            dysfunction: Default::default(),
+            liveness: Default::default(),
+            record_validator: None,
+            keypair: keypair.clone(),
+        };
+
+        (
+            Network {
+                cmd_senders,
+                priority: CmdPriority::default(),
+                keypair,
+                search: config.search,
+                queue,
+                relay,
+                connections,
+                #[cfg(feature = "test-utils")]
+                tap,
+                cmd_queue_size: config.cmd_queue_size,
+            },
+            events,
+            event_loop,
+        )
+    }
+}
+
+// QUIC, plus WebSocket for the peers which cannot speak QUIC, such as browsers.
+#[cfg(not(target_arch = "wasm32"))]
+fn build_transport(
+    keypair: &identity::Keypair,
+    max_streams: usize,
+    muxer: MuxerConfig,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    use futures::future::Either;
+    use libp2p::{tcp, websocket::WsConfig};
+
+    let mut quic_config = libp2p_quic::Config::new(keypair);
+    quic_config.max_concurrent_stream_limit = u32::try_from(max_streams).unwrap_or(u32::MAX);
+    let quic = libp2p_quic::async_std::Transport::new(quic_config);
+    let websocket = WsConfig::new(tcp::async_io::Transport::new(tcp::Config::default()))
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise_config(keypair)?)
+        .multiplex(muxer.to_yamux(max_streams));
+
+    Ok(quic
+        .or_transport(websocket)
+        .map(|either, _| match either {
+            Either::Left((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+            Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+        })
+        .boxed())
+}
+
+// Browsers can only dial out, over the WebSocket API they expose.
+#[cfg(target_arch = "wasm32")]
+fn build_transport(
+    keypair: &identity::Keypair,
+    max_streams: usize,
+    muxer: MuxerConfig,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    use libp2p::wasm_ext::{ffi, ExtTransport};
+
+    Ok(ExtTransport::new(ffi::websocket_transport())
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise_config(keypair)?)
+        .multiplex(muxer.to_yamux(max_streams))
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+        .boxed())
+}
+
+// TCP, with the connections encrypted with the network key before they are authenticated,
+// so that peers without the key fail the handshake.
+#[cfg(not(target_arch = "wasm32"))]
+fn build_private_transport(
+    keypair: &identity::Keypair,
+    network_key: PreSharedKey,
+    max_streams: usize,
+    muxer: MuxerConfig,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    use libp2p::{pnet::PnetConfig, tcp};
+
+    Ok(tcp::async_io::Transport::new(tcp::Config::default())
+        .and_then(move |socket, _| PnetConfig::new(network_key).handshake(socket))
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise_config(keypair)?)
+        .multiplex(muxer.to_yamux(max_streams))
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+        .boxed())
+}
+
+// Browsers cannot take part in private networks, see `build_private_transport`.
+#[cfg(target_arch = "wasm32")]
+fn build_private_transport(
+    _keypair: &identity::Keypair,
+    _network_key: PreSharedKey,
+    _max_streams: usize,
+    _muxer: MuxerConfig,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    Err(Error::Other(
+        "Private networks cannot be joined from a browser".to_string(),
+    ))
+}
+
+// In-process channels, the port of a `/memory/<port>` address standing for a listener.
+fn build_memory_transport(
+    keypair: &identity::Keypair,
+    max_streams: usize,
+    muxer: MuxerConfig,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    use libp2p::core::transport::MemoryTransport;
+
+    Ok(MemoryTransport::default()
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise_config(keypair)?)
+        .multiplex(muxer.to_yamux(max_streams))
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+        .boxed())
+}
+
+fn noise_config(keypair: &identity::Keypair) -> Result<noise::Config> {
+    noise::Config::new(keypair)
+        .map_err(|err| Error::Other(format!("Failed to create the noise config: {err}")))
+}
+
 #[derive(Clone)]
 /// API to interact with the underlying Swarm
 pub struct Network {
-    pub(super) swarm_cmd_sender: mpsc::Sender<SwarmCmd>,
+    cmd_senders: CmdSenders,
+    priority: CmdPriority,
+    keypair: identity::Keypair,
+    search: SearchConfig,
+    queue: Arc<QueueCounters>,
+    relay: Arc<RelayCounters>,
+    connections: Arc<ConnectionCounters>,
+    #[cfg(feature = "test-utils")]
+    tap: MsgTap,
+    cmd_queue_size: usize,
 }
 
 impl Network {
+    /// The `PeerId` of the local node.
+    pub fn peer_id(&self) -> PeerId {
+        self.keypair.public().to_peer_id()
+    }
+
+    /// A handle on the network sending its requests at the given priority, e.g. for the bulk
+    /// traffic between nodes to give way to the queries of clients. `Query` by default.
+    pub fn at_priority(&self, priority: CmdPriority) -> Self {
+        Self {
+            priority,
+            ..self.clone()
+        }
+    }
+
     ///  Listen for incoming connections on the given address.
     pub async fn start_listening(&mut self, addr: Multiaddr) -> Result<()> {
         let (sender, receiver) = oneshot::channel();
-        self.swarm_cmd_sender
-            .send(SwarmCmd::StartListening { addr, sender })
+        self.send_cmd(SwarmCmd::StartListening { addr, sender })
             .await?;
         receiver.await?
     }
@@ -166,13 +728,26 @@ impl Network {
     /// Dial the given peer at the given address.
     pub async fn dial(&mut self, peer_id: PeerId, peer_addr: Multiaddr) -> Result<()> {
         let (sender, receiver) = oneshot::channel();
-        self.swarm_cmd_sender
-            .send(SwarmCmd::Dial {
-                peer_id,
-                peer_addr,
-                sender,
-            })
-            .await?;
+        self.send_cmd(SwarmCmd::Dial {
+            peer_id,
+            peer_addr,
+            sender,
+        })
+        .await?;
+        receiver.await?
+    }
+
+    /// Dial a random subset of the bootstrap contacts, e.g. read from a network-contacts file.
+    pub async fn dial_contacts(&mut self, contacts: Vec<Multiaddr>) -> Result<()> {
+        self.send_cmd_or_shed(SwarmCmd::DialContacts { contacts })
+            .await
+    }
+
+    /// Bootstrap off the routing table, looking ourselves up then refreshing its buckets, e.g.
+    /// on startup or once reconnected after a long disconnection. Returns once all of it is done.
+    pub async fn bootstrap(&mut self) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::Bootstrap { sender }).await?;
         receiver.await?
     }
 
@@ -181,8 +756,7 @@ impl Network {
     /// todo: do not use the provider api to store stuff
     pub async fn store_data(&mut self, xor_name: XorName) -> Result<()> {
         let (sender, receiver) = oneshot::channel();
-        self.swarm_cmd_sender
-            .send(SwarmCmd::StoreData { xor_name, sender })
+        self.send_cmd(SwarmCmd::StoreData { xor_name, sender })
             .await?;
         receiver.await?
     }
@@ -192,30 +766,209 @@ impl Network {
     /// todo: do not use the provider api to store stuff
     pub async fn get_data_providers(&mut self, xor_name: XorName) -> Result<HashSet<PeerId>> {
         let (sender, receiver) = oneshot::channel();
-        self.swarm_cmd_sender
-            .send(SwarmCmd::GetDataProviders { xor_name, sender })
+        self.send_cmd(SwarmCmd::GetDataProviders { xor_name, sender })
+            .await?;
+        Ok(receiver.await?)
+    }
+
+    /// Find the peers closest to the given `XorName`, sorted by their distance to it, each with
+    /// the addresses it is known at in the routing table, to be dialed at right away.
+    pub async fn get_closest_peers(
+        &mut self,
+        xor_name: XorName,
+    ) -> Result<Vec<(PeerId, Vec<Multiaddr>)>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::GetClosestPeers { xor_name, sender })
             .await?;
         Ok(receiver.await?)
     }
 
-    /// Send `Request` to the the given `PeerId`
-    pub async fn send_request(&mut self, req: Request, peer: PeerId) -> Result<Response> {
+    /// Find the peers closest to each of the given `XorName`s, sorted by their distance to
+    /// it, all in a single cmd to the swarm, the lookups being run concurrently.
+    pub async fn get_data_batch(
+        &mut self,
+        keys: Vec<XorName>,
+    ) -> Result<BTreeMap<XorName, Vec<PeerId>>> {
         let (sender, receiver) = oneshot::channel();
-        self.swarm_cmd_sender
-            .send(SwarmCmd::SendRequest { req, peer, sender })
+        self.send_cmd(SwarmCmd::GetDataBatch { keys, sender })
             .await?;
+        Ok(receiver.await?)
+    }
+
+    /// Penalise the peer for the misbehaviour, blocking it once its penalty crosses the
+    /// threshold.
+    pub async fn report_misbehaviour(
+        &mut self,
+        peer_id: PeerId,
+        misbehaviour: Misbehaviour,
+    ) -> Result<()> {
+        self.send_cmd_or_shed(SwarmCmd::ReportMisbehaviour {
+            peer_id,
+            misbehaviour,
+        })
+        .await
+    }
+
+    /// Sets the check of the records pushed by peers through Kademlia, the records it rejects
+    /// not being stored and their peers being penalised.
+    pub async fn set_record_validator(&mut self, validator: RecordValidator) -> Result<()> {
+        self.send_cmd(SwarmCmd::SetRecordValidator { validator })
+            .await
+    }
+
+    /// The reputations of the peers that misbehaved lately.
+    pub async fn peer_reputations(&mut self) -> Result<Vec<PeerReputation>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::GetPeerReputations { sender })
+            .await?;
+        Ok(receiver.await?)
+    }
+
+    /// The peers we currently hold a connection to.
+    pub async fn connected_peers(&mut self) -> Result<Vec<PeerId>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::GetConnectedPeers { sender })
+            .await?;
+        Ok(receiver.await?)
+    }
+
+    /// The addresses we are listening on for incoming connections.
+    pub async fn listen_addrs(&mut self) -> Result<Vec<Multiaddr>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::GetListenAddrs { sender }).await?;
+        Ok(receiver.await?)
+    }
+
+    /// Adds an address we are reached at from outside of the local network, e.g. through
+    /// a port mapping, for it to be advertised to peers.
+    pub async fn add_external_addr(&mut self, addr: Multiaddr) -> Result<()> {
+        self.send_cmd(SwarmCmd::AddExternalAddr { addr }).await
+    }
+
+    /// The number of peers in our routing table.
+    pub async fn routing_table_size(&mut self) -> Result<usize> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::GetRoutingTableSize { sender })
+            .await?;
+        Ok(receiver.await?)
+    }
+
+    /// The [`CLOSE_GROUP_SIZE`] peers of our routing table closest to us, closest first.
+    pub async fn close_group(&mut self) -> Result<Vec<PeerId>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::GetCloseGroup { sender }).await?;
+        Ok(receiver.await?)
+    }
+
+    /// Whether the data at `xor_name` is within the range we are responsible for, i.e. no
+    /// farther from us than the `K_VALUE`th closest peer of our routing table. Everything is
+    /// within range while our routing table holds fewer peers.
+    pub async fn is_responsible_for(&mut self, xor_name: XorName) -> Result<bool> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::IsResponsibleFor { xor_name, sender })
+            .await?;
+        Ok(receiver.await?)
+    }
+
+    /// Whether the peer is in our routing table, i.e. a node we know of rather than a client.
+    pub async fn is_in_routing_table(&mut self, peer_id: PeerId) -> Result<bool> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::IsInRoutingTable { peer_id, sender })
+            .await?;
+        Ok(receiver.await?)
+    }
+
+    /// Send a `Request` to the peers closest to the given `XorName`, returning each of them
+    /// along with its `SignedResponse`, or the error encountered while sending to it.
+    pub async fn send_to_closest(
+        &self,
+        xor_name: XorName,
+        req: Request,
+    ) -> Result<Vec<(PeerId, Result<SignedResponse>)>> {
+        let closest_peers = self
+            .clone()
+            .get_closest_peers(xor_name)
+            .await?
+            .into_iter()
+            .map(|(peer, _)| peer)
+            .collect();
+        Ok(self.send_to_close_group(xor_name, closest_peers, req).await)
+    }
+
+    /// Send each `Request` to the peers closest to its `XorName`, as with
+    /// [`Network::send_to_closest`], looking up the closest peers of all of them at once.
+    pub async fn send_to_closest_batch(
+        &self,
+        reqs: Vec<(XorName, Request)>,
+    ) -> Result<Vec<Vec<(PeerId, Result<SignedResponse>)>>> {
+        let keys = reqs.iter().map(|(xor_name, _)| *xor_name).collect();
+        let closest_peers = self.clone().get_data_batch(keys).await?;
+        let sends = reqs.into_iter().map(|(xor_name, req)| {
+            let peers = closest_peers.get(&xor_name).cloned().unwrap_or_default();
+            self.send_to_close_group(xor_name, peers, req)
+        });
+        Ok(future::join_all(sends).await)
+    }
+
+    // Sends the request to the close group of `xor_name` among the given peers.
+    async fn send_to_close_group(
+        &self,
+        xor_name: XorName,
+        closest_peers: Vec<PeerId>,
+        req: Request,
+    ) -> Vec<(PeerId, Result<SignedResponse>)> {
+        trace!("Sending {req:?} to the peers closest to {xor_name:?}");
+
+        let requests = close_group(&xor_name, closest_peers, None)
+            .into_iter()
+            .map(|peer| {
+                let mut network = self.clone();
+                let req = req.clone();
+                async move {
+                    let result = network.send_request(req, peer).await;
+                    if let Err(err) = &result {
+                        warn!("Failed to get response from {peer:?}: {err}");
+                    }
+                    (peer, result)
+                }
+            });
+
+        future::join_all(requests).await
+    }
+
+    /// Send `Request` to the the given `PeerId`, along with the context of the current span
+    pub async fn send_request(&mut self, req: Request, peer: PeerId) -> Result<SignedResponse> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::SendRequest {
+            header: MsgHeader::with_current_context(),
+            req,
+            peer,
+            sender,
+        })
+        .await?;
         receiver.await?
     }
 
-    /// Send a `Response` through the channel opened by the requester.
+    /// Give the `Cmd` a unique id and sign it with our keypair, so that the receiving
+    /// nodes can verify who issued it and drop replays of it.
+    pub fn sign_cmd(&self, cmd: Cmd) -> ProtocolResult<SignedCmd> {
+        SignedCmd::new(cmd, &self.keypair)
+    }
+
+    /// Returns our keypair, with which we also sign the ops we write to registers.
+    pub(crate) fn keypair(&self) -> &identity::Keypair {
+        &self.keypair
+    }
+
+    /// Sign a `Response` with our keypair and send it through the channel opened by the requester.
     pub async fn send_response(
         &mut self,
         resp: Response,
-        channel: ResponseChannel<Response>,
+        channel: ResponseChannel<SignedResponse>,
     ) -> Result<()> {
-        Ok(self
-            .swarm_cmd_sender
-            .send(SwarmCmd::SendResponse { resp, channel })
-            .await?)
+        let resp = SignedResponse::new(resp, &self.keypair)
+            .map_err(|err| Error::Other(format!("Could not sign response: {err}")))?;
+        self.send_cmd(SwarmCmd::SendResponse { resp, channel })
+            .await
     }
 }
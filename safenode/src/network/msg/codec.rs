@@ -6,51 +6,92 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::storage::chunks::Chunk;
+use super::MsgHeader;
+use crate::{
+    network::PROTOCOL_VERSION,
+    protocol::messages::{Request, SignedResponse},
+};
 use async_trait::async_trait;
 use futures::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use libp2p::{
     core::upgrade::{read_length_prefixed, write_length_prefixed},
     request_response::{self, ProtocolName},
 };
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Serialize};
 use std::io;
-use xor_name::XorName;
-
-/// Send a request to other peers in the network
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Request {
-    /// todo: impl entire DataStorage struct
-    GetChunk(XorName),
-    /// todo: impl entire DataStorage struct
-    GetDBC,
+
+#[cfg(feature = "malice")]
+use {
+    crate::network::malice::{corrupt, Malice, WireFault},
+    std::sync::Arc,
+};
+
+/// The max size of a msg read off the wire, to not allocate for arbitrary lengths claimed by
+/// peers. It fits the largest chunk self-encryption produces, with room for the rest of the msg.
+///
+/// Msgs larger than it are not written either, peers being bound to reject them.
+const MAX_MSG_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub(crate) struct MsgProtocol(String);
+#[derive(Clone, Default)]
+pub(crate) struct MsgCodec {
+    // The faults injected into the msgs written, if any.
+    #[cfg(feature = "malice")]
+    malice: Option<Arc<Malice>>,
 }
 
-/// Respond to other peers in the network
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Response {
-    /// todo: impl entire DataStorage struct
-    Chunk(Chunk),
-    /// todo: impl entire DataStorage struct
-    DBC,
+impl MsgProtocol {
+    // The protocol name carries the protocol version, so that peers running an
+    // incompatible version fail to negotiate it.
+    pub(crate) fn new() -> Self {
+        Self(format!("/safenode/msg/{PROTOCOL_VERSION}"))
+    }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct MsgProtocol();
-#[derive(Clone)]
-pub(crate) struct MsgCodec();
+impl MsgCodec {
+    #[cfg(feature = "malice")]
+    pub(crate) fn with_malice(malice: Option<Arc<Malice>>) -> Self {
+        Self { malice }
+    }
+
+    // Encodes and writes the msg, injecting the fault drawn for it, if any.
+    async fn write_msg<IO, T>(&self, io: &mut IO, data: T) -> io::Result<()>
+    where
+        IO: AsyncWrite + Unpin,
+        T: Serialize,
+    {
+        #[cfg(feature = "malice")]
+        if let Some(malice) = &self.malice {
+            let mut bytes = encode(&data)?;
+            match malice.wire_fault() {
+                WireFault::None => {}
+                WireFault::Drop => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionReset,
+                        "msg dropped by malice",
+                    ))
+                }
+                WireFault::Delay(delay) => async_std::task::sleep(delay).await,
+                WireFault::Corrupt(index) => corrupt(&mut bytes, index),
+            }
+            return write(io, bytes).await;
+        }
+        encode_and_write(io, data).await
+    }
+}
 
 impl ProtocolName for MsgProtocol {
     fn protocol_name(&self) -> &[u8] {
-        "/msg/1".as_bytes()
+        self.0.as_bytes()
     }
 }
 
 #[async_trait]
 impl request_response::Codec for MsgCodec {
     type Protocol = MsgProtocol;
-    type Request = Request;
-    type Response = Response;
+    type Request = (MsgHeader, Request);
+    type Response = SignedResponse;
 
     async fn read_request<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<Self::Request>
     where
@@ -79,7 +120,7 @@ impl request_response::Codec for MsgCodec {
     where
         T: AsyncWrite + Unpin + Send,
     {
-        encode_and_write(io, req).await
+        self.write_msg(io, req).await
     }
 
     async fn write_response<T>(
@@ -91,33 +132,64 @@ impl request_response::Codec for MsgCodec {
     where
         T: AsyncWrite + Unpin + Send,
     {
-        encode_and_write(io, res).await
+        self.write_msg(io, res).await
     }
 }
 
-// Encodes the Response/Response using rmp_serde
+// Encodes the Request/Response using rmp_serde
 async fn encode_and_write<IO, T>(io: &mut IO, data: T) -> io::Result<()>
 where
     IO: AsyncWrite + Unpin,
     T: Serialize,
 {
-    let bytes = rmp_serde::to_vec(&data)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    write(io, encode(&data)?).await
+}
+
+fn encode<T: Serialize>(data: &T) -> io::Result<Vec<u8>> {
+    rmp_serde::to_vec(data).map_err(|e| io::Error::other(e.to_string()))
+}
+
+async fn write<IO: AsyncWrite + Unpin>(io: &mut IO, bytes: Vec<u8>) -> io::Result<()> {
+    if bytes.len() > MAX_MSG_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("msg of {} bytes exceeds the max size", bytes.len()),
+        ));
+    }
     write_length_prefixed(io, bytes).await?;
     io.close().await?;
     Ok(())
 }
 
-// Decodes the Response/Response using rmp_serde
+/// Decodes a request as read off the wire, past its length prefix.
+pub fn decode_request(bytes: &[u8]) -> io::Result<(MsgHeader, Request)> {
+    decode(bytes)
+}
+
+/// Decodes a response as read off the wire, past its length prefix.
+pub fn decode_response(bytes: &[u8]) -> io::Result<SignedResponse> {
+    decode(bytes)
+}
+
+// Decodes the Request/Response using rmp_serde
 async fn read_and_decode<IO, T>(io: &mut IO) -> io::Result<T>
 where
     IO: AsyncRead + Unpin,
     T: DeserializeOwned,
 {
-    let vec = read_length_prefixed(io, 500_000_000).await?; // update transfer maximum
-    if vec.is_empty() {
+    let vec = read_length_prefixed(io, MAX_MSG_SIZE).await?;
+    decode(&vec)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+    if bytes.is_empty() {
         return Err(io::ErrorKind::UnexpectedEof.into());
     }
-    rmp_serde::from_slice::<T>(vec.as_slice())
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    if bytes.len() > MAX_MSG_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("msg of {} bytes exceeds the max size", bytes.len()),
+        ));
+    }
+    rmp_serde::from_slice::<T>(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
@@ -0,0 +1,48 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::Span;
+
+#[cfg(feature = "otlp")]
+use {opentelemetry::global, tracing_opentelemetry::OpenTelemetrySpanExt};
+
+/// Metadata sent along with a `Request`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MsgHeader {
+    /// The context of the trace the request is part of, in the W3C Trace Context format.
+    /// Empty unless traces are exported.
+    trace_context: HashMap<String, String>,
+}
+
+impl MsgHeader {
+    /// Returns a header carrying the context of the current span, for the handling of the
+    /// request by the receiving peer to be traced as part of it.
+    pub(crate) fn with_current_context() -> Self {
+        #[allow(unused_mut)]
+        let mut trace_context = HashMap::new();
+        #[cfg(feature = "otlp")]
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&Span::current().context(), &mut trace_context)
+        });
+        Self { trace_context }
+    }
+
+    /// Sets the span of the sender, carried by the header, as the parent of the given span.
+    #[cfg_attr(not(feature = "otlp"), allow(unused_variables))]
+    pub fn set_parent_of(&self, span: &Span) {
+        #[cfg(feature = "otlp")]
+        {
+            let context = global::get_text_map_propagator(|propagator| {
+                propagator.extract(&self.trace_context)
+            });
+            span.set_parent(context);
+        }
+    }
+}
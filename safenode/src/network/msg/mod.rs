@@ -7,56 +7,146 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod codec;
+mod header;
+
+pub use codec::{decode_request, decode_response};
 pub(crate) use codec::{MsgCodec, MsgProtocol};
-pub use codec::{Request, Response};
+pub use header::MsgHeader;
 
-use crate::network::{error::Error, NetworkEvent, NetworkSwarmLoop};
-use futures::prelude::*;
-use libp2p::request_response::{self, Message};
+use crate::{
+    network::{
+        dysfunction::{Failure, SLOW_RESPONSE},
+        error::Error,
+        event::NodeBehaviour,
+        reputation::Misbehaviour,
+        NetworkEvent, SwarmDriver,
+    },
+    protocol::messages::{Request, Response, SignedResponse},
+};
+use libp2p::{
+    request_response::{self, Message, OutboundFailure},
+    Swarm,
+};
 use tracing::{trace, warn};
 
-impl NetworkSwarmLoop {
+#[cfg(feature = "test-utils")]
+use crate::network::tap::MsgDirection;
+
+impl SwarmDriver<Swarm<NodeBehaviour>> {
     /// Forwards `Request` to the upper layers using `Sender<NetworkEvent>`. Sends `Response` to the peers
-    pub async fn handle_msg(
+    pub(crate) fn handle_msg(
         &mut self,
-        event: request_response::Event<Request, Response>,
+        event: request_response::Event<(MsgHeader, Request), SignedResponse>,
     ) -> Result<(), Error> {
         match event {
-            request_response::Event::Message { message, .. } => match message {
-                Message::Request {
-                    request,
-                    channel,
-                    request_id,
-                    ..
-                } => {
-                    trace!("Received request with id: {request_id:?}, req: {request:?}");
-                    self.event_sender
-                        .send(NetworkEvent::RequestReceived {
+            request_response::Event::Message { message, peer } => {
+                self.idle_connections.touch(peer);
+                match message {
+                    Message::Request {
+                        request: (_header, Request::Event(event)),
+                        channel,
+                        request_id,
+                    } => {
+                        trace!("Received event with id: {request_id:?}, event: {event:?}");
+                        #[cfg(feature = "test-utils")]
+                        self.tap.request(
+                            MsgDirection::Inbound,
+                            peer,
+                            &_header,
+                            &Request::Event(event.clone()),
+                        );
+                        // Events are acknowledged straight away, and handed over to the upper layers.
+                        let resp = SignedResponse::new(Response::EventReceived, &self.keypair)
+                            .map_err(|err| {
+                                Error::Other(format!("Could not sign response: {err}"))
+                            })?;
+                        #[cfg(feature = "test-utils")]
+                        self.tap.response(MsgDirection::Outbound, Some(peer), &resp);
+                        self.swarm
+                            .behaviour_mut()
+                            .request_response
+                            .send_response(channel, resp)
+                            .map_err(|_| {
+                                Error::Other("Connection to peer to be still open.".to_string())
+                            })?;
+                        self.send_event(NetworkEvent::EventReceived { peer, event })?
+                    }
+                    Message::Request {
+                        request: (header, request),
+                        channel,
+                        request_id,
+                    } => {
+                        trace!("Received request with id: {request_id:?}, req: {request:?}");
+                        #[cfg(feature = "test-utils")]
+                        self.tap
+                            .request(MsgDirection::Inbound, peer, &header, &request);
+                        self.send_event(NetworkEvent::RequestReceived {
+                            peer,
+                            request_id,
+                            header,
                             req: request,
                             channel,
-                        })
-                        .await?
+                        })?
+                    }
+                    Message::Response {
+                        request_id,
+                        response,
+                    } => {
+                        trace!("Got response for id: {request_id:?}, res: {response:?} ");
+                        #[cfg(feature = "test-utils")]
+                        self.tap
+                            .response(MsgDirection::Inbound, Some(peer), &response);
+                        if let Some(elapsed) = self.dysfunction.request_ended(&request_id) {
+                            if elapsed > SLOW_RESPONSE {
+                                self.record_failure(peer, Failure::SlowResponse);
+                            }
+                        }
+                        // A response not signed by the peer asked is not handed over, as it
+                        // may be relayed or forged from the response of another peer.
+                        let result = match response.verify() {
+                            Ok(signer) if signer == peer => Ok(response),
+                            Ok(signer) => {
+                                warn!("Response from {peer:?} is signed by {signer:?}");
+                                self.penalise(peer, Misbehaviour::InvalidData);
+                                Err(Error::InvalidResponse {
+                                    peer,
+                                    reason: format!("signed by {signer:?}"),
+                                })
+                            }
+                            Err(err) => {
+                                warn!("Response from {peer:?} is invalid: {err}");
+                                self.penalise(peer, Misbehaviour::InvalidData);
+                                Err(Error::InvalidResponse {
+                                    peer,
+                                    reason: err.to_string(),
+                                })
+                            }
+                        };
+                        self.pending_requests
+                            .remove(&request_id)
+                            .ok_or(Error::Other("Request to still be pending".to_string()))?
+                            .complete(peer, result);
+                    }
                 }
-                Message::Response {
-                    request_id,
-                    response,
-                } => {
-                    trace!("Got response for id: {request_id:?}, res: {response:?} ");
-                    let _ = self
-                        .pending_requests
-                        .remove(&request_id)
-                        .ok_or(Error::Other("Request to still be pending".to_string()))?
-                        .send(Ok(response));
-                }
-            },
+            }
             request_response::Event::OutboundFailure {
-                request_id, error, ..
+                peer,
+                request_id,
+                error,
             } => {
-                let _ = self
-                    .pending_requests
+                let misbehaviour = match error {
+                    OutboundFailure::Timeout => Misbehaviour::Timeout,
+                    OutboundFailure::UnsupportedProtocols => Misbehaviour::ProtocolError,
+                    OutboundFailure::DialFailure | OutboundFailure::ConnectionClosed => {
+                        Misbehaviour::FailedResponse
+                    }
+                };
+                let _ = self.dysfunction.request_ended(&request_id);
+                self.penalise(peer, misbehaviour);
+                self.pending_requests
                     .remove(&request_id)
                     .ok_or(Error::Other("Request to still be pending.".to_string()))?
-                    .send(Err(error.into()));
+                    .complete(peer, Err(error.into()));
             }
             request_response::Event::InboundFailure {
                 peer,
@@ -0,0 +1,53 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use libp2p::yamux;
+
+/// The receive window of the streams by default, fitting a max-size chunk along with its
+/// msg, for it to be sent in one go rather than waiting on window updates along the way.
+pub const DEFAULT_MUXER_RECEIVE_WINDOW: u32 = 2 * 1024 * 1024;
+/// The bytes buffered per stream by default, for the reader to catch up with.
+pub const DEFAULT_MUXER_MAX_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+// Yamux does not take windows smaller than the one of its spec.
+const MIN_RECEIVE_WINDOW: u32 = 256 * 1024;
+
+/// The settings of the multiplexing of streams over the connections of the transports which
+/// have no multiplexing of their own, i.e. all but QUIC, whose flow control windows are
+/// larger than these already.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MuxerConfig {
+    /// The bytes a peer can send over a stream before waiting for them to be read.
+    /// At least 256 KiB.
+    pub receive_window: u32,
+    /// The bytes buffered per stream, no less than the `receive_window`.
+    pub max_buffer_size: usize,
+}
+
+impl Default for MuxerConfig {
+    fn default() -> Self {
+        Self {
+            receive_window: DEFAULT_MUXER_RECEIVE_WINDOW,
+            max_buffer_size: DEFAULT_MUXER_MAX_BUFFER_SIZE,
+        }
+    }
+}
+
+impl MuxerConfig {
+    /// The yamux config of these settings, with at most `max_streams` streams open at once.
+    pub(crate) fn to_yamux(self, max_streams: usize) -> yamux::Config {
+        let receive_window = self.receive_window.max(MIN_RECEIVE_WINDOW);
+        let max_buffer_size = self.max_buffer_size.max(receive_window as usize);
+        let mut config = yamux::Config::default();
+        let _ = config
+            .set_receive_window_size(receive_window)
+            .set_max_buffer_size(max_buffer_size)
+            .set_max_num_streams(max_streams);
+        config
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::command::SwarmCmd;
+use futures::{channel::mpsc, prelude::*};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// The classes of the cmds sent to the driver of the swarm, in the order they are handled in
+/// when several are queued, for the bulk traffic between nodes not to hold up the queries
+/// clients are waiting on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CmdPriority {
+    /// The queries clients are waiting on.
+    #[default]
+    Query,
+    /// The responses to the requests of peers.
+    Response,
+    /// The transfer of records between nodes, e.g. to repair them.
+    Replication,
+    /// The upkeep of the node: dials, reports of misbehaving peers, stats.
+    Maintenance,
+}
+
+const PRIORITIES: usize = 4;
+
+impl SwarmCmd {
+    // The class of the cmd. The requests and lookups made on behalf of the upper layers
+    // take the priority they are made at.
+    pub(super) fn priority(&self, requested: CmdPriority) -> CmdPriority {
+        match self {
+            Self::SendRequest { .. }
+            | Self::GetRecordFromPeer { .. }
+            | Self::GetClosestPeers { .. }
+            | Self::GetDataProviders { .. }
+            | Self::GetDataBatch { .. }
+            | Self::IsResponsibleFor { .. }
+            | Self::IsInRoutingTable { .. }
+            | Self::StoreData { .. } => requested,
+            Self::SendResponse { .. } => CmdPriority::Response,
+            Self::StartListening { .. }
+            | Self::Dial { .. }
+            | Self::DialContacts { .. }
+            | Self::GetConnectedPeers { .. }
+            | Self::GetListenAddrs { .. }
+            | Self::Bootstrap { .. }
+            | Self::GetRoutingTableSize { .. }
+            | Self::GetCloseGroup { .. }
+            | Self::ReportMisbehaviour { .. }
+            | Self::GetPeerReputations { .. }
+            | Self::SetRecordValidator { .. }
+            | Self::AddExternalAddr { .. } => CmdPriority::Maintenance,
+        }
+    }
+}
+
+// The senders of the queues of cmds, one per priority.
+#[derive(Clone, Debug)]
+pub(super) struct CmdSenders([mpsc::Sender<SwarmCmd>; PRIORITIES]);
+
+impl CmdSenders {
+    pub(super) fn queue(&mut self, priority: CmdPriority) -> &mut mpsc::Sender<SwarmCmd> {
+        &mut self.0[priority as usize]
+    }
+}
+
+// The queues of cmds, drained by the driver of the swarm highest priority first.
+pub(super) struct PriorityCmds([mpsc::Receiver<SwarmCmd>; PRIORITIES]);
+
+// Opens a queue of the given size for each priority.
+pub(super) fn cmd_queues(size: usize) -> (CmdSenders, PriorityCmds) {
+    let (query_sender, query_receiver) = mpsc::channel(size);
+    let (response_sender, response_receiver) = mpsc::channel(size);
+    let (replication_sender, replication_receiver) = mpsc::channel(size);
+    let (maintenance_sender, maintenance_receiver) = mpsc::channel(size);
+    (
+        CmdSenders([
+            query_sender,
+            response_sender,
+            replication_sender,
+            maintenance_sender,
+        ]),
+        PriorityCmds([
+            query_receiver,
+            response_receiver,
+            replication_receiver,
+            maintenance_receiver,
+        ]),
+    )
+}
+
+impl Stream for PriorityCmds {
+    type Item = SwarmCmd;
+
+    // Yields the next cmd of the highest priority queued. Ends once all the queues are closed,
+    // which they are together, with the senders being held together.
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<SwarmCmd>> {
+        let mut closed = 0;
+        for queue in self.0.iter_mut() {
+            match queue.poll_next_unpin(cx) {
+                Poll::Ready(Some(cmd)) => return Poll::Ready(Some(cmd)),
+                Poll::Ready(None) => closed += 1,
+                Poll::Pending => {}
+            }
+        }
+        if closed == PRIORITIES {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl stream::FusedStream for PriorityCmds {
+    fn is_terminated(&self) -> bool {
+        self.0.iter().all(|queue| queue.is_terminated())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{swarm::MockSwarm, SwarmConfig, SwarmDriver};
+    use eyre::Result;
+    use futures::channel::oneshot;
+    use libp2p::identity::Keypair;
+    use xor_name::XorName;
+
+    #[async_std::test]
+    async fn cmds_are_handled_highest_priority_first() -> Result<()> {
+        let (network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let mut replication = network.at_priority(CmdPriority::Replication);
+        let mut query = network.at_priority(CmdPriority::Query);
+        let replicated = XorName([1; 32]);
+        let queried = XorName([2; 32]);
+
+        let (sender, _receiver) = oneshot::channel();
+        replication
+            .send_cmd(SwarmCmd::GetConnectedPeers { sender })
+            .await?;
+        let (sender, _receiver) = oneshot::channel();
+        replication
+            .send_cmd(SwarmCmd::GetClosestPeers {
+                xor_name: replicated,
+                sender,
+            })
+            .await?;
+        let (sender, _receiver) = oneshot::channel();
+        query
+            .send_cmd(SwarmCmd::GetClosestPeers {
+                xor_name: queried,
+                sender,
+            })
+            .await?;
+
+        // The lookups of closest peers are told apart by their name, the other cmd by `None`.
+        let handled: Vec<_> = (0..3)
+            .filter_map(|_| driver.cmds.next().now_or_never().flatten())
+            .map(|cmd| match cmd {
+                SwarmCmd::GetClosestPeers { xor_name, .. } => Some(xor_name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(handled, vec![Some(queried), Some(replicated), None]);
+        Ok(())
+    }
+}
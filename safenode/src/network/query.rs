@@ -0,0 +1,272 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{error::Result, swarm::SwarmOps, SwarmDriver};
+use futures::channel::oneshot;
+use libp2p::{Multiaddr, PeerId};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+use tracing::{debug, warn};
+use xor_name::XorName;
+
+/// The max number of Kademlia queries run at once by default.
+pub const DEFAULT_MAX_CONCURRENT_QUERIES: usize = 32;
+
+/// A Kademlia query issued by a cmd, with the channel its outcome is sent through.
+#[derive(Debug)]
+pub(crate) enum KadQuery {
+    StartProviding {
+        xor_name: XorName,
+        sender: oneshot::Sender<Result<()>>,
+    },
+    // The callers waiting on the outcome of these are kept as `Subscribers`.
+    GetProviders {
+        xor_name: XorName,
+    },
+    GetClosestPeers {
+        xor_name: XorName,
+    },
+    GetBatchedClosestPeers {
+        batch_id: u64,
+        xor_name: XorName,
+    },
+}
+
+/// The Kademlia queries to run, a bounded number at a time so that issuing many of them at
+/// once does not slow them all down.
+///
+/// The queries over the limit are queued, and started in the order they were issued as the
+/// running ones end.
+#[derive(Debug)]
+pub(crate) struct QueryQueue<Id> {
+    max_concurrent: usize,
+    running: HashSet<Id>,
+    queued: VecDeque<KadQuery>,
+}
+
+impl<Id: Eq + Hash> QueryQueue<Id> {
+    pub(crate) fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent: max_concurrent.max(1),
+            running: HashSet::new(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Queues the query to be started.
+    pub(crate) fn push(&mut self, query: KadQuery) {
+        self.queued.push_back(query);
+    }
+
+    /// Takes the next query to start, unless the max number of queries are running already.
+    pub(crate) fn next_query(&mut self) -> Option<KadQuery> {
+        if self.running.len() < self.max_concurrent {
+            self.queued.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Tracks the query as running.
+    pub(crate) fn started(&mut self, id: Id) {
+        let _ = self.running.insert(id);
+    }
+
+    /// Ends the query, returning whether it was one of ours.
+    pub(crate) fn ended(&mut self, id: &Id) -> bool {
+        self.running.remove(id)
+    }
+}
+
+/// The callers waiting on the outcome of the queries for each key, so that a query issued
+/// while an identical one is queued or running subscribes to the outcome of the latter,
+/// rather than running again.
+#[derive(Debug)]
+pub(crate) struct Subscribers<T> {
+    waiting: HashMap<XorName, Vec<oneshot::Sender<T>>>,
+}
+
+impl<T> Default for Subscribers<T> {
+    fn default() -> Self {
+        Self {
+            waiting: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> Subscribers<T> {
+    /// Subscribes the caller to the outcome of the query for the key, returning whether the
+    /// query is to be issued, none being in flight for the key.
+    pub(crate) fn subscribe(&mut self, xor_name: XorName, sender: oneshot::Sender<T>) -> bool {
+        match self.waiting.entry(xor_name) {
+            Entry::Occupied(mut entry) => {
+                debug!("Query for {xor_name:?} already in flight, subscribing to it");
+                entry.get_mut().push(sender);
+                false
+            }
+            Entry::Vacant(entry) => {
+                let _ = entry.insert(vec![sender]);
+                true
+            }
+        }
+    }
+
+    /// Sends the outcome of the query for the key to all the callers subscribed to it.
+    pub(crate) fn notify(&mut self, xor_name: &XorName, outcome: T) {
+        for sender in self.waiting.remove(xor_name).unwrap_or_default() {
+            // The caller may have given up on the query.
+            let _ = sender.send(outcome.clone());
+        }
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Starts the queries the queue allows for.
+    pub(crate) fn query_next(&mut self) {
+        while let Some(query) = self.query_queue.next_query() {
+            match query {
+                KadQuery::StartProviding { xor_name, sender } => {
+                    match self.swarm.start_providing(xor_name) {
+                        Ok(query_id) => {
+                            self.query_queue.started(query_id);
+                            let _ = self.pending_start_providing.insert(query_id, sender);
+                        }
+                        Err(err) => {
+                            warn!("Failed to start providing {xor_name:?}: {err}");
+                            let _ = sender.send(Err(err));
+                        }
+                    }
+                }
+                KadQuery::GetProviders { xor_name } => {
+                    let query_id = self.swarm.get_providers(xor_name);
+                    self.query_queue.started(query_id);
+                    let _ = self.pending_get_providers.insert(query_id, xor_name);
+                }
+                KadQuery::GetClosestPeers { xor_name } => {
+                    let query_id = self.swarm.get_closest_peers(xor_name);
+                    self.query_queue.started(query_id);
+                    let _ = self.pending_get_closest_peers.insert(query_id, xor_name);
+                }
+                KadQuery::GetBatchedClosestPeers { batch_id, xor_name } => {
+                    let query_id = self.swarm.get_closest_peers(xor_name);
+                    self.query_queue.started(query_id);
+                    self.data_batches
+                        .lookup_started(query_id, batch_id, xor_name);
+                }
+            }
+        }
+    }
+
+    /// Pairs each of the peers with the addresses it is known at, for the callers to dial them
+    /// without looking them up first.
+    pub(crate) fn with_addrs(&mut self, peers: Vec<PeerId>) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        peers
+            .into_iter()
+            .map(|peer| {
+                let addrs = self.swarm.peer_addrs(&peer);
+                (peer, addrs)
+            })
+            .collect()
+    }
+
+    /// Ends the query, starting the next queued one in its place.
+    pub(crate) fn query_ended(&mut self, query_id: S::QueryId) {
+        if self.query_queue.ended(&query_id) {
+            debug!(
+                "Kademlia query ended, {} queued",
+                self.query_queue.queued.len()
+            );
+            self.query_next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::{command::SwarmCmd, swarm::MockSwarm, SwarmConfig, SwarmDriver};
+    use eyre::Result;
+    use futures::channel::oneshot;
+    use libp2p::{identity::Keypair, Multiaddr, PeerId};
+    use xor_name::XorName;
+
+    #[test]
+    fn queries_beyond_the_max_are_queued_until_a_query_ends() -> Result<()> {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig {
+                max_concurrent_queries: 1,
+                ..Default::default()
+            },
+        );
+        let names = [XorName([1; 32]), XorName([2; 32])];
+        for xor_name in names {
+            let (sender, _receiver) = oneshot::channel();
+            driver.handle_command(SwarmCmd::GetClosestPeers { xor_name, sender })?;
+        }
+        assert_eq!(driver.swarm.queries, vec![names[0]]);
+
+        // Queries not started by a cmd do not make room for the queued ones.
+        driver.query_ended(42);
+        assert_eq!(driver.swarm.queries, vec![names[0]]);
+
+        driver.query_ended(1);
+        assert_eq!(driver.swarm.queries, names.to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn identical_queries_in_flight_are_coalesced() -> Result<()> {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let xor_name = XorName([1; 32]);
+        let mut receivers = vec![];
+        for _ in 0..2 {
+            let (sender, receiver) = oneshot::channel();
+            driver.handle_command(SwarmCmd::GetClosestPeers { xor_name, sender })?;
+            receivers.push(receiver);
+        }
+        assert_eq!(driver.swarm.queries, vec![xor_name]);
+
+        let peers = vec![(PeerId::random(), vec![])];
+        driver
+            .closest_peers_subscribers
+            .notify(&xor_name, peers.clone());
+        for mut receiver in receivers {
+            assert_eq!(receiver.try_recv()?, Some(peers.clone()));
+        }
+
+        // Once the query is over, the next one for the key is run again.
+        let (sender, _receiver) = oneshot::channel();
+        driver.handle_command(SwarmCmd::GetClosestPeers { xor_name, sender })?;
+        assert_eq!(driver.swarm.queries, vec![xor_name, xor_name]);
+        Ok(())
+    }
+
+    #[test]
+    fn closest_peers_come_with_their_addresses() {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let (known, unknown) = (PeerId::random(), PeerId::random());
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1200".parse().expect("valid address");
+        driver.swarm.routing_table.push((known, addr.clone()));
+
+        assert_eq!(
+            driver.with_addrs(vec![known, unknown]),
+            vec![(known, vec![addr]), (unknown, vec![])]
+        );
+    }
+}
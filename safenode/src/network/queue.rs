@@ -0,0 +1,174 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    command::SwarmCmd, error::Result, swarm::SwarmOps, Network, NetworkEvent, SwarmDriver,
+};
+use futures::prelude::*;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use tracing::warn;
+
+/// The number of cmds queued by default for the driver of the swarm, beyond which the
+/// upper layers wait for the driver to catch up.
+pub const DEFAULT_CMD_QUEUE_SIZE: usize = 256;
+/// The number of events queued by default for the upper layers, beyond which the messages
+/// of peers are shed.
+pub const DEFAULT_EVENT_QUEUE_SIZE: usize = 256;
+// The room left in the event queue beyond its size, for the events on the state of the
+// connections not to be shed along with the messages of peers.
+const EVENT_QUEUE_HEADROOM: usize = 32;
+
+/// The depth of the queues between the upper layers and the driver of the swarm, and the
+/// number of messages shed off them since startup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// The cmds sent to the driver not yet picked up by it.
+    pub cmd_depth: usize,
+    /// The events sent to the upper layers not yet picked up by them.
+    pub event_depth: usize,
+    /// The cmds dropped for the driver being saturated.
+    pub cmds_shed: u64,
+    /// The events dropped for the upper layers being saturated.
+    pub events_shed: u64,
+}
+
+// The counters behind the `QueueStats`, shared by the `Network` and the driver.
+#[derive(Debug, Default)]
+pub(super) struct QueueCounters {
+    cmd_depth: AtomicUsize,
+    event_depth: AtomicUsize,
+    cmds_shed: AtomicU64,
+    events_shed: AtomicU64,
+}
+
+impl QueueCounters {
+    pub(super) fn stats(&self) -> QueueStats {
+        QueueStats {
+            cmd_depth: self.cmd_depth.load(Ordering::Relaxed),
+            event_depth: self.event_depth.load(Ordering::Relaxed),
+            cmds_shed: self.cmds_shed.load(Ordering::Relaxed),
+            events_shed: self.events_shed.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(super) fn cmd_received(&self) {
+        let _ = self.cmd_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn event_received(&self) {
+        let _ = self.event_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// The size of the event channel, for the given size of the queue.
+pub(super) fn event_channel_size(event_queue_size: usize) -> usize {
+    event_queue_size + EVENT_QUEUE_HEADROOM
+}
+
+impl Network {
+    /// The depth of the queues between the node layer and the driver of the swarm, and the
+    /// number of messages shed off them.
+    pub fn queue_stats(&self) -> QueueStats {
+        self.queue.stats()
+    }
+
+    // Queues the cmd for the driver, waiting for room if the driver is saturated.
+    pub(super) async fn send_cmd(&mut self, cmd: SwarmCmd) -> Result<()> {
+        // Counted ahead of the send, as the driver may pick the cmd up before it returns.
+        let _ = self.queue.cmd_depth.fetch_add(1, Ordering::Relaxed);
+        let priority = cmd.priority(self.priority);
+        let result = self.cmd_senders.queue(priority).send(cmd).await;
+        if result.is_err() {
+            self.queue.cmd_received();
+        }
+        Ok(result?)
+    }
+
+    // Queues the cmd for the driver, or drops it if the driver is saturated. For the cmds
+    // nothing waits on, which the node can do without.
+    pub(super) async fn send_cmd_or_shed(&mut self, cmd: SwarmCmd) -> Result<()> {
+        if self.queue.cmd_depth.load(Ordering::Relaxed) >= self.cmd_queue_size {
+            warn!("Shedding {cmd:?}, the network driver is saturated");
+            let _ = self.queue.cmds_shed.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        self.send_cmd(cmd).await
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    // Queues the event for the upper layers without waiting on them, so that the swarm keeps
+    // being driven when they fall behind. The messages of peers are shed once the queue is
+    // full, the dropped requests failing at the peers, while the events on the state of the
+    // connections are only shed once the headroom left for them is full as well.
+    pub(super) fn send_event(&mut self, event: NetworkEvent) -> Result<()> {
+        let from_peer = matches!(
+            event,
+            NetworkEvent::RequestReceived { .. } | NetworkEvent::EventReceived { .. }
+        );
+        if from_peer && self.queue.event_depth.load(Ordering::Relaxed) >= self.event_queue_size {
+            warn!("Shedding {event:?}, the node is saturated");
+            let _ = self.queue.events_shed.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        let _ = self.queue.event_depth.fetch_add(1, Ordering::Relaxed);
+        match self.event_sender.try_send(event) {
+            Ok(()) => Ok(()),
+            Err(err) if err.is_full() => {
+                self.queue.event_received();
+                warn!("Shedding {:?}, the node is saturated", err.into_inner());
+                let _ = self.queue.events_shed.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(err) => {
+                self.queue.event_received();
+                Err(err.into_send_error().into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{swarm::MockSwarm, SwarmConfig};
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn events_are_shed_rather_than_blocking_the_driver() -> Result<()> {
+        let (_network, mut events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig {
+                event_queue_size: 0,
+                ..Default::default()
+            },
+        );
+
+        // The channel holds one more event than its size, for its sender.
+        let queued = EVENT_QUEUE_HEADROOM + 1;
+        for _ in 0..queued + 2 {
+            driver.send_event(NetworkEvent::PeerDiscovered)?;
+        }
+        assert_eq!(
+            driver.queue.stats(),
+            QueueStats {
+                event_depth: queued,
+                events_shed: 2,
+                ..Default::default()
+            }
+        );
+
+        // Picking an event up makes room for the next one.
+        assert!(events.next().now_or_never().flatten().is_some());
+        driver.send_event(NetworkEvent::PeerDiscovered)?;
+        assert_eq!(driver.queue.stats().event_depth, queued);
+        assert_eq!(driver.queue.stats().events_shed, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    error::{Error, Result},
+    Network, SwarmCmd,
+};
+use crate::protocol::{
+    error::Error as ProtocolError,
+    messages::{QueryResponse, Replica, Response, SignedResponse},
+};
+use futures::channel::oneshot;
+use libp2p::PeerId;
+use xor_name::XorName;
+
+/// A caller waiting on the response to a request sent to a peer.
+#[derive(Debug)]
+pub(crate) enum PendingRequest {
+    /// Waiting on the response as it is.
+    Response(oneshot::Sender<Result<SignedResponse>>),
+    /// Waiting on the record held by the peer at the key.
+    Record {
+        key: XorName,
+        sender: oneshot::Sender<Result<Replica>>,
+    },
+}
+
+impl PendingRequest {
+    /// Hands the response, or the failure to get one, over to the caller.
+    pub(crate) fn complete(self, peer: PeerId, result: Result<SignedResponse>) {
+        // The caller may have given up on the request.
+        match self {
+            Self::Response(sender) => {
+                let _ = sender.send(result);
+            }
+            Self::Record { key, sender } => {
+                let _ = sender.send(result.and_then(|resp| record_from(peer, key, resp)));
+            }
+        }
+    }
+}
+
+// The record returned by the peer, checked to be the one at the key.
+fn record_from(peer: PeerId, key: XorName, resp: SignedResponse) -> Result<Replica> {
+    match resp.response {
+        Response::Query(QueryResponse::GetRecord(Ok(replica))) => {
+            if replica.name() == Some(key) {
+                Ok(replica)
+            } else {
+                Err(Error::InvalidRecord { peer, key })
+            }
+        }
+        Response::Query(QueryResponse::GetRecord(Err(ProtocolError::RecordNotFound(_)))) => {
+            Err(Error::RecordNotHeld { peer, key })
+        }
+        other => Err(Error::Other(format!(
+            "Unexpected response from {peer:?} to a get of record {key:?}: {other:?}"
+        ))),
+    }
+}
+
+impl Network {
+    /// Fetches the record at the key from the given peer, rather than through a lookup of
+    /// its close group, e.g. for replication repairs and audits to check a specific holder.
+    ///
+    /// Fails with [`Error::RecordNotHeld`] if the peer does not hold it.
+    pub async fn get_record_from_peer(&mut self, peer: PeerId, key: XorName) -> Result<Replica> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_cmd(SwarmCmd::GetRecordFromPeer { peer, key, sender })
+            .await?;
+        receiver.await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::{swarm::MockSwarm, SwarmConfig, SwarmDriver},
+        protocol::messages::{Query, Request},
+    };
+    use bytes::Bytes;
+    use eyre::Result;
+    use libp2p::identity::Keypair;
+
+    fn signed(response: Response, keypair: &Keypair) -> Result<SignedResponse> {
+        Ok(SignedResponse::new(response, keypair)?)
+    }
+
+    #[test]
+    fn record_is_fetched_from_the_peer() -> Result<()> {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let peer = PeerId::random();
+        let key = XorName::from_content(b"record");
+        let (sender, mut receiver) = oneshot::channel();
+
+        driver.handle_command(SwarmCmd::GetRecordFromPeer { peer, key, sender })?;
+
+        assert_eq!(
+            driver.swarm.requests,
+            vec![(peer, Request::Query(Query::GetRecord(key)))]
+        );
+        assert!(driver.pending_requests.contains_key(&1));
+        assert!(receiver.try_recv()?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn record_held_is_returned() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let value = Bytes::from_static(b"record");
+        let key = XorName::from_content(&value);
+        let (sender, mut receiver) = oneshot::channel();
+        let resp = signed(
+            Response::Query(QueryResponse::GetRecord(Ok(Replica::Chunk(value.clone())))),
+            &keypair,
+        )?;
+
+        PendingRequest::Record { key, sender }.complete(PeerId::random(), Ok(resp));
+
+        assert_eq!(
+            receiver.try_recv()?.map(|r| r.ok()),
+            Some(Some(Replica::Chunk(value)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn record_not_held_is_a_typed_error() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let key = XorName::from_content(b"record");
+        let peer = PeerId::random();
+        let (sender, mut receiver) = oneshot::channel();
+        let resp = signed(
+            Response::Query(QueryResponse::GetRecord(Err(
+                ProtocolError::RecordNotFound(key),
+            ))),
+            &keypair,
+        )?;
+
+        PendingRequest::Record { key, sender }.complete(peer, Ok(resp));
+
+        match receiver.try_recv()? {
+            Some(Err(Error::RecordNotHeld { peer: p, key: k })) => {
+                assert_eq!((p, k), (peer, key));
+            }
+            other => panic!("Unexpected outcome: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn record_other_than_the_key_is_invalid() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let key = XorName::from_content(b"record");
+        let (sender, mut receiver) = oneshot::channel();
+        let resp = signed(
+            Response::Query(QueryResponse::GetRecord(Ok(Replica::Chunk(
+                Bytes::from_static(b"other record"),
+            )))),
+            &keypair,
+        )?;
+
+        PendingRequest::Record { key, sender }.complete(PeerId::random(), Ok(resp));
+
+        assert!(matches!(
+            receiver.try_recv()?,
+            Some(Err(Error::InvalidRecord { .. }))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Network;
+use libp2p::relay;
+use serde::Serialize;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tracing::{debug, info};
+
+/// The max number of peers holding a reservation at once, by default.
+pub const DEFAULT_RELAY_MAX_RESERVATIONS: usize = 128;
+/// The max number of circuits relayed at once, by default.
+pub const DEFAULT_RELAY_MAX_CIRCUITS: usize = 16;
+/// How long a circuit is relayed for at most, by default.
+pub const DEFAULT_RELAY_MAX_CIRCUIT_DURATION: Duration = Duration::from_secs(120);
+/// The max number of bytes relayed in each direction of a circuit, by default.
+pub const DEFAULT_RELAY_MAX_CIRCUIT_BYTES: u64 = 1 << 17;
+
+/// The limits of a node relaying the connections of peers behind NATs, for the node not to
+/// be drained by them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelayConfig {
+    /// The max number of peers holding a reservation at once, i.e. reachable through us.
+    pub max_reservations: usize,
+    /// The max number of reservations held by a single peer.
+    pub max_reservations_per_peer: usize,
+    /// How long a reservation lasts before it is to be renewed.
+    pub reservation_duration: Duration,
+    /// The max number of circuits relayed at once.
+    pub max_circuits: usize,
+    /// The max number of circuits relayed at once for a single peer.
+    pub max_circuits_per_peer: usize,
+    /// How long a circuit is relayed for at most, before it is closed.
+    pub max_circuit_duration: Duration,
+    /// The max number of bytes relayed in each direction of a circuit, before it is closed.
+    pub max_circuit_bytes: u64,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            max_reservations: DEFAULT_RELAY_MAX_RESERVATIONS,
+            max_reservations_per_peer: 4,
+            reservation_duration: Duration::from_secs(60 * 60),
+            max_circuits: DEFAULT_RELAY_MAX_CIRCUITS,
+            max_circuits_per_peer: 4,
+            max_circuit_duration: DEFAULT_RELAY_MAX_CIRCUIT_DURATION,
+            max_circuit_bytes: DEFAULT_RELAY_MAX_CIRCUIT_BYTES,
+        }
+    }
+}
+
+impl RelayConfig {
+    // The config of the relay behaviour, with the rate limits of libp2p.
+    pub(super) fn to_behaviour_config(&self) -> relay::Config {
+        relay::Config {
+            max_reservations: self.max_reservations,
+            max_reservations_per_peer: self.max_reservations_per_peer,
+            reservation_duration: self.reservation_duration,
+            max_circuits: self.max_circuits,
+            max_circuits_per_peer: self.max_circuits_per_peer,
+            max_circuit_duration: self.max_circuit_duration,
+            max_circuit_bytes: self.max_circuit_bytes,
+            ..Default::default()
+        }
+    }
+}
+
+/// The usage of the relay since startup, all zero for nodes not relaying.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RelayStats {
+    /// The reservations currently held by peers.
+    pub active_reservations: u64,
+    /// The reservations accepted, renewals excluded.
+    pub reservations_accepted: u64,
+    /// The reservations denied, e.g. for the limits being reached.
+    pub reservations_denied: u64,
+    /// The circuits currently relayed.
+    pub active_circuits: u64,
+    /// The circuits accepted.
+    pub circuits_accepted: u64,
+    /// The circuits denied, e.g. for the limits being reached or the destination holding
+    /// no reservation.
+    pub circuits_denied: u64,
+    /// The circuits closed, for their limits being reached among other reasons.
+    pub circuits_closed: u64,
+}
+
+// The counters behind the `RelayStats`, shared by the `Network` and the driver.
+#[derive(Debug, Default)]
+pub(super) struct RelayCounters {
+    active_reservations: AtomicU64,
+    reservations_accepted: AtomicU64,
+    reservations_denied: AtomicU64,
+    active_circuits: AtomicU64,
+    circuits_accepted: AtomicU64,
+    circuits_denied: AtomicU64,
+    circuits_closed: AtomicU64,
+}
+
+impl RelayCounters {
+    pub(super) fn stats(&self) -> RelayStats {
+        RelayStats {
+            active_reservations: self.active_reservations.load(Ordering::Relaxed),
+            reservations_accepted: self.reservations_accepted.load(Ordering::Relaxed),
+            reservations_denied: self.reservations_denied.load(Ordering::Relaxed),
+            active_circuits: self.active_circuits.load(Ordering::Relaxed),
+            circuits_accepted: self.circuits_accepted.load(Ordering::Relaxed),
+            circuits_denied: self.circuits_denied.load(Ordering::Relaxed),
+            circuits_closed: self.circuits_closed.load(Ordering::Relaxed),
+        }
+    }
+
+    // Counts the reservations and circuits as the relay reports them.
+    pub(super) fn on_event(&self, event: &relay::Event) {
+        match event {
+            relay::Event::ReservationReqAccepted {
+                src_peer_id,
+                renewed,
+            } => {
+                debug!("Relay reservation of {src_peer_id:?} accepted, renewal: {renewed}");
+                if !renewed {
+                    increment(&self.reservations_accepted);
+                    increment(&self.active_reservations);
+                }
+            }
+            relay::Event::ReservationReqDenied { src_peer_id } => {
+                info!("Relay reservation of {src_peer_id:?} denied");
+                increment(&self.reservations_denied);
+            }
+            relay::Event::ReservationTimedOut { src_peer_id } => {
+                debug!("Relay reservation of {src_peer_id:?} timed out");
+                decrement(&self.active_reservations);
+            }
+            relay::Event::CircuitReqAccepted {
+                src_peer_id,
+                dst_peer_id,
+            } => {
+                debug!("Relaying a circuit from {src_peer_id:?} to {dst_peer_id:?}");
+                increment(&self.circuits_accepted);
+                increment(&self.active_circuits);
+            }
+            relay::Event::CircuitReqDenied {
+                src_peer_id,
+                dst_peer_id,
+            } => {
+                info!("Relay circuit from {src_peer_id:?} to {dst_peer_id:?} denied");
+                increment(&self.circuits_denied);
+            }
+            relay::Event::CircuitClosed {
+                src_peer_id,
+                dst_peer_id,
+                error,
+            } => {
+                debug!("Relay circuit from {src_peer_id:?} to {dst_peer_id:?} closed: {error:?}");
+                increment(&self.circuits_closed);
+                decrement(&self.active_circuits);
+            }
+            other => debug!("Relay event: {other:?}"),
+        }
+    }
+}
+
+fn increment(counter: &AtomicU64) {
+    let _ = counter.fetch_add(1, Ordering::Relaxed);
+}
+
+// Decrements the gauge, without wrapping below zero on events of state set before it.
+fn decrement(counter: &AtomicU64) {
+    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+        Some(count.saturating_sub(1))
+    });
+}
+
+impl Network {
+    /// The usage of the relay, for nodes relaying the connections of peers behind NATs.
+    pub fn relay_stats(&self) -> RelayStats {
+        self.relay.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::PeerId;
+
+    #[test]
+    fn renewals_are_not_counted_as_new_reservations() {
+        let counters = RelayCounters::default();
+        let src_peer_id = PeerId::random();
+        counters.on_event(&relay::Event::ReservationReqAccepted {
+            src_peer_id,
+            renewed: false,
+        });
+        counters.on_event(&relay::Event::ReservationReqAccepted {
+            src_peer_id,
+            renewed: true,
+        });
+
+        let stats = counters.stats();
+        assert_eq!(stats.reservations_accepted, 1);
+        assert_eq!(stats.active_reservations, 1);
+    }
+
+    #[test]
+    fn closed_circuits_are_no_longer_active() {
+        let counters = RelayCounters::default();
+        let (src_peer_id, dst_peer_id) = (PeerId::random(), PeerId::random());
+        for _ in 0..2 {
+            counters.on_event(&relay::Event::CircuitReqAccepted {
+                src_peer_id,
+                dst_peer_id,
+            });
+        }
+        counters.on_event(&relay::Event::CircuitClosed {
+            src_peer_id,
+            dst_peer_id,
+            error: None,
+        });
+
+        let stats = counters.stats();
+        assert_eq!(stats.circuits_accepted, 2);
+        assert_eq!(stats.circuits_closed, 1);
+        assert_eq!(stats.active_circuits, 1);
+    }
+
+    #[test]
+    fn gauges_do_not_wrap_below_zero() {
+        let counters = RelayCounters::default();
+        counters.on_event(&relay::Event::ReservationTimedOut {
+            src_peer_id: PeerId::random(),
+        });
+        assert_eq!(counters.stats().active_reservations, 0);
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{dysfunction::Failure, swarm::SwarmOps, SwarmDriver};
+use libp2p::PeerId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// The penalty at which a peer is demoted from the routing table and blocked.
+pub const BLOCK_THRESHOLD: f64 = 100.0;
+/// How long a peer stays blocked for.
+pub const BLOCK_DURATION: Duration = Duration::from_secs(30 * 60);
+/// The time it takes for the penalty of a peer to halve.
+pub const PENALTY_HALF_LIFE: Duration = Duration::from_secs(10 * 60);
+
+// Penalties decayed below this are forgotten.
+const MIN_PENALTY: f64 = 1.0;
+
+/// A misbehaviour of a peer, adding to its penalty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Misbehaviour {
+    /// The peer failed to respond to a request, e.g. the connection was dropped.
+    FailedResponse,
+    /// The peer did not respond in time.
+    Timeout,
+    /// The peer sent data that failed validation, e.g. a badly signed msg.
+    InvalidData,
+    /// The peer does not speak our protocol, or breaks it.
+    ProtocolError,
+    /// The peer could not prove it holds a chunk of its close group.
+    FailedAudit,
+}
+
+impl Misbehaviour {
+    fn penalty(self) -> f64 {
+        match self {
+            Self::FailedResponse | Self::Timeout => 10.0,
+            Self::InvalidData | Self::ProtocolError | Self::FailedAudit => 50.0,
+        }
+    }
+}
+
+/// The reputation of a peer, with the penalty it has accrued for its misbehaviours.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerReputation {
+    /// The peer.
+    pub peer_id: PeerId,
+    /// The penalty of the peer, decayed to the present time.
+    pub penalty: f64,
+    /// Whether the peer is currently blocked.
+    pub blocked: bool,
+}
+
+/// The penalties of the peers that misbehaved, decaying over time, and the peers blocked
+/// for having crossed the [`BLOCK_THRESHOLD`].
+#[derive(Debug, Default)]
+pub(crate) struct Reputations {
+    penalties: HashMap<PeerId, Penalty>,
+    blocked_until: HashMap<PeerId, Instant>,
+}
+
+#[derive(Debug)]
+struct Penalty {
+    value: f64,
+    updated_at: Instant,
+}
+
+impl Penalty {
+    // Decayed in whole seconds, for misbehaviours in quick succession to add up in full.
+    fn decayed(&self, now: Instant) -> f64 {
+        let half_lives = now.saturating_duration_since(self.updated_at).as_secs() as f64
+            / PENALTY_HALF_LIFE.as_secs_f64();
+        self.value * 0.5_f64.powf(half_lives)
+    }
+}
+
+impl Reputations {
+    /// Adds the misbehaviour to the penalty of the peer, returning whether the peer is to be
+    /// blocked for it.
+    pub(crate) fn record(&mut self, peer_id: PeerId, misbehaviour: Misbehaviour) -> bool {
+        self.record_at(peer_id, misbehaviour, Instant::now())
+    }
+
+    /// Whether the peer is blocked.
+    pub(crate) fn is_blocked(&mut self, peer_id: &PeerId) -> bool {
+        self.is_blocked_at(peer_id, Instant::now())
+    }
+
+    /// The reputations of the peers with a penalty, or blocked.
+    pub(crate) fn reputations(&mut self) -> Vec<PeerReputation> {
+        self.reputations_at(Instant::now())
+    }
+
+    fn record_at(&mut self, peer_id: PeerId, misbehaviour: Misbehaviour, now: Instant) -> bool {
+        let penalty = self.penalties.entry(peer_id).or_insert(Penalty {
+            value: 0.0,
+            updated_at: now,
+        });
+        penalty.value = penalty.decayed(now) + misbehaviour.penalty();
+        penalty.updated_at = now;
+        let value = penalty.value;
+        debug!("{peer_id:?} penalised for {misbehaviour:?}, now at {value:.1}");
+
+        if value < BLOCK_THRESHOLD || self.is_blocked_at(&peer_id, now) {
+            return false;
+        }
+        // The peer starts afresh once unblocked.
+        let _ = self.penalties.remove(&peer_id);
+        let _ = self.blocked_until.insert(peer_id, now + BLOCK_DURATION);
+        true
+    }
+
+    fn is_blocked_at(&mut self, peer_id: &PeerId, now: Instant) -> bool {
+        match self.blocked_until.get(peer_id) {
+            Some(until) if *until > now => true,
+            Some(_) => {
+                let _ = self.blocked_until.remove(peer_id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn reputations_at(&mut self, now: Instant) -> Vec<PeerReputation> {
+        self.penalties
+            .retain(|_, penalty| penalty.decayed(now) >= MIN_PENALTY);
+        self.blocked_until.retain(|_, until| *until > now);
+
+        let penalised = self
+            .penalties
+            .iter()
+            .map(|(peer_id, penalty)| PeerReputation {
+                peer_id: *peer_id,
+                penalty: penalty.decayed(now),
+                blocked: self.blocked_until.contains_key(peer_id),
+            });
+        let blocked = self
+            .blocked_until
+            .keys()
+            .filter(|peer_id| !self.penalties.contains_key(peer_id))
+            .map(|peer_id| PeerReputation {
+                peer_id: *peer_id,
+                penalty: 0.0,
+                blocked: true,
+            });
+        penalised.chain(blocked).collect()
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Penalises the peer for the misbehaviour, demoting it from the routing table and
+    /// disconnecting from it once it crosses the [`BLOCK_THRESHOLD`].
+    pub(crate) fn penalise(&mut self, peer_id: PeerId, misbehaviour: Misbehaviour) {
+        match misbehaviour {
+            Misbehaviour::FailedResponse | Misbehaviour::Timeout => {
+                self.record_failure(peer_id, Failure::MissedResponse)
+            }
+            Misbehaviour::InvalidData => self.record_failure(peer_id, Failure::InvalidRecord),
+            Misbehaviour::FailedAudit => self.record_failure(peer_id, Failure::FailedAudit),
+            Misbehaviour::ProtocolError => {}
+        }
+        if self.reputations.record(peer_id, misbehaviour) {
+            warn!("Blocking {peer_id:?} for {BLOCK_DURATION:?}, after {misbehaviour:?}");
+            self.swarm.remove_from_routing_table(&peer_id);
+            self.swarm.disconnect(peer_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peers_are_blocked_once_crossing_the_threshold() {
+        let mut reputations = Reputations::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..9 {
+            assert!(!reputations.record_at(peer_id, Misbehaviour::Timeout, now));
+        }
+        assert!(reputations.record_at(peer_id, Misbehaviour::Timeout, now));
+        assert!(reputations.is_blocked_at(&peer_id, now));
+        // Further misbehaviours do not block it again while blocked.
+        for _ in 0..2 {
+            assert!(!reputations.record_at(peer_id, Misbehaviour::ProtocolError, now));
+        }
+    }
+
+    #[test]
+    fn blocks_are_lifted_after_their_duration() {
+        let mut reputations = Reputations::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+        for _ in 0..2 {
+            let _ = reputations.record_at(peer_id, Misbehaviour::ProtocolError, now);
+        }
+
+        assert!(reputations.is_blocked_at(&peer_id, now + BLOCK_DURATION / 2));
+        assert!(!reputations.is_blocked_at(&peer_id, now + BLOCK_DURATION));
+    }
+
+    #[test]
+    fn penalties_decay_over_time() {
+        let mut reputations = Reputations::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+        let _ = reputations.record_at(peer_id, Misbehaviour::InvalidData, now);
+
+        let halved = reputations.reputations_at(now + PENALTY_HALF_LIFE);
+        assert_eq!(halved.len(), 1);
+        assert!((halved[0].penalty - 25.0).abs() < 0.01);
+        assert!(!halved[0].blocked);
+
+        // A penalty decayed away is forgotten.
+        assert!(reputations
+            .reputations_at(now + 10 * PENALTY_HALF_LIFE)
+            .is_empty());
+        // Two misbehaviours a half-life apart do not add up to a block.
+        let _ = reputations.record_at(peer_id, Misbehaviour::InvalidData, now);
+        assert!(!reputations.record_at(
+            peer_id,
+            Misbehaviour::InvalidData,
+            now + PENALTY_HALF_LIFE
+        ));
+    }
+}
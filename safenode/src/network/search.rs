@@ -0,0 +1,157 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    close_group::closest_peers,
+    error::{Error, Result},
+    Network, CLOSE_GROUP_SIZE,
+};
+use crate::protocol::messages::{Request, SignedResponse};
+use async_std::future::timeout;
+use futures::future;
+use libp2p::PeerId;
+use std::{collections::HashSet, time::Duration};
+use tracing::{debug, trace, warn};
+use xor_name::XorName;
+
+/// The number of attempts made by default at finding a record, the first one being with its
+/// close group.
+pub const DEFAULT_SEARCH_ATTEMPTS: usize = 3;
+/// How long the peers are given to respond by default at the first attempt.
+pub const DEFAULT_SEARCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the search for a record is widened when its close group does not return it.
+///
+/// With every attempt, the closest peers are looked up again, `CLOSE_GROUP_SIZE` more of them
+/// are asked, and they are given twice as long to respond.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchConfig {
+    /// The number of attempts before declaring the record missing.
+    pub attempts: usize,
+    /// How long the peers are given to respond at the first attempt.
+    pub timeout: Duration,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            attempts: DEFAULT_SEARCH_ATTEMPTS,
+            timeout: DEFAULT_SEARCH_TIMEOUT,
+        }
+    }
+}
+
+impl SearchConfig {
+    // The number of peers asked at the given attempt, counting from 0, and how long they are
+    // given to respond.
+    fn widened(&self, attempt: usize) -> (usize, Duration) {
+        let factor = 2u32.saturating_pow(attempt as u32);
+        (
+            CLOSE_GROUP_SIZE.saturating_mul(attempt + 1),
+            self.timeout.saturating_mul(factor),
+        )
+    }
+}
+
+impl Network {
+    /// Send the `Request` to the peers closest to the given `XorName` until one of them
+    /// returns a `Response` accepted by `found`, widening the search with every attempt, as
+    /// configured with [`SearchConfig`].
+    ///
+    /// Returns all the peers asked along with their responses, or the error encountered while
+    /// sending to them. A peer having responded is not asked again.
+    pub async fn search_closest(
+        &self,
+        xor_name: XorName,
+        req: Request,
+        found: impl Fn(&SignedResponse) -> bool,
+    ) -> Result<Vec<(PeerId, Result<SignedResponse>)>> {
+        let mut responses = vec![];
+        let mut responded = HashSet::new();
+        for attempt in 0..self.search.attempts.max(1) {
+            let (peer_count, timeout_duration) = self.search.widened(attempt);
+            let closest = self
+                .clone()
+                .get_closest_peers(xor_name)
+                .await?
+                .into_iter()
+                .map(|(peer, _)| peer);
+            let peers: Vec<PeerId> = closest_peers(&xor_name, closest, peer_count)
+                .into_iter()
+                .filter(|peer| !responded.contains(peer))
+                .collect();
+            if peers.is_empty() {
+                break;
+            }
+            if attempt > 0 {
+                debug!(
+                    "Widening the search for {xor_name:?} to {} peers, attempt {}",
+                    peers.len(),
+                    attempt + 1
+                );
+            }
+            trace!("Sending {req:?} to the peers closest to {xor_name:?}");
+
+            let requests = peers.into_iter().map(|peer| {
+                let mut network = self.clone();
+                let req = req.clone();
+                async move {
+                    let result =
+                        match timeout(timeout_duration, network.send_request(req, peer)).await {
+                            Ok(result) => result,
+                            Err(_) => Err(Error::RequestTimeout(peer)),
+                        };
+                    (peer, result)
+                }
+            });
+            for (peer, result) in future::join_all(requests).await {
+                match &result {
+                    Ok(_) => {
+                        let _ = responded.insert(peer);
+                    }
+                    Err(err) => warn!("Failed to get response from {peer:?}: {err}"),
+                }
+                responses.push((peer, result));
+            }
+
+            if responses
+                .iter()
+                .filter_map(|(_, result)| result.as_ref().ok())
+                .any(&found)
+            {
+                break;
+            }
+        }
+        Ok(responses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_is_widened_with_every_attempt() {
+        let config = SearchConfig {
+            attempts: 3,
+            timeout: Duration::from_secs(10),
+        };
+        assert_eq!(
+            config.widened(0),
+            (CLOSE_GROUP_SIZE, Duration::from_secs(10))
+        );
+        assert_eq!(
+            config.widened(1),
+            (2 * CLOSE_GROUP_SIZE, Duration::from_secs(20))
+        );
+        assert_eq!(
+            config.widened(2),
+            (3 * CLOSE_GROUP_SIZE, Duration::from_secs(40))
+        );
+    }
+}
@@ -0,0 +1,414 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    error::{Error, Result},
+    event::NodeBehaviour,
+    msg::MsgHeader,
+    CLOSE_GROUP_SIZE,
+};
+use crate::protocol::messages::{Request, SignedResponse};
+use libp2p::{
+    kad::{
+        kbucket::{Distance, Key},
+        record::store::RecordStore,
+        QueryId, Record, K_VALUE,
+    },
+    multiaddr::Protocol,
+    request_response::{RequestId, ResponseChannel},
+    swarm::AddressScore,
+    Multiaddr, PeerId, Swarm,
+};
+use std::hash::Hash;
+use xor_name::XorName;
+
+/// The operations on the libp2p swarm that the `SwarmCmd`s are carried out with.
+///
+/// Abstracted over for the handling of the cmds, and the bookkeeping of the pending ones,
+/// to be tested against a test double instead of a real swarm.
+pub(crate) trait SwarmOps {
+    /// Identifies a Kademlia query, for its outcome to be matched to the cmd that started it.
+    type QueryId: Copy + Eq + Hash;
+    /// Identifies a request sent to a peer, for its response to be matched to the cmd that sent it.
+    type RequestId: Copy + Eq + Hash;
+
+    /// Starts listening on the address.
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<()>;
+
+    /// Adds the address of the peer to the routing table.
+    fn add_to_routing_table(&mut self, peer_id: PeerId, peer_addr: Multiaddr);
+
+    /// Removes the peer from the routing table.
+    fn remove_from_routing_table(&mut self, peer_id: &PeerId);
+
+    /// Closes the connections to the peer.
+    fn disconnect(&mut self, peer_id: PeerId);
+
+    /// Dials the peer at the address.
+    fn dial(&mut self, peer_id: PeerId, peer_addr: Multiaddr) -> Result<()>;
+
+    /// Starts a Kademlia bootstrap, looking ourselves up then refreshing the buckets of the
+    /// routing table. Fails if the routing table holds no peer to start from.
+    fn bootstrap(&mut self) -> Result<Self::QueryId>;
+
+    /// Starts announcing that we provide the data at `xor_name`.
+    fn start_providing(&mut self, xor_name: XorName) -> Result<Self::QueryId>;
+
+    /// Stores the record in the Kademlia record store.
+    fn put_record(&mut self, record: Record) -> Result<()>;
+
+    /// Starts looking for the providers of the data at `xor_name`.
+    fn get_providers(&mut self, xor_name: XorName) -> Self::QueryId;
+
+    /// Starts looking for the peers closest to `xor_name`.
+    fn get_closest_peers(&mut self, xor_name: XorName) -> Self::QueryId;
+
+    /// Sends the request to the peer.
+    fn send_request(&mut self, peer: PeerId, header: MsgHeader, req: Request) -> Self::RequestId;
+
+    /// Sends the response to a request, over the channel it was received on.
+    fn send_response(
+        &mut self,
+        channel: ResponseChannel<SignedResponse>,
+        resp: SignedResponse,
+    ) -> Result<()>;
+
+    /// The peers we are connected to.
+    fn connected_peers(&self) -> Vec<PeerId>;
+
+    /// The addresses we are listening on.
+    fn listen_addrs(&self) -> Vec<Multiaddr>;
+
+    /// Adds an address we are reached at from outside of the local network, to be
+    /// advertised to peers.
+    fn add_external_addr(&mut self, addr: Multiaddr);
+
+    /// The number of peers in the routing table.
+    fn routing_table_size(&mut self) -> usize;
+
+    /// The peers of the routing table closest to us.
+    fn close_group(&mut self) -> Vec<PeerId>;
+
+    /// The distance from us to the `K_VALUE`th closest peer of our routing table, within which
+    /// the records we are responsible for lie. `None` while the routing table holds fewer peers.
+    fn responsibility_range(&mut self) -> Option<Distance>;
+
+    /// Whether the peer is in our routing table.
+    fn is_in_routing_table(&mut self, peer_id: &PeerId) -> bool;
+
+    /// The addresses the peer is known at in our routing table, none if it is not in it.
+    fn peer_addrs(&mut self, peer_id: &PeerId) -> Vec<Multiaddr>;
+}
+
+impl SwarmOps for Swarm<NodeBehaviour> {
+    type QueryId = QueryId;
+    type RequestId = RequestId;
+
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<()> {
+        let _listener_id = Swarm::listen_on(self, addr)?;
+        Ok(())
+    }
+
+    fn add_to_routing_table(&mut self, peer_id: PeerId, peer_addr: Multiaddr) {
+        let _routing_update = self
+            .behaviour_mut()
+            .kademlia
+            .add_address(&peer_id, peer_addr);
+    }
+
+    fn remove_from_routing_table(&mut self, peer_id: &PeerId) {
+        let _ = self.behaviour_mut().kademlia.remove_peer(peer_id);
+    }
+
+    fn disconnect(&mut self, peer_id: PeerId) {
+        let _ = self.disconnect_peer_id(peer_id);
+    }
+
+    fn dial(&mut self, peer_id: PeerId, peer_addr: Multiaddr) -> Result<()> {
+        Swarm::dial(self, peer_addr.with(Protocol::P2p(peer_id.into())))?;
+        Ok(())
+    }
+
+    fn bootstrap(&mut self) -> Result<QueryId> {
+        Ok(self.behaviour_mut().kademlia.bootstrap()?)
+    }
+
+    fn start_providing(&mut self, xor_name: XorName) -> Result<QueryId> {
+        Ok(self
+            .behaviour_mut()
+            .kademlia
+            .start_providing(xor_name.0.to_vec().into())?)
+    }
+
+    fn put_record(&mut self, record: Record) -> Result<()> {
+        Ok(self.behaviour_mut().kademlia.store_mut().put(record)?)
+    }
+
+    fn get_providers(&mut self, xor_name: XorName) -> QueryId {
+        self.behaviour_mut()
+            .kademlia
+            .get_providers(xor_name.0.to_vec().into())
+    }
+
+    fn get_closest_peers(&mut self, xor_name: XorName) -> QueryId {
+        self.behaviour_mut()
+            .kademlia
+            .get_closest_peers(xor_name.0.to_vec())
+    }
+
+    fn send_request(&mut self, peer: PeerId, header: MsgHeader, req: Request) -> RequestId {
+        self.behaviour_mut()
+            .request_response
+            .send_request(&peer, (header, req))
+    }
+
+    fn send_response(
+        &mut self,
+        channel: ResponseChannel<SignedResponse>,
+        resp: SignedResponse,
+    ) -> Result<()> {
+        self.behaviour_mut()
+            .request_response
+            .send_response(channel, resp)
+            .map_err(|_| Error::Other("Connection to peer to be still open.".to_string()))
+    }
+
+    fn connected_peers(&self) -> Vec<PeerId> {
+        Swarm::connected_peers(self).copied().collect()
+    }
+
+    fn listen_addrs(&self) -> Vec<Multiaddr> {
+        self.listeners().cloned().collect()
+    }
+
+    fn add_external_addr(&mut self, addr: Multiaddr) {
+        let _ = self.add_external_address(addr, AddressScore::Infinite);
+    }
+
+    fn routing_table_size(&mut self) -> usize {
+        self.behaviour_mut()
+            .kademlia
+            .kbuckets()
+            .map(|bucket| bucket.num_entries())
+            .sum()
+    }
+
+    fn close_group(&mut self) -> Vec<PeerId> {
+        let mut peers = routing_table_by_distance(self);
+        peers.truncate(CLOSE_GROUP_SIZE);
+        peers
+    }
+
+    fn responsibility_range(&mut self) -> Option<Distance> {
+        let local_key = Key::from(*self.local_peer_id());
+        routing_table_by_distance(self)
+            .get(K_VALUE.get() - 1)
+            .map(|peer| local_key.distance(&Key::from(*peer)))
+    }
+
+    fn is_in_routing_table(&mut self, peer_id: &PeerId) -> bool {
+        self.behaviour_mut()
+            .kademlia
+            .kbucket(*peer_id)
+            .is_some_and(|bucket| {
+                bucket
+                    .iter()
+                    .any(|entry| entry.node.key.preimage() == peer_id)
+            })
+    }
+
+    fn peer_addrs(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.behaviour_mut()
+            .kademlia
+            .kbucket(*peer_id)
+            .and_then(|bucket| {
+                bucket
+                    .iter()
+                    .find(|entry| entry.node.key.preimage() == peer_id)
+                    .map(|entry| entry.node.value.iter().cloned().collect())
+            })
+            .unwrap_or_default()
+    }
+}
+
+// The peers of the routing table, sorted by their distance to us.
+fn routing_table_by_distance(swarm: &mut Swarm<NodeBehaviour>) -> Vec<PeerId> {
+    let local_key = Key::from(*swarm.local_peer_id());
+    let mut peers: Vec<_> = swarm
+        .behaviour_mut()
+        .kademlia
+        .kbuckets()
+        .flat_map(|bucket| {
+            bucket
+                .iter()
+                .map(|entry| *entry.node.key.preimage())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    peers.sort_by_key(|peer| local_key.distance(&Key::from(*peer)));
+    peers
+}
+
+/// A test double of the swarm, recording the operations carried out on it.
+///
+/// Queries and requests are given sequential ids, and their outcome is up to the test to feed
+/// back, making the handling of cmds deterministic.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MockSwarm {
+    /// The last id handed out to a query or request.
+    pub(crate) next_id: u64,
+    /// Whether dials fail.
+    pub(crate) fail_dials: bool,
+    /// The addresses listened on.
+    pub(crate) listen_addrs: Vec<Multiaddr>,
+    /// The addresses added as external.
+    pub(crate) external_addrs: Vec<Multiaddr>,
+    /// The peers added to the routing table, with their address.
+    pub(crate) routing_table: Vec<(PeerId, Multiaddr)>,
+    /// The peers removed from the routing table.
+    pub(crate) removed_from_routing_table: Vec<PeerId>,
+    /// The peers disconnected from.
+    pub(crate) disconnected: Vec<PeerId>,
+    /// The peers dialed, with the address they were dialed at.
+    pub(crate) dialed: Vec<(PeerId, Multiaddr)>,
+    /// The number of bootstraps started.
+    pub(crate) bootstraps: usize,
+    /// The names we started providing.
+    pub(crate) providing: Vec<XorName>,
+    /// The records stored in the Kademlia record store.
+    pub(crate) records: Vec<Record>,
+    /// The names of the queries started, other than for providing.
+    pub(crate) queries: Vec<XorName>,
+    /// The requests sent, with the peer they were sent to.
+    pub(crate) requests: Vec<(PeerId, Request)>,
+    /// The peers reported as connected.
+    pub(crate) connected_peers: Vec<PeerId>,
+    /// The size reported for the routing table.
+    pub(crate) routing_table_size: usize,
+    /// The peers reported as our close group.
+    pub(crate) close_group: Vec<PeerId>,
+    /// The range reported for the records we are responsible for.
+    pub(crate) responsibility_range: Option<Distance>,
+}
+
+#[cfg(test)]
+impl MockSwarm {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+#[cfg(test)]
+impl SwarmOps for MockSwarm {
+    type QueryId = u64;
+    type RequestId = u64;
+
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<()> {
+        self.listen_addrs.push(addr);
+        Ok(())
+    }
+
+    fn add_to_routing_table(&mut self, peer_id: PeerId, peer_addr: Multiaddr) {
+        self.routing_table.push((peer_id, peer_addr));
+    }
+
+    fn remove_from_routing_table(&mut self, peer_id: &PeerId) {
+        self.removed_from_routing_table.push(*peer_id);
+    }
+
+    fn disconnect(&mut self, peer_id: PeerId) {
+        self.disconnected.push(peer_id);
+    }
+
+    fn dial(&mut self, peer_id: PeerId, peer_addr: Multiaddr) -> Result<()> {
+        if self.fail_dials {
+            return Err(Error::Other(format!("Failed to dial {peer_id:?}")));
+        }
+        self.dialed.push((peer_id, peer_addr));
+        Ok(())
+    }
+
+    fn bootstrap(&mut self) -> Result<u64> {
+        if self.routing_table.is_empty() {
+            return Err(Error::NoKnownPeers(libp2p::kad::NoKnownPeers()));
+        }
+        self.bootstraps += 1;
+        Ok(self.next_id())
+    }
+
+    fn start_providing(&mut self, xor_name: XorName) -> Result<u64> {
+        self.providing.push(xor_name);
+        Ok(self.next_id())
+    }
+
+    fn put_record(&mut self, record: Record) -> Result<()> {
+        self.records.push(record);
+        Ok(())
+    }
+
+    fn get_providers(&mut self, xor_name: XorName) -> u64 {
+        self.queries.push(xor_name);
+        self.next_id()
+    }
+
+    fn get_closest_peers(&mut self, xor_name: XorName) -> u64 {
+        self.queries.push(xor_name);
+        self.next_id()
+    }
+
+    fn send_request(&mut self, peer: PeerId, _header: MsgHeader, req: Request) -> u64 {
+        self.requests.push((peer, req));
+        self.next_id()
+    }
+
+    fn send_response(
+        &mut self,
+        _channel: ResponseChannel<SignedResponse>,
+        _resp: SignedResponse,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn connected_peers(&self) -> Vec<PeerId> {
+        self.connected_peers.clone()
+    }
+
+    fn listen_addrs(&self) -> Vec<Multiaddr> {
+        self.listen_addrs.clone()
+    }
+
+    fn add_external_addr(&mut self, addr: Multiaddr) {
+        self.external_addrs.push(addr);
+    }
+
+    fn routing_table_size(&mut self) -> usize {
+        self.routing_table_size
+    }
+
+    fn close_group(&mut self) -> Vec<PeerId> {
+        self.close_group.clone()
+    }
+
+    fn responsibility_range(&mut self) -> Option<Distance> {
+        self.responsibility_range
+    }
+
+    fn is_in_routing_table(&mut self, peer_id: &PeerId) -> bool {
+        self.routing_table.iter().any(|(peer, _)| peer == peer_id)
+    }
+
+    fn peer_addrs(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.routing_table
+            .iter()
+            .filter(|(peer, _)| peer == peer_id)
+            .map(|(_, addr)| addr.clone())
+            .collect()
+    }
+}
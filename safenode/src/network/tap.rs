@@ -0,0 +1,197 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{MsgHeader, Network};
+use crate::protocol::messages::{Request, SignedResponse};
+use libp2p::PeerId;
+use serde::Serialize;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+
+/// The number of msgs kept for each subscriber of the tap, beyond which the ones the
+/// subscriber has not read yet are dropped, for a slow subscriber to not hold up the swarm.
+pub const TAP_CAPACITY: usize = 1024;
+
+/// Whether a tapped msg was received from a peer or sent to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MsgDirection {
+    /// Received from the peer.
+    Inbound,
+    /// Sent to the peer.
+    Outbound,
+}
+
+/// The content of a tapped msg.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TappedPayload {
+    /// A request, along with its metadata.
+    Request(MsgHeader, Request),
+    /// A response to a request.
+    Response(SignedResponse),
+}
+
+/// A msg sent or received by the node, as captured by its tap.
+#[derive(Clone, Debug)]
+pub struct TappedMsg {
+    /// Whether the msg was received or sent.
+    pub direction: MsgDirection,
+    /// The peer the msg was received from or sent to. `None` for the responses to the
+    /// requests of peers, which libp2p sends back through the channel of the request
+    /// without telling which peer it is.
+    pub peer: Option<PeerId>,
+    /// When the msg was received, or handed over to be sent.
+    pub at: SystemTime,
+    /// The size of the msg as encoded on the wire, its length prefix aside.
+    pub size: usize,
+    /// The msg.
+    pub payload: TappedPayload,
+}
+
+// Clones the msgs through the driver into a broadcast channel, for tests to assert on the
+// wire traffic. The msgs are only cloned while someone is subscribed.
+#[derive(Clone)]
+pub(super) struct MsgTap {
+    sender: broadcast::Sender<TappedMsg>,
+}
+
+impl MsgTap {
+    pub(super) fn new() -> Self {
+        let (sender, _) = broadcast::channel(TAP_CAPACITY);
+        Self { sender }
+    }
+
+    pub(super) fn subscribe(&self) -> broadcast::Receiver<TappedMsg> {
+        self.sender.subscribe()
+    }
+
+    pub(super) fn request(
+        &self,
+        direction: MsgDirection,
+        peer: PeerId,
+        header: &MsgHeader,
+        req: &Request,
+    ) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let size = encoded_size(&(header, req));
+        self.send(
+            direction,
+            Some(peer),
+            size,
+            TappedPayload::Request(header.clone(), req.clone()),
+        );
+    }
+
+    pub(super) fn response(
+        &self,
+        direction: MsgDirection,
+        peer: Option<PeerId>,
+        resp: &SignedResponse,
+    ) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let size = encoded_size(resp);
+        self.send(direction, peer, size, TappedPayload::Response(resp.clone()));
+    }
+
+    fn send(
+        &self,
+        direction: MsgDirection,
+        peer: Option<PeerId>,
+        size: usize,
+        payload: TappedPayload,
+    ) {
+        // The subscribers may all have gone since they were counted.
+        let _ = self.sender.send(TappedMsg {
+            direction,
+            peer,
+            at: SystemTime::now(),
+            size,
+            payload,
+        });
+    }
+}
+
+// The msgs are encoded as by the codec, which never fails on them.
+fn encoded_size<T: Serialize>(msg: &T) -> usize {
+    rmp_serde::to_vec(msg).map_or(0, |bytes| bytes.len())
+}
+
+impl Network {
+    /// Subscribes to the msgs sent and received by the node from now on, for tests to make
+    /// assertions about the wire traffic, or to sniff it while debugging.
+    ///
+    /// A subscriber falling more than [`TAP_CAPACITY`] msgs behind misses the oldest ones.
+    pub fn tap_msgs(&self) -> broadcast::Receiver<TappedMsg> {
+        self.tap.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::{command::SwarmCmd, swarm::MockSwarm, SwarmConfig, SwarmDriver},
+        protocol::messages::Query,
+        storage::chunks::ChunkAddress,
+    };
+    use eyre::Result;
+    use futures::channel::oneshot;
+    use libp2p::identity::Keypair;
+    use xor_name::XorName;
+
+    #[test]
+    fn sent_requests_are_tapped() -> Result<()> {
+        let (network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let mut tap = network.tap_msgs();
+        let peer = PeerId::random();
+        let req = Request::Query(Query::GetChunk(ChunkAddress(XorName::from_content(
+            b"chunk",
+        ))));
+        let (sender, _receiver) = oneshot::channel();
+
+        driver.handle_command(SwarmCmd::SendRequest {
+            header: MsgHeader::default(),
+            req: req.clone(),
+            peer,
+            sender,
+        })?;
+
+        let msg = tap.try_recv()?;
+        assert_eq!(msg.direction, MsgDirection::Outbound);
+        assert_eq!(msg.peer, Some(peer));
+        assert_eq!(
+            msg.payload,
+            TappedPayload::Request(MsgHeader::default(), req.clone())
+        );
+        assert_eq!(msg.size, encoded_size(&(MsgHeader::default(), req)));
+        Ok(())
+    }
+
+    #[test]
+    fn nothing_is_tapped_without_subscribers() {
+        let tap = MsgTap::new();
+        tap.request(
+            MsgDirection::Inbound,
+            PeerId::random(),
+            &MsgHeader::default(),
+            &Request::Query(Query::GetChunk(ChunkAddress(XorName::from_content(
+                b"chunk",
+            )))),
+        );
+
+        let mut subscriber = tap.subscribe();
+        assert!(subscriber.try_recv().is_err());
+    }
+}
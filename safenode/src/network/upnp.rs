@@ -0,0 +1,170 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{error::Error, Network};
+use async_std::{net::UdpSocket, task::sleep};
+use igd_next::{aio::async_std::search_gateway, PortMappingProtocol, SearchOptions};
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// How long the ports are mapped for at a time, the mappings being renewed halfway through.
+pub const UPNP_LEASE: Duration = Duration::from_secs(60 * 60);
+
+// The description of the mappings, as listed by the gateway.
+const MAPPING_DESCRIPTION: &str = "safenode";
+// How long the listeners are given to come up, and how often they are checked in the meantime.
+const LISTEN_WAIT: Duration = Duration::from_secs(30);
+const LISTEN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Errors mapping the ports of the node on the gateway of the local network.
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum UpnpError {
+    #[error("No UPnP gateway found: {0}")]
+    NoGateway(String),
+    #[error("Could not find our address on the local network: {0}")]
+    LocalAddr(#[from] std::io::Error),
+    #[error("The gateway did not give its external address: {0}")]
+    ExternalAddr(String),
+    #[error("Could not map port {port}: {err}")]
+    MapPort { port: u16, err: String },
+    #[error("Could not read the addresses listened on: {0}")]
+    Network(#[from] Error),
+}
+
+impl Network {
+    /// Maps the ports the node listens on over IPv4 on the gateway of the local network,
+    /// through UPnP, and advertises the external addresses they are reached at to peers.
+    /// The mappings are renewed for as long as the node runs.
+    ///
+    /// Returns if there is no gateway supporting UPnP, the node then being reachable at its
+    /// listen addresses alone.
+    pub async fn map_ports(mut self) {
+        // The ports listened on are only known once the listeners are up.
+        let mut waited = Duration::ZERO;
+        while waited < LISTEN_WAIT
+            && !matches!(self.listen_addrs().await, Ok(addrs) if !addrs.is_empty())
+        {
+            sleep(LISTEN_CHECK_INTERVAL).await;
+            waited += LISTEN_CHECK_INTERVAL;
+        }
+
+        let mut mapped = false;
+        loop {
+            match self.map_listen_ports().await {
+                Ok(external_addrs) => {
+                    if !mapped {
+                        info!("Mapped ports through UPnP, reachable at {external_addrs:?}");
+                    }
+                    mapped = true;
+                }
+                Err(err) if !mapped => {
+                    info!("Not mapping ports through UPnP: {err}");
+                    return;
+                }
+                Err(err) => warn!("Failed to renew the UPnP port mappings: {err}"),
+            }
+            sleep(UPNP_LEASE / 2).await;
+        }
+    }
+
+    // Maps the ports listened on, returning the external addresses they are reached at.
+    async fn map_listen_ports(&mut self) -> Result<Vec<Multiaddr>, UpnpError> {
+        let gateway = search_gateway(SearchOptions::default())
+            .await
+            .map_err(|err| UpnpError::NoGateway(err.to_string()))?;
+        let local_ip = local_ip_towards(gateway.addr).await?;
+        let external_ip = gateway
+            .get_external_ip()
+            .await
+            .map_err(|err| UpnpError::ExternalAddr(err.to_string()))?;
+
+        let mut mapped = vec![];
+        let mut external_addrs = vec![];
+        for addr in self.listen_addrs().await? {
+            let (protocol, port) = match mapping_of(&addr) {
+                Some(mapping) => mapping,
+                None => continue,
+            };
+            if !mapped.contains(&(protocol, port)) {
+                mapped.push((protocol, port));
+                debug!("Mapping {protocol:?} port {port} to {local_ip}");
+                gateway
+                    .add_port(
+                        protocol,
+                        port,
+                        SocketAddr::new(local_ip, port),
+                        UPNP_LEASE.as_secs() as u32,
+                        MAPPING_DESCRIPTION,
+                    )
+                    .await
+                    .map_err(|err| UpnpError::MapPort {
+                        port,
+                        err: err.to_string(),
+                    })?;
+            }
+            let external_addr = addr.replace(0, |_| Some(Protocol::from(external_ip)));
+            if let Some(external_addr) = external_addr {
+                if !external_addrs.contains(&external_addr) {
+                    self.add_external_addr(external_addr.clone()).await?;
+                    external_addrs.push(external_addr);
+                }
+            }
+        }
+        Ok(external_addrs)
+    }
+}
+
+// The protocol and port to map for the IPv4 address listened on, if any.
+fn mapping_of(addr: &Multiaddr) -> Option<(PortMappingProtocol, u16)> {
+    let mut protocols = addr.iter();
+    match protocols.next()? {
+        Protocol::Ip4(ip) if !ip.is_loopback() => {}
+        _ => return None,
+    }
+    match protocols.next()? {
+        Protocol::Udp(port) => Some((PortMappingProtocol::UDP, port)),
+        Protocol::Tcp(port) => Some((PortMappingProtocol::TCP, port)),
+        _ => None,
+    }
+}
+
+// Our address on the local network, as the one the OS routes the traffic to the gateway from.
+async fn local_ip_towards(gateway: SocketAddr) -> Result<IpAddr, UpnpError> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(gateway).await?;
+    Ok(socket.local_addr()?.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    #[test]
+    fn only_the_ports_listened_on_over_ipv4_are_mapped() -> Result<()> {
+        let mapping = |addr: &str| -> Result<_> { Ok(mapping_of(&addr.parse()?)) };
+
+        assert_eq!(
+            mapping("/ip4/192.168.1.2/udp/12000/quic-v1")?,
+            Some((PortMappingProtocol::UDP, 12000))
+        );
+        assert_eq!(
+            mapping("/ip4/192.168.1.2/tcp/12001/ws")?,
+            Some((PortMappingProtocol::TCP, 12001))
+        );
+        assert_eq!(mapping("/ip4/127.0.0.1/udp/12000/quic-v1")?, None);
+        assert_eq!(mapping("/ip6/::1/udp/12000/quic-v1")?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{reputation::Misbehaviour, swarm::SwarmOps, SwarmDriver};
+use libp2p::{kad::Record, PeerId};
+use std::{fmt, sync::Arc};
+use tracing::{debug, warn};
+
+/// Decides whether a record pushed by a peer through Kademlia is stored, supplied by the
+/// node layer through [`Network::set_record_validator`]. The records pushed before one is set
+/// are not stored.
+///
+/// [`Network::set_record_validator`]: super::Network::set_record_validator
+#[derive(Clone)]
+pub struct RecordValidator(Arc<dyn Fn(&Record) -> bool + Send + Sync>);
+
+impl RecordValidator {
+    /// Wraps the check of the records, returning whether a record is to be stored.
+    pub fn new(validate: impl Fn(&Record) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(validate))
+    }
+
+    fn accepts(&self, record: &Record) -> bool {
+        (self.0)(record)
+    }
+}
+
+impl fmt::Debug for RecordValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecordValidator")
+    }
+}
+
+impl<S: SwarmOps> SwarmDriver<S> {
+    /// Stores the record pushed by the peer if the validator accepts it, penalising the peer
+    /// for pushing an invalid one otherwise.
+    pub(crate) fn put_inbound_record(&mut self, peer_id: PeerId, record: Record) {
+        let validator = match &self.record_validator {
+            Some(validator) => validator,
+            None => {
+                debug!(
+                    "Not storing record {:?} from {peer_id:?}, no validator is set",
+                    record.key
+                );
+                return;
+            }
+        };
+        if !validator.accepts(&record) {
+            warn!("Rejecting invalid record {:?} from {peer_id:?}", record.key);
+            self.penalise(peer_id, Misbehaviour::InvalidData);
+            return;
+        }
+        // Peers with a different view of the network may push records we are not responsible
+        // for, which is not held against them.
+        if !self.is_responsible_for(record.key.as_ref()) {
+            debug!(
+                "Not storing record {:?} from {peer_id:?}, out of our range",
+                record.key
+            );
+            return;
+        }
+        if let Err(err) = self.swarm.put_record(record) {
+            warn!("Failed to store record from {peer_id:?}: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{command::SwarmCmd, swarm::MockSwarm, SwarmConfig};
+    use eyre::Result;
+    use libp2p::{identity::Keypair, kad::kbucket::Distance};
+
+    fn record(key: &[u8], value: &[u8]) -> Record {
+        Record::new(key.to_vec(), value.to_vec())
+    }
+
+    #[test]
+    fn only_the_records_the_validator_accepts_are_stored() -> Result<()> {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm::default(),
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let peer_id = PeerId::random();
+
+        // Nothing is stored until a validator is set, without the peer being at fault.
+        driver.put_inbound_record(peer_id, record(b"key", b"key"));
+        assert!(driver.swarm.records.is_empty());
+        assert!(driver.reputations.reputations().is_empty());
+
+        driver.handle_command(SwarmCmd::SetRecordValidator {
+            validator: RecordValidator::new(|record| record.key.as_ref() == record.value),
+        })?;
+        driver.put_inbound_record(peer_id, record(b"key", b"key"));
+        driver.put_inbound_record(peer_id, record(b"key", b"other"));
+
+        assert_eq!(driver.swarm.records, vec![record(b"key", b"key")]);
+        assert_eq!(driver.reputations.reputations().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn records_out_of_our_range_are_not_stored() -> Result<()> {
+        let (_network, _events, mut driver) = SwarmDriver::new(
+            MockSwarm {
+                // Only a key hashing to our own would be within range.
+                responsibility_range: Some(Distance::default()),
+                ..Default::default()
+            },
+            Keypair::generate_ed25519(),
+            &SwarmConfig::default(),
+        );
+        let peer_id = PeerId::random();
+        driver.handle_command(SwarmCmd::SetRecordValidator {
+            validator: RecordValidator::new(|_| true),
+        })?;
+
+        driver.put_inbound_record(peer_id, record(b"key", b"value"));
+        assert!(driver.swarm.records.is_empty());
+        assert!(driver.reputations.reputations().is_empty());
+
+        driver.swarm.responsibility_range = None;
+        driver.put_inbound_record(peer_id, record(b"key", b"value"));
+        assert_eq!(driver.swarm.records, vec![record(b"key", b"value")]);
+        Ok(())
+    }
+}
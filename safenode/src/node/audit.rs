@@ -0,0 +1,229 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+use crate::{
+    network::{CmdPriority, Misbehaviour},
+    protocol::{
+        error::Error as ProtocolError,
+        messages::{Query, QueryResponse, Request, Response},
+    },
+    storage::chunks::Chunk,
+};
+use async_std::task::sleep;
+use futures::future;
+use libp2p::PeerId;
+use rand::seq::SliceRandom;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+/// How long to pause between two audits of the close group of a chunk.
+pub const AUDIT_PAUSE: Duration = Duration::from_secs(5 * 60);
+/// How long a peer joining our close group is given to pull the records it is to hold before
+/// it is audited.
+pub const REPLICATION_GRACE_PERIOD: Duration = Duration::from_secs(10 * 60);
+
+/// The counts of the challenges sent to the peers of the close groups of the chunks held.
+#[derive(Debug, Default)]
+pub(super) struct AuditStats {
+    challenged: AtomicU64,
+    proven: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// A snapshot of the counts of the audits since the node started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AuditCounts {
+    /// The number of peers challenged to prove they hold a chunk.
+    pub challenged: u64,
+    /// The number of challenges answered with a valid proof.
+    pub proven: u64,
+    /// The number of challenges answered with a wrong proof, or by the peer not holding the chunk.
+    pub failed: u64,
+}
+
+impl AuditStats {
+    pub(super) fn counts(&self) -> AuditCounts {
+        AuditCounts {
+            challenged: self.challenged.load(Ordering::Relaxed),
+            proven: self.proven.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// When the peers that joined our close group lately did, for them not to be audited before
+// they could pull the chunks they are to hold.
+#[derive(Clone, Debug, Default)]
+pub(super) struct CloseGroupJoins(Arc<Mutex<HashMap<PeerId, Instant>>>);
+
+impl CloseGroupJoins {
+    pub(super) fn record(&self, peer: PeerId) {
+        let mut joins = self.joins();
+        joins.retain(|_, joined| joined.elapsed() < REPLICATION_GRACE_PERIOD);
+        let _ = joins.insert(peer, Instant::now());
+    }
+
+    fn in_grace_period(&self, peer: &PeerId) -> bool {
+        self.joins()
+            .get(peer)
+            .is_some_and(|joined| joined.elapsed() < REPLICATION_GRACE_PERIOD)
+    }
+
+    fn joins(&self) -> MutexGuard<'_, HashMap<PeerId, Instant>> {
+        match self.0.lock() {
+            Ok(joins) => joins,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+// The outcome of the challenge of a peer.
+enum Challenge {
+    Proven,
+    Failed,
+    // The peer did not answer, which the network counts against it already.
+    Unanswered,
+}
+
+impl Node {
+    /// Audits the close group of a random chunk held by the node, every [`AUDIT_PAUSE`] and
+    /// forever. Each peer of the group is challenged to prove it holds the chunk too, with a
+    /// nonce of its own, the peers failing to being reported as misbehaving.
+    ///
+    /// Peers that joined our close group less than [`REPLICATION_GRACE_PERIOD`] ago are not
+    /// audited, as they may still be pulling the chunks they are to hold.
+    pub async fn audit_neighbours(self) {
+        loop {
+            sleep(AUDIT_PAUSE).await;
+            self.audit_random_chunk().await;
+        }
+    }
+
+    /// The counts of the audits since the node started.
+    pub fn audit_counts(&self) -> AuditCounts {
+        self.audit_stats.counts()
+    }
+
+    async fn audit_random_chunk(&self) {
+        let addr = match self
+            .storage
+            .chunk_addresses()
+            .choose(&mut rand::thread_rng())
+        {
+            Some(addr) => *addr,
+            None => return,
+        };
+        let chunk = match self.storage.query(&addr).await {
+            Ok(chunk) => chunk,
+            // E.g. the chunk was removed since it was listed.
+            Err(err) => {
+                debug!("Could not read chunk {addr:?} to audit: {err}");
+                return;
+            }
+        };
+
+        // Audits give way to the queries of clients.
+        let mut network = self.network.at_priority(CmdPriority::Maintenance);
+        let close_group = match network.get_closest_peers(*addr.name()).await {
+            Ok(peers) => peers,
+            Err(err) => {
+                warn!("Could not find the close group of {addr:?} to audit: {err}");
+                return;
+            }
+        };
+        let own_id = self.network.peer_id();
+        let peers = close_group
+            .into_iter()
+            .map(|(peer, _)| peer)
+            .filter(|peer| *peer != own_id)
+            .filter(|peer| {
+                let joining = self.close_group_joins.in_grace_period(peer);
+                if joining {
+                    debug!("Not auditing {peer:?}, it joined our close group lately");
+                }
+                !joining
+            });
+
+        let chunk = &chunk;
+        // Each peer is given a nonce of its own, for none to answer with the proof of another.
+        let challenges = peers.map(|peer| async move {
+            let challenge = self.challenge(peer, chunk, rand::random()).await;
+            (peer, challenge)
+        });
+        let mut failed = 0;
+        for (peer, challenge) in future::join_all(challenges).await {
+            match challenge {
+                Challenge::Proven => {
+                    let _ = self.audit_stats.proven.fetch_add(1, Ordering::Relaxed);
+                }
+                Challenge::Failed => {
+                    failed += 1;
+                    let _ = self.audit_stats.failed.fetch_add(1, Ordering::Relaxed);
+                    if let Err(err) = network
+                        .report_misbehaviour(peer, Misbehaviour::FailedAudit)
+                        .await
+                    {
+                        warn!("Could not report the misbehaviour of {peer:?}: {err}");
+                    }
+                }
+                Challenge::Unanswered => {}
+            }
+        }
+        info!("Audited the close group of {addr:?}, {failed} peers failed");
+    }
+
+    // Challenges the peer to prove it holds the chunk.
+    async fn challenge(&self, peer: PeerId, chunk: &Chunk, nonce: u64) -> Challenge {
+        let _ = self.audit_stats.challenged.fetch_add(1, Ordering::Relaxed);
+        let key = *chunk.address();
+        let request = Request::Query(Query::ChunkProof { key, nonce });
+        let mut network = self.network.at_priority(CmdPriority::Maintenance);
+        let signed = match network.send_request(request, peer).await {
+            Ok(signed) => signed,
+            Err(err) => {
+                debug!("{peer:?} did not answer the audit of {key:?}: {err}");
+                return Challenge::Unanswered;
+            }
+        };
+        match signed.verify() {
+            Ok(signer) if signer == peer => {}
+            Ok(signer) => {
+                debug!("Discarding the answer to the audit of {key:?} sent to {peer:?}, signed by {signer:?}");
+                return Challenge::Unanswered;
+            }
+            Err(err) => {
+                debug!("Discarding the answer of {peer:?} to the audit of {key:?}: {err}");
+                return Challenge::Unanswered;
+            }
+        }
+        match signed.response {
+            Response::Query(QueryResponse::ChunkProof(Ok(proof)))
+                if proof == chunk.proof(&peer, nonce) =>
+            {
+                Challenge::Proven
+            }
+            Response::Query(QueryResponse::ChunkProof(Ok(_)))
+            | Response::Query(QueryResponse::ChunkProof(Err(ProtocolError::ChunkNotFound(_)))) => {
+                warn!("{peer:?} failed to prove it holds {key:?}");
+                Challenge::Failed
+            }
+            other => {
+                debug!("Unexpected answer of {peer:?} to the audit of {key:?}: {other:?}");
+                Challenge::Unanswered
+            }
+        }
+    }
+}
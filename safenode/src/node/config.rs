@@ -0,0 +1,553 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    log::{LogFormat, LogRotation},
+    network::{
+        decode_public_key, MuxerConfig, RelayConfig, SearchConfig, SwarmConfig,
+        DEFAULT_CMD_QUEUE_SIZE, DEFAULT_CONTACTS_REFRESH, DEFAULT_EVENT_QUEUE_SIZE,
+        DEFAULT_IDLE_CONNECTION_TIMEOUT, DEFAULT_KEEP_ALIVE, DEFAULT_MAX_CONCURRENT_DIALS,
+        DEFAULT_MAX_CONCURRENT_QUERIES, DEFAULT_MAX_CONCURRENT_STREAMS,
+        DEFAULT_MUXER_MAX_BUFFER_SIZE, DEFAULT_MUXER_RECEIVE_WINDOW, DEFAULT_QUERY_PARALLELISM,
+        DEFAULT_QUERY_TIMEOUT, DEFAULT_RELAY_MAX_CIRCUITS, DEFAULT_RELAY_MAX_CIRCUIT_BYTES,
+        DEFAULT_RELAY_MAX_CIRCUIT_DURATION, DEFAULT_RELAY_MAX_RESERVATIONS,
+        DEFAULT_REQUEST_TIMEOUT, DEFAULT_SEARCH_ATTEMPTS, DEFAULT_SEARCH_TIMEOUT,
+    },
+    node::{
+        DEFAULT_HANDOFF_BUDGET, DEFAULT_MIN_PEERS_TO_SERVE, DEFAULT_QUERY_CACHE_TTL,
+        DEFAULT_RELAY_CACHE_TTL,
+    },
+    protocol::types::payment::{RoyaltyPolicy, StoreCost},
+    storage::{
+        Compression, CompressionConfig, DEFAULT_CACHE_CAPACITY, DEFAULT_COMPRESSION_MIN_SIZE,
+        DEFAULT_MAX_CAPACITY,
+    },
+};
+use libp2p::{identity::PublicKey, pnet::PreSharedKey, Multiaddr};
+use serde::{Deserialize, Serialize};
+use sn_dbc::PublicAddress;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use thiserror::Error;
+
+/// The path of the config file, relative to the home dir of the user.
+const CONFIG_FILE_PATH: &str = ".safe/node/config.toml";
+
+/// The amount a node asks for the storage of a record, in nanos, unless configured otherwise.
+pub(super) const DEFAULT_STORE_COST_NANOS: u64 = 1;
+
+/// Errors loading or saving a [`NodeConfig`].
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum ConfigError {
+    #[error("Could not locate the home dir of the user")]
+    NoHomeDir,
+    #[error("I/O error on config file {path:?}: {err}")]
+    Io { path: PathBuf, err: io::Error },
+    #[error("Invalid config file {path:?}: {err}")]
+    Parse { path: PathBuf, err: toml::de::Error },
+    #[error("Could not serialise the config: {0}")]
+    Serialise(#[from] toml::ser::Error),
+    #[error("Invalid network key, expected 64 hex characters: {0}")]
+    InvalidNetworkKey(String),
+    #[error("Network contacts given without the public key they are signed with")]
+    MissingContactsKey,
+    #[error("Invalid network contacts public key: {0}")]
+    InvalidContactsKey(String),
+    #[error("Invalid royalties address: {0}")]
+    InvalidRoyaltiesAddress(String),
+    #[error("Invalid wallet address: {0}")]
+    InvalidWalletAddress(String),
+}
+
+/// The configuration of a node, read from a TOML file.
+///
+/// All settings are optional in the file, the missing ones taking their default value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeConfig {
+    /// The dir the node keeps its keypair, records, wallet and logs in, see
+    /// [`NodeRootDir`](crate::node::NodeRootDir).
+    /// A temporary dir is used if not set.
+    pub root_dir: Option<PathBuf>,
+    /// Addresses to listen on, in addition to the ones chosen by the OS at startup.
+    /// Both IPv4 and IPv6 addresses can be given, e.g. `/ip6/::/udp/12000/quic-v1`.
+    pub listen_addrs: Vec<Multiaddr>,
+    /// Addresses the node is reached at from outside, advertised to peers on top of the ones
+    /// listened on, e.g. the public IP of a VPS the node listens on `0.0.0.0` at.
+    pub external_addrs: Vec<Multiaddr>,
+    /// Peers to connect to on startup, for nodes outside of the local network.
+    /// The addresses must end with the `/p2p/<peer id>` of the peer.
+    pub bootstrap_peers: Vec<Multiaddr>,
+    /// The max number of bytes of records the node stores.
+    pub max_capacity: u64,
+    /// The algorithm the values of the records are compressed with on disk, `none`, `zstd`
+    /// or `lz4`. The records are sent over the network uncompressed either way.
+    pub compression: Compression,
+    /// The size in bytes from which the values of the records are compressed.
+    pub compression_min_size: usize,
+    /// The max number of bytes of the records most recently read held in memory.
+    pub cache_capacity: u64,
+    /// The max number of bytes of the chunks fetched from other nodes to answer the queries
+    /// for chunks the node does not hold, cached to answer the next queries for them.
+    /// Relaying is disabled when set to 0, the default.
+    pub relay_cache_capacity: u64,
+    /// How long relayed chunks are cached for, in seconds.
+    pub relay_cache_ttl_secs: u64,
+    /// How long the answers to idempotent queries, such as for the existence of a chunk, are
+    /// cached for, in milliseconds. The cache is disabled when set to 0.
+    pub query_cache_ttl_millis: u64,
+    /// The hex encoded address the network royalties are paid to, as printed by
+    /// `safe wallet address`. Payments for storage are only checked for royalties if set.
+    pub royalties_address: Option<String>,
+    /// The share of every storage payment due as royalties, in basis points.
+    pub royalty_basis_points: u64,
+    /// The hex encoded address the node is paid at for the storage of records, as printed by
+    /// `safe wallet address`. Advertised to clients asking for the cost of the storage, the
+    /// payments for storage being rejected unless paying the node there. Not set by default,
+    /// the node taking any payment.
+    pub wallet_address: Option<String>,
+    /// The amount the node asks for the storage of a record, in nanos, when paid at its
+    /// wallet address.
+    pub store_cost_nanos: u64,
+    /// The min number of peers in the routing table for the node to take data, puts and
+    /// spends being rejected as `NotReady` until then. Set to 0 to only require a lookup to
+    /// have succeeded lately.
+    pub min_peers_to_serve: usize,
+    /// How long a node being stopped pushes its records to their close group for, in seconds,
+    /// before exiting. The handoff is skipped when set to 0.
+    pub handoff_budget_secs: u64,
+    /// The log settings.
+    pub log: LogConfig,
+    /// The network settings.
+    pub network: NetworkConfig,
+}
+
+/// The log settings of a node.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// The dir log files are written to. Logs go to stdout if not set.
+    pub dir: Option<PathBuf>,
+    /// The format log lines are written in, `default` or `json`.
+    pub format: LogFormat,
+    /// When log files are replaced by new ones, and how many of the old ones are kept.
+    pub rotation: LogRotation,
+}
+
+/// The network settings of a node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// How long Kademlia queries run for before timing out, in seconds.
+    pub query_timeout_secs: u64,
+    /// The number of peers Kademlia queries are sent to at once.
+    pub query_parallelism: usize,
+    /// Whether lookups take as many disjoint paths as their parallelism, for them to resist
+    /// adversarial peers returning peers of their own. On by default.
+    pub disjoint_query_paths: bool,
+    /// The hex encoded 32 bytes key of the private network to join, if any.
+    /// Only peers holding the same key can connect to the node.
+    pub network_key: Option<String>,
+    /// The max number of peers dialed at once, the further ones being queued.
+    pub max_concurrent_dials: usize,
+    /// The max number of Kademlia queries run at once, e.g. looking up the closest peers
+    /// to records, the further ones being queued.
+    pub max_concurrent_queries: usize,
+    /// How long a connection is left unused before it is closed, in seconds.
+    /// The connections to the close group of the node are kept open.
+    pub idle_connection_timeout_secs: u64,
+    /// How long an unused connection is kept open at most, in seconds, including the
+    /// connections to the close group.
+    pub keep_alive_secs: u64,
+    /// How long a peer is given to respond to a request, in seconds.
+    pub request_timeout_secs: u64,
+    /// The max number of streams open at once over a connection, each request in flight
+    /// taking one.
+    pub max_concurrent_streams: usize,
+    /// The bytes a peer can send over a stream before waiting for them to be read, on the
+    /// connections other than QUIC ones. At least 256 KiB.
+    pub muxer_receive_window: u32,
+    /// The bytes buffered per stream, on the connections other than QUIC ones.
+    pub muxer_max_buffer_size: usize,
+    /// The network-contacts file listing the peers to bootstrap from, as a local path or
+    /// an `https://` URL. A random subset of the peers is dialed.
+    pub contacts: Option<String>,
+    /// The hex encoded public key the network contacts are signed with.
+    pub contacts_public_key: Option<String>,
+    /// How often the network contacts are fetched again, in seconds.
+    pub contacts_refresh_secs: u64,
+    /// The number of attempts at finding a record before declaring it missing, the search
+    /// being widened to more peers with every attempt.
+    pub search_attempts: usize,
+    /// How long the peers are given to respond at the first attempt at finding a record,
+    /// in seconds, doubled with every attempt.
+    pub search_timeout_secs: u64,
+    /// Whether the ports listened on are mapped by the gateway of the local network through
+    /// UPnP, for the node to be reached from outside of it. Needs the `upnp` feature.
+    pub upnp: bool,
+    /// Whether the node relays the connections of peers behind NATs, within the limits below.
+    /// Only worth it for nodes reachable from the internet.
+    pub relay_server: bool,
+    /// The max number of peers reachable through the node at once.
+    pub relay_max_reservations: usize,
+    /// The max number of connections relayed at once.
+    pub relay_max_circuits: usize,
+    /// How long a connection is relayed for at most, in seconds.
+    pub relay_max_circuit_duration_secs: u64,
+    /// The max number of bytes relayed in each direction of a connection.
+    pub relay_max_circuit_bytes: u64,
+    /// The number of cmds queued for the network layer, beyond which the node waits for it
+    /// to catch up, and sheds its reports of misbehaving peers.
+    pub cmd_queue_size: usize,
+    /// The number of network events queued for the node, beyond which the requests of peers
+    /// are dropped, failing at the peers.
+    pub event_queue_size: usize,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            root_dir: None,
+            listen_addrs: vec![],
+            external_addrs: vec![],
+            bootstrap_peers: vec![],
+            max_capacity: DEFAULT_MAX_CAPACITY,
+            compression: Compression::default(),
+            compression_min_size: DEFAULT_COMPRESSION_MIN_SIZE,
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+            relay_cache_capacity: 0,
+            relay_cache_ttl_secs: DEFAULT_RELAY_CACHE_TTL.as_secs(),
+            query_cache_ttl_millis: DEFAULT_QUERY_CACHE_TTL.as_millis() as u64,
+            royalties_address: None,
+            royalty_basis_points: 0,
+            wallet_address: None,
+            store_cost_nanos: DEFAULT_STORE_COST_NANOS,
+            min_peers_to_serve: DEFAULT_MIN_PEERS_TO_SERVE,
+            handoff_budget_secs: DEFAULT_HANDOFF_BUDGET.as_secs(),
+            log: LogConfig::default(),
+            network: NetworkConfig::default(),
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            query_timeout_secs: DEFAULT_QUERY_TIMEOUT.as_secs(),
+            query_parallelism: DEFAULT_QUERY_PARALLELISM,
+            disjoint_query_paths: true,
+            network_key: None,
+            max_concurrent_dials: DEFAULT_MAX_CONCURRENT_DIALS,
+            max_concurrent_queries: DEFAULT_MAX_CONCURRENT_QUERIES,
+            idle_connection_timeout_secs: DEFAULT_IDLE_CONNECTION_TIMEOUT.as_secs(),
+            keep_alive_secs: DEFAULT_KEEP_ALIVE.as_secs(),
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT.as_secs(),
+            max_concurrent_streams: DEFAULT_MAX_CONCURRENT_STREAMS,
+            muxer_receive_window: DEFAULT_MUXER_RECEIVE_WINDOW,
+            muxer_max_buffer_size: DEFAULT_MUXER_MAX_BUFFER_SIZE,
+            contacts: None,
+            contacts_public_key: None,
+            contacts_refresh_secs: DEFAULT_CONTACTS_REFRESH.as_secs(),
+            search_attempts: DEFAULT_SEARCH_ATTEMPTS,
+            search_timeout_secs: DEFAULT_SEARCH_TIMEOUT.as_secs(),
+            upnp: false,
+            relay_server: false,
+            relay_max_reservations: DEFAULT_RELAY_MAX_RESERVATIONS,
+            relay_max_circuits: DEFAULT_RELAY_MAX_CIRCUITS,
+            relay_max_circuit_duration_secs: DEFAULT_RELAY_MAX_CIRCUIT_DURATION.as_secs(),
+            relay_max_circuit_bytes: DEFAULT_RELAY_MAX_CIRCUIT_BYTES,
+            cmd_queue_size: DEFAULT_CMD_QUEUE_SIZE,
+            event_queue_size: DEFAULT_EVENT_QUEUE_SIZE,
+        }
+    }
+}
+
+/// The network settings suited to the environment the node runs in, overriding the timeouts
+/// and limits of the config.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkPreset {
+    /// Peers on the same local network, e.g. of a testnet, responding fast over connections
+    /// able to carry many streams.
+    Lan,
+    /// Peers across the internet, some over slow links, given longer to respond.
+    Wan,
+}
+
+impl FromStr for NetworkPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lan" => Ok(Self::Lan),
+            "wan" => Ok(Self::Wan),
+            other => Err(format!(
+                "Unknown network preset {other:?}, expected `lan` or `wan`"
+            )),
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Applies the timeouts and limits of the preset.
+    pub fn apply_preset(&mut self, preset: NetworkPreset) {
+        match preset {
+            NetworkPreset::Lan => {
+                self.request_timeout_secs = 3;
+                self.query_timeout_secs = 30;
+                self.max_concurrent_streams = 4 * DEFAULT_MAX_CONCURRENT_STREAMS;
+            }
+            NetworkPreset::Wan => {
+                self.request_timeout_secs = 30;
+                self.query_timeout_secs = DEFAULT_QUERY_TIMEOUT.as_secs();
+                self.max_concurrent_streams = DEFAULT_MAX_CONCURRENT_STREAMS / 2;
+            }
+        }
+    }
+
+    /// How long Kademlia queries run for before timing out.
+    pub fn query_timeout(&self) -> Duration {
+        Duration::from_secs(self.query_timeout_secs)
+    }
+
+    /// The key of the private network to join, if any.
+    pub fn network_key(&self) -> Result<Option<PreSharedKey>, ConfigError> {
+        let hex_key = match &self.network_key {
+            Some(hex_key) => hex_key,
+            None => return Ok(None),
+        };
+        let bytes = hex::decode(hex_key.trim())
+            .map_err(|err| ConfigError::InvalidNetworkKey(err.to_string()))?;
+        let key: [u8; 32] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            ConfigError::InvalidNetworkKey(format!("got {} bytes", bytes.len()))
+        })?;
+        Ok(Some(PreSharedKey::new(key)))
+    }
+
+    /// The network contacts to bootstrap from, if any, with the public key their signature
+    /// is checked against.
+    pub fn contacts(&self) -> Result<Option<(String, PublicKey)>, ConfigError> {
+        let contacts = match &self.contacts {
+            Some(contacts) => contacts,
+            None => return Ok(None),
+        };
+        let hex_key = self
+            .contacts_public_key
+            .as_ref()
+            .ok_or(ConfigError::MissingContactsKey)?;
+        let public_key = decode_public_key(hex_key)
+            .map_err(|err| ConfigError::InvalidContactsKey(err.to_string()))?;
+        Ok(Some((contacts.clone(), public_key)))
+    }
+
+    /// How often the network contacts are fetched again.
+    pub fn contacts_refresh(&self) -> Duration {
+        Duration::from_secs(self.contacts_refresh_secs)
+    }
+
+    /// The settings of the network layer.
+    pub fn swarm_config(&self) -> Result<SwarmConfig, ConfigError> {
+        Ok(SwarmConfig {
+            query_timeout: self.query_timeout(),
+            query_parallelism: self.query_parallelism,
+            disjoint_query_paths: self.disjoint_query_paths,
+            network_key: self.network_key()?,
+            max_concurrent_dials: self.max_concurrent_dials,
+            max_concurrent_queries: self.max_concurrent_queries,
+            idle_connection_timeout: Duration::from_secs(self.idle_connection_timeout_secs),
+            keep_alive: Duration::from_secs(self.keep_alive_secs),
+            request_timeout: Duration::from_secs(self.request_timeout_secs),
+            max_concurrent_streams: self.max_concurrent_streams,
+            muxer: MuxerConfig {
+                receive_window: self.muxer_receive_window,
+                max_buffer_size: self.muxer_max_buffer_size,
+            },
+            search: SearchConfig {
+                attempts: self.search_attempts,
+                timeout: Duration::from_secs(self.search_timeout_secs),
+            },
+            cmd_queue_size: self.cmd_queue_size,
+            event_queue_size: self.event_queue_size,
+            keypair: None,
+            client_mode: false,
+            relay: self.relay_config(),
+            memory_transport: false,
+            #[cfg(feature = "malice")]
+            malice: None,
+        })
+    }
+
+    /// The limits of the relaying of connections, if the node relays them.
+    pub fn relay_config(&self) -> Option<RelayConfig> {
+        if !self.relay_server {
+            return None;
+        }
+        Some(RelayConfig {
+            max_reservations: self.relay_max_reservations,
+            max_circuits: self.relay_max_circuits,
+            max_circuit_duration: Duration::from_secs(self.relay_max_circuit_duration_secs),
+            max_circuit_bytes: self.relay_max_circuit_bytes,
+            ..Default::default()
+        })
+    }
+}
+
+impl NodeConfig {
+    /// How long a node being stopped hands off its records for.
+    pub fn handoff_budget(&self) -> Duration {
+        Duration::from_secs(self.handoff_budget_secs)
+    }
+
+    /// How the values of the records are compressed on disk.
+    pub fn compression_config(&self) -> CompressionConfig {
+        CompressionConfig {
+            algorithm: self.compression,
+            min_size: self.compression_min_size,
+        }
+    }
+
+    /// How long relayed chunks are cached for.
+    pub fn relay_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.relay_cache_ttl_secs)
+    }
+
+    /// How long the answers to idempotent queries are cached for.
+    pub fn query_cache_ttl(&self) -> Duration {
+        Duration::from_millis(self.query_cache_ttl_millis)
+    }
+
+    /// The royalties the payments for storage are to pay, if any.
+    pub fn royalty_policy(&self) -> Result<Option<RoyaltyPolicy>, ConfigError> {
+        let hex_address = match &self.royalties_address {
+            Some(hex_address) => hex_address,
+            None => return Ok(None),
+        };
+        let address = decode_address(hex_address).map_err(ConfigError::InvalidRoyaltiesAddress)?;
+        Ok(Some(RoyaltyPolicy {
+            address,
+            basis_points: self.royalty_basis_points,
+        }))
+    }
+
+    /// The cost the node asks for the storage of a record, and the address it is paid at,
+    /// if it is configured with one.
+    pub fn store_cost(&self) -> Result<Option<StoreCost>, ConfigError> {
+        let hex_address = match &self.wallet_address {
+            Some(hex_address) => hex_address,
+            None => return Ok(None),
+        };
+        let address = decode_address(hex_address).map_err(ConfigError::InvalidWalletAddress)?;
+        Ok(Some(StoreCost {
+            address,
+            cost_nanos: self.store_cost_nanos,
+        }))
+    }
+
+    /// The path of the config file read by default, `~/.safe/node/config.toml`.
+    pub fn default_path() -> Result<PathBuf, ConfigError> {
+        dirs_next::home_dir()
+            .map(|home| home.join(CONFIG_FILE_PATH))
+            .ok_or(ConfigError::NoHomeDir)
+    }
+
+    /// Reads the config from the file at the given path, or returns the default config
+    /// if there is no such file.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(ConfigError::Io {
+                    path: path.to_path_buf(),
+                    err,
+                })
+            }
+        };
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse {
+            path: path.to_path_buf(),
+            err,
+        })
+    }
+
+    /// Returns the config as a TOML document, in the format it is read in.
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+// Decodes a `PublicAddress` hex encoded as by `safe wallet address`.
+fn decode_address(hex_address: &str) -> Result<PublicAddress, String> {
+    let bytes = hex::decode(hex_address.trim()).map_err(|err| err.to_string())?;
+    rmp_serde::from_slice(&bytes).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xor_name::XorName;
+
+    #[test]
+    fn settings_missing_from_the_file_take_their_default_value() -> eyre::Result<()> {
+        let config: NodeConfig = toml::from_str(
+            "max_capacity = 1024\n\n[log]\nformat = \"json\"\n\n[network]\nupnp = true\n",
+        )?;
+        assert_eq!(config.max_capacity, 1024);
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert!(config.network.upnp);
+
+        let expected = NodeConfig {
+            max_capacity: 1024,
+            log: LogConfig {
+                format: LogFormat::Json,
+                ..Default::default()
+            },
+            network: NetworkConfig {
+                upnp: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_files_give_the_default_config_and_invalid_ones_are_refused() -> eyre::Result<()> {
+        let dir = std::env::temp_dir().join(hex::encode(XorName::random(&mut rand::thread_rng())));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("config.toml");
+        assert_eq!(NodeConfig::load(&path)?, NodeConfig::default());
+
+        std::fs::write(&path, "max_capacity = \"a lot\"\n")?;
+        assert!(matches!(
+            NodeConfig::load(&path),
+            Err(ConfigError::Parse { .. })
+        ));
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn configs_are_read_back_from_their_toml() -> eyre::Result<()> {
+        let mut config = NodeConfig {
+            root_dir: Some(PathBuf::from("/var/lib/safenode")),
+            bootstrap_peers: vec!["/ip4/10.0.0.1/udp/12000/quic-v1".parse()?],
+            ..Default::default()
+        };
+        config.network.apply_preset(NetworkPreset::Wan);
+        config.network.relay_server = true;
+        let read_back: NodeConfig = toml::from_str(&config.to_toml()?)?;
+        assert_eq!(read_back, config);
+        Ok(())
+    }
+}
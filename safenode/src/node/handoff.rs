@@ -0,0 +1,230 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{register_error, spend_error, verified, Node};
+use crate::{
+    network::CmdPriority,
+    protocol::{
+        error::{Error as ProtocolError, Result as ProtocolResult},
+        messages::{Cmd, CmdResponse, Replica, Request, Response},
+        types::spend::{verify_spend, DbcAddress},
+    },
+    storage::{
+        chunks::{Chunk, ChunkAddress},
+        Error as StorageError, LocalRecord, RecordKind,
+    },
+};
+use futures::{stream, StreamExt};
+use libp2p::PeerId;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How long a stopping node hands off its records for, unless configured otherwise.
+pub const DEFAULT_HANDOFF_BUDGET: Duration = Duration::from_secs(60);
+// The number of records handed off at once.
+const HANDOFF_CONCURRENCY: usize = 8;
+// How often the progress of the handoff is logged, in records.
+const HANDOFF_PROGRESS_INTERVAL: usize = 100;
+
+/// Where a replica taken by the node comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ReplicaSource {
+    /// Handed off by a node leaving the network, with a [`Cmd::Replicate`].
+    Handoff,
+    /// Pulled from a close-group peer, with a `FetchReplication` query.
+    Pull,
+}
+
+/// The counts of the records handed off by a stopping node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HandoffCounts {
+    /// The number of records held.
+    pub total: usize,
+    /// The number of records taken by at least one peer.
+    pub handed_off: usize,
+    /// The number of records no peer took.
+    pub failed: usize,
+}
+
+impl Node {
+    /// Pushes the records held to the peers closest to them, for the records not to be
+    /// under-replicated once the node has left. Stops once `budget` is spent, the records
+    /// not handed off by then being left to the rest of their close group.
+    pub async fn hand_off_records(&self, budget: Duration) -> HandoffCounts {
+        let records = self.storage.local_records();
+        let mut counts = HandoffCounts {
+            total: records.len(),
+            ..Default::default()
+        };
+        info!(
+            "Handing off {} records, for at most {budget:?}",
+            counts.total
+        );
+
+        let deadline = Instant::now() + budget;
+        let mut handoffs = stream::iter(records)
+            .map(|record| self.hand_off(record))
+            .buffer_unordered(HANDOFF_CONCURRENCY);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match async_std::future::timeout(remaining, handoffs.next()).await {
+                Ok(Some(true)) => counts.handed_off += 1,
+                Ok(Some(false)) => counts.failed += 1,
+                Ok(None) => break,
+                Err(_) => {
+                    warn!("Handoff budget of {budget:?} spent");
+                    break;
+                }
+            }
+            let done = counts.handed_off + counts.failed;
+            if done.is_multiple_of(HANDOFF_PROGRESS_INTERVAL) {
+                info!("Handed off {done} of {} records", counts.total);
+            }
+        }
+        info!("Handoff done: {counts:?}");
+        counts
+    }
+
+    // Sends the replica of the record to its close group, returning whether a peer took it.
+    async fn hand_off(&self, record: LocalRecord) -> bool {
+        let replica = match self.replica_of(&record).await {
+            Ok(replica) => replica,
+            Err(err) => {
+                warn!("Could not read record {:?} to hand off: {err}", record.key);
+                return false;
+            }
+        };
+        // The handoff gives way to the requests of clients still being served.
+        let network = self.network.at_priority(CmdPriority::Replication);
+        let signed_cmd = match network.sign_cmd(Cmd::Replicate(replica)) {
+            Ok(signed_cmd) => signed_cmd,
+            Err(err) => {
+                warn!("Could not sign the handoff of {:?}: {err}", record.key);
+                return false;
+            }
+        };
+        match network
+            .send_to_closest(record.key, Request::Cmd(signed_cmd))
+            .await
+        {
+            Ok(responses) => verified(responses)
+                .any(|resp| matches!(resp, Response::Cmd(CmdResponse::Ack { .. }))),
+            Err(err) => {
+                debug!("Could not hand off record {:?}: {err}", record.key);
+                false
+            }
+        }
+    }
+
+    async fn replica_of(&self, record: &LocalRecord) -> Result<Replica, StorageError> {
+        match record.kind {
+            RecordKind::Chunk => {
+                let chunk = self.storage.query(&ChunkAddress(record.key)).await?;
+                Ok(Replica::Chunk(chunk.value().clone()))
+            }
+            RecordKind::Register => {
+                let register = self.storage.get_register_by_id(&record.key).await?;
+                Ok(Replica::Register(Box::new(register)))
+            }
+            RecordKind::Spend => {
+                let spends = self.storage.get_spends_by_name(&record.key).await?;
+                Ok(Replica::Spends(spends))
+            }
+        }
+    }
+
+    /// Stores the replica handed off by, or pulled from, the peer, if it is a node and the record
+    /// is within our range. Chunks and spends are checked as they would be when first stored,
+    /// but for their payment. Registers are checked against the signatures of their policy and
+    /// entries, and only merged into a replica we already hold when handed off, no payment
+    /// for their creation being carried.
+    pub(super) async fn store_replica(
+        &self,
+        peer: PeerId,
+        replica: Replica,
+        source: ReplicaSource,
+    ) -> ProtocolResult<()> {
+        let name = match replica.name() {
+            Some(name) => name,
+            None => {
+                return Err(ProtocolError::InvalidReplica(
+                    Default::default(),
+                    "no spends in the replica".to_string(),
+                ))
+            }
+        };
+        let is_node = self
+            .network
+            .clone()
+            .is_in_routing_table(peer)
+            .await
+            .unwrap_or(false);
+        if !is_node {
+            return Err(ProtocolError::InvalidReplica(
+                name,
+                format!("{peer:?} is not in our routing table"),
+            ));
+        }
+        self.check_responsible(&name).await?;
+
+        match replica {
+            Replica::Chunk(value) => {
+                let chunk = Chunk::new(value);
+                self.store_chunk(&chunk).await.map_err(|err| match err {
+                    StorageError::NotEnoughSpace => {
+                        ProtocolError::QuotaExceeded(self.storage.storage_level())
+                    }
+                    other => {
+                        warn!("Failed to store replica of chunk {name:?}: {other}");
+                        ProtocolError::ChunkNotStored(*chunk.address())
+                    }
+                })
+            }
+            Replica::Register(register) => {
+                let addr = *register.address();
+                if source == ReplicaSource::Handoff {
+                    match self.storage.get_register(&addr).await {
+                        Ok(_) => {}
+                        Err(StorageError::RegisterNotFound(_)) => {
+                            return Err(ProtocolError::InvalidReplica(
+                                name,
+                                "register not held, its creation is not paid for".to_string(),
+                            ))
+                        }
+                        Err(err) => return Err(register_error(addr, err)),
+                    }
+                }
+                self.storage
+                    .merge_register(*register)
+                    .await
+                    .map_err(|err| register_error(addr, err))
+            }
+            Replica::Spends(spends) => {
+                for signed_spend in spends {
+                    let addr = DbcAddress::from_dbc_id(signed_spend.dbc_id());
+                    if addr.name() != &name {
+                        return Err(ProtocolError::InvalidReplica(
+                            name,
+                            format!("spend at {addr:?} in the replica"),
+                        ));
+                    }
+                    verify_spend(&signed_spend)?;
+                    // Both spends of a double spend attempt are handed off, and kept.
+                    match self.storage.try_add_spend(&signed_spend).await {
+                        Ok(())
+                        | Err(StorageError::Protocol(ProtocolError::DoubleSpendAttempt {
+                            ..
+                        })) => {}
+                        Err(err) => return Err(spend_error(addr, err)),
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
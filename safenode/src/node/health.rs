@@ -0,0 +1,183 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Node, QueryCacheStats};
+use crate::{
+    http::{read_head, respond},
+    network::{ConnectionStats, RelayStats},
+};
+use async_std::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    task::spawn,
+};
+use futures::StreamExt;
+use serde::Serialize;
+use std::{io, net::SocketAddr};
+use tracing::{info, trace, warn};
+
+/// The min number of peers in the routing table for the node to be reported as ready,
+/// unless configured otherwise.
+pub const DEFAULT_READY_MIN_PEERS: usize = 1;
+
+// The state of the node, as reported by the endpoints.
+#[derive(Serialize, Debug)]
+struct HealthReport {
+    /// Whether the swarm loop answers the requests sent to it.
+    swarm_responsive: bool,
+    /// Whether the swarm listens on at least one address.
+    listening: bool,
+    /// The number of peers in the routing table.
+    peers: usize,
+    /// The min number of peers required to be ready.
+    min_peers: usize,
+    /// Whether records can be written to the storage dir.
+    storage_writable: bool,
+}
+
+// The stored data, earnings and relay usage of the node, as reported by `/metrics`.
+#[derive(Serialize, Debug)]
+struct MetricsReport {
+    /// The number of chunks held.
+    chunk_count: usize,
+    /// The bytes used on disk by the records.
+    used_space: u64,
+    /// The max bytes the records can use on disk.
+    max_capacity: u64,
+    /// The number of chunks and registers stored.
+    puts_accepted: u64,
+    /// The number of chunks and registers refused.
+    puts_rejected: u64,
+    /// The amount paid for the storage of the records stored, in nanos.
+    rewards_nanos: u64,
+    /// The usage of the relay, all zero unless the node relays connections.
+    relay: RelayStats,
+    /// The usage of the cache of the answers to queries.
+    query_cache: QueryCacheStats,
+    /// The dials and connections of the node.
+    connections: ConnectionStats,
+}
+
+impl MetricsReport {
+    fn new(node: &Node) -> Self {
+        let level = node.storage.storage_level();
+        let counts = node.stats_counts();
+        Self {
+            chunk_count: node.storage.chunk_count(),
+            used_space: level.used,
+            max_capacity: level.total,
+            puts_accepted: counts.puts_accepted,
+            puts_rejected: counts.puts_rejected,
+            rewards_nanos: counts.rewards_nanos,
+            relay: node.network.relay_stats(),
+            query_cache: node.query_cache_stats(),
+            connections: node.network.connection_stats(),
+        }
+    }
+}
+
+impl HealthReport {
+    async fn new(node: &Node, min_peers: usize) -> Self {
+        let mut network = node.network.clone();
+        let listen_addrs = network.listen_addrs().await;
+        let peers = network.routing_table_size().await.unwrap_or_default();
+        let storage_writable = match node.storage.check_writable().await {
+            Ok(()) => true,
+            Err(err) => {
+                warn!("Storage is not writable: {err}");
+                false
+            }
+        };
+        Self {
+            swarm_responsive: listen_addrs.is_ok(),
+            listening: listen_addrs.map_or(false, |addrs| !addrs.is_empty()),
+            peers,
+            min_peers,
+            storage_writable,
+        }
+    }
+
+    // The process is alive and not stuck.
+    fn is_healthy(&self) -> bool {
+        self.swarm_responsive
+    }
+
+    // The node is able to serve requests.
+    fn is_ready(&self) -> bool {
+        self.listening && self.peers >= self.min_peers && self.storage_writable
+    }
+}
+
+/// Starts serving `/healthz`, `/readyz` and `/metrics` over HTTP on the given address, for
+/// container orchestrators to probe.
+///
+/// `/readyz` only succeeds once the node is listening, has at least `min_peers` peers in its
+/// routing table, and can write to its storage dir. Both endpoints return the state of the node
+/// as JSON, with a status of `200` on success and `503` otherwise. `/metrics` returns the
+/// records stored by the node and the payments for them, for operators to monitor.
+pub async fn start_health_service(
+    addr: SocketAddr,
+    node: Node,
+    min_peers: usize,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Health service listening on {addr}");
+
+    let _handle = spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(stream) = incoming.next().await {
+            match stream {
+                Ok(stream) => {
+                    let node = node.clone();
+                    let _handle = spawn(async move {
+                        if let Err(err) = handle_connection(stream, &node, min_peers).await {
+                            warn!("Failed to handle health request: {err}");
+                        }
+                    });
+                }
+                Err(err) => warn!("Failed to accept health connection: {err}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn handle_connection(stream: TcpStream, node: &Node, min_peers: usize) -> io::Result<()> {
+    let head = read_head(&mut BufReader::new(&stream)).await?;
+    let path = head.path();
+    trace!("Health request received for {path:?}");
+
+    let (passed, body) = match path {
+        "/healthz" | "/readyz" => {
+            let report = HealthReport::new(node, min_peers).await;
+            let passed = if path == "/healthz" {
+                report.is_healthy()
+            } else {
+                report.is_ready()
+            };
+            let body = serde_json::to_string(&report)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            (Some(passed), body)
+        }
+        "/metrics" => {
+            let body = serde_json::to_string(&MetricsReport::new(node))
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            (Some(true), body)
+        }
+        _ => (None, String::new()),
+    };
+    let status = match passed {
+        Some(true) => "200 OK",
+        Some(false) => "503 Service Unavailable",
+        None => "404 Not Found",
+    };
+
+    let headers = [("Content-Type", "application/json")];
+    respond(&mut &stream, status, &headers, body.as_bytes()).await
+}
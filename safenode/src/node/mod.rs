@@ -0,0 +1,784 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use self::handoff::ReplicaSource;
+use crate::{
+    network::{CmdPriority, Error as NetworkError, Misbehaviour, Network, RecordValidator},
+    protocol::{
+        error::{Error as ProtocolError, Result as ProtocolResult},
+        messages::{
+            Cmd, CmdResponse, Event, Query, QueryResponse, Replica, Request, Response, SignedCmd,
+            SignedResponse, FETCH_REPLICATION_MAX_KEYS, FETCH_REPLICATION_MAX_SIZE,
+        },
+        types::{
+            page::Page,
+            payment::{RoyaltyPolicy, StoreCost},
+            register::RegisterAddress,
+            spend::{
+                is_genesis_spend, verify_parent_spends, verify_parent_tx, verify_spend, DbcAddress,
+            },
+        },
+    },
+    storage::{
+        chunks::{Chunk, ChunkAddress},
+        DataStorage, Error as StorageError,
+    },
+};
+use audit::{AuditStats, CloseGroupJoins};
+use bytes::Bytes;
+use libp2p::PeerId;
+use query_cache::{CacheKey, Cached, QueryCache};
+use readiness::Readiness;
+use relay::RelayCache;
+use replay::ReplayCache;
+use scrub::ScrubStats;
+use sn_dbc::{DbcTransaction, SignedSpend};
+use stats::NodeStats;
+use std::{sync::Arc, time::Duration};
+use subscriptions::Subscriptions;
+use tracing::{debug, instrument, trace, warn};
+use xor_name::XorName;
+
+mod audit;
+mod config;
+mod handoff;
+#[cfg(feature = "health")]
+mod health;
+mod payment;
+mod query_cache;
+mod readiness;
+mod register_sync;
+mod relay;
+mod replay;
+mod replication;
+mod root_dir;
+#[cfg(feature = "rpc")]
+mod rpc;
+mod scrub;
+mod stats;
+mod subscriptions;
+
+pub use audit::{AuditCounts, AUDIT_PAUSE, REPLICATION_GRACE_PERIOD};
+pub use config::{ConfigError, LogConfig, NetworkConfig, NetworkPreset, NodeConfig};
+pub use handoff::{HandoffCounts, DEFAULT_HANDOFF_BUDGET};
+#[cfg(feature = "health")]
+pub use health::{start_health_service, DEFAULT_READY_MIN_PEERS};
+pub use query_cache::{QueryCacheStats, DEFAULT_QUERY_CACHE_TTL, QUERY_CACHE_MAX_ENTRIES};
+pub use readiness::{DEFAULT_MIN_PEERS_TO_SERVE, READY_LOOKUP_MAX_AGE};
+pub use register_sync::{REGISTER_SYNC_PAUSE, REGISTER_SYNC_ROUND_PAUSE};
+pub use relay::DEFAULT_RELAY_CACHE_TTL;
+pub use replication::REPLICATE_RECORDS_BATCH;
+pub use root_dir::{
+    NodeRootDir, RootDirError, KEYPAIR_FILENAME, LOG_DIR_NAME, RECORD_STORE_DIR_NAME,
+    WALLET_DIR_NAME,
+};
+#[cfg(feature = "rpc")]
+pub use rpc::start_rpc_service;
+pub use scrub::{ScrubCounts, SCRUB_PAUSE, SCRUB_ROUND_PAUSE};
+pub use stats::{StatsCounts, STATS_FILENAME, STATS_PERSIST_INTERVAL};
+
+/// Controls sent to the process running the node, by operator tooling.
+#[derive(Debug, Clone, Copy)]
+pub enum NodeCtrl {
+    /// Stop the node after the given delay, or once its records are handed off if longer.
+    Stop {
+        /// How long to wait before stopping.
+        delay: Duration,
+    },
+    /// Restart the node after the given delay.
+    Restart {
+        /// How long to wait before restarting.
+        delay: Duration,
+    },
+}
+
+/// `Node` represents a single node in the distributed network. It processes
+/// incoming requests and interacts with the data storage.
+#[derive(Clone)]
+pub struct Node {
+    network: Network,
+    storage: DataStorage,
+    subscriptions: Subscriptions,
+    replay_cache: ReplayCache,
+    scrub_stats: Arc<ScrubStats>,
+    audit_stats: Arc<AuditStats>,
+    close_group_joins: CloseGroupJoins,
+    stats: Arc<NodeStats>,
+    readiness: Readiness,
+    relay_cache: Option<RelayCache>,
+    query_cache: QueryCache,
+    royalties: Option<RoyaltyPolicy>,
+    store_cost: Option<StoreCost>,
+}
+
+impl Node {
+    /// Creates a `Node` backed by the given storage, reaching out to
+    /// other peers through the given `Network`
+    pub fn new(network: Network, storage: DataStorage) -> Self {
+        Self {
+            network,
+            storage,
+            subscriptions: Subscriptions::default(),
+            replay_cache: ReplayCache::default(),
+            scrub_stats: Arc::default(),
+            audit_stats: Arc::default(),
+            close_group_joins: CloseGroupJoins::default(),
+            stats: Arc::default(),
+            readiness: Readiness::default(),
+            relay_cache: None,
+            query_cache: QueryCache::new(DEFAULT_QUERY_CACHE_TTL),
+            royalties: None,
+            store_cost: None,
+        }
+    }
+
+    /// Has the node answer the queries for chunks it does not hold by fetching them from
+    /// their close group, caching them for `ttl` to answer the next queries, within
+    /// `capacity` bytes apart from the record store.
+    pub fn with_relay_cache(mut self, capacity: u64, ttl: Duration) -> Self {
+        self.relay_cache = Some(RelayCache::new(capacity, ttl));
+        self
+    }
+
+    /// Has the node cache its answers to the queries for the existence of a chunk and for its
+    /// storage level, and whether it is responsible for the data stored, for `ttl` instead of
+    /// [`DEFAULT_QUERY_CACHE_TTL`]. A zero `ttl` disables the cache.
+    pub fn with_query_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.query_cache = QueryCache::new(ttl);
+        self
+    }
+
+    /// The usage of the cache of the answers to queries.
+    pub fn query_cache_stats(&self) -> QueryCacheStats {
+        self.query_cache.stats()
+    }
+
+    /// Has the node take data only once it holds at least `min_peers` peers in its routing
+    /// table, instead of [`DEFAULT_MIN_PEERS_TO_SERVE`].
+    pub fn with_min_peers_to_serve(mut self, min_peers: usize) -> Self {
+        self.readiness = Readiness::new(min_peers);
+        self
+    }
+
+    /// Has the node reject the payments for storage not paying the royalties due as per
+    /// the policy.
+    pub fn with_royalties(mut self, policy: RoyaltyPolicy) -> Self {
+        self.royalties = Some(policy);
+        self
+    }
+
+    /// Has the node advertise the cost of the storage, and the address it is to be paid at,
+    /// rejecting the payments for storage not paying it that cost there.
+    pub fn with_store_cost(mut self, cost: StoreCost) -> Self {
+        self.store_cost = Some(cost);
+        self
+    }
+
+    /// The check of the records pushed by peers through Kademlia, to be set on the network.
+    ///
+    /// Only chunks are taken, under the name of their content. Registers and spends are only
+    /// valid once their signatures and the spentbook are checked, which is done for the `Cmd`s
+    /// storing them. The records pushed carry no payment, so they are kept in the Kademlia
+    /// store alone, never in the records the node stores for payment.
+    pub fn record_validator() -> RecordValidator {
+        RecordValidator::new(|record| {
+            let name = XorName::from_content(&record.value);
+            record.key.as_ref() == name.0.as_slice()
+        })
+    }
+
+    /// Handles an incoming `Request` from the given peer and returns the `Response` to be sent
+    /// back to it
+    #[instrument(skip(self, request))]
+    pub async fn handle_request(&self, peer: PeerId, request: Request) -> Response {
+        trace!("Handling request from {peer:?}: {request:?}");
+        match request {
+            Request::Cmd(signed_cmd) => {
+                Response::Cmd(self.handle_signed_cmd(peer, signed_cmd).await)
+            }
+            Request::Query(query) => Response::Query(self.handle_query(query).await),
+            Request::Event(event) => {
+                // Events are handed over to the upper layers by the network, not expected here.
+                warn!("Unexpected event pushed by {peer:?}: {event:?}");
+                Response::EventReceived
+            }
+        }
+    }
+
+    async fn handle_query(&self, query: Query) -> QueryResponse {
+        match query {
+            Query::GetChunk(addr) => {
+                let result = match self.storage.query(&addr).await {
+                    Ok(chunk) => Ok(chunk),
+                    Err(_) => self
+                        .relay_chunk(addr)
+                        .await
+                        .ok_or(ProtocolError::ChunkNotFound(addr)),
+                };
+                QueryResponse::GetChunk(result)
+            }
+            Query::GetChunkExists(addr) => {
+                self.cached_query(CacheKey::ChunkExists(addr), || {
+                    QueryResponse::GetChunkExists(self.storage.chunk_exists(&addr))
+                })
+                .await
+            }
+            Query::GetRegister(addr) => {
+                let result = self
+                    .storage
+                    .get_register(&addr)
+                    .await
+                    .map_err(|err| register_error(addr, err));
+                QueryResponse::GetRegister(result)
+            }
+            Query::GetRegisterDigest(addr) => {
+                let result = self
+                    .storage
+                    .get_register(&addr)
+                    .await
+                    .map(|register| register.digest())
+                    .map_err(|err| register_error(addr, err));
+                QueryResponse::GetRegisterDigest(result)
+            }
+            Query::GetRegisterEntries { address, known } => {
+                let result = self
+                    .storage
+                    .get_register(&address)
+                    .await
+                    .map(|register| register.entries_since(&known))
+                    .map_err(|err| register_error(address, err));
+                QueryResponse::GetRegisterEntries(result)
+            }
+            Query::GetStorageLevel => {
+                self.cached_query(CacheKey::StorageLevel, || {
+                    QueryResponse::GetStorageLevel(self.storage.storage_level())
+                })
+                .await
+            }
+            Query::ListChunks { cursor } => QueryResponse::ListChunks(Page::new(
+                self.storage.chunk_addresses(),
+                cursor.as_ref(),
+            )),
+            Query::GetSpend(addr) => {
+                let result = self
+                    .storage
+                    .get_spend(&addr)
+                    .await
+                    .map_err(|err| spend_error(addr, err));
+                QueryResponse::GetSpend(result)
+            }
+            Query::GetStoreCost(_) => QueryResponse::GetStoreCost(self.store_cost.clone()),
+            Query::GetRecord(name) => QueryResponse::GetRecord(self.local_record(name).await),
+            // Only the chunks held are proven, never relayed ones.
+            Query::ChunkProof { key, nonce } => {
+                let result = match self.storage.query(&key).await {
+                    Ok(chunk) => Ok(chunk.proof(&self.network.peer_id(), nonce)),
+                    Err(_) => Err(ProtocolError::ChunkNotFound(key)),
+                };
+                QueryResponse::ChunkProof(result)
+            }
+            Query::FetchReplication { keys } => {
+                let mut replicas = vec![];
+                let mut size = 0;
+                for name in keys.into_iter().take(FETCH_REPLICATION_MAX_KEYS) {
+                    if let Ok(replica) = self.local_record(name).await {
+                        size += rmp_serde::to_vec(&replica).map_or(0, |bytes| bytes.len());
+                        // The first record is always returned, for the pull to go on.
+                        if !replicas.is_empty() && size > FETCH_REPLICATION_MAX_SIZE {
+                            break;
+                        }
+                        replicas.push(replica);
+                    }
+                }
+                QueryResponse::FetchReplication(replicas)
+            }
+        }
+    }
+
+    // The record held at the name, looked for among the chunks, registers and spends in turn.
+    async fn local_record(&self, name: XorName) -> ProtocolResult<Replica> {
+        if let Ok(chunk) = self.storage.query(&ChunkAddress(name)).await {
+            return Ok(Replica::Chunk(chunk.value().clone()));
+        }
+        if let Ok(register) = self.storage.get_register_by_id(&name).await {
+            return Ok(Replica::Register(Box::new(register)));
+        }
+        match self.storage.get_spends_by_name(&name).await {
+            Ok(spends) if !spends.is_empty() => Ok(Replica::Spends(spends)),
+            _ => Err(ProtocolError::RecordNotFound(name)),
+        }
+    }
+
+    // Answers the query from the cache, or afresh, caching the answer.
+    async fn cached_query(
+        &self,
+        key: CacheKey,
+        answer: impl FnOnce() -> QueryResponse,
+    ) -> QueryResponse {
+        if let Some(Cached::Response(response)) = self.query_cache.get(&key).await {
+            return response;
+        }
+        let response = answer();
+        self.query_cache
+            .insert(key, Cached::Response(response.clone()))
+            .await;
+        response
+    }
+
+    // Stores the chunk, dropping the cached answers about it.
+    async fn store_chunk(&self, chunk: &Chunk) -> Result<(), StorageError> {
+        let result = self.storage.store(chunk).await;
+        self.query_cache.invalidate_chunk(chunk.address()).await;
+        result
+    }
+
+    // Drops the cmd if it is not signed by the sender, stale or a replay, otherwise handles it.
+    async fn handle_signed_cmd(&self, peer: PeerId, signed_cmd: SignedCmd) -> CmdResponse {
+        if let Err(err) = self.replay_cache.check(peer, &signed_cmd).await {
+            warn!("Dropping cmd {:?} from {peer:?}: {err}", signed_cmd.msg_id);
+            if let Err(err) = self
+                .network
+                .clone()
+                .report_misbehaviour(peer, Misbehaviour::InvalidData)
+                .await
+            {
+                warn!("Could not report the misbehaviour of {peer:?}: {err}");
+            }
+            return cmd_error(&signed_cmd.cmd, err);
+        }
+        self.handle_cmd(peer, signed_cmd.cmd).await
+    }
+
+    // Acknowledges the cmd once it has been applied, otherwise returns the cmd specific
+    // response carrying the error.
+    async fn handle_cmd(&self, peer: PeerId, cmd: Cmd) -> CmdResponse {
+        let cmd_hash = cmd.hash();
+        if mutates_data(&cmd) {
+            if let Err(err) = self.check_ready().await {
+                debug!("Rejecting cmd {cmd_hash:?} from {peer:?}: {err}");
+                if matches!(cmd, Cmd::StoreChunk { .. } | Cmd::RegisterCreate { .. }) {
+                    self.stats.record_rejected();
+                }
+                return cmd_error(&cmd, err);
+            }
+        }
+        let resp = self.apply_cmd(peer, cmd).await;
+        if is_applied(&resp) {
+            CmdResponse::Ack { cmd_hash }
+        } else {
+            resp
+        }
+    }
+
+    async fn apply_cmd(&self, peer: PeerId, cmd: Cmd) -> CmdResponse {
+        match cmd {
+            Cmd::StoreChunk {
+                address,
+                value,
+                payment,
+            } => {
+                if let Err(err) = self.check_responsible(address.name()).await {
+                    self.stats.record_rejected();
+                    return CmdResponse::StoreChunk(Err(err));
+                }
+                let chunk = match validate_chunk(address, value) {
+                    Ok(chunk) => chunk,
+                    Err(resp) => {
+                        self.stats.record_rejected();
+                        return resp;
+                    }
+                };
+                if let Err(err) = self.verify_payment(address.name(), &payment).await {
+                    self.stats.record_rejected();
+                    return CmdResponse::StoreChunk(Err(err));
+                }
+                let result = self.store_chunk(&chunk).await.map_err(|err| match err {
+                    StorageError::NotEnoughSpace => {
+                        ProtocolError::QuotaExceeded(self.storage.storage_level())
+                    }
+                    other => {
+                        warn!("Failed to store chunk {address:?}: {other}");
+                        ProtocolError::ChunkNotStored(address)
+                    }
+                });
+                self.record_put(&result, &payment);
+                CmdResponse::StoreChunk(result)
+            }
+            Cmd::RegisterCreate {
+                address,
+                policy,
+                payment,
+            } => {
+                if let Err(err) = self.check_responsible(&address.id()).await {
+                    self.stats.record_rejected();
+                    return CmdResponse::RegisterCreate(Err(err));
+                }
+                if let Err(err) = self.verify_payment(&address.id(), &payment).await {
+                    self.stats.record_rejected();
+                    return CmdResponse::RegisterCreate(Err(err));
+                }
+                let result = self
+                    .storage
+                    .create_register(&address, policy)
+                    .await
+                    .map_err(|err| register_error(address, err));
+                self.record_put(&result, &payment);
+                CmdResponse::RegisterCreate(result)
+            }
+            Cmd::RegisterEdit(op) => {
+                let addr = op.address;
+                let result = self
+                    .storage
+                    .edit_register(op.clone())
+                    .await
+                    .map_err(|err| register_error(addr, err));
+                if result.is_ok() {
+                    self.subscriptions.publish(Event::RegisterEdited(op)).await;
+                }
+                CmdResponse::RegisterEdit(result)
+            }
+            Cmd::RegisterSetPolicy(op) => {
+                let addr = op.address;
+                let result = self
+                    .storage
+                    .set_register_policy(op)
+                    .await
+                    .map_err(|err| register_error(addr, err));
+                CmdResponse::RegisterSetPolicy(result)
+            }
+            Cmd::SyncRegister(address) => {
+                CmdResponse::SyncRegister(self.sync_register(address).await)
+            }
+            Cmd::SpendDbc {
+                signed_spend,
+                parent_tx,
+            } => {
+                let result = self.handle_spend(&signed_spend, &parent_tx).await;
+                if result.is_ok() {
+                    self.subscriptions
+                        .publish(Event::SpendRecorded(signed_spend))
+                        .await;
+                }
+                CmdResponse::Spend(result)
+            }
+            Cmd::Replicate(replica) => CmdResponse::Replicate(
+                self.store_replica(peer, replica, ReplicaSource::Handoff)
+                    .await,
+            ),
+            Cmd::ReplicateRecords { keys } => {
+                CmdResponse::ReplicateRecords(self.pull_records(peer, keys).await)
+            }
+            Cmd::Subscribe(topic) => {
+                self.subscriptions
+                    .subscribe(peer, topic, &self.network)
+                    .await;
+                CmdResponse::Subscribe(Ok(()))
+            }
+            Cmd::Unsubscribe(topic) => {
+                self.subscriptions.unsubscribe(peer, topic).await;
+                CmdResponse::Unsubscribe(Ok(()))
+            }
+        }
+    }
+
+    // Rejects the data at `name` if it is clearly out of the range we are responsible for.
+    // Should our view of the network not be known, the data is taken.
+    async fn check_responsible(&self, name: &XorName) -> ProtocolResult<()> {
+        let key = CacheKey::Responsible(*name);
+        let responsible = match self.query_cache.get(&key).await {
+            Some(Cached::Responsible(responsible)) => Ok(responsible),
+            _ => {
+                let result = self.network.clone().is_responsible_for(*name).await;
+                if let Ok(responsible) = result {
+                    self.query_cache
+                        .insert(key, Cached::Responsible(responsible))
+                        .await;
+                }
+                result
+            }
+        };
+        match responsible {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                debug!("Rejecting data at {name:?}, out of our range");
+                Err(ProtocolError::OutOfRange(*name))
+            }
+            Err(err) => {
+                warn!("Could not check whether we are responsible for {name:?}: {err}");
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle_spend(
+        &self,
+        signed_spend: &SignedSpend,
+        parent_tx: &DbcTransaction,
+    ) -> ProtocolResult<()> {
+        let addr = DbcAddress::from_dbc_id(signed_spend.dbc_id());
+        self.check_responsible(addr.name()).await?;
+        verify_spend(signed_spend)?;
+        verify_parent_tx(signed_spend, parent_tx)?;
+        if !is_genesis_spend(signed_spend) {
+            self.verify_parents(signed_spend, parent_tx).await?;
+        }
+
+        // Checks against the spends we already hold.
+        self.storage
+            .try_add_spend(signed_spend)
+            .await
+            .map_err(|err| spend_error(addr, err))?;
+
+        // Checks against the spends held by the rest of the close group.
+        if let Some(existing) = self
+            .conflicting_spend_in_close_group(addr, signed_spend)
+            .await
+        {
+            warn!("Double spend attempt detected in the close group of {addr:?}");
+            // Hold on to the conflicting spend as well, as proof of the attempt.
+            let _ = self.storage.try_add_spend(&existing).await;
+            return Err(ProtocolError::DoubleSpendAttempt {
+                new: Box::new(signed_spend.clone()),
+                existing: Box::new(existing),
+            });
+        }
+
+        Ok(())
+    }
+
+    // Asks the close group of the spend for the spends they hold, returning the first one
+    // that does not match the given spend.
+    async fn conflicting_spend_in_close_group(
+        &self,
+        addr: DbcAddress,
+        signed_spend: &SignedSpend,
+    ) -> Option<SignedSpend> {
+        let request = Request::Query(Query::GetSpend(addr));
+        let responses = match self.network.send_to_closest(*addr.name(), request).await {
+            Ok(responses) => responses,
+            Err(err) => {
+                warn!("Could not check the close group of {addr:?} for double spends: {err}");
+                return None;
+            }
+        };
+
+        verified(responses)
+            .find_map(|resp| match resp {
+                Response::Query(QueryResponse::GetSpend(Ok(spend))) => Some(spend),
+                Response::Query(QueryResponse::GetSpend(Err(
+                    ProtocolError::DoubleSpendAttempt { new, existing },
+                ))) => Some(if new.as_ref() == signed_spend {
+                    *existing
+                } else {
+                    *new
+                }),
+                _ => None,
+            })
+            // Only a valid spend proves the attempt, any other is not to be stored.
+            .filter(|spend| spend != signed_spend && verify_spend(spend).is_ok())
+    }
+
+    // Checks that the parents of the spent Dbc are recorded in the spentbook as spent in the
+    // transaction that created it, for no Dbc to be spent that was not paid for.
+    async fn verify_parents(
+        &self,
+        signed_spend: &SignedSpend,
+        parent_tx: &DbcTransaction,
+    ) -> ProtocolResult<()> {
+        let mut parent_spends = vec![];
+        for input in &parent_tx.inputs {
+            let parent_addr = DbcAddress::from_dbc_id(&input.dbc_id);
+            if let Some(parent_spend) = self.recorded_spend(parent_addr).await {
+                parent_spends.push(parent_spend);
+            }
+        }
+        verify_parent_spends(signed_spend, parent_tx, &parent_spends)
+    }
+
+    // Returns the spend recorded at the address, by us or a majority of the close group of
+    // the address, so that a single peer cannot vouch for it.
+    async fn recorded_spend(&self, addr: DbcAddress) -> Option<SignedSpend> {
+        if let Ok(spend) = self.storage.get_spend(&addr).await {
+            return Some(spend);
+        }
+
+        let request = Request::Query(Query::GetSpend(addr));
+        let responses = match self.network.send_to_closest(*addr.name(), request).await {
+            Ok(responses) => responses,
+            Err(err) => {
+                warn!("Could not query the spentbook for {addr:?}: {err}");
+                return None;
+            }
+        };
+        let quorum = responses.len() / 2 + 1;
+        let mut vouched: Vec<(SignedSpend, usize)> = vec![];
+        for resp in verified(responses) {
+            if let Response::Query(QueryResponse::GetSpend(Ok(spend))) = resp {
+                match vouched.iter_mut().find(|(vouched, _)| vouched == &spend) {
+                    Some((_, count)) => *count += 1,
+                    None => vouched.push((spend, 1)),
+                }
+            }
+        }
+        let recorded = vouched
+            .into_iter()
+            .find(|(_, count)| *count >= quorum)
+            .map(|(spend, _)| spend);
+        if recorded.is_none() {
+            debug!("No spend at {addr:?} vouched for by {quorum} peers");
+        }
+        recorded
+    }
+
+    // Answers the query for a chunk not held locally from the relay cache, fetching the chunk
+    // from its close group when not cached. Not done when the relay cache is disabled, nor
+    // when the chunk is already being fetched, for queries relayed back to us not to loop.
+    async fn relay_chunk(&self, addr: ChunkAddress) -> Option<Chunk> {
+        let relay_cache = self.relay_cache.as_ref()?;
+        if let Some(chunk) = relay_cache.get(&addr).await {
+            trace!("Chunk {addr:?} served from the relay cache");
+            return Some(chunk);
+        }
+        if !relay_cache.start(addr).await {
+            return None;
+        }
+        let chunk = self.fetch_chunk(addr, CmdPriority::Query).await;
+        relay_cache.end(addr, chunk.clone()).await;
+        chunk
+    }
+
+    // Fetches the chunk from its close group at the given priority, checking the copies
+    // returned against its address.
+    async fn fetch_chunk(&self, addr: ChunkAddress, priority: CmdPriority) -> Option<Chunk> {
+        let request = Request::Query(Query::GetChunk(addr));
+        let network = self.network.at_priority(priority);
+        let responses = match network.send_to_closest(*addr.name(), request).await {
+            Ok(responses) => responses,
+            Err(err) => {
+                warn!("Could not fetch chunk {addr:?}: {err}");
+                return None;
+            }
+        };
+        verified(responses).find_map(|resp| match resp {
+            // Checked against the name, as peers may hold a corrupt copy.
+            Response::Query(QueryResponse::GetChunk(Ok(chunk))) => {
+                Some(chunk).filter(|chunk| chunk.address() == &addr)
+            }
+            _ => None,
+        })
+    }
+}
+
+// Drops the responses that failed, or that are not signed by the peer they came from.
+fn verified(
+    responses: Vec<(PeerId, Result<SignedResponse, NetworkError>)>,
+) -> impl Iterator<Item = Response> {
+    responses
+        .into_iter()
+        .filter_map(|(peer, result)| result.ok().map(|signed| (peer, signed)))
+        .filter_map(|(peer, signed)| match signed.verify() {
+            Ok(signer) if signer == peer => Some(signed.response),
+            Ok(signer) => {
+                warn!("Discarding response from {peer:?} signed by {signer:?}");
+                None
+            }
+            Err(err) => {
+                warn!("Discarding response from {peer:?}: {err}");
+                None
+            }
+        })
+}
+
+// Whether the response reports the cmd as successfully applied.
+fn is_applied(resp: &CmdResponse) -> bool {
+    matches!(
+        resp,
+        CmdResponse::StoreChunk(Ok(()))
+            | CmdResponse::RegisterCreate(Ok(()))
+            | CmdResponse::RegisterEdit(Ok(()))
+            | CmdResponse::RegisterSetPolicy(Ok(()))
+            | CmdResponse::SyncRegister(Ok(()))
+            | CmdResponse::Spend(Ok(()))
+            | CmdResponse::Subscribe(Ok(()))
+            | CmdResponse::Unsubscribe(Ok(()))
+            | CmdResponse::Replicate(Ok(()))
+            | CmdResponse::ReplicateRecords(Ok(()))
+    )
+}
+
+// Whether the cmd stores or changes data, which the node only takes once ready.
+fn mutates_data(cmd: &Cmd) -> bool {
+    match cmd {
+        Cmd::StoreChunk { .. }
+        | Cmd::RegisterCreate { .. }
+        | Cmd::RegisterEdit(_)
+        | Cmd::RegisterSetPolicy(_)
+        | Cmd::SyncRegister(_)
+        | Cmd::SpendDbc { .. }
+        | Cmd::Replicate(_) => true,
+        // The records are only stored as they are pulled, as replicas.
+        Cmd::Subscribe(_) | Cmd::Unsubscribe(_) | Cmd::ReplicateRecords { .. } => false,
+    }
+}
+
+// The cmd specific response carrying the error.
+fn cmd_error(cmd: &Cmd, err: ProtocolError) -> CmdResponse {
+    match cmd {
+        Cmd::StoreChunk { .. } => CmdResponse::StoreChunk(Err(err)),
+        Cmd::RegisterCreate { .. } => CmdResponse::RegisterCreate(Err(err)),
+        Cmd::RegisterEdit(_) => CmdResponse::RegisterEdit(Err(err)),
+        Cmd::RegisterSetPolicy(_) => CmdResponse::RegisterSetPolicy(Err(err)),
+        Cmd::SyncRegister(_) => CmdResponse::SyncRegister(Err(err)),
+        Cmd::SpendDbc { .. } => CmdResponse::Spend(Err(err)),
+        Cmd::Subscribe(_) => CmdResponse::Subscribe(Err(err)),
+        Cmd::Unsubscribe(_) => CmdResponse::Unsubscribe(Err(err)),
+        Cmd::Replicate(_) => CmdResponse::Replicate(Err(err)),
+        Cmd::ReplicateRecords { .. } => CmdResponse::ReplicateRecords(Err(err)),
+    }
+}
+
+// Recomputes the address of the chunk from its value, and rejects the record if it does not
+// match the address it was sent to be stored at.
+fn validate_chunk(address: ChunkAddress, value: Bytes) -> Result<Chunk, CmdResponse> {
+    let chunk = Chunk::new(value);
+    if chunk.address() != &address {
+        warn!(
+            "Rejecting chunk sent for {address:?}, its content hashes to {:?}",
+            chunk.address()
+        );
+        return Err(CmdResponse::InvalidChunkAddress {
+            claimed: address,
+            computed: *chunk.address(),
+        });
+    }
+    Ok(chunk)
+}
+
+// Maps a storage error from a register operation to the error returned to the requester.
+fn register_error(addr: RegisterAddress, err: StorageError) -> ProtocolError {
+    match err {
+        StorageError::RegisterNotFound(addr) => ProtocolError::RegisterNotFound(addr),
+        StorageError::Protocol(err) => err,
+        other => {
+            warn!("Register operation on {addr:?} failed: {other}");
+            ProtocolError::RegisterNotStored(addr)
+        }
+    }
+}
+
+// Maps a storage error from a spend operation to the error returned to the requester.
+fn spend_error(addr: DbcAddress, err: StorageError) -> ProtocolError {
+    match err {
+        StorageError::SpendNotFound(addr) => ProtocolError::SpendNotFound(addr),
+        StorageError::Protocol(err) => err,
+        other => {
+            warn!("Spend operation on {addr:?} failed: {other}");
+            ProtocolError::SpendNotStored(addr)
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+use crate::protocol::{
+    error::{Error as ProtocolError, Result as ProtocolResult},
+    types::{
+        payment::PaymentProof,
+        spend::{verify_spend, DbcAddress},
+    },
+};
+use sn_dbc::SignedSpend;
+use tracing::debug;
+use xor_name::XorName;
+
+impl Node {
+    /// Verifies that the storage of the data at `name` has been paid for, i.e. that every spend
+    /// in the proof commits to `name` and has been recorded in the spentbook, and that the
+    /// node is paid its store cost and the royalties due are paid, if the node requires them.
+    pub(super) async fn verify_payment(
+        &self,
+        name: &XorName,
+        payment: &PaymentProof,
+    ) -> ProtocolResult<()> {
+        let invalid = |details: String| ProtocolError::InvalidPaymentProof(*name, details);
+
+        if payment.spends.is_empty() {
+            return Err(ProtocolError::PaymentRequired(*name));
+        }
+
+        let expected_reason = PaymentProof::reason_for(name);
+        for signed_spend in &payment.spends {
+            let addr = DbcAddress::from_dbc_id(signed_spend.dbc_id());
+            if signed_spend.reason() != expected_reason {
+                return Err(invalid(format!("spend at {addr:?} pays for other data")));
+            }
+            verify_spend(signed_spend)
+                .map_err(|err| invalid(format!("spend at {addr:?} is invalid: {err}")))?;
+            if !self.is_in_spentbook(addr, signed_spend).await {
+                return Err(invalid(format!(
+                    "spend at {addr:?} is not in the spentbook"
+                )));
+            }
+        }
+
+        if let Some(cost) = &self.store_cost {
+            payment.verify_store_cost(name, cost)?;
+        }
+        if let Some(policy) = &self.royalties {
+            payment.verify_royalties(name, policy)?;
+        }
+
+        debug!("Verified payment for the storage of {name:?}");
+        Ok(())
+    }
+
+    // Checks that the spend is the one recorded in the spentbook, see `recorded_spend`.
+    async fn is_in_spentbook(&self, addr: DbcAddress, signed_spend: &SignedSpend) -> bool {
+        self.recorded_spend(addr).await.as_ref() == Some(signed_spend)
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{protocol::messages::QueryResponse, storage::chunks::ChunkAddress};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use xor_name::XorName;
+
+/// How long the answers to idempotent queries are cached for by default.
+pub const DEFAULT_QUERY_CACHE_TTL: Duration = Duration::from_secs(1);
+/// The max number of answers cached at once.
+pub const QUERY_CACHE_MAX_ENTRIES: usize = 1024;
+
+/// The answers cached, by what they answer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(super) enum CacheKey {
+    ChunkExists(ChunkAddress),
+    StorageLevel,
+    // Whether we are among the closest peers to the name.
+    Responsible(XorName),
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum Cached {
+    Response(QueryResponse),
+    Responsible(bool),
+}
+
+/// The usage of the cache of the answers to idempotent queries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct QueryCacheStats {
+    /// The queries answered from the cache.
+    pub hits: u64,
+    /// The queries answered afresh, the answer being cached.
+    pub misses: u64,
+    /// The answers dropped before expiring, the data they were about having been written.
+    pub invalidations: u64,
+}
+
+/// The answers to the idempotent queries of peers, kept for a short while for hot repeated
+/// queries to not hit the store or look up the closest peers over and over. The answers
+/// about a chunk are invalidated as soon as it is written or removed.
+#[derive(Clone)]
+pub(super) struct QueryCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<CacheKey, (Instant, Cached)>>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    invalidations: Arc<AtomicU64>,
+}
+
+impl QueryCache {
+    /// A cache keeping the answers for `ttl`, a zero `ttl` disabling it.
+    pub(super) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::default(),
+            hits: Arc::default(),
+            misses: Arc::default(),
+            invalidations: Arc::default(),
+        }
+    }
+
+    /// Returns the answer cached for the key, unless expired.
+    pub(super) async fn get(&self, key: &CacheKey) -> Option<Cached> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+        let mut entries = self.entries.lock().await;
+        let cached = match entries.get(key) {
+            Some((cached_at, cached)) if cached_at.elapsed() <= self.ttl => Some(cached.clone()),
+            Some(_) => {
+                let _ = entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if cached.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        let _ = counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    /// Caches the answer, making room for it by dropping the expired answers, or the oldest
+    /// one if none expired.
+    pub(super) async fn insert(&self, key: CacheKey, cached: Cached) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().await;
+        if entries.len() >= QUERY_CACHE_MAX_ENTRIES && !entries.contains_key(&key) {
+            let ttl = self.ttl;
+            entries.retain(|_, (cached_at, _)| cached_at.elapsed() <= ttl);
+            if entries.len() >= QUERY_CACHE_MAX_ENTRIES {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, (cached_at, _))| *cached_at)
+                    .map(|(key, _)| *key);
+                if let Some(oldest) = oldest {
+                    let _ = entries.remove(&oldest);
+                }
+            }
+        }
+        let _ = entries.insert(key, (Instant::now(), cached));
+    }
+
+    /// Drops the answers about the chunk, and the storage level, the chunk having been
+    /// written or removed.
+    pub(super) async fn invalidate_chunk(&self, address: &ChunkAddress) {
+        let mut entries = self.entries.lock().await;
+        for key in [CacheKey::ChunkExists(*address), CacheKey::StorageLevel] {
+            if entries.remove(&key).is_some() {
+                let _ = self.invalidations.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub(super) fn stats(&self) -> QueryCacheStats {
+        QueryCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+        }
+    }
+}
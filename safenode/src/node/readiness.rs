@@ -0,0 +1,97 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+use crate::{
+    network::CLOSE_GROUP_SIZE,
+    protocol::error::{Error as ProtocolError, Result as ProtocolResult},
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+use xor_name::XorName;
+
+/// The min number of peers in the routing table for the node to take data, unless configured
+/// otherwise: with fewer, it cannot tell whether it is among the close group of the data.
+pub const DEFAULT_MIN_PEERS_TO_SERVE: usize = CLOSE_GROUP_SIZE;
+/// How long a successful lookup is taken as proof of the node being connected to the network.
+/// Past it, a new lookup is made before the next data is taken.
+pub const READY_LOOKUP_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Gates the data-mutating cmds until the node is connected enough to the network: it holds
+/// at least `min_peers` peers in its routing table, and a lookup succeeded lately.
+#[derive(Clone)]
+pub(super) struct Readiness {
+    min_peers: usize,
+    // When a lookup last succeeded. Locked during the lookups, for a single one to be made
+    // at a time by the cmds waiting for it.
+    last_lookup: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Readiness {
+    pub(super) fn new(min_peers: usize) -> Self {
+        Self {
+            min_peers,
+            last_lookup: Arc::default(),
+        }
+    }
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_PEERS_TO_SERVE)
+    }
+}
+
+impl Node {
+    // Rejects the cmd with `NotReady` unless the node is connected enough to the network.
+    pub(super) async fn check_ready(&self) -> ProtocolResult<()> {
+        let mut network = self.network.clone();
+        let peers = network
+            .routing_table_size()
+            .await
+            .map_err(|err| ProtocolError::NotReady(format!("routing table unknown: {err}")))?;
+        let min_peers = self.readiness.min_peers;
+        if peers < min_peers {
+            return Err(ProtocolError::NotReady(format!(
+                "{peers} of the {min_peers} peers required in the routing table"
+            )));
+        }
+
+        let mut last_lookup = self.readiness.last_lookup.lock().await;
+        if matches!(*last_lookup, Some(at) if at.elapsed() < READY_LOOKUP_MAX_AGE) {
+            return Ok(());
+        }
+        // Looks up the peers around us, as Kademlia does to refresh its routing table.
+        let our_name = XorName::from_content(&network.peer_id().to_bytes());
+        match network.get_closest_peers(our_name).await {
+            Ok(peers) if !peers.is_empty() => {
+                if last_lookup.is_none() {
+                    info!(
+                        "Node ready to take data, with {} peers found close",
+                        peers.len()
+                    );
+                }
+                *last_lookup = Some(Instant::now());
+                Ok(())
+            }
+            Ok(_) => Err(ProtocolError::NotReady(
+                "no peers found by the latest lookup".to_string(),
+            )),
+            Err(err) => {
+                debug!("Readiness lookup failed: {err}");
+                Err(ProtocolError::NotReady(format!(
+                    "latest lookup failed: {err}"
+                )))
+            }
+        }
+    }
+}
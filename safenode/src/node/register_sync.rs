@@ -0,0 +1,176 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{register_error, Node};
+use crate::{
+    network::{CmdPriority, Misbehaviour},
+    protocol::{
+        error::{Error as ProtocolError, Result as ProtocolResult},
+        messages::{Query, QueryResponse, Request, Response},
+        types::register::RegisterAddress,
+    },
+    storage::{Error as StorageError, RecordKind},
+};
+use async_std::task::sleep;
+use futures::future;
+use libp2p::PeerId;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How long to pause between the syncs of two registers, for the syncing to stay in the
+/// background of the handling of requests.
+pub const REGISTER_SYNC_PAUSE: Duration = Duration::from_secs(1);
+/// How long to pause between two rounds of syncs of all the registers held.
+pub const REGISTER_SYNC_ROUND_PAUSE: Duration = Duration::from_secs(10 * 60);
+
+impl Node {
+    /// Syncs the replicas of the registers held with the ones of the rest of their close
+    /// group, one register at a time and forever, for the replicas edited concurrently on
+    /// different nodes not to stay diverged.
+    ///
+    /// Each holder pulls the entries it misses from the others, so that all converge.
+    pub async fn sync_registers(self) {
+        loop {
+            sleep(REGISTER_SYNC_ROUND_PAUSE).await;
+            let registers: Vec<_> = self
+                .storage
+                .local_records()
+                .into_iter()
+                .filter(|record| record.kind == RecordKind::Register)
+                .collect();
+            info!("Syncing {} registers", registers.len());
+            for record in registers {
+                sleep(REGISTER_SYNC_PAUSE).await;
+                let address = match self.storage.get_register_by_id(&record.key).await {
+                    Ok(register) => *register.address(),
+                    // E.g. the register was removed since it was listed.
+                    Err(err) => {
+                        debug!("Could not read register {:?} to sync: {err}", record.key);
+                        continue;
+                    }
+                };
+                if let Err(err) = self.sync_register(address).await {
+                    warn!("Could not sync register {address:?}: {err}");
+                }
+            }
+        }
+    }
+
+    /// Pulls the entries of the register held by the rest of its close group that our
+    /// replica misses, merging them into it.
+    pub(super) async fn sync_register(&self, address: RegisterAddress) -> ProtocolResult<()> {
+        // Fails if the register is not held.
+        let _ = self
+            .storage
+            .get_register(&address)
+            .await
+            .map_err(|err| register_error(address, err))?;
+
+        let mut network = self.network.at_priority(CmdPriority::Replication);
+        let close_group = network
+            .get_closest_peers(address.id())
+            .await
+            .map_err(|err| {
+                warn!("Could not find the close group of {address:?} to sync: {err}");
+                ProtocolError::RegisterNotStored(address)
+            })?;
+        let own_id = network.peer_id();
+        let syncs = close_group
+            .into_iter()
+            .map(|(peer, _)| peer)
+            .filter(|peer| *peer != own_id)
+            .map(|peer| self.sync_register_with(peer, address));
+        let merged = future::join_all(syncs)
+            .await
+            .into_iter()
+            .filter(|merged| *merged)
+            .count();
+        if merged > 0 {
+            debug!("Merged the entries of {merged} peers into register {address:?}");
+        }
+        Ok(())
+    }
+
+    // Pulls the entries the peer holds that our replica misses, should their digests differ,
+    // returning whether any were merged. The entries are only merged if the policy is signed by
+    // the owner and every op by a key the policy allows to write, the peer being reported
+    // otherwise.
+    async fn sync_register_with(&self, peer: PeerId, address: RegisterAddress) -> bool {
+        let ours = match self.storage.get_register(&address).await {
+            Ok(register) => register,
+            Err(err) => {
+                debug!("Could not read register {address:?} to sync: {err}");
+                return false;
+            }
+        };
+        let mut network = self.network.at_priority(CmdPriority::Replication);
+        let query = Query::GetRegisterDigest(address);
+        let digest = match network.send_request(Request::Query(query), peer).await {
+            Ok(signed) => match signed.response {
+                Response::Query(QueryResponse::GetRegisterDigest(Ok(digest))) => digest,
+                other => {
+                    debug!("{peer:?} sent no digest of register {address:?}: {other:?}");
+                    return false;
+                }
+            },
+            Err(err) => {
+                debug!("Could not get the digest of register {address:?} from {peer:?}: {err}");
+                return false;
+            }
+        };
+        // The peer holds nothing we do not, it is to pull from us instead.
+        let ours_digest = ours.digest();
+        if digest.policy_version <= ours_digest.policy_version
+            && digest.heads.iter().all(|head| ours.has_entry(head))
+        {
+            return false;
+        }
+
+        let query = Query::GetRegisterEntries {
+            address,
+            known: ours_digest.heads,
+        };
+        let entries = match network.send_request(Request::Query(query), peer).await {
+            Ok(signed) => match signed.response {
+                Response::Query(QueryResponse::GetRegisterEntries(Ok(entries))) => entries,
+                other => {
+                    debug!("{peer:?} sent no entries of register {address:?}: {other:?}");
+                    return false;
+                }
+            },
+            Err(err) => {
+                debug!("Could not get the entries of register {address:?} from {peer:?}: {err}");
+                return false;
+            }
+        };
+        let pulled = entries.ops.len();
+        match self.storage.merge_register_entries(&address, entries).await {
+            Ok(()) => {
+                debug!("Merged {pulled} entries of register {address:?} pulled from {peer:?}");
+                true
+            }
+            // The policy or an op is not signed as it should, none of the entries are taken.
+            Err(StorageError::Protocol(err)) => {
+                warn!("Rejected the entries of register {address:?} pulled from {peer:?}: {err}");
+                if let Err(err) = self
+                    .network
+                    .clone()
+                    .report_misbehaviour(peer, Misbehaviour::InvalidData)
+                    .await
+                {
+                    warn!("Could not report the misbehaviour of {peer:?}: {err}");
+                }
+                false
+            }
+            Err(err) => {
+                warn!("Failed to merge the entries of register {address:?} pulled from {peer:?}: {err}");
+                false
+            }
+        }
+    }
+}
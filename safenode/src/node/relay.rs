@@ -0,0 +1,110 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::storage::chunks::{Chunk, ChunkAddress};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// How long relayed chunks are cached for by default.
+pub const DEFAULT_RELAY_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// The chunks a node fetched from their close group to answer a query for a chunk it does not
+/// hold, kept for a while to answer the next queries for them, so that the close group of
+/// popular chunks is not asked for them over and over.
+///
+/// The cache is bounded by its own quota, apart from the record store.
+#[derive(Clone)]
+pub(super) struct RelayCache {
+    capacity: u64,
+    ttl: Duration,
+    relayed: Arc<Mutex<Relayed>>,
+}
+
+#[derive(Default)]
+struct Relayed {
+    used: u64,
+    chunks: HashMap<ChunkAddress, Chunk>,
+    // The chunks in the order they were cached, to expire the oldest first.
+    expiry: VecDeque<(Instant, ChunkAddress)>,
+    // The chunks being fetched, for a query relayed back to us not to be relayed again.
+    in_flight: HashSet<ChunkAddress>,
+}
+
+impl RelayCache {
+    pub(super) fn new(capacity: u64, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            relayed: Arc::default(),
+        }
+    }
+
+    /// Returns the chunk if it was relayed lately.
+    pub(super) async fn get(&self, address: &ChunkAddress) -> Option<Chunk> {
+        let mut relayed = self.relayed.lock().await;
+        relayed.expire(self.ttl);
+        relayed.chunks.get(address).cloned()
+    }
+
+    /// Marks the chunk as being fetched, returning `false` if it already is.
+    pub(super) async fn start(&self, address: ChunkAddress) -> bool {
+        self.relayed.lock().await.in_flight.insert(address)
+    }
+
+    /// Ends the fetch of the chunk, caching it if it was found and fits the quota, the oldest
+    /// chunks being dropped to make room for it.
+    pub(super) async fn end(&self, address: ChunkAddress, chunk: Option<Chunk>) {
+        let mut relayed = self.relayed.lock().await;
+        let _ = relayed.in_flight.remove(&address);
+        let chunk = match chunk {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        let size = chunk.value().len() as u64;
+        if size > self.capacity || relayed.chunks.contains_key(&address) {
+            return;
+        }
+        relayed.expire(self.ttl);
+        while relayed.used + size > self.capacity {
+            if !relayed.pop_oldest() {
+                break;
+            }
+        }
+        relayed.used += size;
+        let _ = relayed.chunks.insert(address, chunk);
+        relayed.expiry.push_back((Instant::now(), address));
+    }
+}
+
+impl Relayed {
+    // Drops the chunks cached for longer than the ttl.
+    fn expire(&mut self, ttl: Duration) {
+        while let Some((cached_at, _)) = self.expiry.front() {
+            if cached_at.elapsed() <= ttl {
+                break;
+            }
+            let _ = self.pop_oldest();
+        }
+    }
+
+    fn pop_oldest(&mut self) -> bool {
+        match self.expiry.pop_front() {
+            Some((_, address)) => {
+                if let Some(chunk) = self.chunks.remove(&address) {
+                    self.used -= chunk.value().len() as u64;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::protocol::{
+    error::{Error as ProtocolError, Result as ProtocolResult},
+    messages::{unix_time_secs, MsgId, SignedCmd},
+};
+use libp2p::PeerId;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// How far the issue time of a cmd may be from the time of the node for the cmd to be accepted.
+/// Older cmds are dropped as stale, so their ids only need to be remembered for this long.
+pub(super) const MAX_CMD_AGE: Duration = Duration::from_secs(5 * 60);
+/// The max number of ids remembered for a single issuer. Further cmds of the issuer are
+/// rate limited until its oldest ids expire.
+const MAX_SEEN_PER_ISSUER: usize = 10_000;
+/// The max number of ids remembered overall. Past it, the oldest ids are forgotten first,
+/// which a flood of cmds from many issuers could otherwise grow without bound.
+const MAX_SEEN: usize = 100_000;
+// How long an id is remembered: both the past and the future margin of the issue time.
+const SEEN_FOR: Duration = Duration::from_secs(2 * MAX_CMD_AGE.as_secs());
+
+/// The ids of the cmds handled within the last [`MAX_CMD_AGE`], per issuer.
+#[derive(Clone, Default)]
+pub(super) struct ReplayCache {
+    seen: Arc<Mutex<Seen>>,
+}
+
+#[derive(Default)]
+struct Seen {
+    ids: HashSet<(PeerId, MsgId)>,
+    // The ids in the order they were seen, to expire the oldest first.
+    expiry: VecDeque<(Instant, PeerId, MsgId)>,
+    // The number of ids remembered for each issuer.
+    per_issuer: HashMap<PeerId, usize>,
+}
+
+impl ReplayCache {
+    /// Checks that the cmd was signed by the peer that sent it, is fresh, and has not been
+    /// handled already, recording its id as seen if so.
+    pub(super) async fn check(&self, sender: PeerId, signed_cmd: &SignedCmd) -> ProtocolResult<()> {
+        let issuer = signed_cmd.verify()?;
+        if issuer != sender {
+            return Err(ProtocolError::InvalidCmdSignature(format!(
+                "cmd {:?} signed by {issuer:?} was sent by {sender:?}",
+                signed_cmd.msg_id
+            )));
+        }
+
+        let max_age_secs = MAX_CMD_AGE.as_secs();
+        if unix_time_secs().abs_diff(signed_cmd.issued_at) > max_age_secs {
+            return Err(ProtocolError::StaleCmd {
+                issued_at: signed_cmd.issued_at,
+                max_age_secs,
+            });
+        }
+
+        self.seen
+            .lock()
+            .await
+            .insert(issuer, signed_cmd.msg_id, Instant::now())
+    }
+}
+
+impl Seen {
+    // Records the id as seen at `now`, unless it was seen already or its issuer is over its quota.
+    fn insert(&mut self, issuer: PeerId, msg_id: MsgId, now: Instant) -> ProtocolResult<()> {
+        self.expire(now);
+        if self.ids.contains(&(issuer, msg_id)) {
+            return Err(ProtocolError::ReplayedCmd(msg_id));
+        }
+        if self.per_issuer.get(&issuer).copied().unwrap_or_default() >= MAX_SEEN_PER_ISSUER {
+            let oldest = self
+                .expiry
+                .iter()
+                .find(|(_, seen_issuer, _)| *seen_issuer == issuer)
+                .map_or(now, |(seen_at, _, _)| *seen_at);
+            let expires_in = SEEN_FOR.saturating_sub(now.saturating_duration_since(oldest));
+            return Err(ProtocolError::RateLimited {
+                retry_after_secs: expires_in.as_secs() + 1,
+            });
+        }
+        while self.ids.len() >= MAX_SEEN {
+            if !self.forget_oldest() {
+                break;
+            }
+        }
+
+        let _ = self.ids.insert((issuer, msg_id));
+        *self.per_issuer.entry(issuer).or_default() += 1;
+        self.expiry.push_back((now, issuer, msg_id));
+        Ok(())
+    }
+
+    // Forgets the ids seen long enough ago that a replay of their cmd would be stale.
+    fn expire(&mut self, now: Instant) {
+        while let Some((seen_at, _, _)) = self.expiry.front() {
+            if now.saturating_duration_since(*seen_at) <= SEEN_FOR {
+                break;
+            }
+            let _ = self.forget_oldest();
+        }
+    }
+
+    // Forgets the oldest id seen, returning whether there was one.
+    fn forget_oldest(&mut self) -> bool {
+        let (_, issuer, msg_id) = match self.expiry.pop_front() {
+            Some(oldest) => oldest,
+            None => return false,
+        };
+        let _ = self.ids.remove(&(issuer, msg_id));
+        if let Some(count) = self.per_issuer.get_mut(&issuer) {
+            *count -= 1;
+            if *count == 0 {
+                let _ = self.per_issuer.remove(&issuer);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xor_name::XorName;
+
+    fn msg_id(index: usize) -> MsgId {
+        MsgId(XorName::from_content(&index.to_be_bytes()))
+    }
+
+    #[test]
+    fn replays_are_rejected_until_they_expire() {
+        let mut seen = Seen::default();
+        let issuer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(seen.insert(issuer, msg_id(0), now).is_ok());
+        assert!(matches!(
+            seen.insert(issuer, msg_id(0), now),
+            Err(ProtocolError::ReplayedCmd(_))
+        ));
+        // The same id from another issuer is another cmd.
+        assert!(seen.insert(PeerId::random(), msg_id(0), now).is_ok());
+
+        let later = now + SEEN_FOR + Duration::from_secs(1);
+        assert!(seen.insert(issuer, msg_id(0), later).is_ok());
+        assert_eq!(seen.ids.len(), 1);
+        assert_eq!(seen.per_issuer.len(), 1);
+    }
+
+    #[test]
+    fn issuers_over_their_quota_are_rate_limited() {
+        let mut seen = Seen::default();
+        let issuer = PeerId::random();
+        let now = Instant::now();
+        for index in 0..MAX_SEEN_PER_ISSUER {
+            assert!(seen.insert(issuer, msg_id(index), now).is_ok());
+        }
+
+        let result = seen.insert(issuer, msg_id(MAX_SEEN_PER_ISSUER), now);
+        assert!(
+            matches!(result, Err(ProtocolError::RateLimited { retry_after_secs }) if retry_after_secs > SEEN_FOR.as_secs()),
+            "unexpected result: {result:?}"
+        );
+        // Other issuers are not held back by it.
+        assert!(seen.insert(PeerId::random(), msg_id(0), now).is_ok());
+    }
+
+    #[test]
+    fn the_oldest_ids_are_forgotten_past_the_max() {
+        let mut seen = Seen::default();
+        let now = Instant::now();
+        let issuers: Vec<_> = (0..MAX_SEEN / MAX_SEEN_PER_ISSUER + 1)
+            .map(|_| PeerId::random())
+            .collect();
+        let first = (issuers[0], msg_id(0));
+        for (index, issuer) in issuers.iter().enumerate() {
+            let count = if index + 1 == issuers.len() {
+                1
+            } else {
+                MAX_SEEN_PER_ISSUER
+            };
+            for id in 0..count {
+                assert!(seen.insert(*issuer, msg_id(id), now).is_ok());
+            }
+        }
+
+        assert_eq!(seen.ids.len(), MAX_SEEN);
+        assert_eq!(seen.expiry.len(), MAX_SEEN);
+        assert!(!seen.ids.contains(&first));
+        assert_eq!(
+            seen.per_issuer.get(&issuers[0]),
+            Some(&(MAX_SEEN_PER_ISSUER - 1))
+        );
+    }
+}
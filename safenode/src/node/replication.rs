@@ -0,0 +1,183 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{handoff::ReplicaSource, Node};
+use crate::{
+    network::{close_group, CmdPriority},
+    protocol::{
+        error::{Error as ProtocolError, Result as ProtocolResult},
+        messages::{
+            Cmd, CmdResponse, Query, QueryResponse, Request, Response, FETCH_REPLICATION_MAX_KEYS,
+        },
+    },
+};
+use async_std::task::{sleep, spawn};
+use libp2p::PeerId;
+use std::{collections::BTreeSet, time::Duration};
+use tracing::{debug, info, warn};
+use xor_name::XorName;
+
+/// The max number of records a node is informed of in a single `ReplicateRecords` cmd.
+pub const REPLICATE_RECORDS_BATCH: usize = 256;
+// How long to wait after connecting to a peer before checking whether it joined our close
+// group, for it to be added to the routing table once identified.
+const REPLICATE_TO_DELAY: Duration = Duration::from_secs(5);
+
+impl Node {
+    /// Informs the peer, if it is a member of our close group, of the records we hold that it
+    /// is in the close group of, for it to pull the ones it lacks. Nodes joining the close
+    /// group of records thus get them without waiting on Kademlia to replicate them.
+    ///
+    /// The close group of each record is taken among our own close group and ourselves.
+    pub async fn replicate_to(self, peer: PeerId) {
+        sleep(REPLICATE_TO_DELAY).await;
+        let mut network = self.network.at_priority(CmdPriority::Replication);
+        let neighbours = match network.close_group().await {
+            Ok(neighbours) => neighbours,
+            Err(err) => {
+                warn!("Could not get our close group to replicate to {peer:?}: {err}");
+                return;
+            }
+        };
+        if !neighbours.contains(&peer) {
+            return;
+        }
+        // It is not audited until it could pull the records it is to hold.
+        self.close_group_joins.record(peer);
+        let own_id = network.peer_id();
+        let keys: Vec<XorName> = self
+            .storage
+            .local_records()
+            .into_iter()
+            .map(|record| record.key)
+            .filter(|key| {
+                close_group(key, neighbours.iter().copied(), Some(own_id)).contains(&peer)
+            })
+            .collect();
+        info!(
+            "Informing {peer:?} of the {} records it should hold",
+            keys.len()
+        );
+
+        for batch in keys.chunks(REPLICATE_RECORDS_BATCH) {
+            let cmd = Cmd::ReplicateRecords {
+                keys: batch.to_vec(),
+            };
+            let signed_cmd = match network.sign_cmd(cmd) {
+                Ok(signed_cmd) => signed_cmd,
+                Err(err) => {
+                    warn!("Could not sign the records to replicate to {peer:?}: {err}");
+                    return;
+                }
+            };
+            match network.send_request(Request::Cmd(signed_cmd), peer).await {
+                Ok(signed) => match signed.response {
+                    Response::Cmd(CmdResponse::Ack { .. }) => {}
+                    other => debug!("{peer:?} did not take the records to replicate: {other:?}"),
+                },
+                Err(err) => {
+                    debug!("Could not inform {peer:?} of the records to replicate: {err}");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Takes the keys of the records the peer informed us we should hold, pulling the ones we
+    /// lack and are responsible for from it in the background.
+    pub(super) async fn pull_records(
+        &self,
+        peer: PeerId,
+        keys: Vec<XorName>,
+    ) -> ProtocolResult<()> {
+        let is_node = self
+            .network
+            .clone()
+            .is_in_routing_table(peer)
+            .await
+            .unwrap_or(false);
+        if !is_node {
+            return Err(ProtocolError::InvalidReplica(
+                keys.first().copied().unwrap_or_default(),
+                format!("{peer:?} is not in our routing table"),
+            ));
+        }
+
+        let held: BTreeSet<_> = self
+            .storage
+            .local_records()
+            .into_iter()
+            .map(|record| record.key)
+            .collect();
+        let mut missing = vec![];
+        for key in keys.into_iter().filter(|key| !held.contains(key)) {
+            if self.check_responsible(&key).await.is_ok() {
+                missing.push(key);
+            }
+        }
+        if !missing.is_empty() {
+            let node = self.clone();
+            let _handle = spawn(async move { node.fetch_replication(peer, missing).await });
+        }
+        Ok(())
+    }
+
+    // Pulls the records from the peer, a batch at a time, storing them as replicas. The keys
+    // of a batch past the last record returned are asked for again, the peer leaving out the
+    // records not fitting in its response.
+    async fn fetch_replication(&self, peer: PeerId, keys: Vec<XorName>) {
+        debug!("Pulling {} records from {peer:?}", keys.len());
+        let mut network = self.network.at_priority(CmdPriority::Replication);
+        let mut stored = 0;
+        let mut pending = keys.as_slice();
+        while !pending.is_empty() {
+            let batch = &pending[..pending.len().min(FETCH_REPLICATION_MAX_KEYS)];
+            let query = Query::FetchReplication {
+                keys: batch.to_vec(),
+            };
+            let signed = match network.send_request(Request::Query(query), peer).await {
+                Ok(signed) => signed,
+                Err(err) => {
+                    warn!("Could not pull records from {peer:?}: {err}");
+                    break;
+                }
+            };
+            let replicas = match signed.response {
+                Response::Query(QueryResponse::FetchReplication(replicas)) => replicas,
+                other => {
+                    warn!("Unexpected response of {peer:?} to the pull of records: {other:?}");
+                    break;
+                }
+            };
+            let mut done = 0;
+            for replica in replicas {
+                // Records not asked for are not taken.
+                let (name, position) = match replica
+                    .name()
+                    .and_then(|name| Some((name, batch.iter().position(|key| key == &name)?)))
+                {
+                    Some(found) => found,
+                    None => continue,
+                };
+                done = done.max(position + 1);
+                match self.store_replica(peer, replica, ReplicaSource::Pull).await {
+                    Ok(()) => stored += 1,
+                    Err(err) => {
+                        warn!("Failed to store the replica of {name:?} pulled from {peer:?}: {err}")
+                    }
+                }
+            }
+            // None of the batch is held by the peer when none was returned.
+            if done == 0 {
+                done = batch.len();
+            }
+            pending = &pending[done..];
+        }
+        info!("Pulled {stored} of {} records from {peer:?}", keys.len());
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::fs::write_secret;
+use libp2p::identity::Keypair;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use tracing::info;
+
+/// The file the keypair of the node is kept in, protobuf encoded.
+pub const KEYPAIR_FILENAME: &str = "secret-key";
+/// The dir the records of the node are stored in.
+pub const RECORD_STORE_DIR_NAME: &str = "record_store";
+/// The dir the wallet of the node is kept in.
+pub const WALLET_DIR_NAME: &str = "wallet";
+/// The dir the log files are written to, unless configured otherwise.
+pub const LOG_DIR_NAME: &str = "logs";
+
+// The dirs of the records, which used to be kept right in the root dir.
+const LEGACY_RECORD_DIRS: [&str; 3] = ["chunks", "registers", "spends"];
+// The file written to check that the root dir is writable.
+const WRITE_CHECK_FILENAME: &str = ".write_check";
+
+/// Errors setting up the root dir of a node.
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum RootDirError {
+    #[error("The root dir {0:?} is not a dir")]
+    NotADir(PathBuf),
+    #[error("The root dir {path:?} is not writable: {err}")]
+    NotWritable { path: PathBuf, err: io::Error },
+    #[error("I/O error on {path:?}: {err}")]
+    Io { path: PathBuf, err: io::Error },
+    #[error("Invalid keypair in {path:?}: {details}")]
+    InvalidKeypair { path: PathBuf, details: String },
+}
+
+/// The dir a node keeps all of its state in, laid out as:
+///
+/// ```text
+/// <root dir>/
+/// ├── secret-key        the keypair of the node, for it to keep its peer id across restarts
+/// ├── peer_id           the peer id of the node, for local tooling to dial it
+/// ├── safenode.pid      the pid of the node process
+/// ├── node_stats.json   the counts of the puts handled, see `StatsCounts`
+/// ├── record_store/     the chunks, registers and spends stored
+/// ├── wallet/           the wallet of the node
+/// └── logs/             the log files, unless a log dir is configured
+/// ```
+#[derive(Clone, Debug)]
+pub struct NodeRootDir {
+    path: PathBuf,
+}
+
+impl NodeRootDir {
+    /// The layout under the given dir, which is only touched by [`NodeRootDir::init`].
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The root dir itself.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path of the keypair of the node.
+    pub fn keypair_path(&self) -> PathBuf {
+        self.path.join(KEYPAIR_FILENAME)
+    }
+
+    /// The dir the records are stored in.
+    pub fn record_store_dir(&self) -> PathBuf {
+        self.path.join(RECORD_STORE_DIR_NAME)
+    }
+
+    /// The dir the wallet of the node is kept in.
+    pub fn wallet_dir(&self) -> PathBuf {
+        self.path.join(WALLET_DIR_NAME)
+    }
+
+    /// The dir the log files are written to, unless configured otherwise.
+    pub fn log_dir(&self) -> PathBuf {
+        self.path.join(LOG_DIR_NAME)
+    }
+
+    /// Creates the dirs of the layout, checks that the root dir is writable, and moves
+    /// the records stored by earlier versions right in the root dir to the record store.
+    pub fn init(&self) -> Result<(), RootDirError> {
+        if self.path.exists() && !self.path.is_dir() {
+            return Err(RootDirError::NotADir(self.path.clone()));
+        }
+        for dir in [
+            self.path.clone(),
+            self.record_store_dir(),
+            self.wallet_dir(),
+        ] {
+            fs::create_dir_all(&dir).map_err(|err| RootDirError::Io { path: dir, err })?;
+        }
+        self.check_writable()?;
+        self.migrate_records()
+    }
+
+    /// Loads the keypair of the node, creating one on first start.
+    pub fn load_or_create_keypair(&self) -> Result<Keypair, RootDirError> {
+        let path = self.keypair_path();
+        match fs::read(&path) {
+            Ok(bytes) => Keypair::from_protobuf_encoding(&bytes).map_err(|err| {
+                RootDirError::InvalidKeypair {
+                    path,
+                    details: err.to_string(),
+                }
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let keypair = Keypair::generate_ed25519();
+                let bytes =
+                    keypair
+                        .to_protobuf_encoding()
+                        .map_err(|err| RootDirError::InvalidKeypair {
+                            path: path.clone(),
+                            details: err.to_string(),
+                        })?;
+                write_secret(&path, &bytes).map_err(|err| RootDirError::Io {
+                    path: path.clone(),
+                    err,
+                })?;
+                info!("Created a new keypair at {path:?}");
+                Ok(keypair)
+            }
+            Err(err) => Err(RootDirError::Io { path, err }),
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), RootDirError> {
+        let path = self.path.join(WRITE_CHECK_FILENAME);
+        fs::write(&path, b"")
+            .and_then(|()| fs::remove_file(&path))
+            .map_err(|err| RootDirError::NotWritable {
+                path: self.path.clone(),
+                err,
+            })
+    }
+
+    // Moves the dirs of the records from the root dir to the record store, where they are
+    // not already.
+    fn migrate_records(&self) -> Result<(), RootDirError> {
+        for name in LEGACY_RECORD_DIRS {
+            let legacy = self.path.join(name);
+            let current = self.record_store_dir().join(name);
+            if !legacy.is_dir() || current.exists() {
+                continue;
+            }
+            fs::rename(&legacy, &current).map_err(|err| RootDirError::Io {
+                path: legacy.clone(),
+                err,
+            })?;
+            info!("Moved the records in {legacy:?} to {current:?}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Node, NodeCtrl};
+use crate::log::LogReopener;
+use futures::{channel::mpsc, SinkExt};
+use std::{
+    net::SocketAddr,
+    process, thread,
+    time::{Duration, Instant},
+};
+use tonic::{transport::Server, Code, Request, Response, Status};
+use tracing::{error, info, trace};
+
+use safenode_proto::{
+    safe_node_server::{SafeNode, SafeNodeServer},
+    NetworkInfoRequest, NetworkInfoResponse, NodeInfoRequest, NodeInfoResponse, PeerReputation,
+    RecordStatsRequest, RecordStatsResponse, ReopenLogsRequest, ReopenLogsResponse, RestartRequest,
+    RestartResponse, StopRequest, StopResponse,
+};
+
+// Defined in protos/safenode.proto
+#[allow(
+    missing_docs,
+    unreachable_pub,
+    unused_qualifications,
+    clippy::unwrap_used
+)]
+mod safenode_proto {
+    tonic::include_proto!("safenode_proto");
+}
+
+// The RPC service handing the requests of operator tooling over to the node.
+struct SafeNodeRpcService {
+    node: Node,
+    log_dir: String,
+    log_reopener: LogReopener,
+    ctrl_tx: mpsc::Sender<NodeCtrl>,
+    started_instant: Instant,
+}
+
+impl SafeNodeRpcService {
+    async fn send_ctrl(&self, ctrl: NodeCtrl) -> Result<(), Status> {
+        self.ctrl_tx.clone().send(ctrl).await.map_err(|err| {
+            Status::new(
+                Code::Internal,
+                format!("Failed to send {ctrl:?} to the node: {err}"),
+            )
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl SafeNode for SafeNodeRpcService {
+    async fn node_info(
+        &self,
+        request: Request<NodeInfoRequest>,
+    ) -> Result<Response<NodeInfoResponse>, Status> {
+        trace!("RPC request received: {:?}", request.get_ref());
+        Ok(Response::new(NodeInfoResponse {
+            peer_id: self.node.network.peer_id().to_bytes(),
+            log_dir: self.log_dir.clone(),
+            pid: process::id(),
+            bin_version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_secs: self.started_instant.elapsed().as_secs(),
+        }))
+    }
+
+    async fn network_info(
+        &self,
+        request: Request<NetworkInfoRequest>,
+    ) -> Result<Response<NetworkInfoResponse>, Status> {
+        trace!("RPC request received: {:?}", request.get_ref());
+        let mut network = self.node.network.clone();
+        let to_status = |err| Status::new(Code::Internal, format!("Network error: {err}"));
+        let connected_peers = network.connected_peers().await.map_err(to_status)?;
+        let listeners = network.listen_addrs().await.map_err(to_status)?;
+        let reputations = network.peer_reputations().await.map_err(to_status)?;
+        let queues = network.queue_stats();
+        Ok(Response::new(NetworkInfoResponse {
+            connected_peers: connected_peers.iter().map(|peer| peer.to_bytes()).collect(),
+            listeners: listeners.iter().map(|addr| addr.to_string()).collect(),
+            peer_reputations: reputations
+                .into_iter()
+                .map(|reputation| PeerReputation {
+                    peer_id: reputation.peer_id.to_bytes(),
+                    penalty: reputation.penalty,
+                    blocked: reputation.blocked,
+                })
+                .collect(),
+            cmd_queue_depth: queues.cmd_depth as u64,
+            event_queue_depth: queues.event_depth as u64,
+            cmds_shed: queues.cmds_shed,
+            events_shed: queues.events_shed,
+        }))
+    }
+
+    async fn record_stats(
+        &self,
+        request: Request<RecordStatsRequest>,
+    ) -> Result<Response<RecordStatsResponse>, Status> {
+        trace!("RPC request received: {:?}", request.get_ref());
+        let level = self.node.storage.storage_level();
+        let scrub_counts = self.node.scrub_counts();
+        let cache_stats = self.node.storage.cache_stats().await;
+        let stats_counts = self.node.stats_counts();
+        Ok(Response::new(RecordStatsResponse {
+            chunk_count: self.node.storage.chunk_count() as u64,
+            used_space: level.used,
+            max_capacity: level.total,
+            corrupt_records: scrub_counts.corrupt,
+            repaired_records: scrub_counts.repaired,
+            cache_hits: cache_stats.hits,
+            cache_misses: cache_stats.misses,
+            puts_accepted: stats_counts.puts_accepted,
+            puts_rejected: stats_counts.puts_rejected,
+            rewards_nanos: stats_counts.rewards_nanos,
+        }))
+    }
+
+    async fn restart(
+        &self,
+        request: Request<RestartRequest>,
+    ) -> Result<Response<RestartResponse>, Status> {
+        trace!("RPC request received: {:?}", request.get_ref());
+        let delay = Duration::from_millis(request.get_ref().delay_millis);
+        self.send_ctrl(NodeCtrl::Restart { delay }).await?;
+        Ok(Response::new(RestartResponse {}))
+    }
+
+    async fn stop(&self, request: Request<StopRequest>) -> Result<Response<StopResponse>, Status> {
+        trace!("RPC request received: {:?}", request.get_ref());
+        let delay = Duration::from_millis(request.get_ref().delay_millis);
+        self.send_ctrl(NodeCtrl::Stop { delay }).await?;
+        Ok(Response::new(StopResponse {}))
+    }
+
+    async fn reopen_logs(
+        &self,
+        request: Request<ReopenLogsRequest>,
+    ) -> Result<Response<ReopenLogsResponse>, Status> {
+        trace!("RPC request received: {:?}", request.get_ref());
+        self.log_reopener.reopen();
+        Ok(Response::new(ReopenLogsResponse {}))
+    }
+}
+
+/// Starts the RPC service of the node on the given address, in a thread of its own.
+///
+/// The `NodeCtrl`s requested through the service are sent to `ctrl_tx`, for the
+/// process running the node to act on.
+pub fn start_rpc_service(
+    addr: SocketAddr,
+    log_dir: String,
+    log_reopener: LogReopener,
+    node: Node,
+    ctrl_tx: mpsc::Sender<NodeCtrl>,
+) {
+    let service = SafeNodeRpcService {
+        node,
+        log_dir,
+        log_reopener,
+        ctrl_tx,
+        started_instant: Instant::now(),
+    };
+
+    // tonic is built on tokio, while the node runs on async-std, so the service
+    // is given a runtime of its own.
+    let _handle = thread::spawn(move || {
+        let runtime = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime,
+            Err(err) => {
+                error!("Failed to start the runtime of the RPC service: {err}");
+                return;
+            }
+        };
+        runtime.block_on(async move {
+            info!("RPC service listening on {addr}");
+            if let Err(err) = Server::builder()
+                .add_service(SafeNodeServer::new(service))
+                .serve(addr)
+                .await
+            {
+                error!("RPC service failed: {err}");
+            }
+        });
+    });
+}
@@ -0,0 +1,125 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+use crate::{
+    network::CmdPriority,
+    storage::{chunks::ChunkAddress, LocalRecord, RecordKind},
+};
+use async_std::task::sleep;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tracing::{debug, info, warn};
+
+/// How long to pause between the checks of two records, for the scrubbing to stay in the
+/// background of the handling of requests.
+pub const SCRUB_PAUSE: Duration = Duration::from_millis(500);
+/// How long to pause between two rounds of checks of all the records.
+pub const SCRUB_ROUND_PAUSE: Duration = Duration::from_secs(60 * 60);
+
+/// The counts of the records checked by the scrubbing, and of the corrupt ones found.
+#[derive(Debug, Default)]
+pub(super) struct ScrubStats {
+    checked: AtomicU64,
+    corrupt: AtomicU64,
+    repaired: AtomicU64,
+}
+
+/// A snapshot of the counts of the scrubbing since the node started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScrubCounts {
+    /// The number of records checked.
+    pub checked: u64,
+    /// The number of records found corrupt, and removed.
+    pub corrupt: u64,
+    /// The number of corrupt records fetched back from peers.
+    pub repaired: u64,
+}
+
+impl ScrubStats {
+    pub(super) fn counts(&self) -> ScrubCounts {
+        ScrubCounts {
+            checked: self.checked.load(Ordering::Relaxed),
+            corrupt: self.corrupt.load(Ordering::Relaxed),
+            repaired: self.repaired.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Node {
+    /// Checks the stored records against their keys, one at a time and forever, to find the
+    /// ones corrupted on disk. Corrupt records are removed, and chunks fetched back from
+    /// the peers holding them, being verifiable against their name.
+    pub async fn scrub_records(self) {
+        loop {
+            let records = self.storage.local_records();
+            info!("Scrubbing {} records", records.len());
+            for record in records {
+                sleep(SCRUB_PAUSE).await;
+                self.scrub(&record).await;
+            }
+            let counts = self.scrub_stats.counts();
+            info!("Scrubbing round done, corrupt records found so far: {counts:?}");
+            sleep(SCRUB_ROUND_PAUSE).await;
+        }
+    }
+
+    /// The counts of the scrubbing since the node started.
+    pub fn scrub_counts(&self) -> ScrubCounts {
+        self.scrub_stats.counts()
+    }
+
+    async fn scrub(&self, record: &LocalRecord) {
+        let _ = self.scrub_stats.checked.fetch_add(1, Ordering::Relaxed);
+        match self.storage.is_intact(record).await {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("Record {:?} is corrupt, removing it", record.key);
+                let _ = self.scrub_stats.corrupt.fetch_add(1, Ordering::Relaxed);
+                if let Err(err) = self.storage.remove(record).await {
+                    warn!("Failed to remove corrupt record {:?}: {err}", record.key);
+                    return;
+                }
+                if record.kind == RecordKind::Chunk {
+                    self.query_cache
+                        .invalidate_chunk(&ChunkAddress(record.key))
+                        .await;
+                }
+                if record.kind == RecordKind::Chunk && self.refetch_chunk(record).await {
+                    let _ = self.scrub_stats.repaired.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            // E.g. the record was removed since it was listed.
+            Err(err) => debug!("Could not check record {:?}: {err}", record.key),
+        }
+    }
+
+    // Fetches the chunk back from its close group, returning whether it is stored again.
+    async fn refetch_chunk(&self, record: &LocalRecord) -> bool {
+        let addr = ChunkAddress(record.key);
+        // Repairs give way to the queries of clients.
+        match self.fetch_chunk(addr, CmdPriority::Replication).await {
+            Some(chunk) => match self.store_chunk(&chunk).await {
+                Ok(()) => {
+                    info!("Corrupt chunk {addr:?} fetched back from peers");
+                    true
+                }
+                Err(err) => {
+                    warn!("Failed to store chunk {addr:?} fetched back: {err}");
+                    false
+                }
+            },
+            None => {
+                warn!("No peer returned a valid copy of corrupt chunk {addr:?}");
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Node;
+use crate::protocol::{error::Result as ProtocolResult, types::payment::PaymentProof};
+use async_std::{fs, task::sleep};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tracing::{debug, info, warn};
+
+/// The name of the file the counters are kept in, in the root dir of the node.
+pub const STATS_FILENAME: &str = "node_stats.json";
+/// How often the counters are written to their file.
+pub const STATS_PERSIST_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The counts of the puts handled by the node, and of the payments for those stored.
+#[derive(Debug, Default)]
+pub(super) struct NodeStats {
+    puts_accepted: AtomicU64,
+    puts_rejected: AtomicU64,
+    rewards_nanos: AtomicU64,
+}
+
+/// A snapshot of the counts of the puts handled by the node, since its root dir was created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsCounts {
+    /// The number of chunks and registers stored.
+    pub puts_accepted: u64,
+    /// The number of chunks and registers refused, for their payment, content or our space.
+    pub puts_rejected: u64,
+    /// The amount paid for the storage of the records stored, in nanos.
+    pub rewards_nanos: u64,
+}
+
+impl NodeStats {
+    pub(super) fn counts(&self) -> StatsCounts {
+        StatsCounts {
+            puts_accepted: self.puts_accepted.load(Ordering::Relaxed),
+            puts_rejected: self.puts_rejected.load(Ordering::Relaxed),
+            rewards_nanos: self.rewards_nanos.load(Ordering::Relaxed),
+        }
+    }
+
+    // Adds the counts to the current ones, for those persisted by a previous run.
+    fn add(&self, counts: StatsCounts) {
+        let _ = self
+            .puts_accepted
+            .fetch_add(counts.puts_accepted, Ordering::Relaxed);
+        let _ = self
+            .puts_rejected
+            .fetch_add(counts.puts_rejected, Ordering::Relaxed);
+        let _ = self
+            .rewards_nanos
+            .fetch_add(counts.rewards_nanos, Ordering::Relaxed);
+    }
+
+    // Counts a put accepted, paid `rewards_nanos` for.
+    pub(super) fn record_accepted(&self, rewards_nanos: u64) {
+        let _ = self.puts_accepted.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .rewards_nanos
+            .fetch_add(rewards_nanos, Ordering::Relaxed);
+    }
+
+    // Counts a put rejected.
+    pub(super) fn record_rejected(&self) {
+        let _ = self.puts_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Node {
+    /// Loads the counts persisted in the root dir by a previous run, if any, to carry on
+    /// from them.
+    pub async fn load_stats(&self, root_dir: &Path) -> io::Result<()> {
+        let path = root_dir.join(STATS_FILENAME);
+        let bytes = match fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let counts: StatsCounts = serde_json::from_slice(&bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        info!("Loaded the node stats from {path:?}: {counts:?}");
+        self.stats.add(counts);
+        Ok(())
+    }
+
+    /// Writes the counts to the root dir every `STATS_PERSIST_INTERVAL`, forever, for them
+    /// to survive restarts.
+    pub async fn persist_stats(self, root_dir: PathBuf) {
+        let path = root_dir.join(STATS_FILENAME);
+        let mut persisted = None;
+        loop {
+            sleep(STATS_PERSIST_INTERVAL).await;
+            let counts = self.stats.counts();
+            if persisted == Some(counts) {
+                continue;
+            }
+            match write_counts(&path, counts).await {
+                Ok(()) => {
+                    debug!("Persisted the node stats: {counts:?}");
+                    persisted = Some(counts);
+                }
+                Err(err) => warn!("Failed to persist the node stats to {path:?}: {err}"),
+            }
+        }
+    }
+
+    // Counts the put by its result, with the payment for it if it was stored.
+    pub(super) fn record_put<T>(&self, result: &ProtocolResult<T>, payment: &PaymentProof) {
+        match result {
+            Ok(_) => {
+                // Only what is paid to the node counts as its earnings, when it is paid at
+                // an address of its own.
+                let earned = match &self.store_cost {
+                    Some(cost) => payment.paid_to(&cost.address),
+                    None => payment.paid_amount(),
+                };
+                self.stats.record_accepted(earned)
+            }
+            Err(_) => self.stats.record_rejected(),
+        }
+    }
+
+    /// The counts of the puts handled by the node, including those of previous runs.
+    pub fn stats_counts(&self) -> StatsCounts {
+        self.stats.counts()
+    }
+}
+
+// Writes to a temporary file first, for a crash not to leave the file half written.
+async fn write_counts(path: &Path, counts: StatsCounts) -> io::Result<()> {
+    let bytes = serde_json::to_vec_pretty(&counts).map_err(io::Error::other)?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, bytes).await?;
+    fs::rename(&tmp_path, path).await
+}
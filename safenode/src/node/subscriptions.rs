@@ -0,0 +1,99 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    network::{CmdPriority, Network},
+    protocol::messages::{Event, Request, Topic},
+};
+use async_std::task::spawn;
+use futures::{channel::mpsc, StreamExt};
+use libp2p::PeerId;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::RwLock;
+use tracing::{debug, trace, warn};
+
+/// The max number of events queued for a subscriber. Further events are dropped
+/// until the queue has drained.
+const EVENT_QUEUE_SIZE: usize = 100;
+
+/// The peers subscribed to the events of the node, and the queues of events being pushed to them.
+#[derive(Clone, Default)]
+pub(super) struct Subscriptions {
+    subscribers: Arc<RwLock<HashMap<PeerId, Subscriber>>>,
+}
+
+struct Subscriber {
+    topics: HashSet<Topic>,
+    queue: mpsc::Sender<Event>,
+}
+
+impl Subscriptions {
+    /// Subscribes the peer to the topic, starting to push events to it if it is a new subscriber.
+    pub(super) async fn subscribe(&self, peer: PeerId, topic: Topic, network: &Network) {
+        debug!("Subscribing {peer:?} to {topic:?}");
+        let mut subscribers = self.subscribers.write().await;
+        let subscriber = subscribers.entry(peer).or_insert_with(|| Subscriber {
+            topics: HashSet::new(),
+            // Events are pushed in answer to the subscription, as its responses are.
+            queue: push_events(network.at_priority(CmdPriority::Response), peer),
+        });
+        let _ = subscriber.topics.insert(topic);
+    }
+
+    /// Unsubscribes the peer from the topic, no longer pushing events to it once it has no topics left.
+    pub(super) async fn unsubscribe(&self, peer: PeerId, topic: Topic) {
+        debug!("Unsubscribing {peer:?} from {topic:?}");
+        let mut subscribers = self.subscribers.write().await;
+        if let Some(subscriber) = subscribers.get_mut(&peer) {
+            let _ = subscriber.topics.remove(&topic);
+            if subscriber.topics.is_empty() {
+                // Dropping the queue ends the task pushing its events.
+                let _ = subscribers.remove(&peer);
+            }
+        }
+    }
+
+    /// Queues the event to be pushed to the peers subscribed to its topic.
+    pub(super) async fn publish(&self, event: Event) {
+        let topic = event.topic();
+        let mut subscribers = self.subscribers.write().await;
+        let mut disconnected = vec![];
+        for (peer, subscriber) in subscribers
+            .iter_mut()
+            .filter(|(_, subscriber)| subscriber.topics.contains(&topic))
+        {
+            trace!("Queueing event on {topic:?} for {peer:?}");
+            if let Err(err) = subscriber.queue.try_send(event.clone()) {
+                if err.is_disconnected() {
+                    disconnected.push(*peer);
+                } else {
+                    warn!("Event queue of {peer:?} is full, dropping event on {topic:?}");
+                }
+            }
+        }
+        for peer in disconnected {
+            let _ = subscribers.remove(&peer);
+        }
+    }
+}
+
+// Spawns a task pushing the events sent to the returned queue to the peer, one at a time.
+fn push_events(mut network: Network, peer: PeerId) -> mpsc::Sender<Event> {
+    let (sender, mut receiver) = mpsc::channel(EVENT_QUEUE_SIZE);
+    let _handle = spawn(async move {
+        while let Some(event) = receiver.next().await {
+            if let Err(err) = network.send_request(Request::Event(event), peer).await {
+                warn!("Failed to push event to {peer:?}: {err}");
+            }
+        }
+    });
+    sender
+}
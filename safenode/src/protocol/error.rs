@@ -0,0 +1,208 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    messages::MsgId,
+    types::{register::RegisterAddress, spend::DbcAddress},
+};
+use crate::storage::{chunks::ChunkAddress, StorageLevel};
+use serde::{Deserialize, Serialize};
+use sn_dbc::SignedSpend;
+use thiserror::Error;
+use xor_name::XorName;
+
+/// A specialised `Result` type for protocol crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The category of an [`Error`], telling the requester whether and how the request can be retried.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum ErrorKind {
+    /// The requested data does not exist.
+    NotFound,
+    /// The request is for data outside of the range the node is responsible for.
+    OutOfRange,
+    /// The node does not have the capacity to fulfil the request.
+    QuotaExceeded,
+    /// The request needs a valid payment.
+    PaymentRequired,
+    /// The node is receiving too many requests, the request can be retried later.
+    RateLimited,
+    /// The node is not connected enough to the network yet, the request can be retried later.
+    NotReady,
+    /// The request itself is invalid, and retrying it will not help.
+    InvalidRequest,
+    /// The node failed to fulfil the request due to an internal error.
+    Internal,
+}
+
+/// Main error types for the SAFE protocol.
+#[derive(Error, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Chunk not found.
+    #[error("Chunk not found: {0:?}")]
+    ChunkNotFound(ChunkAddress),
+    /// We failed to store chunk
+    #[error("Chunk was not stored w/ xorname {0:?}")]
+    ChunkNotStored(ChunkAddress),
+    /// Register not found.
+    #[error("Register not found: {0:?}")]
+    RegisterNotFound(RegisterAddress),
+    /// We failed to store the register, or the op on it.
+    #[error("Register was not stored: {0:?}")]
+    RegisterNotStored(RegisterAddress),
+    /// The op or replica was meant for a different register.
+    #[error("Register op for {op:?} cannot be applied to register at {dst:?}")]
+    RegisterAddressMismatch {
+        /// Address of the register being modified.
+        dst: RegisterAddress,
+        /// Address the op was meant for.
+        op: RegisterAddress,
+    },
+    /// The register op is invalid, e.g. it refers to entries the register does not have.
+    #[error("Invalid register op: {0}")]
+    InvalidRegisterOp(String),
+    /// The writer of the op is not allowed to write by the policy of the register.
+    #[error("Writer is not allowed to write to register {0:?}")]
+    RegisterWriteNotPermitted(RegisterAddress),
+    /// A register already exists at the address, created with another policy.
+    #[error("Register already exists with another policy: {0:?}")]
+    RegisterExists(RegisterAddress),
+    /// The node is not responsible for the data at the given location.
+    #[error("Data at {0:?} is out of the range of the node")]
+    OutOfRange(XorName),
+    /// The replica handed off is not taken, e.g. for not coming from a node.
+    #[error("Replica of {0:?} not taken: {1}")]
+    InvalidReplica(XorName, String),
+    /// The node has run out of space to store more data.
+    #[error("Storage quota exceeded, {} of {} bytes in use", .0.used, .0.total)]
+    QuotaExceeded(StorageLevel),
+    /// The node is receiving too many requests.
+    #[error("Rate limited, retry after {retry_after_secs}s")]
+    RateLimited {
+        /// Seconds to wait before retrying the request.
+        retry_after_secs: u64,
+    },
+    /// The node is not connected enough to the network to take data yet.
+    #[error("Node not ready to take data: {0}")]
+    NotReady(String),
+    /// The storage of the data has not been paid for.
+    #[error("Payment is required for the storage of {0:?}")]
+    PaymentRequired(XorName),
+    /// The payment proof for the storage of the data is invalid.
+    /// The put can be retried with a valid payment proof.
+    #[error("Payment proof for the storage of {0:?} is invalid: {1}")]
+    InvalidPaymentProof(XorName, String),
+    /// The royalties of the payment are not paid as the network requires.
+    #[error("Royalties of the payment for the storage of {0:?} are invalid: {1}")]
+    InvalidRoyaltyPayment(XorName, String),
+    /// The royalties paid fall short of the share of the payment due to the network.
+    #[error("Royalties of {paid} nanos paid for the storage of {name:?}, {due} nanos are due")]
+    RoyaltyUnderpaid {
+        /// The name of the data paid for.
+        name: XorName,
+        /// The royalties paid, in nanos.
+        paid: u64,
+        /// The royalties due, in nanos.
+        due: u64,
+    },
+    /// The payment to the node falls short of the cost it asks for the storage.
+    #[error("{paid} nanos paid to the node for the storage of {name:?}, it costs {due} nanos")]
+    StoreCostUnpaid {
+        /// The name of the data paid for.
+        name: XorName,
+        /// The amount paid to the node, in nanos.
+        paid: u64,
+        /// The cost of the storage, in nanos.
+        due: u64,
+    },
+    /// Spend not found.
+    #[error("Spend not found: {0:?}")]
+    SpendNotFound(DbcAddress),
+    /// No record of any kind is held at the name.
+    #[error("Record not found: {0:?}")]
+    RecordNotFound(XorName),
+    /// We failed to store the spend.
+    #[error("Spend was not stored: {0:?}")]
+    SpendNotStored(DbcAddress),
+    /// The signature of the spend is invalid.
+    #[error("Invalid spend signature for {0:?}: {1}")]
+    InvalidSpendSignature(DbcAddress, String),
+    /// The spend is not one of the Dbc, or its transaction does not balance.
+    #[error("Invalid spend at {0:?}: {1}")]
+    InvalidSpend(DbcAddress, String),
+    /// A different spend of the same Dbc has been seen, both spends are kept as proof.
+    #[error("A double spend attempt was detected at {:?}", new.dbc_id())]
+    DoubleSpendAttempt {
+        /// The spend we were asked to store.
+        new: Box<SignedSpend>,
+        /// The conflicting spend already known to the network.
+        existing: Box<SignedSpend>,
+    },
+    /// The node could not sign its response.
+    #[error("Failed to sign response: {0}")]
+    ResponseSigningFailed(String),
+    /// The signature of a response could not be verified.
+    #[error("Invalid response signature: {0}")]
+    InvalidResponseSignature(String),
+    /// The issuer could not sign its cmd.
+    #[error("Failed to sign cmd: {0}")]
+    CmdSigningFailed(String),
+    /// The signature of a cmd could not be verified, or was not made by the sender.
+    #[error("Invalid cmd signature: {0}")]
+    InvalidCmdSignature(String),
+    /// A cmd with the same id has already been handled, the cmd is a replay.
+    #[error("Cmd {0:?} has already been handled")]
+    ReplayedCmd(MsgId),
+    /// The cmd was issued too long ago, or too far in the future, to be accepted.
+    #[error("Cmd issued at {issued_at} is not within {max_age_secs}s of the node's time")]
+    StaleCmd {
+        /// The time the cmd was issued, in seconds since the unix epoch.
+        issued_at: u64,
+        /// The maximum age in seconds of an accepted cmd.
+        max_age_secs: u64,
+    },
+}
+
+impl Error {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ChunkNotFound(_)
+            | Self::RegisterNotFound(_)
+            | Self::SpendNotFound(_)
+            | Self::RecordNotFound(_) => ErrorKind::NotFound,
+            Self::OutOfRange(_) => ErrorKind::OutOfRange,
+            Self::QuotaExceeded(_) => ErrorKind::QuotaExceeded,
+            Self::RateLimited { .. } => ErrorKind::RateLimited,
+            Self::NotReady(_) => ErrorKind::NotReady,
+            Self::PaymentRequired(_)
+            | Self::InvalidPaymentProof(..)
+            | Self::InvalidRoyaltyPayment(..)
+            | Self::RoyaltyUnderpaid { .. }
+            | Self::StoreCostUnpaid { .. } => ErrorKind::PaymentRequired,
+            Self::RegisterAddressMismatch { .. }
+            | Self::InvalidRegisterOp(_)
+            | Self::InvalidReplica(..)
+            | Self::RegisterWriteNotPermitted(_)
+            | Self::RegisterExists(_)
+            | Self::InvalidSpendSignature(..)
+            | Self::InvalidSpend(..)
+            | Self::DoubleSpendAttempt { .. }
+            | Self::InvalidResponseSignature(_)
+            | Self::InvalidCmdSignature(_)
+            | Self::ReplayedCmd(_)
+            | Self::StaleCmd { .. } => ErrorKind::InvalidRequest,
+            Self::ChunkNotStored(_)
+            | Self::RegisterNotStored(_)
+            | Self::SpendNotStored(_)
+            | Self::ResponseSigningFailed(_)
+            | Self::CmdSigningFailed(_) => ErrorKind::Internal,
+        }
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+// Stable numeric codes of the protocol errors.
+//
+// The codes let clients not written in Rust, and monitoring, tell errors apart without
+// depending on the layout of the `Error` enum or on its display strings.
+// A code, once assigned, is never reused for a different error. New errors get new codes,
+// and removing or changing the meaning of a code bumps `ERROR_CODES_VERSION`.
+//
+// The thousands of a code give the `ErrorKind` of the error, so that a client not knowing
+// a newer code can still tell whether the request can be retried.
+
+use super::error::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+
+/// The version of the registry of error codes.
+pub const ERROR_CODES_VERSION: u32 = 1;
+
+// ===== NotFound: 1xxx =====
+/// [`Error::ChunkNotFound`]
+pub const CHUNK_NOT_FOUND: u32 = 1001;
+/// [`Error::RegisterNotFound`]
+pub const REGISTER_NOT_FOUND: u32 = 1002;
+/// [`Error::SpendNotFound`]
+pub const SPEND_NOT_FOUND: u32 = 1003;
+/// [`Error::RecordNotFound`]
+pub const RECORD_NOT_FOUND: u32 = 1004;
+
+// ===== OutOfRange: 2xxx =====
+/// [`Error::OutOfRange`]
+pub const OUT_OF_RANGE: u32 = 2001;
+
+// ===== QuotaExceeded: 3xxx =====
+/// [`Error::QuotaExceeded`]
+pub const QUOTA_EXCEEDED: u32 = 3001;
+
+// ===== PaymentRequired: 4xxx =====
+/// [`Error::PaymentRequired`]
+pub const PAYMENT_REQUIRED: u32 = 4001;
+/// [`Error::InvalidPaymentProof`]
+pub const INVALID_PAYMENT_PROOF: u32 = 4002;
+/// [`Error::InvalidRoyaltyPayment`]
+pub const INVALID_ROYALTY_PAYMENT: u32 = 4003;
+/// [`Error::RoyaltyUnderpaid`]
+pub const ROYALTY_UNDERPAID: u32 = 4004;
+/// [`Error::StoreCostUnpaid`]
+pub const STORE_COST_UNPAID: u32 = 4005;
+
+// ===== RateLimited: 5xxx =====
+/// [`Error::RateLimited`]
+pub const RATE_LIMITED: u32 = 5001;
+
+// ===== NotReady: 6xxx =====
+/// [`Error::NotReady`]
+pub const NOT_READY: u32 = 6001;
+
+// ===== InvalidRequest: 7xxx =====
+/// [`Error::RegisterAddressMismatch`]
+pub const REGISTER_ADDRESS_MISMATCH: u32 = 7001;
+/// [`Error::InvalidRegisterOp`]
+pub const INVALID_REGISTER_OP: u32 = 7002;
+/// [`Error::InvalidReplica`]
+pub const INVALID_REPLICA: u32 = 7003;
+/// [`Error::RegisterWriteNotPermitted`]
+pub const REGISTER_WRITE_NOT_PERMITTED: u32 = 7004;
+/// [`Error::InvalidSpendSignature`]
+pub const INVALID_SPEND_SIGNATURE: u32 = 7005;
+/// [`Error::DoubleSpendAttempt`]
+pub const DOUBLE_SPEND_ATTEMPT: u32 = 7006;
+/// [`Error::InvalidResponseSignature`]
+pub const INVALID_RESPONSE_SIGNATURE: u32 = 7007;
+/// [`Error::InvalidCmdSignature`]
+pub const INVALID_CMD_SIGNATURE: u32 = 7008;
+/// [`Error::ReplayedCmd`]
+pub const REPLAYED_CMD: u32 = 7009;
+/// [`Error::StaleCmd`]
+pub const STALE_CMD: u32 = 7010;
+/// [`Error::RegisterExists`]
+pub const REGISTER_EXISTS: u32 = 7011;
+/// [`Error::InvalidSpend`]
+pub const INVALID_SPEND: u32 = 7012;
+
+// ===== Internal: 8xxx =====
+/// [`Error::ChunkNotStored`]
+pub const CHUNK_NOT_STORED: u32 = 8001;
+/// [`Error::RegisterNotStored`]
+pub const REGISTER_NOT_STORED: u32 = 8002;
+/// [`Error::SpendNotStored`]
+pub const SPEND_NOT_STORED: u32 = 8003;
+/// [`Error::ResponseSigningFailed`]
+pub const RESPONSE_SIGNING_FAILED: u32 = 8004;
+/// [`Error::CmdSigningFailed`]
+pub const CMD_SIGNING_FAILED: u32 = 8005;
+
+/// The registry of all the codes, with the name of the `Error` variant they stand for.
+pub const ERROR_CODES: &[(u32, &str)] = &[
+    (CHUNK_NOT_FOUND, "ChunkNotFound"),
+    (REGISTER_NOT_FOUND, "RegisterNotFound"),
+    (SPEND_NOT_FOUND, "SpendNotFound"),
+    (RECORD_NOT_FOUND, "RecordNotFound"),
+    (OUT_OF_RANGE, "OutOfRange"),
+    (QUOTA_EXCEEDED, "QuotaExceeded"),
+    (PAYMENT_REQUIRED, "PaymentRequired"),
+    (INVALID_PAYMENT_PROOF, "InvalidPaymentProof"),
+    (INVALID_ROYALTY_PAYMENT, "InvalidRoyaltyPayment"),
+    (ROYALTY_UNDERPAID, "RoyaltyUnderpaid"),
+    (STORE_COST_UNPAID, "StoreCostUnpaid"),
+    (RATE_LIMITED, "RateLimited"),
+    (NOT_READY, "NotReady"),
+    (REGISTER_ADDRESS_MISMATCH, "RegisterAddressMismatch"),
+    (INVALID_REGISTER_OP, "InvalidRegisterOp"),
+    (INVALID_REPLICA, "InvalidReplica"),
+    (REGISTER_WRITE_NOT_PERMITTED, "RegisterWriteNotPermitted"),
+    (INVALID_SPEND_SIGNATURE, "InvalidSpendSignature"),
+    (DOUBLE_SPEND_ATTEMPT, "DoubleSpendAttempt"),
+    (INVALID_RESPONSE_SIGNATURE, "InvalidResponseSignature"),
+    (INVALID_CMD_SIGNATURE, "InvalidCmdSignature"),
+    (REPLAYED_CMD, "ReplayedCmd"),
+    (STALE_CMD, "StaleCmd"),
+    (REGISTER_EXISTS, "RegisterExists"),
+    (INVALID_SPEND, "InvalidSpend"),
+    (CHUNK_NOT_STORED, "ChunkNotStored"),
+    (REGISTER_NOT_STORED, "RegisterNotStored"),
+    (SPEND_NOT_STORED, "SpendNotStored"),
+    (RESPONSE_SIGNING_FAILED, "ResponseSigningFailed"),
+    (CMD_SIGNING_FAILED, "CmdSigningFailed"),
+];
+
+/// The name of the `Error` variant the code stands for, if it is in the registry.
+pub fn variant_name(code: u32) -> Option<&'static str> {
+    ERROR_CODES
+        .iter()
+        .find(|(registered, _)| *registered == code)
+        .map(|(_, name)| *name)
+}
+
+/// The kind of the errors in the range of the code, also for codes not known to this version.
+pub fn kind_of(code: u32) -> Option<ErrorKind> {
+    match code / 1000 {
+        1 => Some(ErrorKind::NotFound),
+        2 => Some(ErrorKind::OutOfRange),
+        3 => Some(ErrorKind::QuotaExceeded),
+        4 => Some(ErrorKind::PaymentRequired),
+        5 => Some(ErrorKind::RateLimited),
+        6 => Some(ErrorKind::NotReady),
+        7 => Some(ErrorKind::InvalidRequest),
+        8 => Some(ErrorKind::Internal),
+        _ => None,
+    }
+}
+
+/// The code of an error carried in a response, with the version of the registry it is from.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct ErrorCode {
+    /// The version of the registry the code is from, see [`ERROR_CODES_VERSION`].
+    pub version: u32,
+    /// The code of the error.
+    pub code: u32,
+}
+
+impl From<&Error> for ErrorCode {
+    fn from(err: &Error) -> Self {
+        Self {
+            version: ERROR_CODES_VERSION,
+            code: err.code(),
+        }
+    }
+}
+
+impl Error {
+    /// The stable numeric code of the error, see the [`error_codes`] registry.
+    ///
+    /// [`error_codes`]: crate::protocol::error_codes
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::ChunkNotFound(_) => CHUNK_NOT_FOUND,
+            Self::RegisterNotFound(_) => REGISTER_NOT_FOUND,
+            Self::SpendNotFound(_) => SPEND_NOT_FOUND,
+            Self::RecordNotFound(_) => RECORD_NOT_FOUND,
+            Self::OutOfRange(_) => OUT_OF_RANGE,
+            Self::QuotaExceeded(_) => QUOTA_EXCEEDED,
+            Self::PaymentRequired(_) => PAYMENT_REQUIRED,
+            Self::InvalidPaymentProof(..) => INVALID_PAYMENT_PROOF,
+            Self::InvalidRoyaltyPayment(..) => INVALID_ROYALTY_PAYMENT,
+            Self::RoyaltyUnderpaid { .. } => ROYALTY_UNDERPAID,
+            Self::StoreCostUnpaid { .. } => STORE_COST_UNPAID,
+            Self::RateLimited { .. } => RATE_LIMITED,
+            Self::NotReady(_) => NOT_READY,
+            Self::RegisterAddressMismatch { .. } => REGISTER_ADDRESS_MISMATCH,
+            Self::InvalidRegisterOp(_) => INVALID_REGISTER_OP,
+            Self::InvalidReplica(..) => INVALID_REPLICA,
+            Self::RegisterWriteNotPermitted(_) => REGISTER_WRITE_NOT_PERMITTED,
+            Self::InvalidSpendSignature(..) => INVALID_SPEND_SIGNATURE,
+            Self::DoubleSpendAttempt { .. } => DOUBLE_SPEND_ATTEMPT,
+            Self::InvalidResponseSignature(_) => INVALID_RESPONSE_SIGNATURE,
+            Self::InvalidCmdSignature(_) => INVALID_CMD_SIGNATURE,
+            Self::ReplayedCmd(_) => REPLAYED_CMD,
+            Self::StaleCmd { .. } => STALE_CMD,
+            Self::RegisterExists(_) => REGISTER_EXISTS,
+            Self::InvalidSpend(..) => INVALID_SPEND,
+            Self::ChunkNotStored(_) => CHUNK_NOT_STORED,
+            Self::RegisterNotStored(_) => REGISTER_NOT_STORED,
+            Self::SpendNotStored(_) => SPEND_NOT_STORED,
+            Self::ResponseSigningFailed(_) => RESPONSE_SIGNING_FAILED,
+            Self::CmdSigningFailed(_) => CMD_SIGNING_FAILED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        protocol::{
+            messages::MsgId,
+            types::{
+                register::RegisterAddress,
+                spend::{
+                    tests::{random_derived_key, signed_spend},
+                    DbcAddress,
+                },
+            },
+        },
+        storage::{chunks::ChunkAddress, StorageLevel},
+    };
+    use std::collections::BTreeSet;
+    use xor_name::XorName;
+
+    // The name of the variant of the error. The match is exhaustive, so that a new variant
+    // does not build until it is given a name here, and an instance in `every_error`.
+    fn name_of(err: &Error) -> &'static str {
+        match err {
+            Error::ChunkNotFound(_) => "ChunkNotFound",
+            Error::ChunkNotStored(_) => "ChunkNotStored",
+            Error::RegisterNotFound(_) => "RegisterNotFound",
+            Error::RegisterNotStored(_) => "RegisterNotStored",
+            Error::RegisterAddressMismatch { .. } => "RegisterAddressMismatch",
+            Error::InvalidRegisterOp(_) => "InvalidRegisterOp",
+            Error::RegisterWriteNotPermitted(_) => "RegisterWriteNotPermitted",
+            Error::RegisterExists(_) => "RegisterExists",
+            Error::OutOfRange(_) => "OutOfRange",
+            Error::InvalidReplica(..) => "InvalidReplica",
+            Error::QuotaExceeded(_) => "QuotaExceeded",
+            Error::RateLimited { .. } => "RateLimited",
+            Error::NotReady(_) => "NotReady",
+            Error::PaymentRequired(_) => "PaymentRequired",
+            Error::InvalidPaymentProof(..) => "InvalidPaymentProof",
+            Error::InvalidRoyaltyPayment(..) => "InvalidRoyaltyPayment",
+            Error::RoyaltyUnderpaid { .. } => "RoyaltyUnderpaid",
+            Error::StoreCostUnpaid { .. } => "StoreCostUnpaid",
+            Error::SpendNotFound(_) => "SpendNotFound",
+            Error::RecordNotFound(_) => "RecordNotFound",
+            Error::SpendNotStored(_) => "SpendNotStored",
+            Error::InvalidSpendSignature(..) => "InvalidSpendSignature",
+            Error::InvalidSpend(..) => "InvalidSpend",
+            Error::DoubleSpendAttempt { .. } => "DoubleSpendAttempt",
+            Error::ResponseSigningFailed(_) => "ResponseSigningFailed",
+            Error::InvalidResponseSignature(_) => "InvalidResponseSignature",
+            Error::CmdSigningFailed(_) => "CmdSigningFailed",
+            Error::InvalidCmdSignature(_) => "InvalidCmdSignature",
+            Error::ReplayedCmd(_) => "ReplayedCmd",
+            Error::StaleCmd { .. } => "StaleCmd",
+        }
+    }
+
+    // An instance of every variant of the error.
+    fn every_error() -> Vec<Error> {
+        let name = XorName::from_content(b"name");
+        let chunk = ChunkAddress(name);
+        let register = RegisterAddress::new(name, 1);
+        let dbc = DbcAddress(name);
+        let derived_key = random_derived_key();
+        let spend = || Box::new(signed_spend(&derived_key, 100, 100));
+        vec![
+            Error::ChunkNotFound(chunk),
+            Error::ChunkNotStored(chunk),
+            Error::RegisterNotFound(register),
+            Error::RegisterNotStored(register),
+            Error::RegisterAddressMismatch {
+                dst: register,
+                op: register,
+            },
+            Error::InvalidRegisterOp(String::new()),
+            Error::RegisterWriteNotPermitted(register),
+            Error::RegisterExists(register),
+            Error::OutOfRange(name),
+            Error::InvalidReplica(name, String::new()),
+            Error::QuotaExceeded(StorageLevel { used: 1, total: 1 }),
+            Error::RateLimited {
+                retry_after_secs: 1,
+            },
+            Error::NotReady(String::new()),
+            Error::PaymentRequired(name),
+            Error::InvalidPaymentProof(name, String::new()),
+            Error::InvalidRoyaltyPayment(name, String::new()),
+            Error::RoyaltyUnderpaid {
+                name,
+                paid: 1,
+                due: 2,
+            },
+            Error::StoreCostUnpaid {
+                name,
+                paid: 1,
+                due: 2,
+            },
+            Error::SpendNotFound(dbc),
+            Error::RecordNotFound(name),
+            Error::SpendNotStored(dbc),
+            Error::InvalidSpendSignature(dbc, String::new()),
+            Error::InvalidSpend(dbc, String::new()),
+            Error::DoubleSpendAttempt {
+                new: spend(),
+                existing: spend(),
+            },
+            Error::ResponseSigningFailed(String::new()),
+            Error::InvalidResponseSignature(String::new()),
+            Error::CmdSigningFailed(String::new()),
+            Error::InvalidCmdSignature(String::new()),
+            Error::ReplayedCmd(MsgId(name)),
+            Error::StaleCmd {
+                issued_at: 0,
+                max_age_secs: 1,
+            },
+        ]
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        let codes: BTreeSet<_> = ERROR_CODES.iter().map(|(code, _)| *code).collect();
+        assert_eq!(codes.len(), ERROR_CODES.len());
+        let names: BTreeSet<_> = ERROR_CODES.iter().map(|(_, name)| *name).collect();
+        assert_eq!(names.len(), ERROR_CODES.len());
+    }
+
+    #[test]
+    fn codes_round_trip_for_every_error() {
+        for err in every_error() {
+            assert_eq!(kind_of(err.code()), Some(err.kind()), "{err:?}");
+            assert_eq!(variant_name(err.code()), Some(name_of(&err)), "{err:?}");
+            assert_eq!(ErrorCode::from(&err).version, ERROR_CODES_VERSION);
+        }
+    }
+
+    #[test]
+    fn registry_covers_every_error() {
+        let errors = every_error();
+        let names: BTreeSet<_> = errors.iter().map(name_of).collect();
+        assert_eq!(names.len(), errors.len(), "an error is listed twice");
+        let registered: BTreeSet<_> = ERROR_CODES.iter().map(|(_, name)| *name).collect();
+        assert_eq!(registered, names);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Topic;
+use crate::{
+    protocol::types::spend::DbcAddress,
+    protocol::types::{
+        payment::PaymentProof,
+        register::{PolicyOp, Register, RegisterAddress, RegisterOp},
+    },
+    storage::chunks::ChunkAddress,
+};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sn_dbc::{DbcTransaction, SignedSpend};
+use xor_name::XorName;
+
+/// Data and Dbc cmds - recording spends or creating, updating, and removing data.
+#[derive(Eq, PartialEq, Clone, Serialize, Deserialize, custom_debug::Debug)]
+pub enum Cmd {
+    /// Store the chunk `value` at the given `address`.
+    ///
+    /// The `address` must be the `XorName` of the `value`, otherwise the chunk is rejected
+    /// with an [`InvalidChunkAddress`] response.
+    ///
+    /// [`InvalidChunkAddress`]: super::CmdResponse::InvalidChunkAddress
+    StoreChunk {
+        /// The address the chunk is to be stored at.
+        address: ChunkAddress,
+        /// The content of the chunk.
+        #[debug(skip)]
+        value: Bytes,
+        /// Proof that the storage of the chunk has been paid for.
+        payment: PaymentProof,
+    },
+    /// Create an empty [`Register`] at the given address.
+    ///
+    /// [`Register`]: crate::protocol::types::register::Register
+    RegisterCreate {
+        /// The address of the register.
+        address: RegisterAddress,
+        /// The owner of the register, and the keys allowed to write to it, signed by the owner.
+        policy: PolicyOp,
+        /// Proof that the storage of the register has been paid for.
+        payment: PaymentProof,
+    },
+    /// Apply an op to the [`Register`] it is addressed to, merging it into the stored replica.
+    ///
+    /// [`Register`]: crate::protocol::types::register::Register
+    RegisterEdit(RegisterOp),
+    /// Replace the [`Policy`] of the register it is addressed to, if signed by its owner.
+    ///
+    /// [`Policy`]: crate::protocol::types::register::Policy
+    RegisterSetPolicy(PolicyOp),
+    /// Sync the replica of the [`Register`] at the given address with the replicas of the rest
+    /// of its close group, before the register is read, for the read to see every entry
+    /// acknowledged by any of them.
+    ///
+    /// [`Register`]: crate::protocol::types::register::Register
+    SyncRegister(RegisterAddress),
+    /// Record the spend of a Dbc, at the [`DbcAddress`] derived from its id.
+    ///
+    /// The spend is rejected if a different spend of the same Dbc is already known
+    /// to the close group of that address, or if the parents of the Dbc, i.e. the inputs of
+    /// the transaction it was created by, are not recorded as spent in that transaction.
+    ///
+    /// [`DbcAddress`]: crate::protocol::types::spend::DbcAddress
+    SpendDbc {
+        /// The spend of the Dbc.
+        signed_spend: Box<SignedSpend>,
+        /// The transaction the Dbc was created by, for its parents to be checked.
+        parent_tx: Box<DbcTransaction>,
+    },
+    /// Subscribe to the [`Event`]s of the given topic, to be pushed to us as they happen.
+    ///
+    /// [`Event`]: super::Event
+    Subscribe(Topic),
+    /// Stop being pushed the events of the given topic.
+    Unsubscribe(Topic),
+    /// Store the replica of a record handed off by a node leaving the network.
+    ///
+    /// Only taken from the peers of the routing table of the node, for records within its
+    /// range: the data is checked against its address and signatures, but no payment is
+    /// carried, having been made when it was first stored. Registers are thus only merged
+    /// into the replicas the node already holds.
+    Replicate(Replica),
+    /// Inform a node that joined the close group of the given records that it should hold
+    /// them, for it to pull the ones it lacks from us with [`FetchReplication`] queries.
+    ///
+    /// Only taken from the peers of the routing table of the node, for records within its range.
+    ///
+    /// [`FetchReplication`]: super::Query::FetchReplication
+    ReplicateRecords {
+        /// The names of the records.
+        keys: Vec<XorName>,
+    },
+}
+
+/// The replica of a record held by a node, handed off to the peers closest to it.
+#[derive(Eq, PartialEq, Clone, Serialize, Deserialize, custom_debug::Debug)]
+pub enum Replica {
+    /// The content of a chunk, stored at the hash of it.
+    Chunk(#[debug(skip)] Bytes),
+    /// A register with all its entries and policy.
+    Register(Box<Register>),
+    /// The spends of a Dbc, more than one if a double spend was attempted.
+    Spends(Vec<SignedSpend>),
+}
+
+impl Replica {
+    /// Returns the name of the record, `None` for a replica of spends holding none.
+    pub fn name(&self) -> Option<XorName> {
+        match self {
+            Self::Chunk(value) => Some(XorName::from_content(value)),
+            Self::Register(register) => Some(register.address().id()),
+            Self::Spends(spends) => spends
+                .first()
+                .map(|spend| *DbcAddress::from_dbc_id(spend.dbc_id()).name()),
+        }
+    }
+}
+
+impl Cmd {
+    /// Returns the name of the record the cmd is about, the peers closest to it
+    /// being the ones the cmd is sent to.
+    pub fn dst(&self) -> XorName {
+        match self {
+            Self::StoreChunk { address, .. } => *address.name(),
+            Self::RegisterCreate { address, .. } => address.id(),
+            Self::RegisterEdit(op) => op.address.id(),
+            Self::RegisterSetPolicy(op) => op.address.id(),
+            Self::SyncRegister(address) => address.id(),
+            Self::SpendDbc { signed_spend, .. } => {
+                *DbcAddress::from_dbc_id(signed_spend.dbc_id()).name()
+            }
+            Self::Subscribe(topic) | Self::Unsubscribe(topic) => topic.name(),
+            Self::Replicate(replica) => replica.name().unwrap_or_default(),
+            Self::ReplicateRecords { keys } => keys.first().copied().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the hash identifying the cmd, with which nodes acknowledge having applied it.
+    pub fn hash(&self) -> XorName {
+        match self {
+            Self::StoreChunk { address, .. } => {
+                XorName::from_content_parts(&[b"StoreChunk", &address.name().0])
+            }
+            Self::RegisterCreate { address, .. } => {
+                XorName::from_content_parts(&[b"RegisterCreate", &address.id().0])
+            }
+            Self::RegisterEdit(op) => XorName::from_content_parts(&[
+                b"RegisterEdit",
+                &op.address.id().0,
+                &op.crdt_op.hash(),
+            ]),
+            Self::RegisterSetPolicy(op) => XorName::from_content_parts(&[
+                b"RegisterSetPolicy",
+                &op.address.id().0,
+                &op.policy.version().to_be_bytes(),
+            ]),
+            Self::SyncRegister(address) => {
+                XorName::from_content_parts(&[b"SyncRegister", &address.id().0])
+            }
+            Self::SpendDbc { signed_spend, .. } => {
+                XorName::from_content_parts(&[b"SpendDbc", &signed_spend.to_bytes()])
+            }
+            Self::Subscribe(topic) => XorName::from_content_parts(&[b"Subscribe", &topic.name().0]),
+            Self::Unsubscribe(topic) => {
+                XorName::from_content_parts(&[b"Unsubscribe", &topic.name().0])
+            }
+            Self::Replicate(replica) => {
+                let value = rmp_serde::to_vec(replica).unwrap_or_default();
+                XorName::from_content_parts(&[b"Replicate", &value])
+            }
+            Self::ReplicateRecords { keys } => {
+                let names: Vec<u8> = keys.iter().flat_map(|key| key.0).collect();
+                XorName::from_content_parts(&[b"ReplicateRecords", &names])
+            }
+        }
+    }
+}
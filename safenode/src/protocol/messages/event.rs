@@ -0,0 +1,56 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::protocol::types::{
+    register::{RegisterAddress, RegisterOp},
+    spend::DbcAddress,
+};
+use serde::{Deserialize, Serialize};
+use sn_dbc::SignedSpend;
+use xor_name::XorName;
+
+/// Events pushed by nodes to the peers subscribed to their [`Topic`].
+#[derive(Eq, PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub enum Event {
+    /// An op was applied to the register it is addressed to.
+    RegisterEdited(RegisterOp),
+    /// The spend of a Dbc was recorded in the spentbook.
+    SpendRecorded(Box<SignedSpend>),
+}
+
+impl Event {
+    /// Returns the topic the event is published under.
+    pub fn topic(&self) -> Topic {
+        match self {
+            Self::RegisterEdited(op) => Topic::Register(op.address),
+            Self::SpendRecorded(signed_spend) => {
+                Topic::Spend(DbcAddress::from_dbc_id(signed_spend.dbc_id()))
+            }
+        }
+    }
+}
+
+/// What a peer can subscribe to, to be pushed the [`Event`]s about it.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+pub enum Topic {
+    /// The ops applied to the register at the given address.
+    Register(RegisterAddress),
+    /// The spend of the Dbc at the given address.
+    Spend(DbcAddress),
+}
+
+impl Topic {
+    /// Returns the `XorName` of the data the topic is about, the peers closest
+    /// to it being the ones publishing its events.
+    pub fn name(&self) -> XorName {
+        match self {
+            Self::Register(addr) => addr.id(),
+            Self::Spend(addr) => *addr.name(),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+mod cmd;
+mod event;
+mod query;
+mod response;
+mod signed;
+
+pub use self::{
+    cmd::{Cmd, Replica},
+    event::{Event, Topic},
+    query::{Query, FETCH_REPLICATION_MAX_KEYS, FETCH_REPLICATION_MAX_SIZE},
+    response::{CmdResponse, QueryResponse},
+    signed::{MsgId, SignedCmd, SignedResponse},
+};
+
+pub(crate) use self::signed::unix_time_secs;
+
+use crate::protocol::error::Error;
+use serde::{Deserialize, Serialize};
+use xor_name::XorName;
+
+/// Send a request to other peers in the network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Request {
+    /// A cmd sent to peers, signed by its issuer. Cmds are writes, i.e. can cause mutation.
+    Cmd(SignedCmd),
+    /// A query sent to peers. Queries are read-only.
+    Query(Query),
+    /// An event pushed to a peer subscribed to its topic.
+    Event(Event),
+}
+
+impl Request {
+    /// Returns the name of the record the request is about, if any.
+    pub fn dst(&self) -> Option<XorName> {
+        match self {
+            Self::Cmd(signed_cmd) => Some(signed_cmd.cmd.dst()),
+            Self::Query(query) => query.dst(),
+            Self::Event(event) => Some(event.topic().name()),
+        }
+    }
+}
+
+/// Respond to other peers in the network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Response {
+    /// The response to a cmd.
+    Cmd(CmdResponse),
+    /// The response to a query.
+    Query(QueryResponse),
+    /// The acknowledgement of a pushed event.
+    EventReceived,
+}
+
+impl Response {
+    /// The error the request failed with, if any.
+    pub fn error(&self) -> Option<&Error> {
+        match self {
+            Self::Cmd(resp) => resp.error(),
+            Self::Query(resp) => resp.error(),
+            Self::EventReceived => None,
+        }
+    }
+}
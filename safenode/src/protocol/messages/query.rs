@@ -0,0 +1,157 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    protocol::types::{
+        register::{EntryHash, RegisterAddress},
+        spend::DbcAddress,
+    },
+    storage::chunks::ChunkAddress,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+/// Data and Dbc queries - retrieving data and inspecting their structure.
+#[derive(Eq, PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub enum Query {
+    /// Retrieve a [`Chunk`] at the given address.
+    ///
+    /// This should eventually lead to a [`GetChunk`] response.
+    ///
+    /// [`Chunk`]:  crate::storage::chunks::Chunk
+    /// [`GetChunk`]: super::QueryResponse::GetChunk
+    GetChunk(ChunkAddress),
+    /// Check whether the [`Chunk`] at the given address is held, without retrieving it,
+    /// e.g. before paying for its upload.
+    ///
+    /// This should eventually lead to a [`GetChunkExists`] response.
+    ///
+    /// [`Chunk`]:  crate::storage::chunks::Chunk
+    /// [`GetChunkExists`]: super::QueryResponse::GetChunkExists
+    GetChunkExists(ChunkAddress),
+    /// Retrieve the [`Register`] at the given address.
+    ///
+    /// This should eventually lead to a [`GetRegister`] response.
+    ///
+    /// [`Register`]: crate::protocol::types::register::Register
+    /// [`GetRegister`]: super::QueryResponse::GetRegister
+    GetRegister(RegisterAddress),
+    /// Retrieve the digest of the replica of the [`Register`] at the given address, for its
+    /// holders to tell whether their replicas diverged.
+    ///
+    /// This should eventually lead to a [`GetRegisterDigest`] response.
+    ///
+    /// [`Register`]: crate::protocol::types::register::Register
+    /// [`GetRegisterDigest`]: super::QueryResponse::GetRegisterDigest
+    GetRegisterDigest(RegisterAddress),
+    /// Retrieve the entries of the replica of the [`Register`] at the given address missing
+    /// from a replica whose current entries are the `known` ones.
+    ///
+    /// This should eventually lead to a [`GetRegisterEntries`] response.
+    ///
+    /// [`Register`]: crate::protocol::types::register::Register
+    /// [`GetRegisterEntries`]: super::QueryResponse::GetRegisterEntries
+    GetRegisterEntries {
+        /// The address of the register.
+        address: RegisterAddress,
+        /// The hashes of the current entries of the replica of the querying node.
+        known: BTreeSet<EntryHash>,
+    },
+    /// Retrieve the used and total record capacity of the queried node.
+    ///
+    /// This should eventually lead to a [`GetStorageLevel`] response.
+    ///
+    /// [`GetStorageLevel`]: super::QueryResponse::GetStorageLevel
+    GetStorageLevel,
+    /// Retrieve the addresses of the chunks held by the queried node, a page at a time,
+    /// starting after the given cursor.
+    ///
+    /// This should eventually lead to a [`ListChunks`] response.
+    ///
+    /// [`ListChunks`]: super::QueryResponse::ListChunks
+    ListChunks {
+        /// The last address of the previous page, `None` for the first page.
+        cursor: Option<ChunkAddress>,
+    },
+    /// Retrieve the spend of the Dbc at the given address.
+    ///
+    /// This should eventually lead to a [`GetSpend`] response.
+    ///
+    /// [`GetSpend`]: super::QueryResponse::GetSpend
+    GetSpend(DbcAddress),
+    /// Retrieve the cost the queried node asks for the storage of the record at the given
+    /// name, and the address it is to be paid to.
+    ///
+    /// This should eventually lead to a [`GetStoreCost`] response.
+    ///
+    /// [`GetStoreCost`]: super::QueryResponse::GetStoreCost
+    GetStoreCost(XorName),
+    /// Retrieve the record the queried node holds at the given name, whichever its kind, for
+    /// the replication repairs and audits fetching it from a specific holder.
+    ///
+    /// This should eventually lead to a [`GetRecord`] response.
+    ///
+    /// [`GetRecord`]: super::QueryResponse::GetRecord
+    GetRecord(XorName),
+    /// Challenge the queried node to prove it holds the [`Chunk`] at the given address, by
+    /// hashing its value along with the nonce and its own peer id, as the audits of the close
+    /// group of a chunk do.
+    ///
+    /// This should eventually lead to a [`ChunkProof`] response.
+    ///
+    /// [`Chunk`]:  crate::storage::chunks::Chunk
+    /// [`ChunkProof`]: super::QueryResponse::ChunkProof
+    ChunkProof {
+        /// The address of the chunk.
+        key: ChunkAddress,
+        /// The nonce picked by the challenger, hashed along with the value of the chunk.
+        nonce: u64,
+    },
+    /// Retrieve the records the queried node holds among the given ones, for a node informed
+    /// of the records it should hold by a [`ReplicateRecords`] cmd to pull them.
+    ///
+    /// At most [`FETCH_REPLICATION_MAX_KEYS`] records are returned, in the order they are
+    /// asked for, and no more than [`FETCH_REPLICATION_MAX_SIZE`] bytes of them past the first
+    /// one, the keys past them being left out for another query.
+    ///
+    /// This should eventually lead to a [`FetchReplication`] response.
+    ///
+    /// [`ReplicateRecords`]: super::Cmd::ReplicateRecords
+    /// [`FetchReplication`]: super::QueryResponse::FetchReplication
+    FetchReplication {
+        /// The names of the records.
+        keys: Vec<XorName>,
+    },
+}
+
+/// The max number of records returned in response to a [`Query::FetchReplication`].
+pub const FETCH_REPLICATION_MAX_KEYS: usize = 8;
+
+/// The max size of the records returned in response to a [`Query::FetchReplication`], in
+/// bytes, well within the max size of a msg.
+pub const FETCH_REPLICATION_MAX_SIZE: usize = 4 * 1024 * 1024;
+
+impl Query {
+    /// Returns the name of the record the query is about, `None` for the queries
+    /// about the queried node itself.
+    pub fn dst(&self) -> Option<XorName> {
+        match self {
+            Self::GetChunk(address)
+            | Self::GetChunkExists(address)
+            | Self::ChunkProof { key: address, .. } => Some(*address.name()),
+            Self::GetRegister(address)
+            | Self::GetRegisterDigest(address)
+            | Self::GetRegisterEntries { address, .. } => Some(address.id()),
+            Self::GetSpend(address) => Some(*address.name()),
+            Self::GetStoreCost(name) | Self::GetRecord(name) => Some(*name),
+            Self::FetchReplication { keys } => keys.first().copied(),
+            Self::GetStorageLevel | Self::ListChunks { .. } => None,
+        }
+    }
+}
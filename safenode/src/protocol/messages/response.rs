@@ -0,0 +1,205 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Replica;
+use crate::{
+    protocol::{
+        error::{Error, Result},
+        types::{
+            page::Page,
+            payment::StoreCost,
+            register::{Register, RegisterDigest, RegisterEntries},
+        },
+    },
+    storage::{
+        chunks::{Chunk, ChunkAddress, ChunkProof},
+        StorageLevel,
+    },
+};
+use serde::{Deserialize, Serialize};
+use sn_dbc::SignedSpend;
+use xor_name::XorName;
+
+/// The response to a query, containing the query result.
+#[allow(clippy::large_enum_variant)]
+#[derive(Eq, PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub enum QueryResponse {
+    //
+    // ===== Chunk =====
+    //
+    /// Response to [`GetChunk`]
+    ///
+    /// [`GetChunk`]: crate::protocol::messages::Query::GetChunk
+    GetChunk(Result<Chunk>),
+    /// Response to [`GetChunkExists`]
+    ///
+    /// [`GetChunkExists`]: crate::protocol::messages::Query::GetChunkExists
+    GetChunkExists(bool),
+    //
+    // ===== Register =====
+    //
+    /// Response to [`GetRegister`]
+    ///
+    /// [`GetRegister`]: crate::protocol::messages::Query::GetRegister
+    GetRegister(Result<Register>),
+    /// Response to [`GetRegisterDigest`]
+    ///
+    /// [`GetRegisterDigest`]: crate::protocol::messages::Query::GetRegisterDigest
+    GetRegisterDigest(Result<RegisterDigest>),
+    /// Response to [`GetRegisterEntries`]
+    ///
+    /// [`GetRegisterEntries`]: crate::protocol::messages::Query::GetRegisterEntries
+    GetRegisterEntries(Result<RegisterEntries>),
+    //
+    // ===== Node =====
+    //
+    /// Response to [`GetStorageLevel`]
+    ///
+    /// [`GetStorageLevel`]: crate::protocol::messages::Query::GetStorageLevel
+    GetStorageLevel(StorageLevel),
+    /// Response to [`ListChunks`]
+    ///
+    /// [`ListChunks`]: crate::protocol::messages::Query::ListChunks
+    ListChunks(Page<ChunkAddress>),
+    /// Response to [`GetStoreCost`], `None` if the node does not ask to be paid.
+    ///
+    /// [`GetStoreCost`]: crate::protocol::messages::Query::GetStoreCost
+    GetStoreCost(Option<StoreCost>),
+    //
+    // ===== Dbc =====
+    //
+    /// Response to [`GetSpend`]
+    ///
+    /// [`GetSpend`]: crate::protocol::messages::Query::GetSpend
+    GetSpend(Result<SignedSpend>),
+    //
+    // ===== Records =====
+    //
+    /// Response to [`GetRecord`], the record as replicated between nodes.
+    ///
+    /// [`GetRecord`]: crate::protocol::messages::Query::GetRecord
+    GetRecord(Result<Replica>),
+    /// Response to [`ChunkProof`], the proof the chunk is held.
+    ///
+    /// [`ChunkProof`]: crate::protocol::messages::Query::ChunkProof
+    ChunkProof(Result<ChunkProof>),
+    /// Response to [`FetchReplication`], the records held among the ones asked for.
+    ///
+    /// [`FetchReplication`]: crate::protocol::messages::Query::FetchReplication
+    FetchReplication(Vec<Replica>),
+}
+
+/// The response to a Cmd, containing the query result.
+#[derive(Eq, PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub enum CmdResponse {
+    /// The cmd has been applied, and the data it carried durably stored.
+    /// Returned in place of the cmd specific response when it succeeds.
+    Ack {
+        /// The [`hash`] of the cmd acknowledged.
+        ///
+        /// [`hash`]: crate::protocol::messages::Cmd::hash
+        cmd_hash: XorName,
+    },
+    //
+    // ===== Chunk =====
+    //
+    /// Response to [`StoreChunk`]
+    ///
+    /// [`StoreChunk`]: crate::protocol::messages::Cmd::StoreChunk
+    StoreChunk(Result<()>),
+    /// The address of a chunk sent to be stored does not match the hash of its content
+    InvalidChunkAddress {
+        /// The address the chunk was sent to be stored at
+        claimed: ChunkAddress,
+        /// The address computed from the content of the chunk
+        computed: ChunkAddress,
+    },
+    //
+    // ===== Register =====
+    //
+    /// Response to [`RegisterCreate`]
+    ///
+    /// [`RegisterCreate`]: crate::protocol::messages::Cmd::RegisterCreate
+    RegisterCreate(Result<()>),
+    /// Response to [`RegisterEdit`]
+    ///
+    /// [`RegisterEdit`]: crate::protocol::messages::Cmd::RegisterEdit
+    RegisterEdit(Result<()>),
+    /// Response to [`RegisterSetPolicy`]
+    ///
+    /// [`RegisterSetPolicy`]: crate::protocol::messages::Cmd::RegisterSetPolicy
+    RegisterSetPolicy(Result<()>),
+    /// Response to [`SyncRegister`]
+    ///
+    /// [`SyncRegister`]: crate::protocol::messages::Cmd::SyncRegister
+    SyncRegister(Result<()>),
+    //
+    // ===== Dbc =====
+    //
+    /// Response to [`SpendDbc`]
+    ///
+    /// [`SpendDbc`]: crate::protocol::messages::Cmd::SpendDbc
+    Spend(Result<()>),
+    //
+    // ===== Events =====
+    //
+    /// Response to [`Subscribe`]
+    ///
+    /// [`Subscribe`]: crate::protocol::messages::Cmd::Subscribe
+    Subscribe(Result<()>),
+    /// Response to [`Unsubscribe`]
+    ///
+    /// [`Unsubscribe`]: crate::protocol::messages::Cmd::Unsubscribe
+    Unsubscribe(Result<()>),
+    //
+    // ===== Replication =====
+    //
+    /// Response to [`Replicate`]
+    ///
+    /// [`Replicate`]: crate::protocol::messages::Cmd::Replicate
+    Replicate(Result<()>),
+    /// Response to [`ReplicateRecords`]
+    ///
+    /// [`ReplicateRecords`]: crate::protocol::messages::Cmd::ReplicateRecords
+    ReplicateRecords(Result<()>),
+}
+
+impl QueryResponse {
+    /// The error the query failed with, if any.
+    pub fn error(&self) -> Option<&Error> {
+        match self {
+            Self::GetChunk(Err(err))
+            | Self::GetRegister(Err(err))
+            | Self::GetRegisterDigest(Err(err))
+            | Self::GetRegisterEntries(Err(err))
+            | Self::GetSpend(Err(err))
+            | Self::GetRecord(Err(err))
+            | Self::ChunkProof(Err(err)) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl CmdResponse {
+    /// The error the cmd failed with, if any.
+    pub fn error(&self) -> Option<&Error> {
+        match self {
+            Self::StoreChunk(Err(err))
+            | Self::RegisterCreate(Err(err))
+            | Self::RegisterEdit(Err(err))
+            | Self::RegisterSetPolicy(Err(err))
+            | Self::SyncRegister(Err(err))
+            | Self::Spend(Err(err))
+            | Self::Subscribe(Err(err))
+            | Self::Unsubscribe(Err(err))
+            | Self::Replicate(Err(err))
+            | Self::ReplicateRecords(Err(err)) => Some(err),
+            _ => None,
+        }
+    }
+}
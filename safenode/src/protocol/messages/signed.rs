@@ -0,0 +1,197 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Cmd, Response};
+use crate::protocol::{
+    error::{Error, Result},
+    error_codes::{ErrorCode, ERROR_CODES_VERSION},
+};
+use instant::SystemTime;
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    PeerId,
+};
+use serde::{Deserialize, Serialize};
+use xor_name::XorName;
+
+/// The unique id of a cmd, with which nodes recognise a cmd they have already handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MsgId(pub(crate) XorName);
+
+impl MsgId {
+    /// Returns a new random id.
+    pub fn random() -> Result<Self> {
+        let mut bytes = [0; xor_name::XOR_NAME_LEN];
+        getrandom::getrandom(&mut bytes)
+            .map_err(|err| Error::CmdSigningFailed(format!("no msg id: {err}")))?;
+        Ok(Self(XorName(bytes)))
+    }
+}
+
+/// A `Cmd` with a unique id, signed by its issuer together with the time it was issued.
+/// Nodes drop cmds that are stale or whose id they have already seen, so that a captured
+/// cmd cannot be replayed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedCmd {
+    /// The cmd.
+    pub cmd: Cmd,
+    /// The unique id of the cmd.
+    pub msg_id: MsgId,
+    /// The time the cmd was issued, in seconds since the unix epoch.
+    pub issued_at: u64,
+    /// The protobuf encoding of the public key of the issuer.
+    pub(crate) signer: Vec<u8>,
+    /// The signature of the issuer over the id, issue time and cmd.
+    pub(crate) signature: Vec<u8>,
+}
+
+impl SignedCmd {
+    /// Gives the cmd a new id and signs it with the given keypair.
+    pub fn new(cmd: Cmd, keypair: &Keypair) -> Result<Self> {
+        let msg_id = MsgId::random()?;
+        let issued_at = unix_time_secs();
+        let bytes = serialise_cmd(&msg_id, issued_at, &cmd)?;
+        let signature = keypair
+            .sign(&bytes)
+            .map_err(|err| Error::CmdSigningFailed(err.to_string()))?;
+        Ok(Self {
+            cmd,
+            msg_id,
+            issued_at,
+            signer: keypair.public().encode_protobuf(),
+            signature,
+        })
+    }
+
+    /// The public key of the issuer of the cmd.
+    pub fn signer(&self) -> Result<PublicKey> {
+        PublicKey::try_decode_protobuf(&self.signer)
+            .map_err(|err| Error::InvalidCmdSignature(format!("invalid signer key: {err}")))
+    }
+
+    /// Verifies that the cmd was signed by the included signer, returning the
+    /// `PeerId` of the signer.
+    pub fn verify(&self) -> Result<PeerId> {
+        let signer = self.signer()?;
+        let bytes = serialise_cmd(&self.msg_id, self.issued_at, &self.cmd)?;
+        if !signer.verify(&bytes, &self.signature) {
+            return Err(Error::InvalidCmdSignature(format!(
+                "signature does not match the cmd {:?} of {:?}",
+                self.msg_id,
+                signer.to_peer_id()
+            )));
+        }
+        Ok(signer.to_peer_id())
+    }
+}
+
+/// The current time, in seconds since the unix epoch.
+pub(crate) fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// A `Response` signed by the node that produced it, so that the requester
+/// can verify which node answered before trusting its content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedResponse {
+    /// The response.
+    pub response: Response,
+    /// The stable code of the error carried in the response, if any, for requesters
+    /// that cannot make sense of the error itself.
+    pub error_code: Option<ErrorCode>,
+    /// The protobuf encoding of the public key of the signing node.
+    pub(crate) signer: Vec<u8>,
+    /// The signature of the signing node over the serialised response.
+    pub(crate) signature: Vec<u8>,
+}
+
+impl SignedResponse {
+    /// Signs the response with the given keypair.
+    pub fn new(response: Response, keypair: &Keypair) -> Result<Self> {
+        let bytes = serialise(&response)?;
+        let signature = keypair
+            .sign(&bytes)
+            .map_err(|err| Error::ResponseSigningFailed(err.to_string()))?;
+        Ok(Self {
+            error_code: response.error().map(ErrorCode::from),
+            response,
+            signer: keypair.public().encode_protobuf(),
+            signature,
+        })
+    }
+
+    /// The public key of the node that signed the response.
+    pub fn signer(&self) -> Result<PublicKey> {
+        PublicKey::try_decode_protobuf(&self.signer)
+            .map_err(|err| Error::InvalidResponseSignature(format!("invalid signer key: {err}")))
+    }
+
+    /// Verifies that the response was signed by the included signer, returning the
+    /// `PeerId` of the signer.
+    pub fn verify(&self) -> Result<PeerId> {
+        let signer = self.signer()?;
+        let bytes = serialise(&self.response)?;
+        if !signer.verify(&bytes, &self.signature) {
+            return Err(Error::InvalidResponseSignature(format!(
+                "signature does not match the response of {:?}",
+                signer.to_peer_id()
+            )));
+        }
+        // The code is not covered by the signature, but is derived from the signed response.
+        if let Some(ErrorCode { version, code }) = self.error_code {
+            let expected = self.response.error().map(Error::code);
+            if version == ERROR_CODES_VERSION && expected != Some(code) {
+                return Err(Error::InvalidResponseSignature(format!(
+                    "error code {code} does not match the response of {:?}",
+                    signer.to_peer_id()
+                )));
+            }
+        }
+        Ok(signer.to_peer_id())
+    }
+}
+
+// The bytes the signature of a cmd is made over.
+fn serialise_cmd(msg_id: &MsgId, issued_at: u64, cmd: &Cmd) -> Result<Vec<u8>> {
+    rmp_serde::to_vec(&(msg_id, issued_at, cmd))
+        .map_err(|err| Error::CmdSigningFailed(err.to_string()))
+}
+
+// The bytes the signature of a response is made over.
+fn serialise(response: &Response) -> Result<Vec<u8>> {
+    rmp_serde::to_vec(response).map_err(|err| Error::ResponseSigningFailed(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{protocol::messages::QueryResponse, storage::chunks::ChunkAddress};
+    use eyre::Result;
+
+    #[test]
+    fn responses_with_a_tampered_error_code_are_rejected() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let err = Error::ChunkNotFound(ChunkAddress(XorName::from_content(b"chunk")));
+        let response = Response::Query(QueryResponse::GetChunk(Err(err)));
+        let mut signed = SignedResponse::new(response, &keypair)?;
+        assert_eq!(signed.verify()?, keypair.public().to_peer_id());
+
+        signed.error_code = Some(ErrorCode {
+            version: ERROR_CODES_VERSION,
+            code: crate::protocol::error_codes::RATE_LIMITED,
+        });
+        assert!(matches!(
+            signed.verify(),
+            Err(Error::InvalidResponseSignature(_))
+        ));
+        Ok(())
+    }
+}
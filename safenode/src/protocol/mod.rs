@@ -0,0 +1,19 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+/// Errors.
+pub mod error;
+/// Stable numeric codes of the errors.
+pub mod error_codes;
+/// Messages types
+pub mod messages;
+/// The protobuf encoding of the messages.
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+/// Data types
+pub mod types;
@@ -0,0 +1,932 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    error::Error as ProtocolError,
+    error_codes::ErrorCode,
+    messages::{
+        Cmd, CmdResponse, Event, MsgId, Query, QueryResponse, Replica, Request, Response,
+        SignedCmd, SignedResponse, Topic,
+    },
+    types::{
+        page::Page,
+        payment::StoreCost,
+        register::{EntryHash, RegisterAddress, RegisterDigest},
+        spend::DbcAddress,
+    },
+};
+use crate::storage::{
+    chunks::{Chunk, ChunkAddress, ChunkProof},
+    StorageLevel,
+};
+use bytes::Bytes;
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use xor_name::XorName;
+
+// Defined in protos/wire.proto
+#[allow(
+    missing_docs,
+    unreachable_pub,
+    unused_qualifications,
+    clippy::unwrap_used
+)]
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/safenode_wire.rs"));
+}
+
+/// A specialised `Result` type for the protobuf encoding.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors in the protobuf encoding and decoding of messages.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The bytes are not a protobuf encoded message.
+    #[error("Failed to decode the protobuf message: {0}")]
+    Decode(#[from] prost::DecodeError),
+    /// A field the message cannot do without is not set.
+    #[error("Missing field: {0}")]
+    MissingField(&'static str),
+    /// A field is set to a value not valid for it.
+    #[error("Invalid field {field}: {reason}")]
+    InvalidField {
+        /// The name of the field.
+        field: &'static str,
+        /// Why the value is not valid.
+        reason: String,
+    },
+    /// A value carried as MessagePack could not be serialised.
+    #[error("Failed to serialise {field}: {reason}")]
+    Serialisation {
+        /// The name of the field.
+        field: &'static str,
+        /// Why the value could not be serialised.
+        reason: String,
+    },
+}
+
+/// Encodes the request as protobuf.
+pub fn encode_request(request: &Request) -> Result<Vec<u8>> {
+    Ok(request_to_proto(request)?.encode_to_vec())
+}
+
+/// Decodes a protobuf encoded request.
+pub fn decode_request(bytes: &[u8]) -> Result<Request> {
+    request_from_proto(proto::Request::decode(bytes)?)
+}
+
+/// Encodes the signed response as protobuf.
+pub fn encode_response(response: &SignedResponse) -> Result<Vec<u8>> {
+    Ok(signed_response_to_proto(response)?.encode_to_vec())
+}
+
+/// Decodes a protobuf encoded signed response.
+pub fn decode_response(bytes: &[u8]) -> Result<SignedResponse> {
+    signed_response_from_proto(proto::SignedResponse::decode(bytes)?)
+}
+
+//
+// ===== Envelopes =====
+//
+
+fn request_to_proto(request: &Request) -> Result<proto::Request> {
+    use proto::request::Kind;
+    let kind = match request {
+        Request::Cmd(signed_cmd) => Kind::Cmd(signed_cmd_to_proto(signed_cmd)?),
+        Request::Query(query) => Kind::Query(query_to_proto(query)),
+        Request::Event(event) => Kind::Event(event_to_proto(event)?),
+    };
+    Ok(proto::Request { kind: Some(kind) })
+}
+
+fn request_from_proto(request: proto::Request) -> Result<Request> {
+    use proto::request::Kind;
+    Ok(match required("request", request.kind)? {
+        Kind::Cmd(signed_cmd) => Request::Cmd(signed_cmd_from_proto(signed_cmd)?),
+        Kind::Query(query) => Request::Query(query_from_proto(query)?),
+        Kind::Event(event) => Request::Event(event_from_proto(event)?),
+    })
+}
+
+fn signed_cmd_to_proto(signed_cmd: &SignedCmd) -> Result<proto::SignedCmd> {
+    Ok(proto::SignedCmd {
+        cmd: Some(cmd_to_proto(&signed_cmd.cmd)?),
+        msg_id: signed_cmd.msg_id.0.to_vec(),
+        issued_at: signed_cmd.issued_at,
+        signer: signed_cmd.signer.clone(),
+        signature: signed_cmd.signature.clone(),
+    })
+}
+
+fn signed_cmd_from_proto(signed_cmd: proto::SignedCmd) -> Result<SignedCmd> {
+    Ok(SignedCmd {
+        cmd: cmd_from_proto(required("signed_cmd.cmd", signed_cmd.cmd)?)?,
+        msg_id: MsgId(xor_name("signed_cmd.msg_id", &signed_cmd.msg_id)?),
+        issued_at: signed_cmd.issued_at,
+        signer: signed_cmd.signer,
+        signature: signed_cmd.signature,
+    })
+}
+
+fn signed_response_to_proto(signed_response: &SignedResponse) -> Result<proto::SignedResponse> {
+    Ok(proto::SignedResponse {
+        response: Some(response_to_proto(&signed_response.response)?),
+        error_code: signed_response
+            .error_code
+            .map(|ErrorCode { version, code }| proto::ErrorCode { version, code }),
+        signer: signed_response.signer.clone(),
+        signature: signed_response.signature.clone(),
+    })
+}
+
+fn signed_response_from_proto(signed_response: proto::SignedResponse) -> Result<SignedResponse> {
+    Ok(SignedResponse {
+        response: response_from_proto(required(
+            "signed_response.response",
+            signed_response.response,
+        )?)?,
+        error_code: signed_response
+            .error_code
+            .map(|proto::ErrorCode { version, code }| ErrorCode { version, code }),
+        signer: signed_response.signer,
+        signature: signed_response.signature,
+    })
+}
+
+fn response_to_proto(response: &Response) -> Result<proto::Response> {
+    use proto::response::Kind;
+    let kind = match response {
+        Response::Cmd(resp) => Kind::Cmd(cmd_response_to_proto(resp)?),
+        Response::Query(resp) => Kind::Query(query_response_to_proto(resp)?),
+        Response::EventReceived => Kind::EventReceived(proto::EventReceived {}),
+    };
+    Ok(proto::Response { kind: Some(kind) })
+}
+
+fn response_from_proto(response: proto::Response) -> Result<Response> {
+    use proto::response::Kind;
+    Ok(match required("response", response.kind)? {
+        Kind::Cmd(resp) => Response::Cmd(cmd_response_from_proto(resp)?),
+        Kind::Query(resp) => Response::Query(query_response_from_proto(resp)?),
+        Kind::EventReceived(_) => Response::EventReceived,
+    })
+}
+
+//
+// ===== Cmds =====
+//
+
+fn cmd_to_proto(cmd: &Cmd) -> Result<proto::Cmd> {
+    use proto::cmd::Kind;
+    let kind = match cmd {
+        Cmd::StoreChunk {
+            address,
+            value,
+            payment,
+        } => Kind::StoreChunk(proto::StoreChunk {
+            address: address.name().to_vec(),
+            value: value.to_vec(),
+            payment: to_msgpack("store_chunk.payment", payment)?,
+        }),
+        Cmd::RegisterCreate {
+            address,
+            policy,
+            payment,
+        } => Kind::RegisterCreate(proto::RegisterCreate {
+            address: Some(register_address_to_proto(address)),
+            policy: to_msgpack("register_create.policy", policy)?,
+            payment: to_msgpack("register_create.payment", payment)?,
+        }),
+        Cmd::RegisterEdit(op) => Kind::RegisterEdit(to_msgpack("register_edit", op)?),
+        Cmd::RegisterSetPolicy(op) => {
+            Kind::RegisterSetPolicy(to_msgpack("register_set_policy", op)?)
+        }
+        Cmd::SyncRegister(address) => Kind::SyncRegister(register_address_to_proto(address)),
+        Cmd::SpendDbc {
+            signed_spend,
+            parent_tx,
+        } => Kind::SpendDbc(proto::SpendDbc {
+            signed_spend: to_msgpack("spend_dbc.signed_spend", signed_spend)?,
+            parent_tx: to_msgpack("spend_dbc.parent_tx", parent_tx)?,
+        }),
+        Cmd::Subscribe(topic) => Kind::Subscribe(topic_to_proto(topic)),
+        Cmd::Unsubscribe(topic) => Kind::Unsubscribe(topic_to_proto(topic)),
+        Cmd::Replicate(replica) => Kind::Replicate(replica_to_proto(replica)?),
+        Cmd::ReplicateRecords { keys } => Kind::ReplicateRecords(proto::ReplicateRecords {
+            keys: keys.iter().map(|key| key.to_vec()).collect(),
+        }),
+    };
+    Ok(proto::Cmd { kind: Some(kind) })
+}
+
+fn cmd_from_proto(cmd: proto::Cmd) -> Result<Cmd> {
+    use proto::cmd::Kind;
+    Ok(match required("cmd", cmd.kind)? {
+        Kind::StoreChunk(store) => Cmd::StoreChunk {
+            address: ChunkAddress(xor_name("store_chunk.address", &store.address)?),
+            value: Bytes::from(store.value),
+            payment: from_msgpack("store_chunk.payment", &store.payment)?,
+        },
+        Kind::RegisterCreate(create) => Cmd::RegisterCreate {
+            address: register_address_from_proto("register_create.address", create.address)?,
+            policy: from_msgpack("register_create.policy", &create.policy)?,
+            payment: from_msgpack("register_create.payment", &create.payment)?,
+        },
+        Kind::RegisterEdit(op) => Cmd::RegisterEdit(from_msgpack("register_edit", &op)?),
+        Kind::RegisterSetPolicy(op) => {
+            Cmd::RegisterSetPolicy(from_msgpack("register_set_policy", &op)?)
+        }
+        Kind::SyncRegister(address) => {
+            Cmd::SyncRegister(register_address_from_proto("sync_register", Some(address))?)
+        }
+        Kind::SpendDbc(spend) => Cmd::SpendDbc {
+            signed_spend: from_msgpack("spend_dbc.signed_spend", &spend.signed_spend)?,
+            parent_tx: from_msgpack("spend_dbc.parent_tx", &spend.parent_tx)?,
+        },
+        Kind::Subscribe(topic) => Cmd::Subscribe(topic_from_proto(topic)?),
+        Kind::Unsubscribe(topic) => Cmd::Unsubscribe(topic_from_proto(topic)?),
+        Kind::Replicate(replica) => Cmd::Replicate(replica_from_proto(replica)?),
+        Kind::ReplicateRecords(replicate) => Cmd::ReplicateRecords {
+            keys: xor_names("replicate_records.keys", &replicate.keys)?,
+        },
+    })
+}
+
+fn cmd_response_to_proto(resp: &CmdResponse) -> Result<proto::CmdResponse> {
+    use proto::cmd_response::Kind;
+    let kind = match resp {
+        CmdResponse::Ack { cmd_hash } => Kind::Ack(proto::Ack {
+            cmd_hash: cmd_hash.to_vec(),
+        }),
+        CmdResponse::StoreChunk(result) => Kind::StoreChunk(outcome_to_proto(result)?),
+        CmdResponse::InvalidChunkAddress { claimed, computed } => {
+            Kind::InvalidChunkAddress(proto::InvalidChunkAddress {
+                claimed: claimed.name().to_vec(),
+                computed: computed.name().to_vec(),
+            })
+        }
+        CmdResponse::RegisterCreate(result) => Kind::RegisterCreate(outcome_to_proto(result)?),
+        CmdResponse::RegisterEdit(result) => Kind::RegisterEdit(outcome_to_proto(result)?),
+        CmdResponse::RegisterSetPolicy(result) => {
+            Kind::RegisterSetPolicy(outcome_to_proto(result)?)
+        }
+        CmdResponse::SyncRegister(result) => Kind::SyncRegister(outcome_to_proto(result)?),
+        CmdResponse::Spend(result) => Kind::Spend(outcome_to_proto(result)?),
+        CmdResponse::Subscribe(result) => Kind::Subscribe(outcome_to_proto(result)?),
+        CmdResponse::Unsubscribe(result) => Kind::Unsubscribe(outcome_to_proto(result)?),
+        CmdResponse::Replicate(result) => Kind::Replicate(outcome_to_proto(result)?),
+        CmdResponse::ReplicateRecords(result) => Kind::ReplicateRecords(outcome_to_proto(result)?),
+    };
+    Ok(proto::CmdResponse { kind: Some(kind) })
+}
+
+fn cmd_response_from_proto(resp: proto::CmdResponse) -> Result<CmdResponse> {
+    use proto::cmd_response::Kind;
+    Ok(match required("cmd_response", resp.kind)? {
+        Kind::Ack(ack) => CmdResponse::Ack {
+            cmd_hash: xor_name("ack.cmd_hash", &ack.cmd_hash)?,
+        },
+        Kind::StoreChunk(outcome) => CmdResponse::StoreChunk(outcome_from_proto(outcome)?),
+        Kind::InvalidChunkAddress(invalid) => CmdResponse::InvalidChunkAddress {
+            claimed: ChunkAddress(xor_name("invalid_chunk_address.claimed", &invalid.claimed)?),
+            computed: ChunkAddress(xor_name(
+                "invalid_chunk_address.computed",
+                &invalid.computed,
+            )?),
+        },
+        Kind::RegisterCreate(outcome) => CmdResponse::RegisterCreate(outcome_from_proto(outcome)?),
+        Kind::RegisterEdit(outcome) => CmdResponse::RegisterEdit(outcome_from_proto(outcome)?),
+        Kind::RegisterSetPolicy(outcome) => {
+            CmdResponse::RegisterSetPolicy(outcome_from_proto(outcome)?)
+        }
+        Kind::SyncRegister(outcome) => CmdResponse::SyncRegister(outcome_from_proto(outcome)?),
+        Kind::Spend(outcome) => CmdResponse::Spend(outcome_from_proto(outcome)?),
+        Kind::Subscribe(outcome) => CmdResponse::Subscribe(outcome_from_proto(outcome)?),
+        Kind::Unsubscribe(outcome) => CmdResponse::Unsubscribe(outcome_from_proto(outcome)?),
+        Kind::Replicate(outcome) => CmdResponse::Replicate(outcome_from_proto(outcome)?),
+        Kind::ReplicateRecords(outcome) => {
+            CmdResponse::ReplicateRecords(outcome_from_proto(outcome)?)
+        }
+    })
+}
+
+//
+// ===== Queries =====
+//
+
+fn query_to_proto(query: &Query) -> proto::Query {
+    use proto::query::Kind;
+    let kind = match query {
+        Query::GetChunk(address) => Kind::GetChunk(address.name().to_vec()),
+        Query::GetChunkExists(address) => Kind::GetChunkExists(address.name().to_vec()),
+        Query::GetRegister(address) => Kind::GetRegister(register_address_to_proto(address)),
+        Query::GetRegisterDigest(address) => {
+            Kind::GetRegisterDigest(register_address_to_proto(address))
+        }
+        Query::GetRegisterEntries { address, known } => {
+            Kind::GetRegisterEntries(proto::GetRegisterEntries {
+                address: Some(register_address_to_proto(address)),
+                known: known.iter().map(|hash| hash.to_vec()).collect(),
+            })
+        }
+        Query::GetStorageLevel => Kind::GetStorageLevel(proto::GetStorageLevel {}),
+        Query::ListChunks { cursor } => Kind::ListChunks(proto::ListChunks {
+            cursor: cursor.map(|address| address.name().to_vec()),
+        }),
+        Query::GetSpend(address) => Kind::GetSpend(address.name().to_vec()),
+        Query::GetStoreCost(name) => Kind::GetStoreCost(name.to_vec()),
+        Query::GetRecord(name) => Kind::GetRecord(name.to_vec()),
+        Query::ChunkProof { key, nonce } => Kind::ChunkProof(proto::ChunkProofChallenge {
+            key: key.name().to_vec(),
+            nonce: *nonce,
+        }),
+        Query::FetchReplication { keys } => Kind::FetchReplication(proto::FetchReplication {
+            keys: keys.iter().map(|key| key.to_vec()).collect(),
+        }),
+    };
+    proto::Query { kind: Some(kind) }
+}
+
+fn query_from_proto(query: proto::Query) -> Result<Query> {
+    use proto::query::Kind;
+    Ok(match required("query", query.kind)? {
+        Kind::GetChunk(address) => Query::GetChunk(ChunkAddress(xor_name("get_chunk", &address)?)),
+        Kind::GetChunkExists(address) => {
+            Query::GetChunkExists(ChunkAddress(xor_name("get_chunk_exists", &address)?))
+        }
+        Kind::GetRegister(address) => {
+            Query::GetRegister(register_address_from_proto("get_register", Some(address))?)
+        }
+        Kind::GetRegisterDigest(address) => Query::GetRegisterDigest(register_address_from_proto(
+            "get_register_digest",
+            Some(address),
+        )?),
+        Kind::GetRegisterEntries(get) => Query::GetRegisterEntries {
+            address: register_address_from_proto("get_register_entries.address", get.address)?,
+            known: get
+                .known
+                .iter()
+                .map(|hash| entry_hash("get_register_entries.known", hash))
+                .collect::<Result<_>>()?,
+        },
+        Kind::GetStorageLevel(_) => Query::GetStorageLevel,
+        Kind::ListChunks(list) => Query::ListChunks {
+            cursor: list
+                .cursor
+                .map(|cursor| xor_name("list_chunks.cursor", &cursor).map(ChunkAddress))
+                .transpose()?,
+        },
+        Kind::GetSpend(address) => Query::GetSpend(DbcAddress(xor_name("get_spend", &address)?)),
+        Kind::GetStoreCost(name) => Query::GetStoreCost(xor_name("get_store_cost", &name)?),
+        Kind::GetRecord(name) => Query::GetRecord(xor_name("get_record", &name)?),
+        Kind::ChunkProof(challenge) => Query::ChunkProof {
+            key: ChunkAddress(xor_name("chunk_proof.key", &challenge.key)?),
+            nonce: challenge.nonce,
+        },
+        Kind::FetchReplication(fetch) => Query::FetchReplication {
+            keys: xor_names("fetch_replication.keys", &fetch.keys)?,
+        },
+    })
+}
+
+fn query_response_to_proto(resp: &QueryResponse) -> Result<proto::QueryResponse> {
+    use proto::query_response::Kind;
+    let kind = match resp {
+        QueryResponse::GetChunk(result) => {
+            Kind::GetChunk(bytes_result_to_proto(result, |chunk| {
+                Ok(chunk.value().to_vec())
+            })?)
+        }
+        QueryResponse::GetChunkExists(exists) => Kind::GetChunkExists(*exists),
+        QueryResponse::GetRegister(result) => {
+            Kind::GetRegister(bytes_result_to_proto(result, |register| {
+                to_msgpack("get_register", register)
+            })?)
+        }
+        QueryResponse::GetRegisterDigest(result) => {
+            use proto::register_digest_result::Result as DigestResult;
+            let result = match result {
+                Ok(digest) => DigestResult::Ok(proto::RegisterDigest {
+                    heads: digest.heads.iter().map(|hash| hash.to_vec()).collect(),
+                    size: digest.size,
+                    policy_version: digest.policy_version,
+                }),
+                Err(err) => DigestResult::Error(error_to_proto(err)?),
+            };
+            Kind::GetRegisterDigest(proto::RegisterDigestResult {
+                result: Some(result),
+            })
+        }
+        QueryResponse::GetRegisterEntries(result) => {
+            Kind::GetRegisterEntries(bytes_result_to_proto(result, |entries| {
+                to_msgpack("get_register_entries", entries)
+            })?)
+        }
+        QueryResponse::GetStorageLevel(level) => Kind::GetStorageLevel(proto::StorageLevel {
+            used: level.used,
+            total: level.total,
+        }),
+        QueryResponse::ListChunks(page) => Kind::ListChunks(proto::ChunkPage {
+            items: page
+                .items
+                .iter()
+                .map(|address| address.name().to_vec())
+                .collect(),
+            next: page.next.map(|address| address.name().to_vec()),
+        }),
+        QueryResponse::GetStoreCost(cost) => Kind::GetStoreCost(proto::StoreCostOption {
+            cost: cost
+                .as_ref()
+                .map(|cost| -> Result<_> {
+                    Ok(proto::StoreCost {
+                        address: to_msgpack("get_store_cost.address", &cost.address)?,
+                        cost_nanos: cost.cost_nanos,
+                    })
+                })
+                .transpose()?,
+        }),
+        QueryResponse::GetSpend(result) => {
+            Kind::GetSpend(bytes_result_to_proto(result, |signed_spend| {
+                to_msgpack("get_spend", signed_spend)
+            })?)
+        }
+        QueryResponse::GetRecord(result) => {
+            use proto::replica_result::Result as ReplicaResult;
+            let result = match result {
+                Ok(replica) => ReplicaResult::Ok(replica_to_proto(replica)?),
+                Err(err) => ReplicaResult::Error(error_to_proto(err)?),
+            };
+            Kind::GetRecord(proto::ReplicaResult {
+                result: Some(result),
+            })
+        }
+        QueryResponse::ChunkProof(result) => {
+            Kind::ChunkProof(bytes_result_to_proto(result, |proof| Ok(proof.0.to_vec()))?)
+        }
+        QueryResponse::FetchReplication(replicas) => Kind::FetchReplication(proto::Replicas {
+            replicas: replicas
+                .iter()
+                .map(replica_to_proto)
+                .collect::<Result<_>>()?,
+        }),
+    };
+    Ok(proto::QueryResponse { kind: Some(kind) })
+}
+
+fn query_response_from_proto(resp: proto::QueryResponse) -> Result<QueryResponse> {
+    use proto::query_response::Kind;
+    Ok(match required("query_response", resp.kind)? {
+        Kind::GetChunk(result) => {
+            QueryResponse::GetChunk(bytes_result_from_proto("get_chunk", result, |value| {
+                Ok(Chunk::new(Bytes::from(value)))
+            })?)
+        }
+        Kind::GetChunkExists(exists) => QueryResponse::GetChunkExists(exists),
+        Kind::GetRegister(result) => {
+            QueryResponse::GetRegister(bytes_result_from_proto("get_register", result, |bytes| {
+                from_msgpack("get_register", &bytes)
+            })?)
+        }
+        Kind::GetRegisterDigest(result) => {
+            use proto::register_digest_result::Result as DigestResult;
+            let result = match required("get_register_digest", result.result)? {
+                DigestResult::Ok(digest) => Ok(RegisterDigest {
+                    heads: digest
+                        .heads
+                        .iter()
+                        .map(|hash| entry_hash("get_register_digest.heads", hash))
+                        .collect::<Result<_>>()?,
+                    size: digest.size,
+                    policy_version: digest.policy_version,
+                }),
+                DigestResult::Error(err) => Err(error_from_proto(err)?),
+            };
+            QueryResponse::GetRegisterDigest(result)
+        }
+        Kind::GetRegisterEntries(result) => QueryResponse::GetRegisterEntries(
+            bytes_result_from_proto("get_register_entries", result, |bytes| {
+                from_msgpack("get_register_entries", &bytes)
+            })?,
+        ),
+        Kind::GetStorageLevel(level) => QueryResponse::GetStorageLevel(StorageLevel {
+            used: level.used,
+            total: level.total,
+        }),
+        Kind::ListChunks(page) => QueryResponse::ListChunks(Page {
+            items: xor_names("list_chunks.items", &page.items)?
+                .into_iter()
+                .map(ChunkAddress)
+                .collect(),
+            next: page
+                .next
+                .map(|next| xor_name("list_chunks.next", &next).map(ChunkAddress))
+                .transpose()?,
+        }),
+        Kind::GetStoreCost(cost) => QueryResponse::GetStoreCost(
+            cost.cost
+                .map(|cost| -> Result<_> {
+                    Ok(StoreCost {
+                        address: from_msgpack("get_store_cost.address", &cost.address)?,
+                        cost_nanos: cost.cost_nanos,
+                    })
+                })
+                .transpose()?,
+        ),
+        Kind::GetSpend(result) => {
+            QueryResponse::GetSpend(bytes_result_from_proto("get_spend", result, |bytes| {
+                from_msgpack("get_spend", &bytes)
+            })?)
+        }
+        Kind::GetRecord(result) => {
+            use proto::replica_result::Result as ReplicaResult;
+            let result = match required("get_record", result.result)? {
+                ReplicaResult::Ok(replica) => Ok(replica_from_proto(replica)?),
+                ReplicaResult::Error(err) => Err(error_from_proto(err)?),
+            };
+            QueryResponse::GetRecord(result)
+        }
+        Kind::ChunkProof(result) => {
+            QueryResponse::ChunkProof(bytes_result_from_proto("chunk_proof", result, |proof| {
+                Ok(ChunkProof(xor_name("chunk_proof", &proof)?))
+            })?)
+        }
+        Kind::FetchReplication(replicas) => QueryResponse::FetchReplication(
+            replicas
+                .replicas
+                .into_iter()
+                .map(replica_from_proto)
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+//
+// ===== Events =====
+//
+
+fn event_to_proto(event: &Event) -> Result<proto::Event> {
+    use proto::event::Kind;
+    let kind = match event {
+        Event::RegisterEdited(op) => Kind::RegisterEdited(to_msgpack("register_edited", op)?),
+        Event::SpendRecorded(signed_spend) => {
+            Kind::SpendRecorded(to_msgpack("spend_recorded", signed_spend)?)
+        }
+    };
+    Ok(proto::Event { kind: Some(kind) })
+}
+
+fn event_from_proto(event: proto::Event) -> Result<Event> {
+    use proto::event::Kind;
+    Ok(match required("event", event.kind)? {
+        Kind::RegisterEdited(op) => Event::RegisterEdited(from_msgpack("register_edited", &op)?),
+        Kind::SpendRecorded(signed_spend) => {
+            Event::SpendRecorded(from_msgpack("spend_recorded", &signed_spend)?)
+        }
+    })
+}
+
+//
+// ===== Common types =====
+//
+
+fn register_address_to_proto(address: &RegisterAddress) -> proto::RegisterAddress {
+    proto::RegisterAddress {
+        name: address.name().to_vec(),
+        tag: address.tag(),
+    }
+}
+
+fn register_address_from_proto(
+    field: &'static str,
+    address: Option<proto::RegisterAddress>,
+) -> Result<RegisterAddress> {
+    let address = required(field, address)?;
+    Ok(RegisterAddress::new(
+        xor_name(field, &address.name)?,
+        address.tag,
+    ))
+}
+
+fn topic_to_proto(topic: &Topic) -> proto::Topic {
+    use proto::topic::Kind;
+    let kind = match topic {
+        Topic::Register(address) => Kind::Register(register_address_to_proto(address)),
+        Topic::Spend(address) => Kind::Spend(address.name().to_vec()),
+    };
+    proto::Topic { kind: Some(kind) }
+}
+
+fn topic_from_proto(topic: proto::Topic) -> Result<Topic> {
+    use proto::topic::Kind;
+    Ok(match required("topic", topic.kind)? {
+        Kind::Register(address) => Topic::Register(register_address_from_proto(
+            "topic.register",
+            Some(address),
+        )?),
+        Kind::Spend(address) => Topic::Spend(DbcAddress(xor_name("topic.spend", &address)?)),
+    })
+}
+
+fn replica_to_proto(replica: &Replica) -> Result<proto::Replica> {
+    use proto::replica::Kind;
+    let kind = match replica {
+        Replica::Chunk(value) => Kind::Chunk(value.to_vec()),
+        Replica::Register(register) => Kind::Register(to_msgpack("replica.register", register)?),
+        Replica::Spends(spends) => Kind::Spends(proto::Spends {
+            spends: spends
+                .iter()
+                .map(|signed_spend| to_msgpack("replica.spends", signed_spend))
+                .collect::<Result<_>>()?,
+        }),
+    };
+    Ok(proto::Replica { kind: Some(kind) })
+}
+
+fn replica_from_proto(replica: proto::Replica) -> Result<Replica> {
+    use proto::replica::Kind;
+    Ok(match required("replica", replica.kind)? {
+        Kind::Chunk(value) => Replica::Chunk(Bytes::from(value)),
+        Kind::Register(register) => Replica::Register(from_msgpack("replica.register", &register)?),
+        Kind::Spends(spends) => Replica::Spends(
+            spends
+                .spends
+                .iter()
+                .map(|signed_spend| from_msgpack("replica.spends", signed_spend))
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+fn error_to_proto(err: &ProtocolError) -> Result<proto::Error> {
+    Ok(proto::Error {
+        code: err.code(),
+        message: err.to_string(),
+        detail: to_msgpack("error.detail", err)?,
+    })
+}
+
+fn error_from_proto(err: proto::Error) -> Result<ProtocolError> {
+    if err.detail.is_empty() {
+        return Err(Error::MissingField("error.detail"));
+    }
+    from_msgpack("error.detail", &err.detail)
+}
+
+fn outcome_to_proto(result: &super::error::Result<()>) -> Result<proto::Outcome> {
+    Ok(proto::Outcome {
+        error: match result {
+            Ok(()) => None,
+            Err(err) => Some(error_to_proto(err)?),
+        },
+    })
+}
+
+fn outcome_from_proto(outcome: proto::Outcome) -> Result<super::error::Result<()>> {
+    Ok(match outcome.error {
+        None => Ok(()),
+        Some(err) => Err(error_from_proto(err)?),
+    })
+}
+
+fn bytes_result_to_proto<T>(
+    result: &super::error::Result<T>,
+    to_bytes: impl FnOnce(&T) -> Result<Vec<u8>>,
+) -> Result<proto::BytesResult> {
+    use proto::bytes_result::Result as BytesResult;
+    let result = match result {
+        Ok(value) => BytesResult::Ok(to_bytes(value)?),
+        Err(err) => BytesResult::Error(error_to_proto(err)?),
+    };
+    Ok(proto::BytesResult {
+        result: Some(result),
+    })
+}
+
+fn bytes_result_from_proto<T>(
+    field: &'static str,
+    result: proto::BytesResult,
+    from_bytes: impl FnOnce(Vec<u8>) -> Result<T>,
+) -> Result<super::error::Result<T>> {
+    use proto::bytes_result::Result as BytesResult;
+    Ok(match required(field, result.result)? {
+        BytesResult::Ok(bytes) => Ok(from_bytes(bytes)?),
+        BytesResult::Error(err) => Err(error_from_proto(err)?),
+    })
+}
+
+fn required<T>(field: &'static str, value: Option<T>) -> Result<T> {
+    value.ok_or(Error::MissingField(field))
+}
+
+fn xor_name(field: &'static str, bytes: &[u8]) -> Result<XorName> {
+    let bytes: [u8; xor_name::XOR_NAME_LEN] =
+        bytes.try_into().map_err(|_| Error::InvalidField {
+            field,
+            reason: format!(
+                "expected {} bytes, got {}",
+                xor_name::XOR_NAME_LEN,
+                bytes.len()
+            ),
+        })?;
+    Ok(XorName(bytes))
+}
+
+fn xor_names(field: &'static str, names: &[Vec<u8>]) -> Result<Vec<XorName>> {
+    names.iter().map(|name| xor_name(field, name)).collect()
+}
+
+fn entry_hash(field: &'static str, bytes: &[u8]) -> Result<EntryHash> {
+    Ok(xor_name(field, bytes)?.0)
+}
+
+fn to_msgpack<T: Serialize>(field: &'static str, value: &T) -> Result<Vec<u8>> {
+    rmp_serde::to_vec(value).map_err(|err| Error::Serialisation {
+        field,
+        reason: err.to_string(),
+    })
+}
+
+fn from_msgpack<T: DeserializeOwned>(field: &'static str, bytes: &[u8]) -> Result<T> {
+    rmp_serde::from_slice(bytes).map_err(|err| Error::InvalidField {
+        field,
+        reason: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::types::{
+        payment::PaymentProof,
+        register::{Policy, Register},
+    };
+    use eyre::Result;
+    use libp2p::{identity::Keypair, PeerId};
+
+    fn register(keypair: &Keypair) -> Result<(Register, Cmd)> {
+        let address = RegisterAddress::new(XorName::from_content(b"register"), 1);
+        let policy = Policy::new(&keypair.public()).sign(address, keypair)?;
+        let mut register = Register::new(address, policy)?;
+        let (_, op) = register.write(b"entry".to_vec(), keypair)?;
+        Ok((register, Cmd::RegisterEdit(op)))
+    }
+
+    fn requests(keypair: &Keypair) -> Result<Vec<Request>> {
+        let value = Bytes::from_static(b"chunk");
+        let chunk = Chunk::new(value.clone());
+        let (register, edit) = register(keypair)?;
+        let cmds = vec![
+            Cmd::StoreChunk {
+                address: *chunk.address(),
+                value: value.clone(),
+                payment: PaymentProof::new(vec![]),
+            },
+            Cmd::RegisterCreate {
+                address: *register.address(),
+                policy: register.signed_policy().clone(),
+                payment: PaymentProof::new(vec![]),
+            },
+            edit,
+            Cmd::Subscribe(Topic::Register(*register.address())),
+            Cmd::Replicate(Replica::Chunk(value)),
+            Cmd::ReplicateRecords {
+                keys: vec![*chunk.name(), register.address().id()],
+            },
+        ];
+        let queries = vec![
+            Query::GetChunk(*chunk.address()),
+            Query::GetRegisterEntries {
+                address: *register.address(),
+                known: register.read().into_iter().map(|(hash, _)| hash).collect(),
+            },
+            Query::GetStorageLevel,
+            Query::ListChunks { cursor: None },
+            Query::ListChunks {
+                cursor: Some(*chunk.address()),
+            },
+            Query::ChunkProof {
+                key: *chunk.address(),
+                nonce: 7,
+            },
+        ];
+        let mut requests = cmds
+            .into_iter()
+            .map(|cmd| Ok(Request::Cmd(SignedCmd::new(cmd, keypair)?)))
+            .collect::<Result<Vec<_>>>()?;
+        requests.extend(queries.into_iter().map(Request::Query));
+        Ok(requests)
+    }
+
+    fn responses(keypair: &Keypair) -> Result<Vec<SignedResponse>> {
+        let chunk = Chunk::new(Bytes::from_static(b"chunk"));
+        let (register, _) = register(keypair)?;
+        let responses = vec![
+            Response::Cmd(CmdResponse::Ack {
+                cmd_hash: XorName::from_content(b"cmd"),
+            }),
+            Response::Cmd(CmdResponse::RegisterEdit(Err(
+                ProtocolError::RegisterWriteNotPermitted(*register.address()),
+            ))),
+            Response::Query(QueryResponse::GetChunk(Ok(chunk.clone()))),
+            Response::Query(QueryResponse::GetChunk(Err(ProtocolError::ChunkNotFound(
+                *chunk.address(),
+            )))),
+            Response::Query(QueryResponse::GetRegisterDigest(Ok(register.digest()))),
+            Response::Query(QueryResponse::ListChunks(Page {
+                items: vec![*chunk.address()],
+                next: Some(*chunk.address()),
+            })),
+            Response::Query(QueryResponse::GetStoreCost(None)),
+            Response::Query(QueryResponse::ChunkProof(Ok(
+                chunk.proof(&PeerId::random(), 7)
+            ))),
+            Response::Query(QueryResponse::FetchReplication(vec![
+                Replica::Chunk(chunk.value().clone()),
+                Replica::Register(Box::new(register)),
+            ])),
+            Response::EventReceived,
+        ];
+        Ok(responses
+            .into_iter()
+            .map(|response| SignedResponse::new(response, keypair))
+            .collect::<std::result::Result<Vec<_>, _>>()?)
+    }
+
+    #[test]
+    fn requests_are_decoded_as_encoded() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        for request in requests(&keypair)? {
+            let decoded = decode_request(&encode_request(&request)?)?;
+            assert_eq!(decoded, request);
+            // The existing encoding, which the signatures are made over, is left unchanged.
+            assert_eq!(rmp_serde::to_vec(&decoded)?, rmp_serde::to_vec(&request)?);
+            if let Request::Cmd(signed_cmd) = decoded {
+                let _ = signed_cmd.verify()?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn responses_are_decoded_as_encoded() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        for response in responses(&keypair)? {
+            let decoded = decode_response(&encode_response(&response)?)?;
+            assert_eq!(decoded, response);
+            assert_eq!(rmp_serde::to_vec(&decoded)?, rmp_serde::to_vec(&response)?);
+            let _ = decoded.verify()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn error_codes_are_readable_without_the_details() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let err = ProtocolError::ChunkNotFound(ChunkAddress(XorName::from_content(b"chunk")));
+        let response = Response::Query(QueryResponse::GetChunk(Err(err.clone())));
+        let bytes = encode_response(&SignedResponse::new(response, &keypair)?)?;
+
+        let decoded = proto::SignedResponse::decode(bytes.as_slice())?;
+        assert_eq!(decoded.error_code.map(|code| code.code), Some(err.code()));
+        let query = match decoded.response.and_then(|response| response.kind) {
+            Some(proto::response::Kind::Query(query)) => query,
+            other => panic!("Unexpected response {other:?}"),
+        };
+        match query.kind {
+            Some(proto::query_response::Kind::GetChunk(proto::BytesResult {
+                result: Some(proto::bytes_result::Result::Error(error)),
+            })) => assert_eq!(error.code, err.code()),
+            other => panic!("Unexpected query response {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_messages_are_rejected() -> Result<()> {
+        assert!(matches!(
+            decode_request(&[]),
+            Err(Error::MissingField("request"))
+        ));
+
+        let truncated = proto::Request {
+            kind: Some(proto::request::Kind::Query(proto::Query {
+                kind: Some(proto::query::Kind::GetChunk(vec![0; 31])),
+            })),
+        };
+        assert!(matches!(
+            decode_request(&truncated.encode_to_vec()),
+            Err(Error::InvalidField {
+                field: "get_chunk",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+}
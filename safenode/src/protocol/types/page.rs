@@ -0,0 +1,45 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use serde::{Deserialize, Serialize};
+
+/// The max number of items returned in a single page.
+pub const PAGE_SIZE: usize = 500;
+
+/// A page of a result set too large to be returned in a single response.
+///
+/// Items are returned in ascending order, and the last item of a page is the cursor
+/// from which the next page is requested.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Page<T> {
+    /// The items of this page.
+    pub items: Vec<T>,
+    /// The cursor to request the next page with, `None` if this is the last page.
+    pub next: Option<T>,
+}
+
+impl<T: Ord + Clone> Page<T> {
+    /// Returns the page of up to [`PAGE_SIZE`] items following the `cursor`,
+    /// or the first page if there is no cursor.
+    pub fn new(items: impl IntoIterator<Item = T>, cursor: Option<&T>) -> Self {
+        let mut items: Vec<T> = items
+            .into_iter()
+            .filter(|item| cursor.is_none_or(|cursor| item > cursor))
+            .collect();
+        items.sort();
+
+        let next = if items.len() > PAGE_SIZE {
+            items.truncate(PAGE_SIZE);
+            items.last().cloned()
+        } else {
+            None
+        };
+
+        Self { items, next }
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::protocol::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sn_dbc::{DbcIdSource, Hash, PedersenGens, PublicAddress, RevealedAmount, SignedSpend};
+use std::collections::{BTreeMap, BTreeSet};
+use xor_name::XorName;
+
+/// The number of basis points in a whole, royalties being set in basis points of the payment.
+pub const BASIS_POINTS: u64 = 10_000;
+
+/// Proof that the storage of a piece of data has been paid for.
+///
+/// Holds the spends of the Dbcs used for the payment. Each spend commits to the data
+/// being paid for through its `reason`, and must be recorded in the spentbook for the
+/// proof to be accepted.
+///
+/// The outputs paying for the storage and the network royalties have their recipient and
+/// amount revealed, for nodes to check the royalties are paid in full.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub struct PaymentProof {
+    /// The spends of the Dbcs used to pay for the storage.
+    pub spends: Vec<SignedSpend>,
+    /// The outputs paying for the storage.
+    #[serde(default)]
+    pub payments: Vec<RevealedOutput>,
+    /// The outputs paying the network royalties.
+    #[serde(default)]
+    pub royalties: Vec<RevealedOutput>,
+}
+
+/// An output of the transaction of a payment, with its recipient and amount revealed.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RevealedOutput {
+    /// The address the output is sent to, along with the index its id is derived at.
+    pub dbc_id_src: DbcIdSource,
+    /// The amount of the output, with the blinding factor opening its commitment.
+    pub revealed_amount: RevealedAmount,
+}
+
+// Neither `DbcIdSource` nor `RevealedAmount` are comparable, so their parts are compared.
+impl PartialEq for RevealedOutput {
+    fn eq(&self, other: &Self) -> bool {
+        self.dbc_id_src.public_address == other.dbc_id_src.public_address
+            && self.dbc_id_src.derivation_index == other.dbc_id_src.derivation_index
+            && self.revealed_amount.to_bytes() == other.revealed_amount.to_bytes()
+    }
+}
+
+impl Eq for RevealedOutput {}
+
+/// The share of every storage payment due to the network, and the address it is to be paid to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoyaltyPolicy {
+    /// The address the royalties are paid to.
+    pub address: PublicAddress,
+    /// The share of the storage payment due, in basis points, i.e. hundredths of a percent.
+    pub basis_points: u64,
+}
+
+impl RoyaltyPolicy {
+    /// Returns the royalty due for the payment of the given amount, rounded up.
+    pub fn due_for(&self, paid_nanos: u64) -> u64 {
+        let due = u128::from(paid_nanos) * u128::from(self.basis_points);
+        let whole = u128::from(BASIS_POINTS);
+        due.div_ceil(whole) as u64
+    }
+}
+
+/// The cost a node asks for the storage of a record, and the address it is to be paid to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreCost {
+    /// The address the node is paid at.
+    pub address: PublicAddress,
+    /// The amount due to the node for the storage of a record, in nanos.
+    pub cost_nanos: u64,
+}
+
+impl PaymentProof {
+    /// Creates a proof out of the spends paying for the storage.
+    pub fn new(spends: Vec<SignedSpend>) -> Self {
+        Self {
+            spends,
+            payments: vec![],
+            royalties: vec![],
+        }
+    }
+
+    /// Returns the proof with the outputs paying for the storage and the royalties revealed.
+    pub fn with_outputs(
+        mut self,
+        payments: Vec<RevealedOutput>,
+        royalties: Vec<RevealedOutput>,
+    ) -> Self {
+        self.payments = payments;
+        self.royalties = royalties;
+        self
+    }
+
+    /// Checks that the royalties paid for the storage of the data at `name` are those due
+    /// as per the policy: every revealed output must be an output of the transaction the
+    /// spends are for, revealed only once, the royalties must be paid to the address of the
+    /// policy, and sum up to at least the share of the storage payment it sets.
+    pub fn verify_royalties(&self, name: &XorName, policy: &RoyaltyPolicy) -> Result<()> {
+        let invalid = |details: String| Error::InvalidRoyaltyPayment(*name, details);
+
+        let mut revealed = BTreeSet::new();
+        for output in self.payments.iter().chain(&self.royalties) {
+            let dbc_id = output.dbc_id_src.dbc_id();
+            if !revealed.insert(dbc_id.to_bytes()) {
+                return Err(invalid(format!(
+                    "revealed output {dbc_id:?} is listed more than once"
+                )));
+            }
+        }
+        for output in self.payments.iter().chain(&self.royalties) {
+            if !self.is_paid_output(output) {
+                return Err(invalid(format!(
+                    "revealed output {:?} is not an output of the payment",
+                    output.dbc_id_src.dbc_id()
+                )));
+            }
+        }
+        if let Some(output) = self
+            .royalties
+            .iter()
+            .find(|output| output.dbc_id_src.public_address != policy.address)
+        {
+            return Err(invalid(format!(
+                "royalty output {:?} is not paid to the royalties address",
+                output.dbc_id_src.dbc_id()
+            )));
+        }
+
+        let paid = sum_of(&self.payments);
+        if paid == 0 {
+            return Err(invalid(
+                "no output paying for the storage is revealed".to_string(),
+            ));
+        }
+        let royalties = sum_of(&self.royalties);
+        let due = policy.due_for(paid);
+        if royalties < due {
+            return Err(Error::RoyaltyUnderpaid {
+                name: *name,
+                paid: royalties,
+                due,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the amount paid for the storage, in nanos, as revealed by the outputs paying
+    /// for it. Outputs not of the transaction the spends are for are not counted, and outputs
+    /// listed more than once are counted once.
+    pub fn paid_amount(&self) -> u64 {
+        sum_of(
+            self.payments
+                .iter()
+                .filter(|output| self.is_paid_output(output)),
+        )
+    }
+
+    /// Returns the amount paid to `address` for the storage, in nanos, as revealed by the
+    /// outputs paying for it, each counted once.
+    pub fn paid_to(&self, address: &PublicAddress) -> u64 {
+        sum_of(
+            self.payments
+                .iter()
+                .filter(|output| &output.dbc_id_src.public_address == address)
+                .filter(|output| self.is_paid_output(output)),
+        )
+    }
+
+    /// Checks that the storage of the data at `name` pays the node at least the cost it asks,
+    /// at its address.
+    pub fn verify_store_cost(&self, name: &XorName, cost: &StoreCost) -> Result<()> {
+        let paid = self.paid_to(&cost.address);
+        if paid < cost.cost_nanos {
+            return Err(Error::StoreCostUnpaid {
+                name: *name,
+                paid,
+                due: cost.cost_nanos,
+            });
+        }
+        Ok(())
+    }
+
+    // Whether the output is one of the transaction the spends are for, with the revealed
+    // amount opening the commitment to its amount.
+    fn is_paid_output(&self, output: &RevealedOutput) -> bool {
+        let dbc_id = output.dbc_id_src.dbc_id();
+        let blinded_amount = output
+            .revealed_amount
+            .blinded_amount(&PedersenGens::default());
+        self.spends.iter().any(|signed_spend| {
+            signed_spend.spend.spent_tx.outputs.iter().any(|tx_output| {
+                tx_output.dbc_id() == &dbc_id && tx_output.blinded_amount() == blinded_amount
+            })
+        })
+    }
+
+    /// Returns the `reason` the paying spends must carry for the storage of data at `name`.
+    pub fn reason_for(name: &XorName) -> Hash {
+        Hash::hash(&name.0)
+    }
+}
+
+// Sums up the amounts of the outputs, an output listed more than once being counted once.
+fn sum_of<'a>(outputs: impl IntoIterator<Item = &'a RevealedOutput>) -> u64 {
+    outputs
+        .into_iter()
+        .map(|output| (output.dbc_id_src.dbc_id().to_bytes(), output))
+        .collect::<BTreeMap<_, _>>()
+        .values()
+        .map(|output| output.revealed_amount.value())
+        .fold(0, u64::saturating_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_dbc::{rng, MainKey};
+
+    fn revealed_output(address: PublicAddress, amount: u64) -> RevealedOutput {
+        let mut rng = rng::thread_rng();
+        RevealedOutput {
+            dbc_id_src: address.random_dbc_id_src(&mut rng),
+            revealed_amount: RevealedAmount::from_amount(amount, &mut rng),
+        }
+    }
+
+    #[test]
+    fn duplicated_outputs_are_counted_once() {
+        let address = MainKey::random().public_address();
+        let output = revealed_output(address, 100);
+        let other = revealed_output(address, 10);
+
+        let outputs = vec![output.clone(), output.clone(), other];
+        assert_eq!(sum_of(&outputs), 110);
+    }
+
+    #[test]
+    fn duplicated_royalty_outputs_are_rejected() {
+        let address = MainKey::random().public_address();
+        let policy = RoyaltyPolicy {
+            address,
+            basis_points: 1_500,
+        };
+        let royalty = revealed_output(address, 15);
+        let payment = revealed_output(MainKey::random().public_address(), 100);
+        let proof =
+            PaymentProof::new(vec![]).with_outputs(vec![payment], vec![royalty.clone(), royalty]);
+
+        let result = proof.verify_royalties(&XorName::default(), &policy);
+        assert!(
+            matches!(&result, Err(Error::InvalidRoyaltyPayment(_, details)) if details.contains("more than once")),
+            "unexpected result: {result:?}"
+        );
+    }
+
+    #[test]
+    fn an_output_revealed_both_as_payment_and_royalty_is_rejected() {
+        let address = MainKey::random().public_address();
+        let policy = RoyaltyPolicy {
+            address,
+            basis_points: 1_500,
+        };
+        let output = revealed_output(address, 100);
+        let proof = PaymentProof::new(vec![]).with_outputs(vec![output.clone()], vec![output]);
+
+        let result = proof.verify_royalties(&XorName::default(), &policy);
+        assert!(
+            matches!(&result, Err(Error::InvalidRoyaltyPayment(_, details)) if details.contains("more than once")),
+            "unexpected result: {result:?}"
+        );
+    }
+}
@@ -0,0 +1,784 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::protocol::error::{Error, Result};
+use crdts::{
+    merkle_reg::{Hash, MerkleReg, Node},
+    CmRDT,
+};
+use libp2p::identity::{Keypair, PublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::debug;
+use xor_name::XorName;
+
+/// Arbitrary data stored in a `Register`.
+pub type Entry = Vec<u8>;
+
+/// Hash of a `Register` entry, used to reference it as the parent of subsequent writes.
+pub type EntryHash = Hash;
+
+/// The max size of an entry, in bytes.
+pub const MAX_REGISTER_ENTRY_SIZE: usize = 1024;
+
+/// The max number of entries ever written to a `Register`, for a whole replica of it to fit
+/// in a single msg.
+pub const MAX_REGISTER_ENTRIES: u64 = 1024;
+
+/// Address of a `Register`, made up of a name and a type tag.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Debug)]
+pub struct RegisterAddress {
+    name: XorName,
+    tag: u64,
+}
+
+impl RegisterAddress {
+    /// Creates a new `RegisterAddress`.
+    pub fn new(name: XorName, tag: u64) -> Self {
+        Self { name, tag }
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> &XorName {
+        &self.name
+    }
+
+    /// Returns the tag.
+    pub fn tag(&self) -> u64 {
+        self.tag
+    }
+
+    /// Returns the `XorName` the register is located at on the network,
+    /// derived from both its name and tag.
+    pub fn id(&self) -> XorName {
+        XorName::from_content_parts(&[&self.name.0, &self.tag.to_be_bytes()])
+    }
+}
+
+/// The public keys allowed to write to a `Register`, set by its owner.
+///
+/// Keys are held in their protobuf encoding. Every change to the policy bumps its version,
+/// replicas keeping the policy of the highest version they have seen.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Policy {
+    owner: Vec<u8>,
+    writers: BTreeSet<Vec<u8>>,
+    version: u64,
+}
+
+impl Policy {
+    /// Creates a policy allowing only the owner to write.
+    pub fn new(owner: &PublicKey) -> Self {
+        Self {
+            owner: owner.encode_protobuf(),
+            writers: BTreeSet::new(),
+            version: 0,
+        }
+    }
+
+    /// Returns the policy with the given keys allowed to write besides the owner.
+    pub fn with_writers<'a>(mut self, writers: impl IntoIterator<Item = &'a PublicKey>) -> Self {
+        self.writers = writers
+            .into_iter()
+            .map(|key| key.encode_protobuf())
+            .collect();
+        self
+    }
+
+    /// Returns the public key of the owner, the only one allowed to change the policy.
+    pub fn owner(&self) -> Result<PublicKey> {
+        decode_key(&self.owner)
+    }
+
+    /// Returns the public keys allowed to write besides the owner.
+    pub fn writers(&self) -> Result<Vec<PublicKey>> {
+        self.writers.iter().map(|key| decode_key(key)).collect()
+    }
+
+    /// Returns the version of the policy.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Whether the key is allowed to write entries.
+    pub fn is_writer(&self, key: &PublicKey) -> bool {
+        let key = key.encode_protobuf();
+        key == self.owner || self.writers.contains(&key)
+    }
+
+    /// Returns the next version of the policy, with the given keys allowed to write besides
+    /// the owner, signed by the owner.
+    pub fn next<'a>(
+        &self,
+        address: RegisterAddress,
+        writers: impl IntoIterator<Item = &'a PublicKey>,
+        owner: &Keypair,
+    ) -> Result<PolicyOp> {
+        let mut policy = self.clone().with_writers(writers);
+        policy.version += 1;
+        policy.sign(address, owner)
+    }
+
+    /// Signs the policy for the register at the given address, as its owner.
+    pub fn sign(self, address: RegisterAddress, owner: &Keypair) -> Result<PolicyOp> {
+        let signature = owner
+            .sign(&serialise(&(address, &self))?)
+            .map_err(|err| Error::InvalidRegisterOp(format!("failed to sign policy: {err}")))?;
+        Ok(PolicyOp {
+            address,
+            policy: self,
+            signature,
+        })
+    }
+}
+
+/// A change of the `Policy` of a `Register`, signed by its owner.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct PolicyOp {
+    /// Address of the register the policy is for.
+    pub address: RegisterAddress,
+    /// The new policy.
+    pub policy: Policy,
+    /// The signature of the owner over the address and policy.
+    signature: Vec<u8>,
+}
+
+impl PolicyOp {
+    // Checks that the policy is signed by its owner.
+    fn verify(&self) -> Result<()> {
+        let owner = self.policy.owner()?;
+        let bytes = serialise(&(self.address, &self.policy))?;
+        if !owner.verify(&bytes, &self.signature) {
+            return Err(Error::InvalidRegisterOp(format!(
+                "policy of {:?} is not signed by its owner",
+                self.address
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// An operation on a `Register`, to be applied on every replica of it.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RegisterOp {
+    /// Address of the register the op is to be applied to.
+    pub address: RegisterAddress,
+    /// The CRDT operation.
+    pub crdt_op: Node<Entry>,
+    /// The version of the policy the writer was allowed to write by.
+    policy_version: u64,
+    /// The protobuf encoding of the public key of the writer.
+    signer: Vec<u8>,
+    /// The signature of the writer over the address, policy version and CRDT operation.
+    signature: Vec<u8>,
+}
+
+impl RegisterOp {
+    /// Returns the public key of the writer of the op.
+    pub fn signer(&self) -> Result<PublicKey> {
+        decode_key(&self.signer)
+    }
+
+    /// Returns the version of the policy the op was signed under.
+    pub fn policy_version(&self) -> u64 {
+        self.policy_version
+    }
+
+    // Checks that the op is signed by the included signer, returning the signer.
+    fn verify(&self) -> Result<PublicKey> {
+        let signer = self.signer()?;
+        let bytes = serialise(&(self.address, self.policy_version, &self.crdt_op))?;
+        if !signer.verify(&bytes, &self.signature) {
+            return Err(Error::InvalidRegisterOp(format!(
+                "signature does not match the op of {:?}",
+                signer.to_peer_id()
+            )));
+        }
+        Ok(signer)
+    }
+}
+
+/// A summary of the state of a replica of a `Register`, equal on the replicas holding the
+/// same entries and policy, for replicas to tell whether they diverged.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RegisterDigest {
+    /// The hashes of the current entries, from which all the others descend.
+    pub heads: BTreeSet<EntryHash>,
+    /// The number of entries ever written to the replica.
+    pub size: u64,
+    /// The version of the policy of the replica.
+    pub policy_version: u64,
+}
+
+/// The entries of a replica of a `Register` missing from another one, along with its policies,
+/// for the other one to merge them.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RegisterEntries {
+    /// The policy of the replica, signed by the owner.
+    pub policy: PolicyOp,
+    /// The policies the one of the replica superseded, for the ops signed under them.
+    pub superseded_policies: Vec<PolicyOp>,
+    /// The signed ops of the entries, each following the entries it supersedes.
+    pub ops: Vec<RegisterOp>,
+}
+
+// The signature of the writer of an entry, kept for the op to be verified again by the
+// replicas it is merged into.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+struct OpSignature {
+    policy_version: u64,
+    signer: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+/// A mutable data type, whose replicas converge to the same state
+/// regardless of the order in which ops are applied to them.
+///
+/// Entries are kept in a Merkle DAG: each write references the entries it supersedes,
+/// and concurrent writes show up as multiple current entries when reading.
+///
+/// The policy is kept along with the signature of its owner, and every entry along with the
+/// signature of its writer, for replicas to be verified when merged into one another. The
+/// policies superseded are kept as well, each entry being verified against the version of the
+/// policy it was written under.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Register {
+    address: RegisterAddress,
+    policy: PolicyOp,
+    superseded_policies: BTreeMap<u64, PolicyOp>,
+    crdt: MerkleReg<Entry>,
+    signatures: BTreeMap<EntryHash, OpSignature>,
+}
+
+impl Register {
+    /// Creates an empty `Register` at the given address, written to as per the policy,
+    /// which must be signed by its owner for the register.
+    pub fn new(address: RegisterAddress, policy: PolicyOp) -> Result<Self> {
+        if policy.address != address {
+            return Err(Error::RegisterAddressMismatch {
+                dst: address,
+                op: policy.address,
+            });
+        }
+        policy.verify()?;
+        Ok(Self {
+            address,
+            policy,
+            superseded_policies: BTreeMap::new(),
+            crdt: MerkleReg::new(),
+            signatures: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the address.
+    pub fn address(&self) -> &RegisterAddress {
+        &self.address
+    }
+
+    /// Returns the policy setting who is allowed to write.
+    pub fn policy(&self) -> &Policy {
+        &self.policy.policy
+    }
+
+    /// Returns the policy along with the signature of its owner.
+    pub fn signed_policy(&self) -> &PolicyOp {
+        &self.policy
+    }
+
+    /// Returns the current entries, i.e. those not yet superseded by a later write.
+    /// More than one entry is returned when there have been concurrent writes.
+    pub fn read(&self) -> BTreeSet<(EntryHash, Entry)> {
+        self.crdt
+            .read()
+            .hashes_and_nodes()
+            .map(|(hash, node)| (hash, node.value.clone()))
+            .collect()
+    }
+
+    /// Returns the number of entries ever written to the register.
+    pub fn size(&self) -> u64 {
+        self.crdt.num_nodes() as u64
+    }
+
+    /// Writes an entry superseding all the current entries, signed by the writer, returning
+    /// the hash of the new entry and the op to be applied on other replicas of the register.
+    pub fn write(&mut self, entry: Entry, writer: &Keypair) -> Result<(EntryHash, RegisterOp)> {
+        if !self.policy().is_writer(&writer.public()) {
+            return Err(Error::RegisterWriteNotPermitted(self.address));
+        }
+        self.check_room_for(&entry)?;
+        let children = self.crdt.read().hashes();
+        let crdt_op = self.crdt.write(entry, children);
+        let policy_version = self.policy().version;
+        let signature = writer
+            .sign(&serialise(&(self.address, policy_version, &crdt_op))?)
+            .map_err(|err| Error::InvalidRegisterOp(format!("failed to sign op: {err}")))?;
+        let op = RegisterOp {
+            address: self.address,
+            crdt_op,
+            policy_version,
+            signer: writer.public().encode_protobuf(),
+            signature,
+        };
+        self.insert(op.clone());
+        Ok((op.crdt_op.hash(), op))
+    }
+
+    /// Applies an op written to another replica of the register, once checked to be signed by
+    /// a key allowed to write by the policy the op was signed under, and still allowed by the
+    /// current policy, for a writer removed from the policy not to write anymore.
+    pub fn apply_op(&mut self, op: RegisterOp) -> Result<()> {
+        let signer = self.verify_op(&op)?;
+        if !self.policy().is_writer(&signer) {
+            debug!(
+                "{:?} is no longer allowed to write to {:?}",
+                signer.to_peer_id(),
+                self.address
+            );
+            return Err(Error::RegisterWriteNotPermitted(self.address));
+        }
+        self.apply_verified_op(op)
+    }
+
+    // Applies an op merged from another replica of the register, once checked to be signed by
+    // a key allowed to write by the policy the op was signed under, regardless of later ones.
+    fn merge_op(&mut self, op: RegisterOp) -> Result<()> {
+        let _ = self.verify_op(&op)?;
+        self.apply_verified_op(op)
+    }
+
+    // Checks the op is signed by a key allowed to write by the policy of the version it was
+    // signed under, returning the signer.
+    fn verify_op(&self, op: &RegisterOp) -> Result<PublicKey> {
+        if op.address != self.address {
+            return Err(Error::RegisterAddressMismatch {
+                dst: self.address,
+                op: op.address,
+            });
+        }
+        let signer = op.verify()?;
+        let policy = self.policy_of_version(op.policy_version).ok_or_else(|| {
+            Error::InvalidRegisterOp(format!(
+                "op of {:?} signed under the unknown policy version {}",
+                self.address, op.policy_version
+            ))
+        })?;
+        if !policy.is_writer(&signer) {
+            debug!(
+                "{:?} is not allowed to write to {:?} by the policy version {}",
+                signer.to_peer_id(),
+                self.address,
+                op.policy_version
+            );
+            return Err(Error::RegisterWriteNotPermitted(self.address));
+        }
+        Ok(signer)
+    }
+
+    // Returns the policy of the version, be it the current one or one it superseded.
+    fn policy_of_version(&self, version: u64) -> Option<&Policy> {
+        if version == self.policy().version {
+            return Some(self.policy());
+        }
+        self.superseded_policies
+            .get(&version)
+            .map(|policy_op| &policy_op.policy)
+    }
+
+    fn apply_verified_op(&mut self, op: RegisterOp) -> Result<()> {
+        self.crdt
+            .validate_op(&op.crdt_op)
+            .map_err(|err| Error::InvalidRegisterOp(format!("{err:?}")))?;
+        if !self.has_entry(&op.crdt_op.hash()) {
+            self.check_room_for(&op.crdt_op.value)?;
+        }
+        self.insert(op);
+        Ok(())
+    }
+
+    // Checks the entry is not too large, nor the register full, for the register to be
+    // written to.
+    fn check_room_for(&self, entry: &Entry) -> Result<()> {
+        if entry.len() > MAX_REGISTER_ENTRY_SIZE {
+            return Err(Error::InvalidRegisterOp(format!(
+                "entry of {} bytes exceeds the max of {MAX_REGISTER_ENTRY_SIZE}",
+                entry.len()
+            )));
+        }
+        if self.size() >= MAX_REGISTER_ENTRIES {
+            return Err(Error::InvalidRegisterOp(format!(
+                "{:?} is full, holding {MAX_REGISTER_ENTRIES} entries",
+                self.address
+            )));
+        }
+        Ok(())
+    }
+
+    // Adds the entry of the op, along with the signature of its writer.
+    fn insert(&mut self, op: RegisterOp) {
+        let hash = op.crdt_op.hash();
+        self.crdt.apply(op.crdt_op);
+        let _ = self.signatures.insert(
+            hash,
+            OpSignature {
+                policy_version: op.policy_version,
+                signer: op.signer,
+                signature: op.signature,
+            },
+        );
+    }
+
+    /// Replaces the policy with the one of the op, if signed by the owner and of a later
+    /// version, the policy replaced being kept for the ops signed under it. Ops of an earlier
+    /// version are only kept as such if none of that version is known yet, and ignored
+    /// otherwise.
+    pub fn apply_policy_op(&mut self, op: PolicyOp) -> Result<()> {
+        if op.address != self.address {
+            return Err(Error::RegisterAddressMismatch {
+                dst: self.address,
+                op: op.address,
+            });
+        }
+        if op.policy.owner != self.policy().owner {
+            return Err(Error::InvalidRegisterOp(format!(
+                "policy of {:?} has another owner",
+                self.address
+            )));
+        }
+        op.verify()?;
+        let version = op.policy.version;
+        if version > self.policy().version {
+            let superseded = std::mem::replace(&mut self.policy, op);
+            let _ = self
+                .superseded_policies
+                .insert(superseded.policy.version, superseded);
+        } else if version < self.policy().version {
+            let _ = self.superseded_policies.entry(version).or_insert(op);
+        }
+        Ok(())
+    }
+
+    /// Returns the summary of the state of the replica, to be compared with the ones of
+    /// other replicas.
+    pub fn digest(&self) -> RegisterDigest {
+        RegisterDigest {
+            heads: self.crdt.read().hashes(),
+            size: self.size(),
+            policy_version: self.policy().version,
+        }
+    }
+
+    /// Whether the replica holds the entry.
+    pub fn has_entry(&self, hash: &EntryHash) -> bool {
+        self.crdt.node(*hash).is_some()
+    }
+
+    /// Returns the entries of the replica missing from one whose current entries are `known`:
+    /// the ones our current entries descend from, down to the known ones, each following the
+    /// entries it supersedes.
+    ///
+    /// Entries older than the known ones but not superseded by them are returned as well, merging
+    /// entries already held being harmless.
+    pub fn entries_since(&self, known: &BTreeSet<EntryHash>) -> RegisterEntries {
+        let mut ops = vec![];
+        let mut visited = BTreeSet::new();
+        // Each entry is pushed once the ones it supersedes were.
+        let mut stack: Vec<(EntryHash, bool)> = self
+            .crdt
+            .read()
+            .hashes()
+            .into_iter()
+            .map(|hash| (hash, false))
+            .collect();
+        while let Some((hash, superseded_pushed)) = stack.pop() {
+            let node = match self.crdt.node(hash) {
+                Some(node) => node,
+                None => continue,
+            };
+            if superseded_pushed {
+                if let Some(signature) = self.signatures.get(&hash) {
+                    ops.push(RegisterOp {
+                        address: self.address,
+                        crdt_op: node.clone(),
+                        policy_version: signature.policy_version,
+                        signer: signature.signer.clone(),
+                        signature: signature.signature.clone(),
+                    });
+                }
+                continue;
+            }
+            if known.contains(&hash) || !visited.insert(hash) {
+                continue;
+            }
+            stack.push((hash, true));
+            stack.extend(node.children.iter().map(|child| (*child, false)));
+        }
+        RegisterEntries {
+            policy: self.policy.clone(),
+            superseded_policies: self.superseded_policies.values().cloned().collect(),
+            ops,
+        }
+    }
+
+    /// Merges the entries pulled from another replica of the register into this one, keeping
+    /// the policy of the later version.
+    ///
+    /// The policies are only taken if signed by the owner, and each op only applied if signed
+    /// by a key allowed to write by the policy of the version it was signed under, the merge
+    /// stopping at the first op that is not.
+    pub fn merge_entries(&mut self, entries: RegisterEntries) -> Result<()> {
+        for policy in entries.superseded_policies {
+            self.apply_policy_op(policy)?;
+        }
+        self.apply_policy_op(entries.policy)?;
+        for op in entries.ops {
+            if self.has_entry(&op.crdt_op.hash()) {
+                continue;
+            }
+            self.merge_op(op)?;
+        }
+        Ok(())
+    }
+
+    /// Merges the state of another replica of the register into this one, verifying its
+    /// policy and entries as [`Register::merge_entries`] does.
+    pub fn merge(&mut self, other: Register) -> Result<()> {
+        if other.address != self.address {
+            return Err(Error::RegisterAddressMismatch {
+                dst: self.address,
+                op: other.address,
+            });
+        }
+        self.merge_entries(other.entries_since(&BTreeSet::new()))
+    }
+
+    /// Returns the replica once its policies and every entry of it are verified, e.g. when
+    /// received from a peer not trusted to hold it.
+    pub fn verified(self) -> Result<Self> {
+        let mut register = Self::new(self.address, self.policy.clone())?;
+        register.merge(self)?;
+        Ok(register)
+    }
+}
+
+fn decode_key(bytes: &[u8]) -> Result<PublicKey> {
+    PublicKey::try_decode_protobuf(bytes)
+        .map_err(|err| Error::InvalidRegisterOp(format!("invalid public key: {err}")))
+}
+
+// The bytes signatures of ops are made over.
+fn serialise(value: &impl Serialize) -> Result<Vec<u8>> {
+    rmp_serde::to_vec(value).map_err(|err| Error::InvalidRegisterOp(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address() -> RegisterAddress {
+        RegisterAddress::new(XorName::from_content(b"register"), 1)
+    }
+
+    // A register owned by `owner`, which `writer` is allowed to write to as well.
+    fn register(owner: &Keypair, writer: &Keypair) -> Result<Register> {
+        let policy = Policy::new(&owner.public())
+            .with_writers([&writer.public()])
+            .sign(address(), owner)?;
+        Register::new(address(), policy)
+    }
+
+    // An op on the register signed by a key its policy does not allow to write.
+    fn op_of_stranger(stranger: &Keypair) -> Result<RegisterOp> {
+        let policy = Policy::new(&stranger.public()).sign(address(), stranger)?;
+        let (_, op) = Register::new(address(), policy)?.write(b"stranger".to_vec(), stranger)?;
+        Ok(op)
+    }
+
+    #[test]
+    fn ops_of_writers_are_applied() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let mut replica = register(&owner, &writer)?;
+        let (hash, op) = replica.clone().write(b"entry".to_vec(), &writer)?;
+
+        replica.apply_op(op)?;
+        assert!(replica.has_entry(&hash));
+        Ok(())
+    }
+
+    #[test]
+    fn ops_of_non_writers_are_rejected() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let mut replica = register(&owner, &writer)?;
+        let op = op_of_stranger(&Keypair::generate_ed25519())?;
+        let hash = op.crdt_op.hash();
+
+        assert!(matches!(
+            replica.apply_op(op),
+            Err(Error::RegisterWriteNotPermitted(_))
+        ));
+        assert!(!replica.has_entry(&hash));
+        Ok(())
+    }
+
+    #[test]
+    fn ops_with_a_bad_signature_are_rejected() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let mut replica = register(&owner, &writer)?;
+        let (hash, mut op) = replica.clone().write(b"entry".to_vec(), &writer)?;
+        op.signature[0] ^= 1;
+
+        assert!(matches!(
+            replica.apply_op(op),
+            Err(Error::InvalidRegisterOp(_))
+        ));
+        assert!(!replica.has_entry(&hash));
+        Ok(())
+    }
+
+    #[test]
+    fn policies_not_signed_by_the_owner_are_rejected() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let stranger = Keypair::generate_ed25519();
+        let mut replica = register(&owner, &writer)?;
+
+        // Claiming the register for another owner.
+        let mut policy = Policy::new(&stranger.public()).with_writers([&stranger.public()]);
+        policy.version = 1;
+        let op = policy.sign(address(), &stranger)?;
+        assert!(matches!(
+            replica.apply_policy_op(op),
+            Err(Error::InvalidRegisterOp(_))
+        ));
+
+        // Changing the policy of the owner without its key.
+        let op = replica
+            .policy()
+            .next(address(), [&stranger.public()], &stranger)?;
+        assert!(matches!(
+            replica.apply_policy_op(op),
+            Err(Error::InvalidRegisterOp(_))
+        ));
+
+        assert_eq!(replica.policy().version(), 0);
+        assert!(!replica.policy().is_writer(&stranger.public()));
+        Ok(())
+    }
+
+    #[test]
+    fn policies_of_a_version_not_newer_are_not_taken() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let stranger = Keypair::generate_ed25519();
+        let mut replica = register(&owner, &writer)?;
+        let newer = replica
+            .policy()
+            .next(address(), [&writer.public()], &owner)?;
+        replica.apply_policy_op(newer.clone())?;
+
+        // Of the same version as the current policy.
+        let same = newer.policy.clone().with_writers([&stranger.public()]);
+        replica.apply_policy_op(same.sign(address(), &owner)?)?;
+        // Of an older version.
+        let older = Policy::new(&owner.public()).with_writers([&stranger.public()]);
+        replica.apply_policy_op(older.sign(address(), &owner)?)?;
+
+        assert_eq!(replica.signed_policy(), &newer);
+        assert!(!replica.policy().is_writer(&stranger.public()));
+        Ok(())
+    }
+
+    #[test]
+    fn merged_entries_of_non_writers_are_dropped() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let mut replica = register(&owner, &writer)?;
+        let (hash, op) = replica.clone().write(b"entry".to_vec(), &writer)?;
+        let stranger_op = op_of_stranger(&Keypair::generate_ed25519())?;
+        let stranger_hash = stranger_op.crdt_op.hash();
+
+        let entries = RegisterEntries {
+            policy: replica.signed_policy().clone(),
+            superseded_policies: vec![],
+            ops: vec![op, stranger_op],
+        };
+        assert!(matches!(
+            replica.merge_entries(entries),
+            Err(Error::RegisterWriteNotPermitted(_))
+        ));
+        assert!(replica.has_entry(&hash));
+        assert!(!replica.has_entry(&stranger_hash));
+        Ok(())
+    }
+
+    #[test]
+    fn entries_of_removed_writers_are_merged_but_no_new_ones() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let mut replica = register(&owner, &writer)?;
+        let (hash, _) = replica.write(b"entry".to_vec(), &writer)?;
+        let (_, later_op) = replica.clone().write(b"later".to_vec(), &writer)?;
+        // The writer is removed from the policy after writing.
+        let removal = replica
+            .policy()
+            .next(address(), std::iter::empty(), &owner)?;
+        replica.apply_policy_op(removal)?;
+
+        // Its entries are verified against the policy they were written under.
+        let mut merged = register(&owner, &writer)?;
+        merged.merge(replica.clone())?;
+        assert!(merged.has_entry(&hash));
+        assert_eq!(merged.policy().version(), 1);
+        assert!(replica.clone().verified()?.has_entry(&hash));
+
+        // While it cannot write anymore, even under the policy it was removed from.
+        assert!(matches!(
+            replica.write(b"removed".to_vec(), &writer),
+            Err(Error::RegisterWriteNotPermitted(_))
+        ));
+        assert!(matches!(
+            replica.apply_op(later_op),
+            Err(Error::RegisterWriteNotPermitted(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn replicas_holding_entries_of_non_writers_are_not_verified() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let mut replica = register(&owner, &writer)?;
+        let _ = replica.write(b"entry".to_vec(), &writer)?;
+        // As held by a node not checking the policy.
+        replica.insert(op_of_stranger(&Keypair::generate_ed25519())?);
+
+        assert!(matches!(
+            replica.verified(),
+            Err(Error::RegisterWriteNotPermitted(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn unsigned_entries_of_replicas_are_dropped() -> Result<()> {
+        let (owner, writer) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let mut replica = register(&owner, &writer)?;
+        let (hash, _) = replica.write(b"entry".to_vec(), &writer)?;
+        // An entry without the signature of its writer.
+        let crdt_op = replica
+            .crdt
+            .write(b"unsigned".to_vec(), replica.crdt.read().hashes());
+        let unsigned_hash = crdt_op.hash();
+        replica.crdt.apply(crdt_op);
+
+        let mut merged = register(&owner, &writer)?;
+        merged.merge(replica.clone())?;
+        assert!(merged.has_entry(&hash));
+        assert!(!merged.has_entry(&unsigned_hash));
+
+        let verified = replica.verified()?;
+        assert!(verified.has_entry(&hash));
+        assert!(!verified.has_entry(&unsigned_hash));
+        Ok(())
+    }
+}
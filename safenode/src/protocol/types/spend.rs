@@ -0,0 +1,301 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::protocol::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sn_dbc::{blsttc::SecretKey, DbcId, DbcTransaction, DerivationIndex, MainKey, SignedSpend};
+use xor_name::XorName;
+
+/// The derivation index of the genesis Dbc, out of the [`genesis_main_key`].
+pub const GENESIS_DERIVATION_INDEX: DerivationIndex = [0; 32];
+
+/// The address of a Dbc spend on the network, derived from the id of the spent Dbc.
+///
+/// Spend proofs are stored at the peers closest to this address, so that any
+/// attempt to spend the same Dbc again ends up at the same close group.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Debug)]
+pub struct DbcAddress(pub(crate) XorName);
+
+impl DbcAddress {
+    /// Creates the address of the spend of the given Dbc.
+    pub fn from_dbc_id(dbc_id: &DbcId) -> Self {
+        Self(XorName::from_content(&dbc_id.to_bytes()))
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> &XorName {
+        &self.0
+    }
+}
+
+/// Verifies the spend before it is stored: it must be signed by the owner of the Dbc, for the
+/// transaction it is spent in, the Dbc must be an input of that transaction, and the amounts
+/// of the transaction must balance, its outputs holding no more than its inputs.
+///
+/// The amounts are blinded, their sums are compared without revealing them.
+pub fn verify_spend(signed_spend: &SignedSpend) -> Result<()> {
+    let addr = DbcAddress::from_dbc_id(signed_spend.dbc_id());
+    signed_spend
+        .verify(signed_spend.spent_tx_hash())
+        .map_err(|err| Error::InvalidSpendSignature(addr, err.to_string()))?;
+
+    let tx = &signed_spend.spend.spent_tx;
+    if !tx
+        .inputs
+        .iter()
+        .any(|input| &input.dbc_id == signed_spend.dbc_id())
+    {
+        return Err(Error::InvalidSpend(
+            addr,
+            "the Dbc is not an input of the transaction".to_string(),
+        ));
+    }
+    let inputs = tx
+        .inputs
+        .iter()
+        .map(|input| input.blinded_amount)
+        .reduce(|sum, amount| sum + amount);
+    let outputs = tx
+        .outputs
+        .iter()
+        .map(|output| output.blinded_amount())
+        .reduce(|sum, amount| sum + amount);
+    if inputs != outputs {
+        return Err(Error::InvalidSpend(
+            addr,
+            "the inputs and outputs of the transaction do not balance".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The main key owning the genesis Dbc, well-known for the faucet of any testnet to mint it.
+///
+/// The genesis Dbc is the only Dbc spent without the spends of its parents being recorded,
+/// and it is spent once, as any other Dbc: the first spend of it recorded is the one the
+/// tokens of the network come from.
+pub fn genesis_main_key() -> MainKey {
+    // Any 32 bytes below the order of the curve make a secret key.
+    let mut bytes = XorName::from_content(b"GENESIS").0;
+    bytes[0] &= 0x3f;
+    MainKey::new(SecretKey::from_bytes(bytes).expect("bytes below the curve order"))
+}
+
+/// Returns the id of the genesis Dbc, see [`genesis_main_key`].
+pub fn genesis_dbc_id() -> DbcId {
+    genesis_main_key()
+        .public_address()
+        .new_dbc_id(&GENESIS_DERIVATION_INDEX)
+}
+
+/// Verifies the transaction the spent Dbc was created by, given along with its spend: it must
+/// be the one the spend refers to, and have the Dbc as an output.
+pub fn verify_parent_tx(signed_spend: &SignedSpend, parent_tx: &DbcTransaction) -> Result<()> {
+    let dbc_id = signed_spend.dbc_id();
+    let addr = DbcAddress::from_dbc_id(dbc_id);
+    if parent_tx.hash() != signed_spend.spend.dbc_creation_tx_hash {
+        return Err(Error::InvalidSpend(
+            addr,
+            "the parent transaction is not the one the Dbc was created by".to_string(),
+        ));
+    }
+    if !parent_tx
+        .outputs
+        .iter()
+        .any(|output| output.dbc_id() == dbc_id)
+    {
+        return Err(Error::InvalidSpend(
+            addr,
+            "the Dbc is not an output of its parent transaction".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns whether the spend is of the genesis Dbc, the only Dbc which parents are not to be
+/// checked against the spentbook, see [`genesis_main_key`].
+pub fn is_genesis_spend(signed_spend: &SignedSpend) -> bool {
+    signed_spend.dbc_id() == &genesis_dbc_id()
+}
+
+/// Verifies that the parents of a Dbc, i.e. the inputs of the transaction it was created by,
+/// were spent in that transaction, given the spends of them recorded in the spentbook.
+///
+/// A Dbc without parents is rejected, the genesis Dbc being spent without this check, see
+/// [`is_genesis_spend`].
+pub fn verify_parent_spends(
+    signed_spend: &SignedSpend,
+    parent_tx: &DbcTransaction,
+    parent_spends: &[SignedSpend],
+) -> Result<()> {
+    let addr = DbcAddress::from_dbc_id(signed_spend.dbc_id());
+    if parent_tx.inputs.is_empty() {
+        return Err(Error::InvalidSpend(
+            addr,
+            "the Dbc has no parents".to_string(),
+        ));
+    }
+    let parent_tx_hash = parent_tx.hash();
+    for input in &parent_tx.inputs {
+        let recorded = parent_spends.iter().any(|parent_spend| {
+            parent_spend.dbc_id() == &input.dbc_id
+                && parent_spend.spent_tx_hash() == parent_tx_hash
+                && verify_spend(parent_spend).is_ok()
+        });
+        if !recorded {
+            return Err(Error::InvalidSpend(
+                addr,
+                format!(
+                    "the parent {:?} is not recorded as spent in the transaction creating the Dbc",
+                    input.dbc_id
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::client::{create_genesis_dbc, create_transfer};
+    use sn_dbc::{
+        rng, Dbc, DerivedKey, Hash, InputHistory, RevealedAmount, RevealedInput, Token,
+        TransactionBuilder,
+    };
+
+    /// Spends a Dbc of the key holding `input` nanos, into a new Dbc of `output` nanos.
+    pub(crate) fn signed_spend(derived_key: &DerivedKey, input: u64, output: u64) -> SignedSpend {
+        let mut rng = rng::thread_rng();
+        let history = InputHistory {
+            input: RevealedInput::new(
+                derived_key.clone(),
+                RevealedAmount::from_amount(input, &mut rng),
+            ),
+            input_src_tx: DbcTransaction {
+                inputs: vec![],
+                outputs: vec![],
+            },
+        };
+        let dbc_id_src = MainKey::random().random_dbc_id_src(&mut rng);
+        TransactionBuilder::default()
+            .add_input(history)
+            .add_output(Token::from_nano(output), dbc_id_src)
+            .build(Hash::hash(b"spend"), &mut rng)
+            .expect("transaction to be built")
+            .signed_spends()
+            .into_iter()
+            .next()
+            .cloned()
+            .expect("the input to be spent")
+    }
+
+    pub(crate) fn random_derived_key() -> DerivedKey {
+        let main_key = MainKey::random();
+        let dbc_id_src = main_key.random_dbc_id_src(&mut rng::thread_rng());
+        main_key.derive_key(&dbc_id_src.derivation_index)
+    }
+
+    #[test]
+    fn balanced_spends_are_valid() -> Result<()> {
+        verify_spend(&signed_spend(&random_derived_key(), 100, 100))
+    }
+
+    #[test]
+    fn spends_creating_tokens_are_rejected() {
+        let derived_key = random_derived_key();
+        for (input, output) in [(100, 101), (100, 99)] {
+            let spend = signed_spend(&derived_key, input, output);
+            assert!(matches!(verify_spend(&spend), Err(Error::InvalidSpend(..))));
+        }
+    }
+
+    // Transfers the whole amount of the Dbc to a new key, returning the spend of the Dbc
+    // along with its parent transaction, and the Dbc created with its key.
+    fn transfer_all(
+        dbc: Dbc,
+        derived_key: DerivedKey,
+    ) -> ((SignedSpend, DbcTransaction), (Dbc, DerivedKey)) {
+        let main_key = MainKey::random();
+        let amount = Token::from_nano(
+            dbc.revealed_amount(&derived_key)
+                .expect("the amount to be revealed")
+                .value(),
+        );
+        let transfer = create_transfer(
+            vec![(dbc, derived_key)],
+            vec![(amount, main_key.public_address())],
+            main_key.public_address(),
+            Hash::default(),
+        )
+        .expect("the transfer to be built");
+        let created = transfer.created[0].clone();
+        let derived_key = created
+            .derived_key(&main_key)
+            .expect("the created Dbc to be owned by the key");
+        (transfer.spends[0].clone(), (created, derived_key))
+    }
+
+    fn genesis() -> (Dbc, DerivedKey) {
+        let genesis = create_genesis_dbc(Token::from_nano(100)).expect("genesis to be created");
+        let derived_key = genesis
+            .derived_key(&genesis_main_key())
+            .expect("genesis to be owned by its key");
+        (genesis, derived_key)
+    }
+
+    #[test]
+    fn the_genesis_dbc_is_spent_without_parents() -> Result<()> {
+        let (genesis, derived_key) = genesis();
+        assert_eq!(genesis.id(), genesis_dbc_id());
+        let ((genesis_spend, genesis_tx), _) = transfer_all(genesis, derived_key);
+
+        assert!(is_genesis_spend(&genesis_spend));
+        verify_parent_tx(&genesis_spend, &genesis_tx)
+    }
+
+    #[test]
+    fn spends_of_dbcs_with_recorded_parents_are_valid() -> Result<()> {
+        let (genesis, derived_key) = genesis();
+        let ((genesis_spend, _), (dbc, derived_key)) = transfer_all(genesis, derived_key);
+        let ((signed_spend, parent_tx), _) = transfer_all(dbc, derived_key);
+
+        assert!(!is_genesis_spend(&signed_spend));
+        verify_parent_tx(&signed_spend, &parent_tx)?;
+        verify_parent_spends(&signed_spend, &parent_tx, &[genesis_spend])
+    }
+
+    #[test]
+    fn spends_of_dbcs_without_recorded_parents_are_rejected() {
+        let (genesis, derived_key) = genesis();
+        let ((genesis_spend, genesis_tx), (dbc, derived_key)) = transfer_all(genesis, derived_key);
+        let ((signed_spend, parent_tx), _) = transfer_all(dbc, derived_key);
+
+        // The parent was not spent, or not in the transaction the Dbc was created by.
+        for parent_spends in [vec![], vec![signed_spend.clone()]] {
+            assert!(matches!(
+                verify_parent_spends(&signed_spend, &parent_tx, &parent_spends),
+                Err(Error::InvalidSpend(..))
+            ));
+        }
+        // The transaction given is not the one the Dbc was created by.
+        assert!(matches!(
+            verify_parent_tx(&signed_spend, &genesis_tx),
+            Err(Error::InvalidSpend(..))
+        ));
+        // A parent transaction without inputs creates the Dbc out of nothing.
+        let input_src_tx = DbcTransaction {
+            inputs: vec![],
+            outputs: vec![],
+        };
+        assert!(matches!(
+            verify_parent_spends(&genesis_spend, &input_src_tx, &[]),
+            Err(Error::InvalidSpend(..))
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{chunks::Chunk, RecordKind};
+use crate::protocol::types::register::Register;
+use sn_dbc::SignedSpend;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Mutex;
+use xor_name::XorName;
+
+/// The default max number of bytes of records held in memory, 64MB.
+pub const DEFAULT_CACHE_CAPACITY: u64 = 64 * 1024 * 1024;
+
+/// A record as held in the cache, deserialised.
+#[derive(Clone, Debug)]
+pub(super) enum CachedValue {
+    Chunk(Chunk),
+    Register(Register),
+    Spends(Vec<SignedSpend>),
+}
+
+/// The counts of the reads served by the record cache, and its size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of reads served from memory.
+    pub hits: u64,
+    /// The number of reads that went to disk.
+    pub misses: u64,
+    /// The number of bytes of records held in memory.
+    pub used: u64,
+    /// The max number of bytes of records held in memory.
+    pub capacity: u64,
+}
+
+type Key = (RecordKind, XorName);
+
+/// An in-memory cache of the records most recently read, bounded by their size on disk, so
+/// that popular records are not read and deserialised again on every request.
+///
+/// Entries are to be invalidated whenever the record is overwritten or removed. A value read
+/// from disk is only cached if no record was invalidated since the read started, as it could
+/// otherwise be the value just overwritten.
+#[derive(Clone, Debug)]
+pub(super) struct RecordCache {
+    entries: Arc<Mutex<Entries>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+#[derive(Debug)]
+struct Entries {
+    capacity: u64,
+    used: u64,
+    // The values by key, with their size and when they were last used.
+    values: HashMap<Key, (CachedValue, u64, u64)>,
+    // The keys by when they were last used, least recently used first.
+    by_use: BTreeMap<u64, Key>,
+    uses: u64,
+    invalidations: u64,
+}
+
+impl RecordCache {
+    pub(super) fn new(capacity: u64) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(Entries {
+                capacity,
+                used: 0,
+                values: HashMap::new(),
+                by_use: BTreeMap::new(),
+                uses: 0,
+                invalidations: 0,
+            })),
+            hits: Arc::default(),
+            misses: Arc::default(),
+        }
+    }
+
+    /// Returns the cached value of the record, counting a hit or a miss.
+    pub(super) async fn get(&self, kind: RecordKind, key: XorName) -> Option<CachedValue> {
+        let value = self.entries.lock().await.touch(&(kind, key));
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        let _ = counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// The number of invalidations so far, to be taken before reading a record from disk,
+    /// and handed back when caching it.
+    pub(super) async fn generation(&self) -> u64 {
+        self.entries.lock().await.invalidations
+    }
+
+    /// Caches the value of the record, of the given size, read from disk at the given
+    /// generation, evicting the least recently used records to make room for it.
+    /// Values larger than the whole cache are not cached.
+    pub(super) async fn insert(
+        &self,
+        kind: RecordKind,
+        key: XorName,
+        value: CachedValue,
+        size: u64,
+        generation: u64,
+    ) {
+        let mut entries = self.entries.lock().await;
+        if entries.invalidations == generation {
+            entries.insert((kind, key), value, size);
+        }
+    }
+
+    /// Drops the cached value of the record, after it was overwritten or removed.
+    pub(super) async fn invalidate(&self, kind: RecordKind, key: XorName) {
+        let mut entries = self.entries.lock().await;
+        entries.invalidations += 1;
+        entries.remove(&(kind, key));
+    }
+
+    pub(super) async fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().await;
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            used: entries.used,
+            capacity: entries.capacity,
+        }
+    }
+}
+
+impl Entries {
+    fn touch(&mut self, key: &Key) -> Option<CachedValue> {
+        self.uses += 1;
+        let uses = self.uses;
+        let (value, _, last_used) = self.values.get_mut(key)?;
+        let _ = self.by_use.remove(last_used);
+        *last_used = uses;
+        let _ = self.by_use.insert(uses, *key);
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: Key, value: CachedValue, size: u64) {
+        self.remove(&key);
+        if size > self.capacity {
+            return;
+        }
+        while self.used + size > self.capacity {
+            let lru = match self.by_use.values().next() {
+                Some(lru) => *lru,
+                None => break,
+            };
+            self.remove(&lru);
+        }
+        self.uses += 1;
+        self.used += size;
+        let _ = self.by_use.insert(self.uses, key);
+        let _ = self.values.insert(key, (value, size, self.uses));
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some((_, size, last_used)) = self.values.remove(key) {
+            self.used -= size;
+            let _ = self.by_use.remove(&last_used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn chunk(size: usize) -> (XorName, CachedValue, u64) {
+        let mut value = vec![0; size];
+        getrandom::getrandom(&mut value).expect("random bytes");
+        let chunk = Chunk::new(Bytes::from(value));
+        (*chunk.name(), CachedValue::Chunk(chunk), size as u64)
+    }
+
+    #[async_std::test]
+    async fn hits_and_misses_are_counted() {
+        let cache = RecordCache::new(100);
+        let (key, value, size) = chunk(10);
+
+        assert!(cache.get(RecordKind::Chunk, key).await.is_none());
+        cache.insert(RecordKind::Chunk, key, value, size, 0).await;
+        assert!(cache.get(RecordKind::Chunk, key).await.is_some());
+        // The same key of another kind is another record.
+        assert!(cache.get(RecordKind::Register, key).await.is_none());
+
+        let stats = cache.stats().await;
+        assert_eq!((stats.hits, stats.misses, stats.used), (1, 2, 10));
+    }
+
+    #[async_std::test]
+    async fn least_recently_used_records_are_evicted_past_the_capacity() {
+        let cache = RecordCache::new(30);
+        let chunks: Vec<_> = (0..3).map(|_| chunk(10)).collect();
+        for (key, value, size) in chunks.clone() {
+            cache.insert(RecordKind::Chunk, key, value, size, 0).await;
+        }
+        // The first chunk is used, the second one becomes the least recently used.
+        assert!(cache.get(RecordKind::Chunk, chunks[0].0).await.is_some());
+
+        let (key, value, size) = chunk(10);
+        cache.insert(RecordKind::Chunk, key, value, size, 0).await;
+
+        assert!(cache.get(RecordKind::Chunk, chunks[1].0).await.is_none());
+        assert!(cache.get(RecordKind::Chunk, chunks[0].0).await.is_some());
+        assert!(cache.get(RecordKind::Chunk, chunks[2].0).await.is_some());
+        assert_eq!(cache.stats().await.used, 30);
+    }
+
+    #[async_std::test]
+    async fn invalidated_and_oversized_records_are_not_cached() {
+        let cache = RecordCache::new(30);
+        let (key, value, size) = chunk(10);
+        cache.insert(RecordKind::Chunk, key, value, size, 0).await;
+        cache.invalidate(RecordKind::Chunk, key).await;
+        assert!(cache.get(RecordKind::Chunk, key).await.is_none());
+
+        // Read before the invalidation, the value may be the one overwritten.
+        let (key, value, size) = chunk(10);
+        cache.insert(RecordKind::Chunk, key, value, size, 0).await;
+        assert!(cache.get(RecordKind::Chunk, key).await.is_none());
+
+        let (key, value, size) = chunk(31);
+        let generation = cache.generation().await;
+        cache
+            .insert(RecordKind::Chunk, key, value, size, generation)
+            .await;
+        assert!(cache.get(RecordKind::Chunk, key).await.is_none());
+        assert_eq!(cache.stats().await.used, 0);
+    }
+}
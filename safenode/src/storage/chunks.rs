@@ -6,25 +6,39 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::errors::{Error, Result};
-use super::prefix_tree_path;
-use async_std::fs::{create_dir_all, read, File};
 use bytes::Bytes;
-use futures::AsyncWriteExt;
+use libp2p::PeerId;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::{
-    fmt::{self, Display, Formatter},
-    io::{self, ErrorKind},
-    path::{Path, PathBuf},
-};
-// use tokio::{
-//     fs::{create_dir_all, metadata, read, remove_file, File},
-//     io::AsyncWriteExt,
-// };
-use tracing::{debug, info, trace};
 use xor_name::XorName;
 
+#[cfg(feature = "node")]
+use {
+    super::{
+        cache::{CachedValue, RecordCache},
+        compression::{decode, CompressionConfig},
+        errors::{Error, Result},
+        list_records, prefix_tree_path, record_path, LocalRecord, RecordKind,
+    },
+    async_std::fs::{create_dir_all, metadata, read, remove_file, File},
+    futures::AsyncWriteExt,
+    std::{
+        collections::BTreeSet,
+        fmt::{self, Display, Formatter},
+        io::{self, ErrorKind},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex, MutexGuard,
+        },
+    },
+    tracing::{debug, info, trace, warn},
+    walkdir::WalkDir,
+};
+
+#[cfg(feature = "node")]
 const CHUNKS_STORE_DIR_NAME: &str = "chunks";
+#[cfg(feature = "node")]
+const WRITE_PROBE_FILE_NAME: &str = ".write_probe";
 
 /// The XorName of the provided `Chunk`
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Debug)]
@@ -36,10 +50,18 @@ impl ChunkAddress {
     }
 }
 
+#[cfg(feature = "node")]
 /// Operations on data chunks.
 #[derive(Clone, Debug)]
 pub(super) struct ChunkStorage {
     file_store_path: PathBuf,
+    used_space: Arc<AtomicU64>,
+    // The addresses of the chunks stored, listed once on start rather than walking the
+    // store on every listing.
+    addresses: Arc<Mutex<BTreeSet<ChunkAddress>>>,
+    max_capacity: u64,
+    compression: CompressionConfig,
+    cache: RecordCache,
 }
 
 /// Chunk, an immutable chunk of data
@@ -86,8 +108,25 @@ impl Chunk {
     pub fn serialised_size(&self) -> usize {
         self.value.len()
     }
+
+    /// Returns the proof the chunk is held by the `prover`, for the given nonce.
+    pub fn proof(&self, prover: &PeerId, nonce: u64) -> ChunkProof {
+        let content = [
+            &nonce.to_be_bytes()[..],
+            &prover.to_bytes(),
+            self.value.as_ref(),
+        ]
+        .concat();
+        ChunkProof(XorName::from_content(&content))
+    }
 }
 
+/// The proof a node holds a chunk: the hash of the value of the chunk prefixed with a nonce
+/// picked by the challenger and the id of the prover, which cannot be computed ahead of the
+/// challenge, nor without the value at hand, nor be relayed from another prover.
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ChunkProof(pub XorName);
+
 impl Serialize for Chunk {
     fn serialize<S: Serializer>(&self, serialiser: S) -> Result<S::Ok, S::Error> {
         // Address is omitted since it's derived from value
@@ -102,15 +141,65 @@ impl<'de> Deserialize<'de> for Chunk {
     }
 }
 
+#[cfg(feature = "node")]
 impl ChunkStorage {
     /// Creates a new `ChunkStorage` at the specified root location
     ///
     /// If the location specified already contains a `ChunkStorage`, it is simply used
     ///
-    /// Used space of the dir is tracked
-    pub(super) fn new(path: &Path) -> Self {
+    /// Used space of the dir is tracked, counting the chunks as they are on disk, i.e. compressed
+    pub(super) fn new(
+        path: &Path,
+        max_capacity: u64,
+        compression: CompressionConfig,
+        cache: RecordCache,
+    ) -> Self {
+        let file_store_path = path.join(CHUNKS_STORE_DIR_NAME);
+        // Account for any chunks already present in the dir
+        let mut used_space = 0;
+        let mut addresses = BTreeSet::new();
+        for entry in WalkDir::new(&file_store_path)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+        {
+            used_space += entry.metadata().map_or(0, |metadata| metadata.len());
+            match filepath_to_chunk_addr(entry.path()) {
+                Ok(addr) => {
+                    let _ = addresses.insert(addr);
+                }
+                Err(err) => warn!("Skipping unexpected file in the chunk store: {err}"),
+            }
+        }
+        debug!(
+            "Chunk store at {file_store_path:?} has {} chunks, {used_space} bytes in use",
+            addresses.len()
+        );
+
         Self {
-            file_store_path: path.join(CHUNKS_STORE_DIR_NAME),
+            file_store_path,
+            used_space: Arc::new(AtomicU64::new(used_space)),
+            addresses: Arc::new(Mutex::new(addresses)),
+            max_capacity,
+            compression,
+            cache,
+        }
+    }
+
+    /// Returns the number of bytes currently used by stored chunks
+    pub(super) fn used_space(&self) -> u64 {
+        self.used_space.load(Ordering::Relaxed)
+    }
+
+    /// Returns the max number of bytes that can be used by stored chunks
+    pub(super) fn max_capacity(&self) -> u64 {
+        self.max_capacity
+    }
+
+    fn stored_addresses(&self) -> MutexGuard<'_, BTreeSet<ChunkAddress>> {
+        match self.addresses.lock() {
+            Ok(addresses) => addresses,
+            Err(poisoned) => poisoned.into_inner(),
         }
     }
 
@@ -123,18 +212,30 @@ impl ChunkStorage {
 
     pub(super) async fn get_chunk(&self, address: &ChunkAddress) -> Result<Chunk> {
         trace!("Getting chunk {:?}", address);
+        let name = *address.name();
+        if let Some(CachedValue::Chunk(chunk)) = self.cache.get(RecordKind::Chunk, name).await {
+            return Ok(chunk);
+        }
 
+        let generation = self.cache.generation().await;
         let file_path = self.chunk_addr_to_filepath(address)?;
         match read(file_path).await {
             Ok(bytes) => {
-                let chunk = Chunk::new(Bytes::from(bytes));
-                if chunk.address() != address {
+                let size = bytes.len() as u64;
+                match decode(bytes) {
+                    Ok(value) if XorName::from_content(&value) == name => {
+                        let chunk = Chunk::new(value);
+                        let cached = CachedValue::Chunk(chunk.clone());
+                        self.cache
+                            .insert(RecordKind::Chunk, name, cached, size, generation)
+                            .await;
+                        Ok(chunk)
+                    }
                     // This can happen if the content read is empty, or incomplete,
                     // possibly due to an issue with the OS synchronising to disk,
-                    // resulting in a mismatch with recreated address of the Chunk.
-                    Err(Error::ChunkNotFound(*address.name()))
-                } else {
-                    Ok(chunk)
+                    // resulting in a failure to decode it or in a mismatch with
+                    // recreated address of the Chunk.
+                    _ => Err(Error::ChunkNotFound(*address.name())),
                 }
             }
             Err(io_error @ io::Error { .. }) if io_error.kind() == ErrorKind::NotFound => {
@@ -144,6 +245,54 @@ impl ChunkStorage {
         }
     }
 
+    /// Checks whether the chunk is in the local disk store
+    pub(super) fn exists(&self, address: &ChunkAddress) -> bool {
+        record_path(&self.file_store_path, *address.name()).exists()
+    }
+
+    /// Lists the addresses of all the chunks in the local disk store, in order
+    pub(super) fn addresses(&self) -> Vec<ChunkAddress> {
+        self.stored_addresses().iter().copied().collect()
+    }
+
+    /// Returns the number of chunks in the local disk store
+    pub(super) fn count(&self) -> usize {
+        self.stored_addresses().len()
+    }
+
+    /// Lists the chunks in the local disk store, with their size and the time they were stored
+    pub(super) fn records(&self) -> Vec<LocalRecord> {
+        list_records(&self.file_store_path, RecordKind::Chunk)
+    }
+
+    /// Checks that the stored value of the chunk still hashes to its name
+    pub(super) async fn is_intact(&self, name: &XorName) -> Result<bool> {
+        let bytes = read(record_path(&self.file_store_path, *name)).await?;
+        Ok(decode(bytes).is_ok_and(|value| XorName::from_content(&value) == *name))
+    }
+
+    /// Removes the chunk from the local disk store
+    pub(super) async fn remove(&self, name: &XorName) -> Result<()> {
+        let filepath = record_path(&self.file_store_path, *name);
+        let size = metadata(&filepath).await?.len();
+        remove_file(filepath).await?;
+        let _ = self.stored_addresses().remove(&ChunkAddress(*name));
+        self.cache.invalidate(RecordKind::Chunk, *name).await;
+        let _ = self.used_space.fetch_sub(size, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Checks that chunks can be written to the local disk store, by writing and removing a probe file
+    pub(super) async fn check_writable(&self) -> Result<()> {
+        create_dir_all(&self.file_store_path).await?;
+        let probe_path = self.file_store_path.join(WRITE_PROBE_FILE_NAME);
+        let mut file = File::create(&probe_path).await?;
+        file.write_all(b"probe").await?;
+        file.sync_data().await?;
+        remove_file(probe_path).await?;
+        Ok(())
+    }
+
     // Read chunk from local store and return NodeQueryResponse
     pub(super) async fn get(&self, address: &ChunkAddress) -> Result<Chunk> {
         self.get_chunk(address).await
@@ -163,24 +312,88 @@ impl ChunkStorage {
             return Ok(());
         }
 
-        // Store the data on disk
-        if let Some(dirs) = filepath.parent() {
-            create_dir_all(dirs).await?;
+        let bytes = self.compression.encode(chunk.value())?;
+        let chunk_size = bytes.len() as u64;
+        // The space is reserved before writing, for concurrent stores not to go over the
+        // capacity together, and given back if the write fails.
+        let _ = self
+            .used_space
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                let used = used + chunk_size;
+                (used <= self.max_capacity).then_some(used)
+            })
+            .map_err(|_| Error::NotEnoughSpace)?;
+        if let Err(err) = write_chunk(&filepath, &bytes).await {
+            let _ = self.used_space.fetch_sub(chunk_size, Ordering::Relaxed);
+            return Err(err);
         }
-
-        let mut file = File::create(filepath).await?;
-
-        file.write_all(chunk.value()).await?;
-        // Let's sync up OS data to disk to reduce the chances of
-        // concurrent reading failing by reading an empty/incomplete file
-        file.sync_data().await?;
+        let _ = self.stored_addresses().insert(*addr);
 
         Ok(())
     }
 }
 
+#[cfg(feature = "node")]
+// Writes the bytes of a chunk to its file
+async fn write_chunk(filepath: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(dirs) = filepath.parent() {
+        create_dir_all(dirs).await?;
+    }
+
+    let mut file = File::create(filepath).await?;
+
+    file.write_all(bytes).await?;
+    // Let's sync up OS data to disk to reduce the chances of
+    // concurrent reading failing by reading an empty/incomplete file
+    file.sync_data().await?;
+    Ok(())
+}
+
+#[cfg(feature = "node")]
+// Recovers the address of a chunk from the hex encoded name of the file it is stored in
+fn filepath_to_chunk_addr(path: &Path) -> Result<ChunkAddress> {
+    let filename = path
+        .file_name()
+        .ok_or_else(|| Error::NoFilename(path.to_path_buf()))?
+        .to_str()
+        .ok_or_else(|| Error::InvalidFilename(path.to_path_buf()))?;
+    let bytes = hex::decode(filename)?;
+    let name = XorName(
+        bytes
+            .try_into()
+            .map_err(|_| Error::InvalidFilename(path.to_path_buf()))?,
+    );
+    Ok(ChunkAddress(name))
+}
+
+#[cfg(feature = "node")]
 impl Display for ChunkStorage {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(formatter, "ChunkStorage")
     }
 }
+
+#[cfg(all(test, feature = "node"))]
+mod tests {
+    use super::*;
+    use futures::future::join_all;
+
+    #[async_std::test]
+    async fn concurrent_stores_do_not_go_over_the_capacity() -> Result<()> {
+        let dir = std::env::temp_dir().join(hex::encode(XorName::random(&mut rand::thread_rng())));
+        let chunks: Vec<Chunk> = (0..10)
+            .map(|i| Chunk::new(Bytes::from(format!("chunk {i:04}"))))
+            .collect();
+        let compression = CompressionConfig::default();
+        let chunk_size = compression.encode(chunks[0].value())?.len() as u64;
+        let storage = ChunkStorage::new(&dir, 3 * chunk_size, compression, RecordCache::new(0));
+
+        let results = join_all(chunks.iter().map(|chunk| storage.store(chunk))).await;
+
+        let stored = results.iter().filter(|result| result.is_ok()).count();
+        assert_eq!(stored, 3);
+        assert_eq!(storage.used_space(), 3 * chunk_size);
+        assert_eq!(storage.count(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::errors::{Error, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The default size in bytes from which record values are compressed on disk.
+pub const DEFAULT_COMPRESSION_MIN_SIZE: usize = 1024;
+
+// The flags stored as the first byte of each record file, telling how its value is encoded.
+const FLAG_RAW: u8 = 0;
+const FLAG_ZSTD: u8 = 1;
+const FLAG_LZ4: u8 = 2;
+
+/// The algorithm record values are compressed with on disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Values are stored as they are.
+    #[default]
+    None,
+    /// Zstandard, for the better ratio.
+    Zstd,
+    /// LZ4, for the faster writes and reads.
+    Lz4,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "zstd" => Ok(Self::Zstd),
+            "lz4" => Ok(Self::Lz4),
+            other => Err(format!(
+                "Unknown compression {other:?}, expected `none`, `zstd` or `lz4`"
+            )),
+        }
+    }
+}
+
+/// How record values are compressed on disk.
+///
+/// The values are stored with a flag telling how they were encoded, so records written
+/// with other settings, e.g. before a restart, are still read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// The algorithm values are compressed with.
+    pub algorithm: Compression,
+    /// The size in bytes from which values are compressed, smaller ones being stored as they are.
+    pub min_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: Compression::None,
+            min_size: DEFAULT_COMPRESSION_MIN_SIZE,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Encodes the value to be written to disk, compressing it if that makes it smaller.
+    pub(super) fn encode(&self, value: &[u8]) -> Result<Vec<u8>> {
+        if value.len() >= self.min_size {
+            let compressed = match self.algorithm {
+                Compression::None => None,
+                Compression::Zstd => Some((FLAG_ZSTD, zstd::encode_all(value, 0)?)),
+                Compression::Lz4 => Some((FLAG_LZ4, lz4_flex::compress_prepend_size(value))),
+            };
+            if let Some((flag, compressed)) = compressed {
+                // Incompressible values, such as encrypted chunks, are stored as they are.
+                if compressed.len() < value.len() {
+                    let mut bytes = Vec::with_capacity(compressed.len() + 1);
+                    bytes.push(flag);
+                    bytes.extend(compressed);
+                    return Ok(bytes);
+                }
+            }
+        }
+        let mut bytes = Vec::with_capacity(value.len() + 1);
+        bytes.push(FLAG_RAW);
+        bytes.extend_from_slice(value);
+        Ok(bytes)
+    }
+}
+
+/// Decodes a value read from disk, whatever the settings it was written with.
+///
+/// Values stored raw are sliced out of the bytes read, without copying them.
+pub(super) fn decode(bytes: Vec<u8>) -> Result<Bytes> {
+    match bytes.first() {
+        Some(&FLAG_RAW) => Ok(Bytes::from(bytes).slice(1..)),
+        Some(&FLAG_ZSTD) => zstd::decode_all(&bytes[1..])
+            .map(Bytes::from)
+            .map_err(|err| Error::Decompression(err.to_string())),
+        Some(&FLAG_LZ4) => lz4_flex::decompress_size_prepended(&bytes[1..])
+            .map(Bytes::from)
+            .map_err(|err| Error::Decompression(err.to_string())),
+        Some(flag) => Err(Error::Decompression(format!(
+            "unknown compression flag {flag}"
+        ))),
+        None => Err(Error::Decompression("empty record file".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(algorithm: Compression) -> CompressionConfig {
+        CompressionConfig {
+            algorithm,
+            min_size: 16,
+        }
+    }
+
+    #[test]
+    fn compressible_values_round_trip() -> Result<()> {
+        let value = b"a very compressible value ".repeat(100);
+        for algorithm in [Compression::Zstd, Compression::Lz4] {
+            let bytes = config(algorithm).encode(&value)?;
+            assert!(bytes.len() < value.len(), "{algorithm:?}");
+            assert_eq!(decode(bytes)?, value);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn small_and_incompressible_values_are_stored_raw() -> Result<()> {
+        let small = b"short".to_vec();
+        let mut random = vec![0; 4096];
+        getrandom::getrandom(&mut random).expect("random bytes");
+        for value in [small, random] {
+            let bytes = config(Compression::Zstd).encode(&value)?;
+            assert_eq!(bytes[0], FLAG_RAW);
+            assert_eq!(decode(bytes)?, value);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn values_are_read_whatever_the_current_settings() -> Result<()> {
+        let value = b"written with zstd, read with lz4 set ".repeat(10);
+        let bytes = config(Compression::Zstd).encode(&value)?;
+        assert_eq!(bytes[0], FLAG_ZSTD);
+        // Decoding only depends on the flag stored with the value.
+        assert_eq!(decode(bytes)?, value);
+        assert!(decode(vec![]).is_err());
+        assert!(decode(vec![42, 1, 2]).is_err());
+        Ok(())
+    }
+}
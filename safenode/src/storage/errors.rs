@@ -6,6 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::protocol::{
+    error::Error as ProtocolError,
+    types::{register::RegisterAddress, spend::DbcAddress},
+};
 use std::{io, path::PathBuf};
 use thiserror::Error;
 use xor_name::XorName;
@@ -23,9 +27,24 @@ pub enum Error {
     /// Chunk not found.
     #[error("Chunk not found: {0:?}")]
     ChunkNotFound(XorName),
+    /// Register not found.
+    #[error("Register not found: {0:?}")]
+    RegisterNotFound(RegisterAddress),
+    /// Spend not found.
+    #[error("Spend not found: {0:?}")]
+    SpendNotFound(DbcAddress),
+    /// Serialisation error.
+    #[error("Serialisation error: {0}")]
+    Serialisation(String),
+    /// Protocol error, e.g. from applying an invalid op to a register.
+    #[error("Protocol error: {0}")]
+    Protocol(#[from] ProtocolError),
     /// I/O error.
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
+    /// A stored value could not be decompressed.
+    #[error("Decompression error: {0}")]
+    Decompression(String),
     /// Hex decoding error.
     #[error("Hex decoding error:: {0}")]
     HexDecoding(#[from] hex::FromHexError),
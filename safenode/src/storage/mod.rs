@@ -6,44 +6,302 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+#[cfg(feature = "node")]
+mod cache;
 /// Chunks
 pub mod chunks;
+#[cfg(feature = "node")]
+mod compression;
+#[cfg(feature = "node")]
 mod errors;
+#[cfg(feature = "node")]
+mod registers;
+#[cfg(feature = "node")]
+mod spends;
 
-use self::chunks::{Chunk, ChunkAddress};
-use chunks::ChunkStorage;
-use errors::Result;
-use std::path::{Path, PathBuf};
-use xor_name::XorName;
+#[cfg(feature = "node")]
+pub use self::{
+    cache::{CacheStats, DEFAULT_CACHE_CAPACITY},
+    compression::{Compression, CompressionConfig, DEFAULT_COMPRESSION_MIN_SIZE},
+    errors::Error,
+};
 
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "node")]
+use {
+    self::chunks::{Chunk, ChunkAddress},
+    crate::protocol::types::{
+        register::{PolicyOp, Register, RegisterAddress, RegisterEntries, RegisterOp},
+        spend::DbcAddress,
+    },
+    cache::RecordCache,
+    chunks::ChunkStorage,
+    errors::Result,
+    registers::RegisterStorage,
+    sn_dbc::SignedSpend,
+    spends::SpendStorage,
+    std::{
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    tracing::instrument,
+    walkdir::WalkDir,
+    xor_name::XorName,
+};
+
+#[cfg(feature = "node")]
 const BIT_TREE_DEPTH: usize = 20;
 
+/// The default max capacity of a node's record store, 2GB.
+#[cfg(feature = "node")]
+pub const DEFAULT_MAX_CAPACITY: u64 = 2 * 1024 * 1024 * 1024;
+
+/// The used and total record capacity of a node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StorageLevel {
+    /// Bytes currently used for storing records.
+    pub used: u64,
+    /// Max number of bytes available for storing records.
+    pub total: u64,
+}
+
+impl StorageLevel {
+    /// Returns the percentage (0-100) of the capacity in use.
+    pub fn percentage(&self) -> u8 {
+        if self.total == 0 {
+            return 100;
+        }
+        (self.used.min(self.total) * 100 / self.total) as u8
+    }
+}
+
+/// The kind of a record held in the local store.
+#[cfg(feature = "node")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum RecordKind {
+    /// A chunk.
+    Chunk,
+    /// A register.
+    Register,
+    /// The spends of a Dbc.
+    Spend,
+}
+
+/// A record held in the local store.
+#[cfg(feature = "node")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct LocalRecord {
+    /// The name the record is stored under.
+    pub key: XorName,
+    /// The kind of the record.
+    pub kind: RecordKind,
+    /// The size in bytes of the stored value.
+    pub size: u64,
+    /// When the value was last written to disk.
+    pub stored_at: SystemTime,
+}
+
 /// Operations on data stored to disk.
+#[cfg(feature = "node")]
 /// As data the storage struct may be cloned throughoout the node
 /// Operations here must be persisted to disk.
 #[derive(Debug, Clone)]
 // exposed as pub due to benches
 pub struct DataStorage {
     chunks: ChunkStorage,
+    registers: RegisterStorage,
+    spends: SpendStorage,
+    cache: RecordCache,
 }
 
+#[cfg(feature = "node")]
 impl DataStorage {
-    /// Set up a new `DataStorage` instance
-    pub fn new(path: &Path) -> Self {
+    /// Set up a new `DataStorage` instance, limited to `max_capacity` bytes of records,
+    /// compressing the values of the records on disk as configured, and holding up to
+    /// `cache_capacity` bytes of the records most recently read in memory
+    pub fn new(
+        path: &Path,
+        max_capacity: u64,
+        compression: CompressionConfig,
+        cache_capacity: u64,
+    ) -> Self {
+        let cache = RecordCache::new(cache_capacity);
         Self {
-            chunks: ChunkStorage::new(path),
+            chunks: ChunkStorage::new(path, max_capacity, compression, cache.clone()),
+            registers: RegisterStorage::new(path, compression, cache.clone()),
+            spends: SpendStorage::new(path, compression, cache.clone()),
+            cache,
         }
     }
 
     /// Store data in the local store
+    #[instrument(skip_all, fields(addr = ?chunk.address()))]
     pub async fn store(&self, chunk: &Chunk) -> Result<()> {
         self.chunks.store(chunk).await
     }
 
     /// Query the local store and return the Chunk
+    #[instrument(skip(self))]
     pub async fn query(&self, addr: &ChunkAddress) -> Result<Chunk> {
         self.chunks.get(addr).await
     }
+
+    /// Check whether the chunk is held in the local store
+    pub fn chunk_exists(&self, addr: &ChunkAddress) -> bool {
+        self.chunks.exists(addr)
+    }
+
+    /// List the addresses of all the chunks in the local store, in order
+    pub fn chunk_addresses(&self) -> Vec<ChunkAddress> {
+        self.chunks.addresses()
+    }
+
+    /// Count the chunks in the local store
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.count()
+    }
+
+    /// List the records in the local store, ordered by their key, with the size and the
+    /// time of the last write of their value
+    pub fn local_records(&self) -> Vec<LocalRecord> {
+        let mut records = self.chunks.records();
+        records.extend(self.registers.records());
+        records.extend(self.spends.records());
+        records.sort();
+        records
+    }
+
+    /// Check that the stored value of the record still matches its key, returning `false`
+    /// if it has been corrupted
+    pub async fn is_intact(&self, record: &LocalRecord) -> Result<bool> {
+        match record.kind {
+            RecordKind::Chunk => self.chunks.is_intact(&record.key).await,
+            RecordKind::Register => self.registers.is_intact(&record.key).await,
+            RecordKind::Spend => self.spends.is_intact(&record.key).await,
+        }
+    }
+
+    /// Remove the record from the local store
+    pub async fn remove(&self, record: &LocalRecord) -> Result<()> {
+        match record.kind {
+            RecordKind::Chunk => self.chunks.remove(&record.key).await,
+            RecordKind::Register => self.registers.remove(&record.key).await,
+            RecordKind::Spend => self.spends.remove(&record.key).await,
+        }
+    }
+
+    /// Create an empty register in the local store
+    #[instrument(skip(self, policy))]
+    pub async fn create_register(&self, addr: &RegisterAddress, policy: PolicyOp) -> Result<()> {
+        self.registers.create(addr, policy).await
+    }
+
+    /// Apply the op to the register in the local store
+    #[instrument(skip_all, fields(addr = ?op.address))]
+    pub async fn edit_register(&self, op: RegisterOp) -> Result<()> {
+        self.registers.edit(op).await
+    }
+
+    /// Replace the policy of the register in the local store
+    #[instrument(skip_all, fields(addr = ?op.address))]
+    pub async fn set_register_policy(&self, op: PolicyOp) -> Result<()> {
+        self.registers.set_policy(op).await
+    }
+
+    /// Query the local store and return the Register
+    #[instrument(skip(self))]
+    pub async fn get_register(&self, addr: &RegisterAddress) -> Result<Register> {
+        self.registers.get(addr).await
+    }
+
+    /// Query the local store for the register of the given id, as listed in its records
+    #[instrument(skip(self))]
+    pub async fn get_register_by_id(&self, id: &XorName) -> Result<Register> {
+        self.registers.get_by_id(id).await
+    }
+
+    /// Merge the replica of a register into the one in the local store, storing it if there
+    /// is none
+    #[instrument(skip_all, fields(addr = ?replica.address()))]
+    pub async fn merge_register(&self, replica: Register) -> Result<()> {
+        self.registers.merge(replica).await
+    }
+
+    /// Merge the entries pulled from another replica into the register in the local store
+    #[instrument(skip(self, entries))]
+    pub async fn merge_register_entries(
+        &self,
+        addr: &RegisterAddress,
+        entries: RegisterEntries,
+    ) -> Result<()> {
+        self.registers.merge_entries(addr, entries).await
+    }
+
+    /// Query the local store for all the spends held under the given name, conflicting
+    /// ones included
+    #[instrument(skip(self))]
+    pub async fn get_spends_by_name(&self, name: &XorName) -> Result<Vec<SignedSpend>> {
+        self.spends.get_all(name).await
+    }
+
+    /// Store the spend in the local store, failing if a conflicting spend is already held
+    #[instrument(skip_all, fields(dbc_id = ?signed_spend.dbc_id()))]
+    pub async fn try_add_spend(&self, signed_spend: &SignedSpend) -> Result<()> {
+        self.spends.try_add(signed_spend).await
+    }
+
+    /// Query the local store and return the spend of the Dbc at the given address
+    #[instrument(skip(self))]
+    pub async fn get_spend(&self, addr: &DbcAddress) -> Result<SignedSpend> {
+        self.spends.get(addr).await
+    }
+
+    /// Checks that records can be written to the local store
+    pub async fn check_writable(&self) -> Result<()> {
+        self.chunks.check_writable().await
+    }
+
+    /// Returns the hits and misses of the in-memory cache of the records
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.cache.stats().await
+    }
+
+    /// Returns the used and total capacity of the local store
+    pub fn storage_level(&self) -> StorageLevel {
+        StorageLevel {
+            used: self.chunks.used_space(),
+            total: self.chunks.max_capacity(),
+        }
+    }
+}
+
+// Lists the records stored under `root`, in files named after the hex encoding of their key.
+// Other files, such as write probes, are skipped.
+#[cfg(feature = "node")]
+fn list_records(root: &Path, kind: RecordKind) -> Vec<LocalRecord> {
+    WalkDir::new(root)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let bytes = hex::decode(entry.file_name().to_str()?).ok()?;
+            let key = XorName(bytes.try_into().ok()?);
+            let metadata = entry.metadata().ok()?;
+            Some(LocalRecord {
+                key,
+                kind,
+                size: metadata.len(),
+                stored_at: metadata.modified().ok()?,
+            })
+        })
+        .collect()
+}
+
+// The path of the file the record of the given key is stored in, under `root`.
+#[cfg(feature = "node")]
+fn record_path(root: &Path, key: XorName) -> PathBuf {
+    prefix_tree_path(root, key).join(hex::encode(key))
 }
 
 // Helper that returns the prefix tree path of depth BIT_TREE_DEPTH for a given xorname
@@ -51,6 +309,7 @@ impl DataStorage {
 // - with a xorname with starting bits `010001110110....`
 // - and a BIT_TREE_DEPTH of `6`
 // returns the path `ROOT_PATH/0/1/0/0/0/1`
+#[cfg(feature = "node")]
 fn prefix_tree_path(root: &Path, xorname: XorName) -> PathBuf {
     let bin = format!("{xorname:b}");
     let prefix_dir_path: PathBuf = bin.chars().take(BIT_TREE_DEPTH).map(String::from).collect();
@@ -0,0 +1,216 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::cache::{CachedValue, RecordCache};
+use super::compression::{decode, CompressionConfig};
+use super::errors::{Error, Result};
+use super::{list_records, prefix_tree_path, record_path, LocalRecord, RecordKind};
+use crate::protocol::{
+    error::Error as ProtocolError,
+    types::register::{PolicyOp, Register, RegisterAddress, RegisterEntries, RegisterOp},
+};
+use async_std::fs::{create_dir_all, read, remove_file, rename, File};
+use futures::AsyncWriteExt;
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+use tracing::{info, trace};
+use xor_name::XorName;
+
+const REGISTERS_STORE_DIR_NAME: &str = "registers";
+
+/// Operations on registers.
+#[derive(Clone, Debug)]
+pub(super) struct RegisterStorage {
+    file_store_path: PathBuf,
+    // Serialises the read-modify-write of register replicas
+    write_lock: Arc<Mutex<()>>,
+    compression: CompressionConfig,
+    cache: RecordCache,
+}
+
+impl RegisterStorage {
+    /// Creates a new `RegisterStorage` at the specified root location
+    ///
+    /// If the location specified already contains a `RegisterStorage`, it is simply used
+    pub(super) fn new(path: &Path, compression: CompressionConfig, cache: RecordCache) -> Self {
+        Self {
+            file_store_path: path.join(REGISTERS_STORE_DIR_NAME),
+            write_lock: Arc::new(Mutex::new(())),
+            compression,
+            cache,
+        }
+    }
+
+    /// Lists the registers in the local disk store, with their size and the time they were
+    /// last edited
+    pub(super) fn records(&self) -> Vec<LocalRecord> {
+        list_records(&self.file_store_path, RecordKind::Register)
+    }
+
+    /// Checks that the stored register still deserialises, to the register of the given id
+    pub(super) async fn is_intact(&self, id: &XorName) -> Result<bool> {
+        let bytes = read(record_path(&self.file_store_path, *id)).await?;
+        let register = decode(bytes)
+            .ok()
+            .and_then(|value| rmp_serde::from_slice::<Register>(&value).ok());
+        Ok(register.is_some_and(|register| register.address().id() == *id))
+    }
+
+    /// Removes the register from the local disk store
+    pub(super) async fn remove(&self, id: &XorName) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        remove_file(record_path(&self.file_store_path, *id)).await?;
+        self.cache.invalidate(RecordKind::Register, *id).await;
+        Ok(())
+    }
+
+    fn register_addr_to_filepath(&self, addr: &RegisterAddress) -> PathBuf {
+        let xorname = addr.id();
+        let path = prefix_tree_path(&self.file_store_path, xorname);
+        let filename = hex::encode(xorname);
+        path.join(filename)
+    }
+
+    /// Read a register from the local store
+    pub(super) async fn get(&self, addr: &RegisterAddress) -> Result<Register> {
+        trace!("Getting register {addr:?}");
+        let id = addr.id();
+        if let Some(CachedValue::Register(register)) =
+            self.cache.get(RecordKind::Register, id).await
+        {
+            return Ok(register);
+        }
+
+        let generation = self.cache.generation().await;
+        let filepath = self.register_addr_to_filepath(addr);
+        match read(filepath).await {
+            Ok(bytes) => {
+                let size = bytes.len() as u64;
+                let register: Register = rmp_serde::from_slice(&decode(bytes)?)
+                    .map_err(|err| Error::Serialisation(err.to_string()))?;
+                if register.address() != addr {
+                    // The file was not written by us, or was corrupted.
+                    return Err(Error::RegisterNotFound(*addr));
+                }
+                let cached = CachedValue::Register(register.clone());
+                self.cache
+                    .insert(RecordKind::Register, id, cached, size, generation)
+                    .await;
+                Ok(register)
+            }
+            Err(io_error @ io::Error { .. }) if io_error.kind() == ErrorKind::NotFound => {
+                Err(Error::RegisterNotFound(*addr))
+            }
+            Err(other) => Err(other.into()),
+        }
+    }
+
+    /// Read the register of the given id from the local store, e.g. as listed in its records
+    pub(super) async fn get_by_id(&self, id: &XorName) -> Result<Register> {
+        let bytes = read(record_path(&self.file_store_path, *id)).await?;
+        let register: Register = rmp_serde::from_slice(&decode(bytes)?)
+            .map_err(|err| Error::Serialisation(err.to_string()))?;
+        if register.address().id() != *id {
+            return Err(Error::Serialisation(format!(
+                "register stored as {id:?} is at {:?}",
+                register.address()
+            )));
+        }
+        Ok(register)
+    }
+
+    /// Merge the replica into the locally stored one, or store it if there is none
+    pub(super) async fn merge(&self, replica: Register) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        match self.get(replica.address()).await {
+            Ok(mut register) => {
+                register.merge(replica)?;
+                self.write(&register).await
+            }
+            Err(Error::RegisterNotFound(_)) => self.write(&replica.verified()?).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Merge the entries pulled from another replica into the locally stored one
+    pub(super) async fn merge_entries(
+        &self,
+        addr: &RegisterAddress,
+        entries: RegisterEntries,
+    ) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut register = self.get(addr).await?;
+        register.merge_entries(entries)?;
+        self.write(&register).await
+    }
+
+    /// Store an empty register in the local disk store unless it is already there. A register
+    /// already created with another policy is not taken over.
+    pub(super) async fn create(&self, addr: &RegisterAddress, policy: PolicyOp) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let register = Register::new(*addr, policy)?;
+        match self.get(addr).await {
+            Ok(existing) if existing.signed_policy() == register.signed_policy() => {
+                info!("{self}: Register already exists, not storing: {addr:?}");
+                Ok(())
+            }
+            Ok(_) => Err(ProtocolError::RegisterExists(*addr).into()),
+            Err(Error::RegisterNotFound(_)) => self.write(&register).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Apply the op to the locally stored replica of the register it is addressed to
+    pub(super) async fn edit(&self, op: RegisterOp) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut register = self.get(&op.address).await?;
+        register.apply_op(op)?;
+        self.write(&register).await
+    }
+
+    /// Replace the policy of the locally stored replica of the register it is addressed to
+    pub(super) async fn set_policy(&self, op: PolicyOp) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut register = self.get(&op.address).await?;
+        register.apply_policy_op(op)?;
+        self.write(&register).await
+    }
+
+    // Writes to a temporary file first, for a crash not to leave the replica half written.
+    async fn write(&self, register: &Register) -> Result<()> {
+        let filepath = self.register_addr_to_filepath(register.address());
+        if let Some(dirs) = filepath.parent() {
+            create_dir_all(dirs).await?;
+        }
+
+        let value =
+            rmp_serde::to_vec(register).map_err(|err| Error::Serialisation(err.to_string()))?;
+        let bytes = self.compression.encode(&value)?;
+        let tmp_path = filepath.with_extension("tmp");
+        let mut file = File::create(&tmp_path).await?;
+        file.write_all(&bytes).await?;
+        file.sync_data().await?;
+        rename(&tmp_path, filepath).await?;
+        self.cache
+            .invalidate(RecordKind::Register, register.address().id())
+            .await;
+
+        Ok(())
+    }
+}
+
+impl Display for RegisterStorage {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "RegisterStorage")
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::cache::{CachedValue, RecordCache};
+use super::compression::{decode, CompressionConfig};
+use super::errors::{Error, Result};
+use super::{list_records, prefix_tree_path, record_path, LocalRecord, RecordKind};
+use crate::protocol::{error::Error as ProtocolError, types::spend::DbcAddress};
+use async_std::fs::{create_dir_all, read, remove_file, rename, File};
+use futures::AsyncWriteExt;
+use sn_dbc::SignedSpend;
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+use tracing::{trace, warn};
+use xor_name::XorName;
+
+const SPENDS_STORE_DIR_NAME: &str = "spends";
+// Two conflicting spends are proof enough of a double spend attempt, further ones being
+// dropped for an attacker not to grow the record without bounds.
+const MAX_SPENDS_PER_ADDRESS: usize = 2;
+
+/// Storage of Dbc spend proofs.
+///
+/// Normally a single spend is held per address. When a double spend attempt is detected,
+/// the first two conflicting spends are kept, as proof of the attempt.
+#[derive(Clone, Debug)]
+pub(super) struct SpendStorage {
+    file_store_path: PathBuf,
+    // Serialises the read-modify-write of the spends held at an address
+    write_lock: Arc<Mutex<()>>,
+    compression: CompressionConfig,
+    cache: RecordCache,
+}
+
+impl SpendStorage {
+    /// Creates a new `SpendStorage` at the specified root location
+    ///
+    /// If the location specified already contains a `SpendStorage`, it is simply used
+    pub(super) fn new(path: &Path, compression: CompressionConfig, cache: RecordCache) -> Self {
+        Self {
+            file_store_path: path.join(SPENDS_STORE_DIR_NAME),
+            write_lock: Arc::new(Mutex::new(())),
+            compression,
+            cache,
+        }
+    }
+
+    /// Lists the spends in the local disk store, with their size and the time they were
+    /// last added to
+    pub(super) fn records(&self) -> Vec<LocalRecord> {
+        list_records(&self.file_store_path, RecordKind::Spend)
+    }
+
+    /// Checks that the stored spends still deserialise, to spends of the Dbc of the given name
+    pub(super) async fn is_intact(&self, name: &XorName) -> Result<bool> {
+        let bytes = read(record_path(&self.file_store_path, *name)).await?;
+        let spends = decode(bytes)
+            .ok()
+            .and_then(|value| rmp_serde::from_slice::<Vec<SignedSpend>>(&value).ok());
+        Ok(spends.is_some_and(|spends| {
+            !spends.is_empty()
+                && spends
+                    .iter()
+                    .all(|spend| DbcAddress::from_dbc_id(spend.dbc_id()).name() == name)
+        }))
+    }
+
+    /// Removes the spends from the local disk store
+    pub(super) async fn remove(&self, name: &XorName) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        remove_file(record_path(&self.file_store_path, *name)).await?;
+        self.cache.invalidate(RecordKind::Spend, *name).await;
+        Ok(())
+    }
+
+    fn dbc_addr_to_filepath(&self, addr: &DbcAddress) -> PathBuf {
+        let xorname = *addr.name();
+        let path = prefix_tree_path(&self.file_store_path, xorname);
+        let filename = hex::encode(xorname);
+        path.join(filename)
+    }
+
+    /// Returns the spend held at the given address.
+    ///
+    /// Returns a `DoubleSpendAttempt` error if conflicting spends are held at it.
+    pub(super) async fn get(&self, addr: &DbcAddress) -> Result<SignedSpend> {
+        trace!("Getting spend {addr:?}");
+        let mut spends = self.read_spends(addr).await?.into_iter();
+        match (spends.next(), spends.next()) {
+            (Some(spend), None) => Ok(spend),
+            (Some(new), Some(existing)) => {
+                Err(Error::Protocol(ProtocolError::DoubleSpendAttempt {
+                    new: Box::new(new),
+                    existing: Box::new(existing),
+                }))
+            }
+            (None, _) => Err(Error::SpendNotFound(*addr)),
+        }
+    }
+
+    /// Returns all the spends held under the given name, the conflicting ones included.
+    pub(super) async fn get_all(&self, name: &XorName) -> Result<Vec<SignedSpend>> {
+        let bytes = read(record_path(&self.file_store_path, *name)).await?;
+        rmp_serde::from_slice(&decode(bytes)?).map_err(|err| Error::Serialisation(err.to_string()))
+    }
+
+    /// Stores the spend, unless a different spend of the same Dbc is already held,
+    /// in which case both are kept and a `DoubleSpendAttempt` error is returned.
+    pub(super) async fn try_add(&self, signed_spend: &SignedSpend) -> Result<()> {
+        let addr = DbcAddress::from_dbc_id(signed_spend.dbc_id());
+        let _guard = self.write_lock.lock().await;
+
+        let mut spends = match self.read_spends(&addr).await {
+            Ok(spends) => spends,
+            Err(Error::SpendNotFound(_)) => vec![],
+            Err(err) => return Err(err),
+        };
+        if spends.contains(signed_spend) {
+            trace!("Spend already held at {addr:?}");
+            return Ok(());
+        }
+
+        let existing = spends.first().cloned();
+        if spends.len() < MAX_SPENDS_PER_ADDRESS {
+            spends.push(signed_spend.clone());
+            self.write_spends(&addr, &spends).await?;
+        }
+
+        match existing {
+            Some(existing) => {
+                warn!("Double spend attempt detected at {addr:?}");
+                Err(Error::Protocol(ProtocolError::DoubleSpendAttempt {
+                    new: Box::new(signed_spend.clone()),
+                    existing: Box::new(existing),
+                }))
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn read_spends(&self, addr: &DbcAddress) -> Result<Vec<SignedSpend>> {
+        let name = *addr.name();
+        if let Some(CachedValue::Spends(spends)) = self.cache.get(RecordKind::Spend, name).await {
+            return Ok(spends);
+        }
+
+        let generation = self.cache.generation().await;
+        let filepath = self.dbc_addr_to_filepath(addr);
+        match read(filepath).await {
+            Ok(bytes) => {
+                let size = bytes.len() as u64;
+                let spends: Vec<SignedSpend> = rmp_serde::from_slice(&decode(bytes)?)
+                    .map_err(|err| Error::Serialisation(err.to_string()))?;
+                let cached = CachedValue::Spends(spends.clone());
+                self.cache
+                    .insert(RecordKind::Spend, name, cached, size, generation)
+                    .await;
+                Ok(spends)
+            }
+            Err(io_error @ io::Error { .. }) if io_error.kind() == ErrorKind::NotFound => {
+                Err(Error::SpendNotFound(*addr))
+            }
+            Err(other) => Err(other.into()),
+        }
+    }
+
+    // Writes to a temporary file first, for a crash not to lose the spends already held.
+    async fn write_spends(&self, addr: &DbcAddress, spends: &[SignedSpend]) -> Result<()> {
+        let filepath = self.dbc_addr_to_filepath(addr);
+        if let Some(dirs) = filepath.parent() {
+            create_dir_all(dirs).await?;
+        }
+
+        let value =
+            rmp_serde::to_vec(spends).map_err(|err| Error::Serialisation(err.to_string()))?;
+        let bytes = self.compression.encode(&value)?;
+        let tmp_path = filepath.with_extension("tmp");
+        let mut file = File::create(&tmp_path).await?;
+        file.write_all(&bytes).await?;
+        file.sync_data().await?;
+        rename(&tmp_path, filepath).await?;
+        self.cache.invalidate(RecordKind::Spend, *addr.name()).await;
+
+        Ok(())
+    }
+}
+
+impl Display for SpendStorage {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "SpendStorage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::types::spend::tests::{random_derived_key, signed_spend};
+
+    fn spend_storage() -> SpendStorage {
+        let dir = std::env::temp_dir().join(hex::encode(XorName::random(&mut rand::thread_rng())));
+        SpendStorage::new(&dir, CompressionConfig::default(), RecordCache::new(0))
+    }
+
+    #[async_std::test]
+    async fn a_spend_is_stored_once() -> Result<()> {
+        let storage = spend_storage();
+        let spend = signed_spend(&random_derived_key(), 100, 100);
+        let addr = DbcAddress::from_dbc_id(spend.dbc_id());
+
+        storage.try_add(&spend).await?;
+        storage.try_add(&spend).await?;
+
+        assert_eq!(storage.get(&addr).await?, spend);
+        assert_eq!(storage.get_all(addr.name()).await?, vec![spend]);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn double_spends_are_kept_as_proof() -> Result<()> {
+        let storage = spend_storage();
+        let derived_key = random_derived_key();
+        let spend = signed_spend(&derived_key, 100, 100);
+        let double_spend = signed_spend(&derived_key, 100, 100);
+        let addr = DbcAddress::from_dbc_id(spend.dbc_id());
+
+        storage.try_add(&spend).await?;
+        assert!(matches!(
+            storage.try_add(&double_spend).await,
+            Err(Error::Protocol(ProtocolError::DoubleSpendAttempt { new, existing }))
+                if *new == double_spend && *existing == spend
+        ));
+
+        assert!(matches!(
+            storage.get(&addr).await,
+            Err(Error::Protocol(ProtocolError::DoubleSpendAttempt { .. }))
+        ));
+        assert_eq!(
+            storage.get_all(addr.name()).await?,
+            vec![spend, double_spend]
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn at_most_two_conflicting_spends_are_kept() -> Result<()> {
+        let storage = spend_storage();
+        let derived_key = random_derived_key();
+        let spend = signed_spend(&derived_key, 100, 100);
+        let double_spend = signed_spend(&derived_key, 100, 100);
+        let triple_spend = signed_spend(&derived_key, 100, 100);
+        let addr = DbcAddress::from_dbc_id(spend.dbc_id());
+
+        storage.try_add(&spend).await?;
+        assert!(storage.try_add(&double_spend).await.is_err());
+        assert!(matches!(
+            storage.try_add(&triple_spend).await,
+            Err(Error::Protocol(ProtocolError::DoubleSpendAttempt { new, existing }))
+                if *new == triple_spend && *existing == spend
+        ));
+
+        assert_eq!(
+            storage.get_all(addr.name()).await?,
+            vec![spend, double_spend]
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn leftover_temporary_files_are_not_listed() -> Result<()> {
+        let storage = spend_storage();
+        let spend = signed_spend(&random_derived_key(), 100, 100);
+        let addr = DbcAddress::from_dbc_id(spend.dbc_id());
+        storage.try_add(&spend).await?;
+
+        // As left by a crash in the middle of a write.
+        let tmp_path = storage.dbc_addr_to_filepath(&addr).with_extension("tmp");
+        File::create(&tmp_path).await?.write_all(b"half").await?;
+
+        let records = storage.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].key, *addr.name());
+        assert_eq!(storage.get(&addr).await?, spend);
+        Ok(())
+    }
+}
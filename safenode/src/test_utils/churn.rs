@@ -0,0 +1,252 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::test_rng;
+use crate::{
+    client::{Client, Wallet},
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use async_std::task::sleep;
+use bytes::Bytes;
+use eyre::{eyre, Result};
+use rand::Rng;
+use sn_dbc::Token;
+use sn_testnet::Testnet;
+use std::{
+    env,
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// The amount paid for the storage of each chunk put during the churn.
+const CHUNK_PAYMENT_NANOS: u64 = 1;
+/// The time between two chunks put during the churn.
+const PUT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The knobs of a churn run.
+///
+/// [`ChurnConfig::from_env`] reads them from the `CHURN_RATE`, `CHURN_INTERVAL_SECS`,
+/// `CHURN_DOWNTIME_SECS` and `CHURN_DURATION_SECS` variables, for CI to run a short variant.
+#[derive(Clone, Debug)]
+pub struct ChurnConfig {
+    /// The percentage of the nodes, the genesis node aside, killed and restarted in each round.
+    pub churn_rate: usize,
+    /// The time between two rounds of churn.
+    pub churn_interval: Duration,
+    /// The time the nodes of a round are down for before being restarted.
+    pub downtime: Duration,
+    /// How long to churn the network for.
+    pub duration: Duration,
+}
+
+impl Default for ChurnConfig {
+    fn default() -> Self {
+        Self {
+            churn_rate: 10,
+            churn_interval: Duration::from_secs(30),
+            downtime: Duration::from_secs(10),
+            duration: Duration::from_secs(600),
+        }
+    }
+}
+
+impl ChurnConfig {
+    /// Returns the default config, overridden by the knobs set in the environment.
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+        if let Some(churn_rate) = env_var("CHURN_RATE")? {
+            if churn_rate > 100 {
+                return Err(eyre!("CHURN_RATE is a percentage, got {churn_rate}"));
+            }
+            config.churn_rate = churn_rate;
+        }
+        if let Some(secs) = env_var("CHURN_INTERVAL_SECS")? {
+            config.churn_interval = Duration::from_secs(secs);
+        }
+        if let Some(secs) = env_var("CHURN_DOWNTIME_SECS")? {
+            config.downtime = Duration::from_secs(secs);
+        }
+        if let Some(secs) = env_var("CHURN_DURATION_SECS")? {
+            config.duration = Duration::from_secs(secs);
+        }
+        Ok(config)
+    }
+}
+
+/// What happened to the data put during a churn run.
+#[derive(Clone, Debug, Default)]
+pub struct ChurnReport {
+    /// The number of nodes killed and restarted.
+    pub nodes_churned: usize,
+    /// The addresses of the chunks stored.
+    pub chunks_stored: Vec<ChunkAddress>,
+    /// The number of chunks that could not be paid for or stored.
+    pub failed_puts: usize,
+    /// The number of reads of stored chunks that failed while the network was churning.
+    pub failed_gets: usize,
+    /// The stored chunks that could no longer be retrieved once the churn was over.
+    pub lost: Vec<ChunkAddress>,
+}
+
+/// Kills and restarts a percentage of the nodes of the testnet in rounds, while the client
+/// puts chunks and reads back all of those stored so far, then checks that all the stored
+/// chunks can still be retrieved.
+///
+/// The chunks are paid for with the wallet, which must hold enough to pay for a chunk every
+/// second of the run.
+pub async fn run_churn(
+    testnet: &Testnet,
+    network_contacts_path: &Path,
+    node_args: &[String],
+    client: &Client,
+    wallet: &mut Wallet,
+    config: &ChurnConfig,
+) -> Result<ChurnReport> {
+    info!("Churning the network with {config:?}");
+    let deadline = Instant::now() + config.duration;
+    let (nodes_churned, puts) = futures::join!(
+        churn_nodes(testnet, network_contacts_path, node_args, config, deadline),
+        put_and_get(client, wallet, deadline),
+    );
+    let (stored, failed_puts, failed_gets) = puts?;
+    let nodes_churned = nodes_churned?;
+
+    // Leaves the last restarted nodes time to rejoin before the final check.
+    sleep(config.downtime).await;
+    let mut lost = vec![];
+    for chunk in &stored {
+        if !is_retrievable(client, chunk).await {
+            lost.push(*chunk.address());
+        }
+    }
+
+    let report = ChurnReport {
+        nodes_churned,
+        chunks_stored: stored.iter().map(|chunk| *chunk.address()).collect(),
+        failed_puts,
+        failed_gets,
+        lost,
+    };
+    info!("Churn over: {report:?}");
+    Ok(report)
+}
+
+// Kills and restarts rounds of nodes until the deadline, returning the number of nodes churned.
+async fn churn_nodes(
+    testnet: &Testnet,
+    network_contacts_path: &Path,
+    node_args: &[String],
+    config: &ChurnConfig,
+    deadline: Instant,
+) -> Result<usize> {
+    // The genesis node is spared, its address being the one the nodes bootstrap from.
+    let nodes: Vec<usize> = (2..=testnet.node_count).collect();
+    if nodes.is_empty() {
+        return Err(eyre!("The testnet has no nodes to churn"));
+    }
+    let per_round = (nodes.len() * config.churn_rate / 100).max(1);
+
+    let mut churned = 0;
+    let mut next = 0;
+    while Instant::now() + config.churn_interval < deadline {
+        sleep(config.churn_interval).await;
+
+        // Rotates through the nodes, for each of them to be churned in turn.
+        let round: Vec<usize> = (0..per_round)
+            .map(|i| nodes[(next + i) % nodes.len()])
+            .collect();
+        next = (next + per_round) % nodes.len();
+
+        info!("Killing nodes {round:?}");
+        for node in &round {
+            testnet.kill_node(*node)?;
+        }
+        sleep(config.downtime).await;
+        info!("Restarting nodes {round:?}");
+        for node in &round {
+            testnet.restart_node(*node, network_contacts_path, node_args.to_vec())?;
+        }
+        churned += round.len();
+    }
+    Ok(churned)
+}
+
+// Puts a chunk at a time until the deadline, reading back all the chunks stored so far after
+// each put. Returns the chunks stored, and the number of failed puts and gets.
+async fn put_and_get(
+    client: &Client,
+    wallet: &mut Wallet,
+    deadline: Instant,
+) -> Result<(Vec<Chunk>, usize, usize)> {
+    let mut stored = vec![];
+    let mut failed_puts = 0;
+    let mut failed_gets = 0;
+    let mut count = 0;
+    // The content of the chunks derives from the test seed, for a failing run to be
+    // re-run with the same chunks.
+    let mut rng = test_rng("churn chunks")?;
+    while Instant::now() < deadline {
+        count += 1;
+        let nonce: u64 = rng.gen();
+        let chunk = Chunk::new(Bytes::from(format!("churn test chunk {count} {nonce:x}")));
+        match put_chunk(client, wallet, &chunk).await {
+            Ok(()) => stored.push(chunk),
+            Err(err) => {
+                warn!("Failed to put chunk {:?}: {err}", chunk.address());
+                failed_puts += 1;
+            }
+        }
+
+        for chunk in &stored {
+            if !is_retrievable(client, chunk).await {
+                failed_gets += 1;
+            }
+        }
+        sleep(PUT_INTERVAL).await;
+    }
+    Ok((stored, failed_puts, failed_gets))
+}
+
+async fn put_chunk(client: &Client, wallet: &mut Wallet, chunk: &Chunk) -> Result<()> {
+    let payment = wallet
+        .pay_for_storage(chunk.name(), Token::from_nano(CHUNK_PAYMENT_NANOS))
+        .await?;
+    client.store_chunk(chunk.clone(), payment).await?;
+    Ok(())
+}
+
+// Whether the chunk can be read back with its original content.
+async fn is_retrievable(client: &Client, chunk: &Chunk) -> bool {
+    match client.get_chunk(*chunk.address()).await {
+        Ok(retrieved) if retrieved.value() == chunk.value() => true,
+        Ok(_) => {
+            warn!(
+                "Chunk {:?} was retrieved with other content",
+                chunk.address()
+            );
+            false
+        }
+        Err(err) => {
+            warn!("Failed to get chunk {:?}: {err}", chunk.address());
+            false
+        }
+    }
+}
+
+fn env_var<T: FromStr>(name: &str) -> Result<Option<T>> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| eyre!("Invalid value for {name}: {value}")),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(eyre!("Invalid value for {name}: {err}")),
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+mod churn;
+mod network;
+mod seed;
+
+pub use self::{
+    churn::{run_churn, ChurnConfig, ChurnReport},
+    network::{TestNetwork, TestNode},
+    seed::{test_keypair, test_rng, test_seed, TEST_SEED_ENV},
+};
+
+use crate::client::{Client, CONNECT_TIMEOUT};
+use eyre::Result;
+use libp2p::Multiaddr;
+
+/// Connects a client to the network through the peer at the given address, ending with
+/// its `/p2p/<peer id>`, waiting for the peer to be in the routing table of the client.
+pub async fn connect_client(peer_addr: Multiaddr) -> Result<Client> {
+    Ok(Client::connect(&[peer_addr], CONNECT_TIMEOUT).await?)
+}
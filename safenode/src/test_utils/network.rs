@@ -0,0 +1,250 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::test_keypair;
+use crate::{
+    client::Client,
+    network::{Network, NetworkEvent, NetworkSwarmLoop, SwarmConfig},
+    node::Node,
+    storage::{CompressionConfig, DataStorage},
+};
+use assert_fs::TempDir;
+use async_std::task::{sleep, spawn};
+use eyre::{eyre, Result};
+use futures::{channel::mpsc, StreamExt};
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// How long to wait for the nodes to listen and to fill their routing tables.
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A network of nodes running within the current process, connected over in-memory
+/// channels, for integration tests to run in seconds rather than spawning a testnet of
+/// processes.
+///
+/// Dropping it removes the storage of the nodes, their tasks running on until the end of
+/// the test process.
+pub struct TestNetwork {
+    nodes: Vec<TestNode>,
+    // The number of clients connected so far, each one's keypair deriving from its index.
+    clients: AtomicUsize,
+}
+
+/// A node of a [`TestNetwork`].
+pub struct TestNode {
+    network: Network,
+    node: Node,
+    addr: Multiaddr,
+    events: mpsc::UnboundedReceiver<NetworkEvent>,
+    // Removed on drop, once the node is gone.
+    _dir: TempDir,
+}
+
+impl TestNetwork {
+    /// Starts `n` nodes, each storing its records in a temp dir, and connects them all to
+    /// each other, returning once every node has all the others in its routing table.
+    ///
+    /// The keypairs of the nodes and clients derive from the test seed, for their peer ids,
+    /// and so the close groups of the data, to be the same when re-run with the same
+    /// `SAFE_TEST_SEED`.
+    pub async fn spawn(n: usize) -> Result<Self> {
+        if n == 0 {
+            return Err(eyre!("A test network needs at least one node"));
+        }
+        let mut nodes = Vec::with_capacity(n);
+        for index in 0..n {
+            nodes.push(TestNode::spawn(index).await?);
+        }
+
+        for i in 1..n {
+            let (dialed, dialers) = nodes.split_at_mut(i);
+            for peer in dialed.iter() {
+                dialers[0]
+                    .network
+                    .dial(peer.peer_id(), peer.addr.clone())
+                    .await?;
+            }
+        }
+
+        let started = Instant::now();
+        for node in &mut nodes {
+            while node.network.routing_table_size().await? < n - 1 {
+                if started.elapsed() > SPAWN_TIMEOUT {
+                    return Err(eyre!(
+                        "{:?} did not add the other nodes to its routing table",
+                        node.peer_id()
+                    ));
+                }
+                sleep(Duration::from_millis(50)).await;
+            }
+        }
+        Ok(Self {
+            nodes,
+            clients: AtomicUsize::new(0),
+        })
+    }
+
+    /// The nodes of the network, in the order they were started.
+    pub fn nodes(&self) -> &[TestNode] {
+        &self.nodes
+    }
+
+    /// The node at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of the bounds of the network.
+    pub fn node(&mut self, index: usize) -> &mut TestNode {
+        &mut self.nodes[index]
+    }
+
+    /// Connects a client to the network through its first node, over in-memory channels.
+    pub async fn client(&self) -> Result<Client> {
+        let index = self.clients.fetch_add(1, Ordering::Relaxed);
+        let (mut network, mut events, swarm_loop) = NetworkSwarmLoop::with_config(SwarmConfig {
+            client_mode: true,
+            memory_transport: true,
+            keypair: Some(test_keypair(&format!("client {index}"))?),
+            ..Default::default()
+        })?;
+        let _handle = spawn(swarm_loop.run());
+        // The client has no use for the events, they are only drained for the swarm to keep running.
+        let _handle = spawn(async move { while events.next().await.is_some() {} });
+
+        let bootstrap = &self.nodes[0];
+        network
+            .dial(bootstrap.peer_id(), bootstrap.addr.clone())
+            .await?;
+        let started = Instant::now();
+        while network.routing_table_size().await? == 0 {
+            if started.elapsed() > SPAWN_TIMEOUT {
+                return Err(eyre!(
+                    "{:?} was not added to the routing table",
+                    bootstrap.peer_id()
+                ));
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        Ok(Client::new(network))
+    }
+}
+
+impl TestNode {
+    async fn spawn(index: usize) -> Result<Self> {
+        let (mut network, mut events, swarm_loop) = NetworkSwarmLoop::with_config(SwarmConfig {
+            memory_transport: true,
+            keypair: Some(test_keypair(&format!("node {index}"))?),
+            ..Default::default()
+        })?;
+        let _handle = spawn(swarm_loop.run());
+        network
+            .set_record_validator(Node::record_validator())
+            .await?;
+
+        let dir = TempDir::new()?;
+        let storage = DataStorage::new(dir.path(), u64::MAX, CompressionConfig::default(), 0);
+        // Small networks are served from their first peer on.
+        let node = Node::new(network.clone(), storage).with_min_peers_to_serve(1);
+
+        // The requests are handled by the node, the other events being handed over to the test.
+        let (event_sender, event_receiver) = mpsc::unbounded();
+        let api = network.clone();
+        let handler = node.clone();
+        let _handle = spawn(async move {
+            while let Some(event) = events.next().await {
+                match event {
+                    NetworkEvent::RequestReceived {
+                        peer, req, channel, ..
+                    } => {
+                        let node = handler.clone();
+                        let mut api = api.clone();
+                        let _handle = spawn(async move {
+                            let resp = node.handle_request(peer, req).await;
+                            let _ = api.send_response(resp, channel).await;
+                        });
+                    }
+                    // Nobody listening is fine, the events are dropped.
+                    event => {
+                        let _ = event_sender.unbounded_send(event);
+                    }
+                }
+            }
+        });
+
+        let started = Instant::now();
+        let addr = loop {
+            if let Some(addr) = network.listen_addrs().await?.into_iter().next() {
+                break addr.with(Protocol::P2p(network.peer_id().into()));
+            }
+            if started.elapsed() > SPAWN_TIMEOUT {
+                return Err(eyre!("The node is not listening"));
+            }
+            sleep(Duration::from_millis(10)).await;
+        };
+
+        Ok(Self {
+            network,
+            node,
+            addr,
+            events: event_receiver,
+            _dir: dir,
+        })
+    }
+
+    /// The peer id of the node.
+    pub fn peer_id(&self) -> PeerId {
+        self.network.peer_id()
+    }
+
+    /// The address the node listens on, ending with its `/p2p/<peer id>`.
+    pub fn addr(&self) -> &Multiaddr {
+        &self.addr
+    }
+
+    /// The `Network` of the node, to inject cmds into its swarm.
+    pub fn network(&self) -> Network {
+        self.network.clone()
+    }
+
+    /// The node layer, e.g. to inspect what it stores.
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    /// Waits for the next event of the network layer of the node, the requests of peers
+    /// aside, which are handled by the node. `None` once the node is stopped.
+    pub async fn next_event(&mut self) -> Option<NetworkEvent> {
+        self.events.next().await
+    }
+
+    /// Waits for the next event the given predicate holds for, dropping the ones before it,
+    /// for at most the given duration.
+    pub async fn wait_for_event<F>(
+        &mut self,
+        timeout: Duration,
+        mut predicate: F,
+    ) -> Result<NetworkEvent>
+    where
+        F: FnMut(&NetworkEvent) -> bool,
+    {
+        let wait = async {
+            while let Some(event) = self.events.next().await {
+                if predicate(&event) {
+                    return Ok(event);
+                }
+            }
+            Err(eyre!("The node was stopped"))
+        };
+        async_std::future::timeout(timeout, wait)
+            .await
+            .map_err(|_| eyre!("No matching event within {timeout:?}"))?
+    }
+}
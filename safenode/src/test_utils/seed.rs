@@ -0,0 +1,62 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use eyre::{eyre, Result};
+use libp2p::identity::{self, ed25519};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{env, sync::Mutex};
+use tracing::info;
+use xor_name::XorName;
+
+/// The variable the seed of the randomness of the tests is read from, for a failing test to
+/// be re-run with the randomness it failed with.
+pub const TEST_SEED_ENV: &str = "SAFE_TEST_SEED";
+
+// The seed of the process, drawn or read once.
+static SEED: Mutex<Option<u64>> = Mutex::new(None);
+
+/// The seed the randomness of the tests derives from: the one set in `SAFE_TEST_SEED`, or a
+/// random one otherwise. It is the same for the whole test process, and printed on first use
+/// for the failing tests to tell it.
+pub fn test_seed() -> Result<u64> {
+    let mut seed = match SEED.lock() {
+        Ok(seed) => seed,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if let Some(seed) = *seed {
+        return Ok(seed);
+    }
+    let value = match env::var(TEST_SEED_ENV) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| eyre!("Invalid value for {TEST_SEED_ENV}: {value}"))?,
+        Err(env::VarError::NotPresent) => rand::random(),
+        Err(err) => return Err(eyre!("Invalid value for {TEST_SEED_ENV}: {err}")),
+    };
+    // Printed rather than logged, for the test harness to show it along with the failure.
+    println!("{TEST_SEED_ENV}={value}");
+    info!("Randomness of the tests seeded with {TEST_SEED_ENV}={value}");
+    *seed = Some(value);
+    Ok(value)
+}
+
+/// A generator seeded with the test seed, for the given stream. Different streams, e.g. one
+/// per node, draw different values, the same stream drawing the same ones for the same seed.
+pub fn test_rng(stream: &str) -> Result<StdRng> {
+    let seed = test_seed()?;
+    let name = XorName::from_content_parts(&[&seed.to_be_bytes(), stream.as_bytes()]);
+    Ok(StdRng::from_seed(name.0))
+}
+
+/// The keypair of the given stream, the same for the same seed.
+pub fn test_keypair(stream: &str) -> Result<identity::Keypair> {
+    let mut bytes: [u8; 32] = test_rng(stream)?.gen();
+    let secret = ed25519::SecretKey::try_from_bytes(&mut bytes)
+        .map_err(|err| eyre!("Failed to derive a keypair: {err}"))?;
+    Ok(ed25519::Keypair::from(secret).into())
+}
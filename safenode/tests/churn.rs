@@ -0,0 +1,151 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Runs a local testnet under churn, asserting the data put meanwhile remains retrievable.
+//!
+//! The wallet paying for the chunks put is credited with the genesis Dbc of the testnet, each
+//! run launching a testnet of its own.
+//!
+//! A short run, of a few nodes and a single round of churn, runs along the other tests, on the
+//! `safenode` built with them. The long one requires a release build of `safenode`, or the path
+//! of one in `SAFENODE_PATH`. Its number of nodes is read from `CHURN_NODE_COUNT`, and the knobs
+//! of the churn as documented on `ChurnConfig`. Being that long, it is ignored unless asked for,
+//! with `cargo test --release --features test-utils --test churn -- --ignored`.
+
+use assert_fs::TempDir;
+use async_std::task::sleep;
+use eyre::Result;
+use safenode::{
+    client::{create_genesis_dbc, create_transfer, Client, Wallet},
+    protocol::types::spend::genesis_main_key,
+    test_utils::{connect_client, run_churn, ChurnConfig, ChurnReport},
+};
+use sn_dbc::{Hash, Token};
+use sn_testnet::Testnet;
+use std::{env, fs, path::PathBuf, time::Duration};
+
+const DEFAULT_NODE_COUNT: usize = 25;
+/// The amount of the genesis Dbc, enough to pay for a chunk every second of the longest runs.
+const GENESIS_NANOS: u64 = 1_000_000_000;
+/// The number of times the spend of the genesis Dbc is submitted, the first requests to a
+/// testnet just launched possibly timing out.
+const GENESIS_SUBMIT_ATTEMPTS: usize = 5;
+/// The time between two submissions of the spend of the genesis Dbc.
+const GENESIS_SUBMIT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[async_std::test]
+async fn data_remains_retrievable_under_a_round_of_churn() -> Result<()> {
+    // Churns one of the three nodes other than the genesis one, once, the nodes taking data
+    // as soon as they know of the others, with the timeouts of a local network.
+    let config = ChurnConfig {
+        churn_rate: 34,
+        churn_interval: Duration::from_secs(20),
+        downtime: Duration::from_secs(3),
+        duration: Duration::from_secs(30),
+    };
+    let node_args = ["--min-peers-to-serve", "2", "--network-preset", "lan"].map(String::from);
+    let node_bin_path = PathBuf::from(env!("CARGO_BIN_EXE_safenode"));
+    let report = churn(node_bin_path, 4, &node_args, &config).await?;
+    assert_eq!(report.nodes_churned, 1);
+    assert_no_chunk_lost(&report);
+    Ok(())
+}
+
+#[async_std::test]
+#[ignore = "needs a release build of safenode, and runs for minutes"]
+async fn data_remains_retrievable_under_churn() -> Result<()> {
+    let config = ChurnConfig::from_env()?;
+    let node_count = match env::var("CHURN_NODE_COUNT") {
+        Ok(count) => count.parse()?,
+        Err(_) => DEFAULT_NODE_COUNT,
+    };
+    let node_bin_path = env::var("SAFENODE_PATH").map_or_else(
+        |_| PathBuf::from("../target/release/safenode"),
+        PathBuf::from,
+    );
+    let report = churn(node_bin_path, node_count, &[], &config).await?;
+    assert!(report.nodes_churned > 0, "No node was churned");
+    assert_no_chunk_lost(&report);
+    Ok(())
+}
+
+// Launches a testnet of the nodes, run with the args, and churns it with a wallet credited
+// with its genesis Dbc.
+async fn churn(
+    node_bin_path: PathBuf,
+    node_count: usize,
+    node_args: &[String],
+    config: &ChurnConfig,
+) -> Result<ChurnReport> {
+    let nodes_dir = TempDir::new()?;
+    let wallet_dir = TempDir::new()?;
+    let (mut testnet, network_contacts_path) = Testnet::configure()
+        .node_bin_path(node_bin_path)
+        .nodes_dir_path(nodes_dir.to_path_buf())
+        .build()?;
+    testnet.launch_genesis(None, node_args.to_vec())?;
+    testnet.wait_for_network_contacts(&network_contacts_path)?;
+    testnet.launch_nodes(node_count - 1, &network_contacts_path, node_args.to_vec())?;
+
+    let result = async {
+        // Leaves the last node launched the time the others were given to join.
+        sleep(Duration::from_millis(testnet.node_launch_interval)).await;
+        let contact = fs::read_to_string(&network_contacts_path)?.parse()?;
+        let client = connect_client(contact).await?;
+        let mut wallet = Wallet::load_from(client.clone(), &wallet_dir).await?;
+        credit_genesis(&client, &mut wallet).await?;
+        run_churn(
+            &testnet,
+            &network_contacts_path,
+            node_args,
+            &client,
+            &mut wallet,
+            config,
+        )
+        .await
+    }
+    .await;
+    testnet.teardown()?;
+    result
+}
+
+// Spends the genesis Dbc to the wallet. The same spend is submitted again on failure, any
+// other being a double spend of the genesis Dbc.
+async fn credit_genesis(client: &Client, wallet: &mut Wallet) -> Result<()> {
+    let amount = Token::from_nano(GENESIS_NANOS);
+    let genesis = create_genesis_dbc(amount)?;
+    let derived_key = genesis.derived_key(&genesis_main_key())?;
+    let transfer = create_transfer(
+        vec![(genesis, derived_key)],
+        vec![(amount, wallet.address())],
+        wallet.address(),
+        Hash::default(),
+    )?;
+    let mut attempt = 1;
+    while let Err(err) = client.submit_transfer(&transfer).await {
+        if attempt == GENESIS_SUBMIT_ATTEMPTS {
+            return Err(err.into());
+        }
+        println!("Failed to spend the genesis Dbc, attempt {attempt}: {err}");
+        attempt += 1;
+        sleep(GENESIS_SUBMIT_INTERVAL).await;
+    }
+    wallet.receive(transfer.created).await?;
+    Ok(())
+}
+
+fn assert_no_chunk_lost(report: &ChurnReport) {
+    assert!(!report.chunks_stored.is_empty(), "No chunk was stored");
+    assert!(
+        report.lost.is_empty(),
+        "{} of {} chunks were lost: {:?}",
+        report.lost.len(),
+        report.chunks_stored.len(),
+        report.lost
+    );
+}
@@ -0,0 +1,195 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Runs a network of nodes within the test process, over in-memory channels.
+
+use bytes::Bytes;
+use eyre::{eyre, Result};
+use safenode::{
+    client::{create_genesis_dbc, create_transfer, Error as ClientError},
+    network::{MsgDirection, NetworkEvent, TappedPayload},
+    protocol::{
+        error::Error as ProtocolError,
+        messages::{Cmd, CmdResponse, Query, QueryResponse, Replica, Request, Response},
+        types::{payment::PaymentProof, spend::genesis_main_key},
+    },
+    storage::chunks::Chunk,
+    test_utils::{test_keypair, TestNetwork},
+};
+use sn_dbc::{
+    rng, Dbc, DbcTransaction, Hash, InputHistory, MainKey, RevealedAmount, RevealedInput, Token,
+    TransactionBuilder,
+};
+use std::time::Duration;
+
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[async_std::test]
+async fn nodes_serve_each_other() -> Result<()> {
+    let mut testnet = TestNetwork::spawn(4).await?;
+    let holder = testnet.node(1).peer_id();
+    let mut network = testnet.node(0).network();
+
+    let chunk = Chunk::new(Bytes::from_static(b"in process test chunk"));
+    let cmd = Cmd::Replicate(Replica::Chunk(chunk.value().clone()));
+    match network
+        .send_request(Request::Cmd(network.sign_cmd(cmd)?), holder)
+        .await?
+        .response
+    {
+        Response::Cmd(CmdResponse::Ack { .. }) => {}
+        other => return Err(eyre!("Unexpected response to a put: {other:?}")),
+    }
+
+    let mut network = testnet.node(2).network();
+    let query = Request::Query(Query::GetChunk(*chunk.address()));
+    match network.send_request(query, holder).await?.response {
+        Response::Query(QueryResponse::GetChunk(Ok(retrieved))) => {
+            assert_eq!(retrieved.value(), chunk.value());
+        }
+        other => return Err(eyre!("Unexpected response to a get: {other:?}")),
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn events_are_observed() -> Result<()> {
+    let mut testnet = TestNetwork::spawn(2).await?;
+    let other_node = testnet.node(1).peer_id();
+    let _client = testnet.client().await?;
+
+    // The connection of the client, the one of the other node having been made on spawn.
+    let _event = testnet
+        .node(0)
+        .wait_for_event(EVENT_TIMEOUT, |event| {
+            matches!(event, NetworkEvent::PeerConnected { peer_id, .. } if *peer_id != other_node)
+        })
+        .await?;
+    Ok(())
+}
+
+#[async_std::test]
+async fn wire_traffic_is_tapped() -> Result<()> {
+    let mut testnet = TestNetwork::spawn(2).await?;
+    let requester = testnet.node(0).peer_id();
+    let mut tap = testnet.node(1).network().tap_msgs();
+    let holder = testnet.node(1).peer_id();
+    let mut network = testnet.node(0).network();
+
+    let chunk = Chunk::new(Bytes::from_static(b"tapped test chunk"));
+    let query = Request::Query(Query::GetChunk(*chunk.address()));
+    let _response = network.send_request(query.clone(), holder).await?;
+
+    // Other traffic may go on between the nodes meanwhile.
+    loop {
+        let msg = tap.recv().await?;
+        if let TappedPayload::Request(_, req) = &msg.payload {
+            if *req == query {
+                assert_eq!(msg.direction, MsgDirection::Inbound);
+                assert_eq!(msg.peer, Some(requester));
+                assert!(msg.size > 0);
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn payments_backed_by_unparented_dbcs_are_rejected() -> Result<()> {
+    let testnet = TestNetwork::spawn(4).await?;
+    let client = testnet.client().await?;
+    let main_key = MainKey::random();
+
+    // A Dbc created out of nothing, as the genesis Dbc is, but which is not the genesis Dbc.
+    let unparented = unparented_dbc(&main_key, 100)?;
+    let chunk = Chunk::new(Bytes::from_static(b"chunk paid with an unparented dbc"));
+    let derived_key = unparented.derived_key(&main_key)?;
+    let transfer = create_transfer(
+        vec![(unparented, derived_key)],
+        vec![(Token::from_nano(100), main_key.public_address())],
+        main_key.public_address(),
+        PaymentProof::reason_for(chunk.name()),
+    )?;
+    let (signed_spend, parent_tx) = transfer.spends[0].clone();
+
+    // The spentbook does not take the spend, so the proof of payment made of it is refused.
+    match client.spend_dbc(signed_spend.clone(), parent_tx).await {
+        Err(ClientError::Protocol(ProtocolError::InvalidSpend(..))) => {}
+        other => return Err(eyre!("Unexpected result of the spend: {other:?}")),
+    }
+    match client
+        .store_chunk(chunk.clone(), PaymentProof::new(vec![signed_spend]))
+        .await
+    {
+        Err(ClientError::PaymentRequired(ProtocolError::InvalidPaymentProof(..))) => {}
+        other => return Err(eyre!("Unexpected result of the store: {other:?}")),
+    }
+
+    // Paid with a Dbc descending from the genesis Dbc, the chunk is stored.
+    let genesis = create_genesis_dbc(Token::from_nano(100))?;
+    let derived_key = genesis.derived_key(&genesis_main_key())?;
+    let minted = create_transfer(
+        vec![(genesis, derived_key)],
+        vec![(Token::from_nano(100), main_key.public_address())],
+        main_key.public_address(),
+        Hash::default(),
+    )?;
+    client.submit_transfer(&minted).await?;
+    let dbc = minted.created[0].clone();
+    let derived_key = dbc.derived_key(&main_key)?;
+    let payment = create_transfer(
+        vec![(dbc, derived_key)],
+        vec![(Token::from_nano(100), main_key.public_address())],
+        main_key.public_address(),
+        PaymentProof::reason_for(chunk.name()),
+    )?;
+    client.submit_transfer(&payment).await?;
+    let spends = payment.spends.into_iter().map(|(spend, _)| spend).collect();
+    client.store_chunk(chunk, PaymentProof::new(spends)).await?;
+    Ok(())
+}
+
+#[test]
+fn keypairs_derive_from_the_test_seed() -> Result<()> {
+    let keypair = test_keypair("node 0")?;
+    assert_eq!(
+        keypair.public().to_peer_id(),
+        test_keypair("node 0")?.public().to_peer_id()
+    );
+    assert_ne!(
+        keypair.public().to_peer_id(),
+        test_keypair("node 1")?.public().to_peer_id()
+    );
+    Ok(())
+}
+
+// Creates a Dbc of the amount owned by the main key, out of an input created by no
+// transaction.
+fn unparented_dbc(main_key: &MainKey, amount: u64) -> Result<Dbc> {
+    let mut rng = rng::thread_rng();
+    let dbc_id_src = main_key.random_dbc_id_src(&mut rng);
+    let input = InputHistory {
+        input: RevealedInput::new(
+            MainKey::random().random_derived_key(&mut rng),
+            RevealedAmount::from_amount(amount, &mut rng),
+        ),
+        input_src_tx: DbcTransaction {
+            inputs: vec![],
+            outputs: vec![],
+        },
+    };
+    let (dbc, _) = TransactionBuilder::default()
+        .add_input(input)
+        .add_output(Token::from_nano(amount), dbc_id_src)
+        .build(Hash::default(), &mut rng)?
+        .build_without_verifying()?
+        .into_iter()
+        .next()
+        .ok_or_else(|| eyre!("No Dbc was created"))?;
+    Ok(dbc)
+}
@@ -0,0 +1,219 @@
+// Copyright 2023 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Bindings to the SAFE Network client for other languages, generated by UniFFI from
+//! `safe_client.udl`.
+//!
+//! The calls block until the operation on the network completes, the client being driven
+//! by the async-std executor in the background.
+
+// The scaffolding generated by UniFFI converts the errors thrown into themselves.
+#![allow(clippy::useless_conversion)]
+
+use async_std::task::block_on;
+use bytes::Bytes;
+use libp2p::Multiaddr;
+use safenode::{
+    client::{Client, EncryptedFile, Error as ClientError, Wallet, CONNECT_TIMEOUT},
+    protocol::types::payment::PaymentProof,
+    storage::chunks::{Chunk, ChunkAddress},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sn_dbc::{Dbc, PublicAddress, Token};
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use xor_name::XorName;
+
+uniffi::include_scaffolding!("safe_client");
+
+/// The amount paid for the storage of each chunk, in nanos, when the nodes storing it do not
+/// ask for a cost of their own.
+const CHUNK_PAYMENT_NANOS: u64 = 1;
+
+/// The errors surfaced to the other languages, as exceptions carrying the message.
+#[derive(Debug, Error)]
+pub enum SafeError {
+    /// An address, transfer or path given could not be parsed.
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    /// The data asked for is not on the network.
+    #[error("Not found: {0}")]
+    NotFound(String),
+    /// The network could not be reached, or failed the request.
+    #[error("Network error: {0}")]
+    Network(String),
+    /// The wallet could not pay or receive.
+    #[error("Wallet error: {0}")]
+    Wallet(String),
+    /// A local file could not be read or written.
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<ClientError> for SafeError {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::NotFound(_) | ClientError::PathNotFound(_) => {
+                Self::NotFound(err.to_string())
+            }
+            ClientError::Dbc(_) | ClientError::TransferFailed(_) | ClientError::InvalidDbc(..) => {
+                Self::Wallet(err.to_string())
+            }
+            ClientError::InvalidPeerAddress(_) => Self::InvalidInput(err.to_string()),
+            ClientError::Io(err) => Self::Io(err),
+            _ => Self::Network(err.to_string()),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, SafeError>;
+
+/// A client connected to the network.
+pub struct SafeClient {
+    client: Client,
+}
+
+impl SafeClient {
+    /// Connects to the network through the given peers, waiting for one of them, or a peer
+    /// found on the local network, to be in the routing table.
+    pub fn new(bootstrap_peers: Vec<String>) -> Result<Self> {
+        let peers = bootstrap_peers
+            .iter()
+            .map(|addr| {
+                addr.parse::<Multiaddr>()
+                    .map_err(|err| SafeError::InvalidInput(format!("{addr}: {err}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let client = block_on(Client::connect(&peers, CONNECT_TIMEOUT))?;
+        Ok(Self { client })
+    }
+
+    /// Pays for the chunk from the wallet and stores it, returning its hex encoded address.
+    pub fn put_chunk(&self, wallet: Arc<SafeWallet>, content: Vec<u8>) -> Result<String> {
+        block_on(async {
+            let chunk = Chunk::new(Bytes::from(content));
+            let address = *chunk.address();
+            let payment = wallet.pay_for(&self.client, chunk.name()).await?;
+            self.client.store_chunk(chunk, payment).await?;
+            Ok(hex::encode(address.name()))
+        })
+    }
+
+    /// Gets the content of the chunk at the hex encoded address.
+    pub fn get_chunk(&self, address: String) -> Result<Vec<u8>> {
+        let address = parse_address(&address)?;
+        let chunk = block_on(self.client.get_chunk(address))?;
+        Ok(chunk.value().to_vec())
+    }
+
+    /// Self-encrypts the file, pays for its chunks from the wallet and uploads them,
+    /// returning the hex encoded address the file is downloaded from.
+    pub fn upload_file(&self, wallet: Arc<SafeWallet>, path: String) -> Result<String> {
+        block_on(async {
+            let file = EncryptedFile::from_path(Path::new(&path)).await?;
+            let mut payments = BTreeMap::new();
+            for name in file.names() {
+                let payment = wallet.pay_for(&self.client, name).await?;
+                let _ = payments.insert(*name, payment);
+            }
+            let address = self.client.upload_file(file, &payments).await?;
+            Ok(hex::encode(address.name()))
+        })
+    }
+
+    /// Downloads the file at the hex encoded address to the given path.
+    pub fn download_file(&self, address: String, dest: String) -> Result<()> {
+        let address = parse_address(&address)?;
+        block_on(async {
+            let bytes = self.client.read_file(address).await?;
+            async_std::fs::write(dest, bytes).await?;
+            Ok(())
+        })
+    }
+}
+
+/// A wallet stored on the local disk, paying for the data stored through the client.
+pub struct SafeWallet {
+    wallet: Mutex<Wallet>,
+}
+
+impl SafeWallet {
+    /// Loads the wallet stored at the given dir, creating it if there is none.
+    pub fn new(client: Arc<SafeClient>, wallet_dir: String) -> Result<Self> {
+        let wallet = block_on(Wallet::load_from(
+            client.client.clone(),
+            Path::new(&wallet_dir),
+        ))?;
+        Ok(Self {
+            wallet: Mutex::new(wallet),
+        })
+    }
+
+    /// Returns the hex encoded address Dbcs are to be sent to for this wallet to receive them.
+    pub fn address(&self) -> String {
+        let address = block_on(self.wallet.lock()).address();
+        // A public key always serialises.
+        encode(&address).unwrap_or_default()
+    }
+
+    /// Returns the sum of the amounts held, in nanos.
+    pub fn balance_nanos(&self) -> Result<u64> {
+        let balance = block_on(self.wallet.lock()).balance()?;
+        Ok(balance.as_nano())
+    }
+
+    /// Sends the amount to the hex encoded address, returning the hex encoded transfer for
+    /// the recipient to receive.
+    pub fn send(&self, amount_nanos: u64, recipient: String) -> Result<String> {
+        let recipient: PublicAddress = decode(&recipient)?;
+        block_on(async {
+            let mut wallet = self.wallet.lock().await;
+            let dbc = wallet
+                .send(Token::from_nano(amount_nanos), recipient)
+                .await?;
+            encode(&dbc)
+        })
+    }
+
+    /// Receives the hex encoded transfer, as returned by [`SafeWallet::send`].
+    pub fn receive(&self, transfer: String) -> Result<()> {
+        let dbc: Dbc = decode(&transfer)?;
+        block_on(async { Ok(self.wallet.lock().await.receive(vec![dbc]).await?) })
+    }
+
+    // Pays for the storage of the data at `name` the costs asked by its close group.
+    async fn pay_for(&self, client: &Client, name: &XorName) -> Result<PaymentProof> {
+        let costs = client.get_store_costs(*name).await?;
+        let mut wallet = self.wallet.lock().await;
+        Ok(wallet
+            .pay_store_costs(name, &costs, Token::from_nano(CHUNK_PAYMENT_NANOS), None)
+            .await?)
+    }
+}
+
+fn parse_address(hex_address: &str) -> Result<ChunkAddress> {
+    let bytes = hex::decode(hex_address)
+        .map_err(|err| SafeError::InvalidInput(format!("Invalid address: {err}")))?;
+    let name: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| SafeError::InvalidInput("Invalid address: expected 32 bytes".to_string()))?;
+    Ok(ChunkAddress(XorName(name)))
+}
+
+// Addresses and transfers are handed around as hex encoded MessagePack, as by the `safe` CLI.
+fn encode<T: Serialize>(value: &T) -> Result<String> {
+    let bytes = rmp_serde::to_vec(value).map_err(|err| SafeError::InvalidInput(err.to_string()))?;
+    Ok(hex::encode(bytes))
+}
+
+fn decode<T: DeserializeOwned>(hex_value: &str) -> Result<T> {
+    let bytes = hex::decode(hex_value.trim())
+        .map_err(|err| SafeError::InvalidInput(format!("Invalid hex: {err}")))?;
+    rmp_serde::from_slice(&bytes).map_err(|err| SafeError::InvalidInput(err.to_string()))
+}
@@ -11,15 +11,21 @@ use mockall::automock;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use tracing::{debug, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 pub const DEFAULT_NODE_LAUNCH_INTERVAL: u64 = 1000;
+pub const DEFAULT_GENESIS_ADDR: &str = "127.0.0.1:12000";
 #[cfg(not(target_os = "windows"))]
 pub const SAFENODE_BIN_NAME: &str = "safenode";
 #[cfg(target_os = "windows")]
 pub const SAFENODE_BIN_NAME: &str = "safenode.exe";
 const GENESIS_NODE_DIR_NAME: &str = "safenode-1";
 const TESTNET_DIR_NAME: &str = "local-test-network";
+const NETWORK_CONTACTS_FILE_NAME: &str = "network_contacts";
+// The process ID file `safenode` leaves in its root dir.
+const PID_FILE_NAME: &str = "safenode.pid";
+const NETWORK_CONTACTS_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// This trait exists for unit testing.
 ///
@@ -128,7 +134,7 @@ impl TestnetBuilder {
         )?;
         let network_contacts_path = nodes_dir_path
             .join(GENESIS_NODE_DIR_NAME)
-            .join("section_tree");
+            .join(NETWORK_CONTACTS_FILE_NAME);
         Ok((testnet, network_contacts_path))
     }
 }
@@ -166,7 +172,7 @@ impl Testnet {
                         .ok_or_else(|| eyre!("Failed to obtain dir name"))?;
                     // This excludes any directories the user may have created under the network
                     // data directory path, either intentionally or unintentionally.
-                    if dir_name.starts_with("safenode-") && dir_name != GENESIS_NODE_DIR_NAME {
+                    if dir_name.starts_with("safenode-") {
                         node_count += 1;
                     }
                 }
@@ -193,7 +199,7 @@ impl Testnet {
     /// # Arguments
     ///
     /// * `address` - Optional address for where the genesis node will listen for connections. If
-    /// not specified, the 127.0.0.1:12000 local address will be used.
+    ///   not specified, the 127.0.0.1:12000 local address will be used.
     /// * `node_args` - Additional arguments to pass to the node process, e.g., --json-logs.
     ///
     /// # Errors
@@ -213,11 +219,15 @@ impl Testnet {
             ));
         }
 
-        let address = address.unwrap_or("127.0.0.1:12000".parse()?);
-        // info!("Launching genesis node using address {address}...");
-        let launch_args =
-            self.get_launch_args("safenode-1".to_string(), Some(address), None, node_args)?;
-        let node_data_dir_path = self.nodes_dir_path.join("safenode-1");
+        let address = address.unwrap_or(DEFAULT_GENESIS_ADDR.parse()?);
+        info!("Launching genesis node using address {address}...");
+        let launch_args = self.get_launch_args(
+            GENESIS_NODE_DIR_NAME.to_string(),
+            Some(address),
+            None,
+            node_args,
+        )?;
+        let node_data_dir_path = self.nodes_dir_path.join(GENESIS_NODE_DIR_NAME);
         std::fs::create_dir_all(node_data_dir_path)?;
 
         let launch_bin = self.get_launch_bin();
@@ -230,8 +240,42 @@ impl Testnet {
         Ok(())
     }
 
+    /// Waits for the genesis node to write its contact to the network contacts file, for the other
+    /// nodes to bootstrap from.
+    ///
+    /// The genesis node is counted from then on, for the nodes launched after it to be numbered
+    /// from 2.
+    ///
+    /// # Arguments
+    ///
+    /// * `network_contacts_path` - The path to the network contacts file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is not written in time.
+    pub fn wait_for_network_contacts(&mut self, network_contacts_path: &Path) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            match std::fs::read_to_string(network_contacts_path) {
+                Ok(contacts) if !contacts.trim().is_empty() => {
+                    info!("Network contacts written to {network_contacts_path:?}");
+                    self.node_count = 1;
+                    return Ok(());
+                }
+                _ if started.elapsed() > NETWORK_CONTACTS_TIMEOUT => {
+                    return Err(eyre!(
+                        "The genesis node did not write its contact to {network_contacts_path:?}"
+                    ));
+                }
+                _ => std::thread::sleep(Duration::from_millis(100)),
+            }
+        }
+    }
+
     /// Launches a number of new nodes, either for a new network or an existing network.
     ///
+    /// The nodes bootstrap from the addresses in the network contacts file.
+    ///
     /// # Arguments
     ///
     /// * `number_of_nodes` - The number of nodes to launch.
@@ -249,7 +293,7 @@ impl Testnet {
         network_contacts_path: &Path,
         node_args: Vec<String>,
     ) -> Result<()> {
-        let start = self.node_count + 2;
+        let start = self.node_count + 1;
         let end = self.node_count + number_of_nodes;
         for i in start..=end {
             info!("Launching node {i} of {end}...");
@@ -282,11 +326,77 @@ impl Testnet {
         Ok(())
     }
 
+    /// Kills the node processes of the network, using the process IDs the nodes leave in their
+    /// data directories.
+    ///
+    /// The data directories are kept, for the logs to be inspected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the nodes directory or a process ID file cannot be read.
+    pub fn teardown(&self) -> Result<()> {
+        if !self.nodes_dir_path.exists() {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(&self.nodes_dir_path)? {
+            kill_node_in(&entry?.path())?;
+        }
+        Ok(())
+    }
+
+    /// Kills the process of a single node, keeping its data directory for it to be restarted.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_number` - The number of the node, as in its `safenode-<number>` directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the process ID file of the node cannot be read.
+    pub fn kill_node(&self, node_number: usize) -> Result<()> {
+        kill_node_in(&self.nodes_dir_path.join(format!("safenode-{node_number}")))
+    }
+
+    /// Launches a node again, with the data directory it had before being killed.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_number` - The number of the node, as in its `safenode-<number>` directory.
+    /// * `network_contacts_path` - The path to the network contacts file.
+    /// * `node_args` - Additional arguments to pass to the node process, e.g., --json-logs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// * The node is the genesis node, whose address the other nodes bootstrap from
+    /// * The node has never been launched
+    /// * The node process fails
+    pub fn restart_node(
+        &self,
+        node_number: usize,
+        network_contacts_path: &Path,
+        node_args: Vec<String>,
+    ) -> Result<()> {
+        if node_number < 2 || node_number > self.node_count {
+            return Err(eyre!("Node {node_number} cannot be restarted"));
+        }
+        info!("Restarting node {node_number}...");
+        let launch_args = self.get_launch_args(
+            format!("safenode-{node_number}"),
+            None,
+            Some(network_contacts_path),
+            node_args,
+        )?;
+        let launch_bin = self.get_launch_bin();
+        self.launcher.launch(&launch_bin, launch_args)?;
+        Ok(())
+    }
+
     fn get_launch_args(
         &self,
         node_name: String,
-        _address: Option<SocketAddr>,
-        _network_contacts_path: Option<&Path>,
+        address: Option<SocketAddr>,
+        network_contacts_path: Option<&Path>,
         node_args: Vec<String>,
     ) -> Result<Vec<String>> {
         let node_data_dir_path = self.nodes_dir_path.join(node_name.clone());
@@ -311,6 +421,23 @@ impl Testnet {
             .to_str()
             .ok_or_else(|| eyre!("Unable to obtain node data directory path"))?
             .to_string();
+        if let Some(address) = address {
+            launch_args.push("--first".to_string());
+            launch_args.push(address.to_string());
+            launch_args.push("--local-addr".to_string());
+            launch_args.push(format!("0.0.0.0:{}", address.port()));
+        }
+        if let Some(network_contacts_path) = network_contacts_path {
+            launch_args.push("--network-contacts-file".to_string());
+            launch_args.push(
+                network_contacts_path
+                    .to_str()
+                    .ok_or_else(|| eyre!("Unable to obtain network contacts path"))?
+                    .to_string(),
+            );
+        }
+        launch_args.push("--root-dir".to_string());
+        launch_args.push(node_data_dir_path.clone());
         launch_args.push("--log-dir".to_string());
         launch_args.push(node_data_dir_path);
         launch_args.extend(node_args);
@@ -327,6 +454,57 @@ impl Testnet {
     }
 }
 
+// Kills the node whose data directory is given, if it left its process ID there.
+fn kill_node_in(node_data_dir_path: &Path) -> Result<()> {
+    let pid_path = node_data_dir_path.join(PID_FILE_NAME);
+    if !pid_path.exists() {
+        return Ok(());
+    }
+    let pid = std::fs::read_to_string(&pid_path)?;
+    match parse_pid(&pid) {
+        Ok(pid) => {
+            info!("Killing node process {pid}");
+            if let Err(err) = kill_process(pid) {
+                warn!("Failed to kill node process {pid}: {err}");
+            }
+        }
+        Err(err) => warn!("Not killing the node of {pid_path:?}: {err}"),
+    }
+    std::fs::remove_file(pid_path)?;
+    Ok(())
+}
+
+// Parses the process ID left by a node. Only IDs a single process can have are taken,
+// `kill` reading values out of the range of `pid_t` as negative IDs, which signal groups
+// of processes, or every process of the user for -1.
+fn parse_pid(pid: &str) -> Result<u32> {
+    let pid = pid.trim();
+    match pid.parse::<u32>() {
+        Ok(parsed) if parsed > 0 && parsed <= i32::MAX as u32 => Ok(parsed),
+        _ => Err(eyre!("{pid:?} is not a valid process ID")),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn kill_process(pid: u32) -> Result<()> {
+    let status = Command::new("kill").arg(pid.to_string()).status()?;
+    if !status.success() {
+        return Err(eyre!("kill exited with {status}"));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn kill_process(pid: u32) -> Result<()> {
+    let status = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/F"])
+        .status()?;
+    if !status.success() {
+        return Err(eyre!("taskkill exited with {status}"));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -335,6 +513,8 @@ mod test {
     use mockall::predicate::*;
 
     const NODE_LAUNCH_INTERVAL: u64 = 0;
+    const NETWORK_CONTACT: &str =
+        "/ip4/127.0.0.1/udp/12000/quic-v1/p2p/12D3KooWRBhwfeP2Y9CDkFRBAZ1pmxUadH36TKuk3KtKm5XXP8mA";
 
     #[test]
     fn new_should_create_a_testnet_with_zero_nodes_when_no_previous_network_exists() -> Result<()> {
@@ -690,7 +870,7 @@ mod test {
         let mut node_launcher = MockNodeLauncher::new();
         for i in 1..=20 {
             let node_data_dir = nodes_dir
-                .join(&format!("safenode-{i}"))
+                .join(format!("safenode-{i}"))
                 .to_str()
                 .ok_or_else(|| eyre!("Unable to obtain path"))?
                 .to_string();
@@ -809,7 +989,7 @@ mod test {
 
         let mut node_launcher = MockNodeLauncher::new();
         for i in 1..=20 {
-            let node_data_dir = nodes_dir.join(&format!("safenode-{i}"));
+            let node_data_dir = nodes_dir.join(format!("safenode-{i}"));
             let graph_output_file_path = node_data_dir
                 .join(format!("safenode-{i}-flame.svg"))
                 .to_str()
@@ -874,7 +1054,7 @@ mod test {
         let mut node_launcher = MockNodeLauncher::new();
         for i in 1..=30 {
             let node_data_dir = nodes_dir
-                .join(&format!("safenode-{i}"))
+                .join(format!("safenode-{i}"))
                 .to_str()
                 .ok_or_else(|| eyre!("Unable to obtain path"))?
                 .to_string();
@@ -924,4 +1104,137 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn wait_for_network_contacts_should_return_once_the_genesis_contact_is_written() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let nodes_dir = tmp_data_dir.child(TESTNET_DIR_NAME);
+        let network_contacts_file = nodes_dir
+            .child(GENESIS_NODE_DIR_NAME)
+            .child(NETWORK_CONTACTS_FILE_NAME);
+        network_contacts_file.write_str(NETWORK_CONTACT)?;
+
+        let node_launcher = MockNodeLauncher::new();
+        let mut testnet = Testnet::new(
+            PathBuf::from(SAFENODE_BIN_NAME),
+            NODE_LAUNCH_INTERVAL,
+            nodes_dir.path().to_path_buf(),
+            false,
+            Box::new(node_launcher),
+        )?;
+        let result = testnet.wait_for_network_contacts(network_contacts_file.path());
+
+        assert!(result.is_ok());
+        assert_eq!(testnet.node_count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn restart_node_should_launch_the_node_with_its_existing_data_directory() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let node_bin_path = tmp_data_dir.child(SAFENODE_BIN_NAME);
+        node_bin_path.write_binary(b"fake safenode code")?;
+        let nodes_dir = tmp_data_dir.child(TESTNET_DIR_NAME);
+        for i in 1..=5 {
+            nodes_dir.child(format!("safenode-{i}")).create_dir_all()?;
+        }
+        let network_contacts_file = tmp_data_dir.child("network-contacts");
+        network_contacts_file.write_str(NETWORK_CONTACT)?;
+        let node_data_dir = nodes_dir
+            .join("safenode-3")
+            .to_str()
+            .ok_or_else(|| eyre!("Unable to obtain path"))?
+            .to_string();
+
+        let mut node_launcher = MockNodeLauncher::new();
+        node_launcher
+            .expect_launch()
+            .times(1)
+            .with(
+                eq(node_bin_path.path().to_path_buf()),
+                eq(vec![
+                    "--network-contacts-file".to_string(),
+                    network_contacts_file.path().to_str().unwrap().to_string(),
+                    "--root-dir".to_string(),
+                    node_data_dir.clone(),
+                    "--log-dir".to_string(),
+                    node_data_dir,
+                    "--json-logs".to_string(),
+                ]),
+            )
+            .returning(|_, _| Ok(()));
+        let testnet = Testnet::new(
+            node_bin_path.path().to_path_buf(),
+            NODE_LAUNCH_INTERVAL,
+            nodes_dir.path().to_path_buf(),
+            false,
+            Box::new(node_launcher),
+        )?;
+        let result = testnet.restart_node(
+            3,
+            network_contacts_file.path(),
+            vec!["--json-logs".to_string()],
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(testnet.node_count, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn restart_node_should_return_error_for_the_genesis_node() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let nodes_dir = tmp_data_dir.child(TESTNET_DIR_NAME);
+        for i in 1..=5 {
+            nodes_dir.child(format!("safenode-{i}")).create_dir_all()?;
+        }
+        let network_contacts_file = tmp_data_dir.child("network-contacts");
+        network_contacts_file.write_str(NETWORK_CONTACT)?;
+
+        let mut node_launcher = MockNodeLauncher::new();
+        node_launcher.expect_launch().times(0);
+        let testnet = Testnet::new(
+            PathBuf::from(SAFENODE_BIN_NAME),
+            NODE_LAUNCH_INTERVAL,
+            nodes_dir.path().to_path_buf(),
+            false,
+            Box::new(node_launcher),
+        )?;
+        let result = testnet.restart_node(1, network_contacts_file.path(), vec![]);
+
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn kill_node_should_remove_the_process_id_file_of_the_node() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let nodes_dir = tmp_data_dir.child(TESTNET_DIR_NAME);
+        let pid_file = nodes_dir.child("safenode-2").child(PID_FILE_NAME);
+        // Out of the range of process IDs, so that it is not handed to `kill`.
+        pid_file.write_str("4294967295")?;
+
+        let node_launcher = MockNodeLauncher::new();
+        let testnet = Testnet::new(
+            PathBuf::from(SAFENODE_BIN_NAME),
+            NODE_LAUNCH_INTERVAL,
+            nodes_dir.path().to_path_buf(),
+            false,
+            Box::new(node_launcher),
+        )?;
+        let result = testnet.kill_node(2);
+
+        assert!(result.is_ok());
+        pid_file.assert(predicates::path::missing());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_pid_should_only_take_the_ids_of_single_processes() {
+        assert_eq!(parse_pid("4194305\n").ok(), Some(4194305));
+        assert_eq!(parse_pid("2147483647").ok(), Some(i32::MAX as u32));
+        for pid in ["", "0", "-1", "2147483648", "4294967295", "1 2", "pid"] {
+            assert!(parse_pid(pid).is_err(), "{pid:?} was taken");
+        }
+    }
 }
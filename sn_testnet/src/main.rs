@@ -51,6 +51,10 @@ struct Cmd {
     #[clap(long = "join", short = 'j', value_parser)]
     join_network: bool,
 
+    /// Kill the node processes of the running testnet, keeping their data and logs.
+    #[clap(long, short = 't')]
+    teardown: bool,
+
     /// Optional location for a network contacts file.
     ///
     /// This should only be used in conjunction with the 'join' command. You can supply it if you
@@ -58,11 +62,11 @@ struct Cmd {
     /// machine in your local network and have them join an existing testnet on the same network.
     ///
     /// If the value is not supplied, we will use the default location for the contacts file, which
-    /// will be at node-data-dir/local-test-network/sn-genesis-node/section_tree.
+    /// will be at node-data-dir/local-test-network/safenode-1/network_contacts.
     #[clap(long = "network-contacts-path", short = 'n', value_name = "FILE_PATH")]
     network_contacts_path: Option<PathBuf>,
 
-    /// Interval between node launches in ms. Defaults to 5000.
+    /// Interval between node launches in ms. Defaults to 1000.
     #[clap(long = "interval", short = 'i')]
     node_launch_interval: Option<u64>,
 
@@ -88,7 +92,7 @@ struct Cmd {
     #[clap(short = 'p', long, value_name = "FILE_PATH")]
     node_path: Option<PathBuf>,
 
-    /// The number of nodes for the testnet. Defaults to 30.
+    /// The number of nodes for the testnet, including the genesis node. Defaults to 25.
     ///
     /// If you use the 'join' command, you must supply this value.
     #[clap(short = 'c', long, env = "NODE_COUNT")]
@@ -115,6 +119,12 @@ async fn main() -> Result<()> {
         return Err(eyre!("Flamegraph cannot be used on Windows"));
     }
 
+    if args.teardown {
+        let (testnet, _) = Testnet::configure().build()?;
+        testnet.teardown()?;
+        return Ok(());
+    }
+
     let mut node_bin_path = PathBuf::new();
     if let Some(node_path) = args.node_path {
         node_bin_path.push(node_path);
@@ -199,7 +209,7 @@ async fn build_node() -> Result<()> {
         args.extend(["--features", "otlp"]);
     }
     if cfg!(feature = "verify-nodes") {
-        args.extend(["--features", "rpc-service"]);
+        args.extend(["--features", "rpc"]);
     }
 
     info!("Building safenode");
@@ -228,7 +238,12 @@ async fn run_network(
         .flamegraph_mode(flamegraph_mode)
         .build()?;
     testnet.launch_genesis(None, node_args.clone())?;
-    testnet.launch_nodes(node_count as usize, &network_contacts_path, node_args)?;
+    testnet.wait_for_network_contacts(&network_contacts_path)?;
+    testnet.launch_nodes(
+        node_count.saturating_sub(1) as usize,
+        &network_contacts_path,
+        node_args,
+    )?;
 
     // Perform a verification on the nodes launched (if requested) as a last step
     #[cfg(feature = "verify-nodes")]